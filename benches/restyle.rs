@@ -0,0 +1,66 @@
+// Compares a full restyle against incremental restyles on a large synthetic document.
+// Run with `cargo bench --bench restyle`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use oliver_explorer::state::Page;
+use url::Url;
+
+const SECTIONS: usize = 1000;
+
+fn synthetic_page() -> Page {
+    let mut html = String::from(
+        "<html><head><style>\
+         :root { --accent: #3366ff }\
+         section { padding: 8px; margin: 4px 0 }\
+         section.open > p { color: var(--accent) }\
+         section:hover { background-color: #eee }\
+         p + p { margin-top: 0 }\
+         a:hover { text-decoration: underline }\
+         .note em, ul li:first-child { font-weight: bold }\
+         </style></head><body>",
+    );
+    for i in 0..SECTIONS {
+        html.push_str(&format!(
+            "<section id=s{i}><h2>Section {i}</h2>\
+             <p>First <em>paragraph</em> of section {i}</p>\
+             <p class=note>Second paragraph with a <a href=/{i}>link</a></p>\
+             <ul><li>One</li><li>Two</li><li>Three</li></ul></section>"
+        ));
+    }
+    html.push_str("</body></html>");
+    Page::new(
+        &html,
+        &Url::parse("about:blank").unwrap(),
+        Default::default(),
+    )
+}
+
+fn restyle(c: &mut Criterion) {
+    let mut page = synthetic_page();
+    // html > body > the middle section
+    let section = [0, 1, SECTIONS / 2];
+
+    c.bench_function("full restyle", |b| b.iter(|| page.restyle()));
+
+    c.bench_function("class change", |b| {
+        let mut open = false;
+        b.iter(|| {
+            open = !open;
+            page.set_attribute(&section, "class", open.then_some("open"));
+            page.restyle_dirty()
+        })
+    });
+
+    c.bench_function("hover change", |b| {
+        let link = [0, 1, SECTIONS / 2, 2, 1];
+        let mut hovered = false;
+        b.iter(|| {
+            hovered = !hovered;
+            page.set_hovered(hovered.then_some(&link[..]));
+            page.restyle_dirty()
+        })
+    });
+}
+
+criterion_group!(benches, restyle);
+criterion_main!(benches);
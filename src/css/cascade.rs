@@ -0,0 +1,1243 @@
+// Selector matching, the cascade and computed values for a document
+
+use crate::css::font_face::FontFaceRule;
+use crate::css::hints;
+use crate::css::invalidation::{Damage, InvalidationMap};
+use crate::css::media::{ColorScheme, MediaQueryList, MediaType};
+use crate::css::page::{PageRule, PageStyle};
+use crate::css::parser::{self, Declaration, Rule, Stylesheet};
+use crate::css::properties;
+use crate::css::selector::{ElementRef, PseudoElement, Selector, Specificity};
+use crate::css::tokenizer::Token;
+use crate::css::values::{self, Color, LengthContext, Value};
+use crate::css::variables::{self, CustomProperties};
+use crate::dom::{Document, Element, ElementState, ElementType, Node};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use url::Url;
+
+pub const DEFAULT_FONT_SIZE: f32 = 16.0;
+
+// The canvas and default text colors of pages shown in their dark color scheme
+pub const DARK_CANVAS: Color = Color::rgb(0x12, 0x12, 0x12);
+const DARK_CANVAS_TEXT: Color = Color::WHITE;
+
+const USER_AGENT_CSS: &str = include_str!("user_agent.css");
+
+/// Where a stylesheet came from, which decides its precedence in the cascade
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Origin {
+    UserAgent,
+    Author,
+}
+
+/// The environment styles are computed for, which media queries are evaluated against
+#[derive(Debug, Clone, PartialEq)]
+pub struct Device {
+    pub media_type: MediaType,
+    pub viewport_width: f32,
+    pub viewport_height: f32,
+    pub color_scheme: ColorScheme,
+    pub reduced_motion: bool,
+    // Device pixels per CSS pixel, which picks between an image's sources
+    pub pixel_ratio: f32,
+}
+
+impl Default for Device {
+    fn default() -> Self {
+        Self {
+            media_type: MediaType::Screen,
+            viewport_width: 800.0,
+            viewport_height: 600.0,
+            color_scheme: ColorScheme::Light,
+            reduced_motion: false,
+            pixel_ratio: 1.0,
+        }
+    }
+}
+
+/// The computed style of an element
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComputedStyle {
+    values: HashMap<String, Value>,
+    pub custom_properties: Arc<CustomProperties>,
+    font_size: f32,
+}
+
+impl Default for ComputedStyle {
+    fn default() -> Self {
+        Self {
+            values: HashMap::new(),
+            custom_properties: Arc::new(CustomProperties::new()),
+            font_size: DEFAULT_FONT_SIZE,
+        }
+    }
+}
+
+impl ComputedStyle {
+    // Creates the style a child inherits from this one before its own declarations apply
+    pub fn inherit(&self) -> Self {
+        Self {
+            values: self
+                .values
+                .iter()
+                .filter(|(name, _)| properties::is_inherited(name))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            custom_properties: Arc::clone(&self.custom_properties),
+            font_size: self.font_size,
+        }
+    }
+
+    // Returns the computed value of a property, falling back to its initial value
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.values.get(name).or_else(|| initial_values().get(name))
+    }
+
+    pub fn set(&mut self, name: &str, value: Value) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    // Returns a property to its initial value
+    pub fn reset(&mut self, name: &str) {
+        self.values.remove(name);
+    }
+
+    pub fn keyword(&self, name: &str) -> Option<&str> {
+        self.get(name).and_then(Value::as_keyword)
+    }
+
+    pub fn font_size(&self) -> f32 {
+        self.font_size
+    }
+
+    // The font-family list, with unquoted multi-word names joined by spaces
+    pub fn font_families(&self) -> Vec<String> {
+        let Some(value) = self.get("font-family") else {
+            return Vec::new();
+        };
+        let families = match value {
+            Value::CommaList(items) => items.as_slice(),
+            value => std::slice::from_ref(value),
+        };
+        families
+            .iter()
+            .filter_map(|family| match family {
+                Value::Str(name) | Value::Keyword(name) => Some(name.clone()),
+                Value::List(words) => words
+                    .iter()
+                    .map(Value::as_keyword)
+                    .collect::<Option<Vec<_>>>()
+                    .map(|words| words.join(" ")),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn font_weight(&self) -> u16 {
+        match self.get("font-weight") {
+            Some(Value::Number(weight)) => *weight as u16,
+            Some(value) if value.is_keyword("bold") => 700,
+            _ => 400,
+        }
+    }
+
+    pub fn is_italic(&self) -> bool {
+        matches!(self.keyword("font-style"), Some("italic" | "oblique"))
+    }
+
+    // Whether `color-scheme` lets the element be shown dark
+    pub fn supports_dark_scheme(&self) -> bool {
+        self.get("color-scheme")
+            .is_some_and(|value| value.items().iter().any(|item| item.is_keyword("dark")))
+    }
+
+    pub fn display(&self) -> &str {
+        self.keyword("display").unwrap_or("inline")
+    }
+
+    // Resolves a color property, with `currentcolor` referring to the `color` property
+    pub fn color(&self, name: &str) -> Color {
+        let current_color = self
+            .get("color")
+            .and_then(|value| value.to_color(Color::BLACK))
+            .unwrap_or(Color::BLACK);
+        if name == "color" {
+            return current_color;
+        }
+        self.get(name)
+            .and_then(|value| value.to_color(current_color))
+            .unwrap_or(Color::TRANSPARENT)
+    }
+
+    // Resolves a length property to pixels. Returns None for `auto`, `none` and the like.
+    pub fn length(&self, name: &str, basis: f32) -> Option<f32> {
+        self.get(name)?.to_px(basis)
+    }
+
+    // Returns the serialized value of a custom property
+    pub fn custom_property(&self, name: &str) -> Option<String> {
+        self.custom_properties
+            .get(name)
+            .map(|tokens| crate::css::tokenizer::serialize(tokens))
+    }
+}
+
+fn initial_values() -> &'static HashMap<&'static str, Value> {
+    static INITIAL_VALUES: OnceLock<HashMap<&'static str, Value>> = OnceLock::new();
+    INITIAL_VALUES.get_or_init(|| {
+        let context = LengthContext {
+            font_size: DEFAULT_FONT_SIZE,
+            root_font_size: DEFAULT_FONT_SIZE,
+            viewport_width: 0.0,
+            viewport_height: 0.0,
+        };
+        properties::PROPERTIES
+            .iter()
+            .filter_map(|property| {
+                let tokens = crate::css::tokenizer::tokenize(property.initial);
+                Some((property.name, values::parse_value(&tokens, &context)?))
+            })
+            .collect()
+    })
+}
+
+/// A computed style for every node of a document, mirroring the shape of the DOM.
+/// Text and comment nodes share the style of their parent element.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StyledNode {
+    pub style: Arc<ComputedStyle>,
+    pub children: Vec<StyledNode>,
+    // Styles of the element's ::marker, ::before and ::after, for those that generate a box
+    pub pseudo_elements: Vec<(PseudoElement, Arc<ComputedStyle>)>,
+}
+
+impl StyledNode {
+    pub fn pseudo_element(&self, pseudo_element: PseudoElement) -> Option<&Arc<ComputedStyle>> {
+        self.pseudo_elements
+            .iter()
+            .find(|(which, _)| *which == pseudo_element)
+            .map(|(_, style)| style)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StyleTree {
+    pub nodes: Vec<StyledNode>,
+}
+
+impl StyleTree {
+    // The style of the node at a path of child indexes
+    pub fn style_at(&self, path: &[usize]) -> Option<&Arc<ComputedStyle>> {
+        let (first, rest) = path.split_first()?;
+        let mut node = self.nodes.get(*first)?;
+        for index in rest {
+            node = node.children.get(*index)?;
+        }
+        Some(&node.style)
+    }
+}
+
+// A style rule flattened for matching, one entry per selector
+#[derive(Debug)]
+struct IndexedRule {
+    selector: Selector,
+    specificity: Specificity,
+    declarations: Arc<Vec<Declaration>>,
+    origin: Origin,
+}
+
+/// Holds the stylesheets that apply to a document and computes styles from them
+#[derive(Debug, Default)]
+pub struct Stylist {
+    sheets: Vec<(Stylesheet, Origin)>,
+    device: Device,
+    // Rules from the sheets and @media blocks that match the current device
+    rules: Vec<IndexedRule>,
+    // The result of every media query list evaluated when the rules were collected
+    media_results: Vec<bool>,
+    // True if any declaration uses vw, vh, vmin or vmax units
+    viewport_dependent: bool,
+    // What the collected rules depend on, for restyling only what a change affects
+    invalidation: InvalidationMap,
+}
+
+impl Stylist {
+    pub fn new(device: Device) -> Self {
+        Self {
+            device,
+            ..Default::default()
+        }
+    }
+
+    // Creates a stylist with the user agent stylesheet and the document's `<style>` sheets
+    pub fn for_document(document: &Document, device: Device) -> Self {
+        let mut stylist = Self::new(device);
+        stylist.add_stylesheet(user_agent_stylesheet(), Origin::UserAgent);
+        for sheet in author_stylesheets(document) {
+            stylist.add_stylesheet(sheet, Origin::Author);
+        }
+        stylist
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    // Whether a document whose root element has this style is shown in its dark color
+    // scheme, which the device must prefer and the root's `color-scheme` allow
+    pub fn uses_dark_scheme(&self, root: &ComputedStyle) -> bool {
+        self.device.color_scheme == ColorScheme::Dark && root.supports_dark_scheme()
+    }
+
+    pub fn add_stylesheet(&mut self, sheet: Stylesheet, origin: Origin) {
+        self.viewport_dependent |= uses_viewport_units(&sheet.rules);
+        self.sheets.push((sheet, origin));
+        self.collect_rules();
+    }
+
+    // Updates the device and re-evaluates media queries. Returns true if styles need to
+    // be recomputed, either because different rules apply now or because the viewport
+    // size changed and the stylesheets use viewport units.
+    pub fn set_device(&mut self, device: Device) -> bool {
+        if device == self.device {
+            return false;
+        }
+        let resized = device.viewport_width != self.device.viewport_width
+            || device.viewport_height != self.device.viewport_height;
+        self.device = device;
+        let previous_results = std::mem::take(&mut self.media_results);
+        self.collect_rules();
+        self.media_results != previous_results || (resized && self.viewport_dependent)
+    }
+
+    // Rebuilds the list of rules that apply to the current device
+    fn collect_rules(&mut self) {
+        let mut rules = Vec::new();
+        let mut media_results = Vec::new();
+        for (sheet, origin) in &self.sheets {
+            let matches = sheet.media.matches(&self.device);
+            media_results.push(matches);
+            if matches {
+                self.collect_from(&sheet.rules, *origin, &mut rules, &mut media_results);
+            }
+        }
+        let mut invalidation = InvalidationMap::default();
+        for rule in &rules {
+            invalidation.add_selector(&rule.selector);
+        }
+        self.rules = rules;
+        self.media_results = media_results;
+        self.invalidation = invalidation;
+    }
+
+    // Returns the @font-face rules that apply to the current device in document order,
+    // with the URL of the sheet each one came from
+    pub fn font_faces(&self) -> Vec<(&FontFaceRule, Option<&Url>)> {
+        fn collect<'a>(
+            rules: &'a [Rule],
+            url: Option<&'a Url>,
+            device: &Device,
+            faces: &mut Vec<(&'a FontFaceRule, Option<&'a Url>)>,
+        ) {
+            for rule in rules {
+                match rule {
+                    Rule::FontFace(face) => faces.push((face, url)),
+                    Rule::Media(media_rule) if media_rule.queries.matches(device) => {
+                        collect(&media_rule.rules, url, device, faces)
+                    }
+                    Rule::Import(import) if import.media.matches(device) => {
+                        if let Some(sheet) = &import.sheet {
+                            collect(&sheet.rules, sheet.url.as_ref(), device, faces);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let mut faces = Vec::new();
+        for (sheet, _) in &self.sheets {
+            if sheet.media.matches(&self.device) {
+                collect(&sheet.rules, sheet.url.as_ref(), &self.device, &mut faces);
+            }
+        }
+        faces
+    }
+
+    // Returns the size and margins of printed pages, from the @page rules that apply to
+    // the current device in cascade order
+    pub fn page_style(&self) -> PageStyle {
+        fn collect<'a>(rules: &'a [Rule], device: &Device, pages: &mut Vec<&'a PageRule>) {
+            for rule in rules {
+                match rule {
+                    Rule::Page(page) => pages.push(page),
+                    Rule::Media(media_rule) if media_rule.queries.matches(device) => {
+                        collect(&media_rule.rules, device, pages)
+                    }
+                    Rule::Import(import) if import.media.matches(device) => {
+                        if let Some(sheet) = &import.sheet {
+                            collect(&sheet.rules, device, pages);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let mut pages = Vec::new();
+        for (sheet, _) in &self.sheets {
+            if sheet.media.matches(&self.device) {
+                collect(&sheet.rules, &self.device, &mut pages);
+            }
+        }
+        let mut style = PageStyle::default();
+        for page in pages {
+            page.apply(&mut style);
+        }
+        style
+    }
+
+    // Adds a stylesheet to a document that has already been styled. Returns the paths of
+    // the elements the new rules match, the only ones whose style can change.
+    pub fn insert_stylesheet(
+        &mut self,
+        sheet: Stylesheet,
+        origin: Origin,
+        document: &Document,
+    ) -> Vec<Vec<usize>> {
+        let mut new_rules = Vec::new();
+        if sheet.media.matches(&self.device) {
+            self.collect_from(&sheet.rules, origin, &mut new_rules, &mut Vec::new());
+        }
+        let mut matched = Vec::new();
+        if !new_rules.is_empty() {
+            for_each_element(
+                &document.nodes,
+                None,
+                &mut Vec::new(),
+                &mut |element, path| {
+                    if new_rules.iter().any(|rule| rule.selector.matches(element)) {
+                        matched.push(path.to_vec());
+                    }
+                },
+            );
+        }
+        self.add_stylesheet(sheet, origin);
+        matched
+    }
+
+    // Returns how far a change to an element's attribute reaches
+    pub fn attribute_damage(&self, name: &str, old: Option<&str>, new: Option<&str>) -> Damage {
+        self.invalidation.attribute_damage(name, old, new)
+    }
+
+    // Returns how far a change to an element's interaction state reaches
+    pub fn state_damage(&self, element: &Element, old: ElementState, new: ElementState) -> Damage {
+        self.invalidation.state_damage(element, old, new)
+    }
+
+    fn collect_from(
+        &self,
+        sheet_rules: &[Rule],
+        origin: Origin,
+        rules: &mut Vec<IndexedRule>,
+        media_results: &mut Vec<bool>,
+    ) {
+        for rule in sheet_rules {
+            match rule {
+                Rule::Style(style_rule) => {
+                    let declarations = Arc::new(style_rule.declarations.clone());
+                    for selector in &style_rule.selectors {
+                        rules.push(IndexedRule {
+                            selector: selector.clone(),
+                            specificity: selector.specificity(),
+                            declarations: Arc::clone(&declarations),
+                            origin,
+                        });
+                    }
+                }
+                Rule::Media(media_rule) => {
+                    let matches = media_rule.queries.matches(&self.device);
+                    media_results.push(matches);
+                    if matches {
+                        self.collect_from(&media_rule.rules, origin, rules, media_results);
+                    }
+                }
+                Rule::FontFace(_) | Rule::Page(_) => {}
+                // Imported rules cascade as if they were written in place of the import
+                Rule::Import(import) => {
+                    let Some(sheet) = &import.sheet else {
+                        continue;
+                    };
+                    let matches = import.media.matches(&self.device);
+                    media_results.push(matches);
+                    if matches {
+                        self.collect_from(&sheet.rules, origin, rules, media_results);
+                    }
+                }
+            }
+        }
+    }
+
+    // Computes styles for every node in the document
+    pub fn style_document(&self, document: &Document) -> StyleTree {
+        let root = Arc::new(ComputedStyle::default());
+        let mut root_font_size = DEFAULT_FONT_SIZE;
+        let nodes = document
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| {
+                self.style_node(
+                    node,
+                    &document.nodes,
+                    index,
+                    None,
+                    &root,
+                    &mut root_font_size,
+                )
+            })
+            .collect();
+        StyleTree { nodes }
+    }
+
+    // Recomputes the styles of the element at `path` and its descendants. Changes to the
+    // root element restyle the whole document as rem units depend on it.
+    pub fn restyle_subtree(&self, document: &Document, styles: &mut StyleTree, path: &[usize]) {
+        if path.len() <= 1 || styles.nodes.len() != document.nodes.len() {
+            *styles = self.style_document(document);
+            return;
+        }
+        let root_font_size = styles.nodes[path[0]].style.font_size();
+        let root = Arc::new(ComputedStyle::default());
+        self.restyle_path(
+            &document.nodes,
+            &mut styles.nodes,
+            path,
+            None,
+            &root,
+            root_font_size,
+        );
+    }
+
+    fn restyle_path(
+        &self,
+        nodes: &[Node],
+        styled: &mut [StyledNode],
+        path: &[usize],
+        parent: Option<&ElementRef>,
+        parent_style: &Arc<ComputedStyle>,
+        mut root_font_size: f32,
+    ) {
+        let index = path[0];
+        let (Some(node), Some(styled_node)) = (nodes.get(index), styled.get_mut(index)) else {
+            return;
+        };
+        if path.len() == 1 {
+            *styled_node = self.style_node(
+                node,
+                nodes,
+                index,
+                parent,
+                parent_style,
+                &mut root_font_size,
+            );
+            return;
+        }
+        let Node::Element(element) = node else {
+            return;
+        };
+        let element_ref = ElementRef::new(element, parent, nodes, index);
+        let style = Arc::clone(&styled_node.style);
+        self.restyle_path(
+            &element.children,
+            &mut styled_node.children,
+            &path[1..],
+            Some(&element_ref),
+            &style,
+            root_font_size,
+        );
+    }
+
+    fn style_node(
+        &self,
+        node: &Node,
+        siblings: &[Node],
+        index: usize,
+        parent: Option<&ElementRef>,
+        parent_style: &Arc<ComputedStyle>,
+        root_font_size: &mut f32,
+    ) -> StyledNode {
+        let Node::Element(element) = node else {
+            return StyledNode {
+                style: Arc::clone(parent_style),
+                children: Vec::new(),
+                pseudo_elements: Vec::new(),
+            };
+        };
+        let element_ref = ElementRef::new(element, parent, siblings, index);
+        let hints = hints::presentational_hints(&element_ref);
+        let inline = inline_declarations(element);
+        let declarations = self.matching_declarations(&element_ref, None, &hints, &inline);
+        let mut style = compute_style(&declarations, parent_style, *root_font_size, &self.device);
+        if parent.is_none() {
+            // Text is light by default on pages shown dark
+            if self.uses_dark_scheme(&style) {
+                let mut dark = (**parent_style).clone();
+                dark.set("color", Value::Color(DARK_CANVAS_TEXT));
+                style = compute_style(&declarations, &dark, *root_font_size, &self.device);
+            }
+            // The root element's font size is the basis for rem units
+            *root_font_size = style.font_size();
+        }
+        let style = Arc::new(style);
+        let children = element
+            .children
+            .iter()
+            .enumerate()
+            .map(|(i, child)| {
+                self.style_node(
+                    child,
+                    &element.children,
+                    i,
+                    Some(&element_ref),
+                    &style,
+                    root_font_size,
+                )
+            })
+            .collect();
+        let pseudo_elements = self.style_pseudo_elements(&element_ref, &style, *root_font_size);
+        StyledNode {
+            style,
+            children,
+            pseudo_elements,
+        }
+    }
+
+    // Computes the styles of an element's pseudo-elements, which inherit from it. List items
+    // always have a marker, ::before and ::after only generate a box when given content.
+    fn style_pseudo_elements(
+        &self,
+        element: &ElementRef,
+        style: &Arc<ComputedStyle>,
+        root_font_size: f32,
+    ) -> Vec<(PseudoElement, Arc<ComputedStyle>)> {
+        let mut styles = Vec::new();
+        for pseudo_element in [
+            PseudoElement::Marker,
+            PseudoElement::Before,
+            PseudoElement::After,
+        ] {
+            let marker = pseudo_element == PseudoElement::Marker;
+            if marker && style.display() != "list-item" {
+                continue;
+            }
+            let declarations = self.matching_declarations(element, Some(pseudo_element), &[], &[]);
+            if !marker && declarations.is_empty() {
+                continue;
+            }
+            let computed = compute_style(&declarations, style, root_font_size, &self.device);
+            if !marker && matches!(computed.keyword("content"), Some("normal" | "none")) {
+                continue;
+            }
+            styles.push((pseudo_element, Arc::new(computed)));
+        }
+        styles
+    }
+
+    // Returns the declarations that apply to an element or one of its pseudo-elements,
+    // sorted from lowest to highest precedence so that later declarations win
+    fn matching_declarations<'a>(
+        &'a self,
+        element: &ElementRef,
+        pseudo_element: Option<PseudoElement>,
+        hints: &'a [Declaration],
+        inline: &'a [Declaration],
+    ) -> Vec<&'a Declaration> {
+        // (origin and importance rank, is inline, specificity, source order)
+        let mut matched: Vec<(u8, bool, Specificity, usize, &Declaration)> = Vec::new();
+        // Presentational hints come before every author rule
+        for declaration in hints {
+            let rank = cascade_rank(Origin::Author, false);
+            matched.push((rank, false, (0, 0, 0), matched.len(), declaration));
+        }
+        for rule in &self.rules {
+            if rule.selector.pseudo_element != pseudo_element || !rule.selector.matches(element) {
+                continue;
+            }
+            for declaration in rule.declarations.iter() {
+                let rank = cascade_rank(rule.origin, declaration.important);
+                matched.push((rank, false, rule.specificity, matched.len(), declaration));
+            }
+        }
+        for declaration in inline {
+            let rank = cascade_rank(Origin::Author, declaration.important);
+            matched.push((rank, true, (0, 0, 0), matched.len(), declaration));
+        }
+        matched.sort_by_key(|entry| (entry.0, entry.1, entry.2, entry.3));
+        matched.into_iter().map(|entry| entry.4).collect()
+    }
+}
+
+// Calls `f` for every element in the tree along with its path of child indexes
+fn for_each_element(
+    nodes: &[Node],
+    parent: Option<&ElementRef>,
+    path: &mut Vec<usize>,
+    f: &mut dyn FnMut(&ElementRef, &[usize]),
+) {
+    for (index, node) in nodes.iter().enumerate() {
+        let Node::Element(element) = node else {
+            continue;
+        };
+        let element_ref = ElementRef::new(element, parent, nodes, index);
+        path.push(index);
+        f(&element_ref, path);
+        for_each_element(&element.children, Some(&element_ref), path, f);
+        path.pop();
+    }
+}
+
+fn cascade_rank(origin: Origin, important: bool) -> u8 {
+    match (origin, important) {
+        (Origin::UserAgent, false) => 0,
+        (Origin::Author, false) => 1,
+        (Origin::Author, true) => 2,
+        (Origin::UserAgent, true) => 3,
+    }
+}
+
+fn inline_declarations(element: &Element) -> Vec<Declaration> {
+    match element.attributes.get("style") {
+        Some(style) => parser::parse_declarations(style),
+        None => Vec::new(),
+    }
+}
+
+pub fn user_agent_stylesheet() -> Stylesheet {
+    parser::parse_stylesheet(USER_AGENT_CSS)
+}
+
+// Returns true if any declaration in the rules uses viewport relative units
+fn uses_viewport_units(rules: &[Rule]) -> bool {
+    let is_viewport_unit = |token: &Token| {
+        matches!(token, Token::Dimension(_, unit)
+            if matches!(unit.to_ascii_lowercase().as_str(), "vw" | "vh" | "vmin" | "vmax"))
+    };
+    rules.iter().any(|rule| match rule {
+        Rule::Style(style_rule) => style_rule
+            .declarations
+            .iter()
+            .any(|declaration| declaration.value.iter().any(is_viewport_unit)),
+        Rule::Media(media_rule) => uses_viewport_units(&media_rule.rules),
+        Rule::Import(import) => import
+            .sheet
+            .as_ref()
+            .is_some_and(|sheet| uses_viewport_units(&sheet.rules)),
+        Rule::FontFace(_) | Rule::Page(_) => false,
+    })
+}
+
+// Collects the contents of every `<style>` element in document order
+pub fn author_stylesheets(document: &Document) -> Vec<Stylesheet> {
+    fn collect(nodes: &[Node], sheets: &mut Vec<Stylesheet>) {
+        for node in nodes {
+            if let Node::Element(element) = node {
+                if element.element_type == ElementType::Style {
+                    let mut sheet = parser::parse_stylesheet(&element.text_content());
+                    if let Some(media) = element.attributes.get("media") {
+                        sheet.media = MediaQueryList::parse(media);
+                    }
+                    sheets.push(sheet);
+                } else {
+                    collect(&element.children, sheets);
+                }
+            }
+        }
+    }
+    let mut sheets = Vec::new();
+    collect(&document.nodes, &mut sheets);
+    sheets
+}
+
+// Computes an element's style from the declarations that apply to it, in cascade order
+pub fn compute_style(
+    declarations: &[&Declaration],
+    parent: &ComputedStyle,
+    root_font_size: f32,
+    device: &Device,
+) -> ComputedStyle {
+    let mut style = parent.inherit();
+
+    // Custom properties are computed first as every other property may reference them
+    let custom: Vec<(&str, &[Token])> = declarations
+        .iter()
+        .filter(|declaration| declaration.is_custom_property())
+        .map(|declaration| (declaration.name.as_str(), declaration.value.as_slice()))
+        .collect();
+    if !custom.is_empty() {
+        style.custom_properties = Arc::new(variables::compute_custom_properties(
+            &parent.custom_properties,
+            &custom,
+        ));
+    }
+
+    // Substitute var() references. None marks a value invalid at computed-value time.
+    let specified: Vec<(&str, Option<Vec<Token>>)> = declarations
+        .iter()
+        .filter(|declaration| !declaration.is_custom_property())
+        .map(|declaration| {
+            let value = match variables::contains_var(&declaration.value) {
+                true => variables::substitute(&declaration.value, &mut |name| {
+                    style.custom_properties.get(name).cloned()
+                })
+                .filter(|tokens| properties::is_valid(&declaration.name, tokens)),
+                false => Some(declaration.value.clone()),
+            };
+            (declaration.name.as_str(), value)
+        })
+        .collect();
+
+    // Font size comes next as em units in other properties depend on it
+    let mut context = LengthContext {
+        font_size: parent.font_size(),
+        root_font_size,
+        viewport_width: device.viewport_width,
+        viewport_height: device.viewport_height,
+    };
+    let mut font_size = parent.font_size();
+    for (name, tokens) in &specified {
+        if *name != "font-size" && *name != "font" {
+            continue;
+        }
+        let value = tokens
+            .as_ref()
+            .and_then(|tokens| values::parse_value(tokens, &context));
+        let value = match (*name, value) {
+            ("font", Some(value)) if css_wide_keyword(&value).is_none() => {
+                properties::expand_shorthand("font", &value)
+                    .and_then(|longhands| longhands.into_iter().find(|(n, _)| *n == "font-size"))
+                    .map(|(_, value)| value)
+            }
+            (_, value) => value,
+        };
+        match value.as_ref().and_then(css_wide_keyword) {
+            Some("initial") => font_size = DEFAULT_FONT_SIZE,
+            Some(_) => font_size = parent.font_size(),
+            None => {
+                if let Some(size) =
+                    value.and_then(|value| resolve_font_size(&value, parent.font_size()))
+                {
+                    font_size = size;
+                } else if tokens.is_none() {
+                    // Invalid at computed-value time, and font-size is inherited
+                    font_size = parent.font_size();
+                }
+            }
+        }
+    }
+    style.font_size = font_size;
+    context.font_size = font_size;
+
+    for (name, tokens) in specified {
+        let value = tokens.and_then(|tokens| values::parse_value(&tokens, &context));
+        let Some(value) = value else {
+            // Declarations made invalid by substitution behave as `unset`
+            if properties::longhands(name).is_some() || properties::lookup(name).is_some() {
+                apply_value(&mut style, parent, name, Value::keyword("unset"));
+            }
+            continue;
+        };
+        apply_value(&mut style, parent, name, value);
+    }
+    style.set("font-size", Value::Length(font_size));
+    style
+}
+
+fn css_wide_keyword(value: &Value) -> Option<&str> {
+    value
+        .as_keyword()
+        .filter(|keyword| matches!(*keyword, "inherit" | "initial" | "unset" | "revert"))
+}
+
+// Applies a value to the style, expanding shorthands and resolving CSS-wide keywords
+fn apply_value(style: &mut ComputedStyle, parent: &ComputedStyle, name: &str, value: Value) {
+    if let Some(longhands) = properties::longhands(name) {
+        if css_wide_keyword(&value).is_some() {
+            for longhand in longhands {
+                apply_value(style, parent, longhand, value.clone());
+            }
+        } else if let Some(expanded) = properties::expand_shorthand(name, &value) {
+            for (longhand, value) in expanded {
+                apply_value(style, parent, longhand, value);
+            }
+        }
+        return;
+    }
+    let keyword = match css_wide_keyword(&value) {
+        Some("unset" | "revert") if properties::is_inherited(name) => "inherit",
+        Some("unset" | "revert") => "initial",
+        Some(keyword) => keyword,
+        None => {
+            if name == "color" && value.is_keyword("currentcolor") {
+                "inherit"
+            } else if name == "font-weight" {
+                // Weights are computed to numbers so bolder and lighter can be resolved
+                if let Some(weight) = resolve_font_weight(&value, parent.font_weight()) {
+                    style.set(name, Value::Number(weight as f32));
+                }
+                return;
+            } else {
+                style.set(name, value);
+                return;
+            }
+        }
+    };
+    match (keyword, parent.values.get(name)) {
+        ("inherit", Some(inherited)) => style.set(name, inherited.clone()),
+        _ => {
+            style.values.remove(name);
+        }
+    }
+}
+
+// Resolves a font-weight value, with bolder and lighter relative to the parent's weight
+fn resolve_font_weight(value: &Value, parent_weight: u16) -> Option<u16> {
+    let weight = match value {
+        Value::Number(weight) if (1.0..=1000.0).contains(weight) => *weight as u16,
+        Value::Keyword(keyword) => match keyword.as_str() {
+            "normal" => 400,
+            "bold" => 700,
+            "bolder" => match parent_weight {
+                0..350 => 400,
+                350..550 => 700,
+                550..900 => 900,
+                _ => parent_weight,
+            },
+            "lighter" => match parent_weight {
+                0..100 => parent_weight,
+                100..550 => 100,
+                550..750 => 400,
+                _ => 700,
+            },
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(weight)
+}
+
+// Resolves a font-size value against the parent's font size
+fn resolve_font_size(value: &Value, parent_size: f32) -> Option<f32> {
+    let size = match value {
+        Value::Keyword(keyword) => match keyword.as_str() {
+            "xx-small" => 9.0,
+            "x-small" => 10.0,
+            "small" => 13.0,
+            "medium" => 16.0,
+            "large" => 18.0,
+            "x-large" => 24.0,
+            "xx-large" => 32.0,
+            "xxx-large" => 48.0,
+            "smaller" => parent_size / 1.2,
+            "larger" => parent_size * 1.2,
+            _ => return None,
+        },
+        value => value.to_px(parent_size)?,
+    };
+    match size >= 0.0 {
+        true => Some(size),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html;
+
+    fn style_for(html: &str, path: &[usize]) -> Arc<ComputedStyle> {
+        let document = html::parse_document(html);
+        let stylist = Stylist::for_document(&document, Device::default());
+        let tree = stylist.style_document(&document);
+        let html_index = document
+            .nodes
+            .iter()
+            .position(|node| matches!(node, Node::Element(_)))
+            .unwrap();
+        let mut node = &tree.nodes[html_index];
+        for index in path {
+            node = &node.children[*index];
+        }
+        Arc::clone(&node.style)
+    }
+
+    // Styles the first element inside body
+    fn body_child(css: &str, body: &str) -> Arc<ComputedStyle> {
+        let html = format!(
+            "<html><head><style>{}</style></head><body>{}</body></html>",
+            css, body
+        );
+        style_for(&html, &[1, 0])
+    }
+
+    #[test]
+    fn test_user_agent_styles() {
+        let style = body_child("", "<p>text</p>");
+        assert_eq!(style.display(), "block");
+        assert_eq!(style.length("margin-top", 0.0), Some(16.0));
+        let body = style_for("<html><body></body></html>", &[1]);
+        assert_eq!(body.length("margin-left", 0.0), Some(8.0));
+    }
+
+    #[test]
+    fn test_specificity_and_order() {
+        let style = body_child(
+            "#a { color: red } p.b { color: blue } p { color: green } .b { color: yellow }",
+            "<p id=a class=b>text</p>",
+        );
+        assert_eq!(style.color("color"), Color::rgb(255, 0, 0));
+        let style = body_child(
+            ".x { color: red } .y { color: blue }",
+            "<p class='x y'></p>",
+        );
+        assert_eq!(style.color("color"), Color::rgb(0, 0, 255));
+    }
+
+    #[test]
+    fn test_important_and_inline() {
+        let style = body_child(
+            "p { color: red !important } #a { color: blue }",
+            "<p id=a style='color: green'></p>",
+        );
+        assert_eq!(style.color("color"), Color::rgb(255, 0, 0));
+        let style = body_child("#a { color: blue }", "<p id=a style='color: lime'></p>");
+        assert_eq!(style.color("color"), Color::rgb(0, 255, 0));
+    }
+
+    #[test]
+    fn test_inheritance_and_keywords() {
+        let child = style_for(
+            "<html><head><style>div { color: red; border-color: blue; font-size: 20px; margin: 5px } p { border-color: inherit; padding: 1em; margin: inherit; margin-left: initial }</style></head><body><div><p></p></div></body></html>",
+            &[1, 0, 0],
+        );
+        assert_eq!(child.color("color"), Color::rgb(255, 0, 0));
+        assert_eq!(child.color("border-left-color"), Color::rgb(0, 0, 255));
+        assert_eq!(child.length("padding-left", 0.0), Some(20.0));
+        assert_eq!(child.length("margin-top", 0.0), Some(5.0));
+        assert_eq!(child.length("margin-left", 0.0), Some(0.0));
+        assert_eq!(child.display(), "block");
+    }
+
+    #[test]
+    fn test_font_size_units() {
+        let child = style_for(
+            "<html style='font-size: 10px'><body style='font-size: 2em'><p style='font-size: 150%; width: 2rem; height: 2em'></p></body></html>",
+            &[1, 0],
+        );
+        assert_eq!(child.font_size(), 30.0);
+        assert_eq!(child.length("width", 0.0), Some(20.0));
+        assert_eq!(child.length("height", 0.0), Some(60.0));
+    }
+
+    #[test]
+    fn test_custom_properties() {
+        let style = body_child(
+            ":root { --gap: 4px; --brand: rgb(1, 2, 3) } p { --double: calc(var(--gap) * 2); margin: var(--double) 0; color: var(--brand) }",
+            "<p></p>",
+        );
+        assert_eq!(style.length("margin-top", 0.0), Some(8.0));
+        assert_eq!(style.length("margin-left", 0.0), Some(0.0));
+        assert_eq!(style.color("color"), Color::rgb(1, 2, 3));
+        assert_eq!(
+            style.custom_property("--double").as_deref(),
+            Some("calc(4px * 2)")
+        );
+    }
+
+    #[test]
+    fn test_invalid_at_computed_value_time() {
+        // An unresolvable var() makes the declaration behave as unset, beating earlier ones
+        let child = style_for(
+            "<html><head><style>div { color: red } p { color: blue; color: var(--missing); background-color: red; background-color: var(--missing) }</style></head><body><div><p></p></div></body></html>",
+            &[1, 0, 0],
+        );
+        assert_eq!(child.color("color"), Color::rgb(255, 0, 0));
+        assert_eq!(child.color("background-color"), Color::TRANSPARENT);
+    }
+
+    #[test]
+    fn test_invalid_declarations_are_dropped() {
+        // Values the property doesn't accept are ignored, so earlier declarations are fallbacks
+        let style = body_child(
+            ":root { --gap: 10px } p { width: 100px; width: red; color: #123; color: newfunc(1); \
+             margin-top: 4px; margin: 1px red; padding-left: 5px; padding-left: var(--gap) }",
+            "<p></p>",
+        );
+        assert_eq!(style.length("width", 0.0), Some(100.0));
+        assert_eq!(style.color("color"), Color::rgb(0x11, 0x22, 0x33));
+        assert_eq!(style.length("margin-top", 0.0), Some(4.0));
+        // Only once var() is substituted can its value be checked
+        assert_eq!(style.length("padding-left", 0.0), Some(10.0));
+        let style = body_child(
+            ":root { --color: red } p { padding-left: 5px; padding-left: var(--color) }",
+            "<p></p>",
+        );
+        assert_eq!(style.length("padding-left", 0.0), Some(0.0));
+    }
+
+    #[test]
+    fn test_self_reference_is_a_cycle() {
+        let style = body_child(
+            ":root { --gap: 4px } p { --gap: calc(var(--gap) * 2); margin-top: var(--gap, 1px) }",
+            "<p></p>",
+        );
+        assert_eq!(style.custom_property("--gap"), None);
+        assert_eq!(style.length("margin-top", 0.0), Some(1.0));
+    }
+
+    #[test]
+    fn test_custom_property_cycles() {
+        let style = body_child(
+            "p { --a: var(--b); --b: var(--a); --c: 5px; width: var(--a, 10px); height: var(--b); padding-left: var(--c) }",
+            "<p></p>",
+        );
+        assert_eq!(style.custom_property("--a"), None);
+        assert_eq!(style.custom_property("--b"), None);
+        assert_eq!(style.length("width", 0.0), Some(10.0));
+        assert_eq!(style.length("height", 0.0), None);
+        assert_eq!(style.length("padding-left", 0.0), Some(5.0));
+    }
+
+    #[test]
+    fn test_var_in_shorthand_and_font_size() {
+        let style = body_child(
+            ":root { --border: 2px solid; --size: 24px } p { border: var(--border) blue; font-size: var(--size); width: 2em }",
+            "<p></p>",
+        );
+        assert_eq!(style.length("border-top-width", 0.0), Some(2.0));
+        assert_eq!(style.keyword("border-bottom-style"), Some("solid"));
+        assert_eq!(style.color("border-left-color"), Color::rgb(0, 0, 255));
+        assert_eq!(style.font_size(), 24.0);
+        assert_eq!(style.length("width", 0.0), Some(48.0));
+    }
+
+    #[test]
+    fn test_media_rules_follow_the_device() {
+        let document = html::parse_document(
+            "<html><head><style>p { color: red } @media (min-width: 600px) { p { color: blue } }\
+             </style></head><body><p></p></body></html>",
+        );
+        let color = |stylist: &Stylist| {
+            let tree = stylist.style_document(&document);
+            tree.nodes[0].children[1].children[0].style.color("color")
+        };
+        let mut stylist = Stylist::for_document(&document, Device::default());
+        assert_eq!(color(&stylist), Color::rgb(0, 0, 255));
+
+        let narrow = Device {
+            viewport_width: 400.0,
+            ..Default::default()
+        };
+        assert!(stylist.set_device(narrow.clone()));
+        assert_eq!(color(&stylist), Color::rgb(255, 0, 0));
+        // Nothing changed, no restyle needed
+        assert!(!stylist.set_device(narrow));
+        // Resizing without crossing a breakpoint doesn't need a restyle either
+        let narrower = Device {
+            viewport_width: 300.0,
+            ..Default::default()
+        };
+        assert!(!stylist.set_device(narrower));
+    }
+
+    #[test]
+    fn test_viewport_units_restyle_on_resize() {
+        let document =
+            html::parse_document("<html><head><style>p { width: 50vw }</style></head></html>");
+        let mut stylist = Stylist::for_document(&document, Device::default());
+        let resized = Device {
+            viewport_width: 1000.0,
+            ..Default::default()
+        };
+        assert!(stylist.set_device(resized));
+        let dark = Device {
+            viewport_width: 1000.0,
+            color_scheme: ColorScheme::Dark,
+            ..Default::default()
+        };
+        assert!(!stylist.set_device(dark));
+    }
+
+    #[test]
+    fn test_style_element_media_attribute() {
+        let style = body_child("", "<p></p>");
+        assert_eq!(style.color("color"), Color::BLACK);
+        let style = style_for(
+            "<html><head><style media=print>p { color: red }</style>\
+             <style media='screen and (orientation: landscape)'>p { font-size: 20px }</style>\
+             </head><body><p></p></body></html>",
+            &[1, 0],
+        );
+        assert_eq!(style.color("color"), Color::BLACK);
+        assert_eq!(style.font_size(), 20.0);
+    }
+
+    #[test]
+    fn test_imported_rules_cascade_in_place() {
+        let document = html::parse_document("<html><body><p></p></body></html>");
+        let mut sheet = parser::parse_stylesheet(
+            "@import 'a.css'; @import 'b.css' print; p { font-size: 20px }",
+        );
+        for (index, css) in ["p { color: red; font-size: 10px }", "p { color: blue }"]
+            .iter()
+            .enumerate()
+        {
+            let Rule::Import(import) = &mut sheet.rules[index] else {
+                panic!("Expected import rule");
+            };
+            import.sheet = Some(Box::new(parser::parse_stylesheet(css)));
+        }
+        let mut stylist = Stylist::new(Device::default());
+        stylist.add_stylesheet(sheet, Origin::Author);
+        let style = |stylist: &Stylist| {
+            let tree = stylist.style_document(&document);
+            Arc::clone(&tree.nodes[0].children[1].children[0].style)
+        };
+        assert_eq!(style(&stylist).color("color"), Color::rgb(255, 0, 0));
+        assert_eq!(style(&stylist).font_size(), 20.0);
+
+        let print = Device {
+            media_type: MediaType::Print,
+            ..Default::default()
+        };
+        assert!(stylist.set_device(print));
+        assert_eq!(style(&stylist).color("color"), Color::rgb(0, 0, 255));
+    }
+
+    #[test]
+    fn test_font_properties() {
+        let style = body_child(
+            "div { font: italic bold 12px 'Open Sans', Demo Font, serif } p { font-weight: lighter }",
+            "<div><p></p></div>",
+        );
+        assert_eq!(
+            style.font_families(),
+            vec!["Open Sans", "demo font", "serif"]
+        );
+        assert_eq!(style.font_weight(), 700);
+        assert!(style.is_italic());
+        let style = style_for(
+            "<html><head><style>div { font-weight: bold } p { font-weight: lighter }\
+             span { font-weight: bolder }</style></head>\
+             <body><div><p><span></span></p></div></body></html>",
+            &[1, 0, 0],
+        );
+        assert_eq!(style.font_weight(), 400);
+        let span = style_for(
+            "<html><head><style>p { font-weight: 300 } span { font-weight: bolder }</style>\
+             </head><body><p><span></span></p></body></html>",
+            &[1, 0, 0],
+        );
+        assert_eq!(span.font_weight(), 400);
+    }
+}
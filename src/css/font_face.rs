@@ -0,0 +1,191 @@
+// The `@font-face` rule and its descriptors
+
+use crate::css::parser::Declaration;
+use crate::css::tokenizer::Token;
+
+/// An `@font-face` rule. Descriptors are interpreted on demand.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontFaceRule {
+    pub declarations: Vec<Declaration>,
+}
+
+/// One entry of the `src` descriptor
+#[derive(Debug, Clone, PartialEq)]
+pub enum FontSource {
+    Url { url: String, format: Option<String> },
+    Local(String),
+}
+
+impl FontFaceRule {
+    // The last valid declaration of a descriptor wins, like properties
+    fn descriptor(&self, name: &str) -> Option<Vec<&Token>> {
+        self.declarations
+            .iter()
+            .rev()
+            .find(|declaration| declaration.name == name)
+            .map(|declaration| {
+                declaration
+                    .value
+                    .iter()
+                    .filter(|token| !token.is_whitespace())
+                    .collect()
+            })
+    }
+
+    // The family name, given as a string or a sequence of identifiers
+    pub fn family(&self) -> Option<String> {
+        let tokens = self.descriptor("font-family")?;
+        match tokens.as_slice() {
+            [Token::String(name)] => Some(name.clone()),
+            idents if !idents.is_empty() => {
+                let words: Option<Vec<&str>> = idents
+                    .iter()
+                    .map(|token| match token {
+                        Token::Ident(word) => Some(word.as_str()),
+                        _ => None,
+                    })
+                    .collect();
+                Some(words?.join(" "))
+            }
+            _ => None,
+        }
+    }
+
+    // The font files to try in order
+    pub fn sources(&self) -> Vec<FontSource> {
+        let Some(declaration) = self
+            .declarations
+            .iter()
+            .rev()
+            .find(|declaration| declaration.name == "src")
+        else {
+            return Vec::new();
+        };
+        declaration
+            .value
+            .split(|token| *token == Token::Comma)
+            .filter_map(parse_source)
+            .collect()
+    }
+
+    // The range of weights the face covers, a single weight for non-variable fonts
+    pub fn weight(&self) -> (u16, u16) {
+        let weight = |token: &Token| match token {
+            Token::Number(weight) => Some(weight.clamp(1.0, 1000.0) as u16),
+            Token::Ident(keyword) if keyword.eq_ignore_ascii_case("normal") => Some(400),
+            Token::Ident(keyword) if keyword.eq_ignore_ascii_case("bold") => Some(700),
+            _ => None,
+        };
+        let tokens = self.descriptor("font-weight").unwrap_or_default();
+        match tokens.as_slice() {
+            [single] => weight(single).map(|w| (w, w)),
+            [min, max] => weight(min).zip(weight(max)),
+            _ => None,
+        }
+        .unwrap_or((400, 400))
+    }
+
+    pub fn is_italic(&self) -> bool {
+        self.descriptor("font-style")
+            .and_then(|tokens| match tokens.first() {
+                Some(Token::Ident(style)) => Some(
+                    style.eq_ignore_ascii_case("italic") || style.eq_ignore_ascii_case("oblique"),
+                ),
+                _ => None,
+            })
+            .unwrap_or(false)
+    }
+}
+
+fn parse_source(tokens: &[Token]) -> Option<FontSource> {
+    let tokens: Vec<&Token> = tokens.iter().filter(|t| !t.is_whitespace()).collect();
+    // The argument of a function such as `format("woff2")` or `local(Name)`
+    let argument = |tokens: &[&Token]| -> Option<(String, usize)> {
+        let close = tokens.iter().position(|t| **t == Token::CloseParen)?;
+        let words: Vec<String> = tokens[1..close]
+            .iter()
+            .map(|token| match token {
+                Token::String(value) | Token::Ident(value) => Some(value.clone()),
+                _ => None,
+            })
+            .collect::<Option<_>>()?;
+        Some((words.join(" "), close + 1))
+    };
+    let (source, rest) = match tokens.first()? {
+        Token::Url(url) => (
+            FontSource::Url {
+                url: url.clone(),
+                format: None,
+            },
+            &tokens[1..],
+        ),
+        Token::Function(name) if name.eq_ignore_ascii_case("url") => {
+            let (url, end) = argument(&tokens)?;
+            (FontSource::Url { url, format: None }, &tokens[end..])
+        }
+        Token::Function(name) if name.eq_ignore_ascii_case("local") => {
+            let (name, end) = argument(&tokens)?;
+            (FontSource::Local(name), &tokens[end..])
+        }
+        _ => return None,
+    };
+    match (source, rest.first()) {
+        (FontSource::Url { url, .. }, Some(Token::Function(name)))
+            if name.eq_ignore_ascii_case("format") =>
+        {
+            let (format, _) = argument(rest)?;
+            Some(FontSource::Url {
+                url,
+                format: Some(format.to_ascii_lowercase()),
+            })
+        }
+        (source, _) => Some(source),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::parser::declarations_from_tokens;
+    use crate::css::tokenizer::tokenize;
+
+    fn rule(css: &str) -> FontFaceRule {
+        FontFaceRule {
+            declarations: declarations_from_tokens(&tokenize(css)),
+        }
+    }
+
+    #[test]
+    fn test_family_and_style() {
+        let face = rule("font-family: My Font; font-weight: 300 700; font-style: italic");
+        assert_eq!(face.family().as_deref(), Some("My Font"));
+        assert_eq!(face.weight(), (300, 700));
+        assert!(face.is_italic());
+
+        let face = rule("font-family: 'Quoted Name'; font-weight: bold");
+        assert_eq!(face.family().as_deref(), Some("Quoted Name"));
+        assert_eq!(face.weight(), (700, 700));
+        assert!(!face.is_italic());
+        assert_eq!(rule("font-family: 12px").family(), None);
+    }
+
+    #[test]
+    fn test_sources() {
+        let face =
+            rule("src: local('Open Sans'), url(a.woff2) format('woff2'), url(\"b.ttf\"), bogus(1)");
+        assert_eq!(
+            face.sources(),
+            vec![
+                FontSource::Local("Open Sans".to_string()),
+                FontSource::Url {
+                    url: "a.woff2".to_string(),
+                    format: Some("woff2".to_string()),
+                },
+                FontSource::Url {
+                    url: "b.ttf".to_string(),
+                    format: None,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,261 @@
+// Presentational hints: HTML attributes such as `bgcolor` and `cellpadding` that style
+// an element. They cascade as author declarations that lose to every author rule.
+
+use crate::css::parser::{self, Declaration};
+use crate::css::selector::ElementRef;
+use crate::dom::{Element, ElementType, Node};
+
+// Attributes that change an element's style or its table layout
+const ATTRIBUTES: &[&str] = &[
+    "width",
+    "height",
+    "bgcolor",
+    "align",
+    "valign",
+    "nowrap",
+    "border",
+    "cellspacing",
+    "cellpadding",
+    "colspan",
+    "rowspan",
+    "span",
+    "start",
+    "reversed",
+    "value",
+];
+
+pub fn is_presentational(name: &str) -> bool {
+    ATTRIBUTES.contains(&name)
+}
+
+// Returns the declarations an element's attributes map to
+pub fn presentational_hints(element: &ElementRef) -> Vec<Declaration> {
+    let attributes = &element.element.attributes;
+    let attribute = |name: &str| attributes.get(name).map(String::as_str);
+    let mut css = String::new();
+    let tag = element.element.element_type.tag_name().to_ascii_lowercase();
+    let sized = matches!(
+        tag.as_str(),
+        "table" | "td" | "th" | "col" | "colgroup" | "tr"
+    );
+    if sized {
+        for name in ["width", "height"] {
+            if let Some(size) = attribute(name).and_then(dimension) {
+                css.push_str(&format!("{}: {};", name, size));
+            }
+        }
+    }
+    if matches!(
+        tag.as_str(),
+        "table" | "td" | "th" | "tr" | "thead" | "tbody" | "tfoot"
+    ) {
+        if let Some(color) = attribute("bgcolor").and_then(color) {
+            css.push_str(&format!("background-color: {};", color));
+        }
+    }
+    match tag.as_str() {
+        "table" => {
+            if let Some(spacing) = attribute("cellspacing").and_then(pixels) {
+                css.push_str(&format!("border-spacing: {}px;", spacing));
+            }
+            if let Some(width) = table_border(element.element) {
+                css.push_str(&format!("border: {}px outset;", width));
+            }
+            if attribute("align").is_some_and(|align| align.eq_ignore_ascii_case("center")) {
+                css.push_str("margin-left: auto; margin-right: auto;");
+            }
+        }
+        "td" | "th" | "tr" | "thead" | "tbody" | "tfoot" => {
+            if let Some(align) = attribute("align") {
+                css.push_str(&format!("text-align: {};", align.to_ascii_lowercase()));
+            }
+            if let Some(valign) = attribute("valign") {
+                css.push_str(&format!("vertical-align: {};", valign.to_ascii_lowercase()));
+            }
+        }
+        // List numbering, counted by the list-item counter
+        "ol" => {
+            let start = attribute("start").and_then(integer);
+            if attributes.contains_key("reversed") {
+                // Items count down from the number of items unless told otherwise
+                let items = element
+                    .element
+                    .children
+                    .iter()
+                    .filter(|child| match child {
+                        Node::Element(child) => {
+                            child.element_type.tag_name().eq_ignore_ascii_case("li")
+                        }
+                        _ => false,
+                    })
+                    .count() as i32;
+                let start = start.unwrap_or(items);
+                css.push_str(&format!(
+                    "counter-reset: reversed(list-item) {};",
+                    start + 1
+                ));
+            } else if let Some(start) = start {
+                css.push_str(&format!("counter-reset: list-item {};", start - 1));
+            }
+        }
+        "li" => {
+            if let Some(value) = attribute("value").and_then(integer) {
+                css.push_str(&format!("counter-set: list-item {};", value));
+            }
+        }
+        // The color schemes a page supports may be given in a `<meta>` in its head
+        "html" => {
+            if let Some(schemes) = meta_color_scheme(element.element) {
+                css.push_str(&format!("color-scheme: {};", schemes));
+            }
+        }
+        _ => {}
+    }
+    if matches!(tag.as_str(), "td" | "th") {
+        if attributes.contains_key("nowrap") {
+            css.push_str("white-space: nowrap;");
+        }
+        // Cells take their padding and borders from the table
+        let table =
+            std::iter::successors(element.parent, |parent| parent.parent).find(|ancestor| {
+                ancestor
+                    .element
+                    .element_type
+                    .tag_name()
+                    .eq_ignore_ascii_case("table")
+            });
+        if let Some(table) = table {
+            let cellpadding = table.element.attributes.get("cellpadding");
+            if let Some(padding) = cellpadding.and_then(|padding| pixels(padding)) {
+                css.push_str(&format!("padding: {}px;", padding));
+            }
+            if table_border(table.element).is_some_and(|width| width > 0.0) {
+                css.push_str("border: 1px inset gray;");
+            }
+        }
+    }
+    match css.is_empty() {
+        true => Vec::new(),
+        false => parser::parse_declarations(&css),
+    }
+}
+
+// The keywords of a `<meta name="color-scheme">` in the head of the root element. Other
+// words are dropped so the content can't add declarations of its own.
+fn meta_color_scheme(html: &Element) -> Option<String> {
+    let head = html.children.iter().find_map(|node| match node {
+        Node::Element(head) if head.element_type == ElementType::Head => Some(head),
+        _ => None,
+    })?;
+    let content = head.children.iter().find_map(|node| match node {
+        Node::Element(meta)
+            if meta.element_type.tag_name().eq_ignore_ascii_case("meta")
+                && meta
+                    .attributes
+                    .get("name")
+                    .is_some_and(|name| name.eq_ignore_ascii_case("color-scheme")) =>
+        {
+            meta.attributes.get("content")
+        }
+        _ => None,
+    })?;
+    let keywords: Vec<&str> = content
+        .split_whitespace()
+        .filter(|word| word.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+        .collect();
+    match keywords.is_empty() {
+        true => None,
+        false => Some(keywords.join(" ")),
+    }
+}
+
+// The width of a table's `border` attribute, where an empty value means 1px
+fn table_border(table: &Element) -> Option<f32> {
+    let border = table.attributes.get("border")?;
+    Some(pixels(border).unwrap_or(1.0))
+}
+
+// Parses a possibly negative integer as used by `start`, ignoring trailing garbage
+fn integer(value: &str) -> Option<i32> {
+    let value = value.trim();
+    let (sign, digits) = match value.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, value),
+    };
+    let digits: String = digits.chars().take_while(char::is_ascii_digit).collect();
+    digits.parse::<i32>().ok().map(|number| sign * number)
+}
+
+// Parses a non-negative integer as used by `cellpadding`, ignoring trailing garbage
+fn pixels(value: &str) -> Option<f32> {
+    let digits: String = value
+        .trim()
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    digits.parse().ok()
+}
+
+// Parses a length such as `120` or `50%` as used by `width`
+fn dimension(value: &str) -> Option<String> {
+    let value = value.trim();
+    let number: String = value
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    let size: f32 = number.parse().ok()?;
+    match value[number.len()..].starts_with('%') {
+        true => Some(format!("{}%", size)),
+        false if size > 0.0 => Some(format!("{}px", size)),
+        false => None,
+    }
+}
+
+// Accepts color names and hex colors, with or without the leading `#`
+fn color(value: &str) -> Option<String> {
+    let value = value.trim();
+    let bare_hex = matches!(value.len(), 3 | 6) && value.chars().all(|c| c.is_ascii_hexdigit());
+    match bare_hex {
+        true => Some(format!("#{}", value)),
+        false if value.chars().all(|c| c.is_ascii_alphanumeric() || c == '#') => {
+            Some(value.to_string())
+        }
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::css::{Device, Stylist, Value};
+    use crate::html;
+
+    #[test]
+    fn test_table_attributes_map_to_styles() {
+        let document = html::parse_document(
+            "<table width='50%' cellspacing='4' cellpadding='3' border='2' bgcolor='ffcc00'>\
+             <tr><td align='right' valign='top' width='40' nowrap>a</td></tr></table>\
+             <style>td { padding-left: 9px }</style>",
+        );
+        let styles = Stylist::for_document(&document, Device::default()).style_document(&document);
+        // html > body > table > tbody > tr > td
+        let body = &styles.nodes[0].children[1];
+        let table = &body.children[0].style;
+        assert_eq!(table.get("width"), Some(&Value::Percentage(50.0)));
+        assert_eq!(table.length("border-top-width", 0.0), Some(2.0));
+        assert_eq!(table.keyword("border-top-style"), Some("outset"));
+        assert_eq!(
+            table.color("background-color"),
+            crate::css::Color::from_hex("ffcc00").unwrap()
+        );
+        assert_eq!(table.get("border-spacing"), Some(&Value::Length(4.0)));
+        let cell = &body.children[0].children[0].children[0].children[0].style;
+        assert_eq!(cell.length("width", 0.0), Some(40.0));
+        assert_eq!(cell.keyword("text-align"), Some("right"));
+        assert_eq!(cell.keyword("vertical-align"), Some("top"));
+        assert_eq!(cell.keyword("white-space"), Some("nowrap"));
+        assert_eq!(cell.length("padding-top", 0.0), Some(3.0));
+        assert_eq!(cell.length("border-left-width", 0.0), Some(1.0));
+        // Author rules override the hints
+        assert_eq!(cell.length("padding-left", 0.0), Some(9.0));
+    }
+}
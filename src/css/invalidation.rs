@@ -0,0 +1,240 @@
+// Works out which elements need restyling when an element changes, from the selectors
+// that depend on each id, class, attribute and interaction state
+
+use crate::css::hints;
+use crate::css::selector::{Combinator, CompoundSelector, PseudoClass, Selector};
+use crate::dom::{Element, ElementState};
+use std::collections::{HashMap, HashSet};
+
+/// How far the effects of a change to an element can reach
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Damage {
+    #[default]
+    None,
+    // The element and its descendants
+    Subtree,
+    // The element's later siblings and their descendants as well
+    LaterSiblings,
+}
+
+/// The damage a change to each selector dependency causes
+#[derive(Debug, Default)]
+pub struct InvalidationMap {
+    ids: HashMap<String, Damage>,
+    classes: HashMap<String, Damage>,
+    attributes: HashMap<String, Damage>,
+    // Compounds containing each interaction pseudo-class, without their pseudo-classes, so
+    // that only elements which could match are invalidated when their state changes
+    hover: Vec<(CompoundSelector, Damage)>,
+    focus: Vec<(CompoundSelector, Damage)>,
+    active: Vec<(CompoundSelector, Damage)>,
+}
+
+impl InvalidationMap {
+    pub fn add_selector(&mut self, selector: &Selector) {
+        for (index, compound) in selector.compounds.iter().enumerate() {
+            // A change that affects this compound affects the subject of the selector,
+            // which is a later sibling if a sibling combinator follows
+            let damage = match selector.combinators[index..].iter().any(|combinator| {
+                matches!(
+                    combinator,
+                    Combinator::NextSibling | Combinator::SubsequentSibling
+                )
+            }) {
+                true => Damage::LaterSiblings,
+                false => Damage::Subtree,
+            };
+            self.add_compound(compound, damage);
+        }
+    }
+
+    fn add_compound(&mut self, compound: &CompoundSelector, damage: Damage) {
+        fn raise(entry: &mut Damage, damage: Damage) {
+            *entry = (*entry).max(damage);
+        }
+        if let Some(id) = &compound.id {
+            raise(self.ids.entry(id.clone()).or_default(), damage);
+        }
+        for class in &compound.classes {
+            raise(self.classes.entry(class.clone()).or_default(), damage);
+        }
+        for attribute in &compound.attributes {
+            let name = attribute.name.to_ascii_lowercase();
+            raise(self.attributes.entry(name).or_default(), damage);
+        }
+        fn add_state(
+            entries: &mut Vec<(CompoundSelector, Damage)>,
+            compound: &CompoundSelector,
+            damage: Damage,
+        ) {
+            let compound = CompoundSelector {
+                pseudo_classes: Vec::new(),
+                ..compound.clone()
+            };
+            match entries
+                .iter_mut()
+                .find(|(existing, _)| *existing == compound)
+            {
+                Some((_, existing)) => raise(existing, damage),
+                None => entries.push((compound, damage)),
+            }
+        }
+        for pseudo_class in &compound.pseudo_classes {
+            match pseudo_class {
+                PseudoClass::Hover => add_state(&mut self.hover, compound, damage),
+                PseudoClass::Focus => add_state(&mut self.focus, compound, damage),
+                PseudoClass::Active => add_state(&mut self.active, compound, damage),
+                PseudoClass::Link => {
+                    raise(self.attributes.entry("href".into()).or_default(), damage)
+                }
+                PseudoClass::Not(arguments) => {
+                    for argument in arguments {
+                        self.add_compound(argument, damage);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Returns the damage caused by changing an attribute from `old` to `new`
+    pub fn attribute_damage(&self, name: &str, old: Option<&str>, new: Option<&str>) -> Damage {
+        if old == new {
+            return Damage::None;
+        }
+        let name = name.to_ascii_lowercase();
+        let mut damage = self.attributes.get(&name).copied().unwrap_or_default();
+        match name.as_str() {
+            // Inline declarations only apply to the element itself, while presentational
+            // hints can also reach table cells
+            "style" => damage = damage.max(Damage::Subtree),
+            name if hints::is_presentational(name) => damage = damage.max(Damage::Subtree),
+            "id" => {
+                for id in [old, new].into_iter().flatten() {
+                    damage = damage.max(self.ids.get(id).copied().unwrap_or_default());
+                }
+            }
+            "class" => {
+                let split = |classes: Option<&str>| -> HashSet<String> {
+                    classes
+                        .unwrap_or_default()
+                        .split_whitespace()
+                        .map(str::to_string)
+                        .collect()
+                };
+                let (old, new) = (split(old), split(new));
+                for class in old.symmetric_difference(&new) {
+                    damage = damage.max(self.classes.get(class).copied().unwrap_or_default());
+                }
+            }
+            _ => {}
+        }
+        damage
+    }
+
+    // Returns the damage caused by an element's interaction state changing
+    pub fn state_damage(&self, element: &Element, old: ElementState, new: ElementState) -> Damage {
+        let damage = |entries: &[(CompoundSelector, Damage)]| {
+            entries
+                .iter()
+                .filter(|(compound, _)| compound.matches_element(element))
+                .map(|(_, damage)| *damage)
+                .max()
+                .unwrap_or_default()
+        };
+        let mut result = Damage::None;
+        if old.hover != new.hover {
+            result = result.max(damage(&self.hover));
+        }
+        if old.focus != new.focus {
+            result = result.max(damage(&self.focus));
+        }
+        if old.active != new.active {
+            result = result.max(damage(&self.active));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::parser::{parse_stylesheet, Rule};
+    use crate::dom::{ElementType, Node};
+
+    fn map(css: &str) -> InvalidationMap {
+        let mut map = InvalidationMap::default();
+        for rule in parse_stylesheet(css).rules {
+            if let Rule::Style(rule) = rule {
+                rule.selectors.iter().for_each(|s| map.add_selector(s));
+            }
+        }
+        map
+    }
+
+    #[test]
+    fn test_class_damage() {
+        let map = map(".a { color: red } .b + p { color: blue } div:not(.c) {}");
+        let damage = |old, new| map.attribute_damage("class", old, new);
+        assert_eq!(damage(Some("x"), Some("x a")), Damage::Subtree);
+        assert_eq!(damage(Some("a b"), Some("b a")), Damage::None);
+        assert_eq!(damage(None, Some("b")), Damage::LaterSiblings);
+        assert_eq!(damage(Some("c"), None), Damage::Subtree);
+        assert_eq!(damage(Some("x"), Some("y")), Damage::None);
+    }
+
+    #[test]
+    fn test_attribute_and_id_damage() {
+        let map = map("#main p {} [data-open] {} a:link {}");
+        assert_eq!(
+            map.attribute_damage("id", None, Some("main")),
+            Damage::Subtree
+        );
+        assert_eq!(
+            map.attribute_damage("id", None, Some("other")),
+            Damage::None
+        );
+        assert_eq!(
+            map.attribute_damage("DATA-OPEN", Some(""), None),
+            Damage::Subtree
+        );
+        assert_eq!(
+            map.attribute_damage("href", None, Some("/")),
+            Damage::Subtree
+        );
+        assert_eq!(map.attribute_damage("title", None, Some("x")), Damage::None);
+        assert_eq!(
+            map.attribute_damage("style", None, Some("color: red")),
+            Damage::Subtree
+        );
+    }
+
+    #[test]
+    fn test_state_damage() {
+        let map = map("a:hover {} li:focus ~ li {}");
+        let element = |tag: &str| match Node::new_element(ElementType::from_str(tag)) {
+            Node::Element(element) => element,
+            _ => unreachable!(),
+        };
+        let (a, li) = (element("a"), element("li"));
+        let hovered = ElementState {
+            hover: true,
+            ..Default::default()
+        };
+        let focused = ElementState {
+            focus: true,
+            ..Default::default()
+        };
+        let active = ElementState {
+            active: true,
+            ..Default::default()
+        };
+        let none = ElementState::default();
+        assert_eq!(map.state_damage(&a, none, hovered), Damage::Subtree);
+        assert_eq!(map.state_damage(&li, none, hovered), Damage::None);
+        assert_eq!(map.state_damage(&li, none, focused), Damage::LaterSiblings);
+        assert_eq!(map.state_damage(&a, none, focused), Damage::None);
+        assert_eq!(map.state_damage(&a, none, active), Damage::None);
+        assert_eq!(map.state_damage(&a, hovered, hovered), Damage::None);
+    }
+}
@@ -0,0 +1,333 @@
+// Fetches a document's stylesheets, including `<link rel="stylesheet">` sheets and
+// everything they `@import`
+
+use crate::css::media::MediaQueryList;
+use crate::css::parser::{self, ImportRule, Rule, Stylesheet};
+use crate::dom::{Document, Element, ElementType, Node};
+use crate::networking;
+use std::fmt;
+use std::thread;
+use url::Url;
+
+/// A stylesheet that could not be loaded. Failures are reported per resource and the
+/// rest of the page is still styled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceError {
+    pub url: String,
+    pub message: String,
+}
+
+impl fmt::Display for ResourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.url, self.message)
+    }
+}
+
+// Where an author stylesheet comes from
+#[derive(Debug)]
+enum Source<'a> {
+    Style(&'a Element),
+    Link(&'a Element),
+}
+
+// Loads every author stylesheet of a document in document order. Sheets and their
+// imports are fetched concurrently. Relative URLs resolve against `base_url`.
+pub fn load_stylesheets(
+    document: &Document,
+    base_url: &Url,
+) -> (Vec<Stylesheet>, Vec<ResourceError>) {
+    load_stylesheets_with(document, base_url, &|url: &Url| {
+        networking::fetch_url(url).map_err(|e| e.to_string())
+    })
+}
+
+pub(crate) fn load_stylesheets_with<F>(
+    document: &Document,
+    base_url: &Url,
+    fetch: &F,
+) -> (Vec<Stylesheet>, Vec<ResourceError>)
+where
+    F: Fn(&Url) -> Result<String, String> + Sync,
+{
+    let mut sources = Vec::new();
+    collect_sources(&document.nodes, &mut sources);
+
+    let results: Vec<(Option<Stylesheet>, Vec<ResourceError>)> = thread::scope(|scope| {
+        let handles: Vec<_> = sources
+            .iter()
+            .map(|source| scope.spawn(move || load_source(source, base_url, fetch)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap_or_else(|_| (None, Vec::new())))
+            .collect()
+    });
+
+    let mut sheets = Vec::new();
+    let mut errors = Vec::new();
+    for (sheet, sheet_errors) in results {
+        sheets.extend(sheet);
+        errors.extend(sheet_errors);
+    }
+    (sheets, errors)
+}
+
+fn collect_sources<'a>(nodes: &'a [Node], sources: &mut Vec<Source<'a>>) {
+    for node in nodes {
+        let Node::Element(element) = node else {
+            continue;
+        };
+        match element.element_type {
+            ElementType::Style => sources.push(Source::Style(element)),
+            ElementType::Link if is_stylesheet_link(element) => sources.push(Source::Link(element)),
+            _ => collect_sources(&element.children, sources),
+        }
+    }
+}
+
+// Alternate stylesheets aren't applied by default so they are skipped
+fn is_stylesheet_link(element: &Element) -> bool {
+    let Some(rel) = element.attributes.get("rel") else {
+        return false;
+    };
+    let has = |keyword: &str| {
+        rel.split_ascii_whitespace()
+            .any(|token| token.eq_ignore_ascii_case(keyword))
+    };
+    has("stylesheet") && !has("alternate") && element.attributes.contains_key("href")
+}
+
+fn load_source<F>(
+    source: &Source,
+    base_url: &Url,
+    fetch: &F,
+) -> (Option<Stylesheet>, Vec<ResourceError>)
+where
+    F: Fn(&Url) -> Result<String, String> + Sync,
+{
+    match source {
+        Source::Style(element) => {
+            let mut sheet = parser::parse_stylesheet(&element.text_content());
+            sheet.media = media_attribute(element);
+            let errors = load_imports(&mut sheet.rules, base_url, &[], fetch);
+            (Some(sheet), errors)
+        }
+        Source::Link(element) => {
+            let href = element.attributes.get("href").map_or("", String::as_str);
+            match fetch_stylesheet(href, base_url, &[], fetch) {
+                Ok((mut sheet, errors)) => {
+                    sheet.media = media_attribute(element);
+                    (Some(sheet), errors)
+                }
+                Err(error) => (None, vec![error]),
+            }
+        }
+    }
+}
+
+fn media_attribute(element: &Element) -> MediaQueryList {
+    match element.attributes.get("media") {
+        Some(media) => MediaQueryList::parse(media),
+        None => MediaQueryList::default(),
+    }
+}
+
+// Fetches and parses a stylesheet and then loads its imports. `ancestors` holds the
+// sheets that are importing this one, which is used to break import cycles.
+fn fetch_stylesheet<F>(
+    href: &str,
+    base_url: &Url,
+    ancestors: &[Url],
+    fetch: &F,
+) -> Result<(Stylesheet, Vec<ResourceError>), ResourceError>
+where
+    F: Fn(&Url) -> Result<String, String> + Sync,
+{
+    let url = base_url.join(href).map_err(|e| ResourceError {
+        url: href.to_string(),
+        message: format!("Failed to parse URL: {}", e),
+    })?;
+    if ancestors.contains(&url) {
+        return Err(ResourceError {
+            url: url.to_string(),
+            message: "@import cycle".to_string(),
+        });
+    }
+    let css = fetch(&url).map_err(|message| ResourceError {
+        url: url.to_string(),
+        message: format!("Failed to fetch stylesheet: {}", message),
+    })?;
+    let mut sheet = parser::parse_stylesheet(&css);
+    sheet.url = Some(url.clone());
+    let mut ancestors = ancestors.to_vec();
+    ancestors.push(url.clone());
+    let errors = load_imports(&mut sheet.rules, &url, &ancestors, fetch);
+    Ok((sheet, errors))
+}
+
+// Fetches the sheets of every @import rule concurrently, storing them in the rules
+fn load_imports<F>(
+    rules: &mut [Rule],
+    base_url: &Url,
+    ancestors: &[Url],
+    fetch: &F,
+) -> Vec<ResourceError>
+where
+    F: Fn(&Url) -> Result<String, String> + Sync,
+{
+    let imports: Vec<&mut ImportRule> = rules
+        .iter_mut()
+        .filter_map(|rule| match rule {
+            Rule::Import(import) => Some(import),
+            _ => None,
+        })
+        .collect();
+    if imports.is_empty() {
+        return Vec::new();
+    }
+    thread::scope(|scope| {
+        let handles: Vec<_> = imports
+            .into_iter()
+            .map(|import| {
+                scope.spawn(move || {
+                    match fetch_stylesheet(&import.url, base_url, ancestors, fetch) {
+                        Ok((sheet, errors)) => {
+                            import.sheet = Some(Box::new(sheet));
+                            errors
+                        }
+                        Err(error) => vec![error],
+                    }
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap_or_default())
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html;
+    use std::collections::HashMap;
+
+    fn load(html: &str, resources: &[(&str, &str)]) -> (Vec<Stylesheet>, Vec<ResourceError>) {
+        let resources: HashMap<String, String> = resources
+            .iter()
+            .map(|(url, css)| (url.to_string(), css.to_string()))
+            .collect();
+        let fetch = |url: &Url| {
+            resources
+                .get(url.as_str())
+                .cloned()
+                .ok_or_else(|| "HTTP error: 404 Not Found".to_string())
+        };
+        let document = html::parse_document(html);
+        let base_url = Url::parse("http://example.com/pages/index.html").unwrap();
+        load_stylesheets_with(&document, &base_url, &fetch)
+    }
+
+    fn imported(sheet: &Stylesheet, index: usize) -> &Stylesheet {
+        match &sheet.rules[index] {
+            Rule::Import(import) => import.sheet.as_deref().expect("Import not loaded"),
+            _ => panic!("Expected import rule"),
+        }
+    }
+
+    #[test]
+    fn test_links_and_styles_in_document_order() {
+        let (sheets, errors) = load(
+            "<html><head><link rel=stylesheet href=a.css><style>p {}</style>\
+             <link rel='alternate stylesheet' href=b.css>\
+             <link rel=stylesheet href=/c.css media=print></head></html>",
+            &[
+                ("http://example.com/pages/a.css", "a {} b {}"),
+                ("http://example.com/c.css", "c {}"),
+            ],
+        );
+        assert!(errors.is_empty());
+        assert_eq!(sheets.len(), 3);
+        assert_eq!(sheets[0].rules.len(), 2);
+        assert_eq!(sheets[1].rules.len(), 1);
+        assert!(sheets[1].media.queries.is_empty());
+        assert_eq!(sheets[2].media.queries.len(), 1);
+    }
+
+    #[test]
+    fn test_imports_resolve_against_the_sheet_url() {
+        let (sheets, errors) = load(
+            "<link rel=stylesheet href=../css/main.css>",
+            &[
+                (
+                    "http://example.com/css/main.css",
+                    "@import 'parts/a.css'; p {}",
+                ),
+                (
+                    "http://example.com/css/parts/a.css",
+                    "@import '../../b.css';",
+                ),
+                ("http://example.com/b.css", "b {}"),
+            ],
+        );
+        assert!(errors.is_empty());
+        let a = imported(&sheets[0], 0);
+        let b = imported(a, 0);
+        assert_eq!(b.rules.len(), 1);
+    }
+
+    #[test]
+    fn test_import_cycles_are_broken() {
+        let (sheets, errors) = load(
+            "<link rel=stylesheet href=/a.css>",
+            &[
+                ("http://example.com/a.css", "@import 'b.css'; a {}"),
+                ("http://example.com/b.css", "@import 'a.css'; b {}"),
+            ],
+        );
+        let b = imported(&sheets[0], 0);
+        assert!(matches!(&b.rules[0], Rule::Import(import) if import.sheet.is_none()));
+        assert_eq!(
+            errors,
+            vec![ResourceError {
+                url: "http://example.com/a.css".to_string(),
+                message: "@import cycle".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_failures_are_reported_per_resource() {
+        let (sheets, errors) = load(
+            "<link rel=stylesheet href=missing.css><style>@import 'gone.css';</style>\
+             <link rel=stylesheet href=ok.css>",
+            &[("http://example.com/pages/ok.css", "p {}")],
+        );
+        assert_eq!(sheets.len(), 2);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].url, "http://example.com/pages/missing.css");
+        assert_eq!(errors[1].url, "http://example.com/pages/gone.css");
+    }
+
+    #[test]
+    fn test_load_stylesheets_over_http() {
+        let mut server = mockito::Server::new();
+        let main = server
+            .mock("GET", "/main.css")
+            .with_body("@import 'print.css' print; p { color: red }")
+            .create();
+        let print = server
+            .mock("GET", "/print.css")
+            .with_body("p { color: black }")
+            .create();
+        let document = html::parse_document("<link rel=stylesheet href=main.css>");
+        let base_url = Url::parse(&format!("{}/index.html", server.url())).unwrap();
+
+        let (sheets, errors) = load_stylesheets(&document, &base_url);
+        main.assert();
+        print.assert();
+        assert!(errors.is_empty());
+        assert_eq!(imported(&sheets[0], 0).rules.len(), 1);
+    }
+}
@@ -0,0 +1,643 @@
+// Media queries, following the Media Queries Level 4 syntax including range syntax
+
+use crate::css::cascade::{Device, DEFAULT_FONT_SIZE};
+use crate::css::tokenizer::{self, Token};
+use crate::css::values::LengthContext;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaType {
+    Screen,
+    Print,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorScheme {
+    Light,
+    Dark,
+}
+
+/// A comma separated list of media queries. An empty list matches every device.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediaQueryList {
+    pub queries: Vec<MediaQuery>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaQuery {
+    pub negated: bool,
+    // None matches all media types, Some(None) is an unknown type that matches nothing
+    pub media_type: Option<Option<MediaType>>,
+    pub condition: Option<MediaCondition>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MediaCondition {
+    Not(Box<MediaCondition>),
+    And(Vec<MediaCondition>),
+    Or(Vec<MediaCondition>),
+    Feature(MediaFeature),
+    // Syntax we don't understand, which is unknown rather than true or false
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaFeature {
+    pub name: String,
+    // Empty for the boolean form such as `(color)`
+    pub constraints: Vec<(Comparison, MediaValue)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    // The comparison with its operands swapped, so `a < x` becomes `x > a`
+    fn flip(self) -> Self {
+        match self {
+            Comparison::Equal => Comparison::Equal,
+            Comparison::Less => Comparison::Greater,
+            Comparison::LessOrEqual => Comparison::GreaterOrEqual,
+            Comparison::Greater => Comparison::Less,
+            Comparison::GreaterOrEqual => Comparison::LessOrEqual,
+        }
+    }
+
+    fn compare(self, actual: f32, expected: f32) -> bool {
+        match self {
+            Comparison::Equal => (actual - expected).abs() < 1e-3,
+            Comparison::Less => actual < expected,
+            Comparison::LessOrEqual => actual <= expected,
+            Comparison::Greater => actual > expected,
+            Comparison::GreaterOrEqual => actual >= expected,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MediaValue {
+    Number(f32),
+    Dimension(f32, String),
+    Ratio(f32, f32),
+    Ident(String),
+}
+
+impl MediaQueryList {
+    pub fn parse(text: &str) -> Self {
+        parse_media_query_list(&tokenizer::tokenize(text))
+    }
+
+    pub fn matches(&self, device: &Device) -> bool {
+        self.queries.is_empty() || self.queries.iter().any(|query| query.matches(device))
+    }
+}
+
+impl MediaQuery {
+    // A query that never matches, used in place of queries that fail to parse
+    fn not_all() -> Self {
+        Self {
+            negated: true,
+            media_type: None,
+            condition: None,
+        }
+    }
+
+    pub fn matches(&self, device: &Device) -> bool {
+        let type_matches = match self.media_type {
+            None => true,
+            Some(media_type) => media_type == Some(device.media_type),
+        };
+        // A condition that's unknown stays unknown when negated, and only then matches
+        // nothing
+        let condition_matches = match &self.condition {
+            None => Some(true),
+            Some(condition) => condition.evaluate(device),
+        };
+        match type_matches {
+            true => condition_matches.is_some_and(|matches| matches != self.negated),
+            false => self.negated,
+        }
+    }
+}
+
+impl MediaCondition {
+    // Whether the condition matches, or None if it's unknown, as conditions use
+    // three-valued logic: unknown is neither true nor false under `not`, `and` and `or`
+    pub fn evaluate(&self, device: &Device) -> Option<bool> {
+        match self {
+            MediaCondition::Not(condition) => condition.evaluate(device).map(|matches| !matches),
+            MediaCondition::And(conditions) => {
+                let results: Vec<Option<bool>> =
+                    conditions.iter().map(|c| c.evaluate(device)).collect();
+                match results.contains(&Some(false)) {
+                    true => Some(false),
+                    false => results.iter().all(Option::is_some).then_some(true),
+                }
+            }
+            MediaCondition::Or(conditions) => {
+                let results: Vec<Option<bool>> =
+                    conditions.iter().map(|c| c.evaluate(device)).collect();
+                match results.contains(&Some(true)) {
+                    true => Some(true),
+                    false => results.iter().all(Option::is_some).then_some(false),
+                }
+            }
+            MediaCondition::Feature(feature) => feature.evaluate(device),
+            MediaCondition::Unknown => None,
+        }
+    }
+}
+
+impl MediaFeature {
+    // Whether the feature matches, or None for features we don't know
+    fn evaluate(&self, device: &Device) -> Option<bool> {
+        let context = LengthContext {
+            font_size: DEFAULT_FONT_SIZE,
+            root_font_size: DEFAULT_FONT_SIZE,
+            viewport_width: device.viewport_width,
+            viewport_height: device.viewport_height,
+        };
+        let matches = match self.name.as_str() {
+            "width" => compare_lengths(&self.constraints, device.viewport_width, &context),
+            "height" => compare_lengths(&self.constraints, device.viewport_height, &context),
+            "aspect-ratio" => {
+                let actual = device.viewport_width / device.viewport_height.max(1.0);
+                match self.constraints.as_slice() {
+                    [] => actual > 0.0,
+                    constraints => constraints.iter().all(|(comparison, value)| match value {
+                        MediaValue::Ratio(w, h) if *h > 0.0 => comparison.compare(actual, w / h),
+                        MediaValue::Number(ratio) => comparison.compare(actual, *ratio),
+                        _ => false,
+                    }),
+                }
+            }
+            "orientation" => {
+                let orientation = match device.viewport_height >= device.viewport_width {
+                    true => "portrait",
+                    false => "landscape",
+                };
+                self.matches_ident(|ident| ident == orientation, true)
+            }
+            "prefers-color-scheme" => {
+                let scheme = match device.color_scheme {
+                    ColorScheme::Light => "light",
+                    ColorScheme::Dark => "dark",
+                };
+                self.matches_ident(|ident| ident == scheme, true)
+            }
+            "prefers-reduced-motion" => {
+                let preference = match device.reduced_motion {
+                    true => "reduce",
+                    false => "no-preference",
+                };
+                self.matches_ident(|ident| ident == preference, device.reduced_motion)
+            }
+            "hover" | "any-hover" => self.matches_ident(|ident| ident == "hover", true),
+            "pointer" | "any-pointer" => self.matches_ident(|ident| ident == "fine", true),
+            "color" => match self.constraints.as_slice() {
+                [] => true,
+                constraints => constraints.iter().all(|(comparison, value)| match value {
+                    MediaValue::Number(bits) => comparison.compare(8.0, *bits),
+                    _ => false,
+                }),
+            },
+            "monochrome" => match self.constraints.as_slice() {
+                [] => false,
+                constraints => constraints.iter().all(|(comparison, value)| match value {
+                    MediaValue::Number(bits) => comparison.compare(0.0, *bits),
+                    _ => false,
+                }),
+            },
+            _ => return None,
+        };
+        Some(matches)
+    }
+
+    // Evaluates a discrete feature that takes a keyword. The boolean form matches `boolean`.
+    fn matches_ident(&self, predicate: impl Fn(&str) -> bool, boolean: bool) -> bool {
+        match self.constraints.as_slice() {
+            [] => boolean,
+            [(Comparison::Equal, MediaValue::Ident(ident))] => predicate(ident),
+            _ => false,
+        }
+    }
+}
+
+fn compare_lengths(
+    constraints: &[(Comparison, MediaValue)],
+    actual: f32,
+    context: &LengthContext,
+) -> bool {
+    if constraints.is_empty() {
+        return actual > 0.0;
+    }
+    constraints.iter().all(|(comparison, value)| {
+        let expected = match value {
+            MediaValue::Dimension(number, unit) => context.length_to_px(*number, unit),
+            MediaValue::Number(number) if *number == 0.0 => Some(0.0),
+            _ => None,
+        };
+        expected.is_some_and(|expected| comparison.compare(actual, expected))
+    })
+}
+
+// Parses a media query list such as `screen and (min-width: 600px), print`
+pub fn parse_media_query_list(tokens: &[Token]) -> MediaQueryList {
+    let tokens: Vec<Token> = tokens.to_vec();
+    if tokens.iter().all(Token::is_whitespace) {
+        return MediaQueryList::default();
+    }
+    let queries = split_top_level_commas(&tokens)
+        .into_iter()
+        .map(|part| parse_media_query(&significant(part)).unwrap_or_else(MediaQuery::not_all))
+        .collect();
+    MediaQueryList { queries }
+}
+
+fn split_top_level_commas(tokens: &[Token]) -> Vec<&[Token]> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        if token.opens_block() {
+            depth += 1;
+        } else if token.closes_block() {
+            depth -= 1;
+        } else if *token == Token::Comma && depth == 0 {
+            parts.push(&tokens[start..i]);
+            start = i + 1;
+        }
+    }
+    parts.push(&tokens[start..]);
+    parts
+}
+
+// Drops whitespace outside of blocks, which is never significant in media queries
+fn significant(tokens: &[Token]) -> Vec<Token> {
+    tokens
+        .iter()
+        .filter(|t| !t.is_whitespace())
+        .cloned()
+        .collect()
+}
+
+fn ident_is(token: Option<&Token>, word: &str) -> bool {
+    matches!(token, Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case(word))
+}
+
+fn parse_media_query(tokens: &[Token]) -> Option<MediaQuery> {
+    // A query that starts with a parenthesised condition, or `not (`
+    if tokens.first() == Some(&Token::OpenParen)
+        || (ident_is(tokens.first(), "not") && tokens.get(1) == Some(&Token::OpenParen))
+    {
+        let mut pos = 0;
+        let condition = parse_condition(tokens, &mut pos, true)?;
+        if pos != tokens.len() {
+            return None;
+        }
+        return Some(MediaQuery {
+            negated: false,
+            media_type: None,
+            condition: Some(condition),
+        });
+    }
+
+    let mut pos = 0;
+    let mut negated = false;
+    if ident_is(tokens.first(), "not") {
+        negated = true;
+        pos += 1;
+    } else if ident_is(tokens.first(), "only") {
+        pos += 1;
+    }
+    let Some(Token::Ident(media_type)) = tokens.get(pos) else {
+        return None;
+    };
+    let media_type = match media_type.to_ascii_lowercase().as_str() {
+        "all" => None,
+        "screen" => Some(Some(MediaType::Screen)),
+        "print" => Some(Some(MediaType::Print)),
+        "and" | "or" | "not" | "only" | "layer" => return None,
+        _ => Some(None),
+    };
+    pos += 1;
+    let mut condition = None;
+    if pos < tokens.len() {
+        if !ident_is(tokens.get(pos), "and") {
+            return None;
+        }
+        pos += 1;
+        // `or` is not allowed after a media type
+        condition = Some(parse_condition(tokens, &mut pos, false)?);
+        if pos != tokens.len() {
+            return None;
+        }
+    }
+    Some(MediaQuery {
+        negated,
+        media_type,
+        condition,
+    })
+}
+
+fn parse_condition(tokens: &[Token], pos: &mut usize, allow_or: bool) -> Option<MediaCondition> {
+    if ident_is(tokens.get(*pos), "not") {
+        *pos += 1;
+        let condition = parse_in_parens(tokens, pos)?;
+        return Some(MediaCondition::Not(Box::new(condition)));
+    }
+    let first = parse_in_parens(tokens, pos)?;
+    let mut conditions = vec![first];
+    let mut operator: Option<&str> = None;
+    while let Some(Token::Ident(word)) = tokens.get(*pos) {
+        let word = match word.to_ascii_lowercase().as_str() {
+            "and" => "and",
+            "or" if allow_or => "or",
+            _ => return None,
+        };
+        // `and` and `or` can't be mixed without parentheses
+        if operator.is_some_and(|operator| operator != word) {
+            return None;
+        }
+        operator = Some(word);
+        *pos += 1;
+        conditions.push(parse_in_parens(tokens, pos)?);
+    }
+    Some(match operator {
+        None => conditions.remove(0),
+        Some("and") => MediaCondition::And(conditions),
+        Some(_) => MediaCondition::Or(conditions),
+    })
+}
+
+fn parse_in_parens(tokens: &[Token], pos: &mut usize) -> Option<MediaCondition> {
+    match tokens.get(*pos)? {
+        Token::OpenParen => {}
+        // General enclosed functions parse but are unknown
+        Token::Function(_) => {
+            *pos = find_close(tokens, *pos)? + 1;
+            return Some(MediaCondition::Unknown);
+        }
+        _ => return None,
+    }
+    let close = find_close(tokens, *pos)?;
+    let inner = &tokens[*pos + 1..close];
+    *pos = close + 1;
+
+    // A nested condition
+    if inner.first() == Some(&Token::OpenParen) || ident_is(inner.first(), "not") {
+        let mut inner_pos = 0;
+        return match parse_condition(inner, &mut inner_pos, true) {
+            Some(condition) if inner_pos == inner.len() => Some(condition),
+            _ => Some(MediaCondition::Unknown),
+        };
+    }
+    Some(
+        parse_feature(inner)
+            .map(MediaCondition::Feature)
+            .unwrap_or(MediaCondition::Unknown),
+    )
+}
+
+fn find_close(tokens: &[Token], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        if token.opens_block() {
+            depth += 1;
+        } else if token.closes_block() {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        }
+    }
+    None
+}
+
+fn parse_feature(tokens: &[Token]) -> Option<MediaFeature> {
+    // Boolean form: `(color)`
+    if let [Token::Ident(name)] = tokens {
+        return Some(MediaFeature {
+            name: name.to_ascii_lowercase(),
+            constraints: Vec::new(),
+        });
+    }
+    // Plain form: `(name: value)`, with min- and max- prefixes mapping to ranges
+    if let [Token::Ident(name), Token::Colon, value @ ..] = tokens {
+        let value = parse_media_value(value)?;
+        let name = name.to_ascii_lowercase();
+        let (name, comparison) = if let Some(name) = name.strip_prefix("min-") {
+            (name.to_string(), Comparison::GreaterOrEqual)
+        } else if let Some(name) = name.strip_prefix("max-") {
+            (name.to_string(), Comparison::LessOrEqual)
+        } else {
+            (name, Comparison::Equal)
+        };
+        return Some(MediaFeature {
+            name,
+            constraints: vec![(comparison, value)],
+        });
+    }
+    parse_range(tokens)
+}
+
+// Parses the range forms `name op value`, `value op name` and `value op name op value`
+fn parse_range(tokens: &[Token]) -> Option<MediaFeature> {
+    // Split the tokens around comparison operators
+    let mut parts: Vec<&[Token]> = Vec::new();
+    let mut operators = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < tokens.len() {
+        let comparison = match &tokens[i] {
+            Token::Delim('<') => Some(Comparison::Less),
+            Token::Delim('>') => Some(Comparison::Greater),
+            Token::Delim('=') => Some(Comparison::Equal),
+            _ => None,
+        };
+        let Some(mut comparison) = comparison else {
+            i += 1;
+            continue;
+        };
+        parts.push(&tokens[start..i]);
+        if comparison != Comparison::Equal && tokens.get(i + 1) == Some(&Token::Delim('=')) {
+            comparison = match comparison {
+                Comparison::Less => Comparison::LessOrEqual,
+                _ => Comparison::GreaterOrEqual,
+            };
+            i += 1;
+        }
+        operators.push(comparison);
+        i += 1;
+        start = i;
+    }
+    parts.push(&tokens[start..]);
+
+    let feature_name = |part: &[Token]| match part {
+        [Token::Ident(name)] => Some(name.to_ascii_lowercase()),
+        _ => None,
+    };
+    match (parts.as_slice(), operators.as_slice()) {
+        ([left, right], [operator]) => {
+            if let Some(name) = feature_name(left) {
+                let value = parse_media_value(right)?;
+                Some(MediaFeature {
+                    name,
+                    constraints: vec![(*operator, value)],
+                })
+            } else {
+                let name = feature_name(right)?;
+                let value = parse_media_value(left)?;
+                Some(MediaFeature {
+                    name,
+                    constraints: vec![(operator.flip(), value)],
+                })
+            }
+        }
+        ([low, name, high], [first, second]) => {
+            // Both operators must point the same way, as in `400px < width <= 800px`
+            let ascending =
+                |c: &Comparison| matches!(c, Comparison::Less | Comparison::LessOrEqual);
+            let descending =
+                |c: &Comparison| matches!(c, Comparison::Greater | Comparison::GreaterOrEqual);
+            if !(ascending(first) && ascending(second) || descending(first) && descending(second)) {
+                return None;
+            }
+            Some(MediaFeature {
+                name: feature_name(name)?,
+                constraints: vec![
+                    (first.flip(), parse_media_value(low)?),
+                    (*second, parse_media_value(high)?),
+                ],
+            })
+        }
+        _ => None,
+    }
+}
+
+fn parse_media_value(tokens: &[Token]) -> Option<MediaValue> {
+    match tokens {
+        [Token::Number(number)] => Some(MediaValue::Number(*number)),
+        [Token::Dimension(number, unit)] => {
+            Some(MediaValue::Dimension(*number, unit.to_ascii_lowercase()))
+        }
+        [Token::Ident(ident)] => Some(MediaValue::Ident(ident.to_ascii_lowercase())),
+        [Token::Number(width), Token::Delim('/'), Token::Number(height)] => {
+            Some(MediaValue::Ratio(*width, *height))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(width: f32, height: f32) -> Device {
+        Device {
+            viewport_width: width,
+            viewport_height: height,
+            ..Default::default()
+        }
+    }
+
+    fn matches(query: &str, device: &Device) -> bool {
+        MediaQueryList::parse(query).matches(device)
+    }
+
+    #[test]
+    fn test_media_types() {
+        let screen = device(800.0, 600.0);
+        let print = Device {
+            media_type: MediaType::Print,
+            ..screen.clone()
+        };
+        assert!(matches("", &screen));
+        assert!(matches("all", &screen));
+        assert!(matches("screen", &screen));
+        assert!(!matches("print", &screen));
+        assert!(matches("print", &print));
+        assert!(matches("not print", &screen));
+        assert!(matches("only screen", &screen));
+        assert!(!matches("tv", &screen));
+        assert!(matches("tv, screen", &screen));
+    }
+
+    #[test]
+    fn test_legacy_width_ranges() {
+        let device = device(800.0, 600.0);
+        assert!(matches("(min-width: 600px)", &device));
+        assert!(!matches("(min-width: 801px)", &device));
+        assert!(matches("screen and (max-width: 50em)", &device));
+        assert!(!matches("screen and (max-width: 40em)", &device));
+        assert!(matches("(width: 800px)", &device));
+        assert!(matches(
+            "(min-height: 500px) and (max-height: 700px)",
+            &device
+        ));
+    }
+
+    #[test]
+    fn test_range_syntax() {
+        let device = device(800.0, 600.0);
+        assert!(matches("(width >= 600px)", &device));
+        assert!(!matches("(width > 800px)", &device));
+        assert!(matches("(width <= 800px)", &device));
+        assert!(matches("(600px < width)", &device));
+        assert!(matches("(400px < width <= 800px)", &device));
+        assert!(!matches("(400px < width < 800px)", &device));
+        assert!(matches("(1000px > width > 700px)", &device));
+        assert!(!matches("(400px < width > 800px)", &device));
+        assert!(matches("(aspect-ratio > 1/1)", &device));
+    }
+
+    #[test]
+    fn test_orientation_and_preferences() {
+        let landscape = device(800.0, 600.0);
+        let portrait = Device {
+            color_scheme: ColorScheme::Dark,
+            reduced_motion: true,
+            ..device(400.0, 900.0)
+        };
+        assert!(matches("(orientation: landscape)", &landscape));
+        assert!(matches("(orientation: portrait)", &portrait));
+        assert!(matches("(prefers-color-scheme: light)", &landscape));
+        assert!(matches("(prefers-color-scheme: dark)", &portrait));
+        assert!(!matches("(prefers-reduced-motion)", &landscape));
+        assert!(matches(
+            "(prefers-reduced-motion: no-preference)",
+            &landscape
+        ));
+        assert!(matches("(prefers-reduced-motion: reduce)", &portrait));
+    }
+
+    #[test]
+    fn test_conditions() {
+        let device = device(800.0, 600.0);
+        assert!(matches("not (max-width: 600px)", &device));
+        assert!(matches(
+            "(max-width: 600px) or (orientation: landscape)",
+            &device
+        ));
+        assert!(matches(
+            "((max-width: 600px) or (min-width: 700px)) and (color)",
+            &device
+        ));
+        // Mixing and/or without parentheses is invalid, and invalid queries match nothing
+        assert!(!matches("(color) and (hover) or (width)", &device));
+        assert!(!matches("(unknown-feature)", &device));
+        // Unknown stays unknown under `not`, and decides `and` and `or` only when the rest
+        // can't
+        assert!(!matches("not (unknown-feature)", &device));
+        assert!(!matches("not ((unknown-feature) and (color))", &device));
+        assert!(matches("not ((unknown-feature) and (monochrome))", &device));
+        assert!(matches("(unknown-feature) or (color)", &device));
+        assert!(!matches("not screen and (unknown-feature)", &device));
+        assert!(!matches("screen and", &device));
+    }
+}
@@ -0,0 +1,33 @@
+// CSS parsing and the style engine
+
+mod cascade;
+mod font_face;
+mod hints;
+mod invalidation;
+mod loader;
+mod media;
+mod page;
+mod parser;
+mod properties;
+mod selector;
+mod tokenizer;
+mod values;
+mod variables;
+
+pub use cascade::{
+    author_stylesheets, user_agent_stylesheet, ComputedStyle, Device, Origin, StyleTree,
+    StyledNode, Stylist, DARK_CANVAS, DEFAULT_FONT_SIZE,
+};
+pub use font_face::{FontFaceRule, FontSource};
+pub use invalidation::Damage;
+pub use loader::{load_stylesheets, ResourceError};
+pub use media::{ColorScheme, MediaQueryList, MediaType};
+pub use page::{PageRule, PageStyle};
+pub use parser::{
+    parse_declarations, parse_stylesheet, Declaration, ImportRule, MediaRule, Rule, StyleRule,
+    Stylesheet,
+};
+pub use selector::{ElementRef, PseudoElement, Selector};
+pub use tokenizer::{tokenize, Token};
+pub use values::{Color, Value};
+pub use variables::CustomProperties;
@@ -0,0 +1,196 @@
+// The `@page` rule: the size and margins of printed pages
+
+use crate::css::parser::Declaration;
+use crate::css::tokenizer::Token;
+use crate::css::values::LengthContext;
+
+// Paper sizes in millimetres, portrait
+const PAGE_SIZES: [(&str, f32, f32); 8] = [
+    ("a5", 148.0, 210.0),
+    ("a4", 210.0, 297.0),
+    ("a3", 297.0, 420.0),
+    ("b5", 176.0, 250.0),
+    ("b4", 250.0, 353.0),
+    ("letter", 215.9, 279.4),
+    ("legal", 215.9, 355.6),
+    ("ledger", 279.4, 431.8),
+];
+const MM: f32 = 96.0 / 25.4;
+
+/// An `@page` rule without a page selector. Descriptors are interpreted on demand.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageRule {
+    pub declarations: Vec<Declaration>,
+}
+
+/// The size of printed pages and their margins, top, right, bottom and left, in CSS
+/// pixels. Pages are A4 with 1cm margins unless the document says otherwise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageStyle {
+    pub width: f32,
+    pub height: f32,
+    pub margins: [f32; 4],
+}
+
+impl Default for PageStyle {
+    fn default() -> Self {
+        Self {
+            width: 210.0 * MM,
+            height: 297.0 * MM,
+            margins: [10.0 * MM; 4],
+        }
+    }
+}
+
+impl PageStyle {
+    // The area inside the margins that content is laid out in
+    pub fn content_width(&self) -> f32 {
+        (self.width - self.margins[1] - self.margins[3]).max(1.0)
+    }
+
+    pub fn content_height(&self) -> f32 {
+        (self.height - self.margins[0] - self.margins[2]).max(1.0)
+    }
+}
+
+impl PageRule {
+    // Applies the rule's descriptors over a page style, as later rules override earlier
+    // ones. Invalid descriptors are ignored.
+    pub fn apply(&self, style: &mut PageStyle) {
+        for declaration in &self.declarations {
+            let tokens: Vec<&Token> = declaration
+                .value
+                .iter()
+                .filter(|token| !token.is_whitespace())
+                .collect();
+            match declaration.name.as_str() {
+                "size" => {
+                    if let Some((width, height)) = page_size(&tokens) {
+                        style.width = width;
+                        style.height = height;
+                    }
+                }
+                "margin" => {
+                    let lengths: Option<Vec<f32>> = tokens
+                        .iter()
+                        .enumerate()
+                        .map(|(index, token)| {
+                            // Percentages of the width for the sides, the height otherwise
+                            let basis = match (tokens.len(), index) {
+                                (2 | 3, 1) | (4, 1 | 3) => style.width,
+                                (1, _) => style.width,
+                                _ => style.height,
+                            };
+                            length(token, basis)
+                        })
+                        .collect();
+                    let margins = match lengths.as_deref() {
+                        Some(&[all]) => [all; 4],
+                        Some(&[vertical, horizontal]) => {
+                            [vertical, horizontal, vertical, horizontal]
+                        }
+                        Some(&[top, horizontal, bottom]) => [top, horizontal, bottom, horizontal],
+                        Some(&[top, right, bottom, left]) => [top, right, bottom, left],
+                        _ => continue,
+                    };
+                    style.margins = margins;
+                }
+                side @ ("margin-top" | "margin-right" | "margin-bottom" | "margin-left") => {
+                    let (index, basis) = match side {
+                        "margin-top" => (0, style.height),
+                        "margin-right" => (1, style.width),
+                        "margin-bottom" => (2, style.height),
+                        _ => (3, style.width),
+                    };
+                    if let [token] = tokens.as_slice() {
+                        if let Some(margin) = length(token, basis) {
+                            style.margins[index] = margin;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// A length in absolute units, or a percentage of `basis`
+fn length(token: &Token, basis: f32) -> Option<f32> {
+    let context = LengthContext {
+        font_size: 16.0,
+        root_font_size: 16.0,
+        viewport_width: basis,
+        viewport_height: basis,
+    };
+    match token {
+        Token::Dimension(number, unit) => context.length_to_px(*number, unit),
+        Token::Percentage(percentage) => Some(basis * percentage / 100.0),
+        Token::Number(number) if *number == 0.0 => Some(0.0),
+        _ => None,
+    }
+}
+
+// `auto`, one or two lengths, or a paper size and an orientation in either order
+fn page_size(tokens: &[&Token]) -> Option<(f32, f32)> {
+    let default = PageStyle::default();
+    let mut size = None;
+    let mut landscape = None;
+    let mut lengths = Vec::new();
+    for token in tokens {
+        match token {
+            Token::Ident(name) => match name.to_ascii_lowercase().as_str() {
+                "auto" if tokens.len() == 1 => return Some((default.width, default.height)),
+                "portrait" if landscape.is_none() => landscape = Some(false),
+                "landscape" if landscape.is_none() => landscape = Some(true),
+                name => {
+                    let (_, width, height) = PAGE_SIZES.iter().find(|(size, ..)| *size == name)?;
+                    size = Some((width * MM, height * MM));
+                }
+            },
+            token => lengths.push(length(token, 0.0).filter(|length| *length > 0.0)?),
+        }
+    }
+    match (lengths.as_slice(), size, landscape) {
+        (&[side], None, None) => Some((side, side)),
+        (&[width, height], None, None) => Some((width, height)),
+        ([], size, landscape) => {
+            let (width, height) = size.unwrap_or((default.width, default.height));
+            match landscape {
+                Some(true) => Some((width.max(height), width.min(height))),
+                _ => Some((width.min(height), width.max(height))),
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::parser::declarations_from_tokens;
+    use crate::css::tokenizer::tokenize;
+
+    fn page(css: &str) -> PageStyle {
+        let rule = PageRule {
+            declarations: declarations_from_tokens(&tokenize(css)),
+        };
+        let mut style = PageStyle::default();
+        rule.apply(&mut style);
+        style
+    }
+
+    #[test]
+    fn test_page_rule() {
+        let letter = page("size: letter landscape; margin: 0.5in 1in");
+        assert_eq!((letter.width, letter.height), (1056.0, 816.0));
+        assert_eq!(letter.margins, [48.0, 96.0, 48.0, 96.0]);
+        assert_eq!(letter.content_width(), 864.0);
+
+        let custom = page("size: 400px 300px; margin: 10%; margin-left: 0");
+        assert_eq!((custom.width, custom.height), (400.0, 300.0));
+        assert_eq!(custom.margins, [40.0, 40.0, 40.0, 0.0]);
+
+        // Invalid descriptors leave the defaults
+        assert_eq!(page("size: huge; margin: red"), PageStyle::default());
+    }
+}
@@ -0,0 +1,441 @@
+// Parses CSS text into stylesheets made of rules and declarations
+
+use crate::css::font_face::FontFaceRule;
+use crate::css::media::{self, MediaQueryList};
+use crate::css::page::PageRule;
+use crate::css::properties;
+use crate::css::selector::{self, Selector};
+use crate::css::tokenizer::{self, Token};
+use crate::css::variables;
+use url::Url;
+
+/// A parsed stylesheet
+#[derive(Debug, Clone, Default)]
+pub struct Stylesheet {
+    pub rules: Vec<Rule>,
+    // The media the whole sheet applies to, from the `media` attribute of its element
+    pub media: MediaQueryList,
+    // The URL the sheet was fetched from, None for sheets embedded in the document
+    pub url: Option<Url>,
+}
+
+/// A top level or nested rule in a stylesheet
+#[derive(Debug, Clone)]
+pub enum Rule {
+    Style(StyleRule),
+    Media(MediaRule),
+    Import(ImportRule),
+    FontFace(FontFaceRule),
+    Page(PageRule),
+}
+
+/// An `@import` rule. The imported sheet is filled in once it has been fetched.
+#[derive(Debug, Clone)]
+pub struct ImportRule {
+    pub url: String,
+    pub media: MediaQueryList,
+    pub sheet: Option<Box<Stylesheet>>,
+}
+
+/// An `@media` rule and the rules it contains
+#[derive(Debug, Clone)]
+pub struct MediaRule {
+    pub queries: MediaQueryList,
+    pub rules: Vec<Rule>,
+}
+
+/// A qualified rule such as `p, .note { color: red }`
+#[derive(Debug, Clone)]
+pub struct StyleRule {
+    pub selectors: Vec<Selector>,
+    pub declarations: Vec<Declaration>,
+}
+
+/// A single `name: value` declaration. The value is kept as tokens so it can be
+/// substituted and interpreted once the computed style is known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declaration {
+    pub name: String,
+    pub value: Vec<Token>,
+    pub important: bool,
+}
+
+impl Declaration {
+    pub fn is_custom_property(&self) -> bool {
+        self.name.starts_with("--")
+    }
+}
+
+// Parses a full stylesheet. Invalid rules are dropped rather than failing the sheet.
+pub fn parse_stylesheet(css: &str) -> Stylesheet {
+    let tokens = tokenizer::tokenize(css);
+    let mut parser = Parser::new(&tokens);
+    Stylesheet {
+        rules: parser.consume_rule_list(true),
+        media: MediaQueryList::default(),
+        url: None,
+    }
+}
+
+// Parses a list of declarations, as found in a `style` attribute
+pub fn parse_declarations(css: &str) -> Vec<Declaration> {
+    let tokens = tokenizer::tokenize(css);
+    style_declarations(&tokens)
+}
+
+pub(crate) struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub(crate) fn new(tokens: &'a [Token]) -> Self {
+        Self { tokens, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    pub(crate) fn at_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(Token::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    // Consumes a token, and the rest of its block if it opens one, into `out`
+    fn consume_component_value(&mut self, out: &mut Vec<Token>) {
+        let Some(token) = self.next() else {
+            return;
+        };
+        out.push(token.clone());
+        if !token.opens_block() {
+            return;
+        }
+        let mut depth = 1;
+        while let Some(token) = self.next() {
+            out.push(token.clone());
+            if token.opens_block() {
+                depth += 1;
+            } else if token.closes_block() {
+                depth -= 1;
+                if depth == 0 {
+                    return;
+                }
+            }
+        }
+    }
+
+    // Consumes the contents of a `{}` block, assuming the opening brace was consumed
+    fn consume_block_contents(&mut self) -> Vec<Token> {
+        let mut contents = Vec::new();
+        while let Some(token) = self.peek() {
+            if *token == Token::CloseCurly {
+                self.pos += 1;
+                break;
+            }
+            self.consume_component_value(&mut contents);
+        }
+        contents
+    }
+
+    pub(crate) fn consume_rule_list(&mut self, top_level: bool) -> Vec<Rule> {
+        let mut rules: Vec<Rule> = Vec::new();
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Whitespace => self.pos += 1,
+                Token::Cdo | Token::Cdc if top_level => self.pos += 1,
+                Token::AtKeyword(_) => {
+                    let imports_allowed =
+                        top_level && rules.iter().all(|r| matches!(r, Rule::Import(_)));
+                    match self.consume_at_rule() {
+                        // @import is only valid at the top level, before any other rule
+                        Some(Rule::Import(_)) if !imports_allowed => {}
+                        Some(rule) => rules.push(rule),
+                        None => {}
+                    }
+                }
+                _ => {
+                    if let Some(rule) = self.consume_qualified_rule() {
+                        rules.push(rule);
+                    }
+                }
+            }
+        }
+        rules
+    }
+
+    fn consume_at_rule(&mut self) -> Option<Rule> {
+        let Some(Token::AtKeyword(name)) = self.next() else {
+            return None;
+        };
+        let mut prelude = Vec::new();
+        let mut block = None;
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Semicolon => {
+                    self.pos += 1;
+                    break;
+                }
+                Token::OpenCurly => {
+                    self.pos += 1;
+                    block = Some(self.consume_block_contents());
+                    break;
+                }
+                _ => self.consume_component_value(&mut prelude),
+            }
+        }
+        // Unsupported at-rules are consumed and dropped
+        match name.to_ascii_lowercase().as_str() {
+            "media" => Some(Rule::Media(MediaRule {
+                queries: media::parse_media_query_list(&prelude),
+                rules: Parser::new(&block?).consume_rule_list(false),
+            })),
+            "font-face" => Some(Rule::FontFace(FontFaceRule {
+                declarations: declarations_from_tokens(&block?),
+            })),
+            // Rules for named pages and page selectors such as `:first` aren't supported
+            "page" if prelude.iter().all(Token::is_whitespace) => Some(Rule::Page(PageRule {
+                declarations: declarations_from_tokens(&block?),
+            })),
+            "import" if block.is_none() => parse_import_prelude(&prelude).map(Rule::Import),
+            _ => None,
+        }
+    }
+
+    fn consume_qualified_rule(&mut self) -> Option<Rule> {
+        let mut prelude = Vec::new();
+        while let Some(token) = self.peek() {
+            if *token == Token::OpenCurly {
+                self.pos += 1;
+                let block = self.consume_block_contents();
+                let selectors = selector::parse_selector_list(&prelude)?;
+                return Some(Rule::Style(StyleRule {
+                    selectors,
+                    declarations: style_declarations(&block),
+                }));
+            }
+            self.consume_component_value(&mut prelude);
+        }
+        // EOF before the block, the rule is invalid
+        None
+    }
+}
+
+// Parses `url("a.css") screen` or `"a.css" screen` into an import rule
+fn parse_import_prelude(prelude: &[Token]) -> Option<ImportRule> {
+    let mut tokens = prelude.iter().skip_while(|t| t.is_whitespace());
+    let url = match tokens.next()? {
+        Token::String(url) | Token::Url(url) => url.clone(),
+        Token::Function(name) if name.eq_ignore_ascii_case("url") => {
+            let url = match tokens.find(|t| !t.is_whitespace())? {
+                Token::String(url) => url.clone(),
+                _ => return None,
+            };
+            match tokens.find(|t| !t.is_whitespace())? {
+                Token::CloseParen => url,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    let media: Vec<Token> = tokens.cloned().collect();
+    Some(ImportRule {
+        url,
+        media: media::parse_media_query_list(&media),
+        sheet: None,
+    })
+}
+
+// Parses the tokens inside a declaration block
+pub(crate) fn declarations_from_tokens(tokens: &[Token]) -> Vec<Declaration> {
+    let mut parser = Parser::new(tokens);
+    let mut declarations = Vec::new();
+    while !parser.at_end() {
+        parser.skip_whitespace();
+        let mut item = Vec::new();
+        while let Some(token) = parser.peek() {
+            if *token == Token::Semicolon {
+                parser.pos += 1;
+                break;
+            }
+            parser.consume_component_value(&mut item);
+        }
+        if let Some(declaration) = parse_declaration(&item) {
+            declarations.push(declaration);
+        }
+    }
+    declarations
+}
+
+// Parses the declarations of a style rule or `style` attribute. Values their property
+// doesn't accept are dropped so earlier declarations still apply, except those using var()
+// which can only be checked once substituted.
+fn style_declarations(tokens: &[Token]) -> Vec<Declaration> {
+    let mut declarations = declarations_from_tokens(tokens);
+    declarations.retain(|declaration| {
+        declaration.is_custom_property()
+            || variables::contains_var(&declaration.value)
+            || properties::is_valid(&declaration.name, &declaration.value)
+    });
+    declarations
+}
+
+fn parse_declaration(tokens: &[Token]) -> Option<Declaration> {
+    let mut iter = tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| !t.is_whitespace());
+    let (_, Token::Ident(name)) = iter.next()? else {
+        return None;
+    };
+    let (colon_index, Token::Colon) = iter.next()? else {
+        return None;
+    };
+    let mut value = tokens[colon_index + 1..].to_vec();
+    trim_whitespace(&mut value);
+
+    // Check for a trailing !important
+    let mut important = false;
+    let significant: Vec<usize> = (0..value.len())
+        .filter(|i| !value[*i].is_whitespace())
+        .collect();
+    if significant.len() >= 2 {
+        let bang = significant[significant.len() - 2];
+        let last = significant[significant.len() - 1];
+        if value[bang] == Token::Delim('!')
+            && matches!(&value[last], Token::Ident(word) if word.eq_ignore_ascii_case("important"))
+        {
+            important = true;
+            value.truncate(bang);
+            trim_whitespace(&mut value);
+        }
+    }
+
+    // Custom property names are case sensitive, everything else is not
+    let name = match name.starts_with("--") {
+        true => name.clone(),
+        false => name.to_ascii_lowercase(),
+    };
+    // Only custom properties may have an empty value
+    if value.is_empty() && !name.starts_with("--") {
+        return None;
+    }
+    Some(Declaration {
+        name,
+        value,
+        important,
+    })
+}
+
+pub(crate) fn trim_whitespace(tokens: &mut Vec<Token>) {
+    while tokens.last().is_some_and(Token::is_whitespace) {
+        tokens.pop();
+    }
+    let leading = tokens.iter().take_while(|t| t.is_whitespace()).count();
+    tokens.drain(..leading);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stylesheet() {
+        let sheet = parse_stylesheet("p, .note { color: red; margin: 0 auto }  div{}");
+        assert_eq!(sheet.rules.len(), 2);
+        let Rule::Style(rule) = &sheet.rules[0] else {
+            panic!("Expected style rule");
+        };
+        assert_eq!(rule.selectors.len(), 2);
+        assert_eq!(rule.declarations.len(), 2);
+        assert_eq!(rule.declarations[0].name, "color");
+        assert_eq!(tokenizer::serialize(&rule.declarations[1].value), "0 auto");
+    }
+
+    #[test]
+    fn test_parse_important() {
+        let declarations = parse_declarations("color: red !important; Width : 10px");
+        assert_eq!(declarations.len(), 2);
+        assert!(declarations[0].important);
+        assert_eq!(declarations[0].value, vec![Token::Ident("red".to_string())]);
+        assert_eq!(declarations[1].name, "width");
+        assert!(!declarations[1].important);
+    }
+
+    #[test]
+    fn test_parse_custom_properties() {
+        let declarations = parse_declarations("--Brand-Color: { a: b }; --empty:;");
+        assert_eq!(declarations.len(), 2);
+        assert_eq!(declarations[0].name, "--Brand-Color");
+        assert!(declarations[0].is_custom_property());
+        assert_eq!(tokenizer::serialize(&declarations[0].value), "{ a: b }");
+        assert!(declarations[1].value.is_empty());
+    }
+
+    #[test]
+    fn test_parse_media_rule() {
+        let sheet = parse_stylesheet(
+            "@media screen and (min-width: 600px) { p { color: red } @media print { a {} } } b {}",
+        );
+        assert_eq!(sheet.rules.len(), 2);
+        let Rule::Media(media_rule) = &sheet.rules[0] else {
+            panic!("Expected media rule");
+        };
+        assert_eq!(media_rule.queries.queries.len(), 1);
+        assert_eq!(media_rule.rules.len(), 2);
+        assert!(matches!(media_rule.rules[1], Rule::Media(_)));
+    }
+
+    #[test]
+    fn test_parse_font_face_rule() {
+        let sheet = parse_stylesheet("@font-face { font-family: Demo; src: url(demo.ttf) }");
+        let Rule::FontFace(rule) = &sheet.rules[0] else {
+            panic!("Expected font face rule");
+        };
+        assert_eq!(rule.family().as_deref(), Some("Demo"));
+        assert_eq!(rule.sources().len(), 1);
+    }
+
+    #[test]
+    fn test_parse_import_rule() {
+        let sheet = parse_stylesheet(
+            "@import url(\"a.css\"); @import 'b.css' print; @import url(c.css) screen, print;\
+             p {} @import 'late.css';",
+        );
+        assert_eq!(sheet.rules.len(), 4);
+        let imports: Vec<&ImportRule> = sheet
+            .rules
+            .iter()
+            .filter_map(|rule| match rule {
+                Rule::Import(rule) => Some(rule),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(imports.len(), 3);
+        assert_eq!(imports[0].url, "a.css");
+        assert!(imports[0].media.queries.is_empty());
+        assert_eq!(imports[1].url, "b.css");
+        assert_eq!(imports[1].media.queries.len(), 1);
+        assert_eq!(imports[2].url, "c.css");
+        assert_eq!(imports[2].media.queries.len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_rules_are_dropped() {
+        let sheet = parse_stylesheet(
+            "@unknown foo { p { color: red } } ]] { color: blue } a { color: green }",
+        );
+        assert_eq!(sheet.rules.len(), 1);
+    }
+}
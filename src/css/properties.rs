@@ -0,0 +1,955 @@
+// Known CSS properties, their initial values and shorthand expansion
+
+use crate::css::tokenizer::Token;
+use crate::css::values::{self, Color, LengthContext, Value};
+
+pub struct PropertyInfo {
+    pub name: &'static str,
+    pub inherited: bool,
+    pub initial: &'static str,
+}
+
+const fn property(name: &'static str, inherited: bool, initial: &'static str) -> PropertyInfo {
+    PropertyInfo {
+        name,
+        inherited,
+        initial,
+    }
+}
+
+pub const PROPERTIES: &[PropertyInfo] = &[
+    // Inherited properties
+    property("color", true, "black"),
+    property("font-family", true, "sans-serif"),
+    property("font-size", true, "medium"),
+    property("font-style", true, "normal"),
+    property("font-weight", true, "normal"),
+    property("line-height", true, "normal"),
+    property("text-align", true, "start"),
+    property("text-indent", true, "0"),
+    property("text-transform", true, "none"),
+    property("white-space", true, "normal"),
+    property("letter-spacing", true, "normal"),
+    property("word-spacing", true, "normal"),
+    property("visibility", true, "visible"),
+    property("cursor", true, "auto"),
+    property("direction", true, "ltr"),
+    property("list-style-type", true, "disc"),
+    property("list-style-position", true, "outside"),
+    property("list-style-image", true, "none"),
+    property("color-scheme", true, "normal"),
+    // Box model
+    property("display", false, "inline"),
+    property("box-sizing", false, "content-box"),
+    property("width", false, "auto"),
+    property("height", false, "auto"),
+    property("min-width", false, "auto"),
+    property("min-height", false, "auto"),
+    property("max-width", false, "none"),
+    property("max-height", false, "none"),
+    property("margin-top", false, "0"),
+    property("margin-right", false, "0"),
+    property("margin-bottom", false, "0"),
+    property("margin-left", false, "0"),
+    property("padding-top", false, "0"),
+    property("padding-right", false, "0"),
+    property("padding-bottom", false, "0"),
+    property("padding-left", false, "0"),
+    property("border-top-width", false, "medium"),
+    property("border-right-width", false, "medium"),
+    property("border-bottom-width", false, "medium"),
+    property("border-left-width", false, "medium"),
+    property("border-top-style", false, "none"),
+    property("border-right-style", false, "none"),
+    property("border-bottom-style", false, "none"),
+    property("border-left-style", false, "none"),
+    property("border-top-color", false, "currentcolor"),
+    property("border-right-color", false, "currentcolor"),
+    property("border-bottom-color", false, "currentcolor"),
+    property("border-left-color", false, "currentcolor"),
+    property("outline-width", false, "medium"),
+    property("outline-style", false, "none"),
+    property("outline-color", false, "currentcolor"),
+    property("outline-offset", false, "0"),
+    // Visual
+    property("background-color", false, "transparent"),
+    property("background-image", false, "none"),
+    property("opacity", false, "1"),
+    property("text-decoration-line", false, "none"),
+    property("text-decoration-color", false, "currentcolor"),
+    property("text-decoration-style", false, "solid"),
+    property("vertical-align", false, "baseline"),
+    property("overflow-x", false, "visible"),
+    property("overflow-y", false, "visible"),
+    property("position", false, "static"),
+    property("top", false, "auto"),
+    property("right", false, "auto"),
+    property("bottom", false, "auto"),
+    property("left", false, "auto"),
+    property("z-index", false, "auto"),
+    property("float", false, "none"),
+    property("clear", false, "none"),
+    property("content", false, "normal"),
+    property("object-fit", false, "fill"),
+    property("counter-reset", false, "none"),
+    property("counter-increment", false, "none"),
+    property("counter-set", false, "none"),
+    // Fragmentation, where printed pages may break
+    property("break-before", false, "auto"),
+    property("break-after", false, "auto"),
+    property("break-inside", false, "auto"),
+    // Flex layout
+    property("flex-direction", false, "row"),
+    property("flex-wrap", false, "nowrap"),
+    property("flex-grow", false, "0"),
+    property("flex-shrink", false, "1"),
+    property("flex-basis", false, "auto"),
+    property("order", false, "0"),
+    property("justify-content", false, "normal"),
+    property("align-items", false, "normal"),
+    property("align-self", false, "auto"),
+    property("align-content", false, "normal"),
+    property("row-gap", false, "normal"),
+    property("column-gap", false, "normal"),
+    // Table layout
+    property("border-collapse", true, "separate"),
+    property("border-spacing", true, "0"),
+    property("caption-side", true, "top"),
+    property("table-layout", false, "auto"),
+    // Grid layout
+    property("grid-template-columns", false, "none"),
+    property("grid-template-rows", false, "none"),
+    property("grid-template-areas", false, "none"),
+    property("grid-auto-columns", false, "auto"),
+    property("grid-auto-rows", false, "auto"),
+    property("grid-auto-flow", false, "row"),
+    property("grid-row-start", false, "auto"),
+    property("grid-row-end", false, "auto"),
+    property("grid-column-start", false, "auto"),
+    property("grid-column-end", false, "auto"),
+    property("justify-items", false, "normal"),
+    property("justify-self", false, "auto"),
+];
+
+pub fn lookup(name: &str) -> Option<&'static PropertyInfo> {
+    PROPERTIES.iter().find(|property| property.name == name)
+}
+
+pub fn is_inherited(name: &str) -> bool {
+    lookup(name).is_some_and(|property| property.inherited)
+}
+
+// Returns the longhands set by a shorthand property
+pub fn longhands(name: &str) -> Option<&'static [&'static str]> {
+    let longhands: &'static [&'static str] = match name {
+        "margin" => &["margin-top", "margin-right", "margin-bottom", "margin-left"],
+        "padding" => &[
+            "padding-top",
+            "padding-right",
+            "padding-bottom",
+            "padding-left",
+        ],
+        "border-width" => &[
+            "border-top-width",
+            "border-right-width",
+            "border-bottom-width",
+            "border-left-width",
+        ],
+        "border-style" => &[
+            "border-top-style",
+            "border-right-style",
+            "border-bottom-style",
+            "border-left-style",
+        ],
+        "border-color" => &[
+            "border-top-color",
+            "border-right-color",
+            "border-bottom-color",
+            "border-left-color",
+        ],
+        "border" => &[
+            "border-top-width",
+            "border-right-width",
+            "border-bottom-width",
+            "border-left-width",
+            "border-top-style",
+            "border-right-style",
+            "border-bottom-style",
+            "border-left-style",
+            "border-top-color",
+            "border-right-color",
+            "border-bottom-color",
+            "border-left-color",
+        ],
+        "border-top" => &["border-top-width", "border-top-style", "border-top-color"],
+        "border-right" => &[
+            "border-right-width",
+            "border-right-style",
+            "border-right-color",
+        ],
+        "border-bottom" => &[
+            "border-bottom-width",
+            "border-bottom-style",
+            "border-bottom-color",
+        ],
+        "border-left" => &[
+            "border-left-width",
+            "border-left-style",
+            "border-left-color",
+        ],
+        "outline" => &["outline-width", "outline-style", "outline-color"],
+        "inset" => &["top", "right", "bottom", "left"],
+        "overflow" => &["overflow-x", "overflow-y"],
+        "background" => &["background-color", "background-image"],
+        "font" => &[
+            "font-style",
+            "font-weight",
+            "font-size",
+            "line-height",
+            "font-family",
+        ],
+        "list-style" => &["list-style-type", "list-style-position", "list-style-image"],
+        "flex" => &["flex-grow", "flex-shrink", "flex-basis"],
+        "flex-flow" => &["flex-direction", "flex-wrap"],
+        "gap" => &["row-gap", "column-gap"],
+        "page-break-before" => &["break-before"],
+        "page-break-after" => &["break-after"],
+        "page-break-inside" => &["break-inside"],
+        "grid-row" => &["grid-row-start", "grid-row-end"],
+        "grid-column" => &["grid-column-start", "grid-column-end"],
+        "grid-area" => &[
+            "grid-row-start",
+            "grid-column-start",
+            "grid-row-end",
+            "grid-column-end",
+        ],
+        "text-decoration" => &[
+            "text-decoration-line",
+            "text-decoration-style",
+            "text-decoration-color",
+        ],
+        _ => return None,
+    };
+    Some(longhands)
+}
+
+// Whether a declaration's value fits the grammar of its property. Unknown properties are
+// accepted here and ignored by the cascade.
+pub fn is_valid(name: &str, tokens: &[Token]) -> bool {
+    if lookup(name).is_none() && longhands(name).is_none() {
+        return true;
+    }
+    // Lengths are only checked for their type, so any context will do
+    let context = LengthContext {
+        font_size: 16.0,
+        root_font_size: 16.0,
+        viewport_width: 800.0,
+        viewport_height: 600.0,
+    };
+    values::parse_value(tokens, &context).is_some_and(|value| is_valid_value(name, &value))
+}
+
+const DISPLAYS: &[&str] = &[
+    "none",
+    "contents",
+    "block",
+    "inline",
+    "inline-block",
+    "list-item",
+    "flow-root",
+    "flex",
+    "inline-flex",
+    "grid",
+    "inline-grid",
+    "table",
+    "inline-table",
+    "table-caption",
+    "table-header-group",
+    "table-row-group",
+    "table-footer-group",
+    "table-row",
+    "table-cell",
+    "table-column-group",
+    "table-column",
+];
+
+fn is_valid_value(name: &str, value: &Value) -> bool {
+    let keyword = value.as_keyword();
+    if keyword.is_some_and(|k| matches!(k, "inherit" | "initial" | "unset" | "revert")) {
+        return true;
+    }
+    if longhands(name).is_some() {
+        return expand_shorthand(name, value).is_some_and(|expanded| {
+            expanded
+                .iter()
+                .all(|(longhand, value)| is_valid_value(longhand, value))
+        });
+    }
+    let one_of = |keywords: &[&str]| keyword.is_some_and(|k| keywords.contains(&k));
+    let length = |value: &Value| value.is_length_percentage();
+    let non_negative = |value: &Value| match value {
+        Value::Length(px) | Value::Percentage(px) => *px >= 0.0,
+        value => value.is_length_percentage(),
+    };
+    let integer = |value: &Value| value.as_number().is_some_and(|n| n.fract() == 0.0);
+    let image = |value: &Value| match value {
+        Value::Url(_) => true,
+        Value::Function(function, _) => function.ends_with("gradient"),
+        value => value.is_keyword("none"),
+    };
+    let sizing = ["min-content", "max-content", "fit-content"];
+    let fit_content = matches!(value, Value::Function(function, _) if function == "fit-content");
+    match name {
+        "color"
+        | "background-color"
+        | "border-top-color"
+        | "border-right-color"
+        | "border-bottom-color"
+        | "border-left-color"
+        | "outline-color"
+        | "text-decoration-color" => value.to_color(Color::BLACK).is_some(),
+        "font-family" => value.items().iter().all(|family| {
+            family
+                .items()
+                .iter()
+                .all(|part| matches!(part, Value::Keyword(_) | Value::Str(_)))
+        }),
+        "font-size" => {
+            one_of(&[
+                "xx-small",
+                "x-small",
+                "small",
+                "medium",
+                "large",
+                "x-large",
+                "xx-large",
+                "xxx-large",
+                "smaller",
+                "larger",
+            ]) || non_negative(value)
+        }
+        "font-style" => match value.items() {
+            [_] => one_of(&["normal", "italic", "oblique"]),
+            [oblique, Value::Dimension(_, _)] => oblique.is_keyword("oblique"),
+            _ => false,
+        },
+        "font-weight" => {
+            one_of(&["normal", "bold", "bolder", "lighter"])
+                || value
+                    .as_number()
+                    .is_some_and(|weight| (1.0..=1000.0).contains(&weight))
+        }
+        "line-height" => {
+            keyword == Some("normal")
+                || value.as_number().is_some_and(|n| n >= 0.0)
+                || non_negative(value)
+        }
+        "text-align" => one_of(&[
+            "start",
+            "end",
+            "left",
+            "right",
+            "center",
+            "justify",
+            "match-parent",
+        ]),
+        "text-indent" => {
+            value.items().iter().any(length)
+                && value.items().iter().all(|item| {
+                    length(item) || matches!(item.as_keyword(), Some("hanging" | "each-line"))
+                })
+        }
+        "text-transform" => one_of(&["none", "capitalize", "uppercase", "lowercase", "full-width"]),
+        "white-space" => one_of(&[
+            "normal",
+            "pre",
+            "nowrap",
+            "pre-wrap",
+            "pre-line",
+            "break-spaces",
+        ]),
+        "letter-spacing" | "word-spacing" => keyword == Some("normal") || length(value),
+        "visibility" => one_of(&["visible", "hidden", "collapse"]),
+        "direction" => one_of(&["ltr", "rtl"]),
+        "list-style-type" => matches!(value, Value::Keyword(_) | Value::Str(_)),
+        "list-style-position" => one_of(&["inside", "outside"]),
+        "list-style-image" => image(value),
+        "background-image" => value.items().iter().all(image),
+        "color-scheme" => value.items().iter().all(|item| item.as_keyword().is_some()),
+        "display" => one_of(DISPLAYS),
+        "box-sizing" => one_of(&["content-box", "border-box"]),
+        "width" | "height" | "min-width" | "min-height" => {
+            keyword == Some("auto") || one_of(&sizing) || fit_content || non_negative(value)
+        }
+        "max-width" | "max-height" => {
+            keyword == Some("none") || one_of(&sizing) || fit_content || non_negative(value)
+        }
+        "margin-top" | "margin-right" | "margin-bottom" | "margin-left" | "top" | "right"
+        | "bottom" | "left" => keyword == Some("auto") || length(value),
+        "padding-top" | "padding-right" | "padding-bottom" | "padding-left" => non_negative(value),
+        "border-top-width"
+        | "border-right-width"
+        | "border-bottom-width"
+        | "border-left-width"
+        | "outline-width" => one_of(&["thin", "medium", "thick"]) || non_negative(value),
+        "border-top-style" | "border-right-style" | "border-bottom-style" | "border-left-style" => {
+            one_of(BORDER_STYLES)
+        }
+        "outline-style" => keyword == Some("auto") || one_of(BORDER_STYLES),
+        "outline-offset" => length(value),
+        "opacity" => matches!(value, Value::Number(_) | Value::Percentage(_)),
+        "text-decoration-line" => value.items().iter().all(|item| {
+            matches!(
+                item.as_keyword(),
+                Some("none" | "underline" | "overline" | "line-through" | "blink")
+            )
+        }),
+        "text-decoration-style" => one_of(&["solid", "double", "dotted", "dashed", "wavy"]),
+        "vertical-align" => {
+            one_of(&[
+                "baseline",
+                "sub",
+                "super",
+                "text-top",
+                "text-bottom",
+                "middle",
+                "top",
+                "bottom",
+            ]) || length(value)
+        }
+        "overflow-x" | "overflow-y" => one_of(&["visible", "hidden", "clip", "scroll", "auto"]),
+        "position" => one_of(&["static", "relative", "absolute", "fixed", "sticky"]),
+        "z-index" => keyword == Some("auto") || integer(value),
+        "order" => integer(value),
+        "float" => one_of(&["none", "left", "right", "inline-start", "inline-end"]),
+        "clear" => one_of(&[
+            "none",
+            "left",
+            "right",
+            "both",
+            "inline-start",
+            "inline-end",
+        ]),
+        "object-fit" => one_of(&["fill", "contain", "cover", "none", "scale-down"]),
+        "break-before" | "break-after" => one_of(&[
+            "auto",
+            "avoid",
+            "always",
+            "all",
+            "avoid-page",
+            "page",
+            "left",
+            "right",
+            "recto",
+            "verso",
+            "avoid-column",
+            "column",
+        ]),
+        "break-inside" => one_of(&["auto", "avoid", "avoid-page", "avoid-column"]),
+        "flex-direction" => one_of(&["row", "row-reverse", "column", "column-reverse"]),
+        "flex-wrap" => one_of(&["nowrap", "wrap", "wrap-reverse"]),
+        "flex-grow" | "flex-shrink" => value.as_number().is_some_and(|n| n >= 0.0),
+        "flex-basis" => one_of(&sizing) || flex_basis(value).is_some(),
+        "justify-content" | "align-items" | "align-self" | "align-content" | "justify-items"
+        | "justify-self" => value.items().iter().all(|item| item.as_keyword().is_some()),
+        "row-gap" | "column-gap" => keyword == Some("normal") || non_negative(value),
+        "border-collapse" => one_of(&["collapse", "separate"]),
+        "border-spacing" => {
+            matches!(value.items(), [_] | [_, _]) && value.items().iter().all(non_negative)
+        }
+        "caption-side" => one_of(&["top", "bottom"]),
+        "table-layout" => one_of(&["auto", "fixed"]),
+        // Properties with richer grammars, such as cursor, content, counters and grid
+        // templates, are checked where they're used
+        _ => true,
+    }
+}
+
+// Expands a shorthand value into its longhands. Returns None if the value is invalid.
+// Longhands not mentioned in the value are reset to their initial values.
+pub fn expand_shorthand(name: &str, value: &Value) -> Option<Vec<(&'static str, Value)>> {
+    let names = longhands(name)?;
+    match name {
+        "margin" | "padding" | "border-width" | "border-style" | "border-color" | "inset" => {
+            let [top, right, bottom, left] = four_sides(value)?;
+            Some(vec![
+                (names[0], top),
+                (names[1], right),
+                (names[2], bottom),
+                (names[3], left),
+            ])
+        }
+        "border" => {
+            let (width, style, color) = border_parts(value)?;
+            let mut expanded = Vec::new();
+            for side in 0..4 {
+                expanded.push((names[side], width.clone()));
+                expanded.push((names[4 + side], style.clone()));
+                expanded.push((names[8 + side], color.clone()));
+            }
+            Some(expanded)
+        }
+        "border-top" | "border-right" | "border-bottom" | "border-left" | "outline" => {
+            let (width, style, color) = border_parts(value)?;
+            Some(vec![
+                (names[0], width),
+                (names[1], style),
+                (names[2], color),
+            ])
+        }
+        "overflow" => match value.items() {
+            [x] => Some(vec![(names[0], x.clone()), (names[1], x.clone())]),
+            [x, y] => Some(vec![(names[0], x.clone()), (names[1], y.clone())]),
+            _ => None,
+        },
+        "background" => {
+            // Only the color and image of the final layer are used
+            let layer = match value {
+                Value::CommaList(layers) => layers.last()?,
+                layer => layer,
+            };
+            let mut color = Value::keyword("transparent");
+            let mut image = Value::keyword("none");
+            for item in layer.items() {
+                match item {
+                    Value::Color(_) => color = item.clone(),
+                    Value::Keyword(keyword)
+                        if item.to_color(Color::BLACK).is_some() || keyword == "currentcolor" =>
+                    {
+                        color = item.clone()
+                    }
+                    Value::Url(_) => image = item.clone(),
+                    Value::Function(function, _) if function.ends_with("gradient") => {
+                        image = item.clone()
+                    }
+                    _ => {}
+                }
+            }
+            Some(vec![(names[0], color), (names[1], image)])
+        }
+        "font" => expand_font(value),
+        // The legacy names of the break properties, where `always` means a page break
+        "page-break-before" | "page-break-after" | "page-break-inside" => match value {
+            Value::Keyword(keyword) if keyword == "always" => {
+                Some(vec![(names[0], Value::keyword("page"))])
+            }
+            Value::Keyword(_) => Some(vec![(names[0], value.clone())]),
+            _ => None,
+        },
+        "list-style" => {
+            let mut list_type = None;
+            let mut position = None;
+            let mut image = None;
+            let mut nones = 0;
+            for item in value.items() {
+                match item {
+                    Value::Keyword(keyword) if keyword == "none" => nones += 1,
+                    Value::Keyword(keyword) if keyword == "inside" || keyword == "outside" => {
+                        position = Some(item.clone())
+                    }
+                    Value::Url(_) => image = Some(item.clone()),
+                    Value::Keyword(_) | Value::Str(_) if list_type.is_none() => {
+                        list_type = Some(item.clone())
+                    }
+                    _ => return None,
+                }
+            }
+            // `none` applies to whichever of type and image is not otherwise set
+            if nones > 0 && list_type.is_none() {
+                list_type = Some(Value::keyword("none"));
+                nones -= 1;
+            }
+            if nones > 0 && image.is_none() {
+                image = Some(Value::keyword("none"));
+                nones -= 1;
+            }
+            if nones > 0 {
+                return None;
+            }
+            Some(vec![
+                (names[0], list_type.unwrap_or(Value::keyword("disc"))),
+                (names[1], position.unwrap_or(Value::keyword("outside"))),
+                (names[2], image.unwrap_or(Value::keyword("none"))),
+            ])
+        }
+        "flex" => expand_flex(value),
+        "flex-flow" => {
+            let mut direction = None;
+            let mut wrap = None;
+            for item in value.items() {
+                match item.as_keyword() {
+                    Some("row" | "row-reverse" | "column" | "column-reverse")
+                        if direction.is_none() =>
+                    {
+                        direction = Some(item.clone())
+                    }
+                    Some("nowrap" | "wrap" | "wrap-reverse") if wrap.is_none() => {
+                        wrap = Some(item.clone())
+                    }
+                    _ => return None,
+                }
+            }
+            Some(vec![
+                (names[0], direction.unwrap_or(Value::keyword("row"))),
+                (names[1], wrap.unwrap_or(Value::keyword("nowrap"))),
+            ])
+        }
+        "gap" => match value.items() {
+            [both] => Some(vec![(names[0], both.clone()), (names[1], both.clone())]),
+            [row, column] => Some(vec![(names[0], row.clone()), (names[1], column.clone())]),
+            _ => None,
+        },
+        "grid-row" | "grid-column" | "grid-area" => {
+            let mut parts: Vec<Value> = Vec::new();
+            for part in value.items().split(|item| *item == Value::Slash) {
+                parts.push(match part {
+                    [single] => single.clone(),
+                    _ => Value::List(part.to_vec()),
+                });
+            }
+            if parts.len() > names.len() || parts.iter().any(|part| part.items().is_empty()) {
+                return None;
+            }
+            // Missing lines repeat a preceding area name, or are auto
+            for index in parts.len()..names.len() {
+                let from = match index {
+                    3 => 1,
+                    _ => 0,
+                };
+                let name = match parts[from].as_keyword() {
+                    Some(keyword) if keyword != "auto" && keyword != "span" => parts[from].clone(),
+                    _ => Value::keyword("auto"),
+                };
+                parts.push(name);
+            }
+            Some(names.iter().copied().zip(parts).collect())
+        }
+        "text-decoration" => {
+            let mut lines = Vec::new();
+            let mut style = Value::keyword("solid");
+            let mut color = Value::keyword("currentcolor");
+            for item in value.items() {
+                match item.as_keyword() {
+                    Some("underline" | "overline" | "line-through" | "none") => {
+                        lines.push(item.clone())
+                    }
+                    Some("solid" | "double" | "dotted" | "dashed" | "wavy") => style = item.clone(),
+                    _ if item.to_color(Color::BLACK).is_some() => color = item.clone(),
+                    _ => return None,
+                }
+            }
+            let line = match lines.len() {
+                0 => Value::keyword("none"),
+                1 => lines.remove(0),
+                _ => Value::List(lines),
+            };
+            Some(vec![(names[0], line), (names[1], style), (names[2], color)])
+        }
+        _ => None,
+    }
+}
+
+// Expands the 1 to 4 value syntax used by margin, padding and friends
+fn four_sides(value: &Value) -> Option<[Value; 4]> {
+    if matches!(value, Value::CommaList(_)) {
+        return None;
+    }
+    let sides = match value.items() {
+        [all] => [all.clone(), all.clone(), all.clone(), all.clone()],
+        [vertical, horizontal] => [
+            vertical.clone(),
+            horizontal.clone(),
+            vertical.clone(),
+            horizontal.clone(),
+        ],
+        [top, horizontal, bottom] => [
+            top.clone(),
+            horizontal.clone(),
+            bottom.clone(),
+            horizontal.clone(),
+        ],
+        [top, right, bottom, left] => [top.clone(), right.clone(), bottom.clone(), left.clone()],
+        _ => return None,
+    };
+    Some(sides)
+}
+
+const BORDER_STYLES: &[&str] = &[
+    "none", "hidden", "dotted", "dashed", "solid", "double", "groove", "ridge", "inset", "outset",
+];
+
+// Splits `<width> || <style> || <color>` in any order
+fn border_parts(value: &Value) -> Option<(Value, Value, Value)> {
+    let mut width = None;
+    let mut style = None;
+    let mut color = None;
+    for item in value.items() {
+        let is_width = item.is_length_percentage()
+            || matches!(item.as_keyword(), Some("thin" | "medium" | "thick"));
+        let is_style = item
+            .as_keyword()
+            .is_some_and(|k| BORDER_STYLES.contains(&k));
+        if is_width && width.is_none() {
+            width = Some(item.clone());
+        } else if is_style && style.is_none() {
+            style = Some(item.clone());
+        } else if color.is_none() && item.to_color(Color::BLACK).is_some() {
+            color = Some(item.clone());
+        } else {
+            return None;
+        }
+    }
+    Some((
+        width.unwrap_or(Value::keyword("medium")),
+        style.unwrap_or(Value::keyword("none")),
+        color.unwrap_or(Value::keyword("currentcolor")),
+    ))
+}
+
+// Expands `flex: none | <grow> <shrink>? || <basis>`. A missing basis is zero rather than
+// the initial auto.
+fn expand_flex(value: &Value) -> Option<Vec<(&'static str, Value)>> {
+    let number = |value: f32| Value::Number(value);
+    let (grow, shrink, basis) = match value.items() {
+        [keyword] if keyword.is_keyword("none") => {
+            (number(0.0), number(0.0), Value::keyword("auto"))
+        }
+        [keyword] if keyword.is_keyword("auto") => {
+            (number(1.0), number(1.0), Value::keyword("auto"))
+        }
+        [Value::Number(grow)] => (number(*grow), number(1.0), Value::Length(0.0)),
+        [basis] => (number(1.0), number(1.0), flex_basis(basis)?),
+        [Value::Number(grow), Value::Number(shrink)] => {
+            (number(*grow), number(*shrink), Value::Length(0.0))
+        }
+        [Value::Number(grow), basis] | [basis, Value::Number(grow)] => {
+            (number(*grow), number(1.0), flex_basis(basis)?)
+        }
+        [Value::Number(grow), Value::Number(shrink), basis]
+        | [basis, Value::Number(grow), Value::Number(shrink)] => {
+            (number(*grow), number(*shrink), flex_basis(basis)?)
+        }
+        _ => return None,
+    };
+    Some(vec![
+        ("flex-grow", grow),
+        ("flex-shrink", shrink),
+        ("flex-basis", basis),
+    ])
+}
+
+fn flex_basis(value: &Value) -> Option<Value> {
+    match value.as_keyword() {
+        Some("auto" | "content") => Some(value.clone()),
+        Some(_) => None,
+        None if value.is_length_percentage() => Some(value.clone()),
+        None => None,
+    }
+}
+
+// Expands `font: [style || weight]? size[/line-height]? family`
+fn expand_font(value: &Value) -> Option<Vec<(&'static str, Value)>> {
+    // The first comma separated item holds everything up to the first family name
+    let (first, other_families) = match value {
+        Value::CommaList(items) => (items.first()?, &items[1..]),
+        value => (value, &[][..]),
+    };
+    let items = first.items();
+    let mut style = Value::keyword("normal");
+    let mut weight = Value::keyword("normal");
+    let mut index = 0;
+    while let Some(item) = items.get(index) {
+        match item {
+            Value::Keyword(k) if matches!(k.as_str(), "italic" | "oblique") => style = item.clone(),
+            Value::Keyword(k) if matches!(k.as_str(), "bold" | "bolder" | "lighter") => {
+                weight = item.clone()
+            }
+            Value::Number(n) if (1.0..=1000.0).contains(n) => weight = item.clone(),
+            Value::Keyword(k) if matches!(k.as_str(), "normal" | "small-caps") => {}
+            _ => break,
+        }
+        index += 1;
+    }
+    let size = items.get(index)?.clone();
+    index += 1;
+    let mut line_height = Value::keyword("normal");
+    if items.get(index) == Some(&Value::Slash) {
+        line_height = items.get(index + 1)?.clone();
+        index += 2;
+    }
+    let mut families: Vec<Value> = Vec::new();
+    let first_family = &items[index..];
+    match first_family.len() {
+        0 => return None,
+        1 => families.push(first_family[0].clone()),
+        _ => families.push(Value::List(first_family.to_vec())),
+    }
+    families.extend(other_families.iter().cloned());
+    let family = match families.len() {
+        1 => families.remove(0),
+        _ => Value::CommaList(families),
+    };
+    Some(vec![
+        ("font-style", style),
+        ("font-weight", weight),
+        ("font-size", size),
+        ("line-height", line_height),
+        ("font-family", family),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::tokenizer::tokenize;
+    use crate::css::values::{parse_value, LengthContext};
+
+    fn parse(css: &str) -> Value {
+        let context = LengthContext {
+            font_size: 16.0,
+            root_font_size: 16.0,
+            viewport_width: 800.0,
+            viewport_height: 600.0,
+        };
+        parse_value(&tokenize(css), &context).unwrap()
+    }
+
+    #[test]
+    fn test_expand_margin() {
+        let expanded = expand_shorthand("margin", &parse("1px 2px 3px")).unwrap();
+        assert_eq!(
+            expanded,
+            vec![
+                ("margin-top", Value::Length(1.0)),
+                ("margin-right", Value::Length(2.0)),
+                ("margin-bottom", Value::Length(3.0)),
+                ("margin-left", Value::Length(2.0)),
+            ]
+        );
+        assert!(expand_shorthand("margin", &parse("1px 2px 3px 4px 5px")).is_none());
+        assert!(expand_shorthand("color", &parse("red")).is_none());
+    }
+
+    #[test]
+    fn test_expand_border() {
+        let expanded = expand_shorthand("border-left", &parse("red 2px dashed")).unwrap();
+        assert_eq!(
+            expanded,
+            vec![
+                ("border-left-width", Value::Length(2.0)),
+                ("border-left-style", Value::keyword("dashed")),
+                ("border-left-color", Value::keyword("red")),
+            ]
+        );
+        assert!(expand_shorthand("border", &parse("1px 2px")).is_none());
+    }
+
+    #[test]
+    fn test_expand_font() {
+        let expanded =
+            expand_shorthand("font", &parse("italic bold 12px/1.5 Arial, serif")).unwrap();
+        assert_eq!(expanded[0], ("font-style", Value::keyword("italic")));
+        assert_eq!(expanded[1], ("font-weight", Value::keyword("bold")));
+        assert_eq!(expanded[2], ("font-size", Value::Length(12.0)));
+        assert_eq!(expanded[3], ("line-height", Value::Number(1.5)));
+        assert_eq!(
+            expanded[4],
+            (
+                "font-family",
+                Value::CommaList(vec![Value::keyword("arial"), Value::keyword("serif")])
+            )
+        );
+    }
+
+    #[test]
+    fn test_expand_flex() {
+        let flex = |css: &str| {
+            expand_shorthand("flex", &parse(css)).map(|expanded| {
+                expanded
+                    .into_iter()
+                    .map(|(_, value)| value)
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(
+            flex("1"),
+            Some(vec![
+                Value::Number(1.0),
+                Value::Number(1.0),
+                Value::Length(0.0)
+            ])
+        );
+        assert_eq!(
+            flex("none"),
+            Some(vec![
+                Value::Number(0.0),
+                Value::Number(0.0),
+                Value::keyword("auto")
+            ])
+        );
+        assert_eq!(
+            flex("2 3 10%"),
+            Some(vec![
+                Value::Number(2.0),
+                Value::Number(3.0),
+                Value::Percentage(10.0)
+            ])
+        );
+        assert_eq!(
+            flex("20px 2"),
+            Some(vec![
+                Value::Number(2.0),
+                Value::Number(1.0),
+                Value::Length(20.0)
+            ])
+        );
+        assert_eq!(flex("1 2 3 4"), None);
+        let flow = expand_shorthand("flex-flow", &parse("wrap column")).unwrap();
+        assert_eq!(flow[0].1, Value::keyword("column"));
+        assert_eq!(flow[1].1, Value::keyword("wrap"));
+        let gap = expand_shorthand("gap", &parse("1px 2px")).unwrap();
+        assert_eq!(
+            gap,
+            vec![
+                ("row-gap", Value::Length(1.0)),
+                ("column-gap", Value::Length(2.0))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_grid_placement() {
+        let expanded = expand_shorthand("grid-column", &parse("span 2 / 4")).unwrap();
+        assert_eq!(
+            expanded,
+            vec![
+                (
+                    "grid-column-start",
+                    Value::List(vec![Value::keyword("span"), Value::Number(2.0)])
+                ),
+                ("grid-column-end", Value::Number(4.0)),
+            ]
+        );
+        let expanded = expand_shorthand("grid-area", &parse("header")).unwrap();
+        assert!(expanded
+            .iter()
+            .all(|(_, value)| *value == Value::keyword("header")));
+        let expanded = expand_shorthand("grid-area", &parse("1 / main")).unwrap();
+        assert_eq!(expanded[2].1, Value::keyword("auto"));
+        assert_eq!(expanded[3].1, Value::keyword("main"));
+        assert_eq!(
+            parse("[a b] 1fr"),
+            Value::List(vec![
+                Value::LineNames(vec!["a".to_string(), "b".to_string()]),
+                Value::Dimension(1.0, "fr".to_string())
+            ])
+        );
+    }
+
+    #[test]
+    fn test_expand_background() {
+        let expanded = expand_shorthand("background", &parse("#fff url(a.png) no-repeat")).unwrap();
+        assert_eq!(expanded[0].1, Value::Color(Color::WHITE));
+        assert_eq!(expanded[1].1, Value::Url("a.png".to_string()));
+    }
+}
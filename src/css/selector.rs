@@ -0,0 +1,703 @@
+// Selector parsing, specificity and matching against DOM elements
+
+use crate::css::tokenizer::{self, Token};
+use crate::dom::{Element, Node};
+
+/// A complex selector such as `div > p.note:first-child`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selector {
+    // Compound selectors from left to right
+    pub compounds: Vec<CompoundSelector>,
+    // combinators[i] sits between compounds[i] and compounds[i + 1]
+    pub combinators: Vec<Combinator>,
+    pub pseudo_element: Option<PseudoElement>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Combinator {
+    Descendant,
+    Child,
+    NextSibling,
+    SubsequentSibling,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompoundSelector {
+    pub tag: Option<String>, // None also covers the universal selector
+    pub id: Option<String>,
+    pub classes: Vec<String>,
+    pub attributes: Vec<AttributeSelector>,
+    pub pseudo_classes: Vec<PseudoClass>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeSelector {
+    pub name: String,
+    pub operator: Option<(AttributeOperator, String)>,
+    pub case_insensitive: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeOperator {
+    Equals,    // =
+    Includes,  // ~=
+    DashMatch, // |=
+    Prefix,    // ^=
+    Suffix,    // $=
+    Substring, // *=
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PseudoClass {
+    Root,
+    Empty,
+    FirstChild,
+    LastChild,
+    OnlyChild,
+    FirstOfType,
+    LastOfType,
+    NthChild(i32, i32),
+    NthLastChild(i32, i32),
+    Link,
+    Visited,
+    Hover,
+    Focus,
+    Active,
+    Not(Vec<CompoundSelector>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PseudoElement {
+    Before,
+    After,
+    Marker,
+}
+
+/// Specificity as (id, class, type) counts, compared lexicographically
+pub type Specificity = (u32, u32, u32);
+
+impl Selector {
+    pub fn specificity(&self) -> Specificity {
+        let mut specificity = (0, 0, 0);
+        for compound in &self.compounds {
+            let (a, b, c) = compound.specificity();
+            specificity.0 += a;
+            specificity.1 += b;
+            specificity.2 += c;
+        }
+        if self.pseudo_element.is_some() {
+            specificity.2 += 1;
+        }
+        specificity
+    }
+
+    // Returns true if the selector matches the element, ignoring any pseudo-element
+    pub fn matches(&self, element: &ElementRef) -> bool {
+        match self.compounds.len() {
+            0 => false,
+            n => self.matches_from(n - 1, element),
+        }
+    }
+
+    fn matches_from(&self, index: usize, element: &ElementRef) -> bool {
+        if !self.compounds[index].matches(element) {
+            return false;
+        }
+        if index == 0 {
+            return true;
+        }
+        match self.combinators[index - 1] {
+            Combinator::Child => element
+                .parent
+                .is_some_and(|parent| self.matches_from(index - 1, parent)),
+            Combinator::Descendant => {
+                let mut ancestor = element.parent;
+                while let Some(current) = ancestor {
+                    if self.matches_from(index - 1, current) {
+                        return true;
+                    }
+                    ancestor = current.parent;
+                }
+                false
+            }
+            Combinator::NextSibling => element
+                .previous_sibling()
+                .is_some_and(|sibling| self.matches_from(index - 1, &sibling)),
+            Combinator::SubsequentSibling => {
+                let mut sibling = element.previous_sibling();
+                while let Some(current) = sibling {
+                    if self.matches_from(index - 1, &current) {
+                        return true;
+                    }
+                    sibling = current.previous_sibling();
+                }
+                false
+            }
+        }
+    }
+}
+
+impl CompoundSelector {
+    pub fn specificity(&self) -> Specificity {
+        let mut specificity = (
+            self.id.is_some() as u32,
+            (self.classes.len() + self.attributes.len()) as u32,
+            self.tag.is_some() as u32,
+        );
+        for pseudo_class in &self.pseudo_classes {
+            match pseudo_class {
+                PseudoClass::Not(arguments) => {
+                    let most_specific = arguments
+                        .iter()
+                        .map(CompoundSelector::specificity)
+                        .max()
+                        .unwrap_or_default();
+                    specificity.0 += most_specific.0;
+                    specificity.1 += most_specific.1;
+                    specificity.2 += most_specific.2;
+                }
+                _ => specificity.1 += 1,
+            }
+        }
+        specificity
+    }
+
+    pub fn matches(&self, element: &ElementRef) -> bool {
+        self.matches_element(element.element)
+            && self
+                .pseudo_classes
+                .iter()
+                .all(|pseudo_class| pseudo_class.matches(element))
+    }
+
+    // Matches everything but the pseudo-classes, which may depend on the element's position
+    pub(crate) fn matches_element(&self, element: &Element) -> bool {
+        if let Some(tag) = &self.tag {
+            if !tag.eq_ignore_ascii_case(element.element_type.tag_name()) {
+                return false;
+            }
+        }
+        if let Some(id) = &self.id {
+            if element.id() != Some(id.as_str()) {
+                return false;
+            }
+        }
+        self.classes.iter().all(|class| element.has_class(class))
+            && self
+                .attributes
+                .iter()
+                .all(|attribute| attribute.matches(element))
+    }
+}
+
+impl AttributeSelector {
+    fn matches(&self, element: &Element) -> bool {
+        let Some(actual) = element.attributes.get(&self.name) else {
+            return false;
+        };
+        let Some((operator, expected)) = &self.operator else {
+            return true;
+        };
+        let (actual, expected) = match self.case_insensitive {
+            true => (actual.to_lowercase(), expected.to_lowercase()),
+            false => (actual.clone(), expected.clone()),
+        };
+        match operator {
+            AttributeOperator::Equals => actual == expected,
+            AttributeOperator::Includes => actual.split_whitespace().any(|word| word == expected),
+            AttributeOperator::DashMatch => {
+                actual == expected || actual.starts_with(&format!("{}-", expected))
+            }
+            AttributeOperator::Prefix => !expected.is_empty() && actual.starts_with(&expected),
+            AttributeOperator::Suffix => !expected.is_empty() && actual.ends_with(&expected),
+            AttributeOperator::Substring => !expected.is_empty() && actual.contains(&expected),
+        }
+    }
+}
+
+impl PseudoClass {
+    fn matches(&self, element: &ElementRef) -> bool {
+        match self {
+            PseudoClass::Root => element.parent.is_none(),
+            PseudoClass::Empty => element.element.children.iter().all(|child| match child {
+                Node::Element(_) => false,
+                Node::Text(text) => text.is_empty(),
+                _ => true,
+            }),
+            PseudoClass::FirstChild => element.previous_sibling().is_none(),
+            PseudoClass::LastChild => element.next_sibling().is_none(),
+            PseudoClass::OnlyChild => {
+                element.previous_sibling().is_none() && element.next_sibling().is_none()
+            }
+            PseudoClass::FirstOfType => element
+                .preceding_elements()
+                .all(|sibling| sibling.element_type != element.element.element_type),
+            PseudoClass::LastOfType => element
+                .following_elements()
+                .all(|sibling| sibling.element_type != element.element.element_type),
+            PseudoClass::NthChild(a, b) => {
+                nth_matches(*a, *b, element.preceding_elements().count() as i32 + 1)
+            }
+            PseudoClass::NthLastChild(a, b) => {
+                nth_matches(*a, *b, element.following_elements().count() as i32 + 1)
+            }
+            PseudoClass::Link => element.element.is_link(),
+            // Visited state is never exposed so history can't be sniffed
+            PseudoClass::Visited => false,
+            PseudoClass::Hover => element.element.state.hover,
+            PseudoClass::Focus => element.element.state.focus,
+            PseudoClass::Active => element.element.state.active,
+            PseudoClass::Not(arguments) => !arguments.iter().any(|a| a.matches(element)),
+        }
+    }
+}
+
+// Returns true if `position` (1 based) is of the form a*n + b for some n >= 0
+fn nth_matches(a: i32, b: i32, position: i32) -> bool {
+    match a {
+        0 => position == b,
+        _ => {
+            let diff = position - b;
+            diff % a == 0 && diff / a >= 0
+        }
+    }
+}
+
+/// An element together with the context needed to match selectors against it
+#[derive(Debug, Clone, Copy)]
+pub struct ElementRef<'a> {
+    pub element: &'a Element,
+    pub parent: Option<&'a ElementRef<'a>>,
+    // All children of the parent (or the document), and this element's index among them
+    pub siblings: &'a [Node],
+    pub index: usize,
+}
+
+impl<'a> ElementRef<'a> {
+    pub fn new(
+        element: &'a Element,
+        parent: Option<&'a ElementRef<'a>>,
+        siblings: &'a [Node],
+        index: usize,
+    ) -> Self {
+        Self {
+            element,
+            parent,
+            siblings,
+            index,
+        }
+    }
+
+    fn sibling_at(&self, index: usize) -> Option<ElementRef<'a>> {
+        match &self.siblings[index] {
+            Node::Element(element) => Some(ElementRef {
+                element,
+                index,
+                ..*self
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn previous_sibling(&self) -> Option<ElementRef<'a>> {
+        (0..self.index).rev().find_map(|i| self.sibling_at(i))
+    }
+
+    pub fn next_sibling(&self) -> Option<ElementRef<'a>> {
+        (self.index + 1..self.siblings.len()).find_map(|i| self.sibling_at(i))
+    }
+
+    fn preceding_elements(&self) -> impl Iterator<Item = &'a Element> {
+        self.siblings[..self.index].iter().filter_map(as_element)
+    }
+
+    fn following_elements(&self) -> impl Iterator<Item = &'a Element> {
+        self.siblings[self.index + 1..]
+            .iter()
+            .filter_map(as_element)
+    }
+}
+
+fn as_element(node: &Node) -> Option<&Element> {
+    match node {
+        Node::Element(element) => Some(element),
+        _ => None,
+    }
+}
+
+// Parses a comma separated selector list. Returns None if any selector is invalid.
+pub fn parse_selector_list(tokens: &[Token]) -> Option<Vec<Selector>> {
+    split_commas(tokens)
+        .into_iter()
+        .map(parse_selector)
+        .collect()
+}
+
+fn split_commas(tokens: &[Token]) -> Vec<&[Token]> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        if token.opens_block() {
+            depth += 1;
+        } else if token.closes_block() {
+            depth -= 1;
+        } else if *token == Token::Comma && depth == 0 {
+            parts.push(&tokens[start..i]);
+            start = i + 1;
+        }
+    }
+    parts.push(&tokens[start..]);
+    parts
+}
+
+fn parse_selector(tokens: &[Token]) -> Option<Selector> {
+    let mut selector = Selector {
+        compounds: Vec::new(),
+        combinators: Vec::new(),
+        pseudo_element: None,
+    };
+    let mut pos = 0;
+    let mut pending_combinator = None;
+    loop {
+        // Read whitespace and combinators between compounds
+        let mut saw_whitespace = false;
+        while let Some(token) = tokens.get(pos) {
+            match token {
+                Token::Whitespace => saw_whitespace = true,
+                Token::Delim('>') => pending_combinator = Some(Combinator::Child),
+                Token::Delim('+') => pending_combinator = Some(Combinator::NextSibling),
+                Token::Delim('~') => pending_combinator = Some(Combinator::SubsequentSibling),
+                _ => break,
+            }
+            pos += 1;
+        }
+        if pos >= tokens.len() {
+            break;
+        }
+        // Nothing may follow a pseudo-element
+        if selector.pseudo_element.is_some() {
+            return None;
+        }
+        if !selector.compounds.is_empty() {
+            let combinator = match (pending_combinator.take(), saw_whitespace) {
+                (Some(combinator), _) => combinator,
+                (None, true) => Combinator::Descendant,
+                (None, false) => return None,
+            };
+            selector.combinators.push(combinator);
+        } else if pending_combinator.is_some() {
+            return None;
+        }
+        let (compound, pseudo_element) = parse_compound(tokens, &mut pos)?;
+        selector.compounds.push(compound);
+        selector.pseudo_element = pseudo_element;
+    }
+    if selector.compounds.is_empty() || pending_combinator.is_some() {
+        return None;
+    }
+    Some(selector)
+}
+
+fn parse_compound(
+    tokens: &[Token],
+    pos: &mut usize,
+) -> Option<(CompoundSelector, Option<PseudoElement>)> {
+    let mut compound = CompoundSelector::default();
+    let mut pseudo_element = None;
+    let start = *pos;
+    while let Some(token) = tokens.get(*pos) {
+        match token {
+            Token::Ident(name) if *pos == start => compound.tag = Some(name.to_lowercase()),
+            Token::Delim('*') if *pos == start => {}
+            Token::Hash(id) => compound.id = Some(id.clone()),
+            Token::Delim('.') => match tokens.get(*pos + 1) {
+                Some(Token::Ident(class)) => {
+                    compound.classes.push(class.clone());
+                    *pos += 1;
+                }
+                _ => return None,
+            },
+            Token::OpenSquare => {
+                let end = find_close(tokens, *pos)?;
+                compound
+                    .attributes
+                    .push(parse_attribute(&tokens[*pos + 1..end])?);
+                *pos = end;
+            }
+            Token::Colon => {
+                if pseudo_element.is_some() {
+                    return None;
+                }
+                let double = tokens.get(*pos + 1) == Some(&Token::Colon);
+                if double {
+                    *pos += 1;
+                }
+                match tokens.get(*pos + 1)? {
+                    Token::Ident(name) => {
+                        *pos += 1;
+                        let name = name.to_lowercase();
+                        match parse_pseudo_element(&name) {
+                            Some(element) if double || name != "marker" => {
+                                pseudo_element = Some(element)
+                            }
+                            _ if double => return None,
+                            _ => compound.pseudo_classes.push(parse_pseudo_class(&name)?),
+                        }
+                    }
+                    Token::Function(name) if !double => {
+                        let end = find_close(tokens, *pos + 1)?;
+                        let arguments = &tokens[*pos + 2..end];
+                        compound
+                            .pseudo_classes
+                            .push(parse_functional_pseudo_class(name, arguments)?);
+                        *pos = end;
+                    }
+                    _ => return None,
+                }
+            }
+            _ => break,
+        }
+        *pos += 1;
+    }
+    match *pos == start {
+        true => None,
+        false => Some((compound, pseudo_element)),
+    }
+}
+
+// Finds the index of the token closing the block opened at `open`
+fn find_close(tokens: &[Token], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        if token.opens_block() {
+            depth += 1;
+        } else if token.closes_block() {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        }
+    }
+    None
+}
+
+fn parse_attribute(tokens: &[Token]) -> Option<AttributeSelector> {
+    let tokens: Vec<&Token> = tokens.iter().filter(|t| !t.is_whitespace()).collect();
+    let Some(Token::Ident(name)) = tokens.first() else {
+        return None;
+    };
+    let mut attribute = AttributeSelector {
+        name: name.to_lowercase(),
+        operator: None,
+        case_insensitive: false,
+    };
+    if tokens.len() == 1 {
+        return Some(attribute);
+    }
+    let (operator, value_index) = match (tokens.get(1)?, tokens.get(2)) {
+        (Token::Delim('='), _) => (AttributeOperator::Equals, 2),
+        (Token::Delim(c), Some(Token::Delim('='))) => {
+            let operator = match c {
+                '~' => AttributeOperator::Includes,
+                '|' => AttributeOperator::DashMatch,
+                '^' => AttributeOperator::Prefix,
+                '$' => AttributeOperator::Suffix,
+                '*' => AttributeOperator::Substring,
+                _ => return None,
+            };
+            (operator, 3)
+        }
+        _ => return None,
+    };
+    let value = match tokens.get(value_index)? {
+        Token::Ident(value) | Token::String(value) => value.clone(),
+        _ => return None,
+    };
+    match tokens.get(value_index + 1) {
+        None => {}
+        Some(Token::Ident(flag)) if flag.eq_ignore_ascii_case("i") => {
+            attribute.case_insensitive = true
+        }
+        Some(Token::Ident(flag)) if flag.eq_ignore_ascii_case("s") => {}
+        Some(_) => return None,
+    }
+    attribute.operator = Some((operator, value));
+    Some(attribute)
+}
+
+fn parse_pseudo_element(name: &str) -> Option<PseudoElement> {
+    match name {
+        "before" => Some(PseudoElement::Before),
+        "after" => Some(PseudoElement::After),
+        "marker" => Some(PseudoElement::Marker),
+        _ => None,
+    }
+}
+
+fn parse_pseudo_class(name: &str) -> Option<PseudoClass> {
+    let pseudo_class = match name {
+        "root" => PseudoClass::Root,
+        "empty" => PseudoClass::Empty,
+        "first-child" => PseudoClass::FirstChild,
+        "last-child" => PseudoClass::LastChild,
+        "only-child" => PseudoClass::OnlyChild,
+        "first-of-type" => PseudoClass::FirstOfType,
+        "last-of-type" => PseudoClass::LastOfType,
+        "link" | "any-link" => PseudoClass::Link,
+        "visited" => PseudoClass::Visited,
+        "hover" => PseudoClass::Hover,
+        "focus" => PseudoClass::Focus,
+        "active" => PseudoClass::Active,
+        _ => return None,
+    };
+    Some(pseudo_class)
+}
+
+fn parse_functional_pseudo_class(name: &str, arguments: &[Token]) -> Option<PseudoClass> {
+    match name.to_lowercase().as_str() {
+        "nth-child" => {
+            let (a, b) = parse_nth(arguments)?;
+            Some(PseudoClass::NthChild(a, b))
+        }
+        "nth-last-child" => {
+            let (a, b) = parse_nth(arguments)?;
+            Some(PseudoClass::NthLastChild(a, b))
+        }
+        "not" => {
+            let mut compounds = Vec::new();
+            for part in split_commas(arguments) {
+                let part: Vec<Token> = part
+                    .iter()
+                    .filter(|t| !t.is_whitespace())
+                    .cloned()
+                    .collect();
+                let mut pos = 0;
+                let (compound, pseudo_element) = parse_compound(&part, &mut pos)?;
+                if pos != part.len() || pseudo_element.is_some() {
+                    return None;
+                }
+                compounds.push(compound);
+            }
+            Some(PseudoClass::Not(compounds))
+        }
+        _ => None,
+    }
+}
+
+// Parses the An+B microsyntax used by :nth-child()
+fn parse_nth(tokens: &[Token]) -> Option<(i32, i32)> {
+    let text: String = tokenizer::serialize(tokens)
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase();
+    match text.as_str() {
+        "odd" => return Some((2, 1)),
+        "even" => return Some((2, 0)),
+        _ => {}
+    }
+    let Some(n_index) = text.find('n') else {
+        return Some((0, text.parse().ok()?));
+    };
+    let a = match &text[..n_index] {
+        "" | "+" => 1,
+        "-" => -1,
+        a => a.parse().ok()?,
+    };
+    let b = match &text[n_index + 1..] {
+        "" => 0,
+        b => b.strip_prefix('+').unwrap_or(b).parse().ok()?,
+    };
+    Some((a, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::tokenizer::tokenize;
+    use crate::dom::{ElementState, ElementType};
+    use std::collections::HashMap;
+
+    fn parse(selector: &str) -> Selector {
+        parse_selector_list(&tokenize(selector)).unwrap().remove(0)
+    }
+
+    fn element(tag: &str, attributes: &[(&str, &str)], children: Vec<Node>) -> Node {
+        Node::Element(Element {
+            element_type: ElementType::from_str(tag),
+            attributes: attributes
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>(),
+            children,
+            state: ElementState::default(),
+        })
+    }
+
+    #[test]
+    fn test_specificity() {
+        assert_eq!(parse("*").specificity(), (0, 0, 0));
+        assert_eq!(parse("li").specificity(), (0, 0, 1));
+        assert_eq!(parse("ul li.red").specificity(), (0, 1, 2));
+        assert_eq!(parse("#x a[href]:hover").specificity(), (1, 2, 1));
+        assert_eq!(parse("p:not(#a, .b)::before").specificity(), (1, 0, 2));
+    }
+
+    #[test]
+    fn test_invalid_selectors() {
+        for selector in ["", "a >", "> a", "a::before b", "::foo", ":unknown", "a..b"] {
+            assert!(
+                parse_selector_list(&tokenize(selector)).is_none(),
+                "{} should be invalid",
+                selector
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_nth() {
+        assert_eq!(parse_nth(&tokenize("odd")), Some((2, 1)));
+        assert_eq!(parse_nth(&tokenize("2n+1")), Some((2, 1)));
+        assert_eq!(parse_nth(&tokenize("-n + 3")), Some((-1, 3)));
+        assert_eq!(parse_nth(&tokenize("4")), Some((0, 4)));
+        assert_eq!(parse_nth(&tokenize("3n-2")), Some((3, -2)));
+    }
+
+    #[test]
+    fn test_matching() {
+        let list = element(
+            "ul",
+            &[("id", "list")],
+            vec![
+                element("li", &[("class", "a b")], vec![]),
+                Node::new_text(" "),
+                element("li", &[("lang", "en-GB")], vec![]),
+                element("li", &[], vec![]),
+            ],
+        );
+        let root_nodes = vec![list];
+        let Node::Element(ul) = &root_nodes[0] else {
+            unreachable!()
+        };
+        let ul_ref = ElementRef::new(ul, None, &root_nodes, 0);
+        let item = |index: usize| {
+            let Node::Element(li) = &ul.children[index] else {
+                unreachable!()
+            };
+            ElementRef::new(li, Some(&ul_ref), &ul.children, index)
+        };
+
+        assert!(parse("#list > li.a.b").matches(&item(0)));
+        assert!(parse("ul li:first-child").matches(&item(0)));
+        assert!(!parse("li:first-child").matches(&item(2)));
+        assert!(parse("li + li[lang|=en]").matches(&item(2)));
+        assert!(parse(".a ~ li:last-child").matches(&item(3)));
+        assert!(parse("li:nth-child(2n+1)").matches(&item(3)));
+        assert!(!parse("li:nth-child(odd)").matches(&item(2)));
+        assert!(parse("li:not(.a)").matches(&item(2)));
+        assert!(parse(":root").matches(&ul_ref));
+        assert!(!parse("div li").matches(&item(0)));
+        assert!(parse("[LANG^=EN i]").matches(&item(2)));
+    }
+}
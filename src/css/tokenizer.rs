@@ -0,0 +1,486 @@
+// CSS tokenizer following the CSS Syntax Level 3 tokenization rules
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Ident(String),
+    Function(String), // Name of the function, the opening paren is implied
+    AtKeyword(String),
+    Hash(String),
+    String(String),
+    BadString,
+    Url(String),
+    BadUrl,
+    Delim(char),
+    Number(f32),
+    Percentage(f32),
+    Dimension(f32, String),
+    Whitespace,
+    Cdo,
+    Cdc,
+    Colon,
+    Semicolon,
+    Comma,
+    OpenSquare,
+    CloseSquare,
+    OpenParen,
+    CloseParen,
+    OpenCurly,
+    CloseCurly,
+}
+
+impl Token {
+    pub fn is_whitespace(&self) -> bool {
+        matches!(self, Token::Whitespace)
+    }
+
+    // Returns true if the token opens a block that must be closed by a matching token
+    pub fn opens_block(&self) -> bool {
+        matches!(
+            self,
+            Token::Function(_) | Token::OpenParen | Token::OpenSquare | Token::OpenCurly
+        )
+    }
+
+    pub fn closes_block(&self) -> bool {
+        matches!(
+            self,
+            Token::CloseParen | Token::CloseSquare | Token::CloseCurly
+        )
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(s) => write!(f, "{}", s),
+            Token::Function(s) => write!(f, "{}(", s),
+            Token::AtKeyword(s) => write!(f, "@{}", s),
+            Token::Hash(s) => write!(f, "#{}", s),
+            Token::String(s) => write!(f, "\"{}\"", s.replace('"', "\\\"")),
+            Token::BadString => Ok(()),
+            Token::Url(s) => write!(f, "url({})", s),
+            Token::BadUrl => Ok(()),
+            Token::Delim(c) => write!(f, "{}", c),
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Percentage(n) => write!(f, "{}%", n),
+            Token::Dimension(n, unit) => write!(f, "{}{}", n, unit),
+            Token::Whitespace => write!(f, " "),
+            Token::Cdo => write!(f, "<!--"),
+            Token::Cdc => write!(f, "-->"),
+            Token::Colon => write!(f, ":"),
+            Token::Semicolon => write!(f, ";"),
+            Token::Comma => write!(f, ","),
+            Token::OpenSquare => write!(f, "["),
+            Token::CloseSquare => write!(f, "]"),
+            Token::OpenParen => write!(f, "("),
+            Token::CloseParen => write!(f, ")"),
+            Token::OpenCurly => write!(f, "{{"),
+            Token::CloseCurly => write!(f, "}}"),
+        }
+    }
+}
+
+// Serializes a list of tokens back into CSS text
+pub fn serialize(tokens: &[Token]) -> String {
+    tokens.iter().map(|token| token.to_string()).collect()
+}
+
+// Splits CSS text into tokens. Comments are dropped.
+pub fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokenizer = Tokenizer {
+        chars: input.chars().collect(),
+        pos: 0,
+    };
+    let mut tokens = Vec::new();
+    while let Some(token) = tokenizer.next_token() {
+        tokens.push(token);
+    }
+    tokens
+}
+
+struct Tokenizer {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Tokenizer {
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn next_token(&mut self) -> Option<Token> {
+        self.skip_comments();
+        let c = self.peek(0)?;
+        let token = match c {
+            c if is_whitespace(c) => {
+                while self.peek(0).is_some_and(is_whitespace) {
+                    self.pos += 1;
+                }
+                Token::Whitespace
+            }
+            '"' | '\'' => {
+                self.pos += 1;
+                self.consume_string(c)
+            }
+            '#' => {
+                if self.peek(1).is_some_and(is_name_char) || self.is_valid_escape(1) {
+                    self.pos += 1;
+                    Token::Hash(self.consume_name())
+                } else {
+                    self.pos += 1;
+                    Token::Delim('#')
+                }
+            }
+            '(' => self.single(Token::OpenParen),
+            ')' => self.single(Token::CloseParen),
+            '[' => self.single(Token::OpenSquare),
+            ']' => self.single(Token::CloseSquare),
+            '{' => self.single(Token::OpenCurly),
+            '}' => self.single(Token::CloseCurly),
+            ',' => self.single(Token::Comma),
+            ':' => self.single(Token::Colon),
+            ';' => self.single(Token::Semicolon),
+            '+' | '.' if self.starts_number() => self.consume_numeric(),
+            '-' => {
+                if self.starts_number() {
+                    self.consume_numeric()
+                } else if self.peek(1) == Some('-') && self.peek(2) == Some('>') {
+                    self.pos += 3;
+                    Token::Cdc
+                } else if self.starts_ident(0) {
+                    self.consume_ident_like()
+                } else {
+                    self.single(Token::Delim('-'))
+                }
+            }
+            '<' if self.peek(1) == Some('!')
+                && self.peek(2) == Some('-')
+                && self.peek(3) == Some('-') =>
+            {
+                self.pos += 4;
+                Token::Cdo
+            }
+            '@' => {
+                if self.starts_ident(1) {
+                    self.pos += 1;
+                    Token::AtKeyword(self.consume_name())
+                } else {
+                    self.single(Token::Delim('@'))
+                }
+            }
+            '\\' if self.is_valid_escape(0) => self.consume_ident_like(),
+            c if c.is_ascii_digit() => self.consume_numeric(),
+            c if is_name_start(c) => self.consume_ident_like(),
+            c => self.single(Token::Delim(c)),
+        };
+        Some(token)
+    }
+
+    fn single(&mut self, token: Token) -> Token {
+        self.pos += 1;
+        token
+    }
+
+    fn skip_comments(&mut self) {
+        while self.peek(0) == Some('/') && self.peek(1) == Some('*') {
+            self.pos += 2;
+            while self.peek(0).is_some()
+                && !(self.peek(0) == Some('*') && self.peek(1) == Some('/'))
+            {
+                self.pos += 1;
+            }
+            self.pos = (self.pos + 2).min(self.chars.len());
+        }
+    }
+
+    fn is_valid_escape(&self, offset: usize) -> bool {
+        self.peek(offset) == Some('\\') && self.peek(offset + 1).is_some_and(|c| c != '\n')
+    }
+
+    fn starts_ident(&self, offset: usize) -> bool {
+        match self.peek(offset) {
+            Some('-') => {
+                self.peek(offset + 1)
+                    .is_some_and(|c| is_name_start(c) || c == '-')
+                    || self.is_valid_escape(offset + 1)
+            }
+            Some('\\') => self.is_valid_escape(offset),
+            Some(c) => is_name_start(c),
+            None => false,
+        }
+    }
+
+    fn starts_number(&self) -> bool {
+        let digit = |offset: usize| self.peek(offset).is_some_and(|c| c.is_ascii_digit());
+        match self.peek(0) {
+            Some('+') | Some('-') => digit(1) || (self.peek(1) == Some('.') && digit(2)),
+            Some('.') => digit(1),
+            Some(c) => c.is_ascii_digit(),
+            None => false,
+        }
+    }
+
+    fn consume_escape(&mut self) -> char {
+        // Assumes the backslash has already been consumed
+        let mut hex = String::new();
+        while hex.len() < 6 && self.peek(0).is_some_and(|c| c.is_ascii_hexdigit()) {
+            hex.push(self.peek(0).unwrap());
+            self.pos += 1;
+        }
+        if hex.is_empty() {
+            let c = self.peek(0).unwrap_or('\u{FFFD}');
+            self.pos += 1;
+            return c;
+        }
+        if self.peek(0).is_some_and(is_whitespace) {
+            self.pos += 1;
+        }
+        u32::from_str_radix(&hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .filter(|c| *c != '\0')
+            .unwrap_or('\u{FFFD}')
+    }
+
+    fn consume_name(&mut self) -> String {
+        let mut name = String::new();
+        loop {
+            match self.peek(0) {
+                Some(c) if is_name_char(c) => {
+                    name.push(c);
+                    self.pos += 1;
+                }
+                Some('\\') if self.is_valid_escape(0) => {
+                    self.pos += 1;
+                    name.push(self.consume_escape());
+                }
+                _ => return name,
+            }
+        }
+    }
+
+    fn consume_number(&mut self) -> f32 {
+        let start = self.pos;
+        if matches!(self.peek(0), Some('+') | Some('-')) {
+            self.pos += 1;
+        }
+        while self.peek(0).is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if self.peek(0) == Some('.') && self.peek(1).is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+            while self.peek(0).is_some_and(|c| c.is_ascii_digit()) {
+                self.pos += 1;
+            }
+        }
+        if matches!(self.peek(0), Some('e') | Some('E')) {
+            let exponent_digit = match self.peek(1) {
+                Some('+') | Some('-') => 2,
+                _ => 1,
+            };
+            if self
+                .peek(exponent_digit)
+                .is_some_and(|c| c.is_ascii_digit())
+            {
+                self.pos += exponent_digit;
+                while self.peek(0).is_some_and(|c| c.is_ascii_digit()) {
+                    self.pos += 1;
+                }
+            }
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse().unwrap_or(0.0)
+    }
+
+    fn consume_numeric(&mut self) -> Token {
+        let number = self.consume_number();
+        if self.starts_ident(0) {
+            let unit = self.consume_name();
+            Token::Dimension(number, unit)
+        } else if self.peek(0) == Some('%') {
+            self.pos += 1;
+            Token::Percentage(number)
+        } else {
+            Token::Number(number)
+        }
+    }
+
+    fn consume_ident_like(&mut self) -> Token {
+        let name = self.consume_name();
+        if self.peek(0) != Some('(') {
+            return Token::Ident(name);
+        }
+        self.pos += 1;
+        if !name.eq_ignore_ascii_case("url") {
+            return Token::Function(name);
+        }
+        // url( followed by a quoted string is a regular function token
+        let mut offset = 0;
+        while self.peek(offset).is_some_and(is_whitespace) {
+            offset += 1;
+        }
+        if matches!(self.peek(offset), Some('"') | Some('\'')) {
+            return Token::Function(name);
+        }
+        self.consume_url()
+    }
+
+    fn consume_url(&mut self) -> Token {
+        let mut url = String::new();
+        while self.peek(0).is_some_and(is_whitespace) {
+            self.pos += 1;
+        }
+        loop {
+            match self.peek(0) {
+                None => return Token::Url(url),
+                Some(')') => {
+                    self.pos += 1;
+                    return Token::Url(url);
+                }
+                Some(c) if is_whitespace(c) => {
+                    while self.peek(0).is_some_and(is_whitespace) {
+                        self.pos += 1;
+                    }
+                    if matches!(self.peek(0), Some(')') | None) {
+                        continue;
+                    }
+                    self.consume_bad_url();
+                    return Token::BadUrl;
+                }
+                Some('"') | Some('\'') | Some('(') => {
+                    self.consume_bad_url();
+                    return Token::BadUrl;
+                }
+                Some('\\') => {
+                    if self.is_valid_escape(0) {
+                        self.pos += 1;
+                        url.push(self.consume_escape());
+                    } else {
+                        self.consume_bad_url();
+                        return Token::BadUrl;
+                    }
+                }
+                Some(c) => {
+                    url.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn consume_bad_url(&mut self) {
+        while let Some(c) = self.peek(0) {
+            self.pos += 1;
+            if c == ')' {
+                return;
+            }
+            if c == '\\' && self.peek(0).is_some() {
+                self.pos += 1;
+            }
+        }
+    }
+
+    fn consume_string(&mut self, quote: char) -> Token {
+        let mut value = String::new();
+        loop {
+            match self.peek(0) {
+                None => return Token::String(value),
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    return Token::String(value);
+                }
+                Some('\n') => return Token::BadString,
+                Some('\\') => {
+                    self.pos += 1;
+                    match self.peek(0) {
+                        None => {}
+                        Some('\n') => self.pos += 1,
+                        Some(_) => value.push(self.consume_escape()),
+                    }
+                }
+                Some(c) => {
+                    value.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+}
+
+fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0C')
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || !c.is_ascii()
+}
+
+fn is_name_char(c: char) -> bool {
+    is_name_start(c) || c.is_ascii_digit() || c == '-'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_declaration() {
+        let tokens = tokenize("color: red;");
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Ident("color".to_string()),
+                Token::Colon,
+                Token::Whitespace,
+                Token::Ident("red".to_string()),
+                Token::Semicolon,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_numbers() {
+        let tokens = tokenize("10px 50% -1.5 2e2 .5em");
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Dimension(10.0, "px".to_string()),
+                Token::Whitespace,
+                Token::Percentage(50.0),
+                Token::Whitespace,
+                Token::Number(-1.5),
+                Token::Whitespace,
+                Token::Number(200.0),
+                Token::Whitespace,
+                Token::Dimension(0.5, "em".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_custom_property_and_functions() {
+        let tokens = tokenize("--main-color: var(--accent, #fff)");
+        assert_eq!(tokens[0], Token::Ident("--main-color".to_string()));
+        assert_eq!(tokens[3], Token::Function("var".to_string()));
+        assert_eq!(tokens[4], Token::Ident("--accent".to_string()));
+        assert_eq!(tokens[7], Token::Hash("fff".to_string()));
+        assert_eq!(tokens[8], Token::CloseParen);
+    }
+
+    #[test]
+    fn test_tokenize_strings_urls_and_comments() {
+        let tokens = tokenize("/* comment */url(a.png) url('b.png') \"it\\\"s\"");
+        assert_eq!(tokens[0], Token::Url("a.png".to_string()));
+        assert_eq!(tokens[2], Token::Function("url".to_string()));
+        assert_eq!(tokens[3], Token::String("b.png".to_string()));
+        assert_eq!(tokens[6], Token::String("it\"s".to_string()));
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let css = "calc(1px + 2em) #abc";
+        assert_eq!(serialize(&tokenize(css)), css);
+    }
+}
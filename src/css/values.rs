@@ -0,0 +1,801 @@
+// Typed CSS values and conversion from tokens into computed values
+
+use crate::css::tokenizer::Token;
+
+/// An sRGB color with alpha
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color::rgba(0, 0, 0, 0);
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b, a: 255 }
+    }
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    pub fn is_transparent(&self) -> bool {
+        self.a == 0
+    }
+
+    // Parses the digits of a hex color such as `fff` or `ff000080`
+    pub fn from_hex(hex: &str) -> Option<Color> {
+        let digit = |i: usize| u8::from_str_radix(hex.get(i..i + 1)?, 16).ok();
+        let pair = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        match hex.len() {
+            3 | 4 => {
+                let a = match hex.len() {
+                    4 => digit(3)? * 17,
+                    _ => 255,
+                };
+                Some(Color::rgba(
+                    digit(0)? * 17,
+                    digit(1)? * 17,
+                    digit(2)? * 17,
+                    a,
+                ))
+            }
+            6 | 8 => {
+                let a = match hex.len() {
+                    8 => pair(6)?,
+                    _ => 255,
+                };
+                Some(Color::rgba(pair(0)?, pair(2)?, pair(4)?, a))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Color> {
+        let name = name.to_ascii_lowercase();
+        if name == "transparent" {
+            return Some(Color::TRANSPARENT);
+        }
+        NAMED_COLORS
+            .iter()
+            .find(|(color_name, _)| *color_name == name)
+            .map(|(_, rgb)| Color::rgb((rgb >> 16) as u8, (rgb >> 8) as u8, *rgb as u8))
+    }
+
+    // Converts hue (degrees), saturation and lightness (0-1) to a color
+    pub fn from_hsla(hue: f32, saturation: f32, lightness: f32, alpha: f32) -> Color {
+        let hue = hue.rem_euclid(360.0) / 360.0;
+        let saturation = saturation.clamp(0.0, 1.0);
+        let lightness = lightness.clamp(0.0, 1.0);
+        let q = match lightness < 0.5 {
+            true => lightness * (1.0 + saturation),
+            false => lightness + saturation - lightness * saturation,
+        };
+        let p = 2.0 * lightness - q;
+        let channel = |t: f32| {
+            let t = t.rem_euclid(1.0);
+            let value = if t < 1.0 / 6.0 {
+                p + (q - p) * 6.0 * t
+            } else if t < 0.5 {
+                q
+            } else if t < 2.0 / 3.0 {
+                p + (q - p) * (2.0 / 3.0 - t) * 6.0
+            } else {
+                p
+            };
+            (value * 255.0).round() as u8
+        };
+        Color::rgba(
+            channel(hue + 1.0 / 3.0),
+            channel(hue),
+            channel(hue - 1.0 / 3.0),
+            (alpha.clamp(0.0, 1.0) * 255.0).round() as u8,
+        )
+    }
+}
+
+/// A computed CSS value. Absolute and font relative lengths are already converted to pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Keyword(String),
+    Length(f32),
+    Percentage(f32),
+    Calc(f32, f32), // pixels plus a percentage of the reference length
+    Number(f32),
+    Dimension(f32, String), // Units that aren't lengths, such as fr or deg
+    Color(Color),
+    Str(String),
+    Url(String),
+    Function(String, Vec<Value>),
+    List(Vec<Value>),      // Space separated values
+    CommaList(Vec<Value>), // Comma separated values
+    Slash,
+    LineNames(Vec<String>), // Grid line names in square brackets
+}
+
+impl Value {
+    pub fn keyword(keyword: &str) -> Value {
+        Value::Keyword(keyword.to_string())
+    }
+
+    pub fn as_keyword(&self) -> Option<&str> {
+        match self {
+            Value::Keyword(keyword) => Some(keyword),
+            _ => None,
+        }
+    }
+
+    pub fn is_keyword(&self, keyword: &str) -> bool {
+        self.as_keyword() == Some(keyword)
+    }
+
+    pub fn as_number(&self) -> Option<f32> {
+        match self {
+            Value::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    // Returns the items of a list, or the value itself as a single item
+    pub fn items(&self) -> &[Value] {
+        match self {
+            Value::List(items) | Value::CommaList(items) => items,
+            value => std::slice::from_ref(value),
+        }
+    }
+
+    pub fn is_length_percentage(&self) -> bool {
+        match self {
+            Value::Length(_) | Value::Percentage(_) | Value::Calc(_, _) => true,
+            Value::Number(number) => *number == 0.0,
+            Value::Function(name, _) => matches!(name.as_str(), "min" | "max" | "clamp"),
+            _ => false,
+        }
+    }
+
+    // Resolves a length or percentage to pixels, with percentages relative to `basis`
+    pub fn to_px(&self, basis: f32) -> Option<f32> {
+        match self {
+            Value::Length(px) => Some(*px),
+            Value::Percentage(percent) => Some(basis * percent / 100.0),
+            Value::Calc(px, percent) => Some(px + basis * percent / 100.0),
+            Value::Number(number) if *number == 0.0 => Some(0.0),
+            Value::Function(name, arguments) => {
+                let resolved: Option<Vec<f32>> = arguments
+                    .iter()
+                    .map(|argument| argument.to_px(basis))
+                    .collect();
+                let resolved = resolved?;
+                match (name.as_str(), resolved.as_slice()) {
+                    ("min", values) if !values.is_empty() => {
+                        Some(values.iter().copied().fold(f32::INFINITY, f32::min))
+                    }
+                    ("max", values) if !values.is_empty() => {
+                        Some(values.iter().copied().fold(f32::NEG_INFINITY, f32::max))
+                    }
+                    ("clamp", [min, value, max]) => Some(value.min(*max).max(*min)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    // Resolves the value to a color, using `current_color` for `currentcolor`
+    pub fn to_color(&self, current_color: Color) -> Option<Color> {
+        match self {
+            Value::Color(color) => Some(*color),
+            Value::Keyword(keyword) if keyword == "currentcolor" => Some(current_color),
+            Value::Keyword(keyword) => Color::from_name(keyword),
+            _ => None,
+        }
+    }
+}
+
+/// Values needed to convert relative lengths into pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LengthContext {
+    pub font_size: f32,
+    pub root_font_size: f32,
+    pub viewport_width: f32,
+    pub viewport_height: f32,
+}
+
+impl LengthContext {
+    pub fn length_to_px(&self, number: f32, unit: &str) -> Option<f32> {
+        let factor = match unit.to_ascii_lowercase().as_str() {
+            "px" => 1.0,
+            "em" => self.font_size,
+            "rem" => self.root_font_size,
+            "ex" | "ch" => self.font_size * 0.5,
+            "vw" => self.viewport_width / 100.0,
+            "vh" => self.viewport_height / 100.0,
+            "vmin" => self.viewport_width.min(self.viewport_height) / 100.0,
+            "vmax" => self.viewport_width.max(self.viewport_height) / 100.0,
+            "in" => 96.0,
+            "cm" => 96.0 / 2.54,
+            "mm" => 96.0 / 25.4,
+            "q" => 96.0 / 101.6,
+            "pt" => 96.0 / 72.0,
+            "pc" => 16.0,
+            _ => return None,
+        };
+        Some(number * factor)
+    }
+}
+
+// Converts a list of tokens into a computed value. Returns None if the value is malformed.
+pub fn parse_value(tokens: &[Token], context: &LengthContext) -> Option<Value> {
+    let mut comma_parts = Vec::new();
+    let mut current = Vec::new();
+    let mut pos = 0;
+    while pos < tokens.len() {
+        match &tokens[pos] {
+            Token::Whitespace => pos += 1,
+            Token::Comma => {
+                comma_parts.push(list_or_single(std::mem::take(&mut current))?);
+                pos += 1;
+            }
+            _ => current.push(parse_component(tokens, &mut pos, context)?),
+        }
+    }
+    let last = list_or_single(current);
+    if comma_parts.is_empty() {
+        return last;
+    }
+    comma_parts.push(last?);
+    Some(Value::CommaList(comma_parts))
+}
+
+fn list_or_single(mut values: Vec<Value>) -> Option<Value> {
+    match values.len() {
+        0 => None,
+        1 => values.pop(),
+        _ => Some(Value::List(values)),
+    }
+}
+
+// Parses a single component value starting at `pos`, advancing past it
+fn parse_component(tokens: &[Token], pos: &mut usize, context: &LengthContext) -> Option<Value> {
+    let token = &tokens[*pos];
+    *pos += 1;
+    let value = match token {
+        Token::Ident(ident) => Value::Keyword(ident.to_ascii_lowercase()),
+        Token::Number(number) => Value::Number(*number),
+        Token::Percentage(percent) => Value::Percentage(*percent),
+        Token::Dimension(number, unit) => match context.length_to_px(*number, unit) {
+            Some(px) => Value::Length(px),
+            None => Value::Dimension(*number, unit.to_ascii_lowercase()),
+        },
+        Token::Hash(hex) => Value::Color(Color::from_hex(hex)?),
+        Token::String(string) => Value::Str(string.clone()),
+        Token::Url(url) => Value::Url(url.clone()),
+        Token::Delim('/') => Value::Slash,
+        Token::OpenSquare => {
+            let mut names = Vec::new();
+            loop {
+                match tokens.get(*pos)? {
+                    Token::CloseSquare => break,
+                    Token::Ident(name) => names.push(name.to_ascii_lowercase()),
+                    Token::Whitespace => {}
+                    _ => return None,
+                }
+                *pos += 1;
+            }
+            *pos += 1;
+            Value::LineNames(names)
+        }
+        Token::Delim(c) => Value::Keyword(c.to_string()),
+        Token::Function(name) => {
+            let start = *pos;
+            let mut depth = 1;
+            while depth > 0 {
+                let token = tokens.get(*pos)?;
+                if token.opens_block() {
+                    depth += 1;
+                } else if token.closes_block() {
+                    depth -= 1;
+                }
+                *pos += 1;
+            }
+            let arguments = &tokens[start..*pos - 1];
+            parse_function(&name.to_ascii_lowercase(), arguments, context)?
+        }
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn parse_function(name: &str, arguments: &[Token], context: &LengthContext) -> Option<Value> {
+    match name {
+        "rgb" | "rgba" => parse_rgb(arguments, context),
+        "hsl" | "hsla" => parse_hsl(arguments, context),
+        "calc" => {
+            let mut calc = CalcParser {
+                tokens: arguments,
+                pos: 0,
+                context,
+            };
+            let result = calc.parse_sum()?;
+            calc.skip_whitespace();
+            if calc.pos != arguments.len() {
+                return None;
+            }
+            Some(result.into_value())
+        }
+        "url" => match parse_value(arguments, context)? {
+            Value::Str(url) => Some(Value::Url(url)),
+            _ => None,
+        },
+        _ => {
+            let arguments = match parse_value(arguments, context) {
+                Some(Value::CommaList(items)) => items,
+                Some(value) => vec![value],
+                None => Vec::new(),
+            };
+            // min(), max() and clamp() fold to a length when no percentages are involved
+            let value = Value::Function(name.to_string(), arguments);
+            if matches!(name, "min" | "max" | "clamp")
+                && !contains_percentage(&value)
+                && value.is_length_percentage()
+            {
+                return Some(Value::Length(value.to_px(0.0)?));
+            }
+            Some(value)
+        }
+    }
+}
+
+fn contains_percentage(value: &Value) -> bool {
+    match value {
+        Value::Percentage(_) | Value::Calc(_, _) => true,
+        Value::Function(_, items) | Value::List(items) | Value::CommaList(items) => {
+            items.iter().any(contains_percentage)
+        }
+        _ => false,
+    }
+}
+
+// Collects the numeric channels of a color function, accepting both the legacy comma
+// syntax and the modern space syntax with an optional `/ alpha`
+fn color_channels(arguments: &[Token], context: &LengthContext) -> Option<Vec<Value>> {
+    let value = parse_value(arguments, context)?;
+    let mut channels = Vec::new();
+    for item in value.items() {
+        match item {
+            Value::List(inner) => channels.extend(inner.iter().cloned()),
+            item => channels.push(item.clone()),
+        }
+    }
+    channels.retain(|channel| *channel != Value::Slash);
+    match channels.len() {
+        3 | 4 => Some(channels),
+        _ => None,
+    }
+}
+
+fn alpha_channel(channels: &[Value]) -> Option<f32> {
+    match channels.get(3) {
+        None => Some(1.0),
+        Some(Value::Number(alpha)) => Some(*alpha),
+        Some(Value::Percentage(alpha)) => Some(alpha / 100.0),
+        Some(_) => None,
+    }
+}
+
+fn parse_rgb(arguments: &[Token], context: &LengthContext) -> Option<Value> {
+    let channels = color_channels(arguments, context)?;
+    let channel = |value: &Value| -> Option<u8> {
+        let value = match value {
+            Value::Number(number) => *number,
+            Value::Percentage(percent) => percent * 2.55,
+            _ => return None,
+        };
+        Some(value.round().clamp(0.0, 255.0) as u8)
+    };
+    let alpha = alpha_channel(&channels)?;
+    Some(Value::Color(Color::rgba(
+        channel(&channels[0])?,
+        channel(&channels[1])?,
+        channel(&channels[2])?,
+        (alpha.clamp(0.0, 1.0) * 255.0).round() as u8,
+    )))
+}
+
+fn parse_hsl(arguments: &[Token], context: &LengthContext) -> Option<Value> {
+    let channels = color_channels(arguments, context)?;
+    let hue = match &channels[0] {
+        Value::Number(degrees) => *degrees,
+        Value::Dimension(angle, unit) => match unit.as_str() {
+            "deg" => *angle,
+            "turn" => angle * 360.0,
+            "rad" => angle.to_degrees(),
+            "grad" => angle * 0.9,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let fraction = |value: &Value| match value {
+        Value::Percentage(percent) => Some(percent / 100.0),
+        Value::Number(number) => Some(number / 100.0),
+        _ => None,
+    };
+    Some(Value::Color(Color::from_hsla(
+        hue,
+        fraction(&channels[1])?,
+        fraction(&channels[2])?,
+        alpha_channel(&channels)?,
+    )))
+}
+
+// The value of a calc() expression, tracking pixels, percentages and plain numbers separately
+#[derive(Debug, Clone, Copy, Default)]
+struct CalcResult {
+    px: f32,
+    percent: f32,
+    number: f32,
+    is_number: bool,
+}
+
+impl CalcResult {
+    fn number(number: f32) -> Self {
+        Self {
+            number,
+            is_number: true,
+            ..Default::default()
+        }
+    }
+
+    fn into_value(self) -> Value {
+        match (self.is_number, self.percent == 0.0) {
+            (true, _) => Value::Number(self.number),
+            (false, true) => Value::Length(self.px),
+            (false, false) if self.px == 0.0 => Value::Percentage(self.percent),
+            (false, false) => Value::Calc(self.px, self.percent),
+        }
+    }
+
+    fn scale(self, factor: f32) -> Self {
+        Self {
+            px: self.px * factor,
+            percent: self.percent * factor,
+            number: self.number * factor,
+            is_number: self.is_number,
+        }
+    }
+}
+
+struct CalcParser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    context: &'a LengthContext,
+}
+
+impl CalcParser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.tokens.get(self.pos).is_some_and(Token::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn parse_sum(&mut self) -> Option<CalcResult> {
+        let mut result = self.parse_product()?;
+        loop {
+            self.skip_whitespace();
+            let sign = match self.tokens.get(self.pos) {
+                Some(Token::Delim('+')) => 1.0,
+                Some(Token::Delim('-')) => -1.0,
+                _ => return Some(result),
+            };
+            self.pos += 1;
+            let rhs = self.parse_product()?.scale(sign);
+            if rhs.is_number != result.is_number {
+                return None;
+            }
+            result.px += rhs.px;
+            result.percent += rhs.percent;
+            result.number += rhs.number;
+        }
+    }
+
+    fn parse_product(&mut self) -> Option<CalcResult> {
+        let mut result = self.parse_term()?;
+        loop {
+            self.skip_whitespace();
+            let operator = match self.tokens.get(self.pos) {
+                Some(Token::Delim('*')) => '*',
+                Some(Token::Delim('/')) => '/',
+                _ => return Some(result),
+            };
+            self.pos += 1;
+            let rhs = self.parse_term()?;
+            result = match (operator, result.is_number, rhs.is_number) {
+                ('*', _, true) => result.scale(rhs.number),
+                ('*', true, false) => rhs.scale(result.number),
+                ('/', _, true) if rhs.number != 0.0 => result.scale(1.0 / rhs.number),
+                _ => return None,
+            };
+        }
+    }
+
+    fn parse_term(&mut self) -> Option<CalcResult> {
+        self.skip_whitespace();
+        let token = self.tokens.get(self.pos)?;
+        self.pos += 1;
+        match token {
+            Token::Number(number) => Some(CalcResult::number(*number)),
+            Token::Percentage(percent) => Some(CalcResult {
+                percent: *percent,
+                ..Default::default()
+            }),
+            Token::Dimension(number, unit) => Some(CalcResult {
+                px: self.context.length_to_px(*number, unit)?,
+                ..Default::default()
+            }),
+            Token::OpenParen => self.parse_nested(),
+            Token::Function(name) if name.eq_ignore_ascii_case("calc") => self.parse_nested(),
+            _ => None,
+        }
+    }
+
+    fn parse_nested(&mut self) -> Option<CalcResult> {
+        let result = self.parse_sum()?;
+        self.skip_whitespace();
+        match self.tokens.get(self.pos) {
+            Some(Token::CloseParen) => {
+                self.pos += 1;
+                Some(result)
+            }
+            _ => None,
+        }
+    }
+}
+
+// CSS named colors as 0xRRGGBB
+const NAMED_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::tokenizer::tokenize;
+
+    const CONTEXT: LengthContext = LengthContext {
+        font_size: 20.0,
+        root_font_size: 16.0,
+        viewport_width: 800.0,
+        viewport_height: 600.0,
+    };
+
+    fn parse(css: &str) -> Option<Value> {
+        parse_value(&tokenize(css), &CONTEXT)
+    }
+
+    #[test]
+    fn test_lengths() {
+        assert_eq!(parse("10px"), Some(Value::Length(10.0)));
+        assert_eq!(parse("2em"), Some(Value::Length(40.0)));
+        assert_eq!(parse("1rem"), Some(Value::Length(16.0)));
+        assert_eq!(parse("50vw"), Some(Value::Length(400.0)));
+        assert_eq!(parse("1in"), Some(Value::Length(96.0)));
+        assert_eq!(parse("1fr"), Some(Value::Dimension(1.0, "fr".to_string())));
+    }
+
+    #[test]
+    fn test_colors() {
+        assert_eq!(parse("#f00"), Some(Value::Color(Color::rgb(255, 0, 0))));
+        assert_eq!(
+            parse("#00ff0080"),
+            Some(Value::Color(Color::rgba(0, 255, 0, 128)))
+        );
+        assert_eq!(
+            parse("rgb(10, 20, 30)"),
+            Some(Value::Color(Color::rgb(10, 20, 30)))
+        );
+        assert_eq!(
+            parse("rgb(10 20 30 / 50%)"),
+            Some(Value::Color(Color::rgba(10, 20, 30, 128)))
+        );
+        assert_eq!(
+            parse("hsl(120, 100%, 50%)"),
+            Some(Value::Color(Color::rgb(0, 255, 0)))
+        );
+        assert_eq!(parse("#ggg"), None);
+        assert_eq!(
+            Value::keyword("rebeccapurple").to_color(Color::BLACK),
+            Some(Color::rgb(0x66, 0x33, 0x99))
+        );
+    }
+
+    #[test]
+    fn test_lists() {
+        assert_eq!(
+            parse("1px solid red"),
+            Some(Value::List(vec![
+                Value::Length(1.0),
+                Value::keyword("solid"),
+                Value::keyword("red"),
+            ]))
+        );
+        assert_eq!(
+            parse("Arial, \"Helvetica Neue\""),
+            Some(Value::CommaList(vec![
+                Value::keyword("arial"),
+                Value::Str("Helvetica Neue".to_string()),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_calc() {
+        assert_eq!(parse("calc(10px + 1em)"), Some(Value::Length(30.0)));
+        assert_eq!(parse("calc(2 * (5px - 1px))"), Some(Value::Length(8.0)));
+        assert_eq!(parse("calc(50% - 10px)"), Some(Value::Calc(-10.0, 50.0)));
+        assert_eq!(parse("calc(1px * 2px)"), None);
+        assert_eq!(parse("calc(1px + 2)"), None);
+        assert_eq!(parse("calc(4 / 2)"), Some(Value::Number(2.0)));
+        assert_eq!(parse("calc(50% - 10px)").unwrap().to_px(200.0), Some(90.0));
+    }
+
+    #[test]
+    fn test_min_max_clamp() {
+        assert_eq!(parse("min(10px, 2em)"), Some(Value::Length(10.0)));
+        assert_eq!(parse("clamp(1px, 50px, 20px)"), Some(Value::Length(20.0)));
+        let value = parse("max(10%, 30px)").unwrap();
+        assert_eq!(value.to_px(100.0), Some(30.0));
+        assert_eq!(value.to_px(1000.0), Some(100.0));
+    }
+}
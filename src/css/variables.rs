@@ -0,0 +1,350 @@
+// Custom properties (`--name`) and var() substitution, following CSS Variables Level 1
+
+use crate::css::parser;
+use crate::css::tokenizer::Token;
+use std::collections::{HashMap, HashSet};
+
+/// Computed custom properties of an element. A missing entry is the guaranteed-invalid value.
+pub type CustomProperties = HashMap<String, Vec<Token>>;
+
+// Guards against exponential growth from properties that reference each other repeatedly
+const MAX_SUBSTITUTED_TOKENS: usize = 1 << 16;
+
+// Returns true if the tokens contain a var() reference
+pub fn contains_var(tokens: &[Token]) -> bool {
+    tokens
+        .iter()
+        .any(|token| matches!(token, Token::Function(name) if name.eq_ignore_ascii_case("var")))
+}
+
+// Returns the names of all custom properties referenced by var(), including inside fallbacks
+fn references(tokens: &[Token]) -> Vec<&str> {
+    let mut names = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if !matches!(token, Token::Function(name) if name.eq_ignore_ascii_case("var")) {
+            continue;
+        }
+        let name = tokens[i + 1..].iter().find(|t| !t.is_whitespace());
+        if let Some(Token::Ident(name)) = name {
+            names.push(name.as_str());
+        }
+    }
+    names
+}
+
+// Replaces every var() in `tokens` using `lookup`. Returns None if the result is
+// invalid at computed-value time: an unknown reference without fallback, or bad syntax.
+pub fn substitute<F>(tokens: &[Token], lookup: &mut F) -> Option<Vec<Token>>
+where
+    F: FnMut(&str) -> Option<Vec<Token>>,
+{
+    let mut output = Vec::new();
+    let mut pos = 0;
+    while pos < tokens.len() {
+        let token = &tokens[pos];
+        pos += 1;
+        if !matches!(token, Token::Function(name) if name.eq_ignore_ascii_case("var")) {
+            output.push(token.clone());
+            continue;
+        }
+        // Find the closing paren of this var()
+        let start = pos;
+        let mut depth = 1;
+        while depth > 0 {
+            let token = tokens.get(pos)?;
+            if token.opens_block() {
+                depth += 1;
+            } else if token.closes_block() {
+                depth -= 1;
+            }
+            pos += 1;
+        }
+        let arguments = &tokens[start..pos - 1];
+        output.extend(substitute_var(arguments, lookup)?);
+        if output.len() > MAX_SUBSTITUTED_TOKENS {
+            return None;
+        }
+    }
+    Some(output)
+}
+
+fn substitute_var<F>(arguments: &[Token], lookup: &mut F) -> Option<Vec<Token>>
+where
+    F: FnMut(&str) -> Option<Vec<Token>>,
+{
+    let mut pos = 0;
+    while arguments.get(pos).is_some_and(Token::is_whitespace) {
+        pos += 1;
+    }
+    let Some(Token::Ident(name)) = arguments.get(pos) else {
+        return None;
+    };
+    if !name.starts_with("--") {
+        return None;
+    }
+    pos += 1;
+    while arguments.get(pos).is_some_and(Token::is_whitespace) {
+        pos += 1;
+    }
+    let fallback = match arguments.get(pos) {
+        None => None,
+        Some(Token::Comma) => Some(&arguments[pos + 1..]),
+        Some(_) => return None,
+    };
+    if let Some(value) = lookup(name) {
+        return Some(value);
+    }
+    let mut fallback = substitute(fallback?, lookup)?;
+    parser::trim_whitespace(&mut fallback);
+    Some(fallback)
+}
+
+// Computes the custom properties of an element from its parent's and the declarations
+// that won the cascade, in cascade order. Properties involved in a dependency cycle are
+// invalid at computed-value time, as is any property whose references can't be resolved.
+pub fn compute_custom_properties(
+    parent: &CustomProperties,
+    declarations: &[(&str, &[Token])],
+) -> CustomProperties {
+    let mut computed = parent.clone();
+    // Later declarations win, CSS-wide keywords are resolved immediately
+    let mut declared: HashMap<&str, &[Token]> = HashMap::new();
+    for (name, value) in declarations {
+        let keyword = match value {
+            [Token::Ident(keyword)] => Some(keyword.to_ascii_lowercase()),
+            _ => None,
+        };
+        match keyword.as_deref() {
+            Some("inherit" | "unset" | "revert") => {
+                declared.remove(name);
+                match parent.get(*name) {
+                    Some(inherited) => computed.insert(name.to_string(), inherited.clone()),
+                    None => computed.remove(*name),
+                };
+            }
+            Some("initial") => {
+                declared.remove(name);
+                computed.remove(*name);
+            }
+            _ => {
+                declared.insert(name, value);
+            }
+        }
+    }
+
+    let cyclic = find_cycles(&declared);
+    let mut resolver = Resolver {
+        parent,
+        declared: &declared,
+        cyclic: &cyclic,
+        resolved: HashMap::new(),
+    };
+    let names: Vec<&str> = declared.keys().copied().collect();
+    for name in names {
+        match resolver.resolve(name) {
+            Some(value) => computed.insert(name.to_string(), value),
+            None => computed.remove(name),
+        };
+    }
+    computed
+}
+
+struct Resolver<'a> {
+    parent: &'a CustomProperties,
+    declared: &'a HashMap<&'a str, &'a [Token]>,
+    cyclic: &'a HashSet<&'a str>,
+    resolved: HashMap<String, Option<Vec<Token>>>,
+}
+
+impl Resolver<'_> {
+    fn resolve(&mut self, name: &str) -> Option<Vec<Token>> {
+        if let Some(resolved) = self.resolved.get(name) {
+            return resolved.clone();
+        }
+        let Some(value) = self.declared.get(name).copied() else {
+            return self.parent.get(name).cloned();
+        };
+        let resolved = match self.cyclic.contains(name) {
+            true => None,
+            false => substitute(value, &mut |reference| self.resolve(reference)),
+        };
+        self.resolved.insert(name.to_string(), resolved.clone());
+        resolved
+    }
+}
+
+// Finds all declared properties that are part of a reference cycle, using Tarjan's
+// strongly connected components algorithm
+fn find_cycles<'a>(declared: &HashMap<&'a str, &'a [Token]>) -> HashSet<&'a str> {
+    struct Tarjan<'a, 'b> {
+        declared: &'b HashMap<&'a str, &'a [Token]>,
+        index: HashMap<&'a str, usize>,
+        low_link: HashMap<&'a str, usize>,
+        stack: Vec<&'a str>,
+        on_stack: HashSet<&'a str>,
+        cyclic: HashSet<&'a str>,
+    }
+
+    impl<'a> Tarjan<'a, '_> {
+        fn visit(&mut self, name: &'a str) {
+            let index = self.index.len();
+            self.index.insert(name, index);
+            self.low_link.insert(name, index);
+            self.stack.push(name);
+            self.on_stack.insert(name);
+
+            let value = self.declared[name];
+            for reference in references(value) {
+                let Some((&reference, _)) = self.declared.get_key_value(reference) else {
+                    continue;
+                };
+                if reference == name {
+                    self.cyclic.insert(name);
+                }
+                if !self.index.contains_key(reference) {
+                    self.visit(reference);
+                    let low = self.low_link[name].min(self.low_link[reference]);
+                    self.low_link.insert(name, low);
+                } else if self.on_stack.contains(reference) {
+                    let low = self.low_link[name].min(self.index[reference]);
+                    self.low_link.insert(name, low);
+                }
+            }
+
+            if self.low_link[name] == self.index[name] {
+                let mut component = Vec::new();
+                while let Some(member) = self.stack.pop() {
+                    self.on_stack.remove(member);
+                    component.push(member);
+                    if member == name {
+                        break;
+                    }
+                }
+                if component.len() > 1 {
+                    self.cyclic.extend(component);
+                }
+            }
+        }
+    }
+
+    let mut tarjan = Tarjan {
+        declared,
+        index: HashMap::new(),
+        low_link: HashMap::new(),
+        stack: Vec::new(),
+        on_stack: HashSet::new(),
+        cyclic: HashSet::new(),
+    };
+    for name in declared.keys() {
+        if !tarjan.index.contains_key(name) {
+            tarjan.visit(name);
+        }
+    }
+    tarjan.cyclic
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::tokenizer::{serialize, tokenize};
+
+    fn compute(parent: &CustomProperties, declarations: &[(&str, &str)]) -> CustomProperties {
+        let tokens: Vec<(&str, Vec<Token>)> = declarations
+            .iter()
+            .map(|(name, value)| (*name, tokenize(value)))
+            .collect();
+        let declarations: Vec<(&str, &[Token])> = tokens
+            .iter()
+            .map(|(name, value)| (*name, value.as_slice()))
+            .collect();
+        compute_custom_properties(parent, &declarations)
+    }
+
+    fn value(properties: &CustomProperties, name: &str) -> Option<String> {
+        properties.get(name).map(|tokens| serialize(tokens))
+    }
+
+    #[test]
+    fn test_substitution_and_inheritance() {
+        let parent = compute(&CustomProperties::new(), &[("--base", "4px")]);
+        let child = compute(
+            &parent,
+            &[("--double", "calc(var(--base) * 2)"), ("--color", "red")],
+        );
+        assert_eq!(value(&child, "--base").as_deref(), Some("4px"));
+        assert_eq!(value(&child, "--double").as_deref(), Some("calc(4px * 2)"));
+        assert_eq!(value(&child, "--color").as_deref(), Some("red"));
+    }
+
+    #[test]
+    fn test_fallbacks() {
+        let properties = compute(
+            &CustomProperties::new(),
+            &[
+                ("--a", "var(--missing, 1px 2px)"),
+                ("--b", "var(--missing, var(--also-missing, blue))"),
+                ("--c", "var(--missing)"),
+                ("--d", "var(--missing,)"),
+            ],
+        );
+        assert_eq!(value(&properties, "--a").as_deref(), Some("1px 2px"));
+        assert_eq!(value(&properties, "--b").as_deref(), Some("blue"));
+        assert_eq!(value(&properties, "--c"), None);
+        assert_eq!(value(&properties, "--d").as_deref(), Some(""));
+    }
+
+    #[test]
+    fn test_cycles_are_invalid() {
+        let properties = compute(
+            &CustomProperties::new(),
+            &[
+                ("--a", "var(--b)"),
+                ("--b", "var(--c)"),
+                ("--c", "var(--a)"),
+                ("--self", "var(--self, red)"),
+                ("--uses-cycle", "var(--a, green)"),
+                ("--fine", "1"),
+            ],
+        );
+        for name in ["--a", "--b", "--c", "--self"] {
+            assert_eq!(value(&properties, name), None, "{} should be invalid", name);
+        }
+        assert_eq!(value(&properties, "--uses-cycle").as_deref(), Some("green"));
+        assert_eq!(value(&properties, "--fine").as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn test_cycle_through_inherited_value_is_not_a_cycle() {
+        let parent = compute(&CustomProperties::new(), &[("--a", "1px")]);
+        // References to properties not declared on the element use the inherited value
+        let child = compute(&parent, &[("--b", "var(--a)")]);
+        assert_eq!(value(&child, "--b").as_deref(), Some("1px"));
+    }
+
+    #[test]
+    fn test_css_wide_keywords() {
+        let parent = compute(&CustomProperties::new(), &[("--a", "red"), ("--b", "blue")]);
+        let child = compute(
+            &parent,
+            &[("--a", "initial"), ("--b", "green"), ("--b", "inherit")],
+        );
+        assert_eq!(value(&child, "--a"), None);
+        assert_eq!(value(&child, "--b").as_deref(), Some("blue"));
+    }
+
+    #[test]
+    fn test_substitution_growth_is_limited() {
+        let mut declarations = vec![("--l0".to_string(), "x x x x x x x x".to_string())];
+        for level in 1..8 {
+            let reference = format!("var(--l{}) ", level - 1);
+            declarations.push((format!("--l{}", level), reference.repeat(8)));
+        }
+        let declarations: Vec<(&str, &str)> = declarations
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        let properties = compute(&CustomProperties::new(), &declarations);
+        assert!(properties.contains_key("--l3"));
+        assert!(!properties.contains_key("--l7"));
+    }
+}
@@ -1,3 +1,9 @@
+mod reader;
+mod selection;
+
+pub use reader::{extract_article, Article};
+pub use selection::{Selection, TextPosition};
+
 use std::collections::HashMap;
 
 /// HTML element types
@@ -14,11 +20,13 @@ pub enum ElementType {
     Img,
     Script,
     Style,
+    Link,
     // Add more HTML elements as needed
     Custom(String), // For custom or less common elements
 }
 
 impl ElementType {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Self {
         match s.to_lowercase().as_str() {
             "html" => ElementType::Html,
@@ -32,10 +40,30 @@ impl ElementType {
             "img" => ElementType::Img,
             "script" => ElementType::Script,
             "style" => ElementType::Style,
+            "link" => ElementType::Link,
             // Add more mappings as needed
             _ => ElementType::Custom(s.to_string()),
         }
     }
+
+    /// Returns the tag name used in markup and selectors
+    pub fn tag_name(&self) -> &str {
+        match self {
+            ElementType::Html => "html",
+            ElementType::Head => "head",
+            ElementType::Title => "title",
+            ElementType::Body => "body",
+            ElementType::Div => "div",
+            ElementType::Span => "span",
+            ElementType::P => "p",
+            ElementType::A => "a",
+            ElementType::Img => "img",
+            ElementType::Script => "script",
+            ElementType::Style => "style",
+            ElementType::Link => "link",
+            ElementType::Custom(name) => name,
+        }
+    }
 }
 
 /// DOCTYPE declarations
@@ -53,6 +81,7 @@ pub enum DoctypeType {
 }
 
 impl DoctypeType {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Self {
         match s.to_lowercase().as_str() {
             "html" => DoctypeType::Html5,
@@ -71,12 +100,54 @@ pub enum Node {
     Doctype(DoctypeType),
 }
 
+/// Interaction state of an element, matched by the `:hover`, `:focus` and `:active`
+/// pseudo-classes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ElementState {
+    pub hover: bool,
+    pub focus: bool,
+    pub active: bool,
+}
+
 /// Represents an HTML element with a tag name, attributes, and child nodes
 #[derive(Debug, Clone)]
 pub struct Element {
     pub element_type: ElementType,
     pub attributes: HashMap<String, String>,
     pub children: Vec<Node>,
+    pub state: ElementState,
+}
+
+impl Element {
+    /// Returns the value of the id attribute
+    pub fn id(&self) -> Option<&str> {
+        self.attributes.get("id").map(String::as_str)
+    }
+
+    /// Returns true if the class attribute contains the given class
+    pub fn has_class(&self, class: &str) -> bool {
+        self.attributes
+            .get("class")
+            .is_some_and(|classes| classes.split_whitespace().any(|c| c == class))
+    }
+
+    /// Returns true for hyperlinks, which are anchors with an href
+    pub fn is_link(&self) -> bool {
+        self.element_type == ElementType::A && self.attributes.contains_key("href")
+    }
+
+    /// Returns the concatenated text of all descendant text nodes
+    pub fn text_content(&self) -> String {
+        let mut text = String::new();
+        for child in &self.children {
+            match child {
+                Node::Text(content) => text.push_str(content),
+                Node::Element(element) => text.push_str(&element.text_content()),
+                _ => {}
+            }
+        }
+        text
+    }
 }
 
 impl Node {
@@ -86,6 +157,7 @@ impl Node {
             element_type: tag_name,
             attributes: HashMap::new(),
             children: Vec::new(),
+            state: ElementState::default(),
         })
     }
 
@@ -111,6 +183,12 @@ pub struct Document {
     pub nodes: Vec<Node>,
 }
 
+impl Default for Document {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Document {
     pub fn new() -> Self {
         Document { nodes: Vec::new() }
@@ -121,6 +199,51 @@ impl Document {
         self.nodes.push(node);
     }
 
+    /// Returns the node at a path of child indexes, starting from the document's nodes
+    pub fn node_at(&self, path: &[usize]) -> Option<&Node> {
+        let (first, rest) = path.split_first()?;
+        let mut node = self.nodes.get(*first)?;
+        for index in rest {
+            let Node::Element(element) = node else {
+                return None;
+            };
+            node = element.children.get(*index)?;
+        }
+        Some(node)
+    }
+
+    /// Returns the element at a path of child indexes
+    pub fn element_at(&self, path: &[usize]) -> Option<&Element> {
+        match self.node_at(path)? {
+            Node::Element(element) => Some(element),
+            _ => None,
+        }
+    }
+
+    /// Returns the contents of the text node at a path of child indexes
+    pub fn text_at(&self, path: &[usize]) -> Option<&str> {
+        match self.node_at(path)? {
+            Node::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Mutable version of `element_at`
+    pub fn element_at_mut(&mut self, path: &[usize]) -> Option<&mut Element> {
+        let (first, rest) = path.split_first()?;
+        let mut node = self.nodes.get_mut(*first)?;
+        for index in rest {
+            let Node::Element(element) = node else {
+                return None;
+            };
+            node = element.children.get_mut(*index)?;
+        }
+        match node {
+            Node::Element(element) => Some(element),
+            _ => None,
+        }
+    }
+
     /// Returns the HTML element if it exists in the document
     pub fn html_element(&self) -> Option<&Element> {
         for node in &self.nodes {
@@ -132,6 +255,19 @@ impl Document {
         }
         None
     }
+
+    /// Returns the text of the `<title>` in the head, with its whitespace collapsed
+    pub fn title(&self) -> Option<String> {
+        fn child(element: &Element, element_type: ElementType) -> Option<&Element> {
+            element.children.iter().find_map(|node| match node {
+                Node::Element(child) if child.element_type == element_type => Some(child),
+                _ => None,
+            })
+        }
+        let head = child(self.html_element()?, ElementType::Head)?;
+        let title = child(head, ElementType::Title)?.text_content();
+        Some(title.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
 }
 
 #[cfg(test)]
@@ -193,6 +329,7 @@ mod tests {
             element_type: ElementType::Html,
             attributes: HashMap::new(),
             children: Vec::new(),
+            state: ElementState::default(),
         };
 
         // Add HEAD element with TITLE
@@ -200,12 +337,14 @@ mod tests {
             element_type: ElementType::Head,
             attributes: HashMap::new(),
             children: Vec::new(),
+            state: ElementState::default(),
         };
 
         let title = Element {
             element_type: ElementType::Title,
             attributes: HashMap::new(),
             children: vec![Node::new_text("Test Page")],
+            state: ElementState::default(),
         };
 
         head.children.push(Node::Element(title));
@@ -215,6 +354,7 @@ mod tests {
             element_type: ElementType::Body,
             attributes: HashMap::new(),
             children: vec![Node::new_text("Hello World")],
+            state: ElementState::default(),
         };
 
         html.children.push(Node::Element(head));
@@ -229,6 +369,16 @@ mod tests {
         let html_element = doc.html_element().unwrap();
         assert_eq!(html_element.element_type, ElementType::Html);
         assert_eq!(html_element.children.len(), 2);
+
+        // Test element_at paths
+        assert_eq!(
+            doc.element_at(&[1, 0, 0]).unwrap().element_type,
+            ElementType::Title
+        );
+        assert!(doc.element_at(&[1, 1, 0]).is_none());
+        assert!(doc.element_at(&[0]).is_none());
+        doc.element_at_mut(&[1, 1]).unwrap().state.hover = true;
+        assert!(doc.element_at(&[1, 1]).unwrap().state.hover);
     }
 
     #[test]
@@ -0,0 +1,451 @@
+// Reader mode: finding the article of a page among its navigation, sidebars and footers,
+// by scoring blocks on how much plain text they hold
+
+use crate::dom::selection::{escape, write_start_tag, VOID_ELEMENTS};
+use crate::dom::{Document, Element, ElementType, Node};
+use std::collections::HashMap;
+
+// Elements that never hold the article's text
+const REMOVED: [&str; 14] = [
+    "script", "style", "noscript", "nav", "aside", "footer", "header", "form", "iframe", "button",
+    "input", "select", "textarea", "svg",
+];
+// Class and id words of the clutter around an article, and of articles themselves
+const UNLIKELY: [&str; 24] = [
+    "ad",
+    "advert",
+    "advertisement",
+    "banner",
+    "breadcrumb",
+    "comment",
+    "cookie",
+    "footer",
+    "menu",
+    "nav",
+    "navbar",
+    "navigation",
+    "popup",
+    "promo",
+    "promoted",
+    "related",
+    "share",
+    "sharing",
+    "sidebar",
+    "social",
+    "sponsor",
+    "sponsored",
+    "subscribe",
+    "subscription",
+];
+const LIKELY: [&str; 8] = [
+    "article", "blog", "body", "content", "entry", "main", "post", "story",
+];
+// Attributes kept on the article's elements, so the page's styles don't follow it
+const KEPT_ATTRIBUTES: [&str; 8] = [
+    "href", "src", "srcset", "sizes", "alt", "title", "colspan", "rowspan",
+];
+// Paragraphs shorter than this are too short to say where the article is
+const MIN_PARAGRAPH_LENGTH: usize = 25;
+
+/// The main content of a page, with its title and author
+#[derive(Debug, Clone)]
+pub struct Article {
+    pub title: String,
+    pub byline: Option<String>,
+    pub content: Element,
+}
+
+impl Article {
+    // The article's content as HTML, without the element around it
+    pub fn html(&self) -> String {
+        let mut html = String::new();
+        for child in &self.content.children {
+            write_node(child, &mut html);
+        }
+        html
+    }
+}
+
+// Finds the article in a document. None if nothing in it reads like one.
+pub fn extract_article(document: &Document) -> Option<Article> {
+    let html = document.html_element()?;
+    let body = child_element(html, &ElementType::Body)?;
+    let mut title = page_title(html);
+    let mut body = body.clone();
+    let byline = byline(html, &mut body);
+    clean(&mut body);
+
+    // Paragraphs give their score to the blocks they're in, and half of it to the blocks
+    // around those
+    let scores = score_paragraphs(&body);
+    let (best, best_score) = scores
+        .iter()
+        .filter_map(|(path, score)| {
+            let element = descendant(&body, path)?;
+            Some((path.clone(), score * (1.0 - link_density(element))))
+        })
+        .max_by(|(a_path, a), (b_path, b)| a.total_cmp(b).then(b_path.cmp(a_path)))?;
+
+    // Siblings that score well enough, or are long paragraphs, are part of it too
+    let mut content = match best.split_last() {
+        Some((&index, parent)) => {
+            let parent = descendant(&body, parent)?;
+            let threshold = (best_score * 0.2).max(10.0);
+            let mut content = Element {
+                element_type: ElementType::Div,
+                attributes: HashMap::new(),
+                children: Vec::new(),
+                state: Default::default(),
+            };
+            for (sibling_index, sibling) in parent.children.iter().enumerate() {
+                let Node::Element(element) = sibling else {
+                    continue;
+                };
+                let mut path = best[..best.len() - 1].to_vec();
+                path.push(sibling_index);
+                let score = scores.get(&path).copied().unwrap_or(0.0);
+                let text = collapse_whitespace(&element.text_content());
+                let paragraph = element.element_type == ElementType::P
+                    && text.len() > 80
+                    && link_density(element) < 0.25;
+                if sibling_index == index || score >= threshold || paragraph {
+                    content.children.push(sibling.clone());
+                }
+            }
+            content
+        }
+        None => body,
+    };
+
+    if title.is_empty() {
+        title = find_element(&content, "h1")
+            .map(|heading| collapse_whitespace(&heading.text_content()))
+            .unwrap_or_default();
+    }
+    // The title is shown above the article, so a heading repeating it is dropped
+    remove_headings(&mut content, &title);
+    Some(Article {
+        title,
+        byline,
+        content,
+    })
+}
+
+fn child_element<'a>(element: &'a Element, element_type: &ElementType) -> Option<&'a Element> {
+    element.children.iter().find_map(|child| match child {
+        Node::Element(child) if &child.element_type == element_type => Some(child),
+        _ => None,
+    })
+}
+
+// The element at a path of child indices under `element`
+fn descendant<'a>(element: &'a Element, path: &[usize]) -> Option<&'a Element> {
+    path.iter().try_fold(element, |element, &index| {
+        match element.children.get(index)? {
+            Node::Element(child) => Some(child),
+            _ => None,
+        }
+    })
+}
+
+fn find_element<'a>(element: &'a Element, tag: &str) -> Option<&'a Element> {
+    element.children.iter().find_map(|child| match child {
+        Node::Element(child) if child.element_type.tag_name() == tag => Some(child),
+        Node::Element(child) => find_element(child, tag),
+        _ => None,
+    })
+}
+
+// The page's `<title>`, without the site name that often follows it
+fn page_title(html: &Element) -> String {
+    let title = child_element(html, &ElementType::Head)
+        .and_then(|head| child_element(head, &ElementType::Title))
+        .map(|title| collapse_whitespace(&title.text_content()))
+        .unwrap_or_default();
+    let separator = [" | ", " - ", " – ", " — ", " :: "]
+        .into_iter()
+        .filter_map(|separator| title.rfind(separator))
+        .max();
+    match separator {
+        Some(end) if title[..end].split_whitespace().count() >= 3 => title[..end].to_string(),
+        _ => title,
+    }
+}
+
+// Who wrote the page, from its metadata or an element marked as the byline. The byline is
+// shown above the article, so that element is taken out of the body.
+fn byline(html: &Element, body: &mut Element) -> Option<String> {
+    let meta = child_element(html, &ElementType::Head).and_then(|head| {
+        head.children.iter().find_map(|child| match child {
+            Node::Element(meta)
+                if meta.attributes.get("name").map(String::as_str) == Some("author") =>
+            {
+                meta.attributes.get("content").cloned()
+            }
+            _ => None,
+        })
+    });
+    let marked = take_byline(body);
+    let text = collapse_whitespace(&meta.or(marked)?);
+    (!text.is_empty() && text.len() < 100).then_some(text)
+}
+
+fn take_byline(element: &mut Element) -> Option<String> {
+    for index in 0..element.children.len() {
+        let Node::Element(child) = &mut element.children[index] else {
+            continue;
+        };
+        let marked = child.attributes.get("rel").map(String::as_str) == Some("author")
+            || hinted(&hints(child), &["byline", "author"]);
+        let text = collapse_whitespace(&child.text_content());
+        if marked && !text.is_empty() && text.len() < 100 {
+            element.children.remove(index);
+            return Some(text);
+        }
+        if let Some(text) = take_byline(child) {
+            return Some(text);
+        }
+    }
+    None
+}
+
+// The words of an element's class and id, lowercased, which hint at what it holds
+fn hints(element: &Element) -> Vec<String> {
+    let class = element.attributes.get("class").map_or("", String::as_str);
+    format!("{} {}", class, element.id().unwrap_or_default())
+        .to_lowercase()
+        .split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// Whether any of the hints is one of the words or its plural. Whole words are compared,
+// so `lead-in` isn't taken for an ad.
+fn hinted(hints: &[String], words: &[&str]) -> bool {
+    hints.iter().any(|hint| {
+        words
+            .iter()
+            .any(|word| hint == word || hint.strip_suffix('s') == Some(word))
+    })
+}
+
+fn class_weight(element: &Element) -> f32 {
+    let hints = hints(element);
+    let mut weight = 0.0;
+    if hinted(&hints, &UNLIKELY) {
+        weight -= 25.0;
+    }
+    if hinted(&hints, &LIKELY) {
+        weight += 25.0;
+    }
+    weight
+}
+
+// Removes what can't be part of the article: scripts, forms and navigation, hidden
+// elements and blocks named like clutter. The attributes left are those that don't style.
+fn clean(element: &mut Element) {
+    element.children.retain(|child| match child {
+        Node::Element(child) => {
+            let tag = child.element_type.tag_name();
+            let hidden = child.attributes.contains_key("hidden")
+                || child
+                    .attributes
+                    .get("style")
+                    .is_some_and(|style| style.replace(' ', "").contains("display:none"));
+            let clutter = class_weight(child) < 0.0 && !matches!(tag, "article" | "main");
+            !REMOVED.contains(&tag) && !hidden && !clutter
+        }
+        Node::Text(_) => true,
+        _ => false,
+    });
+    element
+        .attributes
+        .retain(|name, _| KEPT_ATTRIBUTES.contains(&name.as_str()));
+    for child in &mut element.children {
+        if let Node::Element(child) = child {
+            clean(child);
+        }
+    }
+}
+
+// Whether an element holds a paragraph of text: paragraph-like elements, and blocks with
+// nothing but text and inline elements in them
+fn is_paragraph(element: &Element) -> bool {
+    match element.element_type.tag_name() {
+        "p" | "pre" | "td" | "blockquote" => true,
+        "div" | "section" => element.children.iter().all(|child| match child {
+            Node::Element(child) => matches!(
+                child.element_type.tag_name(),
+                "a" | "b" | "br" | "code" | "em" | "i" | "img" | "small" | "span" | "strong"
+            ),
+            _ => true,
+        }),
+        _ => false,
+    }
+}
+
+// The score a block starts with before its paragraphs are counted
+fn initial_score(element: &Element) -> f32 {
+    let tag_score = match element.element_type.tag_name() {
+        "div" | "article" | "main" | "section" => 5.0,
+        "pre" | "td" | "blockquote" => 3.0,
+        "address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "form" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    };
+    tag_score + class_weight(element)
+}
+
+// Scores the paragraphs under `element`, adding each paragraph's score to its parent and
+// half of it to its grandparent. Blocks start with a score for their tag and class.
+fn score_paragraphs(root: &Element) -> HashMap<Vec<usize>, f32> {
+    let mut paragraphs = Vec::new();
+    collect_paragraphs(root, &mut Vec::new(), &mut paragraphs);
+    let mut scores = HashMap::new();
+    for (path, score) in paragraphs {
+        for (depth, share) in [(1, 1.0), (2, 0.5)] {
+            let Some(ancestor) = path.len().checked_sub(depth).map(|len| &path[..len]) else {
+                break;
+            };
+            let Some(block) = descendant(root, ancestor) else {
+                break;
+            };
+            *scores
+                .entry(ancestor.to_vec())
+                .or_insert_with(|| initial_score(block)) += score * share;
+        }
+    }
+    scores
+}
+
+fn collect_paragraphs(
+    element: &Element,
+    path: &mut Vec<usize>,
+    paragraphs: &mut Vec<(Vec<usize>, f32)>,
+) {
+    for (index, child) in element.children.iter().enumerate() {
+        let Node::Element(child) = child else {
+            continue;
+        };
+        path.push(index);
+        let text = collapse_whitespace(&child.text_content());
+        if is_paragraph(child) && text.len() >= MIN_PARAGRAPH_LENGTH {
+            // Longer paragraphs with more clauses count for more
+            let score = 1.0 + text.matches(',').count() as f32 + (text.len() / 100).min(3) as f32;
+            paragraphs.push((path.clone(), score));
+        }
+        collect_paragraphs(child, path, paragraphs);
+        path.pop();
+    }
+}
+
+// How much of an element's text is in links, from 0 to 1
+fn link_density(element: &Element) -> f32 {
+    fn link_text(element: &Element) -> usize {
+        match element.is_link() {
+            true => collapse_whitespace(&element.text_content()).len(),
+            false => element
+                .children
+                .iter()
+                .map(|child| match child {
+                    Node::Element(child) => link_text(child),
+                    _ => 0,
+                })
+                .sum(),
+        }
+    }
+    let length = collapse_whitespace(&element.text_content()).len();
+    match length {
+        0 => 0.0,
+        length => link_text(element) as f32 / length as f32,
+    }
+}
+
+fn remove_headings(element: &mut Element, title: &str) {
+    element.children.retain(|child| match child {
+        Node::Element(heading) if matches!(heading.element_type.tag_name(), "h1" | "h2") => {
+            collapse_whitespace(&heading.text_content()) != title
+        }
+        _ => true,
+    });
+    for child in &mut element.children {
+        if let Node::Element(child) = child {
+            remove_headings(child, title);
+        }
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn write_node(node: &Node, output: &mut String) {
+    match node {
+        Node::Text(text) => output.push_str(&escape(text, false)),
+        Node::Element(element) => {
+            write_start_tag(element, output);
+            for child in &element.children {
+                write_node(child, output);
+            }
+            let tag = element.element_type.tag_name();
+            if !VOID_ELEMENTS.contains(&tag) {
+                output.push_str(&format!("</{}>", tag));
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html;
+
+    #[test]
+    fn test_extract_article() {
+        let document = html::parse_document(
+            "<html><head><title>How rivers carve canyons | Geology Weekly</title>\
+             <style>p { color: red }</style></head><body>\
+             <nav><a href='/'>Home</a> <a href='/news'>News</a></nav>\
+             <div class='sidebar'><p>Sponsored: buy our rocks, now, today, cheaply.</p></div>\
+             <div id='main-content'>\
+             <h1>How rivers carve canyons</h1>\
+             <span class='byline'>By Ada Stone</span>\
+             <p class='lead-in'>Canyons are among the deepest scars on the face of the earth.</p>\
+             <p style='font-size: 30px'>Rivers cut down through rock over millions of years, \
+             carrying sand, gravel and boulders that grind away at their beds.</p>\
+             <p>Where the land rises, the river cuts faster, and a canyon forms, \
+             deep, narrow and steep-sided.</p>\
+             <script>track()</script></div>\
+             <div class='links'><a href='/a'>A long list of related links to other stories</a></div>\
+             <footer>Copyright, all rights reserved, forever and ever</footer>\
+             </body></html>",
+        );
+        let article = extract_article(&document).unwrap();
+        assert_eq!(article.title, "How rivers carve canyons");
+        assert_eq!(article.byline.as_deref(), Some("By Ada Stone"));
+
+        // Only the article's paragraphs are kept, without the heading repeating the title
+        // or the page's styles
+        let html = article.html();
+        assert!(html.contains("<p>Rivers cut down through rock"));
+        assert!(html.contains("a canyon forms"));
+        assert!(html.contains("<p>Canyons are among the deepest"));
+        for clutter in [
+            "By Ada Stone",
+            "Sponsored",
+            "Home",
+            "track()",
+            "Copyright",
+            "related links",
+            "<h1>",
+        ] {
+            assert!(!html.contains(clutter), "{} in {}", clutter, html);
+        }
+
+        // Pages without paragraphs have no article
+        let document = html::parse_document("<html><body><a href='/'>Home</a></body></html>");
+        assert!(extract_article(&document).is_none());
+    }
+}
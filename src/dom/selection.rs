@@ -0,0 +1,363 @@
+// Selections: ranges of a document's text between two positions, and the text and markup
+// they cover
+
+use crate::dom::{Document, Element, Node};
+use std::fmt::Write;
+use std::ops::Range;
+
+// Elements without an end tag
+pub(super) const VOID_ELEMENTS: [&str; 8] =
+    ["area", "br", "col", "hr", "img", "input", "meta", "wbr"];
+
+/// A point in the document's text: a byte offset into the text node at a path. Positions
+/// order as they appear in the document.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TextPosition {
+    pub node: Vec<usize>,
+    pub offset: usize,
+}
+
+/// A range of the document from the anchor, where selecting started, to the focus, which
+/// moves as the selection is extended and may come before the anchor
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection {
+    pub anchor: TextPosition,
+    pub focus: TextPosition,
+}
+
+impl Selection {
+    pub fn new(anchor: TextPosition, focus: TextPosition) -> Self {
+        Self { anchor, focus }
+    }
+
+    pub fn collapsed(position: TextPosition) -> Self {
+        Self::new(position.clone(), position)
+    }
+
+    pub fn is_collapsed(&self) -> bool {
+        self.anchor == self.focus
+    }
+
+    // The end of the range that comes first in the document
+    pub fn start(&self) -> &TextPosition {
+        std::cmp::min(&self.anchor, &self.focus)
+    }
+
+    pub fn end(&self) -> &TextPosition {
+        std::cmp::max(&self.anchor, &self.focus)
+    }
+
+    // The selected bytes of the text node at `node`, which is `len` bytes long, if any of
+    // it is selected
+    pub fn range_in(&self, node: &[usize], len: usize) -> Option<Range<usize>> {
+        let (start, end) = (self.start(), self.end());
+        if node < start.node.as_slice() || node > end.node.as_slice() {
+            return None;
+        }
+        let from = if node == start.node { start.offset } else { 0 };
+        let to = if node == end.node { end.offset } else { len };
+        (from < to.min(len)).then_some(from..to.min(len))
+    }
+
+    // The word around a position, or the run of spaces or the punctuation mark it's at
+    pub fn word(document: &Document, position: &TextPosition) -> Option<Selection> {
+        let text = document.text_at(&position.node)?;
+        let offset = position.offset.min(text.len());
+        // The character after the position, or the one before at the end of the text
+        let (at, c) = text[offset..]
+            .chars()
+            .next()
+            .map(|c| (offset, c))
+            .or_else(|| text[..offset].char_indices().next_back())?;
+        let class = char_class(c);
+        let start = text[..at]
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| char_class(*c) == class)
+            .last()
+            .map_or(at, |(index, _)| index);
+        let end = text[at..]
+            .char_indices()
+            .find(|(_, c)| char_class(*c) != class)
+            .map_or(text.len(), |(index, _)| at + index);
+        // Punctuation is selected a character at a time
+        let (start, end) = match class {
+            CharClass::Other => (at, at + c.len_utf8()),
+            _ => (start, end),
+        };
+        let position = |offset| TextPosition {
+            node: position.node.clone(),
+            offset,
+        };
+        Some(Selection::new(position(start), position(end)))
+    }
+
+    // All the text inside the node at `path`
+    pub fn contents(document: &Document, path: &[usize]) -> Option<Selection> {
+        let mut texts = Vec::new();
+        match path {
+            [] => {
+                for (index, node) in document.nodes.iter().enumerate() {
+                    collect_text_nodes(node, &mut vec![index], &mut texts);
+                }
+            }
+            _ => collect_text_nodes(document.node_at(path)?, &mut path.to_vec(), &mut texts),
+        }
+        let (first, _) = texts.first()?;
+        let (last, len) = texts.last()?;
+        Some(Selection::new(
+            TextPosition {
+                node: first.clone(),
+                offset: 0,
+            },
+            TextPosition {
+                node: last.clone(),
+                offset: *len,
+            },
+        ))
+    }
+
+    // The selected text, with white space collapsed and block-level elements on lines of
+    // their own. `display` gives the display of an element, elements that aren't styled
+    // count as inline.
+    pub fn text<'a>(
+        &self,
+        document: &Document,
+        display: &dyn Fn(&[usize]) -> Option<&'a str>,
+    ) -> String {
+        let mut text = String::new();
+        for (index, node) in document.nodes.iter().enumerate() {
+            self.write_text(node, &mut vec![index], display, &mut text);
+        }
+        text.trim().to_string()
+    }
+
+    fn write_text<'a>(
+        &self,
+        node: &Node,
+        path: &mut Vec<usize>,
+        display: &dyn Fn(&[usize]) -> Option<&'a str>,
+        output: &mut String,
+    ) {
+        match node {
+            Node::Text(text) => {
+                let Some(range) = self.range_in(path, text.len()) else {
+                    return;
+                };
+                for (index, word) in text[range].split(char::is_whitespace).enumerate() {
+                    let space = index > 0 && !output.is_empty() && !output.ends_with([' ', '\n']);
+                    if space {
+                        output.push(' ');
+                    }
+                    output.push_str(word);
+                }
+            }
+            Node::Element(element) => {
+                let block = match display(path) {
+                    Some("none") => return,
+                    Some(display) => !display.starts_with("inline") && display != "contents",
+                    None => false,
+                };
+                let new_line = |output: &mut String| {
+                    let trimmed = output.trim_end_matches(' ').len();
+                    output.truncate(trimmed);
+                    if !output.is_empty() && !output.ends_with('\n') {
+                        output.push('\n');
+                    }
+                };
+                if block {
+                    new_line(output);
+                }
+                if element.element_type.tag_name() == "br" && self.covers(path) {
+                    let trimmed = output.trim_end_matches(' ').len();
+                    output.truncate(trimmed);
+                    output.push('\n');
+                }
+                for (index, child) in element.children.iter().enumerate() {
+                    path.push(index);
+                    self.write_text(child, path, display, output);
+                    path.pop();
+                }
+                if block {
+                    new_line(output);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // The selected part of the document as HTML, with the elements around the selected
+    // text and inside the body kept
+    pub fn html<'a>(
+        &self,
+        document: &Document,
+        display: &dyn Fn(&[usize]) -> Option<&'a str>,
+    ) -> String {
+        let mut html = String::new();
+        for (index, node) in document.nodes.iter().enumerate() {
+            self.write_html(node, &mut vec![index], display, &mut html);
+        }
+        html
+    }
+
+    fn write_html<'a>(
+        &self,
+        node: &Node,
+        path: &mut Vec<usize>,
+        display: &dyn Fn(&[usize]) -> Option<&'a str>,
+        output: &mut String,
+    ) {
+        match node {
+            Node::Text(text) => {
+                if let Some(range) = self.range_in(path, text.len()) {
+                    output.push_str(&escape(&text[range], false));
+                }
+            }
+            Node::Element(element) => {
+                if !self.covers(path) || display(path) == Some("none") {
+                    return;
+                }
+                let tag = element.element_type.tag_name();
+                let wrapper = matches!(tag, "html" | "body");
+                if !wrapper {
+                    write_start_tag(element, output);
+                }
+                for (index, child) in element.children.iter().enumerate() {
+                    path.push(index);
+                    self.write_html(child, path, display, output);
+                    path.pop();
+                }
+                if !wrapper && !VOID_ELEMENTS.contains(&tag) {
+                    let _ = write!(output, "</{}>", tag);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Whether any of the node at `path` or its descendants is in the range. The nodes
+    // inside it have paths from `path` up to, but not including, that of its next sibling.
+    fn covers(&self, path: &[usize]) -> bool {
+        let mut next = path.to_vec();
+        if let Some(last) = next.last_mut() {
+            *last += 1;
+        }
+        self.end().node.as_slice() >= path && self.start().node < next
+    }
+}
+
+#[derive(PartialEq)]
+enum CharClass {
+    Word,
+    Space,
+    Other,
+}
+
+fn char_class(c: char) -> CharClass {
+    if c.is_alphanumeric() || c == '_' || c == '\'' {
+        CharClass::Word
+    } else if c.is_whitespace() {
+        CharClass::Space
+    } else {
+        CharClass::Other
+    }
+}
+
+// Gathers the path and length of the text nodes under a node, in document order
+fn collect_text_nodes(node: &Node, path: &mut Vec<usize>, texts: &mut Vec<(Vec<usize>, usize)>) {
+    match node {
+        Node::Text(text) => texts.push((path.clone(), text.len())),
+        Node::Element(element) => {
+            for (index, child) in element.children.iter().enumerate() {
+                path.push(index);
+                collect_text_nodes(child, path, texts);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+pub(super) fn write_start_tag(element: &Element, output: &mut String) {
+    let _ = write!(output, "<{}", element.element_type.tag_name());
+    // Attributes are sorted so the output doesn't depend on the map's order
+    let mut attributes: Vec<_> = element.attributes.iter().collect();
+    attributes.sort();
+    for (name, value) in attributes {
+        let _ = write!(output, " {}=\"{}\"", name, escape(value, true));
+    }
+    output.push('>');
+}
+
+pub(super) fn escape(text: &str, attribute: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' if attribute => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html;
+
+    fn position(node: &[usize], offset: usize) -> TextPosition {
+        TextPosition {
+            node: node.to_vec(),
+            offset,
+        }
+    }
+
+    #[test]
+    fn test_selection_text_and_html() {
+        let document = html::parse_document(
+            "<html><body><p>First <b>bold</b> line</p><p>Second   line</p></body></html>",
+        );
+        // Paragraphs are blocks, everything else inline
+        let display = |path: &[usize]| match document.element_at(path)?.element_type.tag_name() {
+            "p" => Some("block"),
+            _ => Some("inline"),
+        };
+
+        // From inside "bold" back to the middle of "Second", selected backwards
+        let selection = Selection::new(position(&[0, 1, 1, 0], 6), position(&[0, 1, 0, 1, 0], 2));
+        assert_eq!(selection.start(), &position(&[0, 1, 0, 1, 0], 2));
+        assert_eq!(selection.range_in(&[0, 1, 0, 1, 0], 4), Some(2..4));
+        assert_eq!(selection.range_in(&[0, 1, 0, 2], 5), Some(0..5));
+        assert_eq!(selection.range_in(&[0, 1, 0, 0], 6), None);
+        assert_eq!(selection.text(&document, &display), "ld line\nSecond");
+        assert_eq!(
+            selection.html(&document, &display),
+            "<p><b>ld</b> line</p><p>Second</p>"
+        );
+
+        let all = Selection::contents(&document, &[]).unwrap();
+        assert_eq!(
+            all.text(&document, &display),
+            "First bold line\nSecond line"
+        );
+    }
+
+    #[test]
+    fn test_word_selection() {
+        let document = html::parse_document("<p>Hello, wide world</p>");
+        let text = [0, 1, 0, 0];
+        let word = |offset| {
+            let selection = Selection::word(&document, &position(&text, offset)).unwrap();
+            (selection.start().offset, selection.end().offset)
+        };
+        assert_eq!(word(1), (0, 5));
+        assert_eq!(word(5), (5, 6));
+        assert_eq!(word(6), (6, 7));
+        assert_eq!(word(9), (7, 11));
+        // The end of the text selects the last word
+        assert_eq!(word(17), (12, 17));
+    }
+}
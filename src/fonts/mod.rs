@@ -0,0 +1,364 @@
+// Web fonts: loading the faces declared by `@font-face` rules and making them available
+// to egui
+
+mod woff;
+
+pub use woff::decode;
+
+use crate::css::{FontFaceRule, FontSource, ResourceError};
+use crate::networking;
+use eframe::egui;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::thread;
+use url::Url;
+
+/// A font face loaded from an `@font-face` rule
+#[derive(Debug, Clone)]
+pub struct FontFace {
+    pub family: String,
+    pub weight: (u16, u16),
+    pub italic: bool,
+    // Decoded TTF/OTF data
+    pub data: Arc<Vec<u8>>,
+    pub url: Url,
+}
+
+impl FontFace {
+    // The name the face is registered under with egui. The URL keeps faces with the same
+    // family name on different pages apart.
+    pub fn key(&self) -> String {
+        let style = match self.italic {
+            true => "italic",
+            false => "normal",
+        };
+        format!(
+            "{} {}-{} {} {}",
+            self.family, self.weight.0, self.weight.1, style, self.url
+        )
+    }
+}
+
+// Loads the faces of the given rules concurrently. Relative URLs resolve against the
+// URL of the sheet the rule came from, or the document for embedded sheets.
+pub fn load_font_faces(
+    rules: &[(&FontFaceRule, Option<&Url>)],
+    document_url: &Url,
+) -> (Vec<FontFace>, Vec<ResourceError>) {
+    load_font_faces_with(rules, document_url, &|url: &Url| {
+        networking::fetch_bytes(url).map_err(|e| e.to_string())
+    })
+}
+
+fn load_font_faces_with<F>(
+    rules: &[(&FontFaceRule, Option<&Url>)],
+    document_url: &Url,
+    fetch: &F,
+) -> (Vec<FontFace>, Vec<ResourceError>)
+where
+    F: Fn(&Url) -> Result<Vec<u8>, String> + Sync,
+{
+    let results: Vec<(Option<FontFace>, Vec<ResourceError>)> = thread::scope(|scope| {
+        let handles: Vec<_> = rules
+            .iter()
+            .map(|(rule, sheet_url)| {
+                let base_url = sheet_url.unwrap_or(document_url);
+                scope.spawn(move || load_face(rule, base_url, fetch))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap_or_else(|_| (None, Vec::new())))
+            .collect()
+    });
+    let mut faces = Vec::new();
+    let mut errors = Vec::new();
+    for (face, face_errors) in results {
+        faces.extend(face);
+        errors.extend(face_errors);
+    }
+    (faces, errors)
+}
+
+// Tries each source in turn until one loads. Local fonts are skipped as system fonts
+// aren't available to egui.
+fn load_face<F>(
+    rule: &FontFaceRule,
+    base_url: &Url,
+    fetch: &F,
+) -> (Option<FontFace>, Vec<ResourceError>)
+where
+    F: Fn(&Url) -> Result<Vec<u8>, String> + Sync,
+{
+    let mut errors = Vec::new();
+    let Some(family) = rule.family() else {
+        return (None, errors);
+    };
+    for source in rule.sources() {
+        let FontSource::Url { url, format } = source else {
+            continue;
+        };
+        if format
+            .as_deref()
+            .is_some_and(|format| !is_supported_format(format))
+        {
+            continue;
+        }
+        let url = match base_url.join(&url) {
+            Ok(url) => url,
+            Err(e) => {
+                errors.push(ResourceError {
+                    url,
+                    message: format!("Failed to parse URL: {}", e),
+                });
+                continue;
+            }
+        };
+        let data = fetch(&url)
+            .map_err(|e| format!("Failed to fetch font: {}", e))
+            .and_then(|data| decode(&data))
+            .and_then(|data| match ttf_parser::Face::parse(&data, 0) {
+                Ok(_) => Ok(data),
+                Err(e) => Err(format!("Invalid font data: {}", e)),
+            });
+        match data {
+            Ok(data) => {
+                let face = FontFace {
+                    family,
+                    weight: rule.weight(),
+                    italic: rule.is_italic(),
+                    data: Arc::new(data),
+                    url,
+                };
+                return (Some(face), errors);
+            }
+            Err(message) => errors.push(ResourceError {
+                url: url.to_string(),
+                message,
+            }),
+        }
+    }
+    (None, errors)
+}
+
+fn is_supported_format(format: &str) -> bool {
+    let format = format.strip_suffix("-variations").unwrap_or(format);
+    matches!(format, "truetype" | "opentype" | "woff" | "woff2")
+}
+
+/// The web fonts a page loaded, used to resolve its `font-family` lists
+#[derive(Debug, Clone, Default)]
+pub struct FontSet {
+    faces: Vec<FontFace>,
+}
+
+impl FontSet {
+    pub fn new(faces: Vec<FontFace>) -> Self {
+        Self { faces }
+    }
+
+    pub fn faces(&self) -> &[FontFace] {
+        &self.faces
+    }
+
+    // Returns the egui family for the first available family in the list. Generic
+    // families map to egui's built in fonts, which are also the final fallback.
+    pub fn resolve(&self, families: &[String], weight: u16, italic: bool) -> egui::FontFamily {
+        for family in families {
+            match family.to_ascii_lowercase().as_str() {
+                "monospace" | "ui-monospace" => return egui::FontFamily::Monospace,
+                "serif" | "sans-serif" | "system-ui" | "cursive" | "fantasy" | "math" | "emoji"
+                | "ui-serif" | "ui-sans-serif" | "ui-rounded" => {
+                    return egui::FontFamily::Proportional
+                }
+                _ => {
+                    if let Some(face) = self.best_match(family, weight, italic) {
+                        return egui::FontFamily::Name(face.key().into());
+                    }
+                }
+            }
+        }
+        egui::FontFamily::Proportional
+    }
+
+    // Picks the face of a family closest to the requested style, then weight
+    fn best_match(&self, family: &str, weight: u16, italic: bool) -> Option<&FontFace> {
+        self.faces
+            .iter()
+            .filter(|face| face.family.eq_ignore_ascii_case(family))
+            .min_by_key(|face| (face.italic != italic, weight_distance(face.weight, weight)))
+    }
+}
+
+// How far a face's weight range is from the desired weight. Like CSS font matching,
+// lighter faces are preferred for normal weights and heavier ones for bold weights.
+fn weight_distance((min, max): (u16, u16), desired: u16) -> (u16, bool) {
+    if (min..=max).contains(&desired) {
+        return (0, false);
+    }
+    let heavier = min > desired;
+    let distance = match heavier {
+        true => min - desired,
+        false => desired - max,
+    };
+    (distance, heavier == (desired <= 500))
+}
+
+/// Every face registered with egui. egui's fonts are shared by the whole app, so the
+/// faces of all tabs end up here.
+#[derive(Default)]
+pub struct FontRegistry {
+    faces: BTreeMap<String, Arc<Vec<u8>>>,
+}
+
+impl FontRegistry {
+    // Returns true if the face wasn't registered before
+    pub fn add(&mut self, face: &FontFace) -> bool {
+        let key = face.key();
+        if self.faces.contains_key(&key) {
+            return false;
+        }
+        self.faces.insert(key, Arc::clone(&face.data));
+        true
+    }
+
+    // egui's default fonts plus a family for each registered face. Each family falls
+    // back to the default proportional fonts for glyphs the face doesn't have.
+    pub fn font_definitions(&self) -> egui::FontDefinitions {
+        let mut definitions = egui::FontDefinitions::default();
+        let fallback = definitions
+            .families
+            .get(&egui::FontFamily::Proportional)
+            .cloned()
+            .unwrap_or_default();
+        for (key, data) in &self.faces {
+            definitions.font_data.insert(
+                key.clone(),
+                Arc::new(egui::FontData::from_owned(data.to_vec())),
+            );
+            let mut fonts = vec![key.clone()];
+            fonts.extend(fallback.iter().cloned());
+            definitions
+                .families
+                .insert(egui::FontFamily::Name(key.as_str().into()), fonts);
+        }
+        definitions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css;
+    use std::collections::HashMap;
+
+    const TTF: &[u8] = include_bytes!("../../tests/fixtures/fonts/demo.ttf");
+    const WOFF: &[u8] = include_bytes!("../../tests/fixtures/fonts/demo.woff");
+
+    fn load(css: &str) -> (Vec<FontFace>, Vec<ResourceError>) {
+        let resources: HashMap<&str, &[u8]> = HashMap::from([
+            ("http://fonts.test/css/demo.ttf", TTF),
+            ("http://fonts.test/fonts/demo.woff", WOFF),
+            ("http://fonts.test/css/broken.ttf", &b"not a font"[..]),
+        ]);
+        let fetch = |url: &Url| {
+            resources
+                .get(url.as_str())
+                .map(|data| data.to_vec())
+                .ok_or_else(|| "HTTP error: 404 Not Found".to_string())
+        };
+        let sheet = css::parse_stylesheet(css);
+        let sheet_url = Url::parse("http://fonts.test/css/style.css").unwrap();
+        let rules: Vec<(&FontFaceRule, Option<&Url>)> = sheet
+            .rules
+            .iter()
+            .filter_map(|rule| match rule {
+                css::Rule::FontFace(face) => Some((face, Some(&sheet_url))),
+                _ => None,
+            })
+            .collect();
+        load_font_faces_with(&rules, &Url::parse("http://fonts.test/").unwrap(), &fetch)
+    }
+
+    fn face(family: &str, weight: (u16, u16), italic: bool) -> FontFace {
+        FontFace {
+            family: family.to_string(),
+            weight,
+            italic,
+            data: Arc::new(TTF.to_vec()),
+            url: Url::parse(&format!("http://fonts.test/{}-{}.ttf", weight.0, italic)).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_load_font_faces() {
+        let (faces, errors) = load(
+            "@font-face { font-family: Demo; src: url(demo.ttf) format('truetype') }\
+             @font-face { font-family: 'Demo Woff'; font-weight: bold;\
+               src: url(missing.woff2), url(broken.ttf), url(x.svg) format('svg'),\
+               url(../fonts/demo.woff) }\
+             @font-face { src: url(demo.ttf) }",
+        );
+        assert_eq!(faces.len(), 2);
+        assert_eq!(faces[0].family, "Demo");
+        assert_eq!(&faces[0].data[..], TTF);
+        assert_eq!(faces[1].family, "Demo Woff");
+        assert_eq!(faces[1].weight, (700, 700));
+        assert_eq!(faces[1].url.as_str(), "http://fonts.test/fonts/demo.woff");
+        assert_eq!(&faces[1].data[..12], &TTF[..12]);
+
+        // Failed sources are reported even when a later one loads
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].url, "http://fonts.test/css/missing.woff2");
+        assert_eq!(errors[1].url, "http://fonts.test/css/broken.ttf");
+    }
+
+    #[test]
+    fn test_resolve_font_family() {
+        let set = FontSet::new(vec![
+            face("Demo", (400, 400), false),
+            face("Demo", (700, 700), false),
+            face("Demo", (400, 400), true),
+            face("Light", (100, 300), false),
+        ]);
+        let resolve = |families: &[&str], weight, italic| {
+            let families: Vec<String> = families.iter().map(|f| f.to_string()).collect();
+            set.resolve(&families, weight, italic)
+        };
+        let key = |index: usize| egui::FontFamily::Name(set.faces()[index].key().into());
+
+        assert_eq!(resolve(&["missing", "demo"], 400, false), key(0));
+        assert_eq!(resolve(&["Demo"], 600, false), key(1));
+        assert_eq!(resolve(&["Demo"], 700, true), key(2));
+        assert_eq!(resolve(&["Light"], 400, false), key(3));
+        assert_eq!(
+            resolve(&["missing", "monospace", "Demo"], 400, false),
+            egui::FontFamily::Monospace
+        );
+        assert_eq!(
+            resolve(&["missing"], 400, false),
+            egui::FontFamily::Proportional
+        );
+    }
+
+    #[test]
+    fn test_registry_font_definitions() {
+        let mut registry = FontRegistry::default();
+        let demo = face("Demo", (400, 400), false);
+        assert!(registry.add(&demo));
+        assert!(!registry.add(&demo));
+        let definitions = registry.font_definitions();
+        assert!(definitions.font_data.contains_key(&demo.key()));
+        let family = &definitions.families[&egui::FontFamily::Name(demo.key().into())];
+        assert_eq!(family[0], demo.key());
+        assert!(family.len() > 1);
+
+        // egui can lay out text with the registered family
+        let ctx = egui::Context::default();
+        ctx.set_fonts(definitions);
+        let _ = ctx.run(Default::default(), |_| {});
+        let font_id = egui::FontId::new(16.0, egui::FontFamily::Name(demo.key().into()));
+        let width = ctx.fonts(|fonts| fonts.glyph_width(&font_id, 'A'));
+        assert!(width > 0.0);
+    }
+}
@@ -0,0 +1,679 @@
+// Decodes WOFF and WOFF2 web fonts back into the plain sfnt (TTF/OTF) data egui loads
+
+use std::io::Read;
+
+const SFNT_TRUETYPE: u32 = 0x0001_0000;
+const SFNT_OPENTYPE: u32 = u32::from_be_bytes(*b"OTTO");
+const SFNT_APPLE: u32 = u32::from_be_bytes(*b"true");
+const WOFF: u32 = u32::from_be_bytes(*b"wOFF");
+const WOFF2: u32 = u32::from_be_bytes(*b"wOF2");
+const COLLECTION: u32 = u32::from_be_bytes(*b"ttcf");
+
+// Tags WOFF2 can refer to by index instead of spelling out
+const KNOWN_TAGS: [&[u8; 4]; 63] = [
+    b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post", b"cvt ", b"fpgm",
+    b"glyf", b"loca", b"prep", b"CFF ", b"VORG", b"EBDT", b"EBLC", b"gasp", b"hdmx", b"kern",
+    b"LTSH", b"PCLT", b"VDMX", b"vhea", b"vmtx", b"BASE", b"GDEF", b"GPOS", b"GSUB", b"EBSC",
+    b"JSTF", b"MATH", b"CBDT", b"CBLC", b"COLR", b"CPAL", b"SVG ", b"sbix", b"acnt", b"avar",
+    b"bdat", b"bloc", b"bsln", b"cvar", b"fdsc", b"feat", b"fmtx", b"fvar", b"gvar", b"hsty",
+    b"just", b"lcar", b"mort", b"morx", b"opbd", b"prop", b"trak", b"Zapf", b"Silf", b"Glat",
+    b"Gloc", b"Feat", b"Sill",
+];
+
+type Tag = [u8; 4];
+
+// Returns sfnt font data, decoding WOFF and WOFF2 files and passing TTF/OTF through
+pub fn decode(data: &[u8]) -> Result<Vec<u8>, String> {
+    let signature = Reader::new(data).u32()?;
+    match signature {
+        SFNT_TRUETYPE | SFNT_OPENTYPE | SFNT_APPLE => Ok(data.to_vec()),
+        WOFF => decode_woff(data),
+        WOFF2 => decode_woff2(data),
+        COLLECTION => Err("Font collections are not supported".to_string()),
+        _ => Err("Unrecognised font format".to_string()),
+    }
+}
+
+// A big endian cursor over font data
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn at(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or("Unexpected end of font data")?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn i16(&mut self) -> Result<i16, String> {
+        Ok(self.u16()? as i16)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn tag(&mut self) -> Result<Tag, String> {
+        Ok(self.u32()?.to_be_bytes())
+    }
+
+    // UIntBase128 from the WOFF2 spec
+    fn base128(&mut self) -> Result<u32, String> {
+        let mut value: u32 = 0;
+        for i in 0..5 {
+            let byte = self.u8()?;
+            if i == 0 && byte == 0x80 {
+                return Err("Invalid UIntBase128 value".to_string());
+            }
+            if value & 0xfe00_0000 != 0 {
+                return Err("UIntBase128 value overflows".to_string());
+            }
+            value = (value << 7) | (byte & 0x7f) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("UIntBase128 value is too long".to_string())
+    }
+
+    // 255UInt16 from the WOFF2 spec
+    fn u255(&mut self) -> Result<u16, String> {
+        match self.u8()? {
+            253 => self.u16(),
+            254 => Ok(self.u8()? as u16 + 253 * 2),
+            255 => Ok(self.u8()? as u16 + 253),
+            code => Ok(code as u16),
+        }
+    }
+}
+
+fn decode_woff(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut reader = Reader::at(data, 4);
+    let flavor = reader.u32()?;
+    let _length = reader.u32()?;
+    let num_tables = reader.u16()?;
+    let mut tables = Vec::with_capacity(num_tables as usize);
+    let mut directory = Reader::at(data, 44);
+    for _ in 0..num_tables {
+        let tag = directory.tag()?;
+        let offset = directory.u32()? as usize;
+        let compressed_length = directory.u32()? as usize;
+        let original_length = directory.u32()? as usize;
+        let _checksum = directory.u32()?;
+        let stored = Reader::at(data, offset).bytes(compressed_length)?;
+        let table = match compressed_length < original_length {
+            true => {
+                let mut table = Vec::with_capacity(original_length);
+                flate2::read::ZlibDecoder::new(stored)
+                    .read_to_end(&mut table)
+                    .map_err(|e| format!("Failed to inflate WOFF table: {}", e))?;
+                table
+            }
+            false => stored.to_vec(),
+        };
+        if table.len() != original_length {
+            return Err("WOFF table has the wrong length".to_string());
+        }
+        tables.push((tag, table));
+    }
+    Ok(build_sfnt(flavor, tables))
+}
+
+// A table entry from the WOFF2 table directory
+struct Woff2Table {
+    tag: Tag,
+    transformed: bool,
+    // Length of the table's data in the decompressed stream
+    length: usize,
+}
+
+fn decode_woff2(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut reader = Reader::at(data, 4);
+    let flavor = reader.u32()?;
+    if flavor == COLLECTION {
+        return Err("WOFF2 font collections are not supported".to_string());
+    }
+    let _length = reader.u32()?;
+    let num_tables = reader.u16()?;
+    let _reserved = reader.u16()?;
+    let _total_sfnt_size = reader.u32()?;
+    let total_compressed_size = reader.u32()? as usize;
+
+    let mut directory = Reader::at(data, 48);
+    let mut entries = Vec::with_capacity(num_tables as usize);
+    for _ in 0..num_tables {
+        let flags = directory.u8()?;
+        let tag = match flags & 0x3f {
+            0x3f => directory.tag()?,
+            index => *KNOWN_TAGS[index as usize],
+        };
+        let original_length = directory.base128()? as usize;
+        let version = flags >> 6;
+        // glyf and loca use version 0 for their transform and 3 for none
+        let transformed = match &tag {
+            b"glyf" | b"loca" => version == 0,
+            _ => version != 0,
+        };
+        let length = match transformed {
+            true => directory.base128()? as usize,
+            false => original_length,
+        };
+        entries.push(Woff2Table {
+            tag,
+            transformed,
+            length,
+        });
+    }
+
+    let compressed = Reader::at(data, directory.pos).bytes(total_compressed_size)?;
+    let mut stream = Vec::new();
+    brotli_decompressor::Decompressor::new(compressed, 4096)
+        .read_to_end(&mut stream)
+        .map_err(|e| format!("Failed to decompress WOFF2 data: {}", e))?;
+
+    // Split the stream into the tables' data
+    let mut stream_reader = Reader::new(&stream);
+    let mut raw: Vec<(&Woff2Table, &[u8])> = Vec::with_capacity(entries.len());
+    for entry in &entries {
+        raw.push((entry, stream_reader.bytes(entry.length)?));
+    }
+    let find = |tag: &[u8; 4]| raw.iter().find(|(entry, _)| &entry.tag == tag);
+
+    let mut tables: Vec<(Tag, Vec<u8>)> = Vec::with_capacity(raw.len());
+    let mut glyph_x_mins = Vec::new();
+    if let Some((glyf, glyf_data)) = find(b"glyf") {
+        if glyf.transformed {
+            let glyphs = reconstruct_glyf(glyf_data)?;
+            tables.push((*b"glyf", glyphs.glyf));
+            tables.push((*b"loca", glyphs.loca));
+            glyph_x_mins = glyphs.x_mins;
+        }
+    }
+    for (entry, table) in &raw {
+        if entry.transformed && matches!(&entry.tag, b"glyf" | b"loca") {
+            continue;
+        }
+        let table = match (&entry.tag, entry.transformed) {
+            (b"hmtx", true) => {
+                let hhea = find(b"hhea").ok_or("WOFF2 font is missing hhea")?.1;
+                let maxp = find(b"maxp").ok_or("WOFF2 font is missing maxp")?.1;
+                let num_h_metrics = Reader::at(hhea, 34).u16()?;
+                let num_glyphs = Reader::at(maxp, 4).u16()?;
+                reconstruct_hmtx(table, num_glyphs, num_h_metrics, &glyph_x_mins)?
+            }
+            (_, true) => return Err("Unknown WOFF2 table transform".to_string()),
+            (_, false) => table.to_vec(),
+        };
+        tables.push((entry.tag, table));
+    }
+    Ok(build_sfnt(flavor, tables))
+}
+
+// A point of a simple glyph, in absolute coordinates
+struct Point {
+    x: i32,
+    y: i32,
+    on_curve: bool,
+}
+
+// The glyf and loca tables rebuilt from a transformed glyf table, along with the xMin of
+// each glyph, which the hmtx transform needs
+struct Glyphs {
+    glyf: Vec<u8>,
+    loca: Vec<u8>,
+    x_mins: Vec<i16>,
+}
+
+fn reconstruct_glyf(data: &[u8]) -> Result<Glyphs, String> {
+    let mut header = Reader::new(data);
+    let _version = header.u16()?;
+    let option_flags = header.u16()?;
+    let num_glyphs = header.u16()? as usize;
+    let index_format = header.u16()?;
+    let mut stream_sizes = [0usize; 7];
+    for size in &mut stream_sizes {
+        *size = header.u32()? as usize;
+    }
+    let mut offset = header.pos;
+    let mut streams = Vec::with_capacity(7);
+    for size in stream_sizes {
+        streams.push(Reader::new(Reader::at(data, offset).bytes(size)?));
+        offset += size;
+    }
+    let overlap_bitmap = match option_flags & 1 {
+        1 => Some(Reader::at(data, offset).bytes(num_glyphs.div_ceil(8))?),
+        _ => None,
+    };
+    let [mut contours, mut points, mut flags, mut glyphs, mut composites, mut bboxes, mut instructions]: [Reader; 7] =
+        streams.try_into().map_err(|_| "Invalid glyf streams")?;
+    let bbox_bitmap = bboxes.bytes(num_glyphs.div_ceil(32) * 4)?;
+    let has_bbox = |glyph: usize| bbox_bitmap[glyph / 8] & (0x80 >> (glyph % 8)) != 0;
+
+    let mut glyf = Vec::new();
+    let mut loca = Vec::with_capacity(num_glyphs + 1);
+    let mut x_mins = Vec::with_capacity(num_glyphs);
+    for glyph in 0..num_glyphs {
+        loca.push(glyf.len());
+        let num_contours = contours.i16()?;
+        match num_contours {
+            0 => x_mins.push(0),
+            // Composite glyph
+            -1 => {
+                if !has_bbox(glyph) {
+                    return Err("Composite glyph without a bounding box".to_string());
+                }
+                let bbox = bboxes.bytes(8)?;
+                let start = composites.pos;
+                let mut have_instructions = false;
+                loop {
+                    let component_flags = composites.u16()?;
+                    let _glyph_index = composites.u16()?;
+                    let mut size = match component_flags & 0x0001 {
+                        0 => 2,
+                        _ => 4,
+                    };
+                    if component_flags & 0x0008 != 0 {
+                        size += 2;
+                    } else if component_flags & 0x0040 != 0 {
+                        size += 4;
+                    } else if component_flags & 0x0080 != 0 {
+                        size += 8;
+                    }
+                    composites.bytes(size)?;
+                    have_instructions |= component_flags & 0x0100 != 0;
+                    if component_flags & 0x0020 == 0 {
+                        break;
+                    }
+                }
+                glyf.extend_from_slice(&(-1i16).to_be_bytes());
+                glyf.extend_from_slice(bbox);
+                glyf.extend_from_slice(&composites.data[start..composites.pos]);
+                if have_instructions {
+                    let length = glyphs.u255()?;
+                    glyf.extend_from_slice(&length.to_be_bytes());
+                    glyf.extend_from_slice(instructions.bytes(length as usize)?);
+                }
+                x_mins.push(i16::from_be_bytes([bbox[0], bbox[1]]));
+            }
+            num_contours if num_contours > 0 => {
+                let mut end_points = Vec::with_capacity(num_contours as usize);
+                let mut total: usize = 0;
+                for _ in 0..num_contours {
+                    total += points.u255()? as usize;
+                    end_points.push(total - 1);
+                }
+                let mut glyph_points = Vec::with_capacity(total);
+                let (mut x, mut y) = (0, 0);
+                for _ in 0..total {
+                    let flag = flags.u8()?;
+                    let (dx, dy) = decode_triplet(flag & 0x7f, &mut glyphs)?;
+                    x += dx;
+                    y += dy;
+                    glyph_points.push(Point {
+                        x,
+                        y,
+                        on_curve: flag & 0x80 == 0,
+                    });
+                }
+                let instruction_length = glyphs.u255()?;
+                let glyph_instructions = instructions.bytes(instruction_length as usize)?;
+                let bbox = match has_bbox(glyph) {
+                    true => {
+                        let mut bbox = [0i16; 4];
+                        for value in &mut bbox {
+                            *value = bboxes.i16()?;
+                        }
+                        bbox
+                    }
+                    false => bounding_box(&glyph_points),
+                };
+                let overlap = overlap_bitmap
+                    .is_some_and(|bitmap| bitmap[glyph / 8] & (0x80 >> (glyph % 8)) != 0);
+                write_simple_glyph(
+                    &mut glyf,
+                    &end_points,
+                    &glyph_points,
+                    bbox,
+                    glyph_instructions,
+                    overlap,
+                )?;
+                x_mins.push(bbox[0]);
+            }
+            _ => return Err("Invalid glyph contour count".to_string()),
+        }
+        // Keep glyphs 4 byte aligned
+        while glyf.len() % 4 != 0 {
+            glyf.push(0);
+        }
+    }
+    loca.push(glyf.len());
+
+    let mut loca_table = Vec::new();
+    for offset in loca {
+        match index_format {
+            0 => loca_table.extend_from_slice(&((offset / 2) as u16).to_be_bytes()),
+            _ => loca_table.extend_from_slice(&(offset as u32).to_be_bytes()),
+        }
+    }
+    Ok(Glyphs {
+        glyf,
+        loca: loca_table,
+        x_mins,
+    })
+}
+
+// Decodes a point's coordinate deltas from the glyph stream
+fn decode_triplet(flag: u8, glyphs: &mut Reader) -> Result<(i32, i32), String> {
+    let with_sign = |flag: u8, value: i32| match flag & 1 {
+        1 => value,
+        _ => -value,
+    };
+    let flag_i = flag as i32;
+    let deltas = match flag {
+        0..=9 => {
+            let b0 = glyphs.u8()? as i32;
+            (0, with_sign(flag, ((flag_i & 14) << 7) + b0))
+        }
+        10..=19 => {
+            let b0 = glyphs.u8()? as i32;
+            (with_sign(flag, (((flag_i - 10) & 14) << 7) + b0), 0)
+        }
+        20..=83 => {
+            let b0 = flag_i - 20;
+            let b1 = glyphs.u8()? as i32;
+            (
+                with_sign(flag, 1 + (b0 & 0x30) + (b1 >> 4)),
+                with_sign(flag >> 1, 1 + ((b0 & 0x0c) << 2) + (b1 & 0x0f)),
+            )
+        }
+        84..=119 => {
+            let b0 = flag_i - 84;
+            let (b1, b2) = (glyphs.u8()? as i32, glyphs.u8()? as i32);
+            (
+                with_sign(flag, 1 + ((b0 / 12) << 8) + b1),
+                with_sign(flag >> 1, 1 + (((b0 % 12) >> 2) << 8) + b2),
+            )
+        }
+        120..=123 => {
+            let bytes = glyphs.bytes(3)?;
+            let (b1, b2, b3) = (bytes[0] as i32, bytes[1] as i32, bytes[2] as i32);
+            (
+                with_sign(flag, (b1 << 4) + (b2 >> 4)),
+                with_sign(flag >> 1, ((b2 & 0x0f) << 8) + b3),
+            )
+        }
+        _ => {
+            let bytes = glyphs.bytes(4)?;
+            let (b1, b2, b3, b4) = (
+                bytes[0] as i32,
+                bytes[1] as i32,
+                bytes[2] as i32,
+                bytes[3] as i32,
+            );
+            (
+                with_sign(flag, (b1 << 8) + b2),
+                with_sign(flag >> 1, (b3 << 8) + b4),
+            )
+        }
+    };
+    Ok(deltas)
+}
+
+fn bounding_box(points: &[Point]) -> [i16; 4] {
+    let mut bbox = [i16::MAX, i16::MAX, i16::MIN, i16::MIN];
+    for point in points {
+        bbox[0] = bbox[0].min(point.x as i16);
+        bbox[1] = bbox[1].min(point.y as i16);
+        bbox[2] = bbox[2].max(point.x as i16);
+        bbox[3] = bbox[3].max(point.y as i16);
+    }
+    bbox
+}
+
+// Writes a simple glyph in the glyf table format
+fn write_simple_glyph(
+    out: &mut Vec<u8>,
+    end_points: &[usize],
+    points: &[Point],
+    bbox: [i16; 4],
+    instructions: &[u8],
+    overlap: bool,
+) -> Result<(), String> {
+    const ON_CURVE: u8 = 0x01;
+    const X_SHORT: u8 = 0x02;
+    const Y_SHORT: u8 = 0x04;
+    const X_SAME_OR_POSITIVE: u8 = 0x10;
+    const Y_SAME_OR_POSITIVE: u8 = 0x20;
+    const OVERLAP_SIMPLE: u8 = 0x40;
+
+    out.extend_from_slice(&(end_points.len() as i16).to_be_bytes());
+    for value in bbox {
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+    for end_point in end_points {
+        let end_point = u16::try_from(*end_point).map_err(|_| "Glyph has too many points")?;
+        out.extend_from_slice(&end_point.to_be_bytes());
+    }
+    out.extend_from_slice(&(instructions.len() as u16).to_be_bytes());
+    out.extend_from_slice(instructions);
+
+    let mut x_bytes = Vec::new();
+    let mut y_bytes = Vec::new();
+    let (mut last_x, mut last_y) = (0, 0);
+    for (i, point) in points.iter().enumerate() {
+        let mut flag = match point.on_curve {
+            true => ON_CURVE,
+            false => 0,
+        };
+        if i == 0 && overlap {
+            flag |= OVERLAP_SIMPLE;
+        }
+        let (dx, dy) = (point.x - last_x, point.y - last_y);
+        (last_x, last_y) = (point.x, point.y);
+        match dx {
+            0 => flag |= X_SAME_OR_POSITIVE,
+            -255..=255 => {
+                flag |= X_SHORT;
+                if dx > 0 {
+                    flag |= X_SAME_OR_POSITIVE;
+                }
+                x_bytes.push(dx.unsigned_abs() as u8);
+            }
+            _ => x_bytes.extend_from_slice(&(dx as i16).to_be_bytes()),
+        }
+        match dy {
+            0 => flag |= Y_SAME_OR_POSITIVE,
+            -255..=255 => {
+                flag |= Y_SHORT;
+                if dy > 0 {
+                    flag |= Y_SAME_OR_POSITIVE;
+                }
+                y_bytes.push(dy.unsigned_abs() as u8);
+            }
+            _ => y_bytes.extend_from_slice(&(dy as i16).to_be_bytes()),
+        }
+        out.push(flag);
+    }
+    out.extend_from_slice(&x_bytes);
+    out.extend_from_slice(&y_bytes);
+    Ok(())
+}
+
+// Rebuilds the hmtx table, restoring left side bearings from the glyphs' xMin
+fn reconstruct_hmtx(
+    data: &[u8],
+    num_glyphs: u16,
+    num_h_metrics: u16,
+    x_mins: &[i16],
+) -> Result<Vec<u8>, String> {
+    let mut reader = Reader::new(data);
+    let flags = reader.u8()?;
+    let (num_glyphs, num_h_metrics) = (num_glyphs as usize, num_h_metrics as usize);
+    if num_h_metrics > num_glyphs || x_mins.len() < num_glyphs {
+        return Err("Invalid hmtx table".to_string());
+    }
+    let mut advances = Vec::with_capacity(num_h_metrics);
+    for _ in 0..num_h_metrics {
+        advances.push(reader.u16()?);
+    }
+    let mut bearings = Vec::with_capacity(num_glyphs);
+    for (glyph, x_min) in x_mins.iter().take(num_glyphs).enumerate() {
+        let explicit = match glyph < num_h_metrics {
+            true => flags & 1 == 0,
+            false => flags & 2 == 0,
+        };
+        bearings.push(match explicit {
+            true => reader.i16()?,
+            false => *x_min,
+        });
+    }
+    let mut table = Vec::with_capacity(num_h_metrics * 2 + num_glyphs * 2);
+    for (glyph, bearing) in bearings.into_iter().enumerate() {
+        if let Some(advance) = advances.get(glyph) {
+            table.extend_from_slice(&advance.to_be_bytes());
+        }
+        table.extend_from_slice(&bearing.to_be_bytes());
+    }
+    Ok(table)
+}
+
+// Assembles tables into an sfnt file, with the table directory sorted by tag
+fn build_sfnt(flavor: u32, mut tables: Vec<(Tag, Vec<u8>)>) -> Vec<u8> {
+    tables.sort_by_key(|(tag, _)| *tag);
+    let num_tables = tables.len() as u16;
+    let entry_selector = (num_tables.max(1)).ilog2() as u16;
+    let search_range = (1u16 << entry_selector) * 16;
+    let range_shift = num_tables * 16 - search_range.min(num_tables * 16);
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&flavor.to_be_bytes());
+    for value in [num_tables, search_range, entry_selector, range_shift] {
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+    let mut offset = 12 + 16 * tables.len();
+    for (tag, table) in &tables {
+        out.extend_from_slice(tag);
+        out.extend_from_slice(&checksum(table).to_be_bytes());
+        out.extend_from_slice(&(offset as u32).to_be_bytes());
+        out.extend_from_slice(&(table.len() as u32).to_be_bytes());
+        offset += table.len().next_multiple_of(4);
+    }
+    for (_, table) in &tables {
+        out.extend_from_slice(table);
+        out.resize(out.len().next_multiple_of(4), 0);
+    }
+    out
+}
+
+fn checksum(table: &[u8]) -> u32 {
+    table.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTF: &[u8] = include_bytes!("../../tests/fixtures/fonts/demo.ttf");
+    const WOFF_FONT: &[u8] = include_bytes!("../../tests/fixtures/fonts/demo.woff");
+    const WOFF2_FONT: &[u8] = include_bytes!("../../tests/fixtures/fonts/open-sans-regular.woff2");
+
+    fn table<'a>(face: &ttf_parser::RawFace<'a>, tag: &[u8; 4]) -> &'a [u8] {
+        face.table(ttf_parser::Tag::from_bytes(tag)).unwrap()
+    }
+
+    #[test]
+    fn test_sfnt_passes_through() {
+        assert_eq!(decode(TTF).unwrap(), TTF);
+        assert!(decode(b"not a font").is_err());
+        assert!(decode(b"").is_err());
+    }
+
+    #[test]
+    fn test_decode_woff() {
+        let decoded = decode(WOFF_FONT).unwrap();
+        let original = ttf_parser::RawFace::parse(TTF, 0).unwrap();
+        let face = ttf_parser::RawFace::parse(&decoded, 0).unwrap();
+        for tag in [b"cmap", b"glyf", b"head", b"hmtx", b"loca"] {
+            assert_eq!(table(&face, tag), table(&original, tag));
+        }
+        assert!(ttf_parser::Face::parse(&decoded, 0).is_ok());
+    }
+
+    #[test]
+    fn test_decode_woff2() {
+        let decoded = decode(WOFF2_FONT).unwrap();
+        let face = ttf_parser::Face::parse(&decoded, 0).unwrap();
+        let family = face
+            .names()
+            .into_iter()
+            .find(|name| name.name_id == ttf_parser::name_id::FAMILY)
+            .and_then(|name| name.to_string());
+        assert_eq!(family.as_deref(), Some("Open Sans"));
+
+        // Reconstructed outlines and metrics
+        let glyph = face.glyph_index('A').unwrap();
+        let bbox = face.glyph_bounding_box(glyph).unwrap();
+        assert!(bbox.width() > 0 && bbox.height() > 0);
+        assert!(face.glyph_hor_advance(glyph).unwrap() > 0);
+        assert_eq!(
+            face.glyph_hor_side_bearing(glyph),
+            Some(bbox.x_min),
+            "left side bearing restored from the glyph's xMin"
+        );
+
+        struct Counter(usize);
+        impl ttf_parser::OutlineBuilder for Counter {
+            fn move_to(&mut self, _: f32, _: f32) {
+                self.0 += 1;
+            }
+            fn line_to(&mut self, _: f32, _: f32) {
+                self.0 += 1;
+            }
+            fn quad_to(&mut self, _: f32, _: f32, _: f32, _: f32) {
+                self.0 += 1;
+            }
+            fn curve_to(&mut self, _: f32, _: f32, _: f32, _: f32, _: f32, _: f32) {
+                self.0 += 1;
+            }
+            fn close(&mut self) {}
+        }
+        let mut counter = Counter(0);
+        assert_eq!(face.outline_glyph(glyph, &mut counter), Some(bbox));
+        assert!(counter.0 > 3);
+    }
+
+    #[test]
+    fn test_truncated_woff2_is_an_error() {
+        assert!(decode(&WOFF2_FONT[..100]).is_err());
+        assert!(decode(&WOFF_FONT[..60]).is_err());
+    }
+}
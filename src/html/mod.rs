@@ -1,8 +1,9 @@
+use crate::dom;
 use scraper::{Html, Selector};
 use std::error::Error;
 
 pub fn parse_html(html: &str) -> Result<String, Box<dyn Error>> {
-    let document = Html::parse_document(&html);
+    let document = Html::parse_document(html);
     let body_selector = Selector::parse("body").unwrap();
     let text_content = document
         .select(&body_selector)
@@ -12,10 +13,71 @@ pub fn parse_html(html: &str) -> Result<String, Box<dyn Error>> {
     Ok(text_content)
 }
 
+// Parses an HTML document into the browser's DOM
+pub fn parse_document(html: &str) -> dom::Document {
+    let parsed = Html::parse_document(html);
+    let mut document = dom::Document::new();
+    for child in parsed.tree.root().children() {
+        if let Some(node) = convert_node(child) {
+            document.add_node(node);
+        }
+    }
+    document
+}
+
+// Converts a node of the scraper tree, and its descendants, into a DOM node
+fn convert_node(node: ego_tree::NodeRef<scraper::Node>) -> Option<dom::Node> {
+    match node.value() {
+        scraper::Node::Element(element) => {
+            let mut converted = dom::Element {
+                element_type: dom::ElementType::from_str(element.name()),
+                attributes: element
+                    .attrs()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect(),
+                children: Vec::new(),
+                state: dom::ElementState::default(),
+            };
+            converted.children = node.children().filter_map(convert_node).collect();
+            Some(dom::Node::Element(converted))
+        }
+        scraper::Node::Text(text) => Some(dom::Node::new_text(text)),
+        scraper::Node::Comment(comment) => Some(dom::Node::new_comment(comment)),
+        scraper::Node::Doctype(doctype) => Some(dom::Node::new_doctype(
+            dom::DoctypeType::from_str(doctype.name()),
+        )),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_document() {
+        let html = r#"<!DOCTYPE html><html><head><title>Test</title></head><body><p class="a">Hello <b>World</b></p><!-- note --></body></html>"#;
+        let document = parse_document(html);
+
+        assert_eq!(document.nodes.len(), 2);
+        assert!(matches!(
+            document.nodes[0],
+            dom::Node::Doctype(dom::DoctypeType::Html5)
+        ));
+        let html_element = document.html_element().unwrap();
+        assert_eq!(html_element.children.len(), 2);
+        let dom::Node::Element(body) = &html_element.children[1] else {
+            panic!("Expected body element");
+        };
+        assert_eq!(body.element_type, dom::ElementType::Body);
+        let dom::Node::Element(paragraph) = &body.children[0] else {
+            panic!("Expected p element");
+        };
+        assert!(paragraph.has_class("a"));
+        assert_eq!(paragraph.text_content(), "Hello World");
+        assert!(matches!(&body.children[1], dom::Node::Comment(c) if c == " note "));
+    }
+
     #[test]
     fn test_parse_html_basic() {
         let html = r#"
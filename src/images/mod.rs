@@ -0,0 +1,370 @@
+// Images: picking an `<img>`'s source, then fetching and decoding it on a few background
+// threads into a cache shared by every page
+
+mod srcset;
+
+pub use srcset::select_source;
+
+use crate::networking;
+use eframe::egui;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use url::Url;
+
+// How many images are fetched and decoded at once
+const WORKERS: usize = 4;
+// The decoded pixels the cache keeps, in bytes. Past it, images no page uses any more are
+// dropped, least recently used first.
+const CACHE_BYTES: usize = 256 * 1024 * 1024;
+
+/// A decoded image and the texture it's uploaded to once it's painted
+pub struct LoadedImage {
+    pub url: Url,
+    pub pixels: egui::ColorImage,
+    texture: OnceLock<egui::TextureHandle>,
+    // The image recolored for forced dark mode, None if it keeps its colors
+    pub(crate) dark: OnceLock<Option<Arc<LoadedImage>>>,
+}
+
+impl LoadedImage {
+    pub fn new(url: Url, pixels: egui::ColorImage) -> Self {
+        Self {
+            url,
+            pixels,
+            texture: OnceLock::new(),
+            dark: OnceLock::new(),
+        }
+    }
+
+    // The size of the image in image pixels
+    pub fn size(&self) -> egui::Vec2 {
+        egui::vec2(self.pixels.size[0] as f32, self.pixels.size[1] as f32)
+    }
+
+    // The image's texture, uploaded the first time it's needed
+    pub fn texture(&self, ctx: &egui::Context) -> &egui::TextureHandle {
+        self.texture.get_or_init(|| {
+            ctx.load_texture(
+                self.url.as_str(),
+                self.pixels.clone(),
+                egui::TextureOptions::LINEAR,
+            )
+        })
+    }
+}
+
+// The pixels are left out, they'd fill the output
+impl fmt::Debug for LoadedImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoadedImage")
+            .field("url", &self.url.as_str())
+            .field("size", &self.pixels.size)
+            .finish()
+    }
+}
+
+impl PartialEq for LoadedImage {
+    fn eq(&self, other: &Self) -> bool {
+        self.url == other.url && self.pixels == other.pixels
+    }
+}
+
+/// Where an image is in loading
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageStatus {
+    Loading,
+    Loaded(Arc<LoadedImage>),
+    Failed(String),
+}
+
+type Fetch = dyn Fn(&Url) -> Result<Vec<u8>, String> + Send + Sync;
+
+/// Images fetched and decoded on background threads, kept per URL. Clones share the same
+/// cache.
+#[derive(Clone)]
+pub struct ImageCache {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    images: Mutex<HashMap<Url, Cached>>,
+    // Images waiting for a worker, and how many workers are running
+    queue: Mutex<(VecDeque<Url>, usize)>,
+    // Counts calls to load, to tell which images were used last
+    uses: AtomicU64,
+    budget: usize,
+    // How many loads have finished, so layouts made while images were loading can tell
+    // they're out of date
+    finished: AtomicUsize,
+    // The egui context to repaint when an image arrives
+    repaint: Mutex<Option<egui::Context>>,
+    fetch: Box<Fetch>,
+}
+
+struct Cached {
+    status: ImageStatus,
+    last_used: u64,
+}
+
+impl Default for ImageCache {
+    fn default() -> Self {
+        Self::with_fetch(CACHE_BYTES, |url: &Url| {
+            networking::fetch_bytes(url).map_err(|e| e.to_string())
+        })
+    }
+}
+
+impl fmt::Debug for ImageCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let images = self.shared.images.lock().map(|images| images.len());
+        f.debug_struct("ImageCache")
+            .field("images", &images.unwrap_or_default())
+            .finish()
+    }
+}
+
+impl ImageCache {
+    fn with_fetch<F>(budget: usize, fetch: F) -> Self
+    where
+        F: Fn(&Url) -> Result<Vec<u8>, String> + Send + Sync + 'static,
+    {
+        Self {
+            shared: Arc::new(Shared {
+                images: Mutex::new(HashMap::new()),
+                queue: Mutex::new((VecDeque::new(), 0)),
+                uses: AtomicU64::new(0),
+                budget,
+                finished: AtomicUsize::new(0),
+                repaint: Mutex::new(None),
+                fetch: Box::new(fetch),
+            }),
+        }
+    }
+
+    // The cache every page shares, so an image used by several is only fetched once
+    pub fn global() -> ImageCache {
+        static GLOBAL: OnceLock<ImageCache> = OnceLock::new();
+        GLOBAL.get_or_init(ImageCache::default).clone()
+    }
+
+    pub fn set_repaint_context(&self, ctx: &egui::Context) {
+        if let Ok(mut repaint) = self.shared.repaint.lock() {
+            *repaint = Some(ctx.clone());
+        }
+    }
+
+    // The number of loads that have finished, which only grows
+    pub fn finished(&self) -> usize {
+        self.shared.finished.load(Ordering::SeqCst)
+    }
+
+    // Returns the status of the image at `url`, queueing it to load if it's new
+    pub fn load(&self, url: &Url) -> ImageStatus {
+        let Ok(mut images) = self.shared.images.lock() else {
+            return ImageStatus::Failed("Image cache unavailable".to_string());
+        };
+        let last_used = self.shared.uses.fetch_add(1, Ordering::Relaxed);
+        if let Some(cached) = images.get_mut(url) {
+            cached.last_used = last_used;
+            return cached.status.clone();
+        }
+        let status = ImageStatus::Loading;
+        images.insert(
+            url.clone(),
+            Cached {
+                status: status.clone(),
+                last_used,
+            },
+        );
+        drop(images);
+
+        // A worker is started unless enough are running already, and they stop once the
+        // queue is empty
+        let Ok(mut queue) = self.shared.queue.lock() else {
+            return ImageStatus::Failed("Image cache unavailable".to_string());
+        };
+        queue.0.push_back(url.clone());
+        if queue.1 < WORKERS {
+            queue.1 += 1;
+            let shared = Arc::clone(&self.shared);
+            thread::spawn(move || shared.work());
+        }
+        status
+    }
+}
+
+impl Shared {
+    // Loads queued images until there are none left
+    fn work(&self) {
+        loop {
+            let url = {
+                let Ok(mut queue) = self.queue.lock() else {
+                    return;
+                };
+                match queue.0.pop_front() {
+                    Some(url) => url,
+                    None => {
+                        queue.1 -= 1;
+                        return;
+                    }
+                }
+            };
+            let status = match (self.fetch)(&url)
+                .map_err(|e| format!("Failed to fetch image: {}", e))
+                .and_then(|data| decode(&data))
+            {
+                Ok(pixels) => ImageStatus::Loaded(Arc::new(LoadedImage::new(url.clone(), pixels))),
+                Err(message) => ImageStatus::Failed(message),
+            };
+            if let Ok(mut images) = self.images.lock() {
+                if let Some(cached) = images.get_mut(&url) {
+                    cached.status = status;
+                }
+                evict(&mut images, &url, self.budget);
+            }
+            self.finished.fetch_add(1, Ordering::SeqCst);
+            if let Ok(repaint) = self.repaint.lock() {
+                if let Some(ctx) = repaint.as_ref() {
+                    ctx.request_repaint();
+                }
+            }
+        }
+    }
+}
+
+// Drops the least recently used images until the pixels kept fit in `budget` bytes. Images
+// a page still holds stay, as they'd only be fetched again, and so does the one just loaded
+// until a page has had the chance to pick it up.
+fn evict(images: &mut HashMap<Url, Cached>, loaded: &Url, budget: usize) {
+    let bytes = |cached: &Cached| match &cached.status {
+        ImageStatus::Loaded(image) => image.pixels.pixels.len() * 4,
+        _ => 0,
+    };
+    let mut total: usize = images.values().map(bytes).sum();
+    if total <= budget {
+        return;
+    }
+    let mut unused: Vec<(u64, Url, usize)> = images
+        .iter()
+        .filter(|(url, cached)| {
+            *url != loaded
+                && matches!(&cached.status, ImageStatus::Loaded(image) if Arc::strong_count(image) == 1)
+        })
+        .map(|(url, cached)| (cached.last_used, url.clone(), bytes(cached)))
+        .collect();
+    unused.sort_by_key(|(last_used, _, _)| *last_used);
+    for (_, url, size) in unused {
+        if total <= budget {
+            break;
+        }
+        images.remove(&url);
+        total -= size;
+    }
+}
+
+// Decodes PNG, JPEG, BMP and ICO data to straight RGBA
+pub fn decode(data: &[u8]) -> Result<egui::ColorImage, String> {
+    let image = image::load_from_memory(data).map_err(|e| format!("Invalid image: {}", e))?;
+    let image = image.to_rgba8();
+    let size = [image.width() as usize, image.height() as usize];
+    Ok(egui::ColorImage::from_rgba_unmultiplied(
+        size,
+        image.as_raw(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    // A 3x2 PNG with a red top left pixel
+    fn png() -> Vec<u8> {
+        let mut image = image::RgbaImage::new(3, 2);
+        image.put_pixel(0, 0, image::Rgba([255, 0, 0, 255]));
+        let mut data = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut data, image::ImageFormat::Png).unwrap();
+        data.into_inner()
+    }
+
+    // Waits for the image at `url` to finish loading
+    fn wait(cache: &ImageCache, url: &Url) -> ImageStatus {
+        let start = Instant::now();
+        loop {
+            match cache.load(url) {
+                ImageStatus::Loading if start.elapsed() < Duration::from_secs(10) => {
+                    thread::sleep(Duration::from_millis(10))
+                }
+                status => return status,
+            }
+        }
+    }
+
+    #[test]
+    fn test_images_load_in_the_background_once_per_url() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/image.png")
+            .with_body(png())
+            .expect(1)
+            .create();
+        server.mock("GET", "/missing.png").with_status(404).create();
+        let base = Url::parse(&server.url()).unwrap();
+        let cache = ImageCache::default();
+
+        let url = base.join("image.png").unwrap();
+        assert_eq!(cache.load(&url), ImageStatus::Loading);
+        let ImageStatus::Loaded(image) = wait(&cache, &url) else {
+            panic!("image didn't load");
+        };
+        assert_eq!(image.size(), egui::vec2(3.0, 2.0));
+        assert_eq!(image.pixels.pixels[0], egui::Color32::RED);
+        assert_eq!(image.pixels.pixels[1], egui::Color32::TRANSPARENT);
+        // A clone shares the cache, so the image isn't fetched again
+        assert_eq!(cache.clone().load(&url), ImageStatus::Loaded(image));
+        mock.assert();
+
+        let missing = base.join("missing.png").unwrap();
+        assert!(matches!(wait(&cache, &missing), ImageStatus::Failed(_)));
+        assert_eq!(cache.finished(), 2);
+        assert!(decode(b"not an image").is_err());
+    }
+
+    #[test]
+    fn test_loads_are_bounded_and_unused_images_evicted() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let (counter, peak) = (Arc::clone(&running), Arc::clone(&most));
+        // Room for one decoded 3x2 image
+        let cache = ImageCache::with_fetch(24, move |_: &Url| {
+            let now = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(20));
+            counter.fetch_sub(1, Ordering::SeqCst);
+            Ok(png())
+        });
+        let urls: Vec<Url> = (0..12)
+            .map(|i| Url::parse(&format!("http://example.com/{}.png", i)).unwrap())
+            .collect();
+        let ImageStatus::Loaded(kept) = wait(&cache, &urls[0]) else {
+            panic!("image didn't load");
+        };
+        for url in &urls[1..] {
+            cache.load(url);
+        }
+        for url in &urls[1..] {
+            wait(&cache, url);
+        }
+        assert!(most.load(Ordering::SeqCst) <= WORKERS);
+
+        // The image still in use stays, while the rest are dropped to fit the budget
+        let images = cache.shared.images.lock().unwrap();
+        assert!(images.contains_key(&urls[0]));
+        assert!(images.len() < urls.len());
+        drop(images);
+        assert_eq!(cache.load(&urls[0]), ImageStatus::Loaded(kept));
+    }
+}
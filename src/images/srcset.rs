@@ -0,0 +1,157 @@
+// Source selection for `<img srcset sizes>`: the candidate whose density best suits the
+// device's pixel ratio
+
+use crate::css::{Device, MediaQueryList};
+
+// Picks the URL an image loads from `src`, `srcset` and `sizes`, along with its density in
+// image pixels per CSS pixel. Takes the lowest density that covers the pixel ratio, or the
+// highest there is.
+pub fn select_source(
+    src: Option<&str>,
+    srcset: Option<&str>,
+    sizes: Option<&str>,
+    device: &Device,
+) -> Option<(String, f32)> {
+    let mut candidates = srcset
+        .map(|srcset| parse_srcset(srcset, sizes, device))
+        .unwrap_or_default();
+    // `src` is the 1x candidate unless srcset has one
+    if let Some(src) = src.map(str::trim).filter(|src| !src.is_empty()) {
+        if !candidates.iter().any(|(_, density)| *density == 1.0) {
+            candidates.push((src.to_string(), 1.0));
+        }
+    }
+    candidates.sort_by(|a, b| a.1.total_cmp(&b.1));
+    let index = candidates
+        .iter()
+        .position(|(_, density)| *density >= device.pixel_ratio)
+        .unwrap_or(candidates.len().checked_sub(1)?);
+    Some(candidates.swap_remove(index))
+}
+
+// Parses the candidates of a srcset as (URL, density). Width descriptors become densities
+// through the slot size `sizes` gives.
+fn parse_srcset(srcset: &str, sizes: Option<&str>, device: &Device) -> Vec<(String, f32)> {
+    let mut candidates = Vec::new();
+    let mut rest = srcset;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == ',');
+        if rest.is_empty() {
+            break;
+        }
+        let end = rest
+            .find(|c: char| c.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        let (url, after) = rest.split_at(end);
+        // A comma ending the URL ends the candidate too
+        let (url, descriptors, next) = match url.strip_suffix(',') {
+            Some(url) => (url.trim_end_matches(','), "", after),
+            None => {
+                let end = after.find(',').unwrap_or(after.len());
+                (url, &after[..end], &after[end..])
+            }
+        };
+        rest = next;
+        let density = match descriptors.split_ascii_whitespace().collect::<Vec<_>>()[..] {
+            [] => Some(1.0),
+            [descriptor] => {
+                if let Some(density) = descriptor.strip_suffix('x') {
+                    density.parse::<f32>().ok()
+                } else if let Some(width) = descriptor.strip_suffix('w') {
+                    let slot = slot_width(sizes, device);
+                    width
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|_| slot > 0.0)
+                        .map(|width| width / slot)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        if let Some(density) = density.filter(|density| *density > 0.0) {
+            candidates.push((url.to_string(), density));
+        }
+    }
+    candidates
+}
+
+// The width of the image's slot from the first `sizes` entry whose media condition
+// matches, or the viewport width
+fn slot_width(sizes: Option<&str>, device: &Device) -> f32 {
+    for entry in sizes.unwrap_or_default().split(',') {
+        let entry = entry.trim();
+        let (condition, length) = match entry.rfind(|c: char| c.is_ascii_whitespace()) {
+            Some(index) => (entry[..index].trim(), &entry[index + 1..]),
+            None => ("", entry),
+        };
+        if !condition.is_empty() && !MediaQueryList::parse(condition).matches(device) {
+            continue;
+        }
+        if let Some(width) = length_to_px(length, device) {
+            return width;
+        }
+    }
+    device.viewport_width
+}
+
+fn length_to_px(length: &str, device: &Device) -> Option<f32> {
+    let split = length
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(length.len());
+    let (number, unit) = length.split_at(split);
+    let number: f32 = number.parse().ok()?;
+    let px = match unit.to_ascii_lowercase().as_str() {
+        "px" => number,
+        "vw" => number * device.viewport_width / 100.0,
+        "vh" => number * device.viewport_height / 100.0,
+        "em" | "rem" => number * crate::css::DEFAULT_FONT_SIZE,
+        "" if number == 0.0 => 0.0,
+        _ => return None,
+    };
+    Some(px)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(pixel_ratio: f32) -> Device {
+        Device {
+            viewport_width: 1000.0,
+            pixel_ratio,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_select_source() {
+        let srcset = Some("a.png 1x, b.png 2x,c.png 3x");
+        let pick = |ratio| select_source(Some("src.png"), srcset, None, &device(ratio));
+        assert_eq!(pick(1.0), Some(("a.png".to_string(), 1.0)));
+        assert_eq!(pick(1.5), Some(("b.png".to_string(), 2.0)));
+        assert_eq!(pick(4.0), Some(("c.png".to_string(), 3.0)));
+
+        // Widths are divided by the slot size, here 500px as the viewport is too wide for
+        // the first entry
+        let sizes = Some("(max-width: 600px) 100vw, 50vw");
+        let pick = |ratio| {
+            select_source(
+                None,
+                Some("small.png 500w, large.png 1000w"),
+                sizes,
+                &device(ratio),
+            )
+        };
+        assert_eq!(pick(1.0), Some(("small.png".to_string(), 1.0)));
+        assert_eq!(pick(2.0), Some(("large.png".to_string(), 2.0)));
+
+        // Without a srcset, src is used whatever the ratio
+        assert_eq!(
+            select_source(Some(" only.png "), None, None, &device(2.0)),
+            Some(("only.png".to_string(), 1.0))
+        );
+        assert_eq!(select_source(None, Some(""), None, &device(1.0)), None);
+    }
+}
@@ -0,0 +1,928 @@
+// Block formatting: sizing block boxes and stacking them vertically, collapsing the
+// margins that meet
+
+use crate::css::{ComputedStyle, Value};
+use crate::layout::box_tree::TablePart;
+use crate::layout::box_tree::{BoxKind, Image, Inside, LayoutBox, Outside};
+use crate::layout::float::{self, FloatArea, FloatContext};
+use crate::layout::fragment::{Edges, Fragment, FragmentKind};
+use crate::layout::{flex, grid, inline, positioned, table, LayoutContext};
+use eframe::egui::{self, Pos2, Rect, Vec2};
+
+/// The box that percentages resolve against. The height is None when it depends on the
+/// content.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct ContainingBlock {
+    pub width: f32,
+    pub height: Option<f32>,
+}
+
+// Adjoining margins, which collapse to the largest positive margin plus the most negative
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(super) struct CollapsedMargin {
+    positive: f32,
+    negative: f32,
+}
+
+impl CollapsedMargin {
+    fn new(margin: f32) -> Self {
+        let mut collapsed = Self::default();
+        collapsed.adjoin_margin(margin);
+        collapsed
+    }
+
+    fn adjoin_margin(&mut self, margin: f32) {
+        self.positive = self.positive.max(margin);
+        self.negative = self.negative.min(margin);
+    }
+
+    fn adjoin(&mut self, other: CollapsedMargin) {
+        self.positive = self.positive.max(other.positive);
+        self.negative = self.negative.min(other.negative);
+    }
+
+    pub fn solve(&self) -> f32 {
+        self.positive + self.negative
+    }
+}
+
+/// A laid out block-level box or atomic inline, with its border box at the origin, or
+/// offset from it by relative positioning
+pub(super) struct BlockLayout {
+    pub fragment: Fragment,
+    pub margin: Edges,
+    // The box's vertical margins, collapsed with any child margins they adjoin
+    pub margin_top: CollapsedMargin,
+    pub margin_bottom: CollapsedMargin,
+    // Empty boxes let the margins above and below them collapse together
+    pub collapses_through: bool,
+    // Offset of the last line's baseline from the top of the border box
+    pub baseline: Option<f32>,
+}
+
+pub(super) fn layout_root(root: &LayoutBox, viewport_width: f32, ctx: &LayoutContext) -> Fragment {
+    let containing = ContainingBlock {
+        width: viewport_width,
+        height: None,
+    };
+    let layout = layout_block_level(root, containing, None, ctx);
+    // The root establishes a formatting context, so its margins don't collapse
+    let offset = egui::vec2(layout.margin.left, layout.margin.top);
+    let mut fragment = layout.fragment;
+    // The initial containing block is the viewport at the top left of the document
+    let height = ctx
+        .viewport_height
+        .unwrap_or_else(|| fragment.rect.max.y + layout.margin.vertical());
+    let initial = Rect::from_min_size(Pos2::ZERO - offset, egui::vec2(viewport_width, height));
+    positioned::layout_absolute_descendants(root, &mut fragment, initial, true, ctx);
+    fragment.make_absolute(offset);
+    fragment
+}
+
+// Lays out a block-level box in normal flow. Boxes in their parent's formatting context
+// get the area of its floats, offset to the top left of their containing block.
+pub(super) fn layout_block_level(
+    layout_box: &LayoutBox,
+    containing: ContainingBlock,
+    floats: Option<&mut FloatArea>,
+    ctx: &LayoutContext,
+) -> BlockLayout {
+    let style = &layout_box.style;
+    let [top, right, bottom, left] = margins(style, containing.width);
+    let border = border(style);
+    let padding = padding(style, containing.width);
+    let extra = border.horizontal() + padding.horizontal();
+    if let BoxKind::Replaced(image) = &layout_box.kind {
+        let size = replaced_size(style, image, containing, border, padding);
+        let (left, right) = solve_margins(size.x, left, right, extra, containing.width);
+        let margin = Edges {
+            top: top.unwrap_or(0.0),
+            right,
+            bottom: bottom.unwrap_or(0.0),
+            left,
+        };
+        return layout_replaced(layout_box, image, size, margin, containing);
+    }
+    if layout_box.kind == BoxKind::Container(Inside::Table) {
+        // A table is as wide as its columns need rather than filling the line
+        let available = containing.width - left.unwrap_or(0.0) - right.unwrap_or(0.0) - extra;
+        let width = table::wrapper_width(layout_box, containing.width, available, ctx);
+        let (left, right) = solve_margins(width, left, right, extra, containing.width);
+        let margin = Edges {
+            top: top.unwrap_or(0.0),
+            right,
+            bottom: bottom.unwrap_or(0.0),
+            left,
+        };
+        return layout_block_box(layout_box, width, None, margin, containing, None, ctx);
+    }
+    let (width, left, right) = block_width(style, left, right, extra, containing.width);
+    let margin = Edges {
+        top: top.unwrap_or(0.0),
+        right,
+        bottom: bottom.unwrap_or(0.0),
+        left,
+    };
+    let mut floats = floats.map(|area| area.nested(egui::vec2(margin.left, 0.0), width));
+    layout_block_box(
+        layout_box,
+        width,
+        None,
+        margin,
+        containing,
+        floats.as_mut(),
+        ctx,
+    )
+}
+
+// Lays out an inline-level box that sits in a line as a whole, such as an inline-block or
+// an image. An auto width shrinks to fit the content.
+pub(super) fn layout_atomic_inline(
+    layout_box: &LayoutBox,
+    containing: ContainingBlock,
+    ctx: &LayoutContext,
+) -> BlockLayout {
+    let style = &layout_box.style;
+    let margin = margin_edges(style, containing.width);
+    let border = border(style);
+    let padding = padding(style, containing.width);
+    if let BoxKind::Replaced(image) = &layout_box.kind {
+        let size = replaced_size(style, image, containing, border, padding);
+        return layout_replaced(layout_box, image, size, margin, containing);
+    }
+    let extra = border.horizontal() + padding.horizontal();
+    let width = match content_size(style, "width", containing.width, extra) {
+        Some(width) => width,
+        None => {
+            let (min, max) = intrinsic_content_widths(layout_box, ctx);
+            let available = (containing.width - margin.horizontal() - extra).max(0.0);
+            max.min(available.max(min))
+        }
+    };
+    let width = clamp_width(style, width, containing.width, extra);
+    layout_block_box(layout_box, width, None, margin, containing, None, ctx)
+}
+
+// Lays out an outside list marker so that it ends where the box's first line starts, or
+// at the top of the content box when there are no lines
+fn layout_marker(
+    marker: &LayoutBox,
+    fragment: &Fragment,
+    content_origin: Vec2,
+    containing: ContainingBlock,
+    ctx: &LayoutContext,
+) -> Fragment {
+    let start = first_line(&fragment.children, Vec2::ZERO)
+        .map_or(content_origin.to_pos2(), |line| line.min);
+    let mut marker = layout_atomic_inline(marker, containing, ctx).fragment;
+    let size = marker.rect.size();
+    marker.rect = Rect::from_min_size(start - egui::vec2(size.x, 0.0), size);
+    marker
+}
+
+// The first line box among some fragments and their descendants, offset by their position
+fn first_line(fragments: &[Fragment], offset: Vec2) -> Option<Rect> {
+    fragments.iter().find_map(|fragment| match fragment.kind {
+        FragmentKind::Line => Some(fragment.rect.translate(offset)),
+        _ => first_line(&fragment.children, offset + fragment.rect.min.to_vec2()),
+    })
+}
+
+// Lays out a box that forms its own formatting context at a content width decided by its
+// parent, such as a flex item. A given content height overrides the box's own.
+pub(super) fn layout_sized_box(
+    layout_box: &LayoutBox,
+    width: f32,
+    height: Option<f32>,
+    containing: ContainingBlock,
+    ctx: &LayoutContext,
+) -> BlockLayout {
+    let style = &layout_box.style;
+    let margin = margin_edges(style, containing.width);
+    if let BoxKind::Replaced(image) = &layout_box.kind {
+        let border = border(style);
+        let padding = padding(style, containing.width);
+        let natural = replaced_size(style, image, containing, border, padding);
+        // An auto height keeps the aspect ratio at the new width
+        let height = height.unwrap_or(match style.length("height", 0.0) {
+            None if natural.x > 0.0 => natural.y * width / natural.x,
+            _ => natural.y,
+        });
+        let size = egui::vec2(width, height);
+        return layout_replaced(layout_box, image, size, margin, containing);
+    }
+    layout_block_box(layout_box, width, height, margin, containing, None, ctx)
+}
+
+// Lays out the contents of a block container whose width is known. Without the floats of
+// a parent's formatting context, the box establishes its own.
+fn layout_block_box(
+    layout_box: &LayoutBox,
+    width: f32,
+    forced_height: Option<f32>,
+    margin: Edges,
+    containing: ContainingBlock,
+    floats: Option<&mut FloatArea>,
+    ctx: &LayoutContext,
+) -> BlockLayout {
+    let style = &layout_box.style;
+    let border = border(style);
+    let padding = padding(style, containing.width);
+    let extra = border.vertical() + padding.vertical();
+    let height =
+        forced_height.or_else(|| resolve_height(style, "height", containing.height, extra));
+    let min_height = resolve_height(style, "min-height", containing.height, extra).unwrap_or(0.0);
+    let max_height =
+        resolve_height(style, "max-height", containing.height, extra).unwrap_or(f32::INFINITY);
+    let clamp_height = |height: f32| height.min(max_height).max(min_height);
+    let inner = ContainingBlock {
+        width,
+        height: height.map(clamp_height),
+    };
+    let content_origin = egui::vec2(border.left + padding.left, border.top + padding.top);
+    let bfc = floats.is_none();
+    let mut own_floats = FloatContext::default();
+    let mut floats = match floats {
+        Some(area) => area.nested(content_origin, width),
+        None => FloatArea::new(&mut own_floats, width),
+    };
+
+    // Margins of the first and last children adjoin ours unless something separates them
+    let top_separated = bfc || border.top > 0.0 || padding.top > 0.0;
+    let bottom_separated =
+        bfc || border.bottom > 0.0 || padding.bottom > 0.0 || height.is_some() || min_height > 0.0;
+
+    let mut fragment = Fragment::new(FragmentKind::Box, style, Rect::NOTHING);
+    fragment.node = layout_box.node.clone();
+    fragment.border = border;
+    fragment.padding = padding;
+    let mut margin_top = CollapsedMargin::new(margin.top);
+    let mut margin_bottom = CollapsedMargin::new(margin.bottom);
+    let mut collapses_through = false;
+    let mut baseline = None;
+    let content_height;
+
+    if layout_box.kind == BoxKind::Container(Inside::Flex) {
+        let flex = flex::layout_flex_content(layout_box, inner, ctx);
+        content_height = flex.height;
+        baseline = flex.baseline;
+        for mut child in flex.children {
+            child.rect = child.rect.translate(content_origin);
+            fragment.children.push(child);
+        }
+    } else if layout_box.kind == BoxKind::Container(Inside::Grid) {
+        let grid = grid::layout_grid_content(layout_box, inner, ctx);
+        content_height = grid.height;
+        baseline = grid.baseline;
+        for mut child in grid.children {
+            child.rect = child.rect.translate(content_origin);
+            fragment.children.push(child);
+        }
+    } else if layout_box.kind == BoxKind::Container(Inside::Table) {
+        let table = table::layout_table_content(layout_box, inner, ctx);
+        content_height = table.height;
+        baseline = table.baseline;
+        for mut child in table.children {
+            child.rect = child.rect.translate(content_origin);
+            fragment.children.push(child);
+        }
+    } else if layout_box.children.iter().any(LayoutBox::is_block_level) {
+        let mut y = 0.0;
+        let mut pending = CollapsedMargin::default();
+        // True until some content separates the children's margins from our top margin
+        let mut leading = !top_separated;
+        let mut float_fragments = Vec::new();
+        for child in &layout_box.children {
+            if child.is_absolutely_positioned() {
+                // Its static position is where the next block would start
+                let top = match leading {
+                    true => y,
+                    false => y + pending.solve(),
+                };
+                let position = Pos2::ZERO + content_origin + egui::vec2(0.0, top);
+                fragment
+                    .children
+                    .push(positioned::placeholder(child, position));
+                continue;
+            }
+            if !child.is_block_level() && !child.is_floated() {
+                continue;
+            }
+            if child.is_floated() {
+                let mut float = float::layout_float(child, inner, &mut floats, y, ctx);
+                float.rect = float.rect.translate(content_origin);
+                float_fragments.push(float);
+                continue;
+            }
+            // Where the child's border box will start, for placing the floats inside it
+            let mut top = match leading {
+                true => y,
+                false => {
+                    let mut margin = pending;
+                    margin.adjoin_margin(margin_edges(&child.style, width).top);
+                    y + margin.solve()
+                }
+            };
+            let clearance = floats.clearance(&child.style);
+            if let Some(clearance) = clearance {
+                top = top.max(clearance);
+            }
+            // Formatting context roots and replaced elements are narrowed to sit beside
+            // the floats rather than overlapping them
+            let mut shift = 0.0;
+            let layout = match avoids_floats(child) {
+                true => {
+                    let (left, right) = floats.band(top, 0.0);
+                    shift = left;
+                    let containing = ContainingBlock {
+                        width: right - left,
+                        ..inner
+                    };
+                    layout_block_level(child, containing, None, ctx)
+                }
+                false => {
+                    let mut area = floats.nested(egui::vec2(0.0, top), width);
+                    layout_block_level(child, inner, Some(&mut area), ctx)
+                }
+            };
+            pending.adjoin(layout.margin_top);
+            let child_y;
+            if layout.collapses_through {
+                child_y = match leading {
+                    true => y,
+                    false => y + pending.solve(),
+                };
+            } else {
+                match leading {
+                    true => margin_top.adjoin(pending),
+                    false => y += pending.solve(),
+                }
+                leading = false;
+                if let Some(clearance) = clearance {
+                    y = y.max(clearance);
+                }
+                child_y = y;
+                y += layout.fragment.rect.height();
+                pending = layout.margin_bottom;
+                if let Some(child_baseline) = layout.baseline {
+                    baseline = Some(child_y + child_baseline);
+                }
+            }
+            let mut child_fragment = layout.fragment;
+            child_fragment.rect = child_fragment
+                .rect
+                .translate(content_origin + egui::vec2(shift + layout.margin.left, child_y));
+            fragment.children.push(child_fragment);
+        }
+        // Floats paint above the backgrounds of the blocks beside them
+        fragment.children.extend(float_fragments);
+        if leading {
+            // Every child's margins adjoin our top margin
+            margin_top.adjoin(pending);
+            if !bottom_separated {
+                collapses_through = true;
+            }
+        } else if bottom_separated {
+            y += pending.solve();
+        } else {
+            margin_bottom.adjoin(pending);
+        }
+        content_height = y;
+    } else {
+        let inline =
+            inline::layout_inline_content(&layout_box.children, style, width, &mut floats, ctx);
+        if inline.lines.is_empty() && !top_separated && !bottom_separated {
+            collapses_through = true;
+        }
+        content_height = inline.height;
+        baseline = inline.baseline;
+        for mut child in inline.lines.into_iter().chain(inline.out_of_flow) {
+            child.rect = child.rect.translate(content_origin);
+            fragment.children.push(child);
+        }
+    }
+    // Formatting context roots contain their floats
+    let content_height = match bfc {
+        true => content_height.max(floats.bottom()),
+        false => content_height,
+    };
+    if collapses_through {
+        margin_top.adjoin(margin_bottom);
+        margin_bottom = margin_top;
+    }
+
+    let height = inner.height.unwrap_or(clamp_height(content_height));
+    fragment.rect = Rect::from_min_size(
+        Pos2::ZERO,
+        egui::vec2(
+            width + border.horizontal() + padding.horizontal(),
+            height + extra,
+        ),
+    );
+    if let Some(marker) = &layout_box.marker {
+        let marker = layout_marker(marker, &fragment, content_origin, inner, ctx);
+        fragment.children.push(marker);
+    }
+    if positioned::is_positioned(style) {
+        let padding_box = fragment.padding_rect();
+        positioned::layout_absolute_descendants(layout_box, &mut fragment, padding_box, false, ctx);
+    }
+    fragment.rect = fragment
+        .rect
+        .translate(positioned::relative_offset(style, containing));
+    BlockLayout {
+        fragment,
+        margin,
+        margin_top,
+        margin_bottom,
+        collapses_through,
+        baseline: baseline.map(|baseline| baseline + content_origin.y),
+    }
+}
+
+fn layout_replaced(
+    layout_box: &LayoutBox,
+    image: &Image,
+    size: Vec2,
+    margin: Edges,
+    containing: ContainingBlock,
+) -> BlockLayout {
+    let style = &layout_box.style;
+    let border = border(style);
+    let padding = padding(style, containing.width);
+    let mut fragment = Fragment::new(
+        FragmentKind::Image(image.clone()),
+        &layout_box.style,
+        Rect::from_min_size(
+            Pos2::ZERO,
+            size + egui::vec2(
+                border.horizontal() + padding.horizontal(),
+                border.vertical() + padding.vertical(),
+            ),
+        ),
+    );
+    fragment.node = layout_box.node.clone();
+    fragment.border = border;
+    fragment.padding = padding;
+    fragment.rect = fragment
+        .rect
+        .translate(positioned::relative_offset(style, containing));
+    BlockLayout {
+        fragment,
+        margin,
+        margin_top: CollapsedMargin::new(margin.top),
+        margin_bottom: CollapsedMargin::new(margin.bottom),
+        collapses_through: false,
+        baseline: None,
+    }
+}
+
+// Boxes that sit beside floats rather than letting their content flow around them
+fn avoids_floats(layout_box: &LayoutBox) -> bool {
+    matches!(layout_box.kind, BoxKind::Replaced(_)) || establishes_formatting_context(layout_box)
+}
+
+// Boxes whose contents are laid out independently of the blocks around them
+fn establishes_formatting_context(layout_box: &LayoutBox) -> bool {
+    let overflows = ["overflow-x", "overflow-y"].iter().any(|name| {
+        !matches!(
+            layout_box.style.keyword(name),
+            None | Some("visible") | Some("clip")
+        )
+    });
+    overflows
+        || layout_box.outside == Outside::Inline
+        || matches!(
+            layout_box.kind,
+            BoxKind::Container(Inside::FlowRoot | Inside::Flex | Inside::Grid | Inside::Table)
+                | BoxKind::TablePart(TablePart::Caption | TablePart::Cell { .. })
+        )
+}
+
+// The used content width and horizontal margins of a block-level box in normal flow
+fn block_width(
+    style: &ComputedStyle,
+    left: Option<f32>,
+    right: Option<f32>,
+    extra: f32,
+    containing_width: f32,
+) -> (f32, f32, f32) {
+    let solve = |width: Option<f32>| match width {
+        Some(width) => {
+            let (left, right) = solve_margins(width, left, right, extra, containing_width);
+            (width, left, right)
+        }
+        None => {
+            let (left, right) = (left.unwrap_or(0.0), right.unwrap_or(0.0));
+            (
+                (containing_width - left - right - extra).max(0.0),
+                left,
+                right,
+            )
+        }
+    };
+    let (width, left, right) = solve(content_size(style, "width", containing_width, extra));
+    let clamped = clamp_width(style, width, containing_width, extra);
+    match clamped == width {
+        true => (width, left, right),
+        false => solve(Some(clamped)),
+    }
+}
+
+// Resolves auto horizontal margins around a box of known width. An over-constrained box
+// gives up its right margin.
+pub(super) fn solve_margins(
+    width: f32,
+    left: Option<f32>,
+    right: Option<f32>,
+    extra: f32,
+    containing_width: f32,
+) -> (f32, f32) {
+    let remaining = containing_width - width - extra;
+    // Auto margins are zero when the box already overflows
+    let overflowing = remaining - left.unwrap_or(0.0) - right.unwrap_or(0.0) < 0.0;
+    let left = match overflowing {
+        true => Some(left.unwrap_or(0.0)),
+        false => left,
+    };
+    match (left, right) {
+        (None, None) if remaining > 0.0 => (remaining / 2.0, remaining / 2.0),
+        (None, None) => (0.0, remaining),
+        (None, Some(right)) => (remaining - right, right),
+        (Some(left), _) => (left, remaining - left),
+    }
+}
+
+pub(super) fn clamp_width(
+    style: &ComputedStyle,
+    width: f32,
+    containing_width: f32,
+    extra: f32,
+) -> f32 {
+    let min = content_size(style, "min-width", containing_width, extra).unwrap_or(0.0);
+    let max = content_size(style, "max-width", containing_width, extra).unwrap_or(f32::INFINITY);
+    width.min(max).max(min)
+}
+
+// The content box size given by a sizing property, honouring box-sizing. None for auto
+// and none.
+pub(super) fn content_size(
+    style: &ComputedStyle,
+    name: &str,
+    basis: f32,
+    extra: f32,
+) -> Option<f32> {
+    let size = style.length(name, basis)?;
+    match style.keyword("box-sizing") {
+        Some("border-box") => Some((size - extra).max(0.0)),
+        _ => Some(size.max(0.0)),
+    }
+}
+
+// Like `content_size`, but percentages of a height that depends on content behave as auto
+pub(super) fn resolve_height(
+    style: &ComputedStyle,
+    name: &str,
+    containing_height: Option<f32>,
+    extra: f32,
+) -> Option<f32> {
+    match (style.get(name)?, containing_height) {
+        (Value::Percentage(_) | Value::Calc(..), None) => None,
+        (_, height) => content_size(style, name, height.unwrap_or(0.0), extra),
+    }
+}
+
+// The content size of a replaced element from its CSS size, its width and height
+// attributes and its image, keeping the aspect ratio when only one dimension is given
+fn replaced_size(
+    style: &ComputedStyle,
+    image: &Image,
+    containing: ContainingBlock,
+    border: Edges,
+    padding: Edges,
+) -> Vec2 {
+    let width = content_size(
+        style,
+        "width",
+        containing.width,
+        border.horizontal() + padding.horizontal(),
+    );
+    let height = resolve_height(
+        style,
+        "height",
+        containing.height,
+        border.vertical() + padding.vertical(),
+    );
+    // The attributes stand in for the image's own size, which is unknown until it loads
+    let natural = image.natural_size();
+    let ratio = |size: Option<Vec2>| {
+        size.filter(|size| size.x > 0.0 && size.y > 0.0)
+            .map(|size| size.x / size.y)
+    };
+    let ratio = ratio(image.width.zip(image.height).map(Vec2::from)).or(ratio(natural));
+    let (intrinsic_width, intrinsic_height) = match (image.width, image.height) {
+        (None, None) => (natural.map(|size| size.x), natural.map(|size| size.y)),
+        (Some(width), None) => (Some(width), ratio.map(|r| width / r)),
+        (None, Some(height)) => (ratio.map(|r| height * r), Some(height)),
+        (width, height) => (width, height),
+    };
+    let (width, height) = match (width, height) {
+        (Some(width), Some(height)) => (width, height),
+        (Some(width), None) => (
+            width,
+            ratio.map(|r| width / r).or(intrinsic_height).unwrap_or(0.0),
+        ),
+        (None, Some(height)) => (
+            ratio.map(|r| height * r).or(intrinsic_width).unwrap_or(0.0),
+            height,
+        ),
+        (None, None) => (
+            intrinsic_width.unwrap_or(0.0),
+            intrinsic_height.unwrap_or(0.0),
+        ),
+    };
+    egui::vec2(width, height)
+}
+
+// The min-content and max-content widths of a box's content
+pub(super) fn intrinsic_content_widths(layout_box: &LayoutBox, ctx: &LayoutContext) -> (f32, f32) {
+    if let BoxKind::Replaced(image) = &layout_box.kind {
+        let containing = ContainingBlock {
+            width: 0.0,
+            height: None,
+        };
+        let size = replaced_size(
+            &layout_box.style,
+            image,
+            containing,
+            Edges::default(),
+            Edges::default(),
+        );
+        return (size.x, size.x);
+    }
+    if layout_box.kind == BoxKind::Container(Inside::Flex) {
+        flex::intrinsic_widths(layout_box, ctx)
+    } else if layout_box.kind == BoxKind::Container(Inside::Grid) {
+        grid::intrinsic_widths(layout_box, ctx)
+    } else if layout_box.kind == BoxKind::Container(Inside::Table) {
+        table::intrinsic_widths(layout_box, ctx)
+    } else if layout_box.children.iter().any(LayoutBox::is_block_level) {
+        layout_box
+            .children
+            .iter()
+            .filter(|child| child.is_block_level() || child.is_floated())
+            .map(|child| intrinsic_outer_widths(child, ctx))
+            .fold((0.0, 0.0), |(min, max), (child_min, child_max)| {
+                (f32::max(min, child_min), f32::max(max, child_max))
+            })
+    } else {
+        inline::intrinsic_widths(&layout_box.children, ctx)
+    }
+}
+
+// The min-content and max-content widths of a box including its margins, borders and
+// padding. Percentages can't be resolved, so they count as zero or auto.
+pub(super) fn intrinsic_outer_widths(layout_box: &LayoutBox, ctx: &LayoutContext) -> (f32, f32) {
+    let style = &layout_box.style;
+    let fixed = |name: &str| match style.get(name) {
+        Some(Value::Percentage(_) | Value::Calc(..)) => None,
+        _ => style.length(name, 0.0),
+    };
+    let border = border(style);
+    let padding = padding(style, 0.0);
+    let extra = border.horizontal() + padding.horizontal();
+    let boxed = |size: f32| match style.keyword("box-sizing") {
+        Some("border-box") => (size - extra).max(0.0),
+        _ => size.max(0.0),
+    };
+    let (min, max) = match (fixed("width").map(boxed), &layout_box.kind) {
+        (Some(width), BoxKind::Container(_) | BoxKind::Replaced(_)) => (width, width),
+        _ => intrinsic_content_widths(layout_box, ctx),
+    };
+    let clamp = |width: f32| {
+        let width = fixed("max-width")
+            .map(boxed)
+            .map_or(width, |max| width.min(max));
+        fixed("min-width")
+            .map(boxed)
+            .map_or(width, |min| width.max(min))
+    };
+    let margin = margin_edges(style, 0.0);
+    let outer = margin.horizontal() + extra;
+    (clamp(min) + outer, clamp(max) + outer)
+}
+
+pub(super) const SIDES: [&str; 4] = ["top", "right", "bottom", "left"];
+
+fn edges([top, right, bottom, left]: [f32; 4]) -> Edges {
+    Edges {
+        top,
+        right,
+        bottom,
+        left,
+    }
+}
+
+// Margins in top, right, bottom, left order, None for auto
+pub(super) fn margins(style: &ComputedStyle, basis: f32) -> [Option<f32>; 4] {
+    SIDES.map(|side| style.length(&format!("margin-{}", side), basis))
+}
+
+// Margins with auto treated as zero
+pub(super) fn margin_edges(style: &ComputedStyle, basis: f32) -> Edges {
+    edges(margins(style, basis).map(|margin| margin.unwrap_or(0.0)))
+}
+
+pub(super) fn padding(style: &ComputedStyle, basis: f32) -> Edges {
+    edges(SIDES.map(|side| {
+        style
+            .length(&format!("padding-{}", side), basis)
+            .unwrap_or(0.0)
+            .max(0.0)
+    }))
+}
+
+// Border widths, which are zero for sides without a border style
+pub(super) fn border(style: &ComputedStyle) -> Edges {
+    edges(SIDES.map(|side| {
+        if matches!(
+            style.keyword(&format!("border-{}-style", side)),
+            None | Some("none" | "hidden")
+        ) {
+            return 0.0;
+        }
+        match style.get(&format!("border-{}-width", side)) {
+            Some(Value::Keyword(keyword)) => match keyword.as_str() {
+                "thin" => 1.0,
+                "medium" => 3.0,
+                "thick" => 5.0,
+                _ => 0.0,
+            },
+            Some(value) => value.to_px(0.0).unwrap_or(0.0).max(0.0),
+            None => 3.0,
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css;
+    use crate::fonts::FontSet;
+    use crate::html;
+    use crate::layout::text::FixedMetrics;
+    use crate::layout::{build_box_tree, layout_document};
+
+    fn layout(source: &str, width: f32) -> Fragment {
+        let document = html::parse_document(source);
+        let stylist = css::Stylist::for_document(&document, css::Device::default());
+        let root = build_box_tree(&document, &stylist.style_document(&document)).unwrap();
+        let fonts = FontSet::default();
+        let ctx = LayoutContext::new(&FixedMetrics, &fonts);
+        layout_document(&root, width, &ctx)
+    }
+
+    // The fragment of the body, the second child of html
+    fn body(source: &str, width: f32) -> Fragment {
+        layout(source, width).children[0].clone()
+    }
+
+    #[test]
+    fn test_block_widths() {
+        let body = body(
+            "<body style='margin: 0'>\
+             <div style='width: 100px; margin: 0 auto; padding: 5px; border: 2px solid'></div>\
+             <div style='margin-left: 10%; margin-right: 20px; padding: 0 10px'></div>\
+             <div style='width: 50%; max-width: 100px; box-sizing: border-box; padding: 10px'></div>\
+             <div style='width: 300px; min-width: 500px; margin-left: auto'></div>\
+             </body>",
+            400.0,
+        );
+        let rects: Vec<Rect> = body.children.iter().map(|child| child.rect).collect();
+        assert_eq!(
+            rects[0],
+            Rect::from_min_size(egui::pos2(143.0, 0.0), egui::vec2(114.0, 14.0))
+        );
+        assert_eq!(rects[0], body.children[0].rect);
+        assert_eq!(body.children[0].content_rect().width(), 100.0);
+        assert_eq!(rects[1].min.x, 40.0);
+        assert_eq!(rects[1].width(), 340.0);
+        assert_eq!(rects[2].width(), 100.0);
+        // Over-constrained, the box overflows to the right
+        assert_eq!(rects[3].min.x, 0.0);
+        assert_eq!(rects[3].width(), 500.0);
+    }
+
+    #[test]
+    fn test_heights_and_percentages() {
+        let body = body(
+            "<body style='margin: 0'>\
+             <div style='height: 50px; padding: 5px'><div style='height: 50%'></div></div>\
+             <div><div style='height: 50%'>text</div></div>\
+             <div style='height: 10px; min-height: 30px'></div>\
+             <div style='max-height: 5px'>text</div>\
+             </body>",
+            400.0,
+        );
+        let heights: Vec<f32> = body.children.iter().map(|c| c.rect.height()).collect();
+        assert_eq!(heights, vec![60.0, 20.0, 30.0, 5.0]);
+        assert_eq!(body.children[0].children[0].rect.height(), 25.0);
+        // Percentages of a height that depends on content are auto
+        assert_eq!(body.children[1].children[0].rect.height(), 20.0);
+        assert_eq!(body.rect.height(), 115.0);
+    }
+
+    #[test]
+    fn test_sibling_margins_collapse() {
+        let body = body(
+            "<body style='margin: 0'>\
+             <div style='height: 10px; margin-bottom: 20px'></div>\
+             <div style='height: 10px; margin-top: 30px; margin-bottom: -5px'></div>\
+             <div style='height: 10px; margin-top: 15px'></div>\
+             <div style='height: 10px; margin-top: -10px'></div>\
+             </body>",
+            400.0,
+        );
+        let tops: Vec<f32> = body.children.iter().map(|c| c.rect.min.y).collect();
+        assert_eq!(tops, vec![0.0, 40.0, 60.0, 60.0]);
+    }
+
+    #[test]
+    fn test_parent_and_empty_margins_collapse() {
+        let root = layout(
+            "<body style='margin: 0'>\
+             <div style='margin-top: 10px'><p style='margin: 20px 0'>text</p></div>\
+             <div style='margin: 15px 0'></div>\
+             <div style='border-top: 1px solid; margin-top: 5px'>\
+               <p style='margin: 20px 0'>text</p></div>\
+             </body>",
+            400.0,
+        );
+        let body = &root.children[0];
+        // The paragraph's top margin collapses through the div and body, past the root
+        assert_eq!(body.rect.min.y, 20.0);
+        let first = &body.children[0];
+        assert_eq!(first.rect.min.y, 20.0);
+        assert_eq!(first.children[0].rect.min.y, 20.0);
+        assert_eq!(first.rect.height(), 20.0);
+        // The empty div's margins collapse with the paragraph's bottom margin and the
+        // next div's top margin
+        let third = &body.children[2];
+        assert_eq!(third.rect.min.y, 60.0);
+        // A border separates the paragraph's top margin from its parent's, but the bottom
+        // margin still collapses through
+        assert_eq!(third.children[0].rect.min.y, 81.0);
+        assert_eq!(third.rect.height(), 41.0);
+    }
+
+    #[test]
+    fn test_formatting_context_roots_contain_margins() {
+        let body = body(
+            "<body style='margin: 0'>\
+             <div style='display: flow-root'><p style='margin: 20px 0'>text</p></div>\
+             <div style='overflow: hidden'><p style='margin: 10px 0'>text</p></div>\
+             </body>",
+            400.0,
+        );
+        assert_eq!(body.children[0].rect.min.y, 0.0);
+        assert_eq!(body.children[0].rect.height(), 60.0);
+        assert_eq!(body.children[1].rect.min.y, 60.0);
+        assert_eq!(body.children[1].children[0].rect.min.y, 70.0);
+    }
+
+    #[test]
+    fn test_replaced_sizes() {
+        let body = body(
+            "<body style='margin: 0'>\
+             <img style='display: block; margin: 0 auto' width=100 height=50>\
+             <img style='display: block; width: 50px' width=100 height=50>\
+             <img style='display: block; height: 10px; border: 1px solid'>\
+             </body>",
+            400.0,
+        );
+        assert_eq!(
+            body.children[0].rect,
+            Rect::from_min_size(egui::pos2(150.0, 0.0), egui::vec2(100.0, 50.0))
+        );
+        assert_eq!(body.children[1].rect.size(), egui::vec2(50.0, 25.0));
+        assert_eq!(body.children[2].rect.size(), egui::vec2(2.0, 12.0));
+        assert!(matches!(body.children[0].kind, FragmentKind::Image(_)));
+    }
+
+    #[test]
+    fn test_outside_list_markers() {
+        // The marker ends where the first line starts, in the list's padding
+        let body = body(
+            "<body style='margin: 0'><ul style='margin: 0'>\
+             <li><div style='float: left; width: 20px; height: 5px'></div>ab</li></ul></body>",
+            400.0,
+        );
+        let item = &body.children[0].children[0];
+        let marker = item.children.last().unwrap();
+        assert_eq!(marker.node, item.node);
+        assert_eq!(
+            marker.rect,
+            Rect::from_min_size(egui::pos2(44.0, 0.0), egui::vec2(16.0, 20.0))
+        );
+    }
+}
@@ -0,0 +1,1164 @@
+// Box tree construction: the boxes generated for each element from its computed `display`
+
+use crate::css::{ComputedStyle, PseudoElement, StyleTree, StyledNode};
+use crate::dom::{Document, Element, ElementType, Node};
+use crate::images::LoadedImage;
+use crate::layout::generated;
+use eframe::egui::Vec2;
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// How a box takes part in the layout of its parent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outside {
+    Block,
+    Inline,
+}
+
+/// How a box lays out its children
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Inside {
+    Flow,
+    // Flow layout in a new block formatting context
+    FlowRoot,
+    Flex,
+    Grid,
+    Table,
+}
+
+/// The parts of a table, from the `table-*` display values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TablePart {
+    Caption,
+    HeaderGroup,
+    RowGroup,
+    FooterGroup,
+    Row,
+    // Spans from the colspan and rowspan attributes. A row span of 0 reaches the end of
+    // the row group.
+    Cell { columns: usize, rows: usize },
+    ColumnGroup { span: usize },
+    Column { span: usize },
+    // The grid of rows and columns inside a table's wrapper box, beside its captions
+    Grid,
+}
+
+/// The computed `display` of an element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Display {
+    None,
+    // The element generates no box, its children take its place
+    Contents,
+    Box(Outside, Inside),
+    Internal(TablePart),
+}
+
+impl Display {
+    pub fn of(style: &ComputedStyle) -> Self {
+        match style.display() {
+            "none" => Display::None,
+            "contents" => Display::Contents,
+            "block" | "list-item" => Display::Box(Outside::Block, Inside::Flow),
+            "flow-root" => Display::Box(Outside::Block, Inside::FlowRoot),
+            "inline-block" => Display::Box(Outside::Inline, Inside::FlowRoot),
+            "flex" => Display::Box(Outside::Block, Inside::Flex),
+            "inline-flex" => Display::Box(Outside::Inline, Inside::Flex),
+            "grid" => Display::Box(Outside::Block, Inside::Grid),
+            "inline-grid" => Display::Box(Outside::Inline, Inside::Grid),
+            "table" => Display::Box(Outside::Block, Inside::Table),
+            "inline-table" => Display::Box(Outside::Inline, Inside::Table),
+            "table-caption" => Display::Internal(TablePart::Caption),
+            "table-header-group" => Display::Internal(TablePart::HeaderGroup),
+            "table-row-group" => Display::Internal(TablePart::RowGroup),
+            "table-footer-group" => Display::Internal(TablePart::FooterGroup),
+            "table-row" => Display::Internal(TablePart::Row),
+            "table-cell" => Display::Internal(TablePart::Cell {
+                columns: 1,
+                rows: 1,
+            }),
+            "table-column-group" => Display::Internal(TablePart::ColumnGroup { span: 1 }),
+            "table-column" => Display::Internal(TablePart::Column { span: 1 }),
+            _ => Display::Box(Outside::Inline, Inside::Flow),
+        }
+    }
+}
+
+/// The content of a replaced element
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub src: Option<String>,
+    pub alt: String,
+    // Size hints from the width and height attributes
+    pub width: Option<f32>,
+    pub height: Option<f32>,
+    pub content: ImageContent,
+}
+
+impl Image {
+    // The size of the loaded image in CSS pixels
+    pub fn natural_size(&self) -> Option<Vec2> {
+        match &self.content {
+            ImageContent::Loaded { image, density } => Some(image.size() / *density),
+            _ => None,
+        }
+    }
+}
+
+/// Where a replaced element's image is, filled in once its source has been picked
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum ImageContent {
+    #[default]
+    Pending,
+    // The density is the image's pixels per CSS pixel
+    Loaded {
+        image: Arc<LoadedImage>,
+        density: f32,
+    },
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BoxKind {
+    // A box containing other boxes, laid out according to `Inside`
+    Container(Inside),
+    // An inline box, whose contents flow in the lines of its block container
+    Inline,
+    Text(String),
+    LineBreak,
+    Replaced(Image),
+    TablePart(TablePart),
+}
+
+/// A box generated for an element, a text node, or anonymously to wrap inline content
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutBox {
+    pub kind: BoxKind,
+    pub outside: Outside,
+    pub style: Arc<ComputedStyle>,
+    // Path of the DOM node that generated the box, None for anonymous boxes
+    pub node: Option<Vec<usize>>,
+    pub children: Vec<LayoutBox>,
+    // The marker of a list item with an outside list-style-position, placed beside its
+    // first line rather than in it
+    pub marker: Option<Box<LayoutBox>>,
+}
+
+impl LayoutBox {
+    fn new(kind: BoxKind, outside: Outside, style: &Arc<ComputedStyle>, node: &[usize]) -> Self {
+        Self {
+            kind,
+            outside,
+            style: Arc::clone(style),
+            node: Some(node.to_vec()),
+            children: Vec::new(),
+            marker: None,
+        }
+    }
+
+    // An anonymous block wrapping inline content
+    fn anonymous_block(style: &Arc<ComputedStyle>, children: Vec<LayoutBox>) -> Self {
+        Self {
+            kind: BoxKind::Container(Inside::Flow),
+            outside: Outside::Block,
+            style: Arc::clone(style),
+            node: None,
+            children,
+            marker: None,
+        }
+    }
+
+    pub fn is_anonymous(&self) -> bool {
+        self.node.is_none()
+    }
+
+    // Block-level boxes in the normal flow, which floats and absolutely positioned boxes
+    // are taken out of
+    pub fn is_block_level(&self) -> bool {
+        self.outside == Outside::Block && !self.is_floated() && !self.is_absolutely_positioned()
+    }
+
+    // Absolute positioning wins over floating
+    pub fn is_floated(&self) -> bool {
+        self.outside == Outside::Block
+            && is_float(&self.style)
+            && !is_absolutely_positioned(&self.style)
+    }
+
+    pub fn is_absolutely_positioned(&self) -> bool {
+        self.outside == Outside::Block && is_absolutely_positioned(&self.style)
+    }
+
+    // Turns a replaced box whose image failed to load into a box holding its alternative
+    // text, as an inline box or a block depending on how the image sat
+    pub fn fall_back_to_alt_text(&mut self) {
+        let BoxKind::Replaced(image) = &self.kind else {
+            return;
+        };
+        let text = LayoutBox {
+            kind: BoxKind::Text(image.alt.clone()),
+            outside: Outside::Inline,
+            style: Arc::clone(&self.style),
+            node: self.node.clone(),
+            children: Vec::new(),
+            marker: None,
+        };
+        self.kind = match self.outside {
+            Outside::Inline => BoxKind::Inline,
+            Outside::Block => BoxKind::Container(Inside::Flow),
+        };
+        self.children = vec![text];
+    }
+
+    // Returns a textual outline of the tree, one box per line, for snapshot tests
+    pub fn dump(&self) -> String {
+        let mut output = String::new();
+        self.dump_into(&mut output, 0);
+        output
+    }
+
+    fn dump_into(&self, output: &mut String, depth: usize) {
+        let indent = "  ".repeat(depth);
+        let label = match (&self.kind, self.outside) {
+            (BoxKind::Container(Inside::Flow), Outside::Block) => "block",
+            (BoxKind::Container(Inside::FlowRoot), Outside::Block) => "flow-root",
+            (BoxKind::Container(Inside::Flow | Inside::FlowRoot), Outside::Inline) => {
+                "inline-block"
+            }
+            (BoxKind::Container(Inside::Flex), Outside::Block) => "flex",
+            (BoxKind::Container(Inside::Flex), Outside::Inline) => "inline-flex",
+            (BoxKind::Container(Inside::Grid), Outside::Block) => "grid",
+            (BoxKind::Container(Inside::Grid), Outside::Inline) => "inline-grid",
+            (BoxKind::Container(Inside::Table), Outside::Block) => "table",
+            (BoxKind::Container(Inside::Table), Outside::Inline) => "inline-table",
+            (BoxKind::Inline, _) => "inline",
+            (BoxKind::LineBreak, _) => "line-break",
+            (BoxKind::Replaced(_), Outside::Block) => "block replaced",
+            (BoxKind::Replaced(_), Outside::Inline) => "replaced",
+            (BoxKind::TablePart(part), _) => match part {
+                TablePart::Caption => "table-caption",
+                TablePart::HeaderGroup => "table-header-group",
+                TablePart::RowGroup => "table-row-group",
+                TablePart::FooterGroup => "table-footer-group",
+                TablePart::Row => "table-row",
+                TablePart::Cell { .. } => "table-cell",
+                TablePart::ColumnGroup { .. } => "table-column-group",
+                TablePart::Column { .. } => "table-column",
+                TablePart::Grid => "table-grid",
+            },
+            (BoxKind::Text(text), _) => {
+                let _ = writeln!(output, "{}text {:?}", indent, text);
+                return;
+            }
+        };
+        let _ = write!(output, "{}{}", indent, label);
+        if self.is_anonymous() {
+            output.push_str(" (anonymous)");
+        }
+        if let BoxKind::Replaced(image) = &self.kind {
+            if let Some(src) = &image.src {
+                let _ = write!(output, " src={:?}", src);
+            }
+        }
+        output.push('\n');
+        if let Some(BoxKind::Text(text)) =
+            self.marker.as_ref().map(|marker| &marker.children[0].kind)
+        {
+            let _ = writeln!(output, "{}  marker {:?}", indent, text);
+        }
+        for child in &self.children {
+            child.dump_into(output, depth + 1);
+        }
+    }
+}
+
+// Builds the box tree for a styled document. Returns None if the root element generates
+// no box.
+pub fn build_box_tree(document: &Document, styles: &StyleTree) -> Option<LayoutBox> {
+    let styles = generated::resolve_content(document, styles);
+    let (index, root, styled) = document
+        .nodes
+        .iter()
+        .zip(&styles.nodes)
+        .enumerate()
+        .find_map(|(index, (node, styled))| match node {
+            Node::Element(element) => Some((index, element, styled)),
+            _ => None,
+        })?;
+    // The root element is always a block container
+    let inside = match Display::of(&styled.style) {
+        Display::None => return None,
+        Display::Contents | Display::Internal(_) => Inside::Flow,
+        Display::Box(_, inside) => inside,
+    };
+    if inside == Inside::Table {
+        return Some(build_table(root, styled, &[index], Outside::Block));
+    }
+    Some(build_container(
+        root,
+        styled,
+        &[index],
+        Outside::Block,
+        inside,
+    ))
+}
+
+fn build_container(
+    element: &Element,
+    styled: &StyledNode,
+    path: &[usize],
+    outside: Outside,
+    inside: Inside,
+) -> LayoutBox {
+    let mut container = LayoutBox::new(BoxKind::Container(inside), outside, &styled.style, path);
+    let mut builder = ContainerBuilder::new(&styled.style);
+    // The children of flex and grid containers are laid out as blocks
+    builder.blockify = matches!(inside, Inside::Flex | Inside::Grid);
+    let marker = styled
+        .pseudo_element(PseudoElement::Marker)
+        .filter(|style| generated::generated_text(style).is_some());
+    if let Some(style) = marker {
+        match style.keyword("list-style-position") {
+            Some("inside") => builder.push_inline(marker_box(style, path, Outside::Inline)),
+            _ => container.marker = Some(Box::new(marker_box(style, path, Outside::Block))),
+        }
+    }
+    builder.add_contents(element, styled, path);
+    container.children = builder.finish();
+    container
+}
+
+// The box of a list item's marker. Inside markers are inline boxes at the start of its
+// content, outside markers sized to fit their text.
+fn marker_box(style: &Arc<ComputedStyle>, path: &[usize], outside: Outside) -> LayoutBox {
+    let text = generated::generated_text(style).unwrap_or_default();
+    let kind = match outside {
+        Outside::Inline => BoxKind::Inline,
+        Outside::Block => BoxKind::Container(Inside::FlowRoot),
+    };
+    let mut marker = LayoutBox::new(kind, Outside::Inline, style, path);
+    marker.children.push(LayoutBox::new(
+        BoxKind::Text(text.to_string()),
+        Outside::Inline,
+        style,
+        path,
+    ));
+    marker
+}
+
+// Builds a table's wrapper box, holding its captions and the table grid
+fn build_table(
+    element: &Element,
+    styled: &StyledNode,
+    path: &[usize],
+    outside: Outside,
+) -> LayoutBox {
+    let mut builder = TableBuilder::new(TablePart::Grid, &styled.style);
+    builder.add_children(element, styled, path);
+    table_wrapper(&styled.style, Some(path), outside, builder)
+}
+
+// Properties that apply to a table's wrapper box rather than its grid
+const WRAPPER_PROPERTIES: &[&str] = &[
+    "margin-top",
+    "margin-right",
+    "margin-bottom",
+    "margin-left",
+    "position",
+    "top",
+    "right",
+    "bottom",
+    "left",
+    "float",
+    "clear",
+    "z-index",
+];
+
+fn table_wrapper(
+    style: &Arc<ComputedStyle>,
+    node: Option<&[usize]>,
+    outside: Outside,
+    builder: TableBuilder,
+) -> LayoutBox {
+    let mut wrapper_style = style.inherit();
+    let mut grid_style = ComputedStyle::clone(style);
+    for name in WRAPPER_PROPERTIES {
+        if let Some(value) = style.get(name) {
+            wrapper_style.set(name, value.clone());
+        }
+        grid_style.reset(name);
+    }
+    let (mut children, rows) = builder.finish();
+    children.push(LayoutBox {
+        kind: BoxKind::TablePart(TablePart::Grid),
+        outside: Outside::Block,
+        style: Arc::new(grid_style),
+        node: node.map(<[usize]>::to_vec),
+        children: rows,
+        marker: None,
+    });
+    LayoutBox {
+        kind: BoxKind::Container(Inside::Table),
+        outside,
+        style: Arc::new(wrapper_style),
+        node: node.map(<[usize]>::to_vec),
+        children,
+        marker: None,
+    }
+}
+
+// Builds the box of a table part found where it belongs
+fn build_table_part(
+    element: &Element,
+    styled: &StyledNode,
+    path: &[usize],
+    part: TablePart,
+) -> LayoutBox {
+    let span = |name: &str, default: usize, range: std::ops::RangeInclusive<usize>| {
+        element
+            .attributes
+            .get(name)
+            .and_then(|value| value.trim().parse::<usize>().ok())
+            .map_or(default, |span| span.clamp(*range.start(), *range.end()))
+    };
+    let part = match part {
+        TablePart::Cell { .. } => TablePart::Cell {
+            columns: span("colspan", 1, 1..=1000),
+            rows: span("rowspan", 1, 0..=65534),
+        },
+        TablePart::Column { .. } => TablePart::Column {
+            span: span("span", 1, 1..=1000),
+        },
+        TablePart::ColumnGroup { .. } => TablePart::ColumnGroup {
+            span: span("span", 1, 1..=1000),
+        },
+        part => part,
+    };
+    let mut layout_box = LayoutBox::new(
+        BoxKind::TablePart(part),
+        Outside::Block,
+        &styled.style,
+        path,
+    );
+    layout_box.children = match part {
+        TablePart::Caption | TablePart::Cell { .. } => {
+            let mut builder = ContainerBuilder::new(&styled.style);
+            builder.add_contents(element, styled, path);
+            builder.finish()
+        }
+        TablePart::HeaderGroup | TablePart::RowGroup | TablePart::FooterGroup | TablePart::Row => {
+            let mut builder = TableBuilder::new(part, &styled.style);
+            builder.add_children(element, styled, path);
+            builder.finish().1
+        }
+        // Column groups only hold columns
+        TablePart::ColumnGroup { .. } => element
+            .children
+            .iter()
+            .zip(&styled.children)
+            .enumerate()
+            .filter_map(|(index, (child, styled_child))| match child {
+                Node::Element(child) => match Display::of(&styled_child.style) {
+                    Display::Internal(column @ TablePart::Column { .. }) => {
+                        let mut child_path = path.to_vec();
+                        child_path.push(index);
+                        Some(build_table_part(child, styled_child, &child_path, column))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect(),
+        TablePart::Column { .. } | TablePart::Grid => Vec::new(),
+    };
+    layout_box
+}
+
+// Collects the children of a table grid, row group or row. Children that don't belong
+// there are wrapped in an anonymous row or cell, and whitespace between parts is dropped.
+struct TableBuilder {
+    part: TablePart,
+    // Anonymous rows and cells inherit from the part
+    anonymous_style: Arc<ComputedStyle>,
+    captions: Vec<LayoutBox>,
+    children: Vec<LayoutBox>,
+    anonymous: Option<AnonymousPart>,
+}
+
+enum AnonymousPart {
+    Row(Box<TableBuilder>),
+    Cell(Box<ContainerBuilder>),
+}
+
+impl TableBuilder {
+    fn new(part: TablePart, style: &ComputedStyle) -> Self {
+        Self {
+            part,
+            anonymous_style: Arc::new(style.inherit()),
+            captions: Vec::new(),
+            children: Vec::new(),
+            anonymous: None,
+        }
+    }
+
+    fn add_children(&mut self, element: &Element, styled: &StyledNode, path: &[usize]) {
+        for (index, (child, styled_child)) in
+            element.children.iter().zip(&styled.children).enumerate()
+        {
+            let mut child_path = path.to_vec();
+            child_path.push(index);
+            self.add_node(child, styled_child, &child_path);
+        }
+    }
+
+    fn add_node(&mut self, node: &Node, styled: &StyledNode, path: &[usize]) {
+        match node {
+            Node::Element(element) => match Display::of(&styled.style) {
+                Display::None => {}
+                Display::Contents => self.add_children(element, styled, path),
+                Display::Internal(part) => self.add_part(element, styled, path, part),
+                Display::Box(..) => self.add_content(node, styled, path),
+            },
+            // Whitespace only counts inside an anonymous cell
+            Node::Text(text)
+                if text.chars().all(|c| c.is_ascii_whitespace())
+                    && !matches!(self.anonymous, Some(AnonymousPart::Cell(_))) => {}
+            Node::Text(_) => self.add_content(node, styled, path),
+            _ => {}
+        }
+    }
+
+    fn add_part(
+        &mut self,
+        element: &Element,
+        styled: &StyledNode,
+        path: &[usize],
+        part: TablePart,
+    ) {
+        let belongs = match (self.part, part) {
+            (TablePart::Grid, TablePart::Caption) => {
+                self.close_anonymous();
+                let caption = build_table_part(element, styled, path, part);
+                return self.captions.push(caption);
+            }
+            (
+                TablePart::Grid,
+                TablePart::HeaderGroup
+                | TablePart::RowGroup
+                | TablePart::FooterGroup
+                | TablePart::ColumnGroup { .. }
+                | TablePart::Column { .. },
+            ) => true,
+            (
+                TablePart::Grid
+                | TablePart::HeaderGroup
+                | TablePart::RowGroup
+                | TablePart::FooterGroup,
+                TablePart::Row,
+            ) => true,
+            (TablePart::Row, TablePart::Cell { .. }) => true,
+            _ => false,
+        };
+        if belongs {
+            self.close_anonymous();
+            let child = build_table_part(element, styled, path, part);
+            return self.children.push(child);
+        }
+        match self.part {
+            // Anything else in a row goes in an anonymous cell, becoming an anonymous table
+            TablePart::Row => self.anonymous_cell().add_element(element, styled, path),
+            _ => self.anonymous_row().add_part(element, styled, path, part),
+        }
+    }
+
+    fn add_content(&mut self, node: &Node, styled: &StyledNode, path: &[usize]) {
+        match self.part {
+            TablePart::Row => self.anonymous_cell().add_node(node, styled, path),
+            _ => self.anonymous_row().add_content(node, styled, path),
+        }
+    }
+
+    fn anonymous_row(&mut self) -> &mut TableBuilder {
+        if !matches!(self.anonymous, Some(AnonymousPart::Row(_))) {
+            self.close_anonymous();
+            let row = TableBuilder::new(TablePart::Row, &self.anonymous_style);
+            self.anonymous = Some(AnonymousPart::Row(Box::new(row)));
+        }
+        match &mut self.anonymous {
+            Some(AnonymousPart::Row(row)) => row,
+            _ => unreachable!(),
+        }
+    }
+
+    fn anonymous_cell(&mut self) -> &mut ContainerBuilder {
+        if !matches!(self.anonymous, Some(AnonymousPart::Cell(_))) {
+            self.close_anonymous();
+            let cell = ContainerBuilder::new(&self.anonymous_style);
+            self.anonymous = Some(AnonymousPart::Cell(Box::new(cell)));
+        }
+        match &mut self.anonymous {
+            Some(AnonymousPart::Cell(cell)) => cell,
+            _ => unreachable!(),
+        }
+    }
+
+    fn close_anonymous(&mut self) {
+        let (part, children) = match self.anonymous.take() {
+            None => return,
+            Some(AnonymousPart::Row(row)) => (TablePart::Row, row.finish().1),
+            Some(AnonymousPart::Cell(cell)) => (
+                TablePart::Cell {
+                    columns: 1,
+                    rows: 1,
+                },
+                cell.finish(),
+            ),
+        };
+        self.children.push(LayoutBox {
+            kind: BoxKind::TablePart(part),
+            outside: Outside::Block,
+            style: Arc::clone(&self.anonymous_style),
+            node: None,
+            children,
+            marker: None,
+        });
+    }
+
+    // Returns the captions and the other children
+    fn finish(mut self) -> (Vec<LayoutBox>, Vec<LayoutBox>) {
+        self.close_anonymous();
+        (self.captions, self.children)
+    }
+}
+
+// Collects the children of a block container. Inline-level boxes are gathered until a
+// block-level box arrives, then wrapped in an anonymous block. Inline boxes that are open
+// when a block arrives are split around it.
+struct ContainerBuilder {
+    // Anonymous blocks inherit from the container
+    anonymous_style: Arc<ComputedStyle>,
+    blocks: Vec<LayoutBox>,
+    inlines: Vec<LayoutBox>,
+    // Inline boxes being built, outermost first
+    open: Vec<LayoutBox>,
+    // Whether child elements become block-level, with text wrapped in anonymous blocks
+    blockify: bool,
+    // Table parts outside a table, gathered into an anonymous table
+    table: Option<TableBuilder>,
+}
+
+impl ContainerBuilder {
+    fn new(style: &ComputedStyle) -> Self {
+        Self {
+            anonymous_style: Arc::new(style.inherit()),
+            blocks: Vec::new(),
+            inlines: Vec::new(),
+            open: Vec::new(),
+            blockify: false,
+            table: None,
+        }
+    }
+
+    fn add_children(&mut self, element: &Element, styled: &StyledNode, path: &[usize]) {
+        for (index, (child, styled_child)) in
+            element.children.iter().zip(&styled.children).enumerate()
+        {
+            let mut child_path = path.to_vec();
+            child_path.push(index);
+            self.add_node(child, styled_child, &child_path);
+        }
+    }
+
+    // Adds an element's children between the boxes of its ::before and ::after
+    fn add_contents(&mut self, element: &Element, styled: &StyledNode, path: &[usize]) {
+        self.add_pseudo_element(styled, PseudoElement::Before, path);
+        self.add_children(element, styled, path);
+        self.add_pseudo_element(styled, PseudoElement::After, path);
+    }
+
+    // Adds the box of a ::before or ::after, which is laid out like a child element holding
+    // its generated text
+    fn add_pseudo_element(
+        &mut self,
+        styled: &StyledNode,
+        pseudo_element: PseudoElement,
+        path: &[usize],
+    ) {
+        let Some(style) = styled.pseudo_element(pseudo_element) else {
+            return;
+        };
+        let Some(text) = generated::generated_text(style) else {
+            return;
+        };
+        let text = LayoutBox::new(
+            BoxKind::Text(text.to_string()),
+            Outside::Inline,
+            style,
+            path,
+        );
+        let (outside, inside) = match Display::of(style) {
+            Display::None => return,
+            Display::Contents => return self.push_inline(text),
+            Display::Box(outside, inside) => (outside, inside),
+            Display::Internal(_) => (Outside::Inline, Inside::Flow),
+        };
+        let outside = match self.blockify || is_float(style) || is_absolutely_positioned(style) {
+            true => Outside::Block,
+            false => outside,
+        };
+        if (outside, inside) == (Outside::Inline, Inside::Flow) {
+            let mut inline = LayoutBox::new(BoxKind::Inline, outside, style, path);
+            inline.children.push(text);
+            return self.push_inline(inline);
+        }
+        let inside = match inside {
+            Inside::Table => Inside::Flow,
+            inside => inside,
+        };
+        let mut container = LayoutBox::new(BoxKind::Container(inside), outside, style, path);
+        let mut builder = ContainerBuilder::new(style);
+        builder.blockify = matches!(inside, Inside::Flex | Inside::Grid);
+        builder.push_inline(text);
+        container.children = builder.finish();
+        self.push(container);
+    }
+
+    fn add_node(&mut self, node: &Node, styled: &StyledNode, path: &[usize]) {
+        match node {
+            // Whitespace between table parts belongs to their anonymous table
+            Node::Text(text)
+                if self.table.is_some() && text.chars().all(|c| c.is_ascii_whitespace()) => {}
+            Node::Text(text) if !text.is_empty() => self.push_inline(LayoutBox::new(
+                BoxKind::Text(text.clone()),
+                Outside::Inline,
+                &styled.style,
+                path,
+            )),
+            Node::Element(element) => self.add_element(element, styled, path),
+            _ => {}
+        }
+    }
+
+    fn add_element(&mut self, element: &Element, styled: &StyledNode, path: &[usize]) {
+        let style = &styled.style;
+        let (outside, inside) = match Display::of(style) {
+            Display::None => return,
+            Display::Contents => return self.add_contents(element, styled, path),
+            Display::Internal(_) if self.blockify || is_absolutely_positioned(style) => {
+                (Outside::Block, Inside::Flow)
+            }
+            Display::Internal(part) => {
+                let anonymous_style = &self.anonymous_style;
+                return self
+                    .table
+                    .get_or_insert_with(|| TableBuilder::new(TablePart::Grid, anonymous_style))
+                    .add_part(element, styled, path, part);
+            }
+            Display::Box(_, inside) if self.blockify => (Outside::Block, inside),
+            // Floats and absolutely positioned boxes are blockified
+            Display::Box(_, inside) if is_float(style) || is_absolutely_positioned(style) => {
+                (Outside::Block, inside)
+            }
+            Display::Box(outside, inside) => (outside, inside),
+        };
+        if element.element_type == ElementType::Img {
+            let image = LayoutBox::new(BoxKind::Replaced(image(element)), outside, style, path);
+            return self.push(image);
+        }
+        if element.element_type.tag_name().eq_ignore_ascii_case("br") {
+            return self.push_inline(LayoutBox::new(
+                BoxKind::LineBreak,
+                Outside::Inline,
+                style,
+                path,
+            ));
+        }
+        match (outside, inside) {
+            (outside, Inside::Table) => self.push(build_table(element, styled, path, outside)),
+            (Outside::Inline, Inside::Flow) => {
+                self.open.push(LayoutBox::new(
+                    BoxKind::Inline,
+                    Outside::Inline,
+                    style,
+                    path,
+                ));
+                self.add_contents(element, styled, path);
+                if let Some(inline) = self.open.pop() {
+                    self.push_inline(inline);
+                }
+            }
+            (Outside::Inline, inside) => {
+                self.push_inline(build_container(element, styled, path, outside, inside))
+            }
+            (Outside::Block, inside) => {
+                self.push(build_container(element, styled, path, outside, inside))
+            }
+        }
+    }
+
+    fn push(&mut self, layout_box: LayoutBox) {
+        match layout_box.outside {
+            // A float among inline content stays in its line so the text flows around it, and
+            // an absolutely positioned box so that its static position is in the line
+            Outside::Block
+                if (layout_box.is_floated() || layout_box.is_absolutely_positioned())
+                    && !self.blockify =>
+            {
+                match self.inlines.is_empty() && self.open.is_empty() {
+                    true => self.push_block(layout_box),
+                    false => self.push_inline(layout_box),
+                }
+            }
+            Outside::Block => self.push_block(layout_box),
+            Outside::Inline => self.push_inline(layout_box),
+        }
+    }
+
+    // Ends an anonymous table of stray table parts, which sits inline inside inline boxes
+    fn close_table(&mut self) {
+        let Some(table) = self.table.take() else {
+            return;
+        };
+        let outside = match self.open.is_empty() && !self.blockify {
+            true => Outside::Block,
+            false if self.blockify => Outside::Block,
+            false => Outside::Inline,
+        };
+        let style = Arc::clone(&self.anonymous_style);
+        let wrapper = table_wrapper(&style, None, outside, table);
+        match outside {
+            Outside::Block => self.push_block(wrapper),
+            Outside::Inline => self.push_inline(wrapper),
+        }
+    }
+
+    fn push_inline(&mut self, inline: LayoutBox) {
+        self.close_table();
+        match self.open.last_mut() {
+            Some(parent) => parent.children.push(inline),
+            None => self.inlines.push(inline),
+        }
+    }
+
+    fn push_block(&mut self, block: LayoutBox) {
+        self.close_table();
+        // The open inline boxes continue after the block with the same style
+        let continuations: Vec<LayoutBox> = self
+            .open
+            .iter()
+            .map(|inline| LayoutBox {
+                children: Vec::new(),
+                ..inline.clone()
+            })
+            .collect();
+        self.close_open_inlines();
+        self.wrap_inlines();
+        self.blocks.push(block);
+        self.open = continuations;
+    }
+
+    fn close_open_inlines(&mut self) {
+        while let Some(inline) = self.open.pop() {
+            self.push_inline(inline);
+        }
+    }
+
+    // Moves pending inline content into an anonymous block, dropping it if it's only
+    // collapsible whitespace
+    fn wrap_inlines(&mut self) {
+        let inlines = std::mem::take(&mut self.inlines);
+        if inlines.iter().any(has_content) {
+            let block = LayoutBox::anonymous_block(&self.anonymous_style, inlines);
+            self.blocks.push(block);
+        }
+    }
+
+    // A container holding only inline content needs no anonymous wrapper
+    fn finish(mut self) -> Vec<LayoutBox> {
+        self.close_table();
+        self.close_open_inlines();
+        if self.blocks.is_empty() && !self.blockify {
+            return self.inlines;
+        }
+        self.wrap_inlines();
+        self.blocks
+    }
+}
+
+// Returns true if an inline-level box would generate anything other than collapsed
+// whitespace
+fn has_content(inline: &LayoutBox) -> bool {
+    match &inline.kind {
+        BoxKind::Text(text) => {
+            let preserves_spaces = matches!(
+                inline.style.keyword("white-space"),
+                Some("pre" | "pre-wrap" | "pre-line" | "break-spaces")
+            );
+            preserves_spaces || !text.chars().all(|c| c.is_ascii_whitespace())
+        }
+        BoxKind::Inline => inline.children.iter().any(has_content),
+        _ => true,
+    }
+}
+
+fn is_float(style: &ComputedStyle) -> bool {
+    matches!(style.keyword("float"), Some("left" | "right"))
+}
+
+fn is_absolutely_positioned(style: &ComputedStyle) -> bool {
+    matches!(style.keyword("position"), Some("absolute" | "fixed"))
+}
+
+fn image(element: &Element) -> Image {
+    let dimension = |name: &str| {
+        element
+            .attributes
+            .get(name)
+            .and_then(|value| value.trim().trim_end_matches("px").parse::<f32>().ok())
+            .filter(|value| *value >= 0.0)
+    };
+    Image {
+        src: element.attributes.get("src").cloned(),
+        alt: element.attributes.get("alt").cloned().unwrap_or_default(),
+        width: dimension("width"),
+        height: dimension("height"),
+        content: ImageContent::Pending,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css;
+    use crate::html;
+
+    fn dump(source: &str) -> String {
+        let document = html::parse_document(source);
+        let stylist = css::Stylist::for_document(&document, css::Device::default());
+        let styles = stylist.style_document(&document);
+        build_box_tree(&document, &styles)
+            .map(|root| root.dump())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_blocks_and_inlines() {
+        assert_eq!(
+            dump(
+                "<html><head><title>Hidden</title></head>\
+                 <body><p>Hello <b>world</b></p><div>Text<br>more</div></body></html>"
+            ),
+            "\
+block
+  block
+    block
+      text \"Hello \"
+      inline
+        text \"world\"
+    block
+      text \"Text\"
+      line-break
+      text \"more\"
+"
+        );
+    }
+
+    #[test]
+    fn test_anonymous_blocks() {
+        // Inline content next to blocks is wrapped, whitespace between blocks is dropped
+        assert_eq!(
+            dump("<body>Before <p>Block</p>\n  <div>Other</div> after</body>"),
+            "\
+block
+  block
+    block (anonymous)
+      text \"Before \"
+    block
+      text \"Block\"
+    block
+      text \"Other\"
+    block (anonymous)
+      text \" after\"
+"
+        );
+    }
+
+    #[test]
+    fn test_block_in_inline_split() {
+        assert_eq!(
+            dump("<body><span>One <em>two<p>Block</p>three</em></span></body>"),
+            "\
+block
+  block
+    block (anonymous)
+      inline
+        text \"One \"
+        inline
+          text \"two\"
+    block
+      text \"Block\"
+    block (anonymous)
+      inline
+        inline
+          text \"three\"
+"
+        );
+    }
+
+    #[test]
+    fn test_display_none_and_contents() {
+        assert_eq!(
+            dump(
+                "<body><div style='display: none'><p>Gone</p></div>\
+                 <div style='display: contents'><p>Kept</p>text</div>\
+                 <span style='display: inline-block'><p>Inner</p></span>\
+                 <img src='a.png' alt='A' width=20>\
+                 <img style='display: block' src='b.png'></body>"
+            ),
+            "\
+block
+  block
+    block
+      text \"Kept\"
+    block (anonymous)
+      text \"text\"
+      inline-block
+        block
+          text \"Inner\"
+      replaced src=\"a.png\"
+    block replaced src=\"b.png\"
+"
+        );
+        assert_eq!(dump("<html style='display: none'></html>"), "");
+    }
+
+    #[test]
+    fn test_flex_items_are_blockified() {
+        assert_eq!(
+            dump(
+                "<body><div style='display: flex'>text <span>item</span> \
+                 <b style='display: inline-block'>other</b></div></body>"
+            ),
+            "\
+block
+  block
+    flex
+      block (anonymous)
+        text \"text \"
+      block
+        text \"item\"
+      flow-root
+        text \"other\"
+"
+        );
+    }
+
+    #[test]
+    fn test_table_boxes() {
+        assert_eq!(
+            dump(
+                "<body><table><caption>Title</caption><col span=2>\
+                 <tr><td colspan=2>a</td></tr></table></body>"
+            ),
+            "\
+block
+  block
+    table
+      table-caption
+        text \"Title\"
+      table-grid
+        table-column-group
+          table-column
+        table-row-group
+          table-row
+            table-cell
+              text \"a\"
+"
+        );
+        // Stray table parts are wrapped in anonymous rows, cells and tables
+        assert_eq!(
+            dump(
+                "<body><div style='display: table-cell'>a</div>\
+                 <div style='display: table-cell'>b</div>\
+                 <div style='display: table'> text <p>c</p></div></body>"
+            ),
+            "\
+block
+  block
+    table (anonymous)
+      table-grid (anonymous)
+        table-row (anonymous)
+          table-cell
+            text \"a\"
+          table-cell
+            text \"b\"
+    table
+      table-grid
+        table-row (anonymous)
+          table-cell (anonymous)
+            block (anonymous)
+              text \" text \"
+            block
+              text \"c\"
+"
+        );
+    }
+
+    #[test]
+    fn test_markers_and_pseudo_elements() {
+        assert_eq!(
+            dump(
+                "<style>p::before { content: 'a' } p::after { content: 'b'; display: block }\
+                 span::before { content: 'c' }</style>\
+                 <body><ul><li>One<li style='list-style-position: inside'>Two</ul>\
+                 <p><span>Text</span></p></body>"
+            ),
+            "\
+block
+  block
+    block
+      block
+        marker \"\u{2022} \"
+        text \"One\"
+      block
+        inline
+          text \"\u{2022} \"
+        text \"Two\"
+    block
+      block (anonymous)
+        inline
+          text \"a\"
+        inline
+          inline
+            text \"c\"
+          text \"Text\"
+      block
+        text \"b\"
+"
+        );
+    }
+
+    #[test]
+    fn test_box_details() {
+        let document = html::parse_document(
+            "<body><p>Text <img src=a.png alt=Alt width=20 height='10px'></p></body>",
+        );
+        let stylist = css::Stylist::for_document(&document, css::Device::default());
+        let root = build_box_tree(&document, &stylist.style_document(&document)).unwrap();
+        let paragraph = &root.children[0].children[0];
+        assert_eq!(paragraph.node.as_deref(), Some(&[0, 1, 0][..]));
+        assert_eq!(
+            paragraph.children[0].node.as_deref(),
+            Some(&[0, 1, 0, 0][..])
+        );
+        assert_eq!(
+            paragraph.children[1].kind,
+            BoxKind::Replaced(Image {
+                src: Some("a.png".to_string()),
+                alt: "Alt".to_string(),
+                width: Some(20.0),
+                height: Some(10.0),
+                content: ImageContent::Pending,
+            })
+        );
+    }
+}
@@ -0,0 +1,828 @@
+// Flex layout: breaking flex items into lines, flexing their sizes along the main axis,
+// then aligning them along both axes
+
+use crate::css::{ComputedStyle, Value};
+use crate::layout::block::{self, BlockLayout, ContainingBlock};
+use crate::layout::box_tree::LayoutBox;
+use crate::layout::fragment::Fragment;
+use crate::layout::LayoutContext;
+use eframe::egui;
+
+/// The items of a flex container positioned relative to its content box
+pub(super) struct FlexLayout {
+    pub children: Vec<Fragment>,
+    pub height: f32,
+    pub baseline: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Align {
+    Start,
+    End,
+    Center,
+    Baseline,
+    Stretch,
+}
+
+struct FlexItem<'a> {
+    layout_box: &'a LayoutBox,
+    // Margins at the start and end of each axis, None for auto
+    main_margins: [Option<f32>; 2],
+    cross_margins: [Option<f32>; 2],
+    // Border and padding along each axis
+    main_extra: f32,
+    cross_extra: f32,
+    grow: f32,
+    shrink: f32,
+    // The flex base size and the limits on the main size, all content sizes
+    base: f32,
+    min: f32,
+    max: f32,
+    // The main size, the hypothetical size until the line is flexed
+    main: f32,
+    frozen: bool,
+    // The content width of items in a column
+    cross: f32,
+    align: Align,
+    layout: Option<BlockLayout>,
+}
+
+impl FlexItem<'_> {
+    fn hypothetical(&self) -> f32 {
+        self.base.min(self.max).max(self.min)
+    }
+
+    // Margin, border and padding along the main axis, with auto margins as zero
+    fn main_outer(&self) -> f32 {
+        self.main_extra + self.main_margins.iter().flatten().sum::<f32>()
+    }
+
+    fn cross_margin_sum(&self) -> f32 {
+        self.cross_margins.iter().flatten().sum()
+    }
+
+    fn fragment(&self) -> &Fragment {
+        &self
+            .layout
+            .as_ref()
+            .expect("Flex item not laid out")
+            .fragment
+    }
+
+    // The border box size along the cross axis
+    fn cross_size(&self, row: bool) -> f32 {
+        let rect = self.fragment().rect;
+        match row {
+            true => rect.height(),
+            false => rect.width(),
+        }
+    }
+
+    // Distance from the cross start margin edge to the baseline
+    fn ascent(&self) -> f32 {
+        let layout = self.layout.as_ref().expect("Flex item not laid out");
+        self.cross_margins[0].unwrap_or(0.0)
+            + layout.baseline.unwrap_or(layout.fragment.rect.height())
+    }
+}
+
+struct FlexLine {
+    items: Vec<usize>,
+    cross: f32,
+    // Position of the line along the cross axis
+    offset: f32,
+    // The largest ascent of the line's baseline-aligned items
+    ascent: f32,
+}
+
+// Lays out the children of a flex container whose content width is known
+pub(super) fn layout_flex_content(
+    layout_box: &LayoutBox,
+    containing: ContainingBlock,
+    ctx: &LayoutContext,
+) -> FlexLayout {
+    let style = &layout_box.style;
+    let direction = style.keyword("flex-direction").unwrap_or("row");
+    let row = direction.starts_with("row");
+    let reverse = direction.ends_with("-reverse");
+    let wrap = style.keyword("flex-wrap").unwrap_or("nowrap");
+    let wraps = wrap != "nowrap";
+    let column_gap = gap(style, "column-gap", containing.width);
+    let row_gap = gap(style, "row-gap", containing.height.unwrap_or(0.0));
+    let (main_gap, cross_gap) = match row {
+        true => (column_gap, row_gap),
+        false => (row_gap, column_gap),
+    };
+    let (main_size, cross_size) = match row {
+        true => (Some(containing.width), containing.height),
+        false => (containing.height, Some(containing.width)),
+    };
+
+    let mut children: Vec<&LayoutBox> = layout_box
+        .children
+        .iter()
+        .filter(|child| !child.is_absolutely_positioned())
+        .collect();
+    children
+        .sort_by(|a, b| number(&a.style, "order", 0.0).total_cmp(&number(&b.style, "order", 0.0)));
+    let mut items: Vec<FlexItem> = children
+        .into_iter()
+        .map(|child| flex_item(child, style, row, wraps, containing, ctx))
+        .collect();
+
+    // Collect the items into lines, breaking before items that would overflow
+    let mut lines: Vec<FlexLine> = Vec::new();
+    let mut line_size = 0.0;
+    for (index, item) in items.iter().enumerate() {
+        let outer = item.hypothetical() + item.main_outer();
+        let breaks = match (lines.last(), main_size) {
+            (Some(line), Some(available)) => {
+                wraps && !line.items.is_empty() && line_size + main_gap + outer > available + 0.01
+            }
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        if breaks {
+            lines.push(FlexLine {
+                items: Vec::new(),
+                cross: 0.0,
+                offset: 0.0,
+                ascent: 0.0,
+            });
+            line_size = outer;
+        } else {
+            line_size += main_gap + outer;
+        }
+        lines.last_mut().unwrap().items.push(index);
+    }
+
+    // Flex each line to fill the container, then lay the items out at their sizes
+    for line in &lines {
+        let gaps = main_gap * line.items.len().saturating_sub(1) as f32;
+        let available = main_size.unwrap_or(used_main_size(&items, line, main_gap, true));
+        resolve_flexible_lengths(&mut items, &line.items, available, gaps);
+    }
+    for item in &mut items {
+        let layout = match row {
+            true => block::layout_sized_box(item.layout_box, item.main, None, containing, ctx),
+            false => block::layout_sized_box(
+                item.layout_box,
+                item.cross,
+                Some(item.main),
+                containing,
+                ctx,
+            ),
+        };
+        item.layout = Some(layout);
+    }
+
+    // Size the lines along the cross axis
+    for line in &mut lines {
+        let mut cross: f32 = 0.0;
+        let mut descent: f32 = 0.0;
+        for &index in &line.items {
+            let item = &items[index];
+            let outer = item.cross_size(row) + item.cross_margin_sum();
+            if row && item.align == Align::Baseline && !has_auto(item.cross_margins) {
+                line.ascent = line.ascent.max(item.ascent());
+                descent = descent.max(outer - item.ascent());
+            } else {
+                cross = cross.max(outer);
+            }
+        }
+        line.cross = cross.max(line.ascent + descent);
+    }
+    if let (false, Some(cross_size), Some(line)) = (wraps, cross_size, lines.first_mut()) {
+        line.cross = cross_size;
+    }
+
+    // Distribute extra cross space between the lines
+    let line_gaps = cross_gap * lines.len().saturating_sub(1) as f32;
+    let lines_size = lines.iter().map(|line| line.cross).sum::<f32>() + line_gaps;
+    let mut initial = 0.0;
+    let mut between = 0.0;
+    if let (true, Some(cross_size)) = (wraps, cross_size) {
+        let free = cross_size - lines_size;
+        match style.keyword("align-content").unwrap_or("normal") {
+            "normal" | "stretch" => {
+                if free > 0.0 {
+                    let count = lines.len() as f32;
+                    for line in &mut lines {
+                        line.cross += free / count;
+                    }
+                }
+            }
+            keyword => {
+                (initial, between) = distribute(content_position(keyword, false), free, lines.len())
+            }
+        }
+    }
+    let mut offset = initial;
+    for line in &mut lines {
+        line.offset = offset;
+        offset += line.cross + cross_gap + between;
+    }
+    let cross_extent = cross_size.unwrap_or(lines_size);
+
+    // Stretch items with an auto cross size to fill their line
+    for line in &lines {
+        for &index in &line.items {
+            let item = &mut items[index];
+            if item.align != Align::Stretch || has_auto(item.cross_margins) {
+                continue;
+            }
+            let size = line.cross - item.cross_margin_sum() - item.cross_extra;
+            let item_style = &item.layout_box.style;
+            let layout = match row {
+                true => {
+                    if block::resolve_height(
+                        item_style,
+                        "height",
+                        containing.height,
+                        item.cross_extra,
+                    )
+                    .is_some()
+                    {
+                        continue;
+                    }
+                    let height = clamp_cross(item_style, size, row, item.cross_extra, containing);
+                    block::layout_sized_box(
+                        item.layout_box,
+                        item.main,
+                        Some(height),
+                        containing,
+                        ctx,
+                    )
+                }
+                false => {
+                    if item_style.length("width", containing.width).is_some() {
+                        continue;
+                    }
+                    let width = clamp_cross(item_style, size, row, item.cross_extra, containing);
+                    if width == item.cross {
+                        continue;
+                    }
+                    item.cross = width;
+                    block::layout_sized_box(
+                        item.layout_box,
+                        width,
+                        Some(item.main),
+                        containing,
+                        ctx,
+                    )
+                }
+            };
+            item.layout = Some(layout);
+        }
+    }
+
+    // Position the items along both axes
+    let main_extent = main_size.unwrap_or_else(|| {
+        lines
+            .iter()
+            .map(|line| used_main_size(&items, line, main_gap, false))
+            .fold(0.0, f32::max)
+    });
+    let justify = style.keyword("justify-content").unwrap_or("normal");
+    let mut positions = vec![egui::Vec2::ZERO; items.len()];
+    for line in &lines {
+        let mut free = main_extent - used_main_size(&items, line, main_gap, false);
+        let auto_margins = line
+            .items
+            .iter()
+            .flat_map(|&index| items[index].main_margins)
+            .filter(Option::is_none)
+            .count();
+        let auto_margin = match free > 0.0 && auto_margins > 0 {
+            true => std::mem::take(&mut free) / auto_margins as f32,
+            false => 0.0,
+        };
+        let (initial, between) =
+            distribute(content_position(justify, reverse), free, line.items.len());
+        let mut main = initial;
+        for &index in &line.items {
+            let item = &items[index];
+            let [start, end] = item
+                .main_margins
+                .map(|margin| margin.unwrap_or(auto_margin));
+            let size = item.main + item.main_extra;
+            main += start;
+            let main_position = match reverse {
+                true => main_extent - main - size,
+                false => main,
+            };
+            main += size + end + main_gap + between;
+
+            let size = item.cross_size(row);
+            let cross = line.offset
+                + match item.cross_margins {
+                    [None, None] => ((line.cross - size) / 2.0).max(0.0),
+                    [None, Some(end)] => line.cross - size - end,
+                    [Some(start), None] => start,
+                    [Some(start), Some(end)] => match item.align {
+                        Align::Start | Align::Stretch => start,
+                        Align::End => line.cross - size - end,
+                        Align::Center => start + (line.cross - size - start - end) / 2.0,
+                        Align::Baseline if row => line.ascent - item.ascent() + start,
+                        Align::Baseline => start,
+                    },
+                };
+            let cross_position = match wrap {
+                "wrap-reverse" => cross_extent - cross - size,
+                _ => cross,
+            };
+            positions[index] = match row {
+                true => egui::vec2(main_position, cross_position),
+                false => egui::vec2(cross_position, main_position),
+            };
+        }
+    }
+
+    let mut baseline = None;
+    let mut fragments = Vec::new();
+    for (item, position) in items.into_iter().zip(positions) {
+        let layout = item.layout.expect("Flex item not laid out");
+        if baseline.is_none() {
+            baseline = layout.baseline.map(|baseline| position.y + baseline);
+        }
+        let mut fragment = layout.fragment;
+        fragment.rect = fragment.rect.translate(position);
+        fragments.push(fragment);
+    }
+    FlexLayout {
+        children: fragments,
+        height: match row {
+            true => cross_extent,
+            false => main_extent,
+        },
+        baseline,
+    }
+}
+
+// The outer size of a line's items along the main axis, with gaps, either before or after
+// they're flexed
+fn used_main_size(items: &[FlexItem], line: &FlexLine, gap: f32, hypothetical: bool) -> f32 {
+    line.items
+        .iter()
+        .map(|&index| {
+            let item = &items[index];
+            let size = match hypothetical {
+                true => item.hypothetical(),
+                false => item.main,
+            };
+            size + item.main_outer()
+        })
+        .sum::<f32>()
+        + gap * line.items.len().saturating_sub(1) as f32
+}
+
+fn flex_item<'a>(
+    layout_box: &'a LayoutBox,
+    container: &ComputedStyle,
+    row: bool,
+    wraps: bool,
+    containing: ContainingBlock,
+    ctx: &LayoutContext,
+) -> FlexItem<'a> {
+    let style = &layout_box.style;
+    let [top, right, bottom, left] = block::margins(style, containing.width);
+    let border = block::border(style);
+    let padding = block::padding(style, containing.width);
+    let horizontal = border.horizontal() + padding.horizontal();
+    let vertical = border.vertical() + padding.vertical();
+    let (main_margins, cross_margins, main_extra, cross_extra) = match row {
+        true => ([left, right], [top, bottom], horizontal, vertical),
+        false => ([top, bottom], [left, right], vertical, horizontal),
+    };
+    let align = match style.get("align-self") {
+        Some(Value::Keyword(keyword)) if keyword == "auto" => align(container.get("align-items")),
+        value => align(value),
+    };
+
+    // Items in a column are laid out at their width first to find their heights
+    let cross = match row {
+        true => 0.0,
+        false => {
+            let margins = cross_margins.iter().flatten().sum::<f32>();
+            let available = (containing.width - margins - horizontal).max(0.0);
+            let width = match block::content_size(style, "width", containing.width, horizontal) {
+                Some(width) => width,
+                None if !wraps && align == Align::Stretch && !has_auto(cross_margins) => available,
+                None => {
+                    let (min, max) = block::intrinsic_content_widths(layout_box, ctx);
+                    max.min(available.max(min))
+                }
+            };
+            block::clamp_width(style, width, containing.width, horizontal)
+        }
+    };
+    let content_height = || {
+        block::layout_sized_box(layout_box, cross, None, containing, ctx)
+            .fragment
+            .rect
+            .height()
+            - vertical
+    };
+
+    // The flex base size comes from flex-basis, then the main size property, then the content
+    let main_property = match row {
+        true => block::content_size(style, "width", containing.width, horizontal),
+        false => block::resolve_height(style, "height", containing.height, vertical),
+    };
+    let basis = match style.get("flex-basis") {
+        Some(Value::Keyword(keyword)) if keyword == "auto" => main_property,
+        Some(Value::Keyword(_)) | None => None,
+        Some(_) => match row {
+            true => block::content_size(style, "flex-basis", containing.width, horizontal),
+            false => block::resolve_height(style, "flex-basis", containing.height, vertical),
+        },
+    };
+    let base = basis.unwrap_or_else(|| match row {
+        true => block::intrinsic_content_widths(layout_box, ctx).1,
+        false => content_height(),
+    });
+
+    let (min, max) = match row {
+        true => {
+            let min = block::content_size(style, "min-width", containing.width, horizontal);
+            let max = block::content_size(style, "max-width", containing.width, horizontal);
+            // By default items don't shrink below their content, unless they scroll
+            let min = min.unwrap_or_else(|| match scrolls(style) {
+                true => 0.0,
+                false => {
+                    let min_content = block::intrinsic_content_widths(layout_box, ctx).0;
+                    main_property.map_or(min_content, |size| size.min(min_content))
+                }
+            });
+            (min, max.unwrap_or(f32::INFINITY))
+        }
+        false => (
+            block::resolve_height(style, "min-height", containing.height, vertical).unwrap_or(0.0),
+            block::resolve_height(style, "max-height", containing.height, vertical)
+                .unwrap_or(f32::INFINITY),
+        ),
+    };
+    FlexItem {
+        layout_box,
+        main_margins,
+        cross_margins,
+        main_extra,
+        cross_extra,
+        grow: number(style, "flex-grow", 0.0),
+        shrink: number(style, "flex-shrink", 1.0),
+        base,
+        min,
+        max: max.max(min),
+        main: 0.0,
+        frozen: false,
+        cross,
+        align,
+        layout: None,
+    }
+}
+
+// Sets the main sizes of the items on a line so they fill the available space, following
+// the spec's loop of distributing the free space and freezing items that hit their limits
+fn resolve_flexible_lengths(items: &mut [FlexItem], line: &[usize], available: f32, gaps: f32) {
+    let hypothetical: f32 = line
+        .iter()
+        .map(|&index| items[index].hypothetical() + items[index].main_outer())
+        .sum();
+    let growing = hypothetical + gaps < available;
+    let factor = |item: &FlexItem| match growing {
+        true => item.grow,
+        false => item.shrink,
+    };
+    for &index in line {
+        let item = &mut items[index];
+        item.main = item.hypothetical();
+        item.frozen = factor(item) == 0.0
+            || (growing && item.base > item.main)
+            || (!growing && item.base < item.main);
+    }
+    let free_space = |items: &[FlexItem]| {
+        available
+            - gaps
+            - line
+                .iter()
+                .map(|&index| {
+                    let item = &items[index];
+                    let size = match item.frozen {
+                        true => item.main,
+                        false => item.base,
+                    };
+                    size + item.main_outer()
+                })
+                .sum::<f32>()
+    };
+    let initial_free = free_space(items);
+    loop {
+        let unfrozen: Vec<usize> = line
+            .iter()
+            .copied()
+            .filter(|&index| !items[index].frozen)
+            .collect();
+        if unfrozen.is_empty() {
+            break;
+        }
+        let mut free = free_space(items);
+        let factors: f32 = unfrozen.iter().map(|&index| factor(&items[index])).sum();
+        if factors < 1.0 && (initial_free * factors).abs() < free.abs() {
+            free = initial_free * factors;
+        }
+        let scaled_shrink: f32 = unfrozen
+            .iter()
+            .map(|&index| items[index].shrink * items[index].base)
+            .sum();
+        let mut violation = 0.0;
+        let mut adjustments = Vec::new();
+        for &index in &unfrozen {
+            let item = &mut items[index];
+            let target = match (growing, scaled_shrink > 0.0) {
+                (true, _) => item.base + free * item.grow / factors,
+                (false, true) => item.base + free * item.shrink * item.base / scaled_shrink,
+                (false, false) => item.base,
+            };
+            let clamped = target.min(item.max).max(item.min);
+            violation += clamped - target;
+            adjustments.push((index, clamped - target));
+            item.main = clamped;
+        }
+        // Freeze everything if the limits cancel out, otherwise only the items that were
+        // clamped the same way as the total
+        for (index, adjustment) in adjustments {
+            items[index].frozen |= violation.abs() < 0.01
+                || (violation > 0.0 && adjustment > 0.0)
+                || (violation < 0.0 && adjustment < 0.0);
+        }
+    }
+}
+
+// The space before the first item and between items for a content distribution keyword
+// such as `justify-content`, falling back to the start or center when there's no space
+pub(super) fn distribute(keyword: &str, free: f32, count: usize) -> (f32, f32) {
+    let count = count as f32;
+    match keyword {
+        "end" => (free, 0.0),
+        "center" => (free / 2.0, 0.0),
+        "space-between" if free > 0.0 && count > 1.0 => (0.0, free / (count - 1.0)),
+        "space-around" if free > 0.0 => (free / count / 2.0, free / count),
+        "space-evenly" if free > 0.0 => (free / (count + 1.0), free / (count + 1.0)),
+        "space-around" | "space-evenly" => (free / 2.0, 0.0),
+        _ => (0.0, 0.0),
+    }
+}
+
+// Maps a content distribution keyword to the flow of the items. Keywords for the
+// writing mode's start and end are swapped when the items run in reverse, as positions
+// are mirrored afterwards.
+pub(super) fn content_position(keyword: &str, reverse: bool) -> &str {
+    match (keyword, reverse) {
+        ("flex-start" | "normal" | "stretch", _) => "start",
+        ("flex-end", _) => "end",
+        ("start" | "left", true) => "end",
+        ("end" | "right", true) => "start",
+        ("left", false) => "start",
+        ("right", false) => "end",
+        (keyword, _) => keyword,
+    }
+}
+
+pub(super) fn align(value: Option<&Value>) -> Align {
+    let keywords: Vec<&str> = value
+        .map(|value| value.items().iter().filter_map(Value::as_keyword).collect())
+        .unwrap_or_default();
+    match keywords.last().copied() {
+        Some("flex-start" | "start" | "self-start") => Align::Start,
+        Some("flex-end" | "end" | "self-end") => Align::End,
+        Some("center") => Align::Center,
+        // Items are aligned by the baseline of their last line
+        Some("baseline") => Align::Baseline,
+        _ => Align::Stretch,
+    }
+}
+
+fn clamp_cross(
+    style: &ComputedStyle,
+    size: f32,
+    row: bool,
+    extra: f32,
+    containing: ContainingBlock,
+) -> f32 {
+    let size = size.max(0.0);
+    match row {
+        true => {
+            let min = block::resolve_height(style, "min-height", containing.height, extra);
+            let max = block::resolve_height(style, "max-height", containing.height, extra);
+            size.min(max.unwrap_or(f32::INFINITY))
+                .max(min.unwrap_or(0.0))
+        }
+        false => block::clamp_width(style, size, containing.width, extra),
+    }
+}
+
+pub(super) fn has_auto(margins: [Option<f32>; 2]) -> bool {
+    margins.iter().any(Option::is_none)
+}
+
+fn scrolls(style: &ComputedStyle) -> bool {
+    ["overflow-x", "overflow-y"]
+        .iter()
+        .any(|name| !matches!(style.keyword(name), None | Some("visible" | "clip")))
+}
+
+pub(super) fn number(style: &ComputedStyle, name: &str, default: f32) -> f32 {
+    style
+        .get(name)
+        .and_then(Value::as_number)
+        .unwrap_or(default)
+}
+
+// `normal` gaps are zero for flex and grid containers
+pub(super) fn gap(style: &ComputedStyle, name: &str, basis: f32) -> f32 {
+    style.length(name, basis).unwrap_or(0.0).max(0.0)
+}
+
+// The min-content and max-content widths of a flex container's content
+pub(super) fn intrinsic_widths(layout_box: &LayoutBox, ctx: &LayoutContext) -> (f32, f32) {
+    let style = &layout_box.style;
+    let row = style
+        .keyword("flex-direction")
+        .is_none_or(|direction| direction.starts_with("row"));
+    let wraps = style
+        .keyword("flex-wrap")
+        .is_some_and(|wrap| wrap != "nowrap");
+    let widths: Vec<(f32, f32)> = layout_box
+        .children
+        .iter()
+        .filter(|child| !child.is_absolutely_positioned())
+        .map(|child| block::intrinsic_outer_widths(child, ctx))
+        .collect();
+    let largest = |widths: &mut dyn Iterator<Item = f32>| widths.fold(0.0, f32::max);
+    if !row {
+        return (
+            largest(&mut widths.iter().map(|(min, _)| *min)),
+            largest(&mut widths.iter().map(|(_, max)| *max)),
+        );
+    }
+    let gaps = gap(style, "column-gap", 0.0) * widths.len().saturating_sub(1) as f32;
+    let max = widths.iter().map(|(_, max)| max).sum::<f32>() + gaps;
+    let min = match wraps {
+        true => largest(&mut widths.iter().map(|(min, _)| *min)),
+        false => widths.iter().map(|(min, _)| min).sum::<f32>() + gaps,
+    };
+    (min, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::layout::testing::{layout, rect, Rect};
+
+    // The border boxes of the first element's children
+    fn item_rects(body: &str) -> Vec<Rect> {
+        layout(body).children[0].children[0]
+            .children
+            .iter()
+            .map(rect)
+            .collect()
+    }
+
+    #[test]
+    fn test_flex_fixtures() {
+        // Each fixture is a flex container in a 400px wide body and the expected rects of
+        // its items. Characters are 8px wide and lines 20px tall.
+        let fixtures: &[(&str, &[Rect])] = &[
+            // Free space is shared by flex-grow, from a zero basis with the `flex` shorthand
+            (
+                "<div style='display: flex'><div style='flex: 1'>a</div>\
+                 <div style='flex: 2'>b</div><div style='width: 100px'>c</div></div>",
+                &[
+                    (0.0, 0.0, 100.0, 20.0),
+                    (100.0, 0.0, 200.0, 20.0),
+                    (300.0, 0.0, 100.0, 20.0),
+                ],
+            ),
+            // Overflow is taken in proportion to flex-shrink times the base size
+            (
+                "<div style='display: flex; width: 300px'><div style='width: 200px'></div>\
+                 <div style='width: 200px; flex-shrink: 3'></div></div>",
+                &[(0.0, 0.0, 175.0, 0.0), (175.0, 0.0, 125.0, 0.0)],
+            ),
+            // Items clamped by max-width are frozen and the rest flex again
+            (
+                "<div style='display: flex'><div style='flex: 1; max-width: 50px'></div>\
+                 <div style='flex: 1'></div></div>",
+                &[(0.0, 0.0, 50.0, 0.0), (50.0, 0.0, 350.0, 0.0)],
+            ),
+            // Content sized items
+            (
+                "<div style='display: flex'><span>abc</span><span>de</span></div>",
+                &[(0.0, 0.0, 24.0, 20.0), (24.0, 0.0, 16.0, 20.0)],
+            ),
+            // Wrapping with row and column gaps
+            (
+                "<div style='display: flex; flex-wrap: wrap; gap: 10px 20px; width: 250px'>\
+                 <div style='width: 100px; height: 10px'></div>\
+                 <div style='width: 100px; height: 10px'></div>\
+                 <div style='width: 100px; height: 10px'></div></div>",
+                &[
+                    (0.0, 0.0, 100.0, 10.0),
+                    (120.0, 0.0, 100.0, 10.0),
+                    (0.0, 20.0, 100.0, 10.0),
+                ],
+            ),
+            // justify-content, align-items and align-self
+            (
+                "<div style='display: flex; justify-content: space-between; \
+                 align-items: center; height: 100px'>\
+                 <div style='width: 50px; height: 20px'></div>\
+                 <div style='width: 50px; height: 40px'></div>\
+                 <div style='width: 50px; height: 10px; align-self: flex-end'></div></div>",
+                &[
+                    (0.0, 40.0, 50.0, 20.0),
+                    (175.0, 30.0, 50.0, 40.0),
+                    (350.0, 90.0, 50.0, 10.0),
+                ],
+            ),
+            // Items without a height stretch to the line
+            (
+                "<div style='display: flex; height: 50px'><div style='width: 10px'></div>\
+                 <div style='width: 10px; height: 20px'></div></div>",
+                &[(0.0, 0.0, 10.0, 50.0), (10.0, 0.0, 10.0, 20.0)],
+            ),
+            // Auto margins take the free space before alignment
+            (
+                "<div style='display: flex; height: 50px; justify-content: flex-end'>\
+                 <div style='margin: auto; width: 100px; height: 10px'></div></div>",
+                &[(150.0, 20.0, 100.0, 10.0)],
+            ),
+            // Baselines line up
+            (
+                "<div style='display: flex; align-items: baseline'>\
+                 <div style='padding-top: 10px'>a</div><div style='font-size: 32px'>b</div></div>",
+                &[(0.0, 2.0, 8.0, 30.0), (8.0, 0.0, 16.0, 40.0)],
+            ),
+            // Reversed columns start at the bottom and stretch across
+            (
+                "<div style='display: flex; flex-direction: column-reverse; height: 100px'>\
+                 <div style='height: 20px'></div><div style='height: 30px'></div></div>",
+                &[(0.0, 80.0, 400.0, 20.0), (0.0, 50.0, 400.0, 30.0)],
+            ),
+            // Columns grow into a definite height
+            (
+                "<div style='display: flex; flex-direction: column; height: 100px; \
+                 align-items: flex-start'><div style='flex-grow: 1'>ab</div><div>c</div></div>",
+                &[(0.0, 0.0, 16.0, 80.0), (0.0, 80.0, 8.0, 20.0)],
+            ),
+            // `order` moves items, and reversed rows start at the right
+            (
+                "<div style='display: flex; flex-direction: row-reverse'>\
+                 <div style='width: 10px; order: 2'></div><div style='width: 20px'></div></div>",
+                &[(380.0, 0.0, 20.0, 0.0), (370.0, 0.0, 10.0, 0.0)],
+            ),
+            // Lines share the free cross space, or are aligned by align-content
+            (
+                "<div style='display: flex; flex-wrap: wrap; width: 100px; height: 100px'>\
+                 <div style='width: 60px; height: 10px'></div>\
+                 <div style='width: 60px; height: 20px'></div></div>",
+                &[(0.0, 0.0, 60.0, 10.0), (0.0, 45.0, 60.0, 20.0)],
+            ),
+            (
+                "<div style='display: flex; flex-wrap: wrap; width: 100px; height: 100px; \
+                 align-content: center'><div style='width: 60px; height: 10px'></div>\
+                 <div style='width: 60px; height: 20px'></div></div>",
+                &[(0.0, 35.0, 60.0, 10.0), (0.0, 45.0, 60.0, 20.0)],
+            ),
+            // wrap-reverse stacks lines from the bottom
+            (
+                "<div style='display: flex; flex-wrap: wrap-reverse; width: 100px'>\
+                 <div style='width: 60px; height: 10px'></div>\
+                 <div style='width: 60px; height: 20px'></div></div>",
+                &[(0.0, 20.0, 60.0, 10.0), (0.0, 0.0, 60.0, 20.0)],
+            ),
+        ];
+        for (index, (body, expected)) in fixtures.iter().enumerate() {
+            assert_eq!(item_rects(body), *expected, "fixture {}: {}", index, body);
+        }
+    }
+
+    #[test]
+    fn test_flex_container_sizes() {
+        // An inline flex container shrinks to fit its items
+        let root = layout(
+            "<span style='display: inline-flex; column-gap: 5px'>\
+             <div style='width: 30px'></div><div style='width: 40px'></div></span>",
+        );
+        let line = &root.children[0].children[0];
+        assert_eq!(line.children[0].rect.width(), 75.0);
+        // A row's height is its tallest line, a column's the sum of its items
+        let root = layout(
+            "<div style='display: flex'><div style='height: 30px'></div><div>a</div></div>\
+             <div style='display: flex; flex-direction: column'><div>a</div><div>b</div></div>",
+        );
+        let body = &root.children[0];
+        assert_eq!(body.children[0].rect.height(), 30.0);
+        assert_eq!(body.children[1].rect.height(), 40.0);
+        assert_eq!(body.children[1].rect.min.y, 30.0);
+    }
+}
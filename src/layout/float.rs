@@ -0,0 +1,277 @@
+// Floats: boxes shifted to the left or right of their containing block, with the lines
+// and formatting context roots after them flowing around them
+
+use crate::css::ComputedStyle;
+use crate::layout::block::{self, BlockLayout, ContainingBlock};
+use crate::layout::box_tree::LayoutBox;
+use crate::layout::fragment::Fragment;
+use crate::layout::LayoutContext;
+use eframe::egui::{self, Pos2, Rect, Vec2};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    pub fn of(style: &ComputedStyle) -> Option<Side> {
+        match style.keyword("float") {
+            Some("left") => Some(Side::Left),
+            Some("right") => Some(Side::Right),
+            _ => None,
+        }
+    }
+}
+
+/// The floats placed so far in a block formatting context, as margin boxes in the
+/// coordinates of its root's content box
+#[derive(Debug, Default)]
+pub(super) struct FloatContext {
+    floats: Vec<(Side, Rect)>,
+}
+
+/// The floats of a formatting context as seen from the content box of a block inside it
+pub(super) struct FloatArea<'f> {
+    context: &'f mut FloatContext,
+    origin: Vec2,
+    width: f32,
+}
+
+impl<'f> FloatArea<'f> {
+    pub fn new(context: &'f mut FloatContext, width: f32) -> Self {
+        Self {
+            context,
+            origin: Vec2::ZERO,
+            width,
+        }
+    }
+
+    // The area of a box at an offset from this one
+    pub fn nested(&mut self, offset: Vec2, width: f32) -> FloatArea<'_> {
+        FloatArea {
+            context: self.context,
+            origin: self.origin + offset,
+            width,
+        }
+    }
+
+    // Floats overlapping a horizontal band, relative to the area
+    fn overlapping(&self, top: f32, height: f32) -> impl Iterator<Item = (Side, Rect)> + '_ {
+        let origin = self.origin;
+        self.context.floats.iter().filter_map(move |(side, rect)| {
+            let rect = rect.translate(-origin);
+            let overlaps = rect.max.y > top && rect.min.y < top + height.max(0.01);
+            overlaps.then_some((*side, rect))
+        })
+    }
+
+    // The left and right edges of the space the floats leave in a band
+    pub fn band(&self, top: f32, height: f32) -> (f32, f32) {
+        self.overlapping(top, height)
+            .fold(
+                (0.0, self.width),
+                |(left, right), (side, rect)| match side {
+                    Side::Left => (left.max(rect.max.x), right),
+                    Side::Right => (left, right.min(rect.min.x)),
+                },
+            )
+    }
+
+    // Where the first float beside a band ends, the next place the band may widen
+    pub fn next_bottom(&self, top: f32, height: f32) -> Option<f32> {
+        self.overlapping(top, height)
+            .map(|(_, rect)| rect.max.y)
+            .reduce(f32::min)
+    }
+
+    // The bottom of the floats a `clear` value moves a box below
+    pub fn clearance(&self, style: &ComputedStyle) -> Option<f32> {
+        let sides: &[Side] = match style.keyword("clear") {
+            Some("left") => &[Side::Left],
+            Some("right") => &[Side::Right],
+            Some("both") => &[Side::Left, Side::Right],
+            _ => return None,
+        };
+        self.context
+            .floats
+            .iter()
+            .filter(|(side, _)| sides.contains(side))
+            .map(|(_, rect)| rect.max.y - self.origin.y)
+            .reduce(f32::max)
+    }
+
+    // The bottom of the lowest float, which a formatting context root grows to contain
+    pub fn bottom(&self) -> f32 {
+        self.context
+            .floats
+            .iter()
+            .map(|(_, rect)| rect.max.y - self.origin.y)
+            .fold(0.0, f32::max)
+    }
+
+    // Places a float's margin box as high as it fits at or below `top`, but no higher than
+    // an earlier float, and as far to its side as it can go. Returns its position.
+    fn place(&mut self, side: Side, size: Vec2, top: f32) -> Pos2 {
+        let mut top = self
+            .context
+            .floats
+            .iter()
+            .map(|(_, rect)| rect.min.y - self.origin.y)
+            .fold(top, f32::max);
+        let (left, right) = loop {
+            let (left, right) = self.band(top, size.y);
+            if right - left >= size.x - 0.01 {
+                break (left, right);
+            }
+            match self.next_bottom(top, size.y) {
+                Some(bottom) => top = bottom,
+                None => break (left, right),
+            }
+        };
+        let x = match side {
+            Side::Left => left,
+            Side::Right => right - size.x,
+        };
+        let position = Pos2::new(x, top);
+        let rect = Rect::from_min_size(position + self.origin, size);
+        self.context.floats.push((side, rect));
+        position
+    }
+}
+
+// Lays out a float at its shrink-to-fit width and places it in the area at or below `top`
+pub(super) fn layout_float(
+    layout_box: &LayoutBox,
+    containing: ContainingBlock,
+    area: &mut FloatArea,
+    top: f32,
+    ctx: &LayoutContext,
+) -> Fragment {
+    let layout = block::layout_atomic_inline(layout_box, containing, ctx);
+    place_float(layout_box, layout, area, top)
+}
+
+// Places a float that has been laid out, returning its fragment relative to the area
+pub(super) fn place_float(
+    layout_box: &LayoutBox,
+    layout: BlockLayout,
+    area: &mut FloatArea,
+    top: f32,
+) -> Fragment {
+    let style = &layout_box.style;
+    let margin = layout.margin;
+    let size = layout.fragment.rect.size() + egui::vec2(margin.horizontal(), margin.vertical());
+    let top = area
+        .clearance(style)
+        .map_or(top, |clearance| top.max(clearance));
+    let position = area.place(Side::of(style).unwrap_or(Side::Left), size, top);
+    let mut fragment = layout.fragment;
+    fragment.rect = fragment
+        .rect
+        .translate(position.to_vec2() + egui::vec2(margin.left, margin.top));
+    fragment
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::layout::testing::{layout, rect, Rect};
+    use crate::layout::{Fragment, FragmentKind};
+
+    // The fragments matching a predicate, in tree order
+    fn find<'f>(
+        fragment: &'f Fragment,
+        predicate: &dyn Fn(&Fragment) -> bool,
+    ) -> Vec<&'f Fragment> {
+        let mut found = Vec::new();
+        if predicate(fragment) {
+            found.push(fragment);
+        }
+        for child in &fragment.children {
+            found.extend(find(child, predicate));
+        }
+        found
+    }
+
+    fn lines(fragment: &Fragment) -> Vec<Rect> {
+        find(fragment, &|f| f.kind == FragmentKind::Line)
+            .into_iter()
+            .map(rect)
+            .collect()
+    }
+
+    #[test]
+    fn test_floats_and_line_boxes() {
+        // Lines beside a left float start after it, and return to full width below it
+        let body = layout(
+            "<div style='float: left; width: 100px; height: 30px'></div>\
+             <p style='margin: 0'>aaaaaaaaaaaaaaaaaaaaaaaaaaaaaa aaaaaaaaaaaaaaaaaaaaaaaaaaaaaa \
+             aaaaaaaaaaaaaaaaaaaaaaaaaaaaaa</p>",
+        );
+        // Floats come after the in-flow blocks so they paint above their backgrounds
+        let float = body.children[0].children.last().unwrap();
+        assert_eq!(rect(float), (0.0, 0.0, 100.0, 30.0));
+        assert_eq!(
+            lines(&body),
+            vec![
+                (100.0, 0.0, 300.0, 20.0),
+                (100.0, 20.0, 300.0, 20.0),
+                (0.0, 40.0, 400.0, 20.0),
+            ]
+        );
+
+        // Right floats stack leftwards, and a float in a line goes beside the text
+        let body = layout(
+            "<div style='float: right; width: 50px; height: 10px'></div>\
+             <div style='float: right; width: 60px; height: 10px'></div>\
+             <p style='margin: 0'>aa<span style='float: left; width: 20px'>b</span>cc</p>",
+        );
+        let floats: Vec<Rect> = find(&body, &|f| {
+            f.kind == FragmentKind::Box && f.style.keyword("float") != Some("none")
+        })
+        .into_iter()
+        .map(rect)
+        .collect();
+        assert_eq!(
+            floats,
+            vec![
+                (0.0, 0.0, 20.0, 20.0),
+                (350.0, 0.0, 50.0, 10.0),
+                (290.0, 0.0, 60.0, 10.0),
+            ]
+        );
+        assert_eq!(lines(&body)[0].0, 20.0);
+    }
+
+    #[test]
+    fn test_clearance_and_formatting_context_roots() {
+        // A cleared block moves below the float
+        let body = layout(
+            "<div style='float: left; width: 100px; height: 50px'></div>\
+             <div style='clear: left; height: 10px'></div>",
+        );
+        assert_eq!(
+            rect(&body.children[0].children[0]),
+            (0.0, 50.0, 400.0, 10.0)
+        );
+
+        // A formatting context root sits beside the float rather than under it
+        let body = layout(
+            "<div style='float: left; width: 100px; height: 50px'></div>\
+             <div style='overflow: hidden; height: 10px'></div>",
+        );
+        assert_eq!(
+            rect(&body.children[0].children[0]),
+            (100.0, 0.0, 300.0, 10.0)
+        );
+
+        // A formatting context root grows to contain its floats, an ordinary block doesn't
+        let body = layout(
+            "<div style='display: flow-root'>\
+             <div style='float: left; width: 10px; height: 50px'></div></div>\
+             <div><div style='float: left; width: 10px; height: 50px'></div></div>",
+        );
+        assert_eq!(body.children[0].children[0].rect.height(), 50.0);
+        assert_eq!(body.children[0].children[1].rect.height(), 0.0);
+    }
+}
@@ -0,0 +1,298 @@
+// The fragment tree: the boxes of a document positioned by layout
+
+use crate::css::{ComputedStyle, Value};
+use crate::layout::box_tree::Image;
+use crate::layout::text::TextMeasurer;
+use eframe::egui::{self, FontId, Pos2, Rect, Vec2};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Widths of the four sides of a margin, border or padding
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Edges {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+impl Edges {
+    pub fn horizontal(&self) -> f32 {
+        self.left + self.right
+    }
+
+    pub fn vertical(&self) -> f32 {
+        self.top + self.bottom
+    }
+
+    // Shrinks a rectangle by the edges
+    pub fn inset(&self, rect: Rect) -> Rect {
+        Rect::from_min_max(
+            rect.min + egui::vec2(self.left, self.top),
+            rect.max - egui::vec2(self.right, self.bottom),
+        )
+    }
+}
+
+/// A run of text on one line, all in the same font
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextRun {
+    pub text: String,
+    pub font: FontId,
+    // Distance from the top of the run to its baseline
+    pub ascent: f32,
+    // Byte range of the run in its DOM text node
+    pub source: Range<usize>,
+    // Byte offset in the DOM text node of each byte of the text, and of its end
+    pub offsets: Vec<usize>,
+}
+
+impl TextRun {
+    // The index in the text of the first character at or after an offset in the DOM text
+    // node
+    pub fn index_of(&self, source_offset: usize) -> usize {
+        let index = self
+            .offsets
+            .iter()
+            .position(|&offset| offset >= source_offset)
+            .unwrap_or(self.text.len());
+        (index..=self.text.len())
+            .find(|&index| self.text.is_char_boundary(index))
+            .unwrap_or(self.text.len())
+    }
+
+    // The offset in the DOM text node of an index in the text
+    pub fn source_offset(&self, index: usize) -> usize {
+        self.offsets.get(index).copied().unwrap_or(self.source.end)
+    }
+
+    // The index of the character boundary nearest to `x`, measured from the start of the run
+    pub fn index_at(&self, x: f32, measurer: &dyn TextMeasurer) -> usize {
+        let mut nearest = (0, x.abs());
+        for (index, c) in self.text.char_indices() {
+            let end = index + c.len_utf8();
+            let distance = (measurer.text_width(&self.text[..end], &self.font) - x).abs();
+            if distance < nearest.1 {
+                nearest = (end, distance);
+            }
+        }
+        nearest.0
+    }
+
+    // How far along the run an index in its text is
+    pub fn x_of(&self, index: usize, measurer: &dyn TextMeasurer) -> f32 {
+        measurer.text_width(&self.text[..index], &self.font)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FragmentKind {
+    // A block-level box, or the box of an atomic inline such as an inline-block
+    Box,
+    // The part of an inline box on one line
+    Inline,
+    Line,
+    Text(TextRun),
+    Image(Image),
+}
+
+/// A box positioned by layout. Rectangles are border boxes in absolute coordinates, with
+/// the top left of the document at the origin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fragment {
+    pub kind: FragmentKind,
+    pub style: Arc<ComputedStyle>,
+    // Path of the DOM node the fragment was generated for, None for anonymous boxes
+    pub node: Option<Vec<usize>>,
+    pub rect: Rect,
+    pub border: Edges,
+    pub padding: Edges,
+    pub children: Vec<Fragment>,
+}
+
+impl Fragment {
+    pub(crate) fn new(kind: FragmentKind, style: &Arc<ComputedStyle>, rect: Rect) -> Self {
+        Self {
+            kind,
+            style: Arc::clone(style),
+            node: None,
+            rect,
+            border: Edges::default(),
+            padding: Edges::default(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn padding_rect(&self) -> Rect {
+        self.border.inset(self.rect)
+    }
+
+    pub fn content_rect(&self) -> Rect {
+        self.padding.inset(self.padding_rect())
+    }
+
+    // Layout positions fragments relative to their parent, this makes them absolute
+    pub(crate) fn make_absolute(&mut self, origin: Vec2) {
+        self.rect = self.rect.translate(origin);
+        let origin = self.rect.min.to_vec2();
+        for child in &mut self.children {
+            child.make_absolute(origin);
+        }
+    }
+
+    // The deepest fragment generated for a DOM node under `point`, in document coordinates,
+    // while `viewport` is visible and scroll containers are scrolled by `offsets`, kept by
+    // the path of their element. Positioned boxes are over the rest by z-index, and later
+    // siblings over earlier ones.
+    pub fn hit_test(
+        &self,
+        point: Pos2,
+        viewport: Rect,
+        offsets: &HashMap<Vec<usize>, Vec2>,
+    ) -> Option<&Fragment> {
+        self.hit_test_local(point, viewport, offsets)
+            .map(|(fragment, _)| fragment)
+    }
+
+    // Like `hit_test`, also giving the point where the fragment is laid out, before its
+    // scroll containers and fixed ancestors move it
+    pub fn hit_test_local(
+        &self,
+        point: Pos2,
+        viewport: Rect,
+        offsets: &HashMap<Vec<usize>, Vec2>,
+    ) -> Option<(&Fragment, Pos2)> {
+        let is_box = matches!(
+            self.kind,
+            FragmentKind::Box | FragmentKind::Inline | FragmentKind::Image(_)
+        );
+        // Fixed boxes are laid out from the document's origin but stay in the viewport
+        let mut point = point;
+        if is_box && self.style.keyword("position") == Some("fixed") {
+            point -= viewport.min.to_vec2();
+        }
+        let clips = is_box
+            && ["overflow-x", "overflow-y"]
+                .iter()
+                .any(|name| !matches!(self.style.keyword(name), None | Some("visible")));
+        if !clips || self.padding_rect().contains(point) {
+            let inner = match (clips, &self.node) {
+                (true, Some(node)) => point + offsets.get(node).copied().unwrap_or_default(),
+                _ => point,
+            };
+            let mut children: Vec<&Fragment> = self.children.iter().rev().collect();
+            children.sort_by(|a, b| stacking_order(b).total_cmp(&stacking_order(a)));
+            if let Some(hit) = children
+                .into_iter()
+                .find_map(|child| child.hit_test_local(inner, viewport, offsets))
+            {
+                return Some(hit);
+            }
+        }
+        let visible = self.style.keyword("visibility") != Some("hidden");
+        (self.node.is_some() && visible && self.rect.contains(point)).then_some((self, point))
+    }
+
+    // Returns a textual outline of the tree, one fragment per line, for snapshot tests
+    pub fn dump(&self) -> String {
+        let mut output = String::new();
+        self.dump_into(&mut output, 0);
+        output
+    }
+
+    fn dump_into(&self, output: &mut String, depth: usize) {
+        let round = |value: f32| (value * 100.0).round() / 100.0;
+        let _ = write!(output, "{}", "  ".repeat(depth));
+        let _ = match &self.kind {
+            FragmentKind::Box => write!(output, "box"),
+            FragmentKind::Inline => write!(output, "inline"),
+            FragmentKind::Line => write!(output, "line"),
+            FragmentKind::Text(run) => write!(output, "text {:?}", run.text),
+            FragmentKind::Image(_) => write!(output, "image"),
+        };
+        let _ = writeln!(
+            output,
+            " ({}, {}) {}x{}",
+            round(self.rect.min.x),
+            round(self.rect.min.y),
+            round(self.rect.width()),
+            round(self.rect.height())
+        );
+        for child in &self.children {
+            child.dump_into(output, depth + 1);
+        }
+    }
+}
+
+// Where a fragment stacks among its siblings: positioned boxes by z-index, just over the
+// boxes in flow when it's zero or auto
+fn stacking_order(fragment: &Fragment) -> f32 {
+    let positioned = matches!(
+        fragment.kind,
+        FragmentKind::Box | FragmentKind::Inline | FragmentKind::Image(_)
+    ) && fragment
+        .style
+        .keyword("position")
+        .is_some_and(|position| position != "static");
+    if !positioned {
+        return 0.0;
+    }
+    let z_index = fragment
+        .style
+        .get("z-index")
+        .and_then(Value::as_number)
+        .unwrap_or(0.0);
+    if z_index < 0.0 {
+        z_index
+    } else {
+        z_index + 0.5
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::css;
+    use crate::fonts::FontSet;
+    use crate::html;
+    use crate::layout::text::FixedMetrics;
+    use crate::layout::{build_box_tree, layout_document, LayoutContext};
+    use eframe::egui;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_hit_test() {
+        let source = "<body style='margin: 0'><p style='margin: 0'>Some <a href=/>link</a></p>\
+                      <div style='height: 40px; overflow: auto'><div style='height: 100px'>\
+                      </div><span>end</span></div>\
+                      <div style='position: absolute; top: 0; left: 0; width: 24px; \
+                      height: 10px'></div></body>";
+        let document = html::parse_document(source);
+        let stylist = css::Stylist::for_document(&document, css::Device::default());
+        let styles = stylist.style_document(&document);
+        let root = build_box_tree(&document, &styles).unwrap();
+        let fonts = FontSet::default();
+        let ctx = LayoutContext::new(&FixedMetrics, &fonts).with_viewport_height(300.0);
+        let root = layout_document(&root, 400.0, &ctx);
+        let viewport = egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(400.0, 300.0));
+        let mut offsets = HashMap::new();
+        let hit = |point: (f32, f32), offsets: &HashMap<Vec<usize>, egui::Vec2>| {
+            root.hit_test(egui::pos2(point.0, point.1), viewport, offsets)
+                .and_then(|fragment| fragment.node.clone())
+        };
+
+        // Text hits its text node, and the absolute box covers the start of the line
+        assert_eq!(hit((44.0, 10.0), &offsets), Some(vec![0, 1, 0, 1, 0]));
+        assert_eq!(hit((4.0, 4.0), &offsets), Some(vec![0, 1, 2]));
+        assert_eq!(hit((4.0, 14.0), &offsets), Some(vec![0, 1, 0, 0]));
+        // Past the end of the line is the paragraph itself
+        assert_eq!(hit((200.0, 10.0), &offsets), Some(vec![0, 1, 0]));
+
+        // The scroll container's overflow can only be hit once it's scrolled into view
+        assert_eq!(hit((4.0, 30.0), &offsets), Some(vec![0, 1, 1, 0]));
+        assert_eq!(hit((4.0, 70.0), &offsets), None);
+        offsets.insert(vec![0, 1, 1], egui::vec2(0.0, 100.0));
+        assert_eq!(hit((4.0, 30.0), &offsets), Some(vec![0, 1, 1, 1, 0]));
+    }
+}
@@ -0,0 +1,396 @@
+// Generated content: the text of ::marker, ::before and ::after boxes, with CSS counters
+// and quotes resolved in tree order
+
+use crate::css::{ComputedStyle, PseudoElement, StyleTree, StyledNode, Value};
+use crate::dom::{Document, Element, Node};
+use crate::layout::box_tree::Display;
+use std::sync::Arc;
+
+// Returns the styles with the `content` of every pseudo-element replaced by the text it
+// generates, or `none` when it generates nothing
+pub(super) fn resolve_content(document: &Document, styles: &StyleTree) -> StyleTree {
+    let mut resolved = styles.clone();
+    let mut counters = Counters::default();
+    counters.resolve_children(&document.nodes, &mut resolved.nodes, 0);
+    resolved
+}
+
+// The text a pseudo-element's resolved style generates
+pub(super) fn generated_text(style: &ComputedStyle) -> Option<&str> {
+    match style.get("content") {
+        Some(Value::Str(text)) if !text.is_empty() => Some(text),
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+struct Counter {
+    name: String,
+    value: i32,
+    // Reversed counters count down from their list items
+    reversed: bool,
+    // Depth of the element that created the counter, whose following siblings and their
+    // descendants see it
+    depth: usize,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    counters: Vec<Counter>,
+    quote_depth: usize,
+}
+
+impl Counters {
+    fn resolve_children(&mut self, nodes: &[Node], styled: &mut [StyledNode], depth: usize) {
+        for (node, styled) in nodes.iter().zip(styled) {
+            let Node::Element(element) = node else {
+                continue;
+            };
+            // Elements without a box don't count
+            if Display::of(&styled.style) == Display::None {
+                continue;
+            }
+            let list_item = styled.style.display() == "list-item";
+            self.apply(&styled.style, list_item, depth);
+            // Pseudo-elements are children of their element
+            for pseudo_element in [PseudoElement::Marker, PseudoElement::Before] {
+                self.resolve_pseudo_element(element, styled, pseudo_element, depth + 1);
+            }
+            self.resolve_children(&element.children, &mut styled.children, depth + 1);
+            self.resolve_pseudo_element(element, styled, PseudoElement::After, depth + 1);
+            // Counters created inside the element go out of scope with it
+            self.counters.retain(|counter| counter.depth <= depth);
+        }
+    }
+
+    fn resolve_pseudo_element(
+        &mut self,
+        element: &Element,
+        styled: &mut StyledNode,
+        pseudo_element: PseudoElement,
+        depth: usize,
+    ) {
+        let Some((_, style)) = styled
+            .pseudo_elements
+            .iter_mut()
+            .find(|(which, _)| *which == pseudo_element)
+        else {
+            return;
+        };
+        self.apply(style, false, depth);
+        let text = match (pseudo_element, style.get("content")) {
+            (PseudoElement::Marker, Some(content)) if content.is_keyword("normal") => {
+                self.marker_text(style)
+            }
+            (_, Some(content)) => self.content_text(content, element),
+            (_, None) => None,
+        };
+        let mut resolved = ComputedStyle::clone(style);
+        match text {
+            Some(text) => resolved.set("content", Value::Str(text)),
+            None => resolved.set("content", Value::keyword("none")),
+        }
+        *style = Arc::new(resolved);
+    }
+
+    // Applies an element's counter properties: resets, then increments, then sets. List
+    // items increment the list-item counter unless they say otherwise.
+    fn apply(&mut self, style: &ComputedStyle, list_item: bool, depth: usize) {
+        for (name, value, reversed) in counter_list(style.get("counter-reset"), 0) {
+            self.reset(name, value, reversed, depth);
+        }
+        let increments = counter_list(style.get("counter-increment"), 1);
+        if list_item && !increments.iter().any(|(name, ..)| name == "list-item") {
+            let step = match self.find("list-item") {
+                Some(counter) if counter.reversed => -1,
+                _ => 1,
+            };
+            self.increment("list-item", step, depth);
+        }
+        for (name, value, _) in increments {
+            self.increment(&name, value, depth);
+        }
+        for (name, value, _) in counter_list(style.get("counter-set"), 0) {
+            if self.find(&name).is_none() {
+                self.reset(name.clone(), 0, false, depth);
+            }
+            if let Some(counter) = self.find_mut(&name) {
+                counter.value = value;
+            }
+        }
+    }
+
+    // Creates a counter, replacing one a preceding sibling created
+    fn reset(&mut self, name: String, value: i32, reversed: bool, depth: usize) {
+        let counter = Counter {
+            name,
+            value,
+            reversed,
+            depth,
+        };
+        match self.find_mut(&counter.name) {
+            Some(existing) if existing.depth == depth => *existing = counter,
+            _ => self.counters.push(counter),
+        }
+    }
+
+    fn increment(&mut self, name: &str, step: i32, depth: usize) {
+        if self.find(name).is_none() {
+            self.reset(name.to_string(), 0, false, depth);
+        }
+        if let Some(counter) = self.find_mut(name) {
+            counter.value = counter.value.saturating_add(step);
+        }
+    }
+
+    // The innermost counter of a name
+    fn find(&self, name: &str) -> Option<&Counter> {
+        self.counters
+            .iter()
+            .rev()
+            .find(|counter| counter.name == name)
+    }
+
+    fn find_mut(&mut self, name: &str) -> Option<&mut Counter> {
+        self.counters
+            .iter_mut()
+            .rev()
+            .find(|counter| counter.name == name)
+    }
+
+    // The text of a marker whose content is `normal`, from its list-style-type
+    fn marker_text(&self, style: &ComputedStyle) -> Option<String> {
+        match style.get("list-style-type")? {
+            Value::Str(text) => Some(text.clone()),
+            Value::Keyword(kind) if kind == "none" => None,
+            Value::Keyword(kind) if matches!(kind.as_str(), "disc" | "circle" | "square") => {
+                Some(format!("{} ", format_counter(0, kind)))
+            }
+            Value::Keyword(kind) => {
+                let value = self.find("list-item").map_or(0, |counter| counter.value);
+                Some(format!("{}. ", format_counter(value, kind)))
+            }
+            _ => None,
+        }
+    }
+
+    // The text of a `content` value. Returns None for `normal`, `none` or invalid values.
+    fn content_text(&mut self, content: &Value, element: &Element) -> Option<String> {
+        let mut text = String::new();
+        for item in content.items() {
+            match item {
+                Value::Str(string) => text.push_str(string),
+                Value::Function(function, arguments) => {
+                    let name = arguments.first()?.as_keyword()?;
+                    let style = |index: usize| {
+                        arguments
+                            .get(index)
+                            .and_then(Value::as_keyword)
+                            .unwrap_or("decimal")
+                    };
+                    match function.as_str() {
+                        "counter" => {
+                            let value = self.find(name).map_or(0, |counter| counter.value);
+                            text.push_str(&format_counter(value, style(1)));
+                        }
+                        "counters" => {
+                            let Some(Value::Str(separator)) = arguments.get(1) else {
+                                return None;
+                            };
+                            let values: Vec<String> = self
+                                .counters
+                                .iter()
+                                .filter(|counter| counter.name == name)
+                                .map(|counter| format_counter(counter.value, style(2)))
+                                .collect();
+                            match values.is_empty() {
+                                true => text.push_str(&format_counter(0, style(2))),
+                                false => text.push_str(&values.join(separator)),
+                            }
+                        }
+                        "attr" => {
+                            let value = element.attributes.get(name);
+                            text.push_str(value.map_or("", String::as_str));
+                        }
+                        _ => return None,
+                    }
+                }
+                Value::Keyword(keyword) => match keyword.as_str() {
+                    "open-quote" => {
+                        text.push(quote(self.quote_depth, true));
+                        self.quote_depth += 1;
+                    }
+                    "close-quote" if self.quote_depth > 0 => {
+                        self.quote_depth -= 1;
+                        text.push(quote(self.quote_depth, false));
+                    }
+                    "no-open-quote" => self.quote_depth += 1,
+                    "close-quote" | "no-close-quote" => {
+                        self.quote_depth = self.quote_depth.saturating_sub(1)
+                    }
+                    _ => return None,
+                },
+                _ => return None,
+            }
+        }
+        Some(text)
+    }
+}
+
+// Parses the counter names of a counter-* property, each with an optional integer and
+// `reversed()` for resets
+fn counter_list(value: Option<&Value>, default: i32) -> Vec<(String, i32, bool)> {
+    let mut counters: Vec<(String, i32, bool)> = Vec::new();
+    for item in value.map_or(&[][..], Value::items) {
+        match item {
+            Value::Keyword(name) if name == "none" => return Vec::new(),
+            Value::Keyword(name) => counters.push((name.clone(), default, false)),
+            Value::Function(function, arguments) if function == "reversed" => {
+                if let Some(name) = arguments.first().and_then(Value::as_keyword) {
+                    counters.push((name.to_string(), default, true));
+                }
+            }
+            Value::Number(number) => {
+                if let Some(counter) = counters.last_mut() {
+                    counter.1 = *number as i32;
+                }
+            }
+            _ => {}
+        }
+    }
+    counters
+}
+
+// Quotation marks, alternating between double and single with nesting
+fn quote(depth: usize, open: bool) -> char {
+    match (depth.is_multiple_of(2), open) {
+        (true, true) => '\u{201c}',
+        (true, false) => '\u{201d}',
+        (false, true) => '\u{2018}',
+        (false, false) => '\u{2019}',
+    }
+}
+
+// Formats a counter value in a list style. Values a style can't represent fall back to
+// decimal.
+pub(super) fn format_counter(value: i32, style: &str) -> String {
+    match style {
+        "none" => String::new(),
+        "disc" => "\u{2022}".to_string(),
+        "circle" => "\u{25e6}".to_string(),
+        "square" => "\u{25aa}".to_string(),
+        "lower-alpha" | "lower-latin" if value > 0 => alphabetic(value),
+        "upper-alpha" | "upper-latin" if value > 0 => alphabetic(value).to_ascii_uppercase(),
+        "lower-roman" if (1..4000).contains(&value) => roman(value),
+        "upper-roman" if (1..4000).contains(&value) => roman(value).to_ascii_uppercase(),
+        "decimal-leading-zero" if (0..10).contains(&value) => format!("0{}", value),
+        _ => value.to_string(),
+    }
+}
+
+// a, b, ..., z, aa, ab, ...
+fn alphabetic(mut value: i32) -> String {
+    let mut letters = Vec::new();
+    while value > 0 {
+        value -= 1;
+        letters.push((b'a' + (value % 26) as u8) as char);
+        value /= 26;
+    }
+    letters.iter().rev().collect()
+}
+
+fn roman(mut value: i32) -> String {
+    const NUMERALS: &[(i32, &str)] = &[
+        (1000, "m"),
+        (900, "cm"),
+        (500, "d"),
+        (400, "cd"),
+        (100, "c"),
+        (90, "xc"),
+        (50, "l"),
+        (40, "xl"),
+        (10, "x"),
+        (9, "ix"),
+        (5, "v"),
+        (4, "iv"),
+        (1, "i"),
+    ];
+    let mut numeral = String::new();
+    for (amount, digits) in NUMERALS {
+        while value >= *amount {
+            numeral.push_str(digits);
+            value -= amount;
+        }
+    }
+    numeral
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::css;
+    use crate::html;
+    use crate::layout::{build_box_tree, BoxKind, LayoutBox};
+
+    // The text of every box, with outside markers before their list item's content
+    fn texts(body: &str) -> Vec<String> {
+        fn collect(layout_box: &LayoutBox, found: &mut Vec<String>) {
+            if let Some(marker) = &layout_box.marker {
+                collect(marker, found);
+            }
+            if let BoxKind::Text(text) = &layout_box.kind {
+                found.push(text.clone());
+            }
+            for child in &layout_box.children {
+                collect(child, found);
+            }
+        }
+        let document = html::parse_document(&format!("<body>{}</body>", body));
+        let stylist = css::Stylist::for_document(&document, css::Device::default());
+        let root = build_box_tree(&document, &stylist.style_document(&document)).unwrap();
+        let mut found = Vec::new();
+        collect(&root, &mut found);
+        found
+    }
+
+    #[test]
+    fn test_list_markers() {
+        assert_eq!(
+            texts("<ol start=3><li>a<li value=10>b<li>c</ol><ul><li>d</ul>"),
+            vec!["3. ", "a", "10. ", "b", "11. ", "c", "\u{2022} ", "d"]
+        );
+        assert_eq!(
+            texts("<ol reversed><li>a<li>b<li>c</ol>"),
+            vec!["3. ", "a", "2. ", "b", "1. ", "c"]
+        );
+        assert_eq!(
+            texts(
+                "<ol style='list-style-type: upper-roman'><li>a<li>b\
+                 <ol style='list-style: lower-alpha inside'><li>c<li>d</ol></ol>\
+                 <ul style='list-style-type: none'><li>e</ul>\
+                 <ul style=\"list-style-type: '-'\"><li>f</ul>"
+            ),
+            vec!["I. ", "a", "II. ", "b", "a. ", "c", "b. ", "d", "e", "-", "f"]
+        );
+    }
+
+    #[test]
+    fn test_counters_and_generated_content() {
+        let style = "<style>\
+            section { counter-reset: item; }\
+            h2::before { counter-increment: item; content: counters(item, '.') ' '; }\
+            q::before { content: open-quote; } q::after { content: close-quote; }\
+            a::after { content: ' (' attr(href) ')'; }\
+            </style>";
+        assert_eq!(
+            texts(&format!(
+                "{}<section><h2>A</h2><h2>B</h2><section><h2>C</h2></section></section>\
+                 <p><q>x <q>y</q></q> <a href=z>link</a></p>",
+                style
+            )),
+            vec![
+                "1 ", "A", "2 ", "B", "2.1 ", "C", "\u{201c}", "x ", "\u{2018}", "y", "\u{2019}",
+                "\u{201d}", " ", "link", " (z)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,1295 @@
+// Grid layout: placing items into the cells of a grid, sizing its tracks from their
+// sizing functions and the items spanning them, then aligning the items in their areas
+
+use crate::css::{ComputedStyle, Value};
+use crate::layout::block::{self, BlockLayout, ContainingBlock};
+use crate::layout::box_tree::{BoxKind, LayoutBox};
+use crate::layout::flex::{self, Align};
+use crate::layout::fragment::Fragment;
+use crate::layout::LayoutContext;
+use eframe::egui;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+/// The items of a grid container positioned relative to its content box
+pub(super) struct GridLayout {
+    pub children: Vec<Fragment>,
+    pub height: f32,
+    pub baseline: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Sizing {
+    Fixed(f32),
+    Fr(f32),
+    Auto,
+    MinContent,
+    MaxContent,
+    FitContent(f32),
+}
+
+// The minimum and maximum sizing functions of a track
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Track {
+    min: Sizing,
+    max: Sizing,
+}
+
+impl Track {
+    fn new(sizing: Sizing) -> Self {
+        match sizing {
+            // Flexible and fit-content tracks have an automatic minimum
+            Sizing::Fr(_) | Sizing::FitContent(_) => Track {
+                min: Sizing::Auto,
+                max: sizing,
+            },
+            sizing => Track {
+                min: sizing,
+                max: sizing,
+            },
+        }
+    }
+
+    fn flex(&self) -> Option<f32> {
+        match self.max {
+            Sizing::Fr(flex) => Some(flex),
+            _ => None,
+        }
+    }
+}
+
+// The explicit tracks of one axis and the names of the lines around them
+#[derive(Debug, Default)]
+struct TrackList {
+    tracks: Vec<Track>,
+    // The names of each line, one more than there are tracks
+    names: Vec<Vec<String>>,
+    // Tracks repeated by auto-fit, which collapse when no item is placed in them
+    auto_fit: Option<Range<usize>>,
+}
+
+impl TrackList {
+    // Appends the tracks of another list, merging the names of the lines where they meet
+    fn append(&mut self, other: &TrackList) {
+        let (first, rest) = other.names.split_first().expect("Track list without lines");
+        self.names
+            .last_mut()
+            .expect("Track list without lines")
+            .extend(first.iter().cloned());
+        self.tracks.extend(&other.tracks);
+        self.names.extend(rest.iter().cloned());
+    }
+}
+
+// Named areas from grid-template-areas as the rows and columns they cover
+#[derive(Debug, Default)]
+struct Areas {
+    rows: usize,
+    columns: usize,
+    areas: HashMap<String, [Range<usize>; 2]>,
+}
+
+// The lines an item's placement properties refer to, counting from zero
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GridLine {
+    Auto,
+    Line(i32),
+    Span(i32),
+}
+
+// Where an item sits along each axis, rows then columns. A start of None is auto-placed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Placement {
+    start: [Option<i32>; 2],
+    span: [i32; 2],
+}
+
+#[derive(Debug, Clone)]
+struct TrackState {
+    track: Track,
+    base: f32,
+    limit: f32,
+    collapsed: bool,
+}
+
+struct GridItem<'a> {
+    layout_box: &'a LayoutBox,
+    // The tracks the item spans along each axis, rows then columns
+    area: [Range<usize>; 2],
+}
+
+struct Grid<'a> {
+    items: Vec<GridItem<'a>>,
+    // The tracks along each axis, rows then columns
+    tracks: [Vec<TrackState>; 2],
+}
+
+// The tracks an item spans and its min-content and max-content contributions to them
+type Contribution = (Range<usize>, f32, f32);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Available {
+    Definite(f32),
+    MinContent,
+    MaxContent,
+}
+
+// Lays out the children of a grid container whose content width is known
+pub(super) fn layout_grid_content(
+    layout_box: &LayoutBox,
+    containing: ContainingBlock,
+    ctx: &LayoutContext,
+) -> GridLayout {
+    let style = &layout_box.style;
+    let gaps = [
+        flex::gap(style, "row-gap", containing.height.unwrap_or(0.0)),
+        flex::gap(style, "column-gap", containing.width),
+    ];
+    let mut grid = build_grid(
+        layout_box,
+        [containing.height, Some(containing.width)],
+        gaps,
+    );
+    let stretches = |name: &str| matches!(style.keyword(name), None | Some("normal" | "stretch"));
+
+    // Columns are sized from the widths of the items
+    let contributions: Vec<Contribution> = grid
+        .items
+        .iter()
+        .map(|item| {
+            let (min, max) = block::intrinsic_outer_widths(item.layout_box, ctx);
+            (item.area[1].clone(), min, max)
+        })
+        .collect();
+    size_tracks(
+        &mut grid.tracks[1],
+        &contributions,
+        Available::Definite(containing.width),
+        gaps[1],
+        stretches("justify-content"),
+    );
+    let (columns, _) = track_offsets(
+        &grid.tracks[1],
+        gaps[1],
+        style.keyword("justify-content"),
+        Some(containing.width),
+    );
+
+    // Rows are sized from the heights of the items at their widths
+    let mut items: Vec<SizedItem> = grid
+        .items
+        .iter()
+        .map(|item| {
+            let width = span_size(&grid.tracks[1], &columns, &item.area[1]);
+            sized_item(item, style, width, ctx)
+        })
+        .collect();
+    let contributions: Vec<Contribution> = items
+        .iter()
+        .map(|item| {
+            let height = item.outer_height();
+            (item.area[0].clone(), height, height)
+        })
+        .collect();
+    let available = match containing.height {
+        Some(height) => Available::Definite(height),
+        None => Available::MaxContent,
+    };
+    size_tracks(
+        &mut grid.tracks[0],
+        &contributions,
+        available,
+        gaps[0],
+        stretches("align-content"),
+    );
+    let (rows, height) = track_offsets(
+        &grid.tracks[0],
+        gaps[0],
+        style.keyword("align-content"),
+        containing.height,
+    );
+
+    // Items are laid out again in their areas, stretching to fill them by default
+    let mut children = Vec::new();
+    let mut baseline: Option<(usize, f32)> = None;
+    for item in &mut items {
+        let area = egui::vec2(
+            span_size(&grid.tracks[1], &columns, &item.area[1]),
+            span_size(&grid.tracks[0], &rows, &item.area[0]),
+        );
+        let style = &item.layout_box.style;
+        let block_margins = [item.margins[0], item.margins[2]];
+        let height = match block::resolve_height(style, "height", Some(area.y), item.vertical) {
+            None if item.align == Align::Stretch
+                && !item.replaced
+                && !flex::has_auto(block_margins) =>
+            {
+                let margins = block_margins.iter().flatten().sum::<f32>();
+                Some((area.y - margins - item.vertical).max(0.0))
+            }
+            _ => None,
+        };
+        let containing = ContainingBlock {
+            width: area.x,
+            height: Some(area.y),
+        };
+        let layout = block::layout_sized_box(item.layout_box, item.width, height, containing, ctx);
+        let size = layout.fragment.rect.size();
+        let x = columns[item.area[1].start]
+            + align_in_area(
+                area.x,
+                size.x,
+                [item.margins[3], item.margins[1]],
+                item.justify,
+            );
+        let y = rows[item.area[0].start] + align_in_area(area.y, size.y, block_margins, item.align);
+        // The grid's baseline comes from the first item in its first row
+        if let (0, Some(item_baseline)) = (item.area[0].start, layout.baseline) {
+            if baseline.is_none_or(|(column, _)| item.area[1].start < column) {
+                baseline = Some((item.area[1].start, y + item_baseline));
+            }
+        }
+        let mut fragment = layout.fragment;
+        fragment.rect = fragment.rect.translate(egui::vec2(x, y));
+        children.push(fragment);
+    }
+    GridLayout {
+        children,
+        height,
+        baseline: baseline.map(|(_, baseline)| baseline),
+    }
+}
+
+// An item sized along the inline axis, ready to size the rows
+struct SizedItem<'a> {
+    layout_box: &'a LayoutBox,
+    area: [Range<usize>; 2],
+    // Margins in top, right, bottom, left order, None for auto
+    margins: [Option<f32>; 4],
+    // Border and padding along the block axis
+    vertical: f32,
+    replaced: bool,
+    justify: Align,
+    align: Align,
+    width: f32,
+    layout: BlockLayout,
+}
+
+impl SizedItem<'_> {
+    fn outer_height(&self) -> f32 {
+        self.layout.fragment.rect.height()
+            + self.margins[0].unwrap_or(0.0)
+            + self.margins[2].unwrap_or(0.0)
+    }
+}
+
+fn sized_item<'a>(
+    item: &GridItem<'a>,
+    container: &ComputedStyle,
+    area_width: f32,
+    ctx: &LayoutContext,
+) -> SizedItem<'a> {
+    let style = &item.layout_box.style;
+    let margins = block::margins(style, area_width);
+    let border = block::border(style);
+    let padding = block::padding(style, area_width);
+    let horizontal = border.horizontal() + padding.horizontal();
+    let replaced = matches!(item.layout_box.kind, BoxKind::Replaced(_));
+    let justify = self_alignment(style, container, "justify-self", "justify-items");
+    let align = self_alignment(style, container, "align-self", "align-items");
+
+    let inline_margins = [margins[3], margins[1]];
+    let available =
+        (area_width - inline_margins.iter().flatten().sum::<f32>() - horizontal).max(0.0);
+    let width = match block::content_size(style, "width", area_width, horizontal) {
+        Some(width) => width,
+        // Replaced elements keep their own size rather than stretching
+        None if justify == Align::Stretch && !replaced && !flex::has_auto(inline_margins) => {
+            available
+        }
+        None => {
+            let (min, max) = block::intrinsic_content_widths(item.layout_box, ctx);
+            max.min(available.max(min))
+        }
+    };
+    let width = block::clamp_width(style, width, area_width, horizontal);
+    let containing = ContainingBlock {
+        width: area_width,
+        height: None,
+    };
+    SizedItem {
+        layout_box: item.layout_box,
+        area: item.area.clone(),
+        margins,
+        vertical: border.vertical() + padding.vertical(),
+        replaced,
+        justify,
+        align,
+        width,
+        layout: block::layout_sized_box(item.layout_box, width, None, containing, ctx),
+    }
+}
+
+// The alignment of an item from its own property, or its container's when auto
+fn self_alignment(
+    style: &ComputedStyle,
+    container: &ComputedStyle,
+    own: &str,
+    items: &str,
+) -> Align {
+    match style.get(own) {
+        Some(Value::Keyword(keyword)) if keyword == "auto" => flex::align(container.get(items)),
+        value => flex::align(value),
+    }
+}
+
+// The offset of an item's border box in its area. Auto margins take up the free space.
+fn align_in_area(area: f32, size: f32, margins: [Option<f32>; 2], align: Align) -> f32 {
+    let start = margins[0].unwrap_or(0.0);
+    let free = area - size - margins.iter().flatten().sum::<f32>();
+    match (margins, align) {
+        ([None, None], _) => free.max(0.0) / 2.0,
+        ([None, Some(_)], _) => free.max(0.0),
+        ([Some(_), None], _) => start,
+        (_, Align::End) => start + free,
+        (_, Align::Center) => start + free / 2.0,
+        _ => start,
+    }
+}
+
+// Resolves the templates and places every item in the grid, with the tracks not yet sized
+fn build_grid(layout_box: &LayoutBox, sizes: [Option<f32>; 2], gaps: [f32; 2]) -> Grid<'_> {
+    let style = &layout_box.style;
+    let areas = template_areas(style.get("grid-template-areas"));
+    let lists = [
+        track_list(style.get("grid-template-rows"), sizes[0], gaps[0]),
+        track_list(style.get("grid-template-columns"), sizes[1], gaps[1]),
+    ];
+    let auto = [
+        auto_tracks(style.get("grid-auto-rows"), sizes[0]),
+        auto_tracks(style.get("grid-auto-columns"), sizes[1]),
+    ];
+    let explicit = [
+        lists[0].tracks.len().max(areas.rows),
+        lists[1].tracks.len().max(areas.columns),
+    ];
+    let flow: Vec<&str> = style
+        .get("grid-auto-flow")
+        .map(|value| value.items().iter().filter_map(Value::as_keyword).collect())
+        .unwrap_or_default();
+    let major = match flow.contains(&"column") {
+        true => 1,
+        false => 0,
+    };
+
+    let mut children: Vec<&LayoutBox> = layout_box
+        .children
+        .iter()
+        .filter(|child| !child.is_absolutely_positioned())
+        .collect();
+    children.sort_by(|a, b| {
+        flex::number(&a.style, "order", 0.0).total_cmp(&flex::number(&b.style, "order", 0.0))
+    });
+    let properties = [
+        ("grid-row-start", "grid-row-end"),
+        ("grid-column-start", "grid-column-end"),
+    ];
+    let mut placements: Vec<Placement> = children
+        .iter()
+        .map(|child| {
+            let mut placement = Placement {
+                start: [None; 2],
+                span: [1; 2],
+            };
+            for (axis, (start, end)) in properties.iter().enumerate() {
+                let lines = AxisLines {
+                    list: &lists[axis],
+                    areas: &areas,
+                    axis,
+                    explicit: explicit[axis],
+                };
+                let start = grid_line(child.style.get(start), "start", &lines);
+                let end = grid_line(child.style.get(end), "end", &lines);
+                (placement.start[axis], placement.span[axis]) = resolve_placement(start, end);
+            }
+            placement
+        })
+        .collect();
+    place_items(&mut placements, explicit, major, flow.contains(&"dense"));
+
+    // The grid covers the explicit grid and every item, adding implicit tracks as needed
+    let mut first_lines = [0; 2];
+    let tracks = [0, 1].map(|axis| {
+        let first = placements
+            .iter()
+            .filter_map(|placement| placement.start[axis])
+            .fold(0, i32::min);
+        let last = placements
+            .iter()
+            .filter_map(|placement| Some(placement.start[axis]? + placement.span[axis]))
+            .fold(explicit[axis] as i32, i32::max);
+        first_lines[axis] = first;
+        let list = &lists[axis];
+        (first..last)
+            .map(|line| {
+                let track = match usize::try_from(line).ok().and_then(|i| list.tracks.get(i)) {
+                    Some(track) => *track,
+                    None => {
+                        // Implicit tracks cycle through grid-auto-rows or grid-auto-columns,
+                        // backwards before the explicit grid
+                        let index = match line < 0 {
+                            true => line,
+                            false => line - list.tracks.len() as i32,
+                        };
+                        auto[axis][index.rem_euclid(auto[axis].len() as i32) as usize]
+                    }
+                };
+                let repeated = list
+                    .auto_fit
+                    .as_ref()
+                    .is_some_and(|range| line >= 0 && range.contains(&(line as usize)));
+                let collapsed = repeated
+                    && !placements.iter().any(|placement| {
+                        let start = placement.start[axis].unwrap_or(0);
+                        (start..start + placement.span[axis]).contains(&line)
+                    });
+                TrackState {
+                    track,
+                    base: 0.0,
+                    limit: 0.0,
+                    collapsed,
+                }
+            })
+            .collect()
+    });
+    let items = children
+        .into_iter()
+        .zip(placements)
+        .map(|(layout_box, placement)| GridItem {
+            layout_box,
+            area: [0, 1].map(|axis| {
+                let start = (placement.start[axis].unwrap_or(0) - first_lines[axis]) as usize;
+                start..start + placement.span[axis] as usize
+            }),
+        })
+        .collect();
+    Grid { items, tracks }
+}
+
+// Places items without a definite position, following grid-auto-flow. `major` is the axis
+// the grid grows along: rows for row flow, columns for column flow.
+fn place_items(placements: &mut [Placement], explicit: [usize; 2], major: usize, dense: bool) {
+    let minor = 1 - major;
+    let mut occupied: HashSet<[i32; 2]> = HashSet::new();
+    let fits = |occupied: &HashSet<[i32; 2]>, start: [i32; 2], span: [i32; 2]| {
+        (start[0]..start[0] + span[0]).all(|row| {
+            (start[1]..start[1] + span[1]).all(|column| !occupied.contains(&[row, column]))
+        })
+    };
+    let occupy = |occupied: &mut HashSet<[i32; 2]>, start: [i32; 2], span: [i32; 2]| {
+        for row in start[0]..start[0] + span[0] {
+            for column in start[1]..start[1] + span[1] {
+                occupied.insert([row, column]);
+            }
+        }
+    };
+    for placement in placements.iter() {
+        if let [Some(row), Some(column)] = placement.start {
+            occupy(&mut occupied, [row, column], placement.span);
+        }
+    }
+
+    // Items locked to a row, or to a column in column flow
+    let mut cursors: HashMap<i32, i32> = HashMap::new();
+    for placement in placements
+        .iter_mut()
+        .filter(|placement| placement.start[major].is_some() && placement.start[minor].is_none())
+    {
+        let line = placement.start[major].unwrap_or(0);
+        let mut start = [line; 2];
+        start[minor] = match dense {
+            true => 0,
+            false => cursors.get(&line).copied().unwrap_or(0),
+        };
+        while !fits(&occupied, start, placement.span) {
+            start[minor] += 1;
+        }
+        occupy(&mut occupied, start, placement.span);
+        placement.start[minor] = Some(start[minor]);
+        cursors.insert(line, start[minor] + placement.span[minor]);
+    }
+
+    // The remaining items run across the explicit grid and any lines other items reach
+    let minor_first = placements
+        .iter()
+        .filter_map(|placement| placement.start[minor])
+        .fold(0, i32::min);
+    let minor_last = placements
+        .iter()
+        .map(|placement| placement.start[minor].unwrap_or(minor_first) + placement.span[minor])
+        .fold(explicit[minor] as i32, i32::max);
+    let major_first = placements
+        .iter()
+        .filter_map(|placement| placement.start[major])
+        .fold(0, i32::min);
+    let (mut cursor_major, mut cursor_minor) = (major_first, minor_first);
+    for placement in placements
+        .iter_mut()
+        .filter(|placement| placement.start[major].is_none())
+    {
+        if dense {
+            (cursor_major, cursor_minor) = (major_first, minor_first);
+        }
+        let mut start = [0; 2];
+        match placement.start[minor] {
+            Some(line) => {
+                if !dense && line < cursor_minor {
+                    cursor_major += 1;
+                }
+                cursor_minor = line;
+                loop {
+                    (start[major], start[minor]) = (cursor_major, line);
+                    if fits(&occupied, start, placement.span) {
+                        break;
+                    }
+                    cursor_major += 1;
+                }
+            }
+            None => loop {
+                if cursor_minor + placement.span[minor] > minor_last && cursor_minor > minor_first {
+                    cursor_major += 1;
+                    cursor_minor = minor_first;
+                }
+                (start[major], start[minor]) = (cursor_major, cursor_minor);
+                if fits(&occupied, start, placement.span) {
+                    break;
+                }
+                cursor_minor += 1;
+            },
+        }
+        occupy(&mut occupied, start, placement.span);
+        placement.start = start.map(Some);
+    }
+}
+
+// Sizes the tracks of one axis from their sizing functions and the items spanning them
+fn size_tracks(
+    tracks: &mut [TrackState],
+    contributions: &[Contribution],
+    available: Available,
+    gap: f32,
+    stretch: bool,
+) {
+    for track in tracks.iter_mut() {
+        track.base = match (track.collapsed, track.track.min) {
+            (false, Sizing::Fixed(size)) => size,
+            _ => 0.0,
+        };
+        track.limit = match (track.collapsed, track.track.max) {
+            (true, _) => 0.0,
+            (false, Sizing::Fixed(size)) => size.max(track.base),
+            _ => f32::INFINITY,
+        };
+    }
+    let contribution = |min: f32, max: f32| match available {
+        Available::MinContent => (min, min),
+        _ => (min, max),
+    };
+
+    // Intrinsic tracks grow to fit the items spanning them, fewest tracks first
+    let mut spanning: Vec<&Contribution> = contributions
+        .iter()
+        .filter(|(range, ..)| !spans_flexible(tracks, range))
+        .collect();
+    spanning.sort_by_key(|(range, ..)| range.len());
+    let mut intrinsic_limits: Vec<Option<f32>> = vec![None; tracks.len()];
+    for (range, min, max) in spanning {
+        let (min, max) = contribution(*min, *max);
+        let spanned: Vec<usize> = range.clone().filter(|&i| !tracks[i].collapsed).collect();
+        let gaps = gap * spanned.len().saturating_sub(1) as f32;
+        let intrinsic_min: Vec<usize> = spanned
+            .iter()
+            .copied()
+            .filter(|&i| !matches!(tracks[i].track.min, Sizing::Fixed(_)))
+            .collect();
+        let wanted = match intrinsic_min
+            .iter()
+            .all(|&i| tracks[i].track.min == Sizing::MaxContent)
+        {
+            true => max,
+            false => min,
+        };
+        let extra = wanted - gaps - spanned.iter().map(|&i| tracks[i].base).sum::<f32>();
+        if extra > 0.0 {
+            for &i in &intrinsic_min {
+                tracks[i].base += extra / intrinsic_min.len() as f32;
+            }
+        }
+
+        let intrinsic_max: Vec<usize> = spanned
+            .iter()
+            .copied()
+            .filter(|&i| tracks[i].limit.is_infinite())
+            .collect();
+        let wanted = match spanned.as_slice() {
+            [i] => match tracks[*i].track.max {
+                Sizing::MinContent => min,
+                Sizing::FitContent(limit) => max.min(limit.max(min)),
+                _ => max,
+            },
+            _ => max,
+        };
+        let limits: Vec<f32> = spanned
+            .iter()
+            .map(|&i| match tracks[i].limit.is_finite() {
+                true => tracks[i].limit,
+                false => intrinsic_limits[i].unwrap_or(tracks[i].base),
+            })
+            .collect();
+        let extra = wanted - gaps - limits.iter().sum::<f32>();
+        if extra > 0.0 {
+            for (&i, limit) in spanned.iter().zip(limits) {
+                if intrinsic_max.contains(&i) {
+                    intrinsic_limits[i] = Some(limit + extra / intrinsic_max.len() as f32);
+                }
+            }
+        }
+    }
+    // Items spanning flexible tracks grow their intrinsic minimums
+    let spanning: Vec<&Contribution> = contributions
+        .iter()
+        .filter(|(range, ..)| spans_flexible(tracks, range))
+        .collect();
+    for (range, min, _) in spanning {
+        let spanned: Vec<usize> = range.clone().filter(|&i| !tracks[i].collapsed).collect();
+        let gaps = gap * spanned.len().saturating_sub(1) as f32;
+        let targets: Vec<usize> = spanned
+            .iter()
+            .copied()
+            .filter(|&i| {
+                tracks[i].track.flex().is_some() && tracks[i].track.min != Sizing::Fixed(0.0)
+            })
+            .filter(|&i| !matches!(tracks[i].track.min, Sizing::Fixed(_)))
+            .collect();
+        let extra = min - gaps - spanned.iter().map(|&i| tracks[i].base).sum::<f32>();
+        if extra > 0.0 {
+            for &i in &targets {
+                tracks[i].base += extra / targets.len() as f32;
+            }
+        }
+    }
+    for (track, limit) in tracks.iter_mut().zip(intrinsic_limits) {
+        if track.limit.is_infinite() && track.track.flex().is_none() {
+            track.limit = limit.unwrap_or(0.0);
+        }
+        if track.limit.is_finite() {
+            track.limit = track.limit.max(track.base);
+        }
+    }
+
+    // Free space grows the tracks equally up to their limits
+    let count = tracks.iter().filter(|track| !track.collapsed).count();
+    let gaps = gap * count.saturating_sub(1) as f32;
+    let free_space =
+        |tracks: &[TrackState], size: f32| size - gaps - tracks.iter().map(|t| t.base).sum::<f32>();
+    match available {
+        Available::Definite(size) => loop {
+            let free = free_space(tracks, size);
+            let growable: Vec<usize> = (0..tracks.len())
+                .filter(|&i| tracks[i].track.flex().is_none() && tracks[i].limit > tracks[i].base)
+                .collect();
+            if free <= 0.01 || growable.is_empty() {
+                break;
+            }
+            let share = free / growable.len() as f32;
+            for i in growable {
+                tracks[i].base = (tracks[i].base + share).min(tracks[i].limit);
+            }
+        },
+        Available::MaxContent => {
+            for track in tracks
+                .iter_mut()
+                .filter(|track| track.track.flex().is_none())
+            {
+                track.base = track.limit;
+            }
+        }
+        Available::MinContent => {}
+    }
+
+    // Flexible tracks share what's left in proportion to their flex factors
+    let flexible: Vec<(usize, f32)> = (0..tracks.len())
+        .filter(|&i| !tracks[i].collapsed)
+        .filter_map(|i| Some((i, tracks[i].track.flex()?)))
+        .collect();
+    if !flexible.is_empty() {
+        let fr = match available {
+            Available::Definite(size) => {
+                // Tracks whose base size is larger than their share are treated as inflexible
+                let mut flexing = flexible.clone();
+                loop {
+                    let others = (0..tracks.len())
+                        .filter(|i| !flexing.iter().any(|(j, _)| i == j))
+                        .map(|i| tracks[i].base)
+                        .sum::<f32>();
+                    let leftover = (size - gaps - others).max(0.0);
+                    let sum = flexing.iter().map(|(_, flex)| flex).sum::<f32>();
+                    let fr = leftover / sum.max(1.0);
+                    let before = flexing.len();
+                    flexing.retain(|&(i, flex)| tracks[i].base <= fr * flex);
+                    if flexing.len() == before {
+                        break fr;
+                    }
+                }
+            }
+            _ => {
+                // The size of one fr is the largest any track or item needs
+                let mut fr = flexible
+                    .iter()
+                    .map(|&(i, flex)| tracks[i].base / flex.max(1.0))
+                    .fold(0.0, f32::max);
+                for (range, min, max) in contributions
+                    .iter()
+                    .filter(|(range, ..)| spans_flexible(tracks, range))
+                {
+                    let (_, max) = contribution(*min, *max);
+                    let sum = range
+                        .clone()
+                        .filter_map(|i| tracks[i].track.flex())
+                        .sum::<f32>();
+                    let fixed = range
+                        .clone()
+                        .filter(|&i| tracks[i].track.flex().is_none())
+                        .map(|i| tracks[i].base)
+                        .sum::<f32>()
+                        + gap * range.len().saturating_sub(1) as f32;
+                    fr = fr.max((max - fixed) / sum.max(1.0));
+                }
+                fr
+            }
+        };
+        for (i, flex) in flexible {
+            tracks[i].base = tracks[i].base.max(fr * flex);
+        }
+    }
+
+    // Auto tracks stretch to fill any space left over
+    if let (true, Available::Definite(size)) = (stretch, available) {
+        let free = free_space(tracks, size);
+        let auto: Vec<usize> = (0..tracks.len())
+            .filter(|&i| !tracks[i].collapsed && tracks[i].track.max == Sizing::Auto)
+            .collect();
+        if free > 0.0 {
+            for &i in &auto {
+                tracks[i].base += free / auto.len() as f32;
+            }
+        }
+    }
+}
+
+fn spans_flexible(tracks: &[TrackState], range: &Range<usize>) -> bool {
+    range.clone().any(|i| tracks[i].track.flex().is_some())
+}
+
+// The start of each track and the size the grid covers along an axis, with any free space
+// distributed by justify-content or align-content
+fn track_offsets(
+    tracks: &[TrackState],
+    gap: f32,
+    keyword: Option<&str>,
+    available: Option<f32>,
+) -> (Vec<f32>, f32) {
+    let count = tracks.iter().filter(|track| !track.collapsed).count();
+    let used =
+        tracks.iter().map(|track| track.base).sum::<f32>() + gap * count.saturating_sub(1) as f32;
+    let free = available.map_or(0.0, |available| available - used);
+    let keyword = flex::content_position(keyword.unwrap_or("normal"), false);
+    let (mut position, between) = flex::distribute(keyword, free, count);
+    let mut offsets = Vec::new();
+    for track in tracks {
+        offsets.push(position);
+        // Collapsed tracks take no space and their gaps collapse with them
+        if !track.collapsed {
+            position += track.base + gap + between;
+        }
+    }
+    (offsets, available.unwrap_or(used))
+}
+
+// The size of the tracks in a range, including the gaps between them
+fn span_size(tracks: &[TrackState], offsets: &[f32], range: &Range<usize>) -> f32 {
+    let last = range.end - 1;
+    offsets[last] + tracks[last].base - offsets[range.start]
+}
+
+// Parses grid-template-rows or grid-template-columns, repeating auto-fill and auto-fit
+// tracks as many times as fit in the available size
+fn track_list(value: Option<&Value>, basis: Option<f32>, gap: f32) -> TrackList {
+    let mut list = TrackList {
+        names: vec![Vec::new()],
+        ..Default::default()
+    };
+    let Some(value) = value.filter(|value| !value.is_keyword("none")) else {
+        return list;
+    };
+    let items = value.items();
+    for item in items {
+        match item {
+            Value::LineNames(names) => list
+                .names
+                .last_mut()
+                .expect("Track list without lines")
+                .extend(names.iter().cloned()),
+            Value::Function(name, arguments) if name == "repeat" => {
+                let [count, tracks] = arguments.as_slice() else {
+                    continue;
+                };
+                let repeated = track_list(Some(tracks), basis, gap);
+                let (count, fit) = match count {
+                    Value::Number(count) => (count.max(1.0) as usize, false),
+                    Value::Keyword(keyword) if keyword == "auto-fill" => {
+                        (auto_repetitions(items, basis, gap), false)
+                    }
+                    Value::Keyword(keyword) if keyword == "auto-fit" => {
+                        (auto_repetitions(items, basis, gap), true)
+                    }
+                    _ => continue,
+                };
+                let start = list.tracks.len();
+                for _ in 0..count {
+                    list.append(&repeated);
+                }
+                if fit {
+                    list.auto_fit = Some(start..list.tracks.len());
+                }
+            }
+            value => {
+                if let Some(track) = track(value, basis) {
+                    list.tracks.push(track);
+                    list.names.push(Vec::new());
+                }
+            }
+        }
+    }
+    list
+}
+
+// How many times an auto-fill or auto-fit repeat fits alongside the other tracks, at least once
+fn auto_repetitions(items: &[Value], basis: Option<f32>, gap: f32) -> usize {
+    let Some(available) = basis else {
+        return 1;
+    };
+    // Tracks count at their fixed maximum or minimum size
+    let size = |track: &Track| match (track.max, track.min) {
+        (Sizing::Fixed(size), _) | (_, Sizing::Fixed(size)) => size,
+        _ => 0.0,
+    };
+    let (mut fixed, mut count) = (0.0, 0);
+    let (mut repeated, mut repeated_count) = (0.0, 0);
+    for item in items {
+        match item {
+            Value::Function(name, arguments) if name == "repeat" => {
+                let [times, tracks] = arguments.as_slice() else {
+                    continue;
+                };
+                let tracks = track_list(Some(tracks), basis, gap).tracks;
+                let total = tracks.iter().map(size).sum::<f32>();
+                match times {
+                    Value::Number(times) => {
+                        fixed += total * times.max(1.0).floor();
+                        count += tracks.len() * times.max(1.0) as usize;
+                    }
+                    _ => (repeated, repeated_count) = (total, tracks.len()),
+                }
+            }
+            value => {
+                if let Some(track) = track(value, basis) {
+                    fixed += size(&track);
+                    count += 1;
+                }
+            }
+        }
+    }
+    let step = repeated + gap * repeated_count as f32;
+    if repeated_count == 0 || step <= 0.0 {
+        return 1;
+    }
+    let space = available - fixed - gap * count as f32 + gap;
+    ((space / step).floor() as usize).max(1)
+}
+
+fn auto_tracks(value: Option<&Value>, basis: Option<f32>) -> Vec<Track> {
+    let tracks = track_list(value, basis, 0.0).tracks;
+    match tracks.is_empty() {
+        true => vec![Track::new(Sizing::Auto)],
+        false => tracks,
+    }
+}
+
+fn track(value: &Value, basis: Option<f32>) -> Option<Track> {
+    match value {
+        Value::Function(name, arguments) if name == "minmax" => {
+            let [min, max] = arguments.as_slice() else {
+                return None;
+            };
+            let min = match sizing(min, basis)? {
+                Sizing::Fr(_) | Sizing::FitContent(_) => return None,
+                min => min,
+            };
+            Some(Track {
+                min,
+                max: sizing(max, basis)?,
+            })
+        }
+        value => sizing(value, basis).map(Track::new),
+    }
+}
+
+// Percentages of an indefinite size behave as auto
+fn sizing(value: &Value, basis: Option<f32>) -> Option<Sizing> {
+    match value {
+        Value::Dimension(flex, unit) if unit == "fr" => Some(Sizing::Fr(flex.max(0.0))),
+        Value::Keyword(keyword) => match keyword.as_str() {
+            "auto" => Some(Sizing::Auto),
+            "min-content" => Some(Sizing::MinContent),
+            "max-content" => Some(Sizing::MaxContent),
+            _ => None,
+        },
+        Value::Function(name, arguments) if name == "fit-content" => {
+            let limit = arguments.first()?;
+            match basis {
+                Some(basis) => limit.to_px(basis).map(Sizing::FitContent),
+                None => limit
+                    .to_px(0.0)
+                    .filter(|_| limit.is_length_percentage())
+                    .map(Sizing::FitContent),
+            }
+        }
+        value if value.is_length_percentage() => Some(
+            match (value, basis) {
+                (Value::Percentage(_) | Value::Calc(..), None) => None,
+                (value, basis) => value.to_px(basis.unwrap_or(0.0)),
+            }
+            .map_or(Sizing::Auto, |size| Sizing::Fixed(size.max(0.0))),
+        ),
+        _ => None,
+    }
+}
+
+fn template_areas(value: Option<&Value>) -> Areas {
+    let mut areas = Areas::default();
+    let rows: Vec<Vec<String>> = value
+        .map(|value| {
+            value
+                .items()
+                .iter()
+                .filter_map(|row| match row {
+                    Value::Str(row) => Some(
+                        row.split_whitespace()
+                            .map(str::to_ascii_lowercase)
+                            .collect(),
+                    ),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    areas.rows = rows.len();
+    for (row, names) in rows.iter().enumerate() {
+        areas.columns = areas.columns.max(names.len());
+        for (column, name) in names.iter().enumerate() {
+            // A run of dots is an unnamed cell
+            if name.chars().all(|c| c == '.') {
+                continue;
+            }
+            let area = areas
+                .areas
+                .entry(name.clone())
+                .or_insert([row..row + 1, column..column + 1]);
+            area[0] = area[0].start.min(row)..area[0].end.max(row + 1);
+            area[1] = area[1].start.min(column)..area[1].end.max(column + 1);
+        }
+    }
+    areas
+}
+
+// The named lines of one axis
+struct AxisLines<'a> {
+    list: &'a TrackList,
+    areas: &'a Areas,
+    axis: usize,
+    explicit: usize,
+}
+
+impl AxisLines<'_> {
+    // The lines with a name, including the `<area>-start` and `<area>-end` lines of areas
+    fn named(&self, name: &str) -> Vec<i32> {
+        let mut lines: Vec<i32> = self
+            .list
+            .names
+            .iter()
+            .enumerate()
+            .filter(|(_, names)| names.iter().any(|line| line == name))
+            .map(|(line, _)| line as i32)
+            .collect();
+        if let Some(area) = name
+            .strip_suffix("-start")
+            .and_then(|area| self.areas.areas.get(area))
+        {
+            lines.push(area[self.axis].start as i32);
+        }
+        if let Some(area) = name
+            .strip_suffix("-end")
+            .and_then(|area| self.areas.areas.get(area))
+        {
+            lines.push(area[self.axis].end as i32);
+        }
+        lines.sort();
+        lines.dedup();
+        lines
+    }
+}
+
+// Parses a grid-row-start style placement. Names that match no line are treated as auto.
+fn grid_line(value: Option<&Value>, side: &str, lines: &AxisLines) -> GridLine {
+    let items = value.map(Value::items).unwrap_or_default();
+    let integer = items.iter().find_map(Value::as_number).map(|n| n as i32);
+    let span = items.iter().any(|item| item.is_keyword("span"));
+    let name = items
+        .iter()
+        .filter_map(Value::as_keyword)
+        .find(|keyword| !matches!(*keyword, "span" | "auto"));
+    match (span, integer, name) {
+        (true, integer, _) => GridLine::Span(integer.unwrap_or(1).max(1)),
+        (false, Some(0), _) => GridLine::Auto,
+        (false, Some(n), None) => GridLine::Line(match n > 0 {
+            true => n - 1,
+            false => lines.explicit as i32 + 1 + n,
+        }),
+        (false, n, Some(name)) => {
+            // A lone area name refers to the area's start or end line
+            let named = match lines.named(&format!("{}-{}", name, side)) {
+                own if n.is_none() && !own.is_empty() => own,
+                _ => lines.named(name),
+            };
+            let n = n.unwrap_or(1);
+            let line = match n > 0 {
+                true => named.get(n as usize - 1),
+                false => named
+                    .len()
+                    .checked_sub(n.unsigned_abs() as usize)
+                    .and_then(|i| named.get(i)),
+            };
+            line.map_or(GridLine::Auto, |line| GridLine::Line(*line))
+        }
+        _ => GridLine::Auto,
+    }
+}
+
+// The start line and span of an item along an axis
+fn resolve_placement(start: GridLine, end: GridLine) -> (Option<i32>, i32) {
+    match (start, end) {
+        (GridLine::Line(start), GridLine::Line(end)) if start == end => (Some(start), 1),
+        (GridLine::Line(start), GridLine::Line(end)) => (Some(start.min(end)), (end - start).abs()),
+        (GridLine::Line(start), GridLine::Span(span)) => (Some(start), span),
+        (GridLine::Line(start), GridLine::Auto) => (Some(start), 1),
+        (GridLine::Span(span), GridLine::Line(end)) => (Some(end - span), span),
+        (GridLine::Auto, GridLine::Line(end)) => (Some(end - 1), 1),
+        (GridLine::Span(span), _) | (GridLine::Auto, GridLine::Span(span)) => (None, span),
+        (GridLine::Auto, GridLine::Auto) => (None, 1),
+    }
+}
+
+// The min-content and max-content widths of a grid container's content
+pub(super) fn intrinsic_widths(layout_box: &LayoutBox, ctx: &LayoutContext) -> (f32, f32) {
+    let gap = flex::gap(&layout_box.style, "column-gap", 0.0);
+    let grid = build_grid(layout_box, [None, None], [0.0, gap]);
+    let contributions: Vec<Contribution> = grid
+        .items
+        .iter()
+        .map(|item| {
+            let (min, max) = block::intrinsic_outer_widths(item.layout_box, ctx);
+            (item.area[1].clone(), min, max)
+        })
+        .collect();
+    let [min, max] = [Available::MinContent, Available::MaxContent].map(|available| {
+        let mut tracks = grid.tracks[1].clone();
+        size_tracks(&mut tracks, &contributions, available, gap, false);
+        track_offsets(&tracks, gap, None, None).1
+    });
+    (min, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::layout::testing::{layout, rect, Rect};
+
+    // The border boxes of the first element's children
+    fn item_rects(body: &str) -> Vec<Rect> {
+        layout(body).children[0].children[0]
+            .children
+            .iter()
+            .map(rect)
+            .collect()
+    }
+
+    #[test]
+    fn test_grid_fixtures() {
+        // Each fixture is a grid container in a 400px wide body and the expected rects of
+        // its items. Characters are 8px wide and lines 20px tall.
+        let fixtures: &[(&str, &[Rect])] = &[
+            // Fixed and flexible columns, with items stretched to their areas
+            (
+                "<div style='display: grid; grid-template-columns: 100px 1fr 2fr'>\
+                 <div>a</div><div>b</div><div>c</div></div>",
+                &[
+                    (0.0, 0.0, 100.0, 20.0),
+                    (100.0, 0.0, 100.0, 20.0),
+                    (200.0, 0.0, 200.0, 20.0),
+                ],
+            ),
+            // Repeated tracks and gaps, wrapping into an implicit row
+            (
+                "<div style='display: grid; grid-template-columns: repeat(3, 1fr); gap: 20px'>\
+                 <div>a</div><div>b</div><div>c</div><div>d</div></div>",
+                &[
+                    (0.0, 0.0, 120.0, 20.0),
+                    (140.0, 0.0, 120.0, 20.0),
+                    (280.0, 0.0, 120.0, 20.0),
+                    (0.0, 40.0, 120.0, 20.0),
+                ],
+            ),
+            // auto-fill repeats as many tracks as fit
+            (
+                "<div style='display: grid; grid-template-columns: repeat(auto-fill, 120px)'>\
+                 <div>a</div><div>b</div><div>c</div><div>d</div></div>",
+                &[
+                    (0.0, 0.0, 120.0, 20.0),
+                    (120.0, 0.0, 120.0, 20.0),
+                    (240.0, 0.0, 120.0, 20.0),
+                    (0.0, 20.0, 120.0, 20.0),
+                ],
+            ),
+            // auto-fit collapses the empty repetitions, so the flexible tracks take the space
+            (
+                "<div style='display: grid; \
+                 grid-template-columns: repeat(auto-fit, minmax(100px, 1fr))'>\
+                 <div>a</div><div>b</div></div>",
+                &[(0.0, 0.0, 200.0, 20.0), (200.0, 0.0, 200.0, 20.0)],
+            ),
+            // Auto tracks fit their content and fr tracks take the rest
+            (
+                "<div style='display: grid; grid-template-columns: auto 1fr'>\
+                 <div>aaaa</div><div>b</div></div>",
+                &[(0.0, 0.0, 32.0, 20.0), (32.0, 0.0, 368.0, 20.0)],
+            ),
+            // fit-content() caps the track, wrapping its text
+            (
+                "<div style='display: grid; grid-template-columns: fit-content(50px) 1fr'>\
+                 <div>aa aa aa</div><div>b</div></div>",
+                &[(0.0, 0.0, 50.0, 40.0), (50.0, 0.0, 350.0, 40.0)],
+            ),
+            // Named areas
+            (
+                "<div style='display: grid; grid-template-areas: \"head head\" \"side main\"; \
+                 grid-template-columns: 100px 1fr; grid-template-rows: 30px 50px'>\
+                 <div style='grid-area: main'>a</div><div style='grid-area: head'>b</div>\
+                 <div style='grid-area: side'>c</div></div>",
+                &[
+                    (100.0, 30.0, 300.0, 50.0),
+                    (0.0, 0.0, 400.0, 30.0),
+                    (0.0, 30.0, 100.0, 50.0),
+                ],
+            ),
+            // Named lines, spans and negative lines; an earlier column moves to the next row
+            (
+                "<div style='display: grid; grid-template-columns: [a] 100px [b] 100px [c] 100px'>\
+                 <div style='grid-column: b / c'>a</div>\
+                 <div style='grid-column: a / span 2'>b</div>\
+                 <div style='grid-column: 1 / -1'>c</div></div>",
+                &[
+                    (100.0, 0.0, 100.0, 20.0),
+                    (0.0, 20.0, 200.0, 20.0),
+                    (0.0, 40.0, 300.0, 20.0),
+                ],
+            ),
+            // Explicit rows with auto-placed items filling the gaps around them
+            (
+                "<div style='display: grid; grid-template-columns: 100px 100px'>\
+                 <div>a</div><div style='grid-row: 1; grid-column: 1'>b</div><div>c</div></div>",
+                &[
+                    (100.0, 0.0, 100.0, 20.0),
+                    (0.0, 0.0, 100.0, 20.0),
+                    (0.0, 20.0, 100.0, 20.0),
+                ],
+            ),
+            // Sparse placement never backtracks
+            (
+                "<div style='display: grid; grid-template-columns: repeat(3, 100px)'>\
+                 <div style='grid-column: span 2'>a</div><div style='grid-column: span 2'>b</div>\
+                 <div>c</div></div>",
+                &[
+                    (0.0, 0.0, 200.0, 20.0),
+                    (0.0, 20.0, 200.0, 20.0),
+                    (200.0, 20.0, 100.0, 20.0),
+                ],
+            ),
+            // Dense placement fills earlier holes
+            (
+                "<div style='display: grid; grid-template-columns: repeat(3, 100px); \
+                 grid-auto-flow: row dense'>\
+                 <div style='grid-column: span 2'>a</div><div style='grid-column: span 2'>b</div>\
+                 <div>c</div></div>",
+                &[
+                    (0.0, 0.0, 200.0, 20.0),
+                    (0.0, 20.0, 200.0, 20.0),
+                    (200.0, 0.0, 100.0, 20.0),
+                ],
+            ),
+            // Column flow with implicit columns
+            (
+                "<div style='display: grid; grid-auto-flow: column; \
+                 grid-template-rows: 20px 20px; grid-auto-columns: 50px'>\
+                 <div>a</div><div>b</div><div>c</div></div>",
+                &[
+                    (0.0, 0.0, 50.0, 20.0),
+                    (0.0, 20.0, 50.0, 20.0),
+                    (50.0, 0.0, 50.0, 20.0),
+                ],
+            ),
+            // Items aligned within their areas
+            (
+                "<div style='display: grid; grid-template-columns: 100px 100px; \
+                 grid-auto-rows: 40px; justify-items: center'>\
+                 <div>ab</div><div style='align-self: end; justify-self: stretch'>c</div></div>",
+                &[(42.0, 0.0, 16.0, 40.0), (100.0, 20.0, 100.0, 20.0)],
+            ),
+            // Content distribution when the tracks don't fill the container
+            (
+                "<div style='display: grid; grid-template-columns: 100px 100px; \
+                 justify-content: space-between'><div>a</div><div>b</div></div>",
+                &[(0.0, 0.0, 100.0, 20.0), (300.0, 0.0, 100.0, 20.0)],
+            ),
+        ];
+        for (source, expected) in fixtures {
+            assert_eq!(item_rects(source), *expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_grid_container_sizes() {
+        // Rows, gaps and a fixed row height add up to the container's height
+        let body = layout(
+            "<div style='display: grid; grid-template-rows: 30px; row-gap: 10px'>\
+             <div>a</div><div>b</div></div>",
+        );
+        assert_eq!(body.children[0].rect.height(), 60.0);
+        // An inline grid shrinks to its max-content width
+        let body = layout(
+            "<div style='display: inline-grid; grid-template-columns: 50px 1fr'>\
+             <div>a</div><div>aaaa</div></div>",
+        );
+        let grid = &body.children[0].children[0].children[0];
+        assert_eq!(grid.rect.width(), 82.0);
+    }
+}
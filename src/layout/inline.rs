@@ -0,0 +1,1074 @@
+// Inline formatting: collapsing white space and breaking inline content into line boxes
+
+use crate::css::{ComputedStyle, Value};
+use crate::layout::block::{self, BlockLayout, ContainingBlock};
+use crate::layout::box_tree::{BoxKind, LayoutBox};
+use crate::layout::float::{self, FloatArea};
+use crate::layout::fragment::{Edges, Fragment, FragmentKind, TextRun};
+use crate::layout::{positioned, LayoutContext};
+use eframe::egui::{self, FontId, Rect};
+use std::ops::Range;
+use std::sync::Arc;
+use unicode_linebreak::{linebreaks, BreakOpportunity};
+
+// Stands in for atomic inlines in the text searched for break opportunities
+const OBJECT_REPLACEMENT: char = '\u{FFFC}';
+
+/// Line boxes and the floats among them positioned relative to the content box of their
+/// block container
+pub(super) struct InlineLayout {
+    pub lines: Vec<Fragment>,
+    // Floats, and placeholders of absolutely positioned boxes that aren't in a line
+    pub out_of_flow: Vec<Fragment>,
+    pub height: f32,
+    // Offset of the last line's baseline from the top of the content box
+    pub baseline: Option<f32>,
+}
+
+enum ItemKind {
+    Text {
+        text: String,
+        // Byte offset in the DOM text node of each byte of the text, and of its end
+        offsets: Vec<usize>,
+        font: FontId,
+    },
+    // The start and end of an inline box
+    Start,
+    End,
+    Atomic {
+        layout: Option<BlockLayout>,
+        min_width: f32,
+        max_width: f32,
+    },
+    Break,
+    // A float, which takes no space in the line it's found in
+    Float {
+        layout: Option<BlockLayout>,
+        min_width: f32,
+        max_width: f32,
+    },
+    // An absolutely positioned box, which only marks its static position in the line
+    Absolute,
+}
+
+// Inline content flattened into a sequence, with text after white space processing
+struct Item<'a> {
+    layout_box: &'a LayoutBox,
+    kind: ItemKind,
+    // Byte range of the item in the text of the whole inline formatting context
+    range: Range<usize>,
+}
+
+struct ItemCollector<'a, 'c> {
+    ctx: &'c LayoutContext<'c>,
+    // Atomic inlines are laid out when the width is known, and measured otherwise
+    containing: Option<ContainingBlock>,
+    items: Vec<Item<'a>>,
+    content: String,
+    // Whether a collapsible space would be dropped here, at the start of a line or after
+    // another space
+    after_space: bool,
+}
+
+impl<'a> ItemCollector<'a, '_> {
+    fn push(&mut self, layout_box: &'a LayoutBox, kind: ItemKind, text: &str) {
+        let start = self.content.len();
+        self.content.push_str(text);
+        self.items.push(Item {
+            layout_box,
+            kind,
+            range: start..self.content.len(),
+        });
+    }
+
+    fn add(&mut self, layout_box: &'a LayoutBox) {
+        match &layout_box.kind {
+            BoxKind::Text(text) => self.add_text(layout_box, text),
+            BoxKind::Inline => {
+                self.push(layout_box, ItemKind::Start, "");
+                for child in &layout_box.children {
+                    self.add(child);
+                }
+                self.push(layout_box, ItemKind::End, "");
+            }
+            BoxKind::LineBreak => {
+                self.push(layout_box, ItemKind::Break, "\n");
+                self.after_space = true;
+            }
+            BoxKind::Container(_) | BoxKind::Replaced(_) | BoxKind::TablePart(_)
+                if layout_box.is_absolutely_positioned() =>
+            {
+                self.push(layout_box, ItemKind::Absolute, "");
+            }
+            BoxKind::Container(_) | BoxKind::Replaced(_) | BoxKind::TablePart(_)
+                if layout_box.is_floated() =>
+            {
+                let (layout, min_width, max_width) = self.measure(layout_box);
+                let kind = ItemKind::Float {
+                    layout,
+                    min_width,
+                    max_width,
+                };
+                self.push(layout_box, kind, "");
+            }
+            BoxKind::Container(_) | BoxKind::Replaced(_) | BoxKind::TablePart(_) => {
+                let (layout, min_width, max_width) = self.measure(layout_box);
+                let kind = ItemKind::Atomic {
+                    layout,
+                    min_width,
+                    max_width,
+                };
+                self.push(layout_box, kind, &OBJECT_REPLACEMENT.to_string());
+                self.after_space = false;
+            }
+        }
+    }
+
+    // Lays out a box that sits in or beside the lines as a whole, returning its layout
+    // when the width is known, and its min-content and max-content widths
+    fn measure(&self, layout_box: &LayoutBox) -> (Option<BlockLayout>, f32, f32) {
+        match self.containing {
+            Some(containing) => {
+                let layout = block::layout_atomic_inline(layout_box, containing, self.ctx);
+                let width = layout.fragment.rect.width() + layout.margin.horizontal();
+                (Some(layout), width, width)
+            }
+            None => {
+                let (min, max) = block::intrinsic_outer_widths(layout_box, self.ctx);
+                (None, min, max)
+            }
+        }
+    }
+
+    // Collapses white space and applies text-transform, keeping track of where each byte
+    // came from
+    fn add_text(&mut self, layout_box: &'a LayoutBox, text: &str) {
+        let style = &layout_box.style;
+        let white_space = style.keyword("white-space").unwrap_or("normal");
+        let collapse = collapses_spaces(style);
+        let keep_newlines = !matches!(white_space, "normal" | "nowrap");
+        let transform = style.keyword("text-transform").unwrap_or("none");
+        let mut word_start = self.content.is_empty() || self.content.ends_with([' ', '\n']);
+
+        let mut processed = String::new();
+        let mut offsets = Vec::new();
+        fn push(processed: &mut String, offsets: &mut Vec<usize>, c: char, offset: usize) {
+            processed.push(c);
+            offsets.resize(processed.len(), offset);
+        }
+        for (offset, c) in text.char_indices() {
+            match c {
+                '\n' if keep_newlines => {
+                    // pre-line drops the spaces around preserved newlines
+                    if collapse {
+                        let trimmed = processed.trim_end_matches(' ').len();
+                        processed.truncate(trimmed);
+                        offsets.truncate(trimmed);
+                    }
+                    push(&mut processed, &mut offsets, '\n', offset);
+                    self.after_space = true;
+                    word_start = true;
+                }
+                ' ' | '\t' | '\n' | '\r' | '\x0c' if collapse => {
+                    if !self.after_space {
+                        push(&mut processed, &mut offsets, ' ', offset);
+                        self.after_space = true;
+                    }
+                    word_start = true;
+                }
+                '\r' => {}
+                '\t' => {
+                    for _ in 0..8 {
+                        push(&mut processed, &mut offsets, ' ', offset);
+                    }
+                    self.after_space = false;
+                }
+                c => {
+                    let transformed: Vec<char> = match transform {
+                        "uppercase" => c.to_uppercase().collect(),
+                        "lowercase" => c.to_lowercase().collect(),
+                        "capitalize" if word_start => c.to_uppercase().collect(),
+                        _ => vec![c],
+                    };
+                    for c in transformed {
+                        push(&mut processed, &mut offsets, c, offset);
+                    }
+                    self.after_space = false;
+                    word_start = c == ' ';
+                }
+            }
+        }
+        offsets.push(text.len());
+        let kind = ItemKind::Text {
+            text: processed.clone(),
+            offsets,
+            font: self.ctx.font(style),
+        };
+        self.push(layout_box, kind, &processed);
+    }
+}
+
+fn collect_items<'a>(
+    children: &'a [LayoutBox],
+    containing: Option<ContainingBlock>,
+    ctx: &LayoutContext,
+) -> (Vec<Item<'a>>, String) {
+    let mut collector = ItemCollector {
+        ctx,
+        containing,
+        items: Vec::new(),
+        content: String::new(),
+        after_space: true,
+    };
+    for child in children {
+        collector.add(child);
+    }
+    (collector.items, collector.content)
+}
+
+fn collapses_spaces(style: &ComputedStyle) -> bool {
+    matches!(
+        style.keyword("white-space"),
+        None | Some("normal" | "nowrap" | "pre-line")
+    )
+}
+
+fn allows_wrapping(style: &ComputedStyle) -> bool {
+    !matches!(style.keyword("white-space"), Some("nowrap" | "pre"))
+}
+
+// Spaces at the end of a line that don't count towards its width
+fn hangs_spaces(style: &ComputedStyle) -> bool {
+    collapses_spaces(style) || style.keyword("white-space") == Some("pre-wrap")
+}
+
+// The horizontal space an inline box takes before and after its content
+struct InlineEdges {
+    margin: Edges,
+    border: Edges,
+    padding: Edges,
+}
+
+impl InlineEdges {
+    fn of(style: &ComputedStyle, basis: f32) -> Self {
+        Self {
+            margin: block::margin_edges(style, basis),
+            border: block::border(style),
+            padding: block::padding(style, basis),
+        }
+    }
+
+    fn start(&self) -> f32 {
+        self.margin.left + self.border.left + self.padding.left
+    }
+
+    fn end(&self) -> f32 {
+        self.padding.right + self.border.right + self.margin.right
+    }
+}
+
+// Part of an item in a segment
+struct Piece {
+    item: usize,
+    range: Range<usize>,
+}
+
+// The content between two break opportunities
+struct Segment {
+    pieces: Vec<Piece>,
+    min_width: f32,
+    max_width: f32,
+    // The width of trailing spaces, which hang past the end of a line
+    hanging: f32,
+    // Whether the line must break after the segment
+    mandatory: bool,
+}
+
+fn segments(items: &[Item], content: &str, basis: f32, ctx: &LayoutContext) -> Vec<Segment> {
+    // The white-space of the content before an opportunity decides whether it's a break
+    let wraps_before = |position: usize| {
+        items
+            .iter()
+            .find(|item| item.range.start < position && position <= item.range.end)
+            .is_none_or(|item| allows_wrapping(&item.layout_box.style))
+    };
+    let mut breaks: Vec<(usize, bool)> = linebreaks(content)
+        .filter_map(|(position, opportunity)| match opportunity {
+            BreakOpportunity::Mandatory => Some((position, position < content.len())),
+            BreakOpportunity::Allowed if wraps_before(position) => Some((position, false)),
+            BreakOpportunity::Allowed => None,
+        })
+        .collect();
+    if breaks.last().map(|(position, _)| *position) != Some(content.len()) {
+        breaks.push((content.len(), false));
+    }
+
+    let mut segments = Vec::with_capacity(breaks.len());
+    let mut next_item = 0;
+    let mut start = 0;
+    let last_break = breaks.len() - 1;
+    for (index, (end, mandatory)) in breaks.into_iter().enumerate() {
+        let is_last = index == last_break;
+        let mut pieces = Vec::new();
+        while let Some(item) = items.get(next_item) {
+            let belongs = match item.kind {
+                // Boxes that start at a break start on the next line, those that end at one
+                // end on this line
+                ItemKind::Start => item.range.start < end || is_last,
+                ItemKind::End => item.range.start <= end,
+                _ if item.range.is_empty() => item.range.start <= end,
+                _ => item.range.start < end || is_last,
+            };
+            if !belongs {
+                break;
+            }
+            let range = item.range.start.max(start)..item.range.end.min(end);
+            if matches!(
+                item.kind,
+                ItemKind::Start | ItemKind::End | ItemKind::Float { .. } | ItemKind::Absolute
+            ) || !range.is_empty()
+            {
+                pieces.push(Piece {
+                    item: next_item,
+                    range,
+                });
+            }
+            if item.range.end > end {
+                break;
+            }
+            next_item += 1;
+        }
+        segments.push(measure_segment(items, pieces, mandatory, basis, ctx));
+        start = end;
+    }
+    segments
+}
+
+fn measure_segment(
+    items: &[Item],
+    pieces: Vec<Piece>,
+    mandatory: bool,
+    basis: f32,
+    ctx: &LayoutContext,
+) -> Segment {
+    let mut min_width = 0.0;
+    let mut max_width = 0.0;
+    for piece in &pieces {
+        let item = &items[piece.item];
+        let (min, max) = match &item.kind {
+            ItemKind::Text { font, .. } => {
+                let text = piece_text(item, &piece.range).replace('\n', "");
+                let width = ctx.text_width(&text, font);
+                (width, width)
+            }
+            ItemKind::Start => {
+                let width = InlineEdges::of(&item.layout_box.style, basis).start();
+                (width, width)
+            }
+            ItemKind::End => {
+                let width = InlineEdges::of(&item.layout_box.style, basis).end();
+                (width, width)
+            }
+            ItemKind::Atomic {
+                min_width,
+                max_width,
+                ..
+            } => (*min_width, *max_width),
+            ItemKind::Break | ItemKind::Float { .. } | ItemKind::Absolute => (0.0, 0.0),
+        };
+        min_width += min;
+        max_width += max;
+    }
+    let hanging = trailing_text(items, &pieces)
+        .map(|index| {
+            let piece = &pieces[index];
+            let item = &items[piece.item];
+            let ItemKind::Text { font, .. } = &item.kind else {
+                return 0.0;
+            };
+            let text = piece_text(item, &piece.range);
+            match hangs_spaces(&item.layout_box.style) && text.ends_with(' ') {
+                true => {
+                    ctx.text_width(text, font) - ctx.text_width(text.trim_end_matches(' '), font)
+                }
+                false => 0.0,
+            }
+        })
+        .unwrap_or(0.0);
+    Segment {
+        pieces,
+        min_width,
+        max_width,
+        hanging,
+        mandatory,
+    }
+}
+
+// The index of the last text piece, if only inline box ends follow it
+fn trailing_text(items: &[Item], pieces: &[Piece]) -> Option<usize> {
+    let index = pieces.iter().rposition(|piece| {
+        !matches!(
+            items[piece.item].kind,
+            ItemKind::End | ItemKind::Float { .. } | ItemKind::Absolute
+        )
+    })?;
+    match items[pieces[index].item].kind {
+        ItemKind::Text { .. } => Some(index),
+        _ => None,
+    }
+}
+
+fn piece_text<'t>(item: &'t Item, range: &Range<usize>) -> &'t str {
+    match &item.kind {
+        ItemKind::Text { text, .. } => {
+            &text[range.start - item.range.start..range.end - item.range.start]
+        }
+        _ => "",
+    }
+}
+
+// The min-content and max-content widths of inline content
+pub(super) fn intrinsic_widths(children: &[LayoutBox], ctx: &LayoutContext) -> (f32, f32) {
+    let (items, content) = collect_items(children, None, ctx);
+    // Floats sit beside the lines
+    let (mut min, floats) = items
+        .iter()
+        .filter_map(|item| match item.kind {
+            ItemKind::Float {
+                min_width,
+                max_width,
+                ..
+            } => Some((min_width, max_width)),
+            _ => None,
+        })
+        .fold(
+            (0.0, 0.0),
+            |(min, max): (f32, f32), (float_min, float_max)| (min.max(float_min), max + float_max),
+        );
+    let mut max: f32 = 0.0;
+    let mut line = 0.0;
+    for segment in segments(&items, &content, 0.0, ctx) {
+        min = min.max(segment.min_width - segment.hanging);
+        line += segment.max_width;
+        max = max.max(line - segment.hanging);
+        if segment.mandatory {
+            line = 0.0;
+        }
+    }
+    (min, max + floats)
+}
+
+// Breaks inline content into lines that fit the width of the block container, flowing
+// around the floats of its formatting context
+pub(super) fn layout_inline_content(
+    children: &[LayoutBox],
+    style: &Arc<ComputedStyle>,
+    width: f32,
+    floats: &mut FloatArea,
+    ctx: &LayoutContext,
+) -> InlineLayout {
+    let containing = ContainingBlock {
+        width,
+        height: None,
+    };
+    let (mut items, content) = collect_items(children, Some(containing), ctx);
+    let (ascent, descent) = line_metrics(style, ctx);
+    let mut lines = Lines {
+        style,
+        indent: style.length("text-indent", width).unwrap_or(0.0),
+        strut: ascent + descent,
+        ctx,
+        layout: InlineLayout {
+            lines: Vec::new(),
+            out_of_flow: Vec::new(),
+            height: 0.0,
+            baseline: None,
+        },
+        count: 0,
+        open: Vec::new(),
+        deferred: Vec::new(),
+    };
+
+    // Fill each line with as many segments as fit beside the floats
+    let mut current: Vec<Piece> = Vec::new();
+    let mut current_width = 0.0;
+    for segment in segments(&items, &content, width, ctx) {
+        let (float_pieces, pieces): (Vec<Piece>, Vec<Piece>) = segment
+            .pieces
+            .into_iter()
+            .partition(|piece| matches!(items[piece.item].kind, ItemKind::Float { .. }));
+        let needed = segment.max_width - segment.hanging;
+        if !pieces.is_empty() {
+            if !current.is_empty() && current_width + needed > lines.available(floats) + 0.01 {
+                lines.finish(&mut items, &std::mem::take(&mut current), floats);
+                current_width = 0.0;
+            }
+            // A line too narrow for its first segment moves down past the floats beside it
+            while current.is_empty() && needed > lines.available(floats) + 0.01 {
+                match floats.next_bottom(lines.layout.height, lines.strut) {
+                    Some(bottom) => lines.layout.height = bottom,
+                    None => break,
+                }
+            }
+            current_width += segment.max_width;
+            // Pieces of the same text that end up on one line become one run
+            for piece in pieces {
+                match current.last_mut() {
+                    Some(last)
+                        if last.item == piece.item && last.range.end == piece.range.start =>
+                    {
+                        last.range.end = piece.range.end
+                    }
+                    _ => current.push(piece),
+                }
+            }
+        }
+        // A float goes beside the current line if it fits there, and below it otherwise
+        for piece in float_pieces {
+            let ItemKind::Float { layout, .. } = &mut items[piece.item].kind else {
+                continue;
+            };
+            let Some(layout) = layout.take() else {
+                continue;
+            };
+            let float_width = layout.fragment.rect.width() + layout.margin.horizontal();
+            let fits = current_width + float_width <= lines.available(floats) + 0.01;
+            match fits && lines.deferred.is_empty() {
+                true => {
+                    let layout_box = items[piece.item].layout_box;
+                    let top = lines.layout.height;
+                    let fragment = float::place_float(layout_box, layout, floats, top);
+                    lines.layout.out_of_flow.push(fragment);
+                }
+                false => lines.deferred.push((piece.item, layout)),
+            }
+        }
+        if segment.mandatory {
+            lines.finish(&mut items, &std::mem::take(&mut current), floats);
+            current_width = 0.0;
+        }
+    }
+    lines.finish(&mut items, &current, floats);
+    lines.layout
+}
+
+// The lines laid out so far, and the floats waiting for the current line to end
+struct Lines<'s, 'c> {
+    style: &'s Arc<ComputedStyle>,
+    indent: f32,
+    // The height of an empty line, used to find the floats beside a line before its
+    // content is known
+    strut: f32,
+    ctx: &'c LayoutContext<'c>,
+    layout: InlineLayout,
+    count: usize,
+    open: Vec<usize>,
+    deferred: Vec<(usize, BlockLayout)>,
+}
+
+impl Lines<'_, '_> {
+    // The width of the next line beside the floats
+    fn available(&self, floats: &FloatArea) -> f32 {
+        let (left, right) = floats.band(self.layout.height, self.strut);
+        let indent = match self.count {
+            0 => self.indent,
+            _ => 0.0,
+        };
+        right - left - indent
+    }
+
+    // Builds a line from its pieces, then places the floats that didn't fit beside it
+    fn finish(&mut self, items: &mut [Item], pieces: &[Piece], floats: &mut FloatArea) {
+        if !pieces.is_empty() {
+            let (left, right) = floats.band(self.layout.height, self.strut);
+            let line = LineBuilder {
+                style: self.style,
+                width: right - left,
+                indent: match self.count {
+                    0 => self.indent,
+                    _ => 0.0,
+                },
+                ctx: self.ctx,
+            };
+            self.count += 1;
+            let top = self.layout.height;
+            match line.build(items, pieces, &mut self.open) {
+                Some((mut fragment, baseline)) => {
+                    fragment.rect = fragment.rect.translate(egui::vec2(left, top));
+                    self.layout.baseline = Some(top + baseline);
+                    self.layout.height += fragment.rect.height();
+                    self.layout.lines.push(fragment);
+                }
+                // Absolutely positioned boxes still need a static position without a line
+                None => {
+                    let placeholders = pieces
+                        .iter()
+                        .map(|piece| &items[piece.item])
+                        .filter(|item| matches!(item.kind, ItemKind::Absolute))
+                        .map(|item| {
+                            positioned::placeholder(item.layout_box, egui::pos2(left, top))
+                        });
+                    self.layout.out_of_flow.extend(placeholders);
+                }
+            }
+        }
+        for (item, layout) in std::mem::take(&mut self.deferred) {
+            let top = self.layout.height;
+            let fragment = float::place_float(items[item].layout_box, layout, floats, top);
+            self.layout.out_of_flow.push(fragment);
+        }
+    }
+}
+
+// The ascent and descent of a style's line box contribution, with half the leading
+// added to each
+fn line_metrics(style: &ComputedStyle, ctx: &LayoutContext) -> (f32, f32) {
+    let metrics = ctx.metrics(&ctx.font(style));
+    let font_size = style.font_size();
+    let line_height = match style.get("line-height") {
+        Some(Value::Number(factor)) => factor * font_size,
+        Some(Value::Percentage(percent)) => percent / 100.0 * font_size,
+        Some(value) => value.to_px(font_size).unwrap_or(metrics.height),
+        None => metrics.height,
+    };
+    let half_leading = (line_height - metrics.height) / 2.0;
+    (
+        metrics.ascent + half_leading,
+        metrics.descent() + half_leading,
+    )
+}
+
+struct LineBuilder<'s, 'c> {
+    style: &'s Arc<ComputedStyle>,
+    width: f32,
+    indent: f32,
+    ctx: &'c LayoutContext<'c>,
+}
+
+impl LineBuilder<'_, '_> {
+    // Builds a line box from its pieces, with `open` holding the inline boxes that
+    // continue from the previous line. Returns the line and its baseline, or None for a
+    // line without content, which takes no space.
+    fn build(
+        &self,
+        items: &mut [Item],
+        pieces: &[Piece],
+        open: &mut Vec<usize>,
+    ) -> Option<(Fragment, f32)> {
+        let continued = open.clone();
+        for piece in pieces {
+            match items[piece.item].kind {
+                ItemKind::Start => open.push(piece.item),
+                ItemKind::End => {
+                    open.pop();
+                }
+                _ => {}
+            }
+        }
+
+        let has_content = pieces.iter().any(|piece| {
+            let item = &items[piece.item];
+            match &item.kind {
+                ItemKind::Text { .. } => {
+                    let text = piece_text(item, &piece.range);
+                    text.contains(|c| c != ' ')
+                        || (!collapses_spaces(&item.layout_box.style) && !text.is_empty())
+                }
+                ItemKind::Start | ItemKind::End => {
+                    let edges = InlineEdges::of(&item.layout_box.style, self.width);
+                    edges.start() != 0.0 || edges.end() != 0.0
+                }
+                ItemKind::Atomic { .. } | ItemKind::Break => true,
+                ItemKind::Float { .. } | ItemKind::Absolute => false,
+            }
+        });
+        if !has_content {
+            return None;
+        }
+
+        // The line is as tall as the strut of its container and everything on it
+        let (mut ascent, mut descent) = line_metrics(self.style, self.ctx);
+        let mut include = |(item_ascent, item_descent): (f32, f32)| {
+            ascent = f32::max(ascent, item_ascent);
+            descent = f32::max(descent, item_descent);
+        };
+        for &item in &continued {
+            include(line_metrics(&items[item].layout_box.style, self.ctx));
+        }
+        for piece in pieces {
+            let item = &items[piece.item];
+            match &item.kind {
+                ItemKind::Text { .. } | ItemKind::Start => {
+                    include(line_metrics(&item.layout_box.style, self.ctx))
+                }
+                ItemKind::Atomic {
+                    layout: Some(layout),
+                    ..
+                } => {
+                    let height = layout.fragment.rect.height() + layout.margin.vertical();
+                    let baseline = atomic_baseline(layout);
+                    include((baseline, height - baseline));
+                }
+                _ => {}
+            }
+        }
+        let baseline = ascent;
+
+        // Spaces at the end of the line are removed
+        let trim = trailing_text(items, pieces)
+            .filter(|index| hangs_spaces(&items[pieces[*index].item].layout_box.style));
+
+        let mut children = Vec::new();
+        let mut stack: Vec<Fragment> = Vec::new();
+        let mut x = self.indent;
+        for &item in &continued {
+            let fragment = self.inline_fragment(items[item].layout_box, x, baseline, false);
+            stack.push(fragment);
+        }
+        for (index, piece) in pieces.iter().enumerate() {
+            let Item {
+                layout_box,
+                kind,
+                range,
+            } = &mut items[piece.item];
+            match kind {
+                ItemKind::Start => {
+                    x += block::margin_edges(&layout_box.style, self.width).left;
+                    let fragment = self.inline_fragment(layout_box, x, baseline, true);
+                    x += fragment.border.left + fragment.padding.left;
+                    stack.push(fragment);
+                }
+                ItemKind::End => {
+                    let Some(mut fragment) = stack.pop() else {
+                        continue;
+                    };
+                    let edges = InlineEdges::of(&layout_box.style, self.width);
+                    x += edges.padding.right + edges.border.right;
+                    fragment.rect.max.x = x;
+                    fragment.border.right = edges.border.right;
+                    fragment.padding.right = edges.padding.right;
+                    fragment.rect = fragment
+                        .rect
+                        .translate(self.relative_offset(&layout_box.style));
+                    attach(&mut stack, &mut children, fragment);
+                    x += edges.margin.right;
+                }
+                ItemKind::Text {
+                    text,
+                    offsets,
+                    font,
+                } => {
+                    let start = piece.range.start - range.start;
+                    let mut end = piece.range.end - range.start;
+                    if trim == Some(index) {
+                        end = start + text[start..end].trim_end_matches(' ').len();
+                    }
+                    let visible = text[start..end].replace('\n', "");
+                    if visible.is_empty() {
+                        continue;
+                    }
+                    let mut run_offsets: Vec<usize> = (start..end)
+                        .filter(|&index| text.as_bytes()[index] != b'\n')
+                        .map(|index| offsets[index])
+                        .collect();
+                    run_offsets.push(offsets[end]);
+                    let width = self.ctx.text_width(&visible, font);
+                    let metrics = self.ctx.metrics(font);
+                    let run = TextRun {
+                        text: visible,
+                        font: font.clone(),
+                        ascent: metrics.ascent,
+                        source: offsets[start]..offsets[end],
+                        offsets: run_offsets,
+                    };
+                    let rect = Rect::from_min_size(
+                        egui::pos2(x, baseline - metrics.ascent),
+                        egui::vec2(width, metrics.height),
+                    );
+                    let mut fragment =
+                        Fragment::new(FragmentKind::Text(run), &layout_box.style, rect);
+                    fragment.node = layout_box.node.clone();
+                    attach(&mut stack, &mut children, fragment);
+                    x += width;
+                }
+                ItemKind::Atomic { layout, .. } => {
+                    let Some(layout) = layout.take() else {
+                        continue;
+                    };
+                    let top = baseline - atomic_baseline(&layout) + layout.margin.top;
+                    let mut fragment = layout.fragment;
+                    fragment.rect = fragment
+                        .rect
+                        .translate(egui::vec2(x + layout.margin.left, top));
+                    x += fragment.rect.width() + layout.margin.horizontal();
+                    attach(&mut stack, &mut children, fragment);
+                }
+                ItemKind::Absolute => {
+                    let placeholder = positioned::placeholder(layout_box, egui::pos2(x, 0.0));
+                    attach(&mut stack, &mut children, placeholder);
+                }
+                ItemKind::Break | ItemKind::Float { .. } => {}
+            }
+        }
+        // Inline boxes that continue on the next line end here without their right edges
+        while let Some(mut fragment) = stack.pop() {
+            fragment.rect.max.x = x.max(fragment.rect.min.x);
+            fragment.rect = fragment
+                .rect
+                .translate(self.relative_offset(&fragment.style));
+            attach(&mut stack, &mut children, fragment);
+        }
+
+        let shift = match self.style.keyword("text-align") {
+            Some("center") => (self.width - x) / 2.0,
+            Some("right" | "end") => self.width - x,
+            _ => 0.0,
+        };
+        if shift > 0.0 {
+            for child in &mut children {
+                child.rect = child.rect.translate(egui::vec2(shift, 0.0));
+            }
+        }
+
+        let rect = Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(self.width, ascent + descent));
+        let mut line = Fragment::new(FragmentKind::Line, self.style, rect);
+        line.children = children;
+        Some((line, baseline))
+    }
+
+    fn relative_offset(&self, style: &ComputedStyle) -> egui::Vec2 {
+        let containing = ContainingBlock {
+            width: self.width,
+            height: None,
+        };
+        positioned::relative_offset(style, containing)
+    }
+
+    // Starts the fragment of an inline box on this line. The content area is the height
+    // of the box's font, extended by its vertical borders and padding.
+    fn inline_fragment(
+        &self,
+        layout_box: &LayoutBox,
+        x: f32,
+        baseline: f32,
+        first: bool,
+    ) -> Fragment {
+        let style = &layout_box.style;
+        let metrics = self.ctx.metrics(&self.ctx.font(style));
+        let mut border = block::border(style);
+        let mut padding = block::padding(style, self.width);
+        border.right = 0.0;
+        padding.right = 0.0;
+        if !first {
+            border.left = 0.0;
+            padding.left = 0.0;
+        }
+        let top = baseline - metrics.ascent - border.top - padding.top;
+        let height = metrics.height + border.vertical() + padding.vertical();
+        let rect = Rect::from_min_size(egui::pos2(x, top), egui::vec2(0.0, height));
+        let mut fragment = Fragment::new(FragmentKind::Inline, style, rect);
+        fragment.node = layout_box.node.clone();
+        fragment.border = border;
+        fragment.padding = padding;
+        fragment
+    }
+}
+
+// The baseline of an atomic inline from the top of its margin box: its last line's
+// baseline, or its bottom margin edge if it has no lines
+fn atomic_baseline(layout: &BlockLayout) -> f32 {
+    match layout.baseline {
+        Some(baseline) => layout.margin.top + baseline,
+        None => layout.margin.top + layout.fragment.rect.height() + layout.margin.bottom,
+    }
+}
+
+// Adds a finished fragment to the innermost open inline box, or the line, making its
+// position relative to its parent
+fn attach(stack: &mut [Fragment], children: &mut Vec<Fragment>, mut fragment: Fragment) {
+    match stack.last_mut() {
+        Some(parent) => {
+            fragment.rect = fragment.rect.translate(-parent.rect.min.to_vec2());
+            parent.children.push(fragment);
+        }
+        None => children.push(fragment),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::css;
+    use crate::fonts::FontSet;
+    use crate::html;
+    use crate::layout::text::FixedMetrics;
+    use crate::layout::{build_box_tree, layout_document, Fragment, LayoutContext};
+
+    fn layout(source: &str, width: f32) -> Fragment {
+        let document = html::parse_document(source);
+        let stylist = css::Stylist::for_document(&document, css::Device::default());
+        let root = build_box_tree(&document, &stylist.style_document(&document)).unwrap();
+        let fonts = FontSet::default();
+        let ctx = LayoutContext::new(&FixedMetrics, &fonts);
+        layout_document(&root, width, &ctx)
+    }
+
+    // Dumps the fragments inside the body, whose margin is removed
+    fn dump(body: &str, width: f32) -> String {
+        let source = format!("<body style='margin: 0'>{}</body>", body);
+        layout(&source, width).children[0]
+            .children
+            .iter()
+            .map(Fragment::dump)
+            .collect()
+    }
+
+    #[test]
+    fn test_line_breaking() {
+        // Characters are 8px wide and lines 20px tall with the default font
+        assert_eq!(
+            dump(
+                "<p style='margin: 0'>The quick  brown\n fox jumps</p>",
+                100.0
+            ),
+            "\
+box (0, 0) 100x60
+  line (0, 0) 100x20
+    text \"The quick\" (0, 0) 72x20
+  line (0, 20) 100x20
+    text \"brown fox\" (0, 20) 72x20
+  line (0, 40) 100x20
+    text \"jumps\" (0, 40) 40x20
+"
+        );
+        // Words wider than the line overflow it
+        assert_eq!(
+            dump("<div>a verylongword b</div>", 50.0),
+            "\
+box (0, 0) 50x60
+  line (0, 0) 50x20
+    text \"a\" (0, 0) 8x20
+  line (0, 20) 50x20
+    text \"verylongword\" (0, 20) 96x20
+  line (0, 40) 50x20
+    text \"b\" (0, 40) 8x20
+"
+        );
+    }
+
+    #[test]
+    fn test_inline_boxes_split_across_lines() {
+        assert_eq!(
+            dump(
+                "<div>one <span style='padding: 0 4px; border: 1px solid'>two three</span> four</div>",
+                90.0
+            ),
+            "\
+box (0, 0) 90x40
+  line (0, 0) 90x20
+    text \"one \" (0, 0) 32x20
+    inline (32, -1) 29x22
+      text \"two\" (37, 0) 24x20
+  line (0, 20) 90x20
+    inline (0, 19) 45x22
+      text \"three\" (0, 20) 40x20
+    text \" four\" (45, 20) 40x20
+"
+        );
+    }
+
+    #[test]
+    fn test_forced_breaks_and_preserved_white_space() {
+        assert_eq!(
+            dump("<div>a<br>b<br><br>c</div>", 100.0),
+            "\
+box (0, 0) 100x80
+  line (0, 0) 100x20
+    text \"a\" (0, 0) 8x20
+  line (0, 20) 100x20
+    text \"b\" (0, 20) 8x20
+  line (0, 40) 100x20
+  line (0, 60) 100x20
+    text \"c\" (0, 60) 8x20
+"
+        );
+        assert_eq!(
+            dump("<pre style='margin: 0'>  a  b\n\nc d e f</pre>", 40.0),
+            "\
+box (0, 0) 40x60
+  line (0, 0) 40x20
+    text \"  a  b\" (0, 0) 48x20
+  line (0, 20) 40x20
+  line (0, 40) 40x20
+    text \"c d e f\" (0, 40) 56x20
+"
+        );
+    }
+
+    #[test]
+    fn test_text_align_indent_and_line_height() {
+        assert_eq!(
+            dump(
+                "<div style='text-align: center; line-height: 30px'>ab cd</div>\
+                 <div style='text-align: right; text-indent: 16px'>ab cd ef</div>\
+                 <div style='text-transform: uppercase'>ab</div>",
+                64.0
+            ),
+            "\
+box (0, 0) 64x30
+  line (0, 0) 64x30
+    text \"ab cd\" (12, 5) 40x20
+box (0, 30) 64x40
+  line (0, 30) 64x20
+    text \"ab cd\" (24, 30) 40x20
+  line (0, 50) 64x20
+    text \"ef\" (48, 50) 16x20
+box (0, 70) 64x20
+  line (0, 70) 64x20
+    text \"AB\" (0, 70) 16x20
+"
+        );
+    }
+
+    #[test]
+    fn test_atomic_inlines_and_fonts() {
+        assert_eq!(
+            dump(
+                "<div>a <span style='display: inline-block; padding: 2px'>b c</span> \
+                 <img width=10 height=30> <small>d</small></div>",
+                200.0
+            ),
+            "\
+box (0, 0) 200x40
+  line (0, 0) 200x40
+    text \"a \" (0, 18) 16x20
+    box (16, 16) 28x24
+      line (18, 18) 24x20
+        text \"b c\" (18, 18) 24x20
+    text \" \" (44, 18) 8x20
+    image (52, 0) 10x30
+    text \" \" (62, 18) 8x20
+    inline (70, 20) 6.67x16.67
+      text \"d\" (70, 20) 6.67x16.67
+"
+        );
+    }
+
+    #[test]
+    fn test_intrinsic_width_and_source_ranges() {
+        let root = layout(
+            "<body style='margin: 0'><span style='display: inline-block'>one two</span>\
+             <p style='margin: 0'>  some   text</p></body>",
+            400.0,
+        );
+        let body = &root.children[0];
+        // The inline-block shrinks to fit its text
+        assert_eq!(body.children[0].children[0].children[0].rect.width(), 56.0);
+        let line = &body.children[1].children[0];
+        let crate::layout::FragmentKind::Text(run) = &line.children[0].kind else {
+            panic!("Expected a text fragment");
+        };
+        assert_eq!(run.text, "some text");
+        assert_eq!(run.source, 2..13);
+        assert_eq!(line.children[0].node.as_deref(), Some(&[0, 1, 1, 0][..]));
+    }
+}
@@ -0,0 +1,126 @@
+// Layout: turning a styled document into boxes and positioning them
+
+mod block;
+mod box_tree;
+mod flex;
+mod float;
+mod fragment;
+mod generated;
+mod grid;
+mod inline;
+mod paginate;
+mod positioned;
+mod table;
+mod text;
+
+pub use box_tree::{
+    build_box_tree, BoxKind, Display, Image, ImageContent, Inside, LayoutBox, Outside,
+};
+pub use fragment::{Edges, Fragment, FragmentKind, TextRun};
+pub use paginate::paginate;
+#[cfg(test)]
+pub(crate) use text::FixedMetrics;
+pub use text::{FontMetrics, TextMeasurer};
+
+use crate::css::ComputedStyle;
+use crate::fonts::FontSet;
+use eframe::egui::{FontFamily, FontId};
+
+/// Text measurement and the page's web fonts, which layout needs besides the box tree
+pub struct LayoutContext<'a> {
+    measurer: &'a dyn TextMeasurer,
+    fonts: &'a FontSet,
+    families: Vec<FontFamily>,
+    // The height of the initial containing block, which fixed boxes are positioned in.
+    // Without one it's the height of the document.
+    viewport_height: Option<f32>,
+}
+
+impl<'a> LayoutContext<'a> {
+    pub fn new(measurer: &'a dyn TextMeasurer, fonts: &'a FontSet) -> Self {
+        Self {
+            measurer,
+            fonts,
+            families: measurer.families(),
+            viewport_height: None,
+        }
+    }
+
+    pub fn with_viewport_height(mut self, height: f32) -> Self {
+        self.viewport_height = Some(height);
+        self
+    }
+
+    // The egui font for a style. Web fonts that egui hasn't loaded yet fall back to the
+    // default font.
+    pub fn font(&self, style: &ComputedStyle) -> FontId {
+        let family = self.fonts.resolve(
+            &style.font_families(),
+            style.font_weight(),
+            style.is_italic(),
+        );
+        let family = match self.families.contains(&family) {
+            true => family,
+            false => FontFamily::Proportional,
+        };
+        // egui can't rasterize empty fonts
+        FontId::new(style.font_size().max(1.0), family)
+    }
+
+    pub(crate) fn metrics(&self, font: &FontId) -> FontMetrics {
+        self.measurer.font_metrics(font)
+    }
+
+    pub(crate) fn text_width(&self, text: &str, font: &FontId) -> f32 {
+        match text.is_empty() {
+            true => 0.0,
+            false => self.measurer.text_width(text, font),
+        }
+    }
+}
+
+// Lays out a box tree for a viewport of the given width
+pub fn layout_document(root: &LayoutBox, viewport_width: f32, context: &LayoutContext) -> Fragment {
+    block::layout_root(root, viewport_width, context)
+}
+
+// Laying out snippets of HTML for the tests of each layout mode
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::{build_box_tree, layout_document, FixedMetrics, Fragment, LayoutContext};
+    use crate::css;
+    use crate::fonts::FontSet;
+    use crate::html;
+
+    // A border box as (x, y, width, height)
+    pub type Rect = (f32, f32, f32, f32);
+
+    // Lays out a body without margins in a 400px wide viewport
+    pub fn layout(body: &str) -> Fragment {
+        lay_out(body, None)
+    }
+
+    // Lays out a body in a viewport of 400px by `height`, which fixed boxes are placed in
+    pub fn layout_in_viewport(body: &str, height: f32) -> Fragment {
+        lay_out(body, Some(height))
+    }
+
+    fn lay_out(body: &str, viewport_height: Option<f32>) -> Fragment {
+        let source = format!("<body style='margin: 0'>{}</body>", body);
+        let document = html::parse_document(&source);
+        let stylist = css::Stylist::for_document(&document, css::Device::default());
+        let styles = stylist.style_document(&document);
+        let root = build_box_tree(&document, &styles).unwrap();
+        let fonts = FontSet::default();
+        let mut ctx = LayoutContext::new(&FixedMetrics, &fonts);
+        if let Some(height) = viewport_height {
+            ctx = ctx.with_viewport_height(height);
+        }
+        layout_document(&root, 400.0, &ctx)
+    }
+
+    pub fn rect(fragment: &Fragment) -> Rect {
+        let rect = fragment.rect;
+        (rect.min.x, rect.min.y, rect.width(), rect.height())
+    }
+}
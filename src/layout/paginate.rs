@@ -0,0 +1,168 @@
+// Pagination: where a laid out document is split into pages when it's printed
+
+use crate::layout::fragment::{Fragment, FragmentKind};
+
+// Break points closer than this are the same point
+const EPSILON: f32 = 0.01;
+
+// Where each page starts in the document, the first at its top, for pages with room for
+// `page_height` pixels of it. Pages break before and after boxes with a forced
+// `break-before` or `break-after`, and otherwise as low as they can between lines and
+// boxes, without splitting a line, an image or a box with `break-inside: avoid` that fits
+// on a page. Content with nowhere to break is cut at the bottom of the page.
+pub fn paginate(root: &Fragment, page_height: f32) -> Vec<f32> {
+    let mut points = BreakPoints::default();
+    points.collect(root);
+    points.forced.sort_by(f32::total_cmp);
+    let avoid: Vec<(f32, f32)> = points
+        .avoid
+        .into_iter()
+        .filter(|(top, bottom)| bottom - top <= page_height)
+        .collect();
+
+    let mut starts = vec![0.0];
+    let mut start: f32 = 0.0;
+    loop {
+        let limit = start + page_height;
+        // Breaks forced at the very end of the content would only add an empty page
+        let forced = points
+            .forced
+            .iter()
+            .copied()
+            .find(|&y| y > start + EPSILON && y <= limit && y < points.bottom - EPSILON);
+        if forced.is_none() && limit >= points.bottom - EPSILON {
+            return starts;
+        }
+        let allowed = |y: f32| {
+            y > start + EPSILON
+                && y <= limit
+                && !avoid
+                    .iter()
+                    .any(|&(top, bottom)| top > start && top + EPSILON < y && y < bottom - EPSILON)
+        };
+        let next = forced
+            .or_else(|| {
+                points
+                    .candidates
+                    .iter()
+                    .copied()
+                    .filter(|&y| allowed(y))
+                    .max_by(f32::total_cmp)
+            })
+            .unwrap_or(limit);
+        starts.push(next);
+        start = next;
+    }
+}
+
+#[derive(Default)]
+struct BreakPoints {
+    // Places the pages may break, and must break
+    candidates: Vec<f32>,
+    forced: Vec<f32>,
+    // Ranges the pages shouldn't break inside
+    avoid: Vec<(f32, f32)>,
+    // The bottom of the content
+    bottom: f32,
+}
+
+impl BreakPoints {
+    fn collect(&mut self, fragment: &Fragment) {
+        let rect = fragment.rect;
+        self.bottom = self.bottom.max(rect.max.y);
+        match fragment.kind {
+            FragmentKind::Box => {
+                self.candidates.extend([rect.min.y, rect.max.y]);
+                let style = &fragment.style;
+                if is_forced(style.keyword("break-before")) {
+                    self.forced.push(rect.min.y);
+                }
+                if is_forced(style.keyword("break-after")) {
+                    self.forced.push(rect.max.y);
+                }
+                if matches!(style.keyword("break-inside"), Some("avoid" | "avoid-page")) {
+                    self.avoid.push((rect.min.y, rect.max.y));
+                }
+            }
+            FragmentKind::Line | FragmentKind::Image(_) => {
+                self.candidates.extend([rect.min.y, rect.max.y]);
+                self.avoid.push((rect.min.y, rect.max.y));
+            }
+            FragmentKind::Inline | FragmentKind::Text(_) => {}
+        }
+        // Text and inline boxes go with their lines
+        if !matches!(fragment.kind, FragmentKind::Line | FragmentKind::Image(_)) {
+            for child in &fragment.children {
+                self.collect(child);
+            }
+        }
+    }
+}
+
+fn is_forced(value: Option<&str>) -> bool {
+    matches!(
+        value,
+        Some("page" | "always" | "left" | "right" | "recto" | "verso")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::{self, MediaType};
+    use crate::layout::{self, FixedMetrics, LayoutContext};
+    use url::Url;
+
+    fn breaks(html: &str, page_height: f32) -> Vec<f32> {
+        let url = Url::parse("http://localhost/").unwrap();
+        let device = css::Device {
+            media_type: MediaType::Print,
+            ..Default::default()
+        };
+        let page = crate::state::Page::new(html, &url, device);
+        let root = layout::build_box_tree(&page.document, &page.styles).unwrap();
+        let fonts = Default::default();
+        let context = LayoutContext::new(&FixedMetrics, &fonts);
+        paginate(
+            &layout::layout_document(&root, 400.0, &context),
+            page_height,
+        )
+    }
+
+    #[test]
+    fn test_paginate() {
+        let block = |height: u32, style: &str| {
+            format!("<div style='height: {}px; {}'></div>", height, style)
+        };
+        let body =
+            |blocks: &[String]| format!("<body style='margin: 0'>{}</body>", blocks.concat());
+
+        // Content that fits takes one page
+        assert_eq!(breaks(&body(&[block(50, "")]), 100.0), vec![0.0]);
+        // Breaks fall between boxes
+        let html = body(&[block(60, ""), block(60, ""), block(60, "")]);
+        assert_eq!(breaks(&html, 100.0), vec![0.0, 60.0, 120.0]);
+        // Forced breaks, also from the legacy properties, come first, and unavoidable ones
+        // cut through boxes
+        let html = body(&[
+            block(20, "break-after: page"),
+            block(250, ""),
+            block(10, "page-break-before: always"),
+        ]);
+        assert_eq!(breaks(&html, 100.0), vec![0.0, 20.0, 120.0, 220.0, 270.0]);
+        // Boxes that avoid breaks move to the next page when they fit on one
+        let html = body(&[
+            block(40, ""),
+            format!(
+                "<div style='break-inside: avoid'>{}{}</div>",
+                block(40, ""),
+                block(40, "")
+            ),
+        ]);
+        assert_eq!(breaks(&html, 100.0), vec![0.0, 40.0]);
+        // Lines aren't split
+        let html = "<body style='margin: 0; font-size: 16px; line-height: 30px'>\
+                    <p style='margin: 0'>one<br>two<br>three<br>four</p></body>";
+        assert_eq!(breaks(html, 100.0), vec![0.0, 90.0]);
+    }
+}
@@ -0,0 +1,302 @@
+// Positioned layout: relative offsets, and absolutely positioned boxes laid out against
+// their containing block once it has been sized
+
+use crate::css::{ComputedStyle, Value};
+use crate::layout::block::{self, ContainingBlock};
+use crate::layout::box_tree::LayoutBox;
+use crate::layout::fragment::{Fragment, FragmentKind};
+use crate::layout::LayoutContext;
+use eframe::egui::{self, Pos2, Rect, Vec2};
+
+// Boxes with a position other than static, which contain the absolutely positioned boxes
+// inside them
+pub(super) fn is_positioned(style: &ComputedStyle) -> bool {
+    style
+        .keyword("position")
+        .is_some_and(|position| position != "static")
+}
+
+// How far a relatively positioned box moves from where the flow put it. Left wins over
+// right and top over bottom.
+pub(super) fn relative_offset(style: &ComputedStyle, containing: ContainingBlock) -> Vec2 {
+    if style.keyword("position") != Some("relative") {
+        return Vec2::ZERO;
+    }
+    let horizontal = |name: &str| style.length(name, containing.width);
+    let vertical = |name: &str| inset(style, name, containing.height);
+    let x = match (horizontal("left"), horizontal("right")) {
+        (Some(left), _) => left,
+        (None, Some(right)) => -right,
+        (None, None) => 0.0,
+    };
+    let y = match (vertical("top"), vertical("bottom")) {
+        (Some(top), _) => top,
+        (None, Some(bottom)) => -bottom,
+        (None, None) => 0.0,
+    };
+    egui::vec2(x, y)
+}
+
+// A vertical inset, which is auto when it's a percentage of a height that depends on
+// content
+fn inset(style: &ComputedStyle, name: &str, height: Option<f32>) -> Option<f32> {
+    match (style.get(name)?, height) {
+        (Value::Percentage(_) | Value::Calc(..), None) => None,
+        (_, height) => style.length(name, height.unwrap_or(0.0)),
+    }
+}
+
+// The empty fragment an absolutely positioned box leaves where it would have been in the
+// flow, until its containing block lays it out there
+pub(super) fn placeholder(layout_box: &LayoutBox, position: Pos2) -> Fragment {
+    let rect = Rect::from_min_size(position, Vec2::ZERO);
+    let mut fragment = Fragment::new(FragmentKind::Box, &layout_box.style, rect);
+    fragment.node = layout_box.node.clone();
+    fragment
+}
+
+// Lays out the absolutely positioned boxes a box is the containing block of, adding them
+// to its fragment. `containing` is its padding box, or for the root the initial
+// containing block, which also holds every fixed box.
+pub(super) fn layout_absolute_descendants(
+    layout_box: &LayoutBox,
+    fragment: &mut Fragment,
+    containing: Rect,
+    initial: bool,
+    ctx: &LayoutContext,
+) {
+    let mut boxes = Vec::new();
+    let contained = initial && is_positioned(&layout_box.style);
+    collect(layout_box, initial, contained, &mut boxes);
+    for absolute in boxes {
+        // Boxes whose placeholder didn't make it into a line start at the content box
+        let static_position = absolute
+            .node
+            .as_deref()
+            .and_then(|node| take_placeholder(fragment, node))
+            .unwrap_or(fragment.content_rect().min);
+        let positioned = layout_absolute(absolute, containing, static_position, ctx);
+        fragment.children.push(positioned);
+    }
+}
+
+// Finds the absolutely positioned descendants of a box in tree order. Positioned boxes
+// take care of those inside them, except for fixed boxes, which belong to the root.
+fn collect<'b>(
+    layout_box: &'b LayoutBox,
+    initial: bool,
+    contained: bool,
+    found: &mut Vec<&'b LayoutBox>,
+) {
+    for child in &layout_box.children {
+        let position = child.style.keyword("position").unwrap_or("static");
+        if child.is_absolutely_positioned() {
+            match position {
+                "fixed" if initial => found.push(child),
+                "absolute" if !contained => found.push(child),
+                _ => {}
+            }
+        }
+        let contained = contained || position != "static";
+        if initial || !contained {
+            collect(child, initial, contained, found);
+        }
+    }
+}
+
+// Removes the placeholder of the box generated for `node` from a fragment's subtree,
+// returning its position relative to the fragment
+fn take_placeholder(fragment: &mut Fragment, node: &[usize]) -> Option<Pos2> {
+    let index = fragment.children.iter().position(|child| {
+        child.kind == FragmentKind::Box
+            && child.node.as_deref() == Some(node)
+            && child.rect.size() == Vec2::ZERO
+            && child.children.is_empty()
+    });
+    if let Some(index) = index {
+        return Some(fragment.children.remove(index).rect.min);
+    }
+    fragment.children.iter_mut().find_map(|child| {
+        let offset = child.rect.min.to_vec2();
+        take_placeholder(child, node).map(|position| position + offset)
+    })
+}
+
+// Lays out an absolutely positioned box in its containing block. Insets that are both
+// auto leave the box at its static position, an auto size between two insets stretches,
+// and otherwise the box shrinks to fit.
+fn layout_absolute(
+    layout_box: &LayoutBox,
+    containing: Rect,
+    static_position: Pos2,
+    ctx: &LayoutContext,
+) -> Fragment {
+    let style = &layout_box.style;
+    let size = containing.size();
+    let static_position = static_position - containing.min;
+    let [margin_top, margin_right, margin_bottom, margin_left] = block::margins(style, size.x);
+    let border = block::border(style);
+    let padding = block::padding(style, size.x);
+    let extra = egui::vec2(
+        border.horizontal() + padding.horizontal(),
+        border.vertical() + padding.vertical(),
+    );
+
+    let left = style.length("left", size.x);
+    let right = style.length("right", size.x);
+    let width = match (
+        block::content_size(style, "width", size.x, extra.x),
+        left,
+        right,
+    ) {
+        (Some(width), _, _) => width,
+        (None, Some(left), Some(right)) => {
+            let margins = margin_left.unwrap_or(0.0) + margin_right.unwrap_or(0.0);
+            (size.x - left - right - margins - extra.x).max(0.0)
+        }
+        (None, _, _) => {
+            let (min, max) = block::intrinsic_content_widths(layout_box, ctx);
+            let margins = margin_left.unwrap_or(0.0) + margin_right.unwrap_or(0.0);
+            let insets = left.unwrap_or(static_position.x) + right.unwrap_or(0.0);
+            let available = (size.x - insets - margins - extra.x).max(0.0);
+            max.min(available.max(min))
+        }
+    };
+    let width = block::clamp_width(style, width, size.x, extra.x);
+    let x = match (left, right) {
+        (Some(left), Some(right)) => {
+            let space = size.x - left - right;
+            left + block::solve_margins(width, margin_left, margin_right, extra.x, space).0
+        }
+        (Some(left), None) => left + margin_left.unwrap_or(0.0),
+        (None, Some(right)) => size.x - right - margin_right.unwrap_or(0.0) - width - extra.x,
+        (None, None) => static_position.x + margin_left.unwrap_or(0.0),
+    };
+
+    let top = style.length("top", size.y);
+    let bottom = style.length("bottom", size.y);
+    let height = match (top, bottom) {
+        (Some(top), Some(bottom)) => block::resolve_height(style, "height", Some(size.y), extra.y)
+            .or_else(|| {
+                let margins = margin_top.unwrap_or(0.0) + margin_bottom.unwrap_or(0.0);
+                Some((size.y - top - bottom - margins - extra.y).max(0.0))
+            }),
+        _ => None,
+    };
+    let inner = ContainingBlock {
+        width: size.x,
+        height: Some(size.y),
+    };
+    let layout = block::layout_sized_box(layout_box, width, height, inner, ctx);
+    let mut fragment = layout.fragment;
+    let height = fragment.rect.height() - extra.y;
+    let y = match (top, bottom) {
+        (Some(top), Some(bottom)) => {
+            let space = size.y - top - bottom;
+            top + block::solve_margins(height, margin_top, margin_bottom, extra.y, space).0
+        }
+        (Some(top), None) => top + margin_top.unwrap_or(0.0),
+        (None, Some(bottom)) => {
+            size.y - bottom - margin_bottom.unwrap_or(0.0) - fragment.rect.height()
+        }
+        (None, None) => static_position.y + margin_top.unwrap_or(0.0),
+    };
+    fragment.rect = fragment
+        .rect
+        .translate(containing.min.to_vec2() + egui::vec2(x, y));
+    fragment
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::layout::testing::{self, rect, Rect};
+    use crate::layout::{Fragment, FragmentKind};
+    use eframe::egui;
+
+    // Lays out a body in a 400x300 viewport
+    fn layout(body: &str) -> Fragment {
+        testing::layout_in_viewport(body, 300.0)
+    }
+
+    // The border boxes of the positioned boxes, in tree order
+    fn boxes(root: &Fragment) -> Vec<Rect> {
+        let mut found = Vec::new();
+        if root.kind == FragmentKind::Box && root.style.keyword("position") != Some("static") {
+            found.push(rect(root));
+        }
+        for child in &root.children {
+            found.extend(boxes(child));
+        }
+        found
+    }
+
+    #[test]
+    fn test_relative_and_absolute_positioning() {
+        // A relative offset moves the box without moving the ones after it
+        let root = layout(
+            "<div style='height: 50px'></div>\
+             <div style='position: relative; left: 10px; top: 5px; height: 20px'></div>\
+             <div style='height: 10px'></div>",
+        );
+        let body = &root.children[0];
+        assert_eq!(body.children[1].rect.min, egui::pos2(10.0, 55.0));
+        assert_eq!(body.children[2].rect.min.y, 70.0);
+        assert_eq!(body.rect.height(), 80.0);
+
+        // Absolute boxes are placed in the padding box of their positioned ancestor, and
+        // take no space in the flow
+        let root = layout(
+            "<div style='position: relative; margin-left: 50px; padding: 10px; height: 100px'>\
+             <div style='position: absolute; top: 0; right: 0; width: 30px; height: 20px'></div>\
+             <div style='position: absolute; inset: 0'></div>\
+             <div style='position: absolute; left: 0; right: 0; width: 100px; margin: 0 auto'>\
+             </div>\
+             <p style='margin: 0'>text</p></div>",
+        );
+        assert_eq!(
+            boxes(&root),
+            vec![
+                (50.0, 0.0, 350.0, 120.0),
+                (370.0, 0.0, 30.0, 20.0),
+                (50.0, 0.0, 350.0, 120.0),
+                (175.0, 10.0, 100.0, 0.0),
+            ]
+        );
+        let div = &root.children[0].children[0];
+        assert_eq!(div.children[0].rect.min.y, 10.0);
+
+        // Without insets, a box shrinks to fit at its static position, in a line or after
+        // the blocks before it
+        let root = layout(
+            "<div style='height: 30px'></div>\
+             <div style='position: absolute'>ab</div>\
+             <p style='margin: 0'>aa<span style='position: absolute'>b</span>cc</p>",
+        );
+        assert_eq!(
+            boxes(&root),
+            vec![(0.0, 30.0, 16.0, 20.0), (16.0, 30.0, 8.0, 20.0)]
+        );
+    }
+
+    #[test]
+    fn test_fixed_boxes_and_the_initial_containing_block() {
+        // Fixed boxes are placed in the viewport whatever their ancestors, and absolute
+        // boxes without a positioned ancestor in the initial containing block. Both end up
+        // in the root in tree order.
+        let root = layout(
+            "<div style='position: relative; height: 1000px'>\
+             <div style='position: fixed; bottom: 0; left: 0; width: 10px; height: 10px'>\
+             </div></div>\
+             <div style='position: absolute; right: 0; bottom: 0; width: 20px; height: 20px'>\
+             </div>",
+        );
+        assert_eq!(
+            boxes(&root),
+            vec![
+                (0.0, 0.0, 400.0, 1000.0),
+                (0.0, 290.0, 10.0, 10.0),
+                (380.0, 280.0, 20.0, 20.0),
+            ]
+        );
+    }
+}
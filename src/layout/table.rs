@@ -0,0 +1,1067 @@
+// Table layout: placing cells into the slots of the table grid, sizing the columns with
+// the automatic or fixed algorithm, then stacking the rows between the captions
+
+use crate::css::{Color, ComputedStyle, Value};
+use crate::layout::block::{self, BlockLayout, ContainingBlock, SIDES};
+use crate::layout::box_tree::{BoxKind, LayoutBox, TablePart};
+use crate::layout::fragment::{Edges, Fragment, FragmentKind};
+use crate::layout::LayoutContext;
+use eframe::egui::{self, Pos2, Rect};
+use std::borrow::Cow;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// The captions and grid of a table positioned relative to the wrapper's content box
+pub(super) struct TableLayout {
+    pub children: Vec<Fragment>,
+    pub height: f32,
+    pub baseline: Option<f32>,
+}
+
+// A cell and the slots of the grid it covers
+struct Cell<'a> {
+    layout_box: Cow<'a, LayoutBox>,
+    row: usize,
+    column: usize,
+    rows: usize,
+    columns: usize,
+}
+
+impl Cell<'_> {
+    fn column_range(&self) -> Range<usize> {
+        self.column..self.column + self.columns
+    }
+
+    fn row_range(&self) -> Range<usize> {
+        self.row..self.row + self.rows
+    }
+}
+
+// A row group, or a run of rows outside any group, and the rows it holds
+struct Group<'a> {
+    layout_box: Option<&'a LayoutBox>,
+    rows: Range<usize>,
+}
+
+// The widths a column's cells and column elements ask for, including the cells' borders
+// and padding
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct ColumnWidth {
+    min: f32,
+    max: f32,
+    fixed: Option<f32>,
+    percent: Option<f32>,
+}
+
+impl ColumnWidth {
+    // The width the column gets before any space is shared out
+    fn base(&self, target: f32) -> f32 {
+        match (self.percent, self.fixed) {
+            (Some(percent), _) => (percent / 100.0 * target).max(self.min),
+            (None, Some(fixed)) => fixed.max(self.min),
+            (None, None) => self.min,
+        }
+    }
+
+    fn is_auto(&self) -> bool {
+        self.percent.is_none() && self.fixed.is_none()
+    }
+}
+
+// One side of a border in the collapsing border model
+#[derive(Debug, Clone, PartialEq)]
+struct Edge {
+    width: f32,
+    style: String,
+    color: Color,
+}
+
+// The table grid box with its rows, cells and columns sorted into place
+struct Table<'a> {
+    grid_box: &'a LayoutBox,
+    style: Arc<ComputedStyle>,
+    groups: Vec<Group<'a>>,
+    rows: Vec<&'a LayoutBox>,
+    cells: Vec<Cell<'a>>,
+    // The column element each column belongs to
+    columns: Vec<Option<&'a LayoutBox>>,
+    column_groups: Vec<(&'a LayoutBox, Range<usize>)>,
+    spacing: egui::Vec2,
+    border: Edges,
+    padding: Edges,
+}
+
+impl<'a> Table<'a> {
+    fn new(grid_box: &'a LayoutBox, basis: f32) -> Self {
+        let rows_of = |group: &'a LayoutBox| -> Vec<&'a LayoutBox> {
+            group
+                .children
+                .iter()
+                .filter(|row| row.kind == BoxKind::TablePart(TablePart::Row))
+                .collect()
+        };
+        let mut header = None;
+        let mut footer = None;
+        let mut bodies: Vec<(Option<&LayoutBox>, Vec<&LayoutBox>)> = Vec::new();
+        let mut columns = Vec::new();
+        let mut column_groups = Vec::new();
+        for child in &grid_box.children {
+            let BoxKind::TablePart(part) = child.kind else {
+                continue;
+            };
+            match part {
+                // Only the first header and footer move to the ends of the table
+                TablePart::HeaderGroup if header.is_none() => header = Some(child),
+                TablePart::FooterGroup if footer.is_none() => footer = Some(child),
+                TablePart::HeaderGroup | TablePart::RowGroup | TablePart::FooterGroup => {
+                    bodies.push((Some(child), rows_of(child)))
+                }
+                TablePart::Row => match bodies.last_mut() {
+                    Some((None, rows)) => rows.push(child),
+                    _ => bodies.push((None, vec![child])),
+                },
+                TablePart::Column { span } => {
+                    columns.extend(std::iter::repeat_n(Some(child), span))
+                }
+                TablePart::ColumnGroup { span } => {
+                    let start = columns.len();
+                    for column in &child.children {
+                        if let BoxKind::TablePart(TablePart::Column { span }) = column.kind {
+                            columns.extend(std::iter::repeat_n(Some(column), span));
+                        }
+                    }
+                    // A group without columns spans columns of its own
+                    if columns.len() == start {
+                        columns.extend(std::iter::repeat_n(Some(child), span));
+                    }
+                    column_groups.push((child, start..columns.len()));
+                }
+                _ => {}
+            }
+        }
+        let header = header.map(|group| (Some(group), rows_of(group)));
+        let footer = footer.map(|group| (Some(group), rows_of(group)));
+
+        let mut groups = Vec::new();
+        let mut rows = Vec::new();
+        for (group, group_rows) in header.into_iter().chain(bodies).chain(footer) {
+            let start = rows.len();
+            rows.extend(group_rows);
+            groups.push(Group {
+                layout_box: group,
+                rows: start..rows.len(),
+            });
+        }
+
+        // Each cell takes the first free slot in its row, and rows spanned from above
+        // push it right. Row spans stop at the end of their group.
+        let mut occupied: Vec<Vec<bool>> = vec![Vec::new(); rows.len()];
+        let mut cells = Vec::new();
+        for group in &groups {
+            for row in group.rows.clone() {
+                let mut column = 0;
+                for cell in &rows[row].children {
+                    let BoxKind::TablePart(TablePart::Cell {
+                        columns: column_span,
+                        rows: row_span,
+                    }) = cell.kind
+                    else {
+                        continue;
+                    };
+                    while occupied[row].get(column).copied().unwrap_or(false) {
+                        column += 1;
+                    }
+                    let end = match row_span {
+                        0 => group.rows.end,
+                        span => (row + span).min(group.rows.end),
+                    };
+                    for line in &mut occupied[row..end] {
+                        if line.len() < column + column_span {
+                            line.resize(column + column_span, false);
+                        }
+                        line[column..column + column_span].fill(true);
+                    }
+                    cells.push(Cell {
+                        layout_box: Cow::Borrowed(cell),
+                        row,
+                        column,
+                        rows: end - row,
+                        columns: column_span,
+                    });
+                    column += column_span;
+                }
+            }
+        }
+        let count = occupied
+            .iter()
+            .map(Vec::len)
+            .fold(columns.len(), usize::max);
+        columns.resize(count, None);
+
+        let style = Arc::clone(&grid_box.style);
+        let collapse = style.keyword("border-collapse") == Some("collapse");
+        let spacing = match style.get("border-spacing") {
+            Some(value) if !collapse => {
+                let items = value.items();
+                let horizontal = items[0].to_px(0.0).unwrap_or(0.0).max(0.0);
+                let vertical = items.get(1).and_then(|item| item.to_px(0.0));
+                egui::vec2(horizontal, vertical.map_or(horizontal, |v| v.max(0.0)))
+            }
+            _ => egui::Vec2::ZERO,
+        };
+        let mut table = Self {
+            grid_box,
+            border: block::border(&style),
+            padding: block::padding(&style, basis),
+            style,
+            groups,
+            rows,
+            cells,
+            columns,
+            column_groups,
+            spacing,
+        };
+        if collapse {
+            table.collapse_borders();
+        }
+        table
+    }
+
+    // Resolves each edge between cells to the stronger of the borders that meet there.
+    // Every cell then draws half of its edges and the table the outer halves.
+    fn collapse_borders(&mut self) {
+        let count = self.columns.len();
+        let mut slots = vec![vec![None; count]; self.rows.len()];
+        for (index, cell) in self.cells.iter().enumerate() {
+            for line in &mut slots[cell.row_range()] {
+                line[cell.column_range()].fill(Some(index));
+            }
+        }
+        let table_edges = border_edges(&self.style);
+        let cell_edges: Vec<[Edge; 4]> = self
+            .cells
+            .iter()
+            .map(|cell| border_edges(&cell.layout_box.style))
+            .collect();
+        let mut outer: [Option<Edge>; 4] = Default::default();
+        let mut resolved = Vec::with_capacity(self.cells.len());
+        for (index, cell) in self.cells.iter().enumerate() {
+            let last_row = cell.row + cell.rows;
+            let last_column = cell.column + cell.columns;
+            // The neighbouring slots across each side, or None along the table's edge
+            let neighbours: [Option<Vec<Option<usize>>>; 4] = [
+                (cell.row > 0).then(|| slots[cell.row - 1][cell.column_range()].to_vec()),
+                (last_column < count).then(|| {
+                    slots[cell.row_range()]
+                        .iter()
+                        .map(|line| line[last_column])
+                        .collect()
+                }),
+                (last_row < self.rows.len()).then(|| slots[last_row][cell.column_range()].to_vec()),
+                (cell.column > 0).then(|| {
+                    slots[cell.row_range()]
+                        .iter()
+                        .map(|line| line[cell.column - 1])
+                        .collect()
+                }),
+            ];
+            let edges: [Edge; 4] = std::array::from_fn(|side| {
+                let own = cell_edges[index][side].clone();
+                match &neighbours[side] {
+                    Some(slots) => slots
+                        .iter()
+                        .flatten()
+                        .map(|&neighbour| cell_edges[neighbour][(side + 2) % 4].clone())
+                        .fold(own, stronger),
+                    None => {
+                        let edge = stronger(own, table_edges[side].clone());
+                        outer[side] = Some(match outer[side].take() {
+                            Some(other) => stronger(other, edge.clone()),
+                            None => edge.clone(),
+                        });
+                        edge
+                    }
+                }
+            });
+            resolved.push(edges);
+        }
+        for (cell, edges) in self.cells.iter_mut().zip(&resolved) {
+            let mut style = (*cell.layout_box.style).clone();
+            set_border(&mut style, edges);
+            let mut layout_box = cell.layout_box.clone().into_owned();
+            layout_box.style = Arc::new(style);
+            cell.layout_box = Cow::Owned(layout_box);
+        }
+        let edges: [Edge; 4] =
+            std::array::from_fn(|side| outer[side].take().unwrap_or(table_edges[side].clone()));
+        let mut style = (*self.style).clone();
+        set_border(&mut style, &edges);
+        for side in SIDES {
+            style.set(&format!("padding-{}", side), Value::Length(0.0));
+        }
+        self.border = block::border(&style);
+        self.padding = Edges::default();
+        self.style = Arc::new(style);
+    }
+
+    // The grid's borders, padding and spacing, which the columns don't take up
+    fn non_column_width(&self) -> f32 {
+        let spacing = match self.columns.len() {
+            0 => 0.0,
+            count => self.spacing.x * (count + 1) as f32,
+        };
+        self.border.horizontal() + self.padding.horizontal() + spacing
+    }
+
+    // The border box width given by the table's width property
+    fn specified_width(&self, basis: f32) -> Option<f32> {
+        let extra = self.border.horizontal() + self.padding.horizontal();
+        block::content_size(&self.style, "width", basis, extra).map(|width| width + extra)
+    }
+
+    fn is_fixed(&self, basis: f32) -> bool {
+        self.style.keyword("table-layout") == Some("fixed") && self.specified_width(basis).is_some()
+    }
+
+    // The automatic algorithm's column widths: single-column cells first, then cells
+    // spanning several columns share out whatever more they need
+    fn column_widths(&self, ctx: &LayoutContext) -> Vec<ColumnWidth> {
+        let mut widths: Vec<ColumnWidth> = self
+            .columns
+            .iter()
+            .map(|column| {
+                let mut width = ColumnWidth::default();
+                if let Some(column) = column {
+                    match column.style.get("width") {
+                        Some(Value::Percentage(percent)) => width.percent = Some(*percent),
+                        _ => width.fixed = column.style.length("width", 0.0),
+                    }
+                }
+                width
+            })
+            .collect();
+        let mut cells: Vec<&Cell> = self.cells.iter().collect();
+        cells.sort_by_key(|cell| cell.columns);
+        for cell in cells {
+            let cell_width = cell_width(&cell.layout_box, ctx);
+            if cell.columns == 1 {
+                let width = &mut widths[cell.column];
+                width.min = width.min.max(cell_width.min);
+                width.max = width.max.max(cell_width.max);
+                width.fixed = larger(width.fixed, cell_width.fixed);
+                width.percent = larger(width.percent, cell_width.percent);
+                continue;
+            }
+            let spacing = self.spacing.x * (cell.columns - 1) as f32;
+            let spanned = &mut widths[cell.column_range()];
+            spread(spanned, cell_width.min - spacing, |width| &mut width.min);
+            spread(spanned, cell_width.max - spacing, |width| &mut width.max);
+            // Columns without a percentage share what the spanned ones don't already have
+            if let Some(percent) = cell_width.percent {
+                let assigned: f32 = spanned.iter().filter_map(|width| width.percent).sum();
+                let free = spanned
+                    .iter()
+                    .filter(|width| width.percent.is_none())
+                    .count();
+                if free > 0 && percent > assigned {
+                    for width in spanned.iter_mut().filter(|width| width.percent.is_none()) {
+                        width.percent = Some((percent - assigned) / free as f32);
+                    }
+                }
+            }
+        }
+        for width in &mut widths {
+            width.max = match width.fixed {
+                Some(fixed) => fixed.max(width.min),
+                None => width.max.max(width.min),
+            };
+        }
+        widths
+    }
+
+    // The fixed algorithm's column widths, taken from the column elements and the first
+    // row alone. Columns without a width share what is left equally.
+    fn fixed_column_widths(&self, target: f32) -> Vec<f32> {
+        let mut widths: Vec<Option<f32>> = self
+            .columns
+            .iter()
+            .map(|column| column.and_then(|column| column.style.length("width", target)))
+            .collect();
+        for cell in self.cells.iter().filter(|cell| cell.row == 0) {
+            let style = &cell.layout_box.style;
+            let extra =
+                block::border(style).horizontal() + block::padding(style, target).horizontal();
+            let Some(width) = block::content_size(style, "width", target, extra) else {
+                continue;
+            };
+            let spanned = &mut widths[cell.column_range()];
+            let assigned: f32 = spanned.iter().flatten().sum();
+            let free = spanned.iter().filter(|width| width.is_none()).count();
+            let remaining = width + extra - assigned - self.spacing.x * (cell.columns - 1) as f32;
+            for slot in spanned.iter_mut().filter(|width| width.is_none()) {
+                *slot = Some((remaining / free as f32).max(0.0));
+            }
+        }
+        let assigned: f32 = widths.iter().flatten().sum();
+        let free = widths.iter().filter(|width| width.is_none()).count();
+        let remaining = (target - assigned).max(0.0);
+        let widths = widths
+            .into_iter()
+            .map(|width| width.unwrap_or(remaining / free.max(1) as f32));
+        match free == 0 && assigned > 0.0 && remaining > 0.0 {
+            // Every column has a width, so they all grow to fill the table
+            true => widths.map(|width| width * target / assigned).collect(),
+            false => widths.collect(),
+        }
+    }
+
+    // The border box width of the table in a containing block
+    fn used_width(&self, containing_width: f32, available: f32, ctx: &LayoutContext) -> f32 {
+        let extra = self.non_column_width();
+        if self.is_fixed(containing_width) {
+            let width = self.specified_width(containing_width).unwrap_or(0.0);
+            let columns: f32 = self.fixed_column_widths(width - extra).iter().sum();
+            return width.max(columns + extra);
+        }
+        let (min, max) = width_bounds(&self.column_widths(ctx));
+        match self.specified_width(containing_width) {
+            Some(width) => width.max(min + extra),
+            None => (max + extra).min(available).max(min + extra),
+        }
+    }
+
+    // Lays out the grid box at a border box width, returning it with its baseline
+    fn layout(&self, width: f32, basis: f32, ctx: &LayoutContext) -> (Fragment, Option<f32>) {
+        let extra = self.non_column_width();
+        let target = (width - extra).max(0.0);
+        let columns = match self.is_fixed(basis) {
+            true => self.fixed_column_widths(target),
+            false => distribute(&self.column_widths(ctx), target),
+        };
+        let width = width.max(columns.iter().sum::<f32>() + extra);
+        let spacing = self.spacing;
+        let mut xs = Vec::with_capacity(columns.len());
+        let mut x = self.border.left + self.padding.left + spacing.x;
+        for column in &columns {
+            xs.push(x);
+            x += column + spacing.x;
+        }
+        let span_width = |cell: &Cell| {
+            columns[cell.column_range()].iter().sum::<f32>() + spacing.x * (cell.columns - 1) as f32
+        };
+        let containing = ContainingBlock {
+            width: target,
+            height: None,
+        };
+        let layouts: Vec<BlockLayout> = self
+            .cells
+            .iter()
+            .map(|cell| {
+                let style = &cell.layout_box.style;
+                let extra =
+                    block::border(style).horizontal() + block::padding(style, target).horizontal();
+                let width = (span_width(cell) - extra).max(0.0);
+                block::layout_sized_box(&cell.layout_box, width, None, containing, ctx)
+            })
+            .collect();
+
+        // Rows grow to fit their cells, with baseline aligned cells lined up on the
+        // lowest of their baselines
+        let mut heights: Vec<f32> = self
+            .rows
+            .iter()
+            .map(|row| block::resolve_height(&row.style, "height", None, 0.0).unwrap_or(0.0))
+            .collect();
+        let mut ascents: Vec<Option<f32>> = vec![None; self.rows.len()];
+        for (cell, layout) in self.cells.iter().zip(&layouts) {
+            if cell.rows == 1 && is_baseline_aligned(&cell.layout_box.style) {
+                let ascent = cell_ascent(layout);
+                ascents[cell.row] = Some(ascents[cell.row].map_or(ascent, |a| a.max(ascent)));
+            }
+        }
+        for (cell, layout) in self.cells.iter().zip(&layouts) {
+            let height = layout.fragment.rect.height();
+            if cell.rows == 1 {
+                let needed = match (
+                    ascents[cell.row],
+                    is_baseline_aligned(&cell.layout_box.style),
+                ) {
+                    (Some(ascent), true) => ascent - cell_ascent(layout) + height,
+                    _ => height,
+                };
+                heights[cell.row] = heights[cell.row].max(needed);
+            }
+        }
+        for (cell, layout) in self.cells.iter().zip(&layouts) {
+            if cell.rows > 1 {
+                let spanned = &mut heights[cell.row_range()];
+                let total = spanned.iter().sum::<f32>() + spacing.y * (cell.rows - 1) as f32;
+                let extra = layout.fragment.rect.height() - total;
+                if extra > 0.0 {
+                    spanned
+                        .iter_mut()
+                        .for_each(|height| *height += extra / cell.rows as f32);
+                }
+            }
+        }
+        // A taller table shares the extra height between its rows
+        let vertical = self.border.vertical() + self.padding.vertical();
+        let row_spacing = match self.rows.len() {
+            0 => 0.0,
+            count => spacing.y * (count + 1) as f32,
+        };
+        if let Some(height) = block::resolve_height(&self.style, "height", None, vertical) {
+            let extra = height - row_spacing - heights.iter().sum::<f32>();
+            if extra > 0.0 && !heights.is_empty() {
+                let share = extra / heights.len() as f32;
+                heights.iter_mut().for_each(|height| *height += share);
+            }
+        }
+        let mut ys = Vec::with_capacity(heights.len());
+        let mut y = self.border.top + self.padding.top + spacing.y;
+        for height in &heights {
+            ys.push(y);
+            y += height + spacing.y;
+        }
+        if heights.is_empty() {
+            y = self.border.top + self.padding.top;
+        }
+        let height = y + self.padding.bottom + self.border.bottom;
+
+        // Rows stretch across the columns, and the cells sit in them
+        let row_left = xs
+            .first()
+            .copied()
+            .unwrap_or(self.border.left + self.padding.left);
+        let row_right = match (xs.last(), columns.last()) {
+            (Some(x), Some(column)) => x + column,
+            _ => row_left,
+        };
+        let mut row_cells: Vec<Vec<Fragment>> = vec![Vec::new(); self.rows.len()];
+        for (cell, layout) in self.cells.iter().zip(layouts) {
+            let height =
+                heights[cell.row_range()].iter().sum::<f32>() + spacing.y * (cell.rows - 1) as f32;
+            let natural = layout.fragment.rect.height();
+            let offset = match cell.layout_box.style.keyword("vertical-align") {
+                Some("top") => 0.0,
+                Some("middle") => (height - natural) / 2.0,
+                Some("bottom") => height - natural,
+                _ => match ascents[cell.row] {
+                    Some(ascent) if cell.rows == 1 => ascent - cell_ascent(&layout),
+                    _ => 0.0,
+                },
+            };
+            let mut fragment = layout.fragment;
+            for child in &mut fragment.children {
+                child.rect = child.rect.translate(egui::vec2(0.0, offset.max(0.0)));
+            }
+            fragment.rect = Rect::from_min_size(
+                Pos2::new(xs[cell.column] - row_left, 0.0),
+                egui::vec2(fragment.rect.width(), height),
+            );
+            row_cells[cell.row].push(fragment);
+        }
+        let row_rect = |rows: Range<usize>| {
+            Rect::from_min_max(
+                Pos2::new(row_left, ys[rows.start]),
+                Pos2::new(row_right, ys[rows.end - 1] + heights[rows.end - 1]),
+            )
+        };
+
+        let mut grid = box_fragment(
+            self.grid_box,
+            &self.style,
+            Rect::from_min_size(Pos2::ZERO, egui::vec2(width, height)),
+        );
+        grid.border = self.border;
+        grid.padding = self.padding;
+        if !self.rows.is_empty() {
+            let top = ys[0];
+            let bottom = row_rect(0..self.rows.len()).max.y;
+            grid.children = self.column_fragments(&xs, &columns, top, bottom - top);
+        }
+        let mut row_cells = row_cells.into_iter();
+        for group in &self.groups {
+            let mut rows = Vec::new();
+            for (row, cells) in group.rows.clone().zip(row_cells.by_ref()) {
+                let layout_box = self.rows[row];
+                let mut fragment =
+                    box_fragment(layout_box, &layout_box.style, row_rect(row..row + 1));
+                fragment.children = cells;
+                rows.push(fragment);
+            }
+            match group.layout_box {
+                Some(layout_box) if !group.rows.is_empty() => {
+                    let rect = row_rect(group.rows.clone());
+                    let mut fragment = box_fragment(layout_box, &layout_box.style, rect);
+                    for mut row in rows {
+                        row.rect = row.rect.translate(-rect.min.to_vec2());
+                        fragment.children.push(row);
+                    }
+                    grid.children.push(fragment);
+                }
+                _ => grid.children.extend(rows),
+            }
+        }
+        let baseline = ys.first().map(|y| y + ascents[0].unwrap_or(heights[0]));
+        (grid, baseline)
+    }
+
+    // Fragments for the column groups and columns, which paint their backgrounds under
+    // the rows
+    fn column_fragments(&self, xs: &[f32], widths: &[f32], top: f32, height: f32) -> Vec<Fragment> {
+        let rect = |columns: &Range<usize>| {
+            Rect::from_min_max(
+                Pos2::new(xs[columns.start], top),
+                Pos2::new(xs[columns.end - 1] + widths[columns.end - 1], top + height),
+            )
+        };
+        // Runs of columns generated by the same column element
+        let mut runs: Vec<(&LayoutBox, Range<usize>)> = Vec::new();
+        for (index, column) in self.columns.iter().enumerate() {
+            let Some(column) = column else {
+                continue;
+            };
+            if !matches!(column.kind, BoxKind::TablePart(TablePart::Column { .. })) {
+                continue;
+            }
+            match runs.last_mut() {
+                Some((last, run)) if std::ptr::eq(*last, *column) && run.end == index => {
+                    run.end += 1
+                }
+                _ => runs.push((column, index..index + 1)),
+            }
+        }
+        let mut fragments = Vec::new();
+        for (group, columns) in &self.column_groups {
+            if columns.is_empty() {
+                continue;
+            }
+            let group_rect = rect(columns);
+            let mut fragment = box_fragment(group, &group.style, group_rect);
+            for (column, run) in &runs {
+                if run.start >= columns.start && run.end <= columns.end {
+                    let column_rect = rect(run).translate(-group_rect.min.to_vec2());
+                    fragment
+                        .children
+                        .push(box_fragment(column, &column.style, column_rect));
+                }
+            }
+            fragments.push(fragment);
+        }
+        for (column, run) in &runs {
+            let grouped = self
+                .column_groups
+                .iter()
+                .any(|(_, columns)| run.start >= columns.start && run.end <= columns.end);
+            if !grouped {
+                fragments.push(box_fragment(column, &column.style, rect(run)));
+            }
+        }
+        fragments
+    }
+}
+
+// Lays out a table wrapper's captions and grid at the wrapper's content width
+pub(super) fn layout_table_content(
+    wrapper: &LayoutBox,
+    containing: ContainingBlock,
+    ctx: &LayoutContext,
+) -> TableLayout {
+    let (grid_box, captions) = parts(wrapper);
+    let mut children = Vec::new();
+    let mut y = 0.0;
+    let mut baseline = None;
+    stack_captions(&captions, false, containing, &mut y, &mut children, ctx);
+    if let Some(grid_box) = grid_box {
+        let table = Table::new(grid_box, containing.width);
+        let (mut grid, grid_baseline) = table.layout(containing.width, containing.width, ctx);
+        grid.rect = grid.rect.translate(egui::vec2(0.0, y));
+        baseline = grid_baseline.map(|baseline| baseline + y);
+        y += grid.rect.height();
+        children.push(grid);
+    }
+    stack_captions(&captions, true, containing, &mut y, &mut children, ctx);
+    TableLayout {
+        children,
+        height: y,
+        baseline,
+    }
+}
+
+// The content width of a block-level table wrapper: the table's width, widened to fit
+// the captions
+pub(super) fn wrapper_width(
+    wrapper: &LayoutBox,
+    containing_width: f32,
+    available: f32,
+    ctx: &LayoutContext,
+) -> f32 {
+    let (grid_box, captions) = parts(wrapper);
+    let width = grid_box.map_or(0.0, |grid_box| {
+        Table::new(grid_box, containing_width).used_width(containing_width, available, ctx)
+    });
+    captions
+        .iter()
+        .map(|caption| block::intrinsic_outer_widths(caption, ctx).0)
+        .fold(width, f32::max)
+}
+
+// The min-content and max-content widths of a table wrapper
+pub(super) fn intrinsic_widths(wrapper: &LayoutBox, ctx: &LayoutContext) -> (f32, f32) {
+    let (grid_box, captions) = parts(wrapper);
+    let (min, max) = grid_box.map_or((0.0, 0.0), |grid_box| {
+        let table = Table::new(grid_box, 0.0);
+        let extra = table.non_column_width();
+        let (min, max) = width_bounds(&table.column_widths(ctx));
+        let (min, max) = (min + extra, max + extra);
+        match table.style.get("width") {
+            Some(Value::Percentage(_) | Value::Calc(..)) => (min, max),
+            _ => match table.specified_width(0.0) {
+                Some(width) => (width.max(min), width.max(min)),
+                None => (min, max),
+            },
+        }
+    });
+    let captions = captions
+        .iter()
+        .map(|caption| block::intrinsic_outer_widths(caption, ctx).0)
+        .fold(0.0, f32::max);
+    (min.max(captions), max.max(captions))
+}
+
+// Splits a table wrapper into its grid box and its captions
+fn parts(wrapper: &LayoutBox) -> (Option<&LayoutBox>, Vec<&LayoutBox>) {
+    let grid_box = wrapper
+        .children
+        .iter()
+        .find(|child| child.kind == BoxKind::TablePart(TablePart::Grid));
+    let captions = wrapper
+        .children
+        .iter()
+        .filter(|child| child.kind == BoxKind::TablePart(TablePart::Caption))
+        .collect();
+    (grid_box, captions)
+}
+
+// Stacks the captions on one side of the table below `y`
+fn stack_captions(
+    captions: &[&LayoutBox],
+    bottom: bool,
+    containing: ContainingBlock,
+    y: &mut f32,
+    children: &mut Vec<Fragment>,
+    ctx: &LayoutContext,
+) {
+    for caption in captions {
+        if (caption.style.keyword("caption-side") == Some("bottom")) != bottom {
+            continue;
+        }
+        let layout = block::layout_block_level(caption, containing, None, ctx);
+        let mut fragment = layout.fragment;
+        fragment.rect = fragment
+            .rect
+            .translate(egui::vec2(layout.margin.left, *y + layout.margin.top));
+        *y += layout.margin.top + fragment.rect.height() + layout.margin.bottom;
+        children.push(fragment);
+    }
+}
+
+fn box_fragment(layout_box: &LayoutBox, style: &Arc<ComputedStyle>, rect: Rect) -> Fragment {
+    let mut fragment = Fragment::new(FragmentKind::Box, style, rect);
+    fragment.node = layout_box.node.clone();
+    fragment
+}
+
+// The widths a cell asks of the columns it spans
+fn cell_width(cell: &LayoutBox, ctx: &LayoutContext) -> ColumnWidth {
+    let style = &cell.style;
+    let extra = block::border(style).horizontal() + block::padding(style, 0.0).horizontal();
+    let (min, max) = block::intrinsic_content_widths(cell, ctx);
+    let min = min + extra;
+    let (fixed, percent) = match style.get("width") {
+        Some(Value::Percentage(percent)) => (None, Some(*percent)),
+        _ => (
+            block::content_size(style, "width", 0.0, extra).map(|width| (width + extra).max(min)),
+            None,
+        ),
+    };
+    ColumnWidth {
+        min,
+        max: fixed.unwrap_or(max + extra).max(min),
+        fixed,
+        percent,
+    }
+}
+
+// The sums of the columns' smallest and preferred widths
+fn width_bounds(widths: &[ColumnWidth]) -> (f32, f32) {
+    let min = widths.iter().map(|width| width.base(0.0)).sum();
+    let max = widths.iter().map(|width| width.max).sum();
+    (min, max)
+}
+
+// Grows a run of columns so a spanning cell fits, in proportion to their preferred widths
+fn spread(widths: &mut [ColumnWidth], total: f32, field: fn(&mut ColumnWidth) -> &mut f32) {
+    let current: f32 = widths.iter_mut().map(|width| *field(width)).sum();
+    if total <= current {
+        return;
+    }
+    let weight: f32 = widths.iter().map(|width| width.max).sum();
+    let count = widths.len() as f32;
+    for width in widths.iter_mut() {
+        let share = match weight > 0.0 {
+            true => width.max / weight,
+            false => 1.0 / count,
+        };
+        *field(width) += (total - current) * share;
+    }
+}
+
+// Shares the width of the columns out between them. Up to the sum of their preferred
+// widths every column moves from its smallest towards its preferred width, and beyond
+// that the auto columns take the rest.
+fn distribute(widths: &[ColumnWidth], target: f32) -> Vec<f32> {
+    let base: Vec<f32> = widths.iter().map(|width| width.base(target)).collect();
+    let preferred: Vec<f32> = widths
+        .iter()
+        .zip(&base)
+        .map(|(width, base)| match width.is_auto() {
+            true => width.max,
+            false => *base,
+        })
+        .collect();
+    let base_sum: f32 = base.iter().sum();
+    let preferred_sum: f32 = preferred.iter().sum();
+    if target <= base_sum {
+        return base;
+    }
+    if target <= preferred_sum {
+        let fraction = (target - base_sum) / (preferred_sum - base_sum);
+        return base
+            .iter()
+            .zip(&preferred)
+            .map(|(base, preferred)| base + (preferred - base) * fraction)
+            .collect();
+    }
+    let extra = target - preferred_sum;
+    let growing: Vec<bool> = match widths.iter().any(ColumnWidth::is_auto) {
+        true => widths.iter().map(ColumnWidth::is_auto).collect(),
+        false => vec![true; widths.len()],
+    };
+    let weight: f32 = preferred
+        .iter()
+        .zip(&growing)
+        .filter(|(_, growing)| **growing)
+        .map(|(preferred, _)| preferred)
+        .sum();
+    let count = growing.iter().filter(|growing| **growing).count() as f32;
+    preferred
+        .iter()
+        .zip(&growing)
+        .map(|(preferred, growing)| match (growing, weight > 0.0) {
+            (false, _) => *preferred,
+            (true, true) => preferred + extra * preferred / weight,
+            (true, false) => preferred + extra / count,
+        })
+        .collect()
+}
+
+fn larger(a: Option<f32>, b: Option<f32>) -> Option<f32> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn is_baseline_aligned(style: &ComputedStyle) -> bool {
+    !matches!(
+        style.keyword("vertical-align"),
+        Some("top" | "middle" | "bottom")
+    )
+}
+
+// Distance from the top of a cell to its baseline, or to the bottom of its content box
+// when it has no lines
+fn cell_ascent(layout: &BlockLayout) -> f32 {
+    let fragment = &layout.fragment;
+    layout.baseline.unwrap_or_else(|| {
+        fragment.rect.height() - fragment.border.bottom - fragment.padding.bottom
+    })
+}
+
+fn border_edges(style: &ComputedStyle) -> [Edge; 4] {
+    let widths = block::border(style);
+    let widths = [widths.top, widths.right, widths.bottom, widths.left];
+    std::array::from_fn(|side| Edge {
+        width: widths[side],
+        style: style
+            .keyword(&format!("border-{}-style", SIDES[side]))
+            .unwrap_or("none")
+            .to_string(),
+        color: style.color(&format!("border-{}-color", SIDES[side])),
+    })
+}
+
+// Gives a style half of each collapsed edge
+fn set_border(style: &mut ComputedStyle, edges: &[Edge; 4]) {
+    for (side, edge) in SIDES.iter().zip(edges) {
+        style.set(
+            &format!("border-{}-width", side),
+            Value::Length(edge.width / 2.0),
+        );
+        style.set(
+            &format!("border-{}-style", side),
+            Value::keyword(&edge.style),
+        );
+        style.set(&format!("border-{}-color", side), Value::Color(edge.color));
+    }
+}
+
+// Picks the border that wins a collapsed edge: hidden beats everything, then wider
+// borders, then the more solid style. Ties go to the first.
+fn stronger(a: Edge, b: Edge) -> Edge {
+    let rank = |style: &str| {
+        [
+            "none", "inset", "groove", "outset", "ridge", "dotted", "dashed", "solid", "double",
+        ]
+        .iter()
+        .position(|name| *name == style)
+        .unwrap_or(0)
+    };
+    if a.style == "hidden" {
+        return a;
+    }
+    if b.style == "hidden"
+        || b.width > a.width
+        || (b.width == a.width && rank(&b.style) > rank(&a.style))
+    {
+        return b;
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::layout::testing::{layout, rect, Rect};
+
+    // The border boxes of the cells in a table without captions, in document order
+    fn cell_rects(body: &str) -> Vec<Rect> {
+        let body = layout(body);
+        // wrapper > grid > row group > row > cell
+        let grid = &body.children[0].children[0].children[0];
+        grid.children
+            .iter()
+            .flat_map(|group| &group.children)
+            .flat_map(|row| &row.children)
+            .map(rect)
+            .collect()
+    }
+
+    #[test]
+    fn test_table_fixtures() {
+        // Each fixture is a table in a 400px wide body and the expected rects of its
+        // cells. Characters are 8px wide and lines 20px tall, and tables have no spacing
+        // or cell padding unless a fixture gives them.
+        let style = "<style>table { border-spacing: 0 } td { padding: 0 }</style>";
+        let fixtures: &[(&str, &[Rect])] = &[
+            // Auto columns take their content's width
+            (
+                "<table><tr><td>aa</td><td>aaaa</td></tr></table>",
+                &[(0.0, 0.0, 16.0, 20.0), (16.0, 0.0, 32.0, 20.0)],
+            ),
+            // A wider table shares the extra space in proportion to the content
+            (
+                "<table width='96'><tr><td>aa</td><td>aaaa</td></tr></table>",
+                &[(0.0, 0.0, 32.0, 20.0), (32.0, 0.0, 64.0, 20.0)],
+            ),
+            // A narrower table wraps the text down to its longest word
+            (
+                "<table width='50'><tr><td>aa aa</td><td>aaa</td></tr></table>",
+                &[(0.0, 0.0, 26.0, 40.0), (26.0, 0.0, 24.0, 40.0)],
+            ),
+            // Fixed cell widths are kept, and the auto column takes the rest
+            (
+                "<table width='200'><tr><td width='50'>a</td><td>a</td></tr></table>",
+                &[(0.0, 0.0, 50.0, 20.0), (50.0, 0.0, 150.0, 20.0)],
+            ),
+            // Percentage widths resolve against the table
+            (
+                "<table width='200'><tr><td width='25%'>a</td><td>a</td></tr></table>",
+                &[(0.0, 0.0, 50.0, 20.0), (50.0, 0.0, 150.0, 20.0)],
+            ),
+            // Spacing around and between the cells, and cell padding
+            (
+                "<table style='border-spacing: 4px'>\
+                 <tr><td style='padding: 2px'>a</td><td style='padding: 2px'>a</td></tr></table>",
+                &[(4.0, 4.0, 12.0, 24.0), (20.0, 4.0, 12.0, 24.0)],
+            ),
+            // colspan and rowspan fill the slots, pushing later cells along
+            (
+                "<table><tr><td colspan='2'>aaaa</td><td rowspan='2'>a<br>a<br>a</td></tr>\
+                 <tr><td>a</td><td>a</td></tr></table>",
+                &[
+                    (0.0, 0.0, 32.0, 30.0),
+                    (32.0, 0.0, 8.0, 60.0),
+                    (0.0, 30.0, 16.0, 30.0),
+                    (16.0, 30.0, 16.0, 30.0),
+                ],
+            ),
+            // Fixed layout takes widths from the first row and shares the rest equally
+            (
+                "<table style='table-layout: fixed; width: 300px'>\
+                 <tr><td style='width: 100px'>a</td><td>a</td><td>a</td></tr>\
+                 <tr><td>aaaaaaaa aaaaaaaa</td><td>a</td><td>a</td></tr></table>",
+                &[
+                    (0.0, 0.0, 100.0, 20.0),
+                    (100.0, 0.0, 100.0, 20.0),
+                    (200.0, 0.0, 100.0, 20.0),
+                    (0.0, 20.0, 100.0, 40.0),
+                    (100.0, 20.0, 100.0, 40.0),
+                    (200.0, 20.0, 100.0, 40.0),
+                ],
+            ),
+        ];
+        for (table, expected) in fixtures {
+            let source = format!("{}{}", style, table);
+            assert_eq!(cell_rects(&source), *expected, "{}", table);
+        }
+    }
+
+    #[test]
+    fn test_table_sections_and_captions() {
+        // The footer moves to the end and the header to the start, with a caption above
+        let body = layout(
+            "<table style='border-spacing: 0'><caption>cap</caption>\
+             <tfoot><tr><td>f</td></tr></tfoot><tbody><tr><td>b</td></tr></tbody>\
+             <thead><tr><td>h</td></tr></thead></table>",
+        );
+        let wrapper = &body.children[0].children[0];
+        let caption = &wrapper.children[0];
+        assert_eq!(caption.rect.height(), 20.0);
+        let grid = &wrapper.children[1];
+        assert_eq!(grid.rect.min.y, 20.0);
+        let group_tops: Vec<f32> = grid.children.iter().map(|group| group.rect.min.y).collect();
+        assert_eq!(group_tops, vec![20.0, 42.0, 64.0]);
+        assert!(grid.children[0].dump().contains("\"h\""));
+        assert!(grid.children[2].dump().contains("\"f\""));
+        assert_eq!(wrapper.rect.height(), 86.0);
+
+        // Collapsed borders are shared between neighbouring cells
+        let body = layout(
+            "<table style='border-collapse: collapse; border: 4px solid'>\
+             <tr><td style='border: 2px solid; padding: 0'>a</td>\
+             <td style='border: 2px solid; padding: 0'>a</td></tr></table>",
+        );
+        let grid = &body.children[0].children[0].children[0];
+        assert_eq!(grid.border.left, 2.0);
+        let row = &grid.children[0].children[0];
+        let cells: Vec<(f32, f32, f32)> = row
+            .children
+            .iter()
+            .map(|cell| (cell.rect.min.x, cell.border.left, cell.border.right))
+            .collect();
+        assert_eq!(cells, vec![(2.0, 2.0, 1.0), (13.0, 1.0, 2.0)]);
+        assert_eq!(grid.rect.width(), 26.0);
+    }
+}
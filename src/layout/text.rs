@@ -0,0 +1,75 @@
+// Text measurement for layout, backed by egui's font system
+
+use eframe::egui;
+use egui::epaint::{FontFamily, FontId, Fonts};
+
+/// Vertical metrics of a font at a given size
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FontMetrics {
+    pub ascent: f32,
+    // The height of a line of text in the font, which `line-height: normal` uses
+    pub height: f32,
+}
+
+impl FontMetrics {
+    pub fn descent(&self) -> f32 {
+        self.height - self.ascent
+    }
+}
+
+/// Measures text for layout
+pub trait TextMeasurer {
+    fn text_width(&self, text: &str, font: &FontId) -> f32;
+    fn font_metrics(&self, font: &FontId) -> FontMetrics;
+    // The font families that fonts can currently be looked up in
+    fn families(&self) -> Vec<FontFamily>;
+}
+
+impl TextMeasurer for Fonts {
+    fn text_width(&self, text: &str, font: &FontId) -> f32 {
+        self.layout_no_wrap(text.to_string(), font.clone(), egui::Color32::PLACEHOLDER)
+            .size()
+            .x
+    }
+
+    fn font_metrics(&self, font: &FontId) -> FontMetrics {
+        let galley = self.layout_no_wrap("x".to_string(), font.clone(), egui::Color32::PLACEHOLDER);
+        let ascent = galley
+            .rows
+            .first()
+            .and_then(|row| row.glyphs.first())
+            .map(|glyph| glyph.font_ascent)
+            .unwrap_or(font.size * 0.8);
+        FontMetrics {
+            ascent,
+            height: self.row_height(font),
+        }
+    }
+
+    fn families(&self) -> Vec<FontFamily> {
+        Fonts::families(self)
+    }
+}
+
+// Metrics where every character is half the font size wide, so tests can check exact
+// positions
+#[cfg(test)]
+pub(crate) struct FixedMetrics;
+
+#[cfg(test)]
+impl TextMeasurer for FixedMetrics {
+    fn text_width(&self, text: &str, font: &FontId) -> f32 {
+        text.chars().count() as f32 * font.size / 2.0
+    }
+
+    fn font_metrics(&self, font: &FontId) -> FontMetrics {
+        FontMetrics {
+            ascent: font.size * 0.75,
+            height: font.size * 1.25,
+        }
+    }
+
+    fn families(&self) -> Vec<FontFamily> {
+        vec![FontFamily::Proportional, FontFamily::Monospace]
+    }
+}
@@ -1,9 +1,16 @@
+pub mod css;
 pub mod dom;
+pub mod fonts;
 pub mod html;
+pub mod images;
+pub mod layout;
 pub mod networking;
+pub mod rendering;
 pub mod state;
 pub mod ui;
 
+use std::path::Path;
+
 // Start the browser
 pub fn start_browser() -> eframe::Result {
     let options = eframe::NativeOptions {
@@ -18,3 +25,51 @@ pub fn start_browser() -> eframe::Result {
         Box::new(|cc| Ok(Box::new(state::OliverExplorer::new(cc)))),
     )
 }
+
+// Renders the page at `url` in a viewport of `width` by `height` CSS pixels to a PNG file,
+// without opening a window
+pub fn screenshot(url: &url::Url, width: f32, height: f32, path: &Path) -> Result<(), String> {
+    let html = networking::fetch_url(url).map_err(|e| format!("Failed to fetch URL: {}", e))?;
+    let mut page = state::Page::new(&html, url, css::Device::default());
+    // The page's image textures belong to the headless renderer
+    page.images = images::ImageCache::default();
+    let image = page.screenshot(eframe::egui::vec2(width, height))?;
+    rendering::save_png(&image, path)
+}
+
+// Runs `oliver_explorer --screenshot URL OUTPUT.png [--size WIDTHxHEIGHT]`
+pub fn screenshot_command(args: &[String]) -> Result<(), String> {
+    let usage = "Usage: oliver_explorer --screenshot URL OUTPUT.png [--size WIDTHxHEIGHT]";
+    let (url, path, options) = match args {
+        [url, path, options @ ..] => (url, path, options),
+        _ => return Err(usage.to_string()),
+    };
+    let url = url::Url::parse(url).map_err(|e| format!("Failed to parse URL: {}", e))?;
+    let (width, height) = match options {
+        [] => (1280.0, 800.0),
+        [flag, size] if flag == "--size" => size
+            .split_once('x')
+            .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+            .ok_or_else(|| format!("Invalid size: {}", size))?,
+        _ => return Err(usage.to_string()),
+    };
+    screenshot(&url, width, height, Path::new(path))
+}
+
+// Prints the page at `url` to a PDF file, without opening a window
+pub fn print_to_pdf(url: &url::Url, path: &Path) -> Result<(), String> {
+    let html = networking::fetch_url(url).map_err(|e| format!("Failed to fetch URL: {}", e))?;
+    let mut page = state::Page::new(&html, url, css::Device::default());
+    page.images = images::ImageCache::default();
+    let pdf = page.print_to_pdf()?;
+    std::fs::write(path, pdf).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+// Runs `oliver_explorer --pdf URL OUTPUT.pdf`
+pub fn pdf_command(args: &[String]) -> Result<(), String> {
+    let [url, path] = args else {
+        return Err("Usage: oliver_explorer --pdf URL OUTPUT.pdf".to_string());
+    };
+    let url = url::Url::parse(url).map_err(|e| format!("Failed to parse URL: {}", e))?;
+    print_to_pdf(&url, Path::new(path))
+}
@@ -1,5 +1,13 @@
-use oliver_explorer;
-
 pub fn main() -> eframe::Result<()> {
-    oliver_explorer::start_browser()
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = match args.first().map(String::as_str) {
+        Some("--screenshot") => oliver_explorer::screenshot_command,
+        Some("--pdf") => oliver_explorer::pdf_command,
+        _ => return oliver_explorer::start_browser(),
+    };
+    if let Err(e) = command(&args[1..]) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    Ok(())
 }
@@ -3,6 +3,17 @@ use std::time::Duration;
 use url::Url;
 
 pub fn fetch_url(url: &Url) -> Result<String, Box<dyn Error>> {
+    let body = get(url)?.text()?;
+    Ok(body)
+}
+
+// Fetches a binary resource such as a font or an image
+pub fn fetch_bytes(url: &Url) -> Result<Vec<u8>, Box<dyn Error>> {
+    let body = get(url)?.bytes()?;
+    Ok(body.to_vec())
+}
+
+fn get(url: &Url) -> Result<reqwest::blocking::Response, Box<dyn Error>> {
     let client = reqwest::blocking::ClientBuilder::new()
         .user_agent("OliverExplorer/0.1")
         .timeout(Duration::from_secs(30))
@@ -14,9 +25,7 @@ pub fn fetch_url(url: &Url) -> Result<String, Box<dyn Error>> {
     if !response.status().is_success() {
         return Err(format!("HTTP error: {}", response.status()).into());
     }
-
-    let body = response.text()?;
-    Ok(body)
+    Ok(response)
 }
 
 #[cfg(test)]
@@ -70,4 +79,18 @@ mod tests {
         // Verify the request was made and failed
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_fetch_bytes() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/font.woff2")
+            .with_body([0u8, 1, 2, 255])
+            .create();
+        let url = Url::parse(&format!("{}/font.woff2", server.url())).unwrap();
+        let result = fetch_bytes(&url);
+
+        mock.assert();
+        assert_eq!(result.unwrap(), vec![0, 1, 2, 255]);
+    }
 }
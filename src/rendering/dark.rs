@@ -0,0 +1,193 @@
+// Forced dark mode: display lists recolored so light pages show dark. Colors have their
+// lightness inverted in Oklab, which keeps their hue and looks even to the eye. Images
+// keep their colors, except mostly transparent ones such as icons, which are drawn over
+// the page's colors and would vanish against them.
+
+use crate::images::LoadedImage;
+use crate::layout::ImageContent;
+use crate::rendering::{DisplayItem, DisplayList};
+use eframe::egui::{ecolor, Color32};
+use std::sync::Arc;
+
+// The lightness white and black turn into, so pages match the app's dark theme rather
+// than being pure black and white
+const DARKEST: f32 = 0.2;
+const LIGHTEST: f32 = 0.93;
+
+// The lightness below which a canvas is already dark
+const DARK_CANVAS: f32 = 0.5;
+
+// The share of an image's pixels that must be transparent for it to be recolored
+const ICON_TRANSPARENCY: f32 = 0.5;
+
+// Recolors everything in the list, and the canvas, which is white on pages without a
+// background. Pages already on a dark canvas, such as those with dark styles for
+// `prefers-color-scheme`, are left as they are.
+pub fn force_dark(list: &mut DisplayList) {
+    let canvas = list.background.unwrap_or(Color32::WHITE);
+    if oklab([canvas.r(), canvas.g(), canvas.b()])[0] < DARK_CANVAS {
+        return;
+    }
+    list.background = Some(dark_color(canvas));
+    for item in &mut list.items {
+        match item {
+            DisplayItem::Rect { color, .. } | DisplayItem::Text { color, .. } => {
+                *color = dark_color(*color);
+            }
+            DisplayItem::Border { sides, .. } => {
+                for side in sides {
+                    side.color = dark_color(side.color);
+                }
+            }
+            DisplayItem::Image { image, .. } => {
+                if let ImageContent::Loaded { image, .. } = &mut image.content {
+                    if let Some(dark) = dark_image(image) {
+                        *image = dark;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// A color with its lightness inverted, keeping its hue and alpha
+pub fn dark_color(color: Color32) -> Color32 {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    let [lightness, green_red, blue_yellow] = oklab([r, g, b]);
+    let lightness = DARKEST + (1.0 - lightness) * (LIGHTEST - DARKEST);
+    let [r, g, b] = srgb([lightness, green_red, blue_yellow]);
+    Color32::from_rgba_unmultiplied(r, g, b, a)
+}
+
+// The recolored version of a mostly transparent image, made once and kept with the image
+// so its texture is only uploaded once. None for images that keep their colors.
+fn dark_image(image: &LoadedImage) -> Option<Arc<LoadedImage>> {
+    image
+        .dark
+        .get_or_init(|| {
+            let pixels = &image.pixels.pixels;
+            let transparent = pixels.iter().filter(|pixel| pixel.a() < 128).count();
+            if (transparent as f32) < pixels.len() as f32 * ICON_TRANSPARENCY {
+                return None;
+            }
+            let mut dark = image.pixels.clone();
+            for pixel in &mut dark.pixels {
+                if pixel.a() > 0 {
+                    *pixel = dark_color(*pixel);
+                }
+            }
+            Some(Arc::new(LoadedImage::new(image.url.clone(), dark)))
+        })
+        .clone()
+}
+
+// Converts sRGB to Oklab lightness and its two color axes
+fn oklab([r, g, b]: [u8; 3]) -> [f32; 3] {
+    let [r, g, b] = [r, g, b].map(ecolor::linear_f32_from_gamma_u8);
+    let l = (0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b).cbrt();
+    let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+    let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+    [
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+    ]
+}
+
+// Converts Oklab back to sRGB, clipping colors outside its gamut
+fn srgb([lightness, a, b]: [f32; 3]) -> [u8; 3] {
+    let l = (lightness + 0.396_337_78 * a + 0.215_803_76 * b).powi(3);
+    let m = (lightness - 0.105_561_346 * a - 0.063_854_17 * b).powi(3);
+    let s = (lightness - 0.089_484_18 * a - 1.291_485_5 * b).powi(3);
+    [
+        4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s,
+        -1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s,
+        -0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s,
+    ]
+    .map(|channel| ecolor::gamma_u8_from_linear_f32(channel.clamp(0.0, 1.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::Image;
+    use eframe::egui::{self, Pos2, Rect};
+
+    // The hue of a color in Oklab, in radians
+    fn hue(color: Color32) -> f32 {
+        let [_, a, b] = oklab([color.r(), color.g(), color.b()]);
+        b.atan2(a)
+    }
+
+    #[test]
+    fn test_force_dark() {
+        // White turns dark and black light, both staying grey
+        let white = dark_color(Color32::WHITE);
+        let black = dark_color(Color32::BLACK);
+        assert!(white.r() < 40 && white.r() == white.g() && white.g() == white.b());
+        assert!(black.r() > 220 && black.r() == black.g() && black.g() == black.b());
+        // Light colors turn dark with the same hue
+        let pink = Color32::from_rgb(255, 200, 210);
+        let dark_pink = dark_color(pink);
+        assert!(oklab([dark_pink.r(), dark_pink.g(), dark_pink.b()])[0] < 0.4);
+        assert!((hue(pink) - hue(dark_pink)).abs() < 0.05);
+        assert_eq!(dark_color(Color32::from_black_alpha(100)).a(), 100);
+
+        let image = |pixels: Vec<Color32>| Image {
+            src: None,
+            alt: String::new(),
+            width: None,
+            height: None,
+            content: ImageContent::Loaded {
+                image: Arc::new(LoadedImage::new(
+                    url::Url::parse("http://localhost/image.png").unwrap(),
+                    egui::ColorImage {
+                        size: [2, 2],
+                        pixels,
+                    },
+                )),
+                density: 1.0,
+            },
+        };
+        let rect = Rect::from_min_size(Pos2::ZERO, egui::vec2(2.0, 2.0));
+        let item = |image| DisplayItem::Image {
+            rect,
+            fitted: rect,
+            image,
+            opacity: 1.0,
+        };
+        let mut list = DisplayList {
+            items: vec![
+                // A black icon on a transparent background, and a photo
+                item(image(vec![
+                    Color32::BLACK,
+                    Color32::TRANSPARENT,
+                    Color32::TRANSPARENT,
+                    Color32::TRANSPARENT,
+                ])),
+                item(image(vec![Color32::from_rgb(250, 250, 250); 4])),
+            ],
+            ..Default::default()
+        };
+        force_dark(&mut list);
+        assert_eq!(list.background, Some(white));
+        let pixels = |index: usize| match &list.items[index] {
+            DisplayItem::Image { image, .. } => match &image.content {
+                ImageContent::Loaded { image, .. } => image.pixels.pixels.clone(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        assert_eq!(pixels(0)[0], black);
+        assert_eq!(pixels(0)[1], Color32::TRANSPARENT);
+        assert_eq!(pixels(1)[0], Color32::from_rgb(250, 250, 250));
+
+        // Pages that are dark already keep their colors
+        let mut list = DisplayList {
+            background: Some(Color32::from_rgb(20, 20, 30)),
+            ..Default::default()
+        };
+        force_dark(&mut list);
+        assert_eq!(list.background, Some(Color32::from_rgb(20, 20, 30)));
+    }
+}
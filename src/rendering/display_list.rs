@@ -0,0 +1,1083 @@
+// Display lists: the drawing commands for a fragment tree, in painting order
+
+use crate::css::{Color, ComputedStyle, Value};
+use crate::dom::Selection;
+use crate::layout::{Fragment, FragmentKind, Image, TextMeasurer, TextRun};
+use crate::rendering::color32;
+use eframe::egui::{self, Color32, FontId, Pos2, Rangef, Rect, Vec2};
+use std::collections::HashMap;
+use std::ops::Range;
+
+// Backgrounds of selected text, of the matches of a search and of its current match
+pub const SELECTION_COLOR: Color32 = Color32::from_rgb(180, 213, 254);
+pub const MATCH_COLOR: Color32 = Color32::from_rgb(255, 235, 90);
+pub const CURRENT_MATCH_COLOR: Color32 = Color32::from_rgb(255, 150, 50);
+
+/// The scroll offsets of the scroll containers of a page, by the path of their element
+pub type ScrollOffsets = HashMap<Vec<usize>, Vec2>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BorderStyle {
+    Solid,
+    Dashed,
+    Dotted,
+    Double,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BorderSide {
+    pub width: f32,
+    pub color: Color32,
+    pub style: BorderStyle,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DisplayItem {
+    Rect {
+        rect: Rect,
+        color: Color32,
+    },
+    // A border drawn inside `rect`, with sides in the order top, right, bottom, left
+    Border {
+        rect: Rect,
+        sides: [BorderSide; 4],
+    },
+    // A run of text with the top left of its line box at the corner of `rect`
+    Text {
+        rect: Rect,
+        text: String,
+        font: FontId,
+        color: Color32,
+    },
+    // An image in the content box `rect`, scaled to `fitted` by object-fit and clipped to
+    // the content box
+    Image {
+        rect: Rect,
+        fitted: Rect,
+        image: Image,
+        opacity: f32,
+    },
+}
+
+impl DisplayItem {
+    // The area the item paints in
+    pub fn rect(&self) -> Rect {
+        match self {
+            DisplayItem::Rect { rect, .. }
+            | DisplayItem::Border { rect, .. }
+            | DisplayItem::Text { rect, .. }
+            | DisplayItem::Image { rect, .. } => *rect,
+        }
+    }
+}
+
+/// How the items of a layer move when the document scrolls, rather than with it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Attachment {
+    // Fixed in the viewport, with document coordinates measured from its top left
+    Fixed,
+    // Kept inside the viewport by the box's insets, in top, right, bottom, left order,
+    // without leaving its container
+    Sticky {
+        rect: Rect,
+        container: Rect,
+        insets: [Option<f32>; 4],
+    },
+}
+
+/// A run of items painted by a fixed or sticky box
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layer {
+    pub items: Range<usize>,
+    pub attachment: Attachment,
+}
+
+impl Layer {
+    // How far the layer moves from its place in the document while `viewport`, in
+    // document coordinates, is visible
+    pub fn offset(&self, viewport: Rect) -> Vec2 {
+        match &self.attachment {
+            Attachment::Fixed => viewport.min.to_vec2(),
+            Attachment::Sticky {
+                rect,
+                container,
+                insets: [top, right, bottom, left],
+            } => egui::vec2(
+                sticky_shift(
+                    rect.x_range(),
+                    container.x_range(),
+                    viewport.x_range(),
+                    *left,
+                    *right,
+                ),
+                sticky_shift(
+                    rect.y_range(),
+                    container.y_range(),
+                    viewport.y_range(),
+                    *top,
+                    *bottom,
+                ),
+            ),
+        }
+    }
+}
+
+// Moves a sticky box along one axis to keep it `start` from the viewport's start edge or
+// `end` from its end edge, as far as its container allows
+fn sticky_shift(
+    rect: Rangef,
+    container: Rangef,
+    viewport: Rangef,
+    start: Option<f32>,
+    end: Option<f32>,
+) -> f32 {
+    let mut shift: f32 = 0.0;
+    if let Some(start) = start {
+        let limit = (container.max - rect.max).max(0.0);
+        shift = shift.max(viewport.min + start - rect.min).min(limit);
+    }
+    if let Some(end) = end {
+        let limit = (container.min - rect.min).min(0.0);
+        shift = shift.min(viewport.max - end - rect.max).max(limit);
+    }
+    shift
+}
+
+/// A box whose overflow is clipped to its padding box, and which may scroll
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScrollContainer {
+    // The element its offset is kept under, None for anonymous boxes, which can't scroll
+    pub node: Option<Vec<usize>>,
+    pub rect: Rect,
+    // How far the content can scroll on each axis, zero when it fits or the axis is hidden
+    pub max_offset: Vec2,
+    // The scroll container it's inside, and the fixed or sticky layer it moves with
+    pub parent: Option<usize>,
+    pub layer: Option<usize>,
+}
+
+impl ScrollContainer {
+    pub fn can_scroll(&self) -> bool {
+        self.node.is_some() && (self.max_offset.x > 0.0 || self.max_offset.y > 0.0)
+    }
+
+    // The container's offset, kept within the distance its content can scroll
+    pub fn offset(&self, offsets: &ScrollOffsets) -> Vec2 {
+        let offset = self
+            .node
+            .as_ref()
+            .and_then(|node| offsets.get(node))
+            .copied()
+            .unwrap_or_default();
+        offset.clamp(Vec2::ZERO, self.max_offset)
+    }
+}
+
+/// Drawing commands for a page in the order they're painted, in document coordinates
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DisplayList {
+    // The color of the canvas behind the document, from the root or body background
+    pub background: Option<Color32>,
+    pub items: Vec<DisplayItem>,
+    // Items that don't scroll with the document, which are otherwise painted in place
+    pub layers: Vec<Layer>,
+    pub scroll_containers: Vec<ScrollContainer>,
+    // The innermost scroll container of each item
+    pub containers: Vec<Option<usize>>,
+    // The size of the area from the origin that contains everything painted outside
+    // scroll containers
+    pub size: Vec2,
+}
+
+impl DisplayList {
+    // How far an item's layer moves it from its place in the document while `viewport`
+    // is visible
+    pub fn layer_offset(&self, index: usize, viewport: Rect) -> Vec2 {
+        self.layers
+            .iter()
+            .find(|layer| layer.items.contains(&index))
+            .map_or(Vec2::ZERO, |layer| layer.offset(viewport))
+    }
+
+    // Where an item paints while `viewport` is visible and its scroll containers are
+    // scrolled: how far it moves from its place in the document, and the area it's
+    // clipped to after moving
+    pub fn placement(&self, index: usize, viewport: Rect, offsets: &ScrollOffsets) -> (Vec2, Rect) {
+        let mut offset = self.layer_offset(index, viewport);
+        let mut clip = Rect::EVERYTHING;
+        for container in self
+            .chain(self.containers.get(index).copied().flatten())
+            .rev()
+        {
+            let container = &self.scroll_containers[container];
+            clip = clip.intersect(container.rect.translate(offset));
+            offset -= container.offset(offsets);
+        }
+        (offset, clip)
+    }
+
+    // The scroll containers under a point of the viewport, innermost first, that can
+    // scroll
+    pub fn scroll_chain_at(
+        &self,
+        point: Pos2,
+        viewport: Rect,
+        offsets: &ScrollOffsets,
+    ) -> Vec<usize> {
+        let innermost = (0..self.scroll_containers.len())
+            .filter(|&index| {
+                self.container_rect(index, viewport, offsets)
+                    .contains(point)
+            })
+            .max_by_key(|&index| self.chain(Some(index)).count());
+        self.chain(innermost)
+            .filter(|&index| self.scroll_containers[index].can_scroll())
+            .collect()
+    }
+
+    // The scroll containers whose content holds a rect in document coordinates, innermost
+    // first, that can scroll
+    pub fn scroll_chain_around(&self, rect: Rect) -> Vec<usize> {
+        let innermost = (0..self.scroll_containers.len())
+            .filter(|&index| {
+                let container = &self.scroll_containers[index];
+                let content = Rect::from_min_max(
+                    container.rect.min,
+                    container.rect.max + container.max_offset,
+                );
+                content.contains(rect.min)
+            })
+            .max_by_key(|&index| self.chain(Some(index)).count());
+        self.chain(innermost)
+            .filter(|&index| self.scroll_containers[index].can_scroll())
+            .collect()
+    }
+
+    // The part of a scroll container's padding box that shows on the page, in document
+    // coordinates
+    pub fn container_rect(&self, index: usize, viewport: Rect, offsets: &ScrollOffsets) -> Rect {
+        let mut offset = self.scroll_containers[index]
+            .layer
+            .map_or(Vec2::ZERO, |layer| self.layers[layer].offset(viewport));
+        let mut clip = Rect::EVERYTHING;
+        for container in self.chain(Some(index)).rev() {
+            let container = &self.scroll_containers[container];
+            clip = clip.intersect(container.rect.translate(offset));
+            offset -= container.offset(offsets);
+        }
+        clip
+    }
+
+    // A scroll container and the ones around it, innermost first
+    fn chain(&self, innermost: Option<usize>) -> std::vec::IntoIter<usize> {
+        let mut chain = Vec::new();
+        let mut next = innermost;
+        while let Some(index) = next {
+            chain.push(index);
+            next = self.scroll_containers[index].parent;
+        }
+        chain.into_iter()
+    }
+}
+
+// A positioned box, painted after the rest of its stacking context in z-index order
+struct Positioned<'f> {
+    fragment: &'f Fragment,
+    // The opacity of its ancestors
+    opacity: f32,
+    // The content box of the nearest block around it, which a sticky box stays inside
+    container: Rect,
+    // The innermost scroll container around it
+    clip: Option<usize>,
+}
+
+// A text decoration line propagated from an inline or block box to the text inside it
+#[derive(Debug, Clone, Copy)]
+struct Decoration {
+    line: DecorationLine,
+    color: Color,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DecorationLine {
+    Underline,
+    Overline,
+    LineThrough,
+}
+
+// Builds the display list of a laid out document. Items are in CSS painting order: the
+// backgrounds and borders of block boxes, then inline content with atomic inlines painted
+// as a unit, with positioned boxes below or above it by z-index, then outlines over
+// everything.
+pub fn build_display_list(root: &Fragment) -> DisplayList {
+    build(root, &[], None)
+}
+
+// Builds the display list with ranges of text highlighted in the given colors, measuring
+// the runs they cover. Later ranges are painted over earlier ones.
+pub fn build_display_list_with_highlights(
+    root: &Fragment,
+    highlights: &[(&Selection, Color32)],
+    measurer: &dyn TextMeasurer,
+) -> DisplayList {
+    build(root, highlights, Some(measurer))
+}
+
+fn build(
+    root: &Fragment,
+    highlights: &[(&Selection, Color32)],
+    measurer: Option<&dyn TextMeasurer>,
+) -> DisplayList {
+    // The root's background covers the canvas, or the body's if the root has none
+    let body = root
+        .children
+        .iter()
+        .find(|child| child.kind == FragmentKind::Box && child.node.is_some());
+    let canvas = match (background(root), body) {
+        (Some(_), _) | (None, None) => root,
+        (None, Some(body)) => body,
+    };
+    let mut builder = Builder {
+        root,
+        canvas,
+        items: Vec::new(),
+        containers: Vec::new(),
+        outlines: Vec::new(),
+        layers: Vec::new(),
+        layered: false,
+        scroll_containers: Vec::new(),
+        entered: HashMap::new(),
+        current: None,
+        highlights,
+        measurer,
+    };
+    builder.atomic(root, 1.0);
+    let mut items = builder.items;
+    let mut containers = builder.containers;
+    for (outline, container) in builder.outlines {
+        items.push(outline);
+        containers.push(container);
+    }
+    let size = items
+        .iter()
+        .zip(&containers)
+        .filter(|(_, container)| container.is_none())
+        .map(|(item, _)| item.rect())
+        .fold(root.rect.max.to_vec2(), |size, rect| {
+            size.max(rect.max.to_vec2())
+        });
+    DisplayList {
+        background: background(canvas).map(|color| color32(color, 1.0)),
+        items,
+        layers: builder.layers,
+        scroll_containers: builder.scroll_containers,
+        containers,
+        size,
+    }
+}
+
+struct Builder<'a> {
+    root: &'a Fragment,
+    // The fragment whose background is painted as the canvas rather than on its own
+    canvas: &'a Fragment,
+    items: Vec<DisplayItem>,
+    containers: Vec<Option<usize>>,
+    outlines: Vec<(DisplayItem, Option<usize>)>,
+    layers: Vec<Layer>,
+    // Whether the items being added are already in a layer, which moves them all
+    layered: bool,
+    scroll_containers: Vec<ScrollContainer>,
+    // The scroll container made for each fragment, by address, as painting visits boxes
+    // more than once
+    entered: HashMap<usize, usize>,
+    // The scroll container the items being added are in
+    current: Option<usize>,
+    highlights: &'a [(&'a Selection, Color32)],
+    measurer: Option<&'a dyn TextMeasurer>,
+}
+
+impl Builder<'_> {
+    fn push(&mut self, item: DisplayItem) {
+        self.items.push(item);
+        self.containers.push(self.current);
+    }
+
+    // The scroll container for the contents of a fragment inside `parent`, which is
+    // `parent` itself unless the fragment clips its overflow
+    fn scroll_container(&mut self, fragment: &Fragment, parent: Option<usize>) -> Option<usize> {
+        if fragment.kind != FragmentKind::Box
+            || std::ptr::eq(fragment, self.root)
+            || !clips_overflow(&fragment.style)
+        {
+            return parent;
+        }
+        let address = fragment as *const Fragment as usize;
+        if let Some(&index) = self.entered.get(&address) {
+            return Some(index);
+        }
+        let rect = fragment.padding_rect();
+        let extent = overflow_extent(fragment);
+        let scrolls = |name: &str| matches!(fragment.style.keyword(name), Some("auto" | "scroll"));
+        let max_offset = (extent.max - rect.max).max(Vec2::ZERO);
+        self.scroll_containers.push(ScrollContainer {
+            node: fragment.node.clone(),
+            rect,
+            max_offset: egui::vec2(
+                if scrolls("overflow-x") {
+                    max_offset.x
+                } else {
+                    0.0
+                },
+                if scrolls("overflow-y") {
+                    max_offset.y
+                } else {
+                    0.0
+                },
+            ),
+            parent,
+            layer: self.layered.then_some(self.layers.len()),
+        });
+        let index = self.scroll_containers.len() - 1;
+        self.entered.insert(address, index);
+        Some(index)
+    }
+
+    // Makes the items that follow go in a fragment's scroll container, returning the
+    // container to go back to
+    fn enter(&mut self, fragment: &Fragment) -> Option<usize> {
+        let previous = self.current;
+        self.current = self.scroll_container(fragment, previous);
+        previous
+    }
+
+    // Paints a box and everything in it as a unit, as for the root, inline-blocks and
+    // positioned boxes. Its positioned descendants with negative z-indices go below its
+    // in-flow content, and the rest above it.
+    fn atomic(&mut self, fragment: &Fragment, opacity: f32) {
+        let opacity = opacity * own_opacity(&fragment.style);
+        self.box_decorations(fragment, opacity);
+        let previous = self.enter(fragment);
+        let mut positioned = Vec::new();
+        let clip = self.current;
+        self.positioned_descendants(
+            fragment,
+            opacity,
+            fragment.content_rect(),
+            clip,
+            &mut positioned,
+        );
+        positioned.sort_by(|a, b| z_index(a.fragment).total_cmp(&z_index(b.fragment)));
+        let below = positioned.partition_point(|entry| z_index(entry.fragment) < 0.0);
+
+        for entry in &positioned[..below] {
+            self.positioned(entry);
+        }
+        self.block_backgrounds(fragment, opacity);
+        self.inline_content(fragment, opacity, &decorations(&fragment.style, &[]));
+        for entry in &positioned[below..] {
+            self.positioned(entry);
+        }
+        self.current = previous;
+    }
+
+    // Finds the positioned descendants of a box that paint in its stacking context,
+    // without looking inside them or inside the atomic inlines that paint their own
+    fn positioned_descendants<'f>(
+        &mut self,
+        fragment: &'f Fragment,
+        opacity: f32,
+        container: Rect,
+        clip: Option<usize>,
+        found: &mut Vec<Positioned<'f>>,
+    ) {
+        for child in &fragment.children {
+            if is_positioned(child) {
+                found.push(Positioned {
+                    fragment: child,
+                    opacity,
+                    container,
+                    clip,
+                });
+                continue;
+            }
+            let in_line = matches!(fragment.kind, FragmentKind::Line | FragmentKind::Inline);
+            match child.kind {
+                FragmentKind::Box if !in_line => {
+                    let opacity = opacity * own_opacity(&child.style);
+                    let clip = self.scroll_container(child, clip);
+                    self.positioned_descendants(child, opacity, child.content_rect(), clip, found);
+                }
+                FragmentKind::Inline => {
+                    let opacity = opacity * own_opacity(&child.style);
+                    self.positioned_descendants(child, opacity, container, clip, found);
+                }
+                // Lines share the style of their block
+                FragmentKind::Line => {
+                    self.positioned_descendants(child, opacity, container, clip, found)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Paints a positioned box, putting it in a layer if it's fixed or sticky
+    fn positioned(&mut self, entry: &Positioned) {
+        let fragment = entry.fragment;
+        let attachment = match fragment.style.keyword("position") {
+            _ if self.layered => None,
+            Some("fixed") => Some(Attachment::Fixed),
+            Some("sticky") => Some(Attachment::Sticky {
+                rect: fragment.rect,
+                container: entry.container,
+                insets: ["top", "right", "bottom", "left"]
+                    .map(|side| fragment.style.length(side, 0.0)),
+            }),
+            _ => None,
+        };
+        let start = self.items.len();
+        self.layered |= attachment.is_some();
+        let previous = std::mem::replace(&mut self.current, entry.clip);
+        // Decorations don't propagate into positioned boxes
+        self.inline_level(fragment, entry.opacity, &[]);
+        self.current = previous;
+        if let Some(attachment) = attachment {
+            self.layered = false;
+            self.layers.push(Layer {
+                items: start..self.items.len(),
+                attachment,
+            });
+        }
+    }
+
+    // Paints the backgrounds and borders of the block-level descendants of a box
+    fn block_backgrounds(&mut self, fragment: &Fragment, opacity: f32) {
+        for child in &fragment.children {
+            if is_positioned(child) {
+                continue;
+            }
+            let opacity = opacity * own_opacity(&child.style);
+            match child.kind {
+                FragmentKind::Box => {
+                    self.box_decorations(child, opacity);
+                    let previous = self.enter(child);
+                    self.block_backgrounds(child, opacity);
+                    self.current = previous;
+                }
+                FragmentKind::Image(_) => self.box_decorations(child, opacity),
+                _ => {}
+            }
+        }
+    }
+
+    // Paints the lines of a box and its block-level descendants, along with the content
+    // of block-level images
+    fn inline_content(&mut self, fragment: &Fragment, opacity: f32, inherited: &[Decoration]) {
+        for child in fragment
+            .children
+            .iter()
+            .filter(|child| !is_positioned(child))
+        {
+            match &child.kind {
+                FragmentKind::Box => {
+                    let decorations = decorations(&child.style, inherited);
+                    let opacity = opacity * own_opacity(&child.style);
+                    let previous = self.enter(child);
+                    self.inline_content(child, opacity, &decorations);
+                    self.current = previous;
+                }
+                FragmentKind::Image(image) => {
+                    let opacity = opacity * own_opacity(&child.style);
+                    self.image(child, image, opacity);
+                }
+                // Lines share the style of their block, which has already been applied
+                FragmentKind::Line => {
+                    for item in child.children.iter().filter(|item| !is_positioned(item)) {
+                        self.inline_level(item, opacity, inherited);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn inline_level(&mut self, fragment: &Fragment, opacity: f32, inherited: &[Decoration]) {
+        match &fragment.kind {
+            FragmentKind::Inline => {
+                let opacity = opacity * own_opacity(&fragment.style);
+                self.box_decorations(fragment, opacity);
+                let decorations = decorations(&fragment.style, inherited);
+                for child in fragment
+                    .children
+                    .iter()
+                    .filter(|child| !is_positioned(child))
+                {
+                    self.inline_level(child, opacity, &decorations);
+                }
+            }
+            FragmentKind::Text(run) if is_visible(&fragment.style) => {
+                let color = color32(fragment.style.color("color"), opacity);
+                for &(range, color) in self.highlights {
+                    if let Some(rect) = self.highlighted_part(fragment, run, range) {
+                        self.push(DisplayItem::Rect { rect, color });
+                    }
+                }
+                self.push(DisplayItem::Text {
+                    rect: fragment.rect,
+                    text: run.text.clone(),
+                    font: run.font.clone(),
+                    color,
+                });
+                for decoration in inherited {
+                    self.decoration_line(fragment.rect, run, decoration, opacity);
+                }
+            }
+            FragmentKind::Image(image) => {
+                let opacity = opacity * own_opacity(&fragment.style);
+                self.box_decorations(fragment, opacity);
+                self.image(fragment, image, opacity);
+            }
+            // Decorations don't propagate into atomic inlines
+            FragmentKind::Box => self.atomic(fragment, opacity),
+            _ => {}
+        }
+    }
+
+    // The part of a text fragment a range covers
+    fn highlighted_part(
+        &self,
+        fragment: &Fragment,
+        run: &TextRun,
+        highlight: &Selection,
+    ) -> Option<Rect> {
+        let measurer = self.measurer?;
+        let range = highlight.range_in(fragment.node.as_ref()?, run.source.end)?;
+        let start = run.index_of(range.start.max(run.source.start));
+        let end = run.index_of(range.end);
+        if start >= end {
+            return None;
+        }
+        let left = fragment.rect.left() + run.x_of(start, measurer);
+        let right = fragment.rect.left() + run.x_of(end, measurer);
+        Some(Rect::from_x_y_ranges(left..=right, fragment.rect.y_range()))
+    }
+
+    fn image(&mut self, fragment: &Fragment, image: &Image, opacity: f32) {
+        if is_visible(&fragment.style) && opacity > 0.0 {
+            let rect = fragment.content_rect();
+            self.push(DisplayItem::Image {
+                rect,
+                fitted: object_fit(&fragment.style, rect, image),
+                image: image.clone(),
+                opacity,
+            });
+        }
+    }
+
+    // Paints the background, border and outline of a box
+    fn box_decorations(&mut self, fragment: &Fragment, opacity: f32) {
+        let style = &fragment.style;
+        if !is_visible(style) {
+            return;
+        }
+        if !std::ptr::eq(fragment, self.canvas) {
+            if let Some(color) = background(fragment) {
+                self.push(DisplayItem::Rect {
+                    rect: fragment.rect,
+                    color: color32(color, opacity),
+                });
+            }
+        }
+        let widths = [
+            fragment.border.top,
+            fragment.border.right,
+            fragment.border.bottom,
+            fragment.border.left,
+        ];
+        let sides = ["top", "right", "bottom", "left"];
+        let sides = std::array::from_fn(|i| BorderSide {
+            width: widths[i],
+            color: color32(style.color(&format!("border-{}-color", sides[i])), opacity),
+            style: border_style(style.keyword(&format!("border-{}-style", sides[i]))),
+        });
+        if has_visible_side(&sides) {
+            self.push(DisplayItem::Border {
+                rect: fragment.rect,
+                sides,
+            });
+        }
+
+        if matches!(
+            style.keyword("outline-style"),
+            None | Some("none" | "hidden")
+        ) {
+            return;
+        }
+        let width = line_width(style.get("outline-width"));
+        let offset = style.length("outline-offset", 0.0).unwrap_or(0.0);
+        let side = BorderSide {
+            width,
+            color: color32(style.color("outline-color"), opacity),
+            style: border_style(style.keyword("outline-style")),
+        };
+        if has_visible_side(&[side]) {
+            let outline = DisplayItem::Border {
+                rect: fragment.rect.expand(offset + width),
+                sides: [side; 4],
+            };
+            self.outlines.push((outline, self.current));
+        }
+    }
+
+    fn decoration_line(
+        &mut self,
+        rect: Rect,
+        run: &TextRun,
+        decoration: &Decoration,
+        opacity: f32,
+    ) {
+        let size = run.font.size;
+        let thickness = (size / 14.0).max(1.0);
+        let y = match decoration.line {
+            DecorationLine::Underline => rect.top() + run.ascent + thickness,
+            DecorationLine::Overline => rect.top(),
+            DecorationLine::LineThrough => rect.top() + run.ascent - size * 0.3,
+        };
+        self.push(DisplayItem::Rect {
+            rect: Rect::from_min_max(
+                egui::pos2(rect.left(), y),
+                egui::pos2(rect.right(), y + thickness),
+            ),
+            color: color32(decoration.color, opacity),
+        });
+    }
+}
+
+fn background(fragment: &Fragment) -> Option<Color> {
+    let color = fragment.style.color("background-color");
+    match color.is_transparent() {
+        true => None,
+        false => Some(color),
+    }
+}
+
+// Boxes with a position other than static. Lines and text share the style of the box
+// they're in, so they don't count.
+fn is_positioned(fragment: &Fragment) -> bool {
+    matches!(
+        fragment.kind,
+        FragmentKind::Box | FragmentKind::Inline | FragmentKind::Image(_)
+    ) && fragment
+        .style
+        .keyword("position")
+        .is_some_and(|position| position != "static")
+}
+
+// Auto counts as zero, which paints in tree order with the boxes at zero
+fn z_index(fragment: &Fragment) -> f32 {
+    fragment
+        .style
+        .get("z-index")
+        .and_then(Value::as_number)
+        .unwrap_or(0.0)
+}
+
+// Boxes whose overflow doesn't show outside their padding box
+fn clips_overflow(style: &ComputedStyle) -> bool {
+    ["overflow-x", "overflow-y"]
+        .iter()
+        .any(|name| !matches!(style.keyword(name), None | Some("visible")))
+}
+
+// The area a box's descendants cover, not counting what inner scroll containers clip
+fn overflow_extent(fragment: &Fragment) -> Rect {
+    fragment
+        .children
+        .iter()
+        .fold(fragment.padding_rect(), |extent, child| {
+            let extent = extent.union(child.rect);
+            match child.kind != FragmentKind::Box || !clips_overflow(&child.style) {
+                true => extent.union(overflow_extent(child)),
+                false => extent,
+            }
+        })
+}
+
+fn own_opacity(style: &ComputedStyle) -> f32 {
+    match style.get("opacity") {
+        Some(Value::Number(opacity)) => opacity.clamp(0.0, 1.0),
+        Some(Value::Percentage(percent)) => (percent / 100.0).clamp(0.0, 1.0),
+        _ => 1.0,
+    }
+}
+
+fn is_visible(style: &ComputedStyle) -> bool {
+    !matches!(style.keyword("visibility"), Some("hidden" | "collapse"))
+}
+
+// Where an image is drawn in its content box, centred and scaled as object-fit says.
+// Images that haven't loaded fill the box.
+fn object_fit(style: &ComputedStyle, rect: Rect, image: &Image) -> Rect {
+    let Some(natural) = image
+        .natural_size()
+        .filter(|size| size.x > 0.0 && size.y > 0.0)
+    else {
+        return rect;
+    };
+    let contain = (rect.width() / natural.x).min(rect.height() / natural.y);
+    let scale = match style.keyword("object-fit") {
+        Some("contain") => contain,
+        Some("cover") => (rect.width() / natural.x).max(rect.height() / natural.y),
+        Some("none") => 1.0,
+        Some("scale-down") => contain.min(1.0),
+        _ => return rect,
+    };
+    Rect::from_center_size(rect.center(), natural * scale)
+}
+
+fn has_visible_side(sides: &[BorderSide]) -> bool {
+    sides
+        .iter()
+        .any(|side| side.width > 0.0 && side.color.a() > 0)
+}
+
+// Other styles such as groove and inset are drawn solid
+fn border_style(keyword: Option<&str>) -> BorderStyle {
+    match keyword {
+        Some("dashed") => BorderStyle::Dashed,
+        Some("dotted") => BorderStyle::Dotted,
+        Some("double") => BorderStyle::Double,
+        _ => BorderStyle::Solid,
+    }
+}
+
+// Resolves a line width such as `outline-width`, including the width keywords
+fn line_width(value: Option<&Value>) -> f32 {
+    match value {
+        Some(Value::Keyword(keyword)) => match keyword.as_str() {
+            "thin" => 1.0,
+            "thick" => 5.0,
+            _ => 3.0,
+        },
+        Some(value) => value.to_px(0.0).unwrap_or(0.0).max(0.0),
+        None => 3.0,
+    }
+}
+
+// The decorations of a box's text: those it inherits plus any of its own
+fn decorations(style: &ComputedStyle, inherited: &[Decoration]) -> Vec<Decoration> {
+    let mut decorations = inherited.to_vec();
+    let keywords = match style.get("text-decoration-line") {
+        Some(Value::Keyword(keyword)) => vec![keyword.as_str()],
+        Some(Value::List(values)) => values.iter().filter_map(Value::as_keyword).collect(),
+        _ => Vec::new(),
+    };
+    let color = style.color("text-decoration-color");
+    for keyword in keywords {
+        let line = match keyword {
+            "underline" => DecorationLine::Underline,
+            "overline" => DecorationLine::Overline,
+            "line-through" => DecorationLine::LineThrough,
+            _ => continue,
+        };
+        decorations.push(Decoration { line, color });
+    }
+    decorations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css;
+    use crate::fonts::FontSet;
+    use crate::html;
+    use crate::layout::{self, FixedMetrics, LayoutContext};
+
+    fn display_list(source: &str) -> DisplayList {
+        let document = html::parse_document(source);
+        let mut stylist = css::Stylist::new(css::Device::default());
+        stylist.add_stylesheet(css::user_agent_stylesheet(), css::Origin::UserAgent);
+        for sheet in css::author_stylesheets(&document) {
+            stylist.add_stylesheet(sheet, css::Origin::Author);
+        }
+        let styles = stylist.style_document(&document);
+        let root = layout::build_box_tree(&document, &styles).unwrap();
+        let fonts = FontSet::default();
+        let ctx = LayoutContext::new(&FixedMetrics, &fonts);
+        build_display_list(&layout::layout_document(&root, 400.0, &ctx))
+    }
+
+    // Describes each item by its kind and the position of its rect
+    fn outline(list: &DisplayList) -> Vec<String> {
+        list.items
+            .iter()
+            .map(|item| {
+                let rect = item.rect();
+                let kind = match item {
+                    DisplayItem::Rect { .. } => "rect".to_string(),
+                    DisplayItem::Border { .. } => "border".to_string(),
+                    DisplayItem::Text { text, .. } => format!("text {:?}", text),
+                    DisplayItem::Image { .. } => "image".to_string(),
+                };
+                let round = |value: f32| (value * 100.0).round() / 100.0;
+                format!("{} ({}, {})", kind, round(rect.min.x), round(rect.min.y))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_selection_highlight() {
+        let document =
+            html::parse_document("<body style='margin: 0'><p>One</p><p>Two three</p></body>");
+        let stylist = css::Stylist::for_document(&document, css::Device::default());
+        let styles = stylist.style_document(&document);
+        let root = layout::build_box_tree(&document, &styles).unwrap();
+        let fonts = FontSet::default();
+        let ctx = LayoutContext::new(&FixedMetrics, &fonts);
+        let root = layout::layout_document(&root, 400.0, &ctx);
+        let position = |node: &[usize], offset| crate::dom::TextPosition {
+            node: node.to_vec(),
+            offset,
+        };
+        let selection = Selection::new(position(&[0, 1, 0, 0], 1), position(&[0, 1, 1, 0], 3));
+        let highlights = [(&selection, SELECTION_COLOR)];
+        let list = build_display_list_with_highlights(&root, &highlights, &FixedMetrics);
+
+        // Each selected part is highlighted under its text
+        let highlights: Vec<Rect> = list
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::Rect { rect, color } if *color == SELECTION_COLOR => Some(*rect),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            highlights,
+            [
+                Rect::from_min_size(egui::pos2(8.0, 16.0), egui::vec2(16.0, 20.0)),
+                Rect::from_min_size(egui::pos2(0.0, 52.0), egui::vec2(24.0, 20.0)),
+            ]
+        );
+        assert_eq!(
+            outline(&list)[1..],
+            [
+                "text \"One\" (0, 16)",
+                "rect (0, 52)",
+                "text \"Two three\" (0, 52)"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_painting_order() {
+        let list = display_list(
+            "<body style='margin: 0'>\
+             <div style='background: red; outline: 1px solid'>a \
+               <span style='display: inline-block; background: blue'>b</span>\
+               <div style='border: 1px solid; background: green'>c</div></div>\
+             <img width=10 height=10 style='border: 2px solid'></body>",
+        );
+        assert_eq!(
+            outline(&list),
+            vec![
+                // Block backgrounds and borders
+                "rect (0, 0)",
+                "rect (0, 20)",
+                "border (0, 20)",
+                // Inline content, with the inline-block painted as a unit
+                "text \"a \" (0, 0)",
+                "rect (16, 0)",
+                "text \"b\" (16, 0)",
+                "text \"c\" (1, 21)",
+                "border (0, 42)",
+                "image (2, 44)",
+                // Outlines
+                "border (-1, -1)",
+            ]
+        );
+        assert_eq!(list.size, egui::vec2(401.0, 64.0));
+    }
+
+    #[test]
+    fn test_canvas_background() {
+        let list = display_list("<body style='background: blue'><p>text</p></body>");
+        assert_eq!(list.background, Some(Color32::BLUE));
+        // The body's background is painted as the canvas instead of as an item
+        assert_eq!(outline(&list), vec!["text \"text\" (8, 16)"]);
+
+        let list = display_list("<html style='background: red'><body style='background: blue'>");
+        assert_eq!(list.background, Some(Color32::RED));
+        assert_eq!(outline(&list), vec!["rect (8, 8)"]);
+        assert_eq!(display_list("<p>text</p>").background, None);
+    }
+
+    #[test]
+    fn test_visibility_opacity_and_decorations() {
+        let list = display_list(
+            "<body style='margin: 0'>\
+             <div style='visibility: hidden; background: red'>a \
+               <span style='visibility: visible'>b</span></div>\
+             <div style='opacity: 0.5; color: black; text-decoration: underline'>c</div>\
+             </body>",
+        );
+        assert_eq!(
+            outline(&list),
+            vec![
+                "text \"b\" (16, 0)",
+                "text \"c\" (0, 20)",
+                "rect (0, 33.14)"
+            ]
+        );
+        let DisplayItem::Text { color, .. } = &list.items[1] else {
+            panic!("Expected a text item");
+        };
+        assert_eq!(color.a(), 128);
+    }
+
+    #[test]
+    fn test_stacking_order_and_layers() {
+        let list = display_list(
+            "<body style='margin: 0'>\
+             <div style='position: relative; z-index: 1; background: red; height: 10px'></div>\
+             <div style='position: absolute; z-index: -1; background: blue; width: 10px; \
+               height: 10px'></div>\
+             <div style='background: green; height: 10px'></div>\
+             <div style='position: fixed; top: 0; background: yellow; width: 10px; \
+               height: 10px'></div>\
+             <div style='position: sticky; top: 5px; background: black; height: 10px'></div>\
+             <div style='height: 100px'></div></body>",
+        );
+        assert_eq!(
+            outline(&list),
+            vec![
+                // Negative z-indices go below the in-flow blocks
+                "rect (0, 10)",
+                "rect (0, 10)",
+                // Positioned boxes go above them in z-index then fragment order, where the
+                // fixed box has moved to the root
+                "rect (0, 20)",
+                "rect (0, 0)",
+                "rect (0, 0)",
+            ]
+        );
+        let Some(DisplayItem::Rect { color, .. }) = list.items.last() else {
+            panic!("Expected a rect item");
+        };
+        assert_eq!(*color, Color32::RED);
+
+        // The fixed box moves with the viewport, and the sticky one stays 5px from its top
+        // until it reaches the bottom of the body
+        assert_eq!(list.layers.len(), 2);
+        assert_eq!(list.layers[1].items, 3..4);
+        let viewport =
+            |top: f32| Rect::from_min_size(egui::pos2(0.0, top), egui::vec2(400.0, 300.0));
+        assert_eq!(list.layer_offset(3, viewport(50.0)), egui::vec2(0.0, 50.0));
+        assert_eq!(list.layer_offset(2, viewport(0.0)), Vec2::ZERO);
+        assert_eq!(list.layer_offset(2, viewport(50.0)), egui::vec2(0.0, 35.0));
+        assert_eq!(
+            list.layer_offset(2, viewport(200.0)),
+            egui::vec2(0.0, 100.0)
+        );
+        assert_eq!(list.layer_offset(1, viewport(50.0)), Vec2::ZERO);
+    }
+}
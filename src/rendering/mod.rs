@@ -0,0 +1,26 @@
+// Rendering: turning laid out fragments into drawing commands and painting them with egui
+
+mod dark;
+mod display_list;
+mod painter;
+mod pdf;
+mod raster;
+
+pub use dark::{dark_color, force_dark};
+pub use display_list::{
+    build_display_list, build_display_list_with_highlights, Attachment, BorderSide, BorderStyle,
+    DisplayItem, DisplayList, Layer, ScrollContainer, ScrollOffsets, CURRENT_MATCH_COLOR,
+    MATCH_COLOR, SELECTION_COLOR,
+};
+pub use painter::paint;
+pub use pdf::write_pdf;
+pub use raster::{save_png, Rasterizer};
+
+use crate::css;
+use eframe::egui::Color32;
+
+// Converts a CSS color to an egui color, applying the opacity of the fragments it's in
+pub(crate) fn color32(color: css::Color, opacity: f32) -> Color32 {
+    let alpha = (color.a as f32 * opacity.clamp(0.0, 1.0)).round() as u8;
+    Color32::from_rgba_unmultiplied(color.r, color.g, color.b, alpha)
+}
@@ -0,0 +1,176 @@
+// Replays display lists onto an egui painter
+
+use crate::layout::ImageContent;
+use crate::rendering::{BorderSide, BorderStyle, DisplayItem, DisplayList, ScrollOffsets};
+use eframe::egui::{self, Align2, Color32, FontId, Pos2, Rect, Shape, Stroke, Vec2};
+use eframe::emath::TSTransform;
+
+// Paints a display list with the document's origin at `offset`, skipping items outside
+// the painter's clip rect. The clip rect is the viewport that fixed and sticky boxes stay
+// in as the document scrolls, and `scroll` holds the offsets of its scroll containers.
+// Each CSS pixel of the list takes `zoom` points on screen.
+pub fn paint(
+    list: &DisplayList,
+    painter: &egui::Painter,
+    offset: Vec2,
+    scroll: &ScrollOffsets,
+    zoom: f32,
+) {
+    let visible = TSTransform::new(offset, zoom).inverse() * painter.clip_rect();
+    for (index, item) in list.items.iter().enumerate() {
+        let (shift, clip) = list.placement(index, visible, scroll);
+        let clip = clip.intersect(visible);
+        if !item.rect().translate(shift).intersects(clip) {
+            continue;
+        }
+        let transform = TSTransform::new(offset + shift * zoom, zoom);
+        let painter = painter.with_clip_rect(TSTransform::new(offset, zoom) * clip);
+        let painter = &painter;
+        match item {
+            DisplayItem::Rect { rect, color } => {
+                painter.rect_filled(transform * *rect, 0.0, *color);
+            }
+            DisplayItem::Border { rect, sides } => {
+                let sides = sides.map(|side| BorderSide {
+                    width: side.width * zoom,
+                    ..side
+                });
+                paint_border(painter, transform * *rect, &sides);
+            }
+            DisplayItem::Text {
+                rect,
+                text,
+                font,
+                color,
+            } => {
+                painter.text(
+                    transform * rect.min,
+                    Align2::LEFT_TOP,
+                    text,
+                    FontId::new(font.size * zoom, font.family.clone()),
+                    *color,
+                );
+            }
+            DisplayItem::Image {
+                rect,
+                fitted,
+                image,
+                opacity,
+            } => {
+                let rect = transform * *rect;
+                match &image.content {
+                    ImageContent::Loaded { image, .. } => {
+                        let texture = image.texture(painter.ctx());
+                        let uv = Rect::from_min_max(Pos2::ZERO, egui::pos2(1.0, 1.0));
+                        painter
+                            .with_clip_rect(rect.intersect(painter.clip_rect()))
+                            .image(
+                                texture.id(),
+                                transform * *fitted,
+                                uv,
+                                Color32::WHITE.gamma_multiply(*opacity),
+                            );
+                        continue;
+                    }
+                    // Nothing shows until the image arrives
+                    ImageContent::Pending => continue,
+                    ImageContent::Failed => {}
+                }
+                // Images that failed to load get a frame with their alternative text
+                let color = Color32::GRAY.gamma_multiply(*opacity);
+                painter.rect_stroke(
+                    rect,
+                    0.0,
+                    Stroke::new(zoom, color),
+                    egui::StrokeKind::Inside,
+                );
+                if !image.alt.is_empty() {
+                    painter
+                        .with_clip_rect(rect.intersect(painter.clip_rect()))
+                        .text(
+                            rect.min + egui::vec2(2.0, 2.0) * zoom,
+                            Align2::LEFT_TOP,
+                            &image.alt,
+                            FontId::proportional(14.0 * zoom),
+                            color,
+                        );
+                }
+            }
+        }
+    }
+}
+
+fn paint_border(painter: &egui::Painter, rect: Rect, sides: &[BorderSide; 4]) {
+    let widths = sides.map(|side| side.width);
+    let inner = inset(rect, widths, 1.0);
+    for (index, side) in sides.iter().enumerate() {
+        if side.width <= 0.0 || side.color.a() == 0 {
+            continue;
+        }
+        match side.style {
+            BorderStyle::Solid => {
+                painter.add(side_polygon(rect, inner, index, side.color));
+            }
+            // Two bands a third of the width each, at the outer and inner edges
+            BorderStyle::Double => {
+                painter.add(side_polygon(
+                    rect,
+                    inset(rect, widths, 1.0 / 3.0),
+                    index,
+                    side.color,
+                ));
+                painter.add(side_polygon(
+                    inset(rect, widths, 2.0 / 3.0),
+                    inner,
+                    index,
+                    side.color,
+                ));
+            }
+            // Dashes and dots run along the middle of the side
+            BorderStyle::Dashed | BorderStyle::Dotted => {
+                let middle = corners(inset(rect, widths, 0.5));
+                let line = [middle[index], middle[(index + 1) % 4]];
+                let shapes = match side.style {
+                    BorderStyle::Dashed => Shape::dashed_line(
+                        &line,
+                        Stroke::new(side.width, side.color),
+                        side.width * 3.0,
+                        side.width * 3.0,
+                    ),
+                    _ => Shape::dotted_line(&line, side.color, side.width * 2.0, side.width / 2.0),
+                };
+                painter.extend(shapes);
+            }
+        }
+    }
+}
+
+// Shrinks a rect by a fraction of the border widths, which are top, right, bottom, left
+fn inset(rect: Rect, widths: [f32; 4], fraction: f32) -> Rect {
+    Rect::from_min_max(
+        rect.min + egui::vec2(widths[3], widths[0]) * fraction,
+        rect.max - egui::vec2(widths[1], widths[2]) * fraction,
+    )
+}
+
+// The corners of a rect clockwise from the top left, so side n runs from corner n to n + 1
+fn corners(rect: Rect) -> [Pos2; 4] {
+    [
+        rect.left_top(),
+        rect.right_top(),
+        rect.right_bottom(),
+        rect.left_bottom(),
+    ]
+}
+
+// The band of one side between two rects, mitred at the corners
+fn side_polygon(outer: Rect, inner: Rect, side: usize, color: Color32) -> Shape {
+    let outer = corners(outer);
+    let inner = corners(inner);
+    let next = (side + 1) % 4;
+    Shape::convex_polygon(
+        vec![outer[side], outer[next], inner[next], inner[side]],
+        color,
+        Stroke::NONE,
+    )
+}
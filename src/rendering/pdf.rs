@@ -0,0 +1,889 @@
+// Writes paginated display lists as PDF documents. Text is real text in the fonts egui
+// lays it out with, embedded whole, so it can be selected and searched. Boxes and borders
+// are vector paths and images are embedded with their alpha.
+
+use crate::css::PageStyle;
+use crate::images::LoadedImage;
+use crate::layout::ImageContent;
+use crate::rendering::{BorderSide, BorderStyle, DisplayItem, DisplayList, ScrollOffsets};
+use eframe::egui::{self, epaint, Color32, FontFamily, FontId, Pos2, Rect};
+use flate2::write::ZlibEncoder;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::sync::Arc;
+
+// CSS pixels are 3/4 of a PDF point
+const POINTS_PER_PIXEL: f32 = 0.75;
+
+// Writes the display list as pages of `page` size, each showing the part of the document
+// from its start in `starts` to the next one. Text is set in `fonts`, the definitions the
+// page was laid out with.
+pub fn write_pdf(
+    list: &DisplayList,
+    starts: &[f32],
+    page: &PageStyle,
+    fonts: &egui::FontDefinitions,
+    title: &str,
+) -> Vec<u8> {
+    let mut resources = Resources::new(fonts);
+    let bottom = list.size.y;
+    let contents: Vec<Vec<u8>> = starts
+        .iter()
+        .enumerate()
+        .map(|(index, &start)| {
+            let end = starts.get(index + 1).copied().unwrap_or(bottom.max(start));
+            let content = page_content(list, start, end, page, &mut resources);
+            deflate(content.as_bytes())
+        })
+        .collect();
+
+    let mut pdf = PdfFile::default();
+    let pages_id = 2;
+    let info_id = 3;
+    let resources_id = 4;
+    let mut next_id = 5;
+    let mut allocate = |count: usize| {
+        let id = next_id;
+        next_id += count;
+        id
+    };
+    let page_ids: Vec<usize> = contents.iter().map(|_| allocate(2)).collect();
+    // Fallback fonts the text didn't need aren't embedded
+    let font_ids: Vec<Option<usize>> = resources
+        .fonts
+        .iter()
+        .map(|font| (!font.glyphs.is_empty()).then(|| allocate(5)))
+        .collect();
+    let image_ids: Vec<usize> = resources.images.iter().map(|_| allocate(2)).collect();
+
+    pdf.object(
+        1,
+        &format!("<< /Type /Catalog /Pages {} 0 R >>", pages_id),
+        None,
+    );
+    let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+    pdf.object(
+        pages_id,
+        &format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            kids.len()
+        ),
+        None,
+    );
+    pdf.object(
+        info_id,
+        &format!(
+            "<< /Title {} /Producer (Oliver Explorer) >>",
+            text_string(title)
+        ),
+        None,
+    );
+
+    let mut dictionary = String::from("<< /ProcSet [/PDF /Text /ImageB /ImageC] /Font <<");
+    for (index, id) in font_ids.iter().enumerate() {
+        let Some(id) = id else {
+            continue;
+        };
+        let _ = write!(dictionary, " /F{} {} 0 R", index, id);
+    }
+    dictionary.push_str(" >> /XObject <<");
+    for (index, id) in image_ids.iter().enumerate() {
+        let _ = write!(dictionary, " /Im{} {} 0 R", index, id);
+    }
+    dictionary.push_str(" >> /ExtGState <<");
+    for alpha in &resources.alphas {
+        let alpha_value = number(*alpha as f32 / 255.0);
+        let _ = write!(
+            dictionary,
+            " /GS{} << /ca {} /CA {} >>",
+            alpha, alpha_value, alpha_value
+        );
+    }
+    dictionary.push_str(" >> >>");
+    pdf.object(resources_id, &dictionary, None);
+
+    let media_box = format!(
+        "[0 0 {} {}]",
+        number(page.width * POINTS_PER_PIXEL),
+        number(page.height * POINTS_PER_PIXEL)
+    );
+    for (id, content) in page_ids.iter().zip(&contents) {
+        pdf.object(
+            *id,
+            &format!(
+                "<< /Type /Page /Parent {} 0 R /MediaBox {} /Resources {} 0 R /Contents {} 0 R >>",
+                pages_id,
+                media_box,
+                resources_id,
+                id + 1
+            ),
+            None,
+        );
+        pdf.object(id + 1, "<< /Filter /FlateDecode >>", Some(content));
+    }
+    for (font, id) in resources.fonts.iter().zip(&font_ids) {
+        if let Some(id) = id {
+            font.write(&mut pdf, *id);
+        }
+    }
+    for (image, id) in resources.images.iter().zip(&image_ids) {
+        write_image(&mut pdf, image, *id);
+    }
+    pdf.finish(info_id)
+}
+
+// The drawing commands of one page. They work in CSS pixels with y pointing down, and
+// document coordinates once past the page's margins.
+fn page_content(
+    list: &DisplayList,
+    start: f32,
+    end: f32,
+    page: &PageStyle,
+    resources: &mut Resources,
+) -> String {
+    let mut content = String::new();
+    let _ = writeln!(
+        content,
+        "{} 0 0 {} 0 {} cm",
+        number(POINTS_PER_PIXEL),
+        number(-POINTS_PER_PIXEL),
+        number(page.height * POINTS_PER_PIXEL)
+    );
+    if let Some(background) = list.background {
+        let area = Rect::from_min_size(Pos2::ZERO, egui::vec2(page.width, page.height));
+        fill_rect(&mut content, area, background, resources);
+    }
+    let [top, _, _, left] = page.margins;
+    let _ = writeln!(
+        content,
+        "1 0 0 1 {} {} cm",
+        number(left),
+        number(top - start)
+    );
+    // The part of the document on the page, which fixed boxes are repeated in
+    let slice = Rect::from_min_max(
+        egui::pos2(0.0, start),
+        egui::pos2(page.content_width(), end),
+    );
+    let viewport = Rect::from_min_size(slice.min, egui::vec2(slice.width(), page.content_height()));
+    content.push_str("q\n");
+    clip(&mut content, slice);
+
+    let scroll = ScrollOffsets::default();
+    for (index, item) in list.items.iter().enumerate() {
+        let (shift, item_clip) = list.placement(index, viewport, &scroll);
+        let item_clip = item_clip.intersect(slice);
+        // Clipped text would still be selectable, so items only touching the page are left
+        // off it
+        let visible = item.rect().translate(shift).intersect(item_clip);
+        if visible.width() <= 0.0 || visible.height() <= 0.0 {
+            continue;
+        }
+        content.push_str("q\n");
+        if item_clip != slice {
+            clip(&mut content, item_clip);
+        }
+        if shift != egui::Vec2::ZERO {
+            let _ = writeln!(
+                content,
+                "1 0 0 1 {} {} cm",
+                number(shift.x),
+                number(shift.y)
+            );
+        }
+        match item {
+            DisplayItem::Rect { rect, color } => fill_rect(&mut content, *rect, *color, resources),
+            DisplayItem::Border { rect, sides } => {
+                draw_border(&mut content, *rect, sides, resources)
+            }
+            DisplayItem::Text {
+                rect,
+                text,
+                font,
+                color,
+            } => resources.text(&mut content, rect.min, text, font, *color),
+            DisplayItem::Image {
+                rect,
+                fitted,
+                image,
+                opacity,
+            } => match &image.content {
+                ImageContent::Loaded { image, .. } => {
+                    let name = resources.image(image);
+                    clip(&mut content, *rect);
+                    let alpha = (opacity.clamp(0.0, 1.0) * 255.0).round() as u8;
+                    resources.set_alpha(&mut content, alpha);
+                    let _ = writeln!(
+                        content,
+                        "{} 0 0 {} {} {} cm /Im{} Do",
+                        number(fitted.width()),
+                        number(-fitted.height()),
+                        number(fitted.min.x),
+                        number(fitted.max.y),
+                        name
+                    );
+                }
+                ImageContent::Pending => {}
+                // Images that failed to load get a frame with their alternative text
+                ImageContent::Failed => {
+                    let color = Color32::GRAY.gamma_multiply(*opacity);
+                    let side = BorderSide {
+                        width: 1.0,
+                        color,
+                        style: BorderStyle::Solid,
+                    };
+                    draw_border(&mut content, *rect, &[side; 4], resources);
+                    if !image.alt.is_empty() {
+                        clip(&mut content, *rect);
+                        let font = FontId::proportional(14.0);
+                        let position = rect.min + egui::vec2(2.0, 2.0);
+                        resources.text(&mut content, position, &image.alt, &font, color);
+                    }
+                }
+            },
+        }
+        content.push_str("Q\n");
+    }
+    content.push_str("Q\n");
+    content
+}
+
+fn clip(content: &mut String, rect: Rect) {
+    let _ = writeln!(content, "{} re W n", rect_operands(rect));
+}
+
+fn rect_operands(rect: Rect) -> String {
+    format!(
+        "{} {} {} {}",
+        number(rect.min.x),
+        number(rect.min.y),
+        number(rect.width()),
+        number(rect.height())
+    )
+}
+
+fn fill_rect(content: &mut String, rect: Rect, color: Color32, resources: &mut Resources) {
+    if color.a() == 0 {
+        return;
+    }
+    resources.set_fill(content, color);
+    let _ = writeln!(content, "{} re f", rect_operands(rect));
+}
+
+// The same bands, dashes and dots as the screen painter
+fn draw_border(
+    content: &mut String,
+    rect: Rect,
+    sides: &[BorderSide; 4],
+    resources: &mut Resources,
+) {
+    let widths = sides.map(|side| side.width);
+    let inner = inset(rect, widths, 1.0);
+    for (index, side) in sides.iter().enumerate() {
+        if side.width <= 0.0 || side.color.a() == 0 {
+            continue;
+        }
+        match side.style {
+            BorderStyle::Solid => {
+                resources.set_fill(content, side.color);
+                polygon(content, rect, inner, index);
+            }
+            BorderStyle::Double => {
+                resources.set_fill(content, side.color);
+                polygon(content, rect, inset(rect, widths, 1.0 / 3.0), index);
+                polygon(content, inset(rect, widths, 2.0 / 3.0), inner, index);
+            }
+            BorderStyle::Dashed | BorderStyle::Dotted => {
+                let middle = corners(inset(rect, widths, 0.5));
+                let (from, to) = (middle[index], middle[(index + 1) % 4]);
+                resources.set_stroke(content, side.color);
+                let dash = match side.style {
+                    BorderStyle::Dashed => format!(
+                        "0 J [{} {}] 0 d",
+                        number(side.width * 3.0),
+                        number(side.width * 3.0)
+                    ),
+                    _ => format!("1 J [0 {}] 0 d", number(side.width * 2.0)),
+                };
+                let _ = writeln!(
+                    content,
+                    "{} w {} {} {} m {} {} l S",
+                    number(side.width),
+                    dash,
+                    number(from.x),
+                    number(from.y),
+                    number(to.x),
+                    number(to.y)
+                );
+            }
+        }
+    }
+}
+
+// Shrinks a rect by a fraction of the border widths, which are top, right, bottom, left
+fn inset(rect: Rect, widths: [f32; 4], fraction: f32) -> Rect {
+    Rect::from_min_max(
+        rect.min + egui::vec2(widths[3], widths[0]) * fraction,
+        rect.max - egui::vec2(widths[1], widths[2]) * fraction,
+    )
+}
+
+// The corners of a rect clockwise from the top left, so side n runs from corner n to n + 1
+fn corners(rect: Rect) -> [Pos2; 4] {
+    [
+        rect.left_top(),
+        rect.right_top(),
+        rect.right_bottom(),
+        rect.left_bottom(),
+    ]
+}
+
+// Fills the band of one side between two rects, mitred at the corners
+fn polygon(content: &mut String, outer: Rect, inner: Rect, side: usize) {
+    let (outer, inner) = (corners(outer), corners(inner));
+    let next = (side + 1) % 4;
+    let points = [outer[side], outer[next], inner[next], inner[side]];
+    for (index, point) in points.iter().enumerate() {
+        let operator = if index == 0 { "m" } else { "l" };
+        let _ = write!(
+            content,
+            "{} {} {} ",
+            number(point.x),
+            number(point.y),
+            operator
+        );
+    }
+    content.push_str("h f\n");
+}
+
+/// The fonts, images and transparency the pages of a document use
+struct Resources<'a> {
+    definitions: &'a egui::FontDefinitions,
+    fonts: Vec<EmbeddedFont<'a>>,
+    // The index in `fonts` of each font by name, None for fonts that can't be parsed
+    font_indices: HashMap<String, Option<usize>>,
+    images: Vec<Arc<LoadedImage>>,
+    alphas: Vec<u8>,
+}
+
+/// A font file embedded in the document and the glyphs its text uses, with the
+/// character each one stands for
+struct EmbeddedFont<'a> {
+    name: String,
+    data: &'a [u8],
+    face: ttf_parser::Face<'a>,
+    tweak: epaint::text::FontTweak,
+    glyphs: BTreeMap<u16, char>,
+}
+
+impl<'a> Resources<'a> {
+    fn new(definitions: &'a egui::FontDefinitions) -> Self {
+        Self {
+            definitions,
+            fonts: Vec::new(),
+            font_indices: HashMap::new(),
+            images: Vec::new(),
+            alphas: Vec::new(),
+        }
+    }
+
+    fn font(&mut self, name: &str) -> Option<usize> {
+        if let Some(index) = self.font_indices.get(name) {
+            return *index;
+        }
+        let definitions = self.definitions;
+        let index = definitions.font_data.get(name).and_then(|data| {
+            let bytes: &'a [u8] = &data.font;
+            let face = ttf_parser::Face::parse(bytes, data.index).ok()?;
+            self.fonts.push(EmbeddedFont {
+                name: font_name(&face, name),
+                data: bytes,
+                face,
+                tweak: data.tweak,
+                glyphs: BTreeMap::new(),
+            });
+            Some(self.fonts.len() - 1)
+        });
+        self.font_indices.insert(name.to_string(), index);
+        index
+    }
+
+    // Sets the alpha of what's painted next, through a graphics state
+    fn set_alpha(&mut self, content: &mut String, alpha: u8) {
+        if !self.alphas.contains(&alpha) {
+            self.alphas.push(alpha);
+        }
+        let _ = writeln!(content, "/GS{} gs", alpha);
+    }
+
+    fn set_fill(&mut self, content: &mut String, color: Color32) {
+        let [r, g, b, a] = color.to_srgba_unmultiplied();
+        self.set_alpha(content, a);
+        let _ = writeln!(content, "{} rg", rgb(r, g, b));
+    }
+
+    fn set_stroke(&mut self, content: &mut String, color: Color32) {
+        let [r, g, b, a] = color.to_srgba_unmultiplied();
+        self.set_alpha(content, a);
+        let _ = writeln!(content, "{} RG", rgb(r, g, b));
+    }
+
+    fn image(&mut self, image: &Arc<LoadedImage>) -> usize {
+        match self
+            .images
+            .iter()
+            .position(|other| Arc::ptr_eq(other, image))
+        {
+            Some(index) => index,
+            None => {
+                self.images.push(Arc::clone(image));
+                self.images.len() - 1
+            }
+        }
+    }
+
+    // Sets a run of text with the top left of its line at `position`. Like egui, each
+    // character is set in the first font of the family that has it, on the baseline of the
+    // family's first font.
+    fn text(
+        &mut self,
+        content: &mut String,
+        position: Pos2,
+        text: &str,
+        font: &FontId,
+        color: Color32,
+    ) {
+        let family = match self.definitions.families.contains_key(&font.family) {
+            true => &font.family,
+            false => &FontFamily::Proportional,
+        };
+        let names = self
+            .definitions
+            .families
+            .get(family)
+            .cloned()
+            .unwrap_or_default();
+        let fonts: Vec<usize> = names.iter().filter_map(|name| self.font(name)).collect();
+        let Some(&primary) = fonts.first() else {
+            return;
+        };
+        let size = font.size;
+        let baseline = position.y + self.fonts[primary].ascent(size);
+
+        // Runs of characters in the same font
+        let mut segments: Vec<(usize, Vec<u16>)> = Vec::new();
+        for c in text.chars() {
+            let (font, glyph) = fonts
+                .iter()
+                .find_map(|&index| Some((index, self.fonts[index].face.glyph_index(c)?)))
+                .unwrap_or((primary, ttf_parser::GlyphId(0)));
+            self.fonts[font].glyphs.entry(glyph.0).or_insert(c);
+            match segments.last_mut() {
+                Some((last, glyphs)) if *last == font => glyphs.push(glyph.0),
+                _ => segments.push((font, vec![glyph.0])),
+            }
+        }
+
+        self.set_fill(content, color);
+        content.push_str("BT\n");
+        let mut x = position.x;
+        for (index, glyphs) in segments {
+            let embedded = &self.fonts[index];
+            let em = embedded.em(size);
+            let y = baseline + embedded.y_offset(size);
+            let mut hex = String::with_capacity(glyphs.len() * 4);
+            for glyph in &glyphs {
+                let _ = write!(hex, "{:04X}", glyph);
+            }
+            let _ = writeln!(
+                content,
+                "/F{} {} Tf 1 0 0 -1 {} {} Tm <{}> Tj",
+                index,
+                number(em),
+                number(x),
+                number(y),
+                hex
+            );
+            x += glyphs
+                .iter()
+                .map(|&glyph| embedded.advance(glyph) * em)
+                .sum::<f32>();
+        }
+        content.push_str("ET\n");
+    }
+}
+
+impl EmbeddedFont<'_> {
+    fn units_per_em(&self) -> f32 {
+        self.face.units_per_em() as f32
+    }
+
+    // The size the font's glyphs are drawn at for a font size. egui scales fonts so their
+    // ascent to descent is the size, rounded to whole pixels.
+    fn em(&self, size: f32) -> f32 {
+        let height = self.height() / self.units_per_em();
+        match height > 0.0 {
+            true => (size * height * self.tweak.scale).round().max(1.0) / height,
+            false => size * self.tweak.scale,
+        }
+    }
+
+    fn height(&self) -> f32 {
+        self.face.ascender() as f32 - self.face.descender() as f32
+    }
+
+    // How far below the top of a line of `size` text its baseline is
+    fn ascent(&self, size: f32) -> f32 {
+        let scale = size / self.units_per_em();
+        let baseline_offset =
+            self.height() * scale * self.tweak.scale * self.tweak.baseline_offset_factor;
+        self.face.ascender() as f32 * scale + baseline_offset
+    }
+
+    // How far the glyphs are moved down from the baseline, to center glyphs egui scales
+    fn y_offset(&self, size: f32) -> f32 {
+        let scale = size / self.units_per_em();
+        let (ascent, descent) = (
+            self.face.ascender() as f32 * scale,
+            self.face.descender() as f32 * scale,
+        );
+        let scaled_size = self.height() * scale * self.tweak.scale;
+        scaled_size * self.tweak.y_offset_factor + self.tweak.y_offset
+            - (1.0 - self.tweak.scale) * 0.5 * (ascent + descent)
+    }
+
+    // A glyph's advance as a fraction of the em
+    fn advance(&self, glyph: u16) -> f32 {
+        let advance = self
+            .face
+            .glyph_hor_advance(ttf_parser::GlyphId(glyph))
+            .unwrap_or(0);
+        advance as f32 / self.units_per_em()
+    }
+
+    // A composite font of the glyphs by their ids, with a CID font, its descriptor, the
+    // font file and the map from glyphs back to text in the four objects after it
+    fn write(&self, pdf: &mut PdfFile, id: usize) {
+        let (cid_font, descriptor, file, to_unicode) = (id + 1, id + 2, id + 3, id + 4);
+        pdf.object(
+            id,
+            &format!(
+                "<< /Type /Font /Subtype /Type0 /BaseFont /{} /Encoding /Identity-H \
+                 /DescendantFonts [{} 0 R] /ToUnicode {} 0 R >>",
+                self.name, cid_font, to_unicode
+            ),
+            None,
+        );
+        // Fonts with PostScript outlines are embedded as OpenType
+        let cff = self
+            .face
+            .raw_face()
+            .table(ttf_parser::Tag::from_bytes(b"CFF "))
+            .is_some();
+        let (subtype, file_key) = match cff {
+            true => ("CIDFontType0", "FontFile3"),
+            false => ("CIDFontType2", "FontFile2"),
+        };
+        let mut widths = String::new();
+        for glyph in self.glyphs.keys() {
+            let _ = write!(
+                widths,
+                "{} [{}] ",
+                glyph,
+                number(self.advance(*glyph) * 1000.0)
+            );
+        }
+        let cid_to_gid = if cff { "" } else { " /CIDToGIDMap /Identity" };
+        pdf.object(
+            cid_font,
+            &format!(
+                "<< /Type /Font /Subtype /{} /BaseFont /{} /CIDSystemInfo << /Registry (Adobe) \
+                 /Ordering (Identity) /Supplement 0 >> /FontDescriptor {} 0 R /W [{}]{} >>",
+                subtype,
+                self.name,
+                descriptor,
+                widths.trim_end(),
+                cid_to_gid
+            ),
+            None,
+        );
+        let scale = 1000.0 / self.units_per_em();
+        let bounds = self.face.global_bounding_box();
+        let units = |value: i16| number(value as f32 * scale);
+        let flags = match self.face.is_italic() {
+            true => 4 | 64,
+            false => 4,
+        };
+        pdf.object(
+            descriptor,
+            &format!(
+                "<< /Type /FontDescriptor /FontName /{} /Flags {} /FontBBox [{} {} {} {}] \
+                 /ItalicAngle {} /Ascent {} /Descent {} /CapHeight {} /StemV 80 /{} {} 0 R >>",
+                self.name,
+                flags,
+                units(bounds.x_min),
+                units(bounds.y_min),
+                units(bounds.x_max),
+                units(bounds.y_max),
+                number(self.face.italic_angle()),
+                units(self.face.ascender()),
+                units(self.face.descender()),
+                units(self.face.capital_height().unwrap_or(self.face.ascender())),
+                file_key,
+                file
+            ),
+            None,
+        );
+        let file_dictionary = match cff {
+            true => "<< /Subtype /OpenType /Filter /FlateDecode >>".to_string(),
+            false => format!("<< /Length1 {} /Filter /FlateDecode >>", self.data.len()),
+        };
+        pdf.object(file, &file_dictionary, Some(&deflate(self.data)));
+        pdf.object(
+            to_unicode,
+            "<< /Filter /FlateDecode >>",
+            Some(&deflate(self.to_unicode().as_bytes())),
+        );
+    }
+
+    // A CMap from the glyph ids the text is written in to the characters they stand for
+    fn to_unicode(&self) -> String {
+        let mut cmap = String::from(
+            "/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n\
+             /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+             /CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n\
+             1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+        );
+        let glyphs: Vec<(&u16, &char)> = self.glyphs.iter().collect();
+        // A block can hold at most 100 mappings
+        for block in glyphs.chunks(100) {
+            let _ = writeln!(cmap, "{} beginbfchar", block.len());
+            for (glyph, c) in block {
+                let mut utf16 = String::new();
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    let _ = write!(utf16, "{:04X}", unit);
+                }
+                let _ = writeln!(cmap, "<{:04X}> <{}>", glyph, utf16);
+            }
+            cmap.push_str("endbfchar\n");
+        }
+        cmap.push_str("endcmap\nCMapName currentdict /CMap defineresource pop\nend\nend\n");
+        cmap
+    }
+}
+
+// The font's PostScript name, or its name in egui, as a PDF name
+fn font_name(face: &ttf_parser::Face, fallback: &str) -> String {
+    let name = face
+        .names()
+        .into_iter()
+        .find(|name| name.name_id == ttf_parser::name_id::POST_SCRIPT_NAME)
+        .and_then(|name| name.to_string())
+        .unwrap_or_else(|| fallback.to_string());
+    let name: String = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        .take(63)
+        .collect();
+    match name.is_empty() {
+        true => "Font".to_string(),
+        false => name,
+    }
+}
+
+// An image's colors as an RGB image, with its alpha as a soft mask in the object after it
+fn write_image(pdf: &mut PdfFile, image: &LoadedImage, id: usize) {
+    let [width, height] = image.pixels.size;
+    let mut colors = Vec::with_capacity(width * height * 3);
+    let mut alpha = Vec::with_capacity(width * height);
+    for pixel in &image.pixels.pixels {
+        let [r, g, b, a] = pixel.to_srgba_unmultiplied();
+        colors.extend([r, g, b]);
+        alpha.push(a);
+    }
+    let opaque = alpha.iter().all(|&a| a == 255);
+    let mask = match opaque {
+        true => String::new(),
+        false => format!(" /SMask {} 0 R", id + 1),
+    };
+    let dictionary = |color_space: &str, mask: &str| {
+        format!(
+            "<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /{} \
+             /BitsPerComponent 8 /Filter /FlateDecode{} >>",
+            width, height, color_space, mask
+        )
+    };
+    pdf.object(id, &dictionary("DeviceRGB", &mask), Some(&deflate(&colors)));
+    if !opaque {
+        pdf.object(
+            id + 1,
+            &dictionary("DeviceGray", ""),
+            Some(&deflate(&alpha)),
+        );
+    }
+}
+
+/// The bytes of a PDF file and where each object starts in them
+#[derive(Default)]
+struct PdfFile {
+    bytes: Vec<u8>,
+    offsets: BTreeMap<usize, usize>,
+}
+
+impl PdfFile {
+    fn object(&mut self, id: usize, dictionary: &str, stream: Option<&[u8]>) {
+        if self.bytes.is_empty() {
+            // The binary comment marks the file as binary to tools that guess
+            self.bytes
+                .extend_from_slice(b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n");
+        }
+        self.offsets.insert(id, self.bytes.len());
+        let _ = writeln!(self.bytes, "{} 0 obj", id);
+        match stream {
+            Some(stream) => {
+                // The length goes into the dictionary
+                let dictionary = dictionary.trim_end_matches(">>").trim_end();
+                let _ = write!(
+                    self.bytes,
+                    "{} /Length {} >>\nstream\n",
+                    dictionary,
+                    stream.len()
+                );
+                self.bytes.extend_from_slice(stream);
+                self.bytes.extend_from_slice(b"\nendstream");
+            }
+            None => self.bytes.extend_from_slice(dictionary.as_bytes()),
+        }
+        self.bytes.extend_from_slice(b"\nendobj\n");
+    }
+
+    // Adds the cross-reference table and trailer. Object ids without an object, such as
+    // the masks of opaque images, are free entries.
+    fn finish(mut self, info_id: usize) -> Vec<u8> {
+        let size = self.offsets.keys().max().map_or(1, |id| id + 1);
+        let xref = self.bytes.len();
+        let _ = write!(self.bytes, "xref\n0 {}\n0000000000 65535 f \n", size);
+        for id in 1..size {
+            match self.offsets.get(&id) {
+                Some(offset) => {
+                    let _ = writeln!(self.bytes, "{:010} 00000 n ", offset);
+                }
+                None => self.bytes.extend_from_slice(b"0000000000 00000 f \n"),
+            }
+        }
+        let _ = write!(
+            self.bytes,
+            "trailer\n<< /Size {} /Root 1 0 R /Info {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            size, info_id, xref
+        );
+        self.bytes
+    }
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    let _ = encoder.write_all(data);
+    encoder.finish().unwrap_or_default()
+}
+
+// A number with at most three decimals and no trailing zeros
+fn number(value: f32) -> String {
+    let value = if value.is_finite() { value } else { 0.0 };
+    let text = format!("{:.3}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "" | "-0" => "0".to_string(),
+        text => text.to_string(),
+    }
+}
+
+fn rgb(r: u8, g: u8, b: u8) -> String {
+    [r, g, b]
+        .map(|channel| number(channel as f32 / 255.0))
+        .join(" ")
+}
+
+// A text string in the document information, as UTF-16 with a byte order mark
+fn text_string(text: &str) -> String {
+    let mut hex = String::from("<FEFF");
+    for unit in text.encode_utf16() {
+        let _ = write!(hex, "{:04X}", unit);
+    }
+    hex.push('>');
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::Image;
+
+    #[test]
+    fn test_write_pdf() {
+        let image = LoadedImage::new(
+            url::Url::parse("http://localhost/a.png").unwrap(),
+            egui::ColorImage::new([2, 2], Color32::from_rgba_unmultiplied(255, 0, 0, 128)),
+        );
+        let list = DisplayList {
+            items: vec![
+                DisplayItem::Rect {
+                    rect: Rect::from_min_size(Pos2::ZERO, egui::vec2(100.0, 40.0)),
+                    color: Color32::from_rgb(255, 0, 0),
+                },
+                DisplayItem::Text {
+                    rect: Rect::from_min_size(egui::pos2(0.0, 50.0), egui::vec2(100.0, 20.0)),
+                    text: "Hi €".to_string(),
+                    font: FontId::proportional(16.0),
+                    color: Color32::BLACK,
+                },
+                DisplayItem::Image {
+                    rect: Rect::from_min_size(egui::pos2(0.0, 150.0), egui::vec2(20.0, 20.0)),
+                    fitted: Rect::from_min_size(egui::pos2(0.0, 150.0), egui::vec2(20.0, 20.0)),
+                    image: Image {
+                        src: None,
+                        alt: String::new(),
+                        width: None,
+                        height: None,
+                        content: ImageContent::Loaded {
+                            image: Arc::new(image),
+                            density: 1.0,
+                        },
+                    },
+                    opacity: 1.0,
+                },
+            ],
+            containers: vec![None; 3],
+            size: egui::vec2(100.0, 170.0),
+            ..Default::default()
+        };
+        let page = PageStyle {
+            width: 200.0,
+            height: 120.0,
+            margins: [10.0; 4],
+        };
+        let fonts = egui::FontDefinitions::default();
+        let pdf = write_pdf(&list, &[0.0, 100.0], &page, &fonts, "Test");
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.starts_with("%PDF-1.7"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(text.contains("/Count 2"));
+        assert!(text.contains("/MediaBox [0 0 150 90]"));
+        // The text's font is embedded with a way back to the characters
+        assert!(text.contains("/Subtype /Type0"));
+        assert_eq!(text.matches("/FontFile2").count(), 1);
+        assert!(text.contains("/ToUnicode"));
+        // The translucent image has a mask
+        assert!(text.contains("/SMask"));
+
+        // Every object the cross-reference table lists starts where it says
+        let xref = text.rfind("xref\n").unwrap();
+        for (id, line) in text[xref..].lines().skip(2).enumerate() {
+            if line.ends_with(" n ") {
+                let offset: usize = line[..10].parse().unwrap();
+                assert!(pdf[offset..].starts_with(format!("{} 0 obj", id).as_bytes()));
+            }
+        }
+    }
+}
@@ -0,0 +1,266 @@
+// A CPU backend for egui's output: fills the triangles of tessellated shapes into an image,
+// so pages can be rendered without a display or a GPU
+
+use eframe::egui::{
+    epaint::{ClippedPrimitive, ImageData, Primitive, Vertex},
+    Color32, ColorImage, Pos2, TextureFilter, TextureId, TextureOptions, TexturesDelta,
+};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The textures egui has uploaded, and what draws with them
+#[derive(Default)]
+pub struct Rasterizer {
+    textures: HashMap<TextureId, (ColorImage, TextureOptions)>,
+}
+
+impl Rasterizer {
+    // Uploads new and changed textures, before painting a frame
+    pub fn set_textures(&mut self, delta: &TexturesDelta) {
+        for (id, image_delta) in &delta.set {
+            let image = match &image_delta.image {
+                ImageData::Color(image) => (**image).clone(),
+                ImageData::Font(font) => ColorImage {
+                    size: font.size,
+                    pixels: font.srgba_pixels(None).collect(),
+                },
+            };
+            match (image_delta.pos, self.textures.get_mut(id)) {
+                (Some([x, y]), Some((texture, _))) => {
+                    let width = texture.size[0];
+                    for (row, pixels) in image.pixels.chunks(image.size[0]).enumerate() {
+                        let start = (y + row) * width + x;
+                        texture.pixels[start..start + pixels.len()].copy_from_slice(pixels);
+                    }
+                }
+                _ => {
+                    self.textures.insert(*id, (image, image_delta.options));
+                }
+            }
+        }
+    }
+
+    // Drops the textures egui is done with, after painting a frame
+    pub fn free_textures(&mut self, delta: &TexturesDelta) {
+        for id in &delta.free {
+            self.textures.remove(id);
+        }
+    }
+
+    // Draws meshes over an image, scaling points to pixels. Meshes whose texture is
+    // unknown are skipped, as are paint callbacks.
+    pub fn draw(
+        &self,
+        primitives: &[ClippedPrimitive],
+        image: &mut ColorImage,
+        pixels_per_point: f32,
+    ) {
+        for primitive in primitives {
+            let Primitive::Mesh(mesh) = &primitive.primitive else {
+                continue;
+            };
+            let Some(texture) = self.textures.get(&mesh.texture_id) else {
+                continue;
+            };
+            let clip = primitive.clip_rect * pixels_per_point;
+            for triangle in mesh.indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| {
+                    let vertex = mesh.vertices[triangle[i] as usize];
+                    Vertex {
+                        pos: (vertex.pos.to_vec2() * pixels_per_point).to_pos2(),
+                        ..vertex
+                    }
+                });
+                fill_triangle(image, texture, [a, b, c], clip);
+            }
+        }
+    }
+}
+
+// Which side of the line from `a` to `b` a point is on, as twice the area of the triangle
+// they make
+fn edge(a: Pos2, b: Pos2, point: Pos2) -> f32 {
+    (b.x - a.x) * (point.y - a.y) - (b.y - a.y) * (point.x - a.x)
+}
+
+// Fills the pixels whose centres are inside a triangle. Centres exactly on an edge two
+// triangles share are filled by only one of them, so translucent shapes don't get seams.
+fn fill_triangle(
+    image: &mut ColorImage,
+    (texture, options): &(ColorImage, TextureOptions),
+    [a, mut b, mut c]: [Vertex; 3],
+    clip: eframe::egui::Rect,
+) {
+    let area = edge(a.pos, b.pos, c.pos);
+    if area == 0.0 {
+        return;
+    }
+    // Wind every triangle the same way, so shared edges run in opposite directions
+    if area < 0.0 {
+        std::mem::swap(&mut b, &mut c);
+    }
+    let area = area.abs();
+    let [width, height] = image.size;
+    let bounds = eframe::egui::Rect::from_points(&[a.pos, b.pos, c.pos]).intersect(clip);
+    let x_range = (bounds.min.x.floor().max(0.0) as usize)
+        ..(bounds.max.x.ceil().min(width as f32).max(0.0) as usize);
+    let y_range = (bounds.min.y.floor().max(0.0) as usize)
+        ..(bounds.max.y.ceil().min(height as f32).max(0.0) as usize);
+    let edges = [(b.pos, c.pos), (c.pos, a.pos), (a.pos, b.pos)];
+    // Of the two triangles on an edge, the one it runs down or left in owns its centres
+    let owns = edges.map(|(from, to)| to.y > from.y || (to.y == from.y && to.x < from.x));
+    for y in y_range {
+        for x in x_range.clone() {
+            let point = Pos2::new(x as f32 + 0.5, y as f32 + 0.5);
+            if !clip.contains(point) {
+                continue;
+            }
+            let weights = [0, 1, 2].map(|i| edge(edges[i].0, edges[i].1, point));
+            let inside = (0..3).all(|i| weights[i] > 0.0 || (weights[i] == 0.0 && owns[i]));
+            if !inside {
+                continue;
+            }
+            let [wa, wb, wc] = weights.map(|weight| weight / area);
+            let uv = a.uv.to_vec2() * wa + b.uv.to_vec2() * wb + c.uv.to_vec2() * wc;
+            let sample = sample(texture, *options, uv.to_pos2());
+            let color = [a.color, b.color, c.color].map(|color| color.to_array().map(f32::from));
+            let mut source = [0.0; 4];
+            for channel in 0..4 {
+                let tint = color[0][channel] * wa + color[1][channel] * wb + color[2][channel] * wc;
+                source[channel] = sample[channel] * tint / 255.0;
+            }
+            let pixel = &mut image.pixels[y * width + x];
+            *pixel = blend(*pixel, source);
+        }
+    }
+}
+
+// The premultiplied color of a texture at a point, from 0 to 255 per channel
+fn sample(texture: &ColorImage, options: TextureOptions, uv: Pos2) -> [f32; 4] {
+    let [width, height] = texture.size;
+    let texel = |x: isize, y: isize| {
+        let x = x.clamp(0, width as isize - 1) as usize;
+        let y = y.clamp(0, height as isize - 1) as usize;
+        texture.pixels[y * width + x].to_array().map(f32::from)
+    };
+    let x = uv.x * width as f32;
+    let y = uv.y * height as f32;
+    if options.magnification == TextureFilter::Nearest {
+        return texel(x.floor() as isize, y.floor() as isize);
+    }
+    // Bilinear filtering between the four nearest texel centres
+    let (x, y) = (x - 0.5, y - 0.5);
+    let (left, top) = (x.floor(), y.floor());
+    let (fx, fy) = (x - left, y - top);
+    let (left, top) = (left as isize, top as isize);
+    let corners = [
+        texel(left, top),
+        texel(left + 1, top),
+        texel(left, top + 1),
+        texel(left + 1, top + 1),
+    ];
+    let weights = [
+        (1.0 - fx) * (1.0 - fy),
+        fx * (1.0 - fy),
+        (1.0 - fx) * fy,
+        fx * fy,
+    ];
+    let mut color = [0.0; 4];
+    for (corner, weight) in corners.iter().zip(weights) {
+        for channel in 0..4 {
+            color[channel] += corner[channel] * weight;
+        }
+    }
+    color
+}
+
+// Draws a premultiplied color over a pixel
+fn blend(destination: Color32, source: [f32; 4]) -> Color32 {
+    let keep = 1.0 - source[3] / 255.0;
+    let [r, g, b, a] = destination.to_array();
+    let mix = |source: f32, destination: u8| {
+        (source + destination as f32 * keep)
+            .round()
+            .clamp(0.0, 255.0) as u8
+    };
+    Color32::from_rgba_premultiplied(
+        mix(source[0], r),
+        mix(source[1], g),
+        mix(source[2], b),
+        mix(source[3], a),
+    )
+}
+
+// Writes an image to a PNG file
+pub fn save_png(image: &ColorImage, path: &Path) -> Result<(), String> {
+    let pixels = image
+        .pixels
+        .iter()
+        .flat_map(|pixel| pixel.to_srgba_unmultiplied())
+        .collect();
+    let [width, height] = image.size;
+    let buffer = image::RgbaImage::from_raw(width as u32, height as u32, pixels)
+        .ok_or("Image size doesn't match its pixels")?;
+    buffer
+        .save_with_format(path, image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to save PNG: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eframe::egui::{self, epaint, Rect, Shape};
+
+    #[test]
+    fn test_rasterize_shapes() {
+        let fonts = epaint::text::Fonts::new(1.0, 2048, egui::FontDefinitions::default());
+        fonts.begin_pass(1.0, 2048);
+        let red = Rect::from_min_max(Pos2::new(2.0, 2.0), Pos2::new(12.0, 12.0));
+        let translucent = Rect::from_min_max(Pos2::new(0.0, 20.0), Pos2::new(20.0, 40.0));
+        let shapes = vec![
+            Shape::rect_filled(red, 0.0, Color32::RED),
+            Shape::rect_filled(translucent, 0.0, Color32::from_black_alpha(128)),
+            Shape::text(
+                &fonts,
+                Pos2::new(24.0, 0.0),
+                egui::Align2::LEFT_TOP,
+                "Hi",
+                egui::FontId::proportional(16.0),
+                Color32::BLACK,
+            ),
+        ];
+        let clipped = shapes
+            .into_iter()
+            .map(|shape| epaint::ClippedShape {
+                clip_rect: Rect::from_min_max(Pos2::ZERO, Pos2::new(40.0, 36.0)),
+                shape,
+            })
+            .collect();
+        let mut tessellator =
+            epaint::Tessellator::new(1.0, Default::default(), fonts.font_image_size(), vec![]);
+        let primitives = tessellator.tessellate_shapes(clipped);
+        let mut rasterizer = Rasterizer::default();
+        let font_image = fonts.image();
+        rasterizer.set_textures(&TexturesDelta {
+            set: vec![(
+                TextureId::default(),
+                epaint::ImageDelta::full(font_image, TextureOptions::LINEAR),
+            )],
+            free: vec![],
+        });
+        let mut image = ColorImage::new([40, 40], Color32::WHITE);
+        rasterizer.draw(&primitives, &mut image, 1.0);
+        let pixel = |x: usize, y: usize| image.pixels[y * 40 + x];
+
+        assert_eq!(pixel(7, 7), Color32::RED);
+        assert_eq!(pixel(1, 7), Color32::WHITE);
+        // Translucent fills blend evenly, without seams along their triangles' edges
+        let grey = pixel(5, 25);
+        assert!((grey.r() as i32 - 127).abs() <= 1);
+        assert!((21..35).all(|y| (1..19).all(|x| pixel(x, y) == grey)));
+        // Clipped at the bottom
+        assert_eq!(pixel(5, 38), Color32::WHITE);
+        // The text is drawn with the font atlas
+        assert!((24..40).any(|x| (0..16).any(|y| pixel(x, y).r() < 100)));
+    }
+}
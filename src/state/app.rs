@@ -1,3 +1,4 @@
+use crate::fonts::FontRegistry;
 use crate::state::settings::AppSettings;
 use crate::state::window::Window;
 use std::sync::Arc;
@@ -5,22 +6,29 @@ use std::sync::Arc;
 pub struct OliverExplorer {
     pub windows: Vec<Window>,
     pub settings: Arc<AppSettings>,
+    // Web fonts of every open page, registered with egui
+    pub fonts: FontRegistry,
     next_window_id: usize,
 }
 
 impl Default for OliverExplorer {
     fn default() -> Self {
-        let default_settings = Arc::new(AppSettings::default());
-        let default_window = Window::new(0, Arc::clone(&default_settings));
+        Self::with_settings(AppSettings::default())
+    }
+}
+
+impl OliverExplorer {
+    pub fn with_settings(settings: AppSettings) -> Self {
+        let settings = Arc::new(settings);
+        let default_window = Window::new(0, Arc::clone(&settings));
         Self {
             windows: vec![default_window],
-            settings: default_settings,
+            settings,
+            fonts: FontRegistry::default(),
             next_window_id: 1,
         }
     }
-}
 
-impl OliverExplorer {
     pub fn new_window(&mut self) {
         let new_window = Window::new(self.next_window_id, Arc::clone(&self.settings));
         self.windows.push(new_window);
@@ -0,0 +1,160 @@
+// Dark mode: the sites it's on for, which pages are shown in dark colors of their own and
+// which are recolored
+
+use crate::css;
+use crate::dom;
+use crate::rendering;
+use crate::state::page::Page;
+use crate::state::site_store::{data_path, SiteStore};
+use crate::state::tab::Tab;
+use eframe::egui::Color32;
+use std::path::PathBuf;
+use std::sync::Arc;
+use url::Url;
+
+/// The sites shown in forced dark mode, by origin. With a path they're read from and
+/// saved to a file, one origin per line.
+#[derive(Debug, Default)]
+pub struct DarkSites {
+    sites: SiteStore,
+}
+
+impl DarkSites {
+    // Sites kept in the file at `path`, which needn't exist yet
+    pub fn load(path: PathBuf) -> Self {
+        Self {
+            sites: SiteStore::load(path),
+        }
+    }
+
+    // The file in the user's data directory
+    pub fn default_path() -> Option<PathBuf> {
+        data_path("dark_sites")
+    }
+
+    pub fn contains(&self, url: &Url) -> bool {
+        self.sites.get(url).is_some()
+    }
+
+    pub fn set(&self, url: &Url, dark: bool) -> Result<(), String> {
+        self.sites
+            .set(url, dark.then(String::new))
+            .map_err(|e| format!("Failed to save dark mode: {}", e))
+    }
+}
+
+impl Page {
+    // The style of the document's root element
+    fn root_style(&self) -> Option<&Arc<css::ComputedStyle>> {
+        let html = self
+            .document
+            .nodes
+            .iter()
+            .position(|node| matches!(node, dom::Node::Element(_)))?;
+        self.styles.style_at(&[html])
+    }
+
+    // Whether the page says it can be shown dark, with `color-scheme` on its root element
+    // or in a `<meta name="color-scheme">`
+    pub fn supports_dark_scheme(&self) -> bool {
+        self.root_style()
+            .is_some_and(|style| style.supports_dark_scheme())
+    }
+
+    // Whether the page is shown in its own dark colors, as it supports them and the device
+    // prefers them
+    pub fn uses_dark_scheme(&self) -> bool {
+        self.root_style()
+            .is_some_and(|style| self.stylist.uses_dark_scheme(style))
+    }
+
+    // The color behind pages without a background of their own
+    pub fn canvas_color(&self) -> Color32 {
+        match self.uses_dark_scheme() {
+            true => rendering::color32(css::DARK_CANVAS, 1.0),
+            false => Color32::WHITE,
+        }
+    }
+}
+
+impl Tab {
+    // Whether forced dark mode is on for the tab's site
+    pub fn is_dark_site(&self) -> bool {
+        self.settings.dark_sites.contains(&self.url)
+    }
+
+    // Whether the page is recolored dark: it's on a dark site and can't be shown dark by
+    // itself. Pages that can are styled for a device preferring dark instead.
+    pub fn forced_dark(&self) -> bool {
+        self.is_dark_site() && !self.page.supports_dark_scheme()
+    }
+
+    // Turns forced dark mode on or off for every page of the tab's site
+    pub fn toggle_forced_dark(&mut self) -> Result<(), String> {
+        let dark = !self.is_dark_site();
+        self.settings.dark_sites.set(&self.url, dark)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::settings::AppSettings;
+    use std::fs;
+
+    #[test]
+    fn test_forced_dark_sites() {
+        let path = std::env::temp_dir()
+            .join(format!("oliver-explorer-dark-{}", std::process::id()))
+            .join("dark_sites");
+        let settings = AppSettings {
+            dark_sites: DarkSites::load(path.clone()),
+            ..Default::default()
+        };
+        let mut tab = Tab::new(0, Arc::new(settings));
+        tab.url = Url::parse("https://news.example/today").unwrap();
+        let url = tab.url.clone();
+        let page = |html: &str| Page::new(html, &url, css::Device::default());
+        tab.page = page("<p>Bright</p>");
+        assert!(!tab.forced_dark());
+        tab.toggle_forced_dark().unwrap();
+        assert!(tab.forced_dark());
+
+        // Pages that can be dark by themselves are left alone
+        tab.page =
+            page("<html><head><style>:root { color-scheme: light dark }</style></head></html>");
+        assert!(!tab.forced_dark());
+        tab.page = page("<html><head><meta name='color-scheme' content='dark'></head></html>");
+        assert!(!tab.forced_dark());
+        tab.page =
+            page("<html><head><meta name='color-scheme' content='only light'></head></html>");
+        assert!(tab.forced_dark());
+
+        // They're shown in their own dark colors on a device preferring dark, light text on a
+        // dark canvas when they don't choose colors themselves
+        let mut page = page(
+            "<html><head><style>:root { color-scheme: light dark }</style></head>\
+             <body><p>Text</p></body></html>",
+        );
+        assert_eq!(page.canvas_color(), Color32::WHITE);
+        assert!(page.set_device(css::Device {
+            color_scheme: css::ColorScheme::Dark,
+            ..Default::default()
+        }));
+        assert!(page.uses_dark_scheme());
+        assert_eq!(
+            page.canvas_color(),
+            rendering::color32(css::DARK_CANVAS, 1.0)
+        );
+        let text = page.styles.style_at(&[0, 1, 0]).unwrap();
+        assert_eq!(text.color("color"), css::Color::WHITE);
+
+        // The site stays dark in later sessions
+        let sites = DarkSites::load(path.clone());
+        assert!(sites.contains(&Url::parse("https://news.example/archive").unwrap()));
+        assert!(!sites.contains(&Url::parse("https://other.example/").unwrap()));
+        tab.toggle_forced_dark().unwrap();
+        assert!(!DarkSites::load(path.clone()).contains(&tab.url));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,290 @@
+use crate::dom::{Document, Selection, TextPosition};
+use crate::layout::{Fragment, FragmentKind, TextRun};
+use eframe::egui::Rect;
+use regex::{Regex, RegexBuilder};
+
+/// A match of the find bar's query: the text it covers and the line it starts on
+#[derive(Debug, Clone, PartialEq)]
+pub struct FindMatch {
+    pub range: Selection,
+    pub rect: Rect,
+}
+
+/// The find bar of a tab, with the matches of its query in the rendered text of the page
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FindState {
+    pub open: bool,
+    pub query: String,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    pub regex: bool,
+    pub matches: Vec<FindMatch>,
+    // Index of the current match
+    pub current: usize,
+    // Why the query couldn't be searched for, such as an invalid pattern
+    pub error: Option<String>,
+    // The query and options the matches are for
+    searched: Option<(String, bool, bool, bool)>,
+    // The layout they were found in
+    searched_layout: u64,
+    // Whether the current match has moved since the page was last scrolled to it
+    moved: bool,
+}
+
+// A text run in the rendered text, starting at `start`
+struct Piece<'a> {
+    start: usize,
+    fragment: &'a Fragment,
+    run: &'a TextRun,
+}
+
+impl FindState {
+    pub fn open(&mut self) {
+        self.open = true;
+        self.moved = true;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.matches.clear();
+        self.searched = None;
+    }
+
+    // Searches the rendered text of a layout again if it or the query has changed since the
+    // last search. The current match stays where it was unless the query changed.
+    pub fn update(&mut self, root: &Fragment, document: &Document, layout_id: u64) {
+        let key = (
+            self.query.clone(),
+            self.case_sensitive,
+            self.whole_word,
+            self.regex,
+        );
+        if self.searched.as_ref() == Some(&key) && self.searched_layout == layout_id {
+            return;
+        }
+        if self.searched.as_ref() != Some(&key) {
+            self.current = 0;
+            self.moved = true;
+        }
+        self.searched = Some(key);
+        self.searched_layout = layout_id;
+        self.error = None;
+        self.matches.clear();
+        if self.query.is_empty() {
+            return;
+        }
+        let pattern = match self.pattern() {
+            Ok(pattern) => pattern,
+            Err(e) => {
+                self.error = Some(e);
+                return;
+            }
+        };
+        let mut text = String::new();
+        let mut pieces = Vec::new();
+        collect_text(root, document, &mut text, &mut pieces);
+        // Whole words have no letters, digits or underscores right before or after them,
+        // whatever the query starts and ends with
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let whole = |start: usize, end: usize| {
+            !text[..start].chars().next_back().is_some_and(is_word)
+                && !text[end..].chars().next().is_some_and(is_word)
+        };
+        self.matches = pattern
+            .find_iter(&text)
+            .filter(|found| !found.is_empty())
+            .filter(|found| !self.whole_word || whole(found.start(), found.end()))
+            .filter_map(|found| find_match(&pieces, found.start(), found.end()))
+            .collect();
+        if self.current >= self.matches.len() {
+            self.current = 0;
+        }
+    }
+
+    // The query as a regex, escaped unless it's a pattern itself
+    fn pattern(&self) -> Result<Regex, String> {
+        let pattern = match self.regex {
+            true => self.query.clone(),
+            false => regex::escape(&self.query),
+        };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .build()
+            .map_err(|_| "Invalid pattern".to_string())
+    }
+
+    // Steps to the next match, wrapping around at the end
+    pub fn next(&mut self) {
+        if !self.matches.is_empty() {
+            self.current = (self.current + 1) % self.matches.len();
+            self.moved = true;
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if !self.matches.is_empty() {
+            self.current = (self.current + self.matches.len() - 1) % self.matches.len();
+            self.moved = true;
+        }
+    }
+
+    // Where the current match is if it's moved since this was last asked, to scroll to it
+    pub fn take_moved(&mut self) -> Option<Rect> {
+        let found = self.matches.get(self.current)?;
+        std::mem::take(&mut self.moved).then_some(found.rect)
+    }
+
+    // The count shown in the find bar, such as "3 of 17"
+    pub fn status(&self) -> String {
+        if let Some(error) = &self.error {
+            return error.clone();
+        }
+        match self.matches.len() {
+            _ if self.query.is_empty() => String::new(),
+            0 => "No matches".to_string(),
+            count => format!("{} of {}", self.current + 1, count),
+        }
+    }
+}
+
+// Joins the visible text runs of DOM text nodes in a fragment tree, with a space between
+// lines and a line break between blocks, so text that isn't rendered can't match
+fn collect_text<'a>(
+    fragment: &'a Fragment,
+    document: &Document,
+    text: &mut String,
+    pieces: &mut Vec<Piece<'a>>,
+) {
+    let separator = match fragment.kind {
+        FragmentKind::Box => Some('\n'),
+        FragmentKind::Line => Some(' '),
+        _ => None,
+    };
+    if let Some(separator) = separator {
+        if !text.is_empty() && !text.ends_with(char::is_whitespace) {
+            text.push(separator);
+        }
+    }
+    if let FragmentKind::Text(run) = &fragment.kind {
+        let rendered = fragment
+            .node
+            .as_deref()
+            .is_some_and(|node| document.text_at(node).is_some());
+        if rendered && fragment.style.keyword("visibility") != Some("hidden") {
+            pieces.push(Piece {
+                start: text.len(),
+                fragment,
+                run,
+            });
+            text.push_str(&run.text);
+        }
+    }
+    for child in &fragment.children {
+        collect_text(child, document, text, pieces);
+    }
+}
+
+// Turns a match in the joined text into a range of the DOM text. Matches that start or
+// end in a separator are trimmed to the runs around it.
+fn find_match(pieces: &[Piece], start: usize, end: usize) -> Option<FindMatch> {
+    // Pieces are in order and don't overlap, so both ends are found by bisection
+    let first = pieces.partition_point(|piece| piece.start + piece.run.text.len() <= start);
+    let first = pieces.get(first)?;
+    let last = pieces
+        .partition_point(|piece| piece.start < end)
+        .checked_sub(1)?;
+    let last = &pieces[last];
+    if first.start >= end {
+        return None;
+    }
+    let position = |piece: &Piece, index: usize| {
+        Some(TextPosition {
+            node: piece.fragment.node.clone()?,
+            offset: piece.run.source_offset(index),
+        })
+    };
+    let from = position(first, start.saturating_sub(first.start))?;
+    let to = position(last, (end - last.start).min(last.run.text.len()))?;
+    Some(FindMatch {
+        range: Selection::new(from, to),
+        rect: first.fragment.rect,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css;
+    use crate::fonts::FontSet;
+    use crate::html;
+    use crate::layout::{self, FixedMetrics, LayoutContext};
+
+    #[test]
+    fn test_find_in_rendered_text() {
+        let document = html::parse_document(
+            "<body style='margin: 0'><p>Find the cat. The <b>cat</b>alog</p>\
+             <p style='display: none'>cat</p><p style='visibility: hidden'>cat</p>\
+             <p>Concat cat</p></body>",
+        );
+        let stylist = css::Stylist::for_document(&document, css::Device::default());
+        let styles = stylist.style_document(&document);
+        let root = layout::build_box_tree(&document, &styles).unwrap();
+        let fonts = FontSet::default();
+        let ctx = LayoutContext::new(&FixedMetrics, &fonts);
+        let root = layout::layout_document(&root, 400.0, &ctx);
+        let mut find = FindState::default();
+        find.open();
+        let search = |find: &mut FindState, query: &str| {
+            find.query = query.to_string();
+            find.update(&root, &document, 1);
+            find.matches.len()
+        };
+
+        // Hidden text isn't matched, and matches run across elements
+        assert_eq!(search(&mut find, "cat"), 4);
+        assert_eq!(find.status(), "1 of 4");
+        let found = &find.matches[1];
+        assert_eq!(found.range.start().node, vec![0, 1, 0, 1, 0]);
+        assert_eq!(search(&mut find, "catalog"), 1);
+        let found = &find.matches[0];
+        assert_eq!(found.range.start().node, vec![0, 1, 0, 1, 0]);
+        assert_eq!(
+            found.range.end(),
+            &TextPosition {
+                node: vec![0, 1, 0, 2],
+                offset: 4
+            }
+        );
+
+        find.whole_word = true;
+        assert_eq!(search(&mut find, "cat"), 2);
+        assert_eq!(search(&mut find, "cat."), 1);
+        find.case_sensitive = true;
+        assert_eq!(search(&mut find, "the"), 1);
+        find.whole_word = false;
+        find.case_sensitive = false;
+        find.regex = true;
+        assert_eq!(search(&mut find, r"con\w+"), 1);
+        assert_eq!(search(&mut find, "("), 0);
+        assert_eq!(find.status(), "Invalid pattern");
+
+        // Stepping wraps around and scrolls to the current match once
+        assert_eq!(search(&mut find, "cat"), 4);
+        assert!(find.take_moved().is_some());
+        assert_eq!(find.take_moved(), None);
+        find.previous();
+        assert_eq!(find.status(), "4 of 4");
+        assert_eq!(find.take_moved(), Some(find.matches[3].rect));
+        find.next();
+        assert_eq!(find.current, 0);
+        find.update(&root, &document, 2);
+        assert_eq!(find.current, 0);
+
+        // Nothing is searched again until the query or the layout changes
+        find.matches.clear();
+        find.update(&root, &document, 2);
+        assert!(find.matches.is_empty());
+        find.update(&root, &document, 3);
+        assert_eq!(find.matches.len(), 4);
+    }
+}
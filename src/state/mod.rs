@@ -1,11 +1,27 @@
 mod app;
+mod dark_mode;
+mod find;
+mod page;
+mod print;
+mod reader;
+mod screenshot;
+mod scroll;
 mod settings;
+mod site_store;
 mod tab;
 mod theme;
 mod window;
+mod zoom;
 
 pub use app::OliverExplorer;
+pub use dark_mode::DarkSites;
+pub use find::{FindMatch, FindState};
+pub use page::Page;
+pub use print::download_dir;
+pub use reader::{ReaderMode, ReaderPalette, READER_FONT_SIZES};
+pub use scroll::ScrollState;
 pub use settings::AppSettings;
 pub use tab::Tab;
 pub use theme::default_style;
-pub use window::Window;
+pub use window::{Window, MESSAGE_DURATION};
+pub use zoom::{zoom_in, zoom_out, SiteZoom, ZOOM_LEVELS};
@@ -0,0 +1,750 @@
+use crate::css;
+use crate::dom;
+use crate::fonts;
+use crate::html;
+use crate::images;
+use crate::layout;
+use eframe::egui::{FontFamily, Pos2, Rect, Vec2};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use url::Url;
+
+// Counts the layouts made by every page, so each one has its own id
+static LAYOUTS: AtomicU64 = AtomicU64::new(0);
+
+// The parsed document shown in a tab and the styles computed for it
+#[derive(Debug, Default)]
+pub struct Page {
+    pub document: dom::Document,
+    pub stylist: css::Stylist,
+    pub styles: css::StyleTree,
+    // Web fonts declared by the page's `@font-face` rules
+    pub fonts: fonts::FontSet,
+    // Stylesheets and fonts that failed to load, the page is styled without them
+    pub resource_errors: Vec<css::ResourceError>,
+    pub images: images::ImageCache,
+    // The selected part of the document, if anything is selected
+    pub selection: Option<dom::Selection>,
+    // The document's URL, which image sources are resolved against
+    url: Option<Url>,
+    // Roots of the subtrees whose styles are out of date, as element paths
+    dirty: Vec<Vec<usize>>,
+    hovered: Option<Vec<usize>>,
+    focused: Option<Vec<usize>>,
+    // The last layout, dropped whenever the styles change
+    laid_out: Option<LaidOut>,
+}
+
+// A layout and what it was computed for
+#[derive(Debug)]
+struct LaidOut {
+    width: f32,
+    // Fixed boxes are positioned in the viewport, so its height matters too
+    height: f32,
+    // Web fonts become available a frame after they're registered, which changes layout
+    families: Vec<FontFamily>,
+    // Images still loading when it was made, and how many loads had finished by then
+    pending_images: bool,
+    finished_images: usize,
+    root: Option<layout::Fragment>,
+    id: u64,
+}
+
+impl Page {
+    // Parses the document and loads its stylesheets, resolving them against `url`
+    pub fn new(html_content: &str, url: &Url, device: css::Device) -> Self {
+        let document = html::parse_document(html_content);
+        let (sheets, mut resource_errors) = css::load_stylesheets(&document, url);
+        let mut stylist = css::Stylist::new(device);
+        stylist.add_stylesheet(css::user_agent_stylesheet(), css::Origin::UserAgent);
+        for sheet in sheets {
+            stylist.add_stylesheet(sheet, css::Origin::Author);
+        }
+        let (faces, font_errors) = fonts::load_font_faces(&stylist.font_faces(), url);
+        resource_errors.extend(font_errors);
+        let styles = stylist.style_document(&document);
+        Self {
+            document,
+            stylist,
+            styles,
+            fonts: fonts::FontSet::new(faces),
+            resource_errors,
+            images: images::ImageCache::global(),
+            url: Some(url.clone()),
+            ..Default::default()
+        }
+    }
+
+    pub fn device(&self) -> &css::Device {
+        self.stylist.device()
+    }
+
+    // Updates the device the page is styled for, restyling only if the result would change.
+    // Returns true if the page was restyled.
+    pub fn set_device(&mut self, device: css::Device) -> bool {
+        // Pages that can be dark have different default colors in each scheme
+        let scheme_changed = device.color_scheme != self.device().color_scheme;
+        let restyle =
+            self.stylist.set_device(device) || (scheme_changed && self.supports_dark_scheme());
+        if !restyle {
+            return false;
+        }
+        self.restyle();
+        true
+    }
+
+    pub fn restyle(&mut self) {
+        self.styles = self.stylist.style_document(&self.document);
+        self.dirty.clear();
+        self.laid_out = None;
+    }
+
+    // Lays the page out for a viewport of the given width, reusing the last layout if
+    // nothing has changed since. None if the document generates no boxes.
+    pub fn layout(
+        &mut self,
+        width: f32,
+        measurer: &dyn layout::TextMeasurer,
+    ) -> Option<&layout::Fragment> {
+        let families = measurer.families();
+        let height = self.device().viewport_height;
+        let finished_images = self.images.finished();
+        let current = self.laid_out.as_ref().is_some_and(|laid_out| {
+            laid_out.width == width
+                && laid_out.height == height
+                && laid_out.families == families
+                && (!laid_out.pending_images || laid_out.finished_images == finished_images)
+        });
+        if !current {
+            let context =
+                layout::LayoutContext::new(measurer, &self.fonts).with_viewport_height(height);
+            let mut pending_images = false;
+            let root = layout::build_box_tree(&self.document, &self.styles).map(|mut root| {
+                pending_images = self.load_images(&mut root);
+                layout::layout_document(&root, width, &context)
+            });
+            self.laid_out = Some(LaidOut {
+                width,
+                height,
+                families,
+                pending_images,
+                finished_images,
+                root,
+                id: LAYOUTS.fetch_add(1, Ordering::Relaxed) + 1,
+            });
+        }
+        self.laid_out.as_ref()?.root.as_ref()
+    }
+
+    // The root fragment of the last layout
+    pub fn root(&self) -> Option<&layout::Fragment> {
+        self.laid_out.as_ref()?.root.as_ref()
+    }
+
+    // Identifies the last layout, so what's derived from it is only redone when it changes.
+    // 0 before the page is laid out.
+    pub fn layout_id(&self) -> u64 {
+        self.laid_out.as_ref().map_or(0, |laid_out| laid_out.id)
+    }
+
+    // Whether images were still loading when the last layout was made
+    pub fn loading_images(&self) -> bool {
+        self.laid_out
+            .as_ref()
+            .is_some_and(|laid_out| laid_out.pending_images)
+    }
+
+    // The deepest fragment under a point of the last layout, see `Fragment::hit_test`
+    pub fn hit_test(
+        &self,
+        point: Pos2,
+        viewport: Rect,
+        offsets: &HashMap<Vec<usize>, Vec2>,
+    ) -> Option<&layout::Fragment> {
+        self.root()?.hit_test(point, viewport, offsets)
+    }
+
+    // The position in the text nearest to a point, see `Fragment::hit_test`. A point beside
+    // the text of the box it's in goes to the nearest of that text.
+    pub fn position_at(
+        &self,
+        point: Pos2,
+        viewport: Rect,
+        offsets: &HashMap<Vec<usize>, Vec2>,
+        measurer: &dyn layout::TextMeasurer,
+    ) -> Option<dom::TextPosition> {
+        let root = self.root()?;
+        let (hit, point) = root
+            .hit_test_local(point, viewport, offsets)
+            .unwrap_or((root, point));
+        let mut texts = Vec::new();
+        self.collect_texts(hit, &mut texts);
+        if texts.is_empty() {
+            self.collect_texts(root, &mut texts);
+        }
+        // Text on the line at the point's height comes first, then the closest
+        let distance = |rect: Rect| {
+            let outside = |range: eframe::egui::Rangef, value: f32| {
+                (range.min - value).max(value - range.max).max(0.0)
+            };
+            (
+                outside(rect.y_range(), point.y),
+                outside(rect.x_range(), point.x),
+            )
+        };
+        let (fragment, run) = texts.into_iter().min_by(|(a, _), (b, _)| {
+            let (a, b) = (distance(a.rect), distance(b.rect));
+            a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1))
+        })?;
+        let index = run.index_at(point.x - fragment.rect.left(), measurer);
+        Some(dom::TextPosition {
+            node: fragment.node.clone()?,
+            offset: run.source_offset(index),
+        })
+    }
+
+    // The text fragments of DOM text nodes in a fragment tree, leaving out generated content
+    fn collect_texts<'a>(
+        &self,
+        fragment: &'a layout::Fragment,
+        texts: &mut Vec<(&'a layout::Fragment, &'a layout::TextRun)>,
+    ) {
+        if let layout::FragmentKind::Text(run) = &fragment.kind {
+            let node = fragment.node.as_deref();
+            if node.is_some_and(|node| self.document.text_at(node).is_some()) {
+                texts.push((fragment, run));
+            }
+        }
+        for child in &fragment.children {
+            self.collect_texts(child, texts);
+        }
+    }
+
+    // All the text of the block a position is in, as selected by a triple click
+    pub fn block_selection(&self, position: &dom::TextPosition) -> Option<dom::Selection> {
+        let block = (1..position.node.len()).rev().find(|&len| {
+            self.styles
+                .style_at(&position.node[..len])
+                .is_some_and(|style| !style.display().starts_with("inline"))
+        })?;
+        dom::Selection::contents(&self.document, &position.node[..block])
+    }
+
+    pub fn select_all(&mut self) {
+        self.selection = dom::Selection::contents(&self.document, &[]);
+    }
+
+    // The selected text, as copied to the clipboard
+    pub fn selected_text(&self) -> Option<String> {
+        let selection = self.selection.as_ref()?;
+        let text = selection.text(&self.document, &|path| self.display_at(path));
+        (!text.is_empty()).then_some(text)
+    }
+
+    // The selected text as HTML, with the elements around it
+    pub fn selected_html(&self) -> Option<String> {
+        let selection = self.selection.as_ref()?;
+        let html = selection.html(&self.document, &|path| self.display_at(path));
+        (!html.is_empty()).then_some(html)
+    }
+
+    fn display_at(&self, path: &[usize]) -> Option<&str> {
+        self.styles.style_at(path).map(|style| style.display())
+    }
+
+    // The URL of the hyperlink the node at `path` is in, resolved against the base URL
+    pub fn link_at(&self, path: &[usize]) -> Option<Url> {
+        let href = (1..=path.len())
+            .rev()
+            .filter_map(|len| self.document.element_at(&path[..len]))
+            .find(|element| element.is_link())
+            .and_then(|element| element.attributes.get("href"))?;
+        self.base_url()?.join(href.trim()).ok()
+    }
+
+    // The URL relative links resolve against: the first `<base href>` in the head, or
+    // the document's own
+    pub fn base_url(&self) -> Option<Url> {
+        let url = self.url.as_ref()?;
+        let base = self
+            .document
+            .html_element()
+            .into_iter()
+            .flat_map(|html| &html.children)
+            .filter_map(|node| match node {
+                dom::Node::Element(head) if head.element_type == dom::ElementType::Head => {
+                    Some(&head.children)
+                }
+                _ => None,
+            })
+            .flatten()
+            .find_map(|node| match node {
+                dom::Node::Element(base) if base.element_type.tag_name() == "base" => {
+                    base.attributes.get("href")
+                }
+                _ => None,
+            });
+        match base.and_then(|href| url.join(href.trim()).ok()) {
+            Some(base) => Some(base),
+            None => Some(url.clone()),
+        }
+    }
+
+    // Picks and loads the source of every image in the box tree, filling in those that have
+    // arrived. Returns true if any are still loading.
+    fn load_images(&self, layout_box: &mut layout::LayoutBox) -> bool {
+        let mut pending = false;
+        if let layout::BoxKind::Replaced(image) = &mut layout_box.kind {
+            let element = layout_box
+                .node
+                .as_deref()
+                .and_then(|path| self.document.element_at(path));
+            let attribute = |name: &str| {
+                element
+                    .and_then(|element| element.attributes.get(name))
+                    .map(String::as_str)
+            };
+            let source = images::select_source(
+                image.src.as_deref(),
+                attribute("srcset"),
+                attribute("sizes"),
+                self.device(),
+            );
+            let url = source.and_then(|(src, density)| {
+                let url = match &self.url {
+                    Some(base) => base.join(&src),
+                    None => Url::parse(&src),
+                };
+                Some((url.ok()?, density))
+            });
+            image.content = match url.map(|(url, density)| (self.images.load(&url), density)) {
+                Some((images::ImageStatus::Loading, _)) => {
+                    pending = true;
+                    layout::ImageContent::Pending
+                }
+                Some((images::ImageStatus::Loaded(image), density)) => {
+                    layout::ImageContent::Loaded { image, density }
+                }
+                Some((images::ImageStatus::Failed(_), _)) | None => layout::ImageContent::Failed,
+            };
+            // A broken image with nothing to size it shows its alt text instead
+            let sized = image.width.is_some()
+                || image.height.is_some()
+                || layout_box.style.keyword("width") != Some("auto")
+                || layout_box.style.keyword("height") != Some("auto");
+            if image.content == layout::ImageContent::Failed && !image.alt.is_empty() && !sized {
+                layout_box.fall_back_to_alt_text();
+            }
+        }
+        for child in &mut layout_box.children {
+            pending |= self.load_images(child);
+        }
+        pending
+    }
+
+    // Sets or removes an attribute of the element at `path`
+    pub fn set_attribute(&mut self, path: &[usize], name: &str, value: Option<&str>) {
+        let Some(element) = self.document.element_at_mut(path) else {
+            return;
+        };
+        let old = match value {
+            Some(value) => element
+                .attributes
+                .insert(name.to_string(), value.to_string()),
+            None => element.attributes.remove(name),
+        };
+        let damage = self.stylist.attribute_damage(name, old.as_deref(), value);
+        self.invalidate(path, damage);
+    }
+
+    pub fn set_element_state(&mut self, path: &[usize], state: dom::ElementState) {
+        let Some(element) = self.document.element_at_mut(path) else {
+            return;
+        };
+        let old = std::mem::replace(&mut element.state, state);
+        let damage = self.stylist.state_damage(element, old, state);
+        self.invalidate(path, damage);
+    }
+
+    // Moves the hover to the element at `path`, which also hovers its ancestors
+    pub fn set_hovered(&mut self, path: Option<&[usize]>) {
+        let old = std::mem::replace(&mut self.hovered, path.map(<[usize]>::to_vec));
+        let affected: Vec<&[usize]> = [old.as_deref(), path]
+            .into_iter()
+            .flatten()
+            .flat_map(|path| (1..=path.len()).map(move |len| &path[..len]))
+            .collect();
+        for ancestor in affected {
+            let Some(element) = self.document.element_at(ancestor) else {
+                continue;
+            };
+            let hover = path.is_some_and(|path| path.starts_with(ancestor));
+            if element.state.hover != hover {
+                let state = dom::ElementState {
+                    hover,
+                    ..element.state
+                };
+                self.set_element_state(ancestor, state);
+            }
+        }
+    }
+
+    pub fn set_focused(&mut self, path: Option<&[usize]>) {
+        let old = std::mem::replace(&mut self.focused, path.map(<[usize]>::to_vec));
+        for (path, focus) in [(old.as_deref(), false), (path, true)] {
+            let Some(path) = path else {
+                continue;
+            };
+            if let Some(element) = self.document.element_at(path) {
+                let state = dom::ElementState {
+                    focus,
+                    ..element.state
+                };
+                self.set_element_state(path, state);
+            }
+        }
+    }
+
+    // Adds an author stylesheet, marking only the elements it matches as dirty
+    pub fn add_stylesheet(&mut self, sheet: css::Stylesheet) {
+        let matched = self
+            .stylist
+            .insert_stylesheet(sheet, css::Origin::Author, &self.document);
+        self.dirty.extend(matched);
+    }
+
+    fn invalidate(&mut self, path: &[usize], damage: css::Damage) {
+        match damage {
+            css::Damage::None => {}
+            css::Damage::Subtree => self.dirty.push(path.to_vec()),
+            // Restyling the parent's subtree covers every later sibling
+            css::Damage::LaterSiblings => self.dirty.push(path[..path.len() - 1].to_vec()),
+        }
+    }
+
+    pub fn needs_restyle(&self) -> bool {
+        !self.dirty.is_empty()
+    }
+
+    // Restyles the dirty subtrees. Returns the number of subtrees restyled.
+    pub fn restyle_dirty(&mut self) -> usize {
+        let mut dirty = std::mem::take(&mut self.dirty);
+        dirty.sort();
+        dirty.dedup();
+        // Drop subtrees that are inside another dirty subtree
+        let mut roots: Vec<Vec<usize>> = Vec::new();
+        for path in dirty {
+            if !roots.last().is_some_and(|root| path.starts_with(root)) {
+                roots.push(path);
+            }
+        }
+        for root in &roots {
+            self.stylist
+                .restyle_subtree(&self.document, &mut self.styles, root);
+        }
+        if !roots.is_empty() {
+            self.laid_out = None;
+        }
+        roots.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restyle_on_device_change() {
+        let mut page = page(
+            "<html><head><style>@media (prefers-color-scheme: dark) { html { color: white } }\
+             </style></head></html>",
+        );
+        let color = |page: &Page| page.styles.nodes[0].style.color("color");
+        assert_eq!(color(&page), css::Color::BLACK);
+
+        let dark = css::Device {
+            color_scheme: css::ColorScheme::Dark,
+            ..Default::default()
+        };
+        assert!(page.set_device(dark.clone()));
+        assert_eq!(color(&page), css::Color::WHITE);
+        assert_eq!(page.device(), &dark);
+        assert!(!page.set_device(dark));
+    }
+
+    fn page(html: &str) -> Page {
+        Page::new(
+            html,
+            &Url::parse("about:blank").unwrap(),
+            css::Device::default(),
+        )
+    }
+
+    fn color_at(page: &Page, path: &[usize]) -> css::Color {
+        let mut node = &page.styles.nodes[path[0]];
+        for index in &path[1..] {
+            node = &node.children[*index];
+        }
+        node.style.color("color")
+    }
+
+    #[test]
+    fn test_incremental_restyle_matches_full_restyle() {
+        let mut page = page(
+            "<html><head><style>.on { color: red } .on + p { color: blue }\
+             div:hover p { color: green } a:focus { color: white }</style></head>\
+             <body><p>1</p><p>2</p><div><p>3</p><a href=/>4</a></div></body></html>",
+        );
+        page.set_attribute(&[0, 1, 0], "class", Some("on"));
+        page.set_hovered(Some(&[0, 1, 2, 0]));
+        page.set_focused(Some(&[0, 1, 2, 1]));
+        page.set_attribute(&[0, 1, 2], "title", Some("ignored"));
+        assert!(page.needs_restyle());
+        page.restyle_dirty();
+        assert!(!page.needs_restyle());
+        assert_eq!(color_at(&page, &[0, 1, 0]), css::Color::rgb(255, 0, 0));
+        assert_eq!(color_at(&page, &[0, 1, 1]), css::Color::rgb(0, 0, 255));
+        assert_eq!(color_at(&page, &[0, 1, 2, 0]), css::Color::rgb(0, 128, 0));
+        assert_eq!(color_at(&page, &[0, 1, 2, 1]), css::Color::WHITE);
+
+        let incremental = page.styles.clone();
+        page.restyle();
+        assert_eq!(incremental, page.styles);
+
+        page.set_hovered(None);
+        page.set_focused(None);
+        page.restyle_dirty();
+        assert_eq!(color_at(&page, &[0, 1, 2, 0]), css::Color::BLACK);
+        assert_eq!(color_at(&page, &[0, 1, 2, 1]), css::Color::rgb(0, 0, 238));
+    }
+
+    #[test]
+    fn test_unrelated_changes_are_not_restyled() {
+        let mut page =
+            page("<html><head><style>.a { color: red }</style></head><body><p></p></body></html>");
+        page.set_attribute(&[0, 1, 0], "class", Some("b"));
+        page.set_hovered(Some(&[0, 1, 0]));
+        assert!(!page.needs_restyle());
+        assert_eq!(page.restyle_dirty(), 0);
+    }
+
+    #[test]
+    fn test_layout_is_cached_until_styles_change() {
+        let mut page = page("<html><body><p>text</p></body></html>");
+        let metrics = layout::FixedMetrics;
+        let root = page.layout(400.0, &metrics).unwrap().clone();
+        assert_eq!(root.rect.width(), 400.0);
+        assert_eq!(page.layout(400.0, &metrics), Some(&root));
+        assert_eq!(page.layout(200.0, &metrics).unwrap().rect.width(), 200.0);
+
+        page.add_stylesheet(css::parse_stylesheet("p { height: 100px }"));
+        page.restyle_dirty();
+        let body = &page.layout(200.0, &metrics).unwrap().children[0];
+        assert_eq!(body.children[0].rect.height(), 100.0);
+    }
+
+    #[test]
+    fn test_inserted_stylesheet_restyles_matched_elements() {
+        let mut page = page("<html><body><p></p><div><span></span></div></body></html>");
+        page.add_stylesheet(css::parse_stylesheet(
+            "div { color: red } em { color: blue }",
+        ));
+        assert_eq!(page.restyle_dirty(), 1);
+        assert_eq!(color_at(&page, &[0, 1, 1, 0]), css::Color::rgb(255, 0, 0));
+        assert_eq!(color_at(&page, &[0, 1, 0]), css::Color::BLACK);
+    }
+
+    #[test]
+    fn test_stylesheet_errors_do_not_fail_the_page() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/style.css")
+            .with_body("html { color: red }")
+            .create();
+        server.mock("GET", "/missing.css").with_status(404).create();
+        let url = Url::parse(&server.url()).unwrap();
+        let page = Page::new(
+            "<html><head><link rel=stylesheet href=missing.css>\
+             <link rel=stylesheet href=style.css></head></html>",
+            &url,
+            css::Device::default(),
+        );
+        assert_eq!(
+            page.styles.nodes[0].style.color("color"),
+            css::Color::rgb(255, 0, 0)
+        );
+        assert_eq!(page.resource_errors.len(), 1);
+        assert!(page.resource_errors[0].url.ends_with("/missing.css"));
+    }
+
+    #[test]
+    fn test_links_resolve_against_the_base_url() {
+        let url = Url::parse("https://example.com/docs/page.html").unwrap();
+        let body = "<body><a href='next.html'><span>Next</span></a><p>Text</p></body>";
+        let page = Page::new(
+            &format!("<html>{}</html>", body),
+            &url,
+            css::Device::default(),
+        );
+        let next = Url::parse("https://example.com/docs/next.html").unwrap();
+        assert_eq!(page.link_at(&[0, 1, 0, 0, 0]), Some(next.clone()));
+        assert_eq!(page.link_at(&[0, 1, 0]), Some(next));
+        assert_eq!(page.link_at(&[0, 1, 1, 0]), None);
+
+        let mut page = Page::new(
+            &format!("<html><head><base href='/other/'></head>{}</html>", body),
+            &url,
+            css::Device::default(),
+        );
+        assert_eq!(
+            page.link_at(&[0, 1, 0]),
+            Some(Url::parse("https://example.com/other/next.html").unwrap())
+        );
+
+        // The link is found from a point on its text
+        page.layout(400.0, &layout::FixedMetrics);
+        let viewport = Rect::from_min_size(Pos2::ZERO, eframe::egui::vec2(400.0, 300.0));
+        let hit = page.hit_test(eframe::egui::pos2(10.0, 10.0), viewport, &HashMap::new());
+        assert_eq!(
+            hit.and_then(|fragment| fragment.node.clone()),
+            Some(vec![0, 1, 0, 0, 0])
+        );
+    }
+
+    #[test]
+    fn test_select_and_copy_text() {
+        let mut page = page(
+            "<html><body style='margin: 0'><p style='margin: 0'>Some <b>bold</b> text</p>\
+             <p style='margin: 0'>Next   line</p></body></html>",
+        );
+        let metrics = layout::FixedMetrics;
+        page.layout(400.0, &metrics);
+        let viewport = Rect::from_min_size(Pos2::ZERO, eframe::egui::vec2(400.0, 300.0));
+        let position = |x: f32, y: f32| {
+            let point = eframe::egui::pos2(x, y);
+            page.position_at(point, viewport, &HashMap::new(), &metrics)
+                .unwrap()
+        };
+
+        // Characters are 8px wide, so points go to the nearest gap between them
+        let start = position(19.0, 10.0);
+        assert_eq!(
+            start,
+            dom::TextPosition {
+                node: vec![0, 1, 0, 0],
+                offset: 2
+            }
+        );
+        // Past the end of a line is the end of its text, and the collapsed spaces of the
+        // second line still map to their place in the DOM
+        assert_eq!(position(300.0, 10.0).offset, 5);
+        let end = position(47.0, 30.0);
+        assert_eq!(
+            end,
+            dom::TextPosition {
+                node: vec![0, 1, 1, 0],
+                offset: 8
+            }
+        );
+        let bold = position(44.0, 10.0);
+
+        page.selection = Some(dom::Selection::new(end, start));
+        assert_eq!(
+            page.selected_text().as_deref(),
+            Some("me bold text\nNext l")
+        );
+        assert_eq!(
+            page.selected_html().as_deref(),
+            Some(
+                "<p style=\"margin: 0\">me <b>bold</b> text</p><p style=\"margin: 0\">Next   l</p>"
+            )
+        );
+
+        let word = dom::Selection::word(&page.document, &bold).unwrap();
+        page.selection = Some(word);
+        assert_eq!(page.selected_text().as_deref(), Some("bold"));
+        page.selection = page.block_selection(&bold);
+        assert_eq!(page.selected_text().as_deref(), Some("Some bold text"));
+        page.select_all();
+        assert_eq!(
+            page.selected_text().as_deref(),
+            Some("Some bold text\nNext line")
+        );
+    }
+
+    #[test]
+    fn test_images_are_laid_out_once_loaded() {
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::new(60, 30)
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/image.png")
+            .with_body(png.into_inner())
+            .create();
+        server.mock("GET", "/missing.png").with_status(404).create();
+        let url = Url::parse(&server.url()).unwrap();
+        let mut page = Page::new(
+            "<html><body style='margin: 0'><img src=image.png><div><img src=missing.png \
+             alt=Missing></div></body></html>",
+            &url,
+            css::Device::default(),
+        );
+        let metrics = layout::FixedMetrics;
+        // The text the missing image's alt fallback lays out, once it has
+        let alt_text = |body: &layout::Fragment| {
+            let line = &body.children[1].children[0];
+            match &line.children[0].children.first()?.kind {
+                layout::FragmentKind::Text(run) => Some(run.text.clone()),
+                _ => None,
+            }
+        };
+        // Layouts are redone as the images arrive
+        let start = std::time::Instant::now();
+        let body = loop {
+            let body = page.layout(400.0, &metrics).unwrap().children[0].clone();
+            let image = &body.children[0].children[0].children[0];
+            let loaded = image.rect.width() > 0.0 && alt_text(&body).is_some();
+            if loaded || start.elapsed() > std::time::Duration::from_secs(10) {
+                break body;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        };
+        let image = &body.children[0].children[0].children[0];
+        assert_eq!(image.rect.size(), eframe::egui::vec2(60.0, 30.0));
+        assert_eq!(alt_text(&body).as_deref(), Some("Missing"));
+    }
+
+    #[test]
+    fn test_page_loads_web_fonts() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/fonts/open-sans.woff2")
+            .with_body(include_bytes!(
+                "../../tests/fixtures/fonts/open-sans-regular.woff2"
+            ))
+            .create();
+        server
+            .mock("GET", "/style.css")
+            .with_body(
+                "@font-face { font-family: 'Open Sans'; src: url(fonts/open-sans.woff2) }\
+                 p { font-family: 'Open Sans', sans-serif }",
+            )
+            .create();
+        let url = Url::parse(&server.url()).unwrap();
+        let page = Page::new(
+            "<html><head><link rel=stylesheet href=style.css></head><body><p>Hi</p></body></html>",
+            &url,
+            css::Device::default(),
+        );
+        assert!(page.resource_errors.is_empty());
+        assert_eq!(page.fonts.faces().len(), 1);
+        let face = &page.fonts.faces()[0];
+        assert!(face.url.path().ends_with("/fonts/open-sans.woff2"));
+        assert_eq!(
+            page.fonts.resolve(&["Open Sans".to_string()], 400, false),
+            eframe::egui::FontFamily::Name(face.key().into())
+        );
+    }
+}
@@ -0,0 +1,203 @@
+// Printing pages: laying them out for paper and writing the pages as a PDF document
+
+use crate::css;
+use crate::fonts::FontRegistry;
+use crate::layout;
+use crate::rendering;
+use crate::state::page::Page;
+use crate::state::tab::Tab;
+use eframe::egui::epaint;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How long to wait for a page's images before printing it without them
+const IMAGE_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl Page {
+    // Lays the page out for print media on the pages its `@page` rules ask for and writes
+    // it as a PDF document. The page is styled for its previous device again afterwards.
+    pub fn print_to_pdf(&mut self) -> Result<Vec<u8>, String> {
+        let screen = self.device().clone();
+        let print = css::Device {
+            media_type: css::MediaType::Print,
+            ..screen.clone()
+        };
+        // `@page` rules may be inside print media rules, and the page's size decides the
+        // viewport the rest of the styles see
+        self.set_device(print.clone());
+        let page_style = self.stylist.page_style();
+        self.set_device(css::Device {
+            viewport_width: page_style.content_width(),
+            viewport_height: page_style.content_height(),
+            ..print
+        });
+
+        let mut registry = FontRegistry::default();
+        for face in self.fonts.faces() {
+            registry.add(face);
+        }
+        let definitions = registry.font_definitions();
+        let fonts = epaint::text::Fonts::new(1.0, 2048, definitions.clone());
+        fonts.begin_pass(1.0, 2048);
+        let start = Instant::now();
+        let pdf = loop {
+            self.layout(page_style.content_width(), &fonts);
+            if self.loading_images() && start.elapsed() < IMAGE_TIMEOUT {
+                thread::sleep(Duration::from_millis(20));
+                continue;
+            }
+            let Some(root) = self.root() else {
+                break Err("The page has nothing to print".to_string());
+            };
+            let list = rendering::build_display_list(root);
+            let starts = layout::paginate(root, page_style.content_height());
+            let title = self.document.title().unwrap_or_default();
+            break Ok(rendering::write_pdf(
+                &list,
+                &starts,
+                &page_style,
+                &definitions,
+                &title,
+            ));
+        };
+        self.set_device(screen);
+        pdf
+    }
+}
+
+impl Tab {
+    // Prints the tab's page to a new PDF file in `dir`, named after the page's title, and
+    // returns its path
+    pub fn save_pdf(&mut self, dir: &Path) -> Result<PathBuf, String> {
+        let pdf = self.page.print_to_pdf()?;
+        let title = self.page.document.title().unwrap_or_default();
+        let name: String = title
+            .chars()
+            .map(|c| match c {
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+                c if c.is_control() => '_',
+                c => c,
+            })
+            .take(100)
+            .collect();
+        let name = match name.trim() {
+            "" => self.url.host_str().unwrap_or("page").to_string(),
+            name => name.to_string(),
+        };
+        // Existing files are never replaced: the file is only created if there's none at
+        // the path, or the next copy's name is tried
+        let mut copy = 1;
+        loop {
+            let path = match copy {
+                1 => dir.join(format!("{}.pdf", name)),
+                copy => dir.join(format!("{} ({}).pdf", name, copy)),
+            };
+            let file = OpenOptions::new().write(true).create_new(true).open(&path);
+            match file {
+                Ok(mut file) => {
+                    file.write_all(&pdf)
+                        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+                    return Ok(path);
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => copy += 1,
+                Err(e) => return Err(format!("Failed to write {}: {}", path.display(), e)),
+            }
+        }
+    }
+}
+
+// The folder saved pages go in: the user's downloads folder as set in the XDG user
+// directories, else `~/Downloads`, or the working directory when neither exists
+pub fn download_dir() -> PathBuf {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| home.as_ref().map(|home| home.join(".config")));
+    let user_dirs = config
+        .and_then(|config| fs::read_to_string(config.join("user-dirs.dirs")).ok())
+        .unwrap_or_default();
+    home.and_then(|home| {
+        user_download_dir(&user_dirs, &home).or_else(|| Some(home.join("Downloads")))
+    })
+    .filter(|path| path.is_dir())
+    .unwrap_or_else(|| PathBuf::from("."))
+}
+
+// The `XDG_DOWNLOAD_DIR` line of a `user-dirs.dirs` file, which is an absolute path or one
+// relative to `$HOME`
+fn user_download_dir(user_dirs: &str, home: &Path) -> Option<PathBuf> {
+    let value = user_dirs
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("XDG_DOWNLOAD_DIR="))?;
+    let value = value.trim().trim_matches('"');
+    match value.strip_prefix("$HOME") {
+        Some(rest) => Some(home.join(rest.trim_start_matches('/'))),
+        None if value.starts_with('/') => Some(PathBuf::from(value)),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use url::Url;
+
+    #[test]
+    fn test_print_to_pdf() {
+        let url = Url::parse("http://localhost/").unwrap();
+        let mut page = Page::new(
+            "<html><head><title>Printed</title><style>\
+             @media print { @page { size: 400px 300px; margin: 0 } .screen { display: none } }\
+             body { margin: 0 } div { height: 200px }\
+             </style></head><body><div>First</div><div class='screen'>Screen only</div>\
+             <div style='break-before: page'>Second</div><div>Third</div></body></html>",
+            &url,
+            css::Device::default(),
+        );
+        let pdf = page.print_to_pdf().unwrap();
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.contains("/MediaBox [0 0 300 225]"));
+        // Each forced break starts a page, and the third box doesn't fit under the second
+        assert!(text.contains("/Count 3"));
+        // The title is UTF-16
+        assert!(text.contains("/Title <FEFF005000720069006E007400650064>"));
+        // The page is back on screen
+        assert_eq!(page.device().media_type, css::MediaType::Screen);
+    }
+
+    #[test]
+    fn test_pdfs_never_replace_files() {
+        let dir = std::env::temp_dir().join(format!("oliver-explorer-pdf-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut tab = Tab::new(0, Default::default());
+        tab.page = Page::new(
+            "<title>Notes: draft</title><p>Text</p>",
+            &tab.url.clone(),
+            css::Device::default(),
+        );
+        let first = tab.save_pdf(&dir).unwrap();
+        assert_eq!(first, dir.join("Notes_ draft.pdf"));
+        let second = tab.save_pdf(&dir).unwrap();
+        assert_eq!(second, dir.join("Notes_ draft (2).pdf"));
+        assert!(fs::read(&first).unwrap().starts_with(b"%PDF"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let home = Path::new("/home/ada");
+        let user_dirs = "# written by xdg-user-dirs-update\n\
+                         XDG_DESKTOP_DIR=\"$HOME/Desktop\"\n\
+                         XDG_DOWNLOAD_DIR=\"$HOME/Téléchargements\"\n";
+        assert_eq!(
+            user_download_dir(user_dirs, home),
+            Some(PathBuf::from("/home/ada/Téléchargements"))
+        );
+        assert_eq!(
+            user_download_dir("XDG_DOWNLOAD_DIR=\"/data/downloads\"", home),
+            Some(PathBuf::from("/data/downloads"))
+        );
+        assert_eq!(user_download_dir("", home), None);
+    }
+}
@@ -0,0 +1,149 @@
+use crate::dom;
+use crate::state::page::Page;
+use std::fmt::Write;
+
+// The sizes the reader's text can be set to, in CSS pixels
+pub const READER_FONT_SIZES: [f32; 7] = [14.0, 16.0, 18.0, 20.0, 22.0, 26.0, 30.0];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReaderPalette {
+    Light,
+    Dark,
+}
+
+/// Reader mode of a tab: whether it's showing the page's article on its own, and how
+#[derive(Debug)]
+pub struct ReaderMode {
+    pub font_size: f32,
+    pub palette: ReaderPalette,
+    // The page the article was taken from while the reader shows it
+    pub(in crate::state) original: Option<Page>,
+}
+
+impl Default for ReaderMode {
+    fn default() -> Self {
+        Self {
+            font_size: 18.0,
+            palette: ReaderPalette::Light,
+            original: None,
+        }
+    }
+}
+
+impl ReaderMode {
+    pub fn is_active(&self) -> bool {
+        self.original.is_some()
+    }
+
+    // The next size up from the current one, or down with `larger` false
+    pub fn step_font_size(&mut self, larger: bool) {
+        let size = self.font_size;
+        let next = match larger {
+            true => READER_FONT_SIZES.into_iter().find(|s| *s > size),
+            false => READER_FONT_SIZES.into_iter().rev().find(|s| *s < size),
+        };
+        self.font_size = next.unwrap_or(size);
+    }
+
+    // The document the reader shows for an article, styled by its own stylesheet alone
+    pub fn html(&self, article: &dom::Article) -> String {
+        let (background, text, muted, link) = match self.palette {
+            ReaderPalette::Light => ("#fbfaf7", "#1d1d1f", "#6e6e73", "#0b57d0"),
+            ReaderPalette::Dark => ("#1c1c1e", "#e6e6e6", "#98989d", "#8ab4f8"),
+        };
+        let mut html = String::from("<!DOCTYPE html><html><head><title>");
+        html.push_str(&escape(&article.title));
+        html.push_str("</title><style>");
+        let _ = write!(
+            html,
+            "html {{ background: {background}; color: {text} }}\
+             body {{ max-width: 680px; margin: 0 auto; padding: 32px 24px; \
+             font-family: serif; font-size: {size}px; line-height: 1.6 }}\
+             h1 {{ font-family: sans-serif; font-size: 1.8em; line-height: 1.25; \
+             margin: 0 0 8px }}\
+             h2, h3, h4 {{ font-family: sans-serif; line-height: 1.3 }}\
+             .byline {{ color: {muted}; font-family: sans-serif; font-size: 0.85em; \
+             margin: 0 0 24px }}\
+             a {{ color: {link} }}\
+             img {{ max-width: 100%; height: auto }}\
+             pre, code {{ font-family: monospace; font-size: 0.85em }}\
+             pre {{ white-space: pre-wrap }}\
+             blockquote {{ color: {muted}; margin: 1em 0; padding-left: 1em; \
+             border-left: 3px solid {muted} }}",
+            size = self.font_size,
+        );
+        html.push_str("</style></head><body><h1>");
+        html.push_str(&escape(&article.title));
+        html.push_str("</h1>");
+        if let Some(byline) = &article.byline {
+            let _ = write!(html, "<p class=\"byline\">{}</p>", escape(byline));
+        }
+        html.push_str(&article.html());
+        html.push_str("</body></html>");
+        html
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::settings::AppSettings;
+    use crate::state::tab::Tab;
+    use std::sync::Arc;
+    use url::Url;
+
+    #[test]
+    fn test_toggle_reader() {
+        let mut tab = Tab::new(0, Arc::new(AppSettings::default()));
+        tab.url = Url::parse("http://localhost/story").unwrap();
+        tab.page = Page::new(
+            "<html><head><title>A long story about things</title></head><body>\
+             <ul class='menu'><li><a href='/'>Home</a></li></ul>\
+             <article><p>The first paragraph of the story, which goes on for a while.</p>\
+             <p>The second paragraph, with <a href='/more'>a link</a>, is shorter.</p>\
+             </article></body></html>",
+            &tab.url,
+            Default::default(),
+        );
+        tab.toggle_reader().unwrap();
+        assert!(tab.reader.is_active());
+        let text = |tab: &Tab| {
+            let body = tab.page.document.html_element().unwrap();
+            body.text_content()
+        };
+        assert!(text(&tab).contains("The first paragraph"));
+        assert!(!text(&tab).contains("Home"));
+        // The body's text is the reader's size
+        let font_size = |tab: &Tab| {
+            let document = &tab.page.document;
+            let html = document
+                .nodes
+                .iter()
+                .position(|node| matches!(node, dom::Node::Element(_)))
+                .unwrap();
+            let style = tab.page.styles.style_at(&[html, 1]).unwrap();
+            style.font_size()
+        };
+        assert_eq!(font_size(&tab), 18.0);
+
+        // Changing the text size lays the article out again
+        tab.reader.step_font_size(true);
+        tab.restyle_reader();
+        assert_eq!(tab.reader.font_size, 20.0);
+        assert_eq!(font_size(&tab), 20.0);
+
+        tab.toggle_reader().unwrap();
+        assert!(!tab.reader.is_active());
+        assert!(text(&tab).contains("Home"));
+
+        tab.page = Page::new("<p>Short</p>", &tab.url, Default::default());
+        assert!(tab.toggle_reader().is_err());
+        assert!(!tab.reader.is_active());
+    }
+}
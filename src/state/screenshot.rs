@@ -0,0 +1,100 @@
+// Rendering pages without a display: a frame of a headless egui context painted by the
+// CPU rasterizer
+
+use crate::css;
+use crate::fonts::FontRegistry;
+use crate::rendering;
+use crate::state::page::Page;
+use eframe::egui::{self, Color32, ColorImage, LayerId, Pos2, Rect, Vec2};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How long to wait for a page's images before rendering it without them
+const IMAGE_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl Page {
+    // Renders the top of the page in a viewport of `size` CSS pixels, once its images have
+    // loaded. Images whose textures were made by another egui context aren't drawn, so
+    // pages shown on screen need a fresh image cache first.
+    pub fn screenshot(&mut self, size: Vec2) -> Result<ColorImage, String> {
+        if size.x < 1.0 || size.y < 1.0 {
+            return Err(format!("Invalid viewport size: {}x{}", size.x, size.y));
+        }
+        let device = css::Device {
+            viewport_width: size.x,
+            viewport_height: size.y,
+            ..self.device().clone()
+        };
+        self.set_device(device);
+        let ctx = egui::Context::default();
+        let mut registry = FontRegistry::default();
+        for face in self.fonts.faces() {
+            registry.add(face);
+        }
+        ctx.set_fonts(registry.font_definitions());
+
+        let screen = Rect::from_min_size(Pos2::ZERO, size);
+        let mut rasterizer = rendering::Rasterizer::default();
+        let start = Instant::now();
+        // The web fonts are only usable from the second frame
+        for frame in 0.. {
+            let input = egui::RawInput {
+                screen_rect: Some(screen),
+                ..Default::default()
+            };
+            let output = ctx.run(input, |ctx| {
+                let list = ctx
+                    .fonts(|fonts| Some(rendering::build_display_list(self.layout(size.x, fonts)?)))
+                    .unwrap_or_default();
+                let painter = ctx.layer_painter(LayerId::background());
+                let canvas = list.background.unwrap_or(self.canvas_color());
+                painter.rect_filled(screen, 0.0, canvas);
+                let scroll = rendering::ScrollOffsets::default();
+                rendering::paint(&list, &painter, Vec2::ZERO, &scroll, 1.0);
+            });
+            rasterizer.set_textures(&output.textures_delta);
+            let waiting = self.loading_images() && start.elapsed() < IMAGE_TIMEOUT;
+            if frame > 0 && !waiting {
+                let primitives = ctx.tessellate(output.shapes, output.pixels_per_point);
+                let pixels = [size.x.round() as usize, size.y.round() as usize];
+                let mut image = ColorImage::new(pixels, Color32::TRANSPARENT);
+                rasterizer.draw(&primitives, &mut image, output.pixels_per_point);
+                return Ok(image);
+            }
+            rasterizer.free_textures(&output.textures_delta);
+            if waiting {
+                thread::sleep(Duration::from_millis(20));
+            }
+        }
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use url::Url;
+
+    #[test]
+    fn test_screenshot() {
+        let url = Url::parse("http://localhost/").unwrap();
+        let mut page = Page::new(
+            "<body style='margin: 0; background: rgb(0, 0, 255)'>\
+             <div style='width: 40px; height: 20px; background: rgb(255, 0, 0); \
+             border: 5px solid rgb(0, 255, 0)'></div>\
+             <p style='margin: 0; color: white; font-size: 20px'>Headless</p></body>",
+            &url,
+            css::Device::default(),
+        );
+        let image = page.screenshot(egui::vec2(120.0, 80.0)).unwrap();
+        assert_eq!(image.size, [120, 80]);
+        let pixel = |x: usize, y: usize| image.pixels[y * 120 + x];
+
+        assert_eq!(pixel(2, 12), Color32::from_rgb(0, 255, 0));
+        assert_eq!(pixel(20, 15), Color32::from_rgb(255, 0, 0));
+        assert_eq!(pixel(100, 15), Color32::from_rgb(0, 0, 255));
+        // The text is drawn in white below the box
+        assert!((0..80).any(|x| (30..55).any(|y| pixel(x, y).g() > 200)));
+        assert_eq!(pixel(110, 70), Color32::from_rgb(0, 0, 255));
+    }
+}
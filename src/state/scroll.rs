@@ -0,0 +1,220 @@
+use crate::rendering::{DisplayList, ScrollOffsets};
+use eframe::egui::{self, Pos2, Rect, Vec2};
+
+// How far the arrow keys scroll
+const LINE_STEP: f32 = 40.0;
+// The part of the viewport that stays in view when paging
+const PAGE_OVERLAP: f32 = 0.1;
+
+/// The scroll offsets of a tab's viewport and the scroll containers in its page, which
+/// the tab keeps while others are shown
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScrollState {
+    pub viewport: Vec2,
+    pub containers: ScrollOffsets,
+}
+
+impl ScrollState {
+    // The part of the document a viewport of the given size shows
+    pub fn visible(&self, size: Vec2) -> Rect {
+        Rect::from_min_size(self.viewport.to_pos2(), size)
+    }
+
+    // Keeps the viewport within the document, which may have shrunk since it scrolled
+    pub fn clamp(&mut self, list: &DisplayList, size: Vec2) {
+        self.viewport = self.viewport.clamp(Vec2::ZERO, max_offset(list, size));
+    }
+
+    // Scrolls by `delta`, starting with the innermost scroll container under `point`, in
+    // document coordinates. Whatever a container can't take goes to the ones around it,
+    // and finally the viewport.
+    pub fn scroll_by(&mut self, list: &DisplayList, point: Option<Pos2>, size: Vec2, delta: Vec2) {
+        let visible = self.visible(size);
+        let chain = point
+            .map(|point| list.scroll_chain_at(point, visible, &self.containers))
+            .unwrap_or_default();
+        let mut remaining = delta;
+        for index in chain {
+            let container = &list.scroll_containers[index];
+            let Some(node) = &container.node else {
+                continue;
+            };
+            let current = container.offset(&self.containers);
+            let next = (current + remaining).clamp(Vec2::ZERO, container.max_offset);
+            remaining -= next - current;
+            self.containers.insert(node.clone(), next);
+        }
+        self.viewport = (self.viewport + remaining).clamp(Vec2::ZERO, max_offset(list, size));
+    }
+
+    // Scrolls the scroll containers around `rect`, in document coordinates, and then the
+    // viewport to bring it into view, centring it in each on the axes it's out of view on
+    pub fn reveal(&mut self, list: &DisplayList, rect: Rect, size: Vec2) {
+        let mut rect = rect;
+        for index in list.scroll_chain_around(rect) {
+            let container = &list.scroll_containers[index];
+            let Some(node) = &container.node else {
+                continue;
+            };
+            // The container shows its content from its offset, and moves it back by as much
+            let current = container.offset(&self.containers);
+            let next = centre(current, container.rect.translate(current), rect)
+                .clamp(Vec2::ZERO, container.max_offset);
+            self.containers.insert(node.clone(), next);
+            rect = rect.translate(-next);
+        }
+        self.viewport = centre(self.viewport, self.visible(size), rect)
+            .clamp(Vec2::ZERO, max_offset(list, size));
+    }
+
+    // How far a key scrolls a viewport of the given size, if it scrolls at all
+    pub fn key_delta(key: egui::Key, size: Vec2) -> Option<Vec2> {
+        let page = size.y * (1.0 - PAGE_OVERLAP);
+        let delta = match key {
+            egui::Key::ArrowDown => egui::vec2(0.0, LINE_STEP),
+            egui::Key::ArrowUp => egui::vec2(0.0, -LINE_STEP),
+            egui::Key::ArrowRight => egui::vec2(LINE_STEP, 0.0),
+            egui::Key::ArrowLeft => egui::vec2(-LINE_STEP, 0.0),
+            egui::Key::PageDown | egui::Key::Space => egui::vec2(0.0, page),
+            egui::Key::PageUp => egui::vec2(0.0, -page),
+            egui::Key::End => egui::vec2(0.0, f32::INFINITY),
+            egui::Key::Home => egui::vec2(0.0, f32::NEG_INFINITY),
+            _ => return None,
+        };
+        Some(delta)
+    }
+}
+
+// The offset that centres `rect` in `visible`, shown at `offset`, on each axis it isn't
+// entirely in view on
+fn centre(offset: Vec2, visible: Rect, rect: Rect) -> Vec2 {
+    let mut offset = offset;
+    if !visible.x_range().contains(rect.left()) || !visible.x_range().contains(rect.right()) {
+        offset.x += rect.center().x - visible.center().x;
+    }
+    if !visible.y_range().contains(rect.top()) || !visible.y_range().contains(rect.bottom()) {
+        offset.y += rect.center().y - visible.center().y;
+    }
+    offset
+}
+
+// How far the viewport can scroll over the document
+fn max_offset(list: &DisplayList, size: Vec2) -> Vec2 {
+    (list.size - size).max(Vec2::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css;
+    use crate::fonts::FontSet;
+    use crate::html;
+    use crate::layout::{self, FixedMetrics, LayoutContext};
+    use crate::rendering;
+
+    fn display_list(source: &str) -> DisplayList {
+        let document = html::parse_document(source);
+        let stylist = css::Stylist::for_document(&document, css::Device::default());
+        let styles = stylist.style_document(&document);
+        let root = layout::build_box_tree(&document, &styles).unwrap();
+        let fonts = FontSet::default();
+        let ctx = LayoutContext::new(&FixedMetrics, &fonts);
+        rendering::build_display_list(&layout::layout_document(&root, 400.0, &ctx))
+    }
+
+    #[test]
+    fn test_scrolling_is_routed_to_the_innermost_container() {
+        // A 100px tall scroller with 300px of content in a 1000px tall document
+        let list = display_list(
+            "<body style='margin: 0'>\
+             <div style='overflow: auto; height: 100px'>\
+               <div style='height: 300px; background: red'></div></div>\
+             <div style='height: 900px'></div></body>",
+        );
+        assert_eq!(list.scroll_containers.len(), 1);
+        assert_eq!(list.scroll_containers[0].max_offset, egui::vec2(0.0, 200.0));
+        let size = egui::vec2(400.0, 500.0);
+        let mut scroll = ScrollState::default();
+
+        // The container under the pointer scrolls until it reaches its end, then the
+        // viewport takes the rest
+        let inside = Some(egui::pos2(10.0, 50.0));
+        scroll.scroll_by(&list, inside, size, egui::vec2(0.0, 150.0));
+        assert_eq!(scroll.containers[&vec![0, 1, 0]], egui::vec2(0.0, 150.0));
+        assert_eq!(scroll.viewport, Vec2::ZERO);
+        scroll.scroll_by(&list, inside, size, egui::vec2(0.0, 100.0));
+        assert_eq!(scroll.containers[&vec![0, 1, 0]], egui::vec2(0.0, 200.0));
+        assert_eq!(scroll.viewport, egui::vec2(0.0, 50.0));
+
+        // Elsewhere only the viewport scrolls, as far as the end of the document
+        scroll.scroll_by(
+            &list,
+            Some(egui::pos2(10.0, 300.0)),
+            size,
+            egui::vec2(0.0, 1e6),
+        );
+        assert_eq!(scroll.viewport, egui::vec2(0.0, 500.0));
+        let key = ScrollState::key_delta(egui::Key::Home, size).unwrap();
+        scroll.scroll_by(&list, None, size, key);
+        assert_eq!(scroll.viewport, Vec2::ZERO);
+        assert_eq!(scroll.containers[&vec![0, 1, 0]], egui::vec2(0.0, 200.0));
+
+        // Items inside the container move with it and are clipped to its padding box
+        let visible = scroll.visible(size);
+        let (offset, clip) = list.placement(list.items.len() - 1, visible, &scroll.containers);
+        assert_eq!(offset, egui::vec2(0.0, -200.0));
+        assert_eq!(
+            clip,
+            Rect::from_min_size(Pos2::ZERO, egui::vec2(400.0, 100.0))
+        );
+
+        // Revealing a rect only scrolls when it's out of view, centring it where it can
+        let line = |y: f32| Rect::from_min_size(egui::pos2(0.0, y), egui::vec2(100.0, 20.0));
+        scroll.reveal(&list, line(100.0), size);
+        assert_eq!(scroll.viewport, Vec2::ZERO);
+        scroll.reveal(&list, line(600.0), size);
+        assert_eq!(scroll.viewport, egui::vec2(0.0, 360.0));
+        scroll.reveal(&list, line(960.0), size);
+        assert_eq!(scroll.viewport, egui::vec2(0.0, 500.0));
+    }
+
+    #[test]
+    fn test_revealing_scrolls_the_containers_around_a_rect() {
+        // A 100px tall scroller with 300px of content, 150px down a 200px tall one
+        let list = display_list(
+            "<body style='margin: 0'>\
+             <div style='overflow: auto; height: 200px'><div style='height: 150px'></div>\
+               <div style='overflow: auto; height: 100px'>\
+                 <div style='height: 300px; background: red'></div></div></div>\
+             <div style='height: 900px'></div></body>",
+        );
+        assert_eq!(list.scroll_containers.len(), 2);
+        let size = egui::vec2(400.0, 100.0);
+        let mut scroll = ScrollState::default();
+
+        // A line below the fold of the inner scroller is centred in it, then the outer one
+        // scrolls as far as it can and the viewport brings it the rest of the way
+        let line = Rect::from_min_size(egui::pos2(0.0, 380.0), egui::vec2(100.0, 20.0));
+        scroll.reveal(&list, line, size);
+        assert_eq!(scroll.containers[&vec![0, 1, 0, 1]], egui::vec2(0.0, 190.0));
+        assert_eq!(scroll.containers[&vec![0, 1, 0]], egui::vec2(0.0, 50.0));
+        assert_eq!(scroll.viewport, egui::vec2(0.0, 100.0));
+
+        // Where the line paints with the inner scroller's content, which is now in view
+        let inner = list.scroll_containers.len() - 1;
+        let index = list
+            .containers
+            .iter()
+            .position(|container| *container == Some(inner))
+            .unwrap();
+        let (offset, clip) = list.placement(index, scroll.visible(size), &scroll.containers);
+        let shown = line.translate(offset - scroll.viewport);
+        assert_eq!(shown.top(), 40.0);
+        assert!(clip.translate(-scroll.viewport).contains_rect(shown));
+
+        // Once in view nothing moves
+        let before = scroll.clone();
+        scroll.reveal(&list, line, size);
+        assert_eq!(scroll, before);
+    }
+}
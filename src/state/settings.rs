@@ -1,4 +1,6 @@
+use crate::state::dark_mode::DarkSites;
 use crate::state::theme::Layout;
+use crate::state::zoom::SiteZoom;
 use std::fmt;
 use url::Url;
 
@@ -6,6 +8,12 @@ pub struct AppSettings {
     pub title: String,
     pub default_url: Url,
     pub layout: Layout,
+    // Reported to pages through the prefers-reduced-motion media feature
+    pub reduced_motion: bool,
+    // The page zoom of each site, which Ctrl+plus, Ctrl+minus and Ctrl+0 change
+    pub site_zoom: SiteZoom,
+    // The sites whose pages are recolored dark
+    pub dark_sites: DarkSites,
 }
 
 impl Default for AppSettings {
@@ -14,6 +22,9 @@ impl Default for AppSettings {
             title: "Oliver Explorer".to_string(),
             default_url: Url::parse("http://localhost:3333").unwrap(),
             layout: Layout::default(),
+            reduced_motion: false,
+            site_zoom: SiteZoom::default(),
+            dark_sites: DarkSites::default(),
         }
     }
 }
@@ -23,6 +34,9 @@ impl fmt::Debug for AppSettings {
         f.debug_struct("AppSettings")
             .field("title", &self.title)
             .field("default_url", &self.default_url)
+            .field("reduced_motion", &self.reduced_motion)
+            .field("site_zoom", &self.site_zoom)
+            .field("dark_sites", &self.dark_sites)
             // Intentionally skip the theme field
             .finish()
     }
@@ -40,5 +54,6 @@ mod tests {
             settings.default_url,
             Url::parse("http://localhost:3333").unwrap()
         );
+        assert!(!settings.reduced_motion);
     }
 }
@@ -0,0 +1,112 @@
+// Settings kept per site, such as zoom levels and dark mode, in files in the app's data
+// directory
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use url::Url;
+
+/// A value for each site, by origin. With a path the values are read from and saved to a
+/// file, one origin per line followed by its value if it has one.
+#[derive(Debug, Default)]
+pub struct SiteStore {
+    // Sorted so the file doesn't change order between saves
+    values: Mutex<BTreeMap<String, String>>,
+    path: Option<PathBuf>,
+}
+
+impl SiteStore {
+    // Values kept in the file at `path`, which needn't exist yet
+    pub fn load(path: PathBuf) -> Self {
+        let values = fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| match line.split_once(' ') {
+                Some((origin, value)) => (origin.to_string(), value.to_string()),
+                None => (line.to_string(), String::new()),
+            })
+            .collect();
+        Self {
+            values: Mutex::new(values),
+            path: Some(path),
+        }
+    }
+
+    // The value of the site of `url`, None if it has none or the store is unavailable
+    pub fn get(&self, url: &Url) -> Option<String> {
+        let Ok(values) = self.values.lock() else {
+            return None;
+        };
+        values.get(&origin(url)).cloned()
+    }
+
+    // Sets or, with None, removes the value of the site of `url`, and saves the store
+    pub fn set(&self, url: &Url, value: Option<String>) -> Result<(), String> {
+        let Ok(mut values) = self.values.lock() else {
+            return Err("Site settings unavailable".to_string());
+        };
+        match value {
+            Some(value) => values.insert(origin(url), value),
+            None => values.remove(&origin(url)),
+        };
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let lines: String = values
+            .iter()
+            .map(|(origin, value)| match value.is_empty() {
+                true => format!("{}\n", origin),
+                false => format!("{} {}\n", origin, value),
+            })
+            .collect();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        fs::write(path, lines).map_err(|e| e.to_string())
+    }
+}
+
+// A file in the app's data directory, following the XDG base directories
+pub fn data_path(name: &str) -> Option<PathBuf> {
+    let data = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share"))
+        })?;
+    Some(data.join("oliver-explorer").join(name))
+}
+
+// Pages with an opaque origin, such as files, are each a site of their own
+fn origin(url: &Url) -> String {
+    match url.origin() {
+        origin @ url::Origin::Tuple(..) => origin.ascii_serialization(),
+        url::Origin::Opaque(_) => url[..url::Position::AfterPath].to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_poisoned_store_is_unavailable() {
+        let store = Arc::new(SiteStore::default());
+        let url = Url::parse("https://news.example/").unwrap();
+        store.set(&url, Some("on".to_string())).unwrap();
+        assert_eq!(store.get(&url), Some("on".to_string()));
+
+        // A thread that panics while holding the lock leaves it poisoned
+        let poisoner = Arc::clone(&store);
+        let _ = std::thread::spawn(move || {
+            let _values = poisoner.values.lock();
+            panic!("poisoned");
+        })
+        .join();
+        assert_eq!(store.get(&url), None);
+        assert!(store.set(&url, None).is_err());
+    }
+}
@@ -1,6 +1,13 @@
+use crate::css;
+use crate::dom;
 use crate::html;
 use crate::networking;
+use crate::state::find::FindState;
+use crate::state::page::Page;
+use crate::state::reader::ReaderMode;
+use crate::state::scroll::ScrollState;
 use crate::state::settings::AppSettings;
+use crate::state::zoom::{self, ZOOM_LEVELS};
 use std::sync::Arc;
 use url::Url;
 
@@ -15,24 +22,56 @@ pub struct Tab {
     pub id: usize,
     pub url: Url,
     pub content: String,
+    pub page: Page,
     pub settings: Arc<AppSettings>,
     state: TabState,
     pub search_buffer: String,
+    pub scroll: ScrollState,
+    pub find: FindState,
+    pub reader: ReaderMode,
 }
 
 impl Tab {
     pub fn new(id: usize, settings: Arc<AppSettings>) -> Self {
-        let content = Tab::content_from_url(&settings.default_url).unwrap_or_default();
+        let (content, page) = Tab::content_from_url(&settings.default_url, css::Device::default())
+            .unwrap_or_default();
         Self {
             id,
             url: settings.default_url.clone(),
             content,
+            page,
             settings,
             state: TabState::Active,
             search_buffer: String::new(),
+            scroll: ScrollState::default(),
+            find: FindState::default(),
+            reader: ReaderMode::default(),
         }
     }
 
+    // Creates a tab showing `url` rather than the default page. Fails, without a tab, if the
+    // page can't be loaded.
+    pub fn open(
+        id: usize,
+        settings: Arc<AppSettings>,
+        url: &Url,
+        device: css::Device,
+    ) -> Result<Self, String> {
+        let (content, page) = Tab::content_from_url(url, device)?;
+        Ok(Self {
+            id,
+            url: url.clone(),
+            content,
+            page,
+            settings,
+            state: TabState::Active,
+            search_buffer: url.to_string(),
+            scroll: ScrollState::default(),
+            find: FindState::default(),
+            reader: ReaderMode::default(),
+        })
+    }
+
     pub fn is_active(&self) -> bool {
         self.state == TabState::Active
     }
@@ -52,15 +91,75 @@ impl Tab {
             Ok(url) => url,
             Err(e) => return Err(format!("Failed to parse URL: {}", e)),
         };
+        self.navigate(&url)
+    }
 
-        let content = Tab::content_from_url(&url)?;
+    // Loads `url` into the tab, starting again from the top of the page
+    pub fn navigate(&mut self, url: &Url) -> Result<(), String> {
+        let (content, page) = Tab::content_from_url(url, self.page.device().clone())?;
+        self.url = url.clone();
+        self.search_buffer = url.to_string();
         self.content = content;
+        self.page = page;
+        self.scroll = ScrollState::default();
+        self.reader.original = None;
+        Ok(())
+    }
+
+    // Shows the page's article on its own in the reader's style, or the page again if
+    // the reader is showing
+    pub fn toggle_reader(&mut self) -> Result<(), String> {
+        match self.reader.original.take() {
+            Some(original) => self.page = original,
+            None => {
+                let article = dom::extract_article(&self.page.document)
+                    .ok_or("No article found on the page")?;
+                let html = self.reader.html(&article);
+                let reader = Page::new(&html, &self.url, self.page.device().clone());
+                self.reader.original = Some(std::mem::replace(&mut self.page, reader));
+            }
+        }
+        self.scroll = ScrollState::default();
         Ok(())
     }
 
-    fn content_from_url(url: &Url) -> Result<String, String> {
+    // Shows the article again after the reader's font size or palette changed
+    pub fn restyle_reader(&mut self) {
+        let Some(original) = &self.reader.original else {
+            return;
+        };
+        if let Some(article) = dom::extract_article(&original.document) {
+            let html = self.reader.html(&article);
+            self.page = Page::new(&html, &self.url, self.page.device().clone());
+        }
+    }
+
+    // The page zoom of the tab's site: how many pixels of the screen a CSS pixel takes
+    pub fn zoom(&self) -> f32 {
+        self.settings.site_zoom.get(&self.url)
+    }
+
+    // Zooms every page of the tab's site, keeping the level for the next visit
+    pub fn set_zoom(&mut self, zoom: f32) -> Result<(), String> {
+        let zoom = zoom.clamp(ZOOM_LEVELS[0], ZOOM_LEVELS[ZOOM_LEVELS.len() - 1]);
+        self.settings.site_zoom.set(&self.url, zoom)
+    }
+
+    pub fn zoom_in(&mut self) -> Result<(), String> {
+        self.set_zoom(zoom::zoom_in(self.zoom()))
+    }
+
+    pub fn zoom_out(&mut self) -> Result<(), String> {
+        self.set_zoom(zoom::zoom_out(self.zoom()))
+    }
+
+    pub fn reset_zoom(&mut self) -> Result<(), String> {
+        self.set_zoom(1.0)
+    }
+
+    fn content_from_url(url: &Url, device: css::Device) -> Result<(String, Page), String> {
         // Fetch the URL content
-        let html_content = match networking::fetch_url(&url) {
+        let html_content = match networking::fetch_url(url) {
             Ok(html_content) => html_content,
             Err(e) => return Err(format!("Failed to fetch URL: {}", e)),
         };
@@ -69,7 +168,7 @@ impl Tab {
             Ok(parsed_content) => parsed_content,
             Err(e) => return Err(format!("Failed to parse HTML: {}", e)),
         };
-        Ok(parsed_content)
+        Ok((parsed_content, Page::new(&html_content, url, device)))
     }
 }
 
@@ -106,4 +205,25 @@ mod tests {
         tab.set_state(TabState::Active);
         assert!(tab.is_active());
     }
+
+    #[test]
+    fn test_zoom_is_shared_by_the_site() {
+        let settings = Arc::new(AppSettings::default());
+        let mut tab = Tab::new(1, Arc::clone(&settings));
+        assert_eq!(tab.zoom(), 1.0);
+        tab.zoom_out().unwrap();
+        tab.zoom_out().unwrap();
+        assert_eq!(tab.zoom(), 0.8);
+
+        // Other tabs on the site open at the same level
+        let mut other = Tab::new(2, Arc::clone(&settings));
+        assert_eq!(other.zoom(), 0.8);
+        other.url = Url::parse("https://elsewhere.example").unwrap();
+        assert_eq!(other.zoom(), 1.0);
+
+        tab.set_zoom(10.0).unwrap();
+        assert_eq!(tab.zoom(), 3.0);
+        tab.reset_zoom().unwrap();
+        assert_eq!(Tab::new(3, settings).zoom(), 1.0);
+    }
 }
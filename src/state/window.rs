@@ -1,12 +1,19 @@
 use crate::state::settings::AppSettings;
 use crate::state::tab::{Tab, TabState};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use url::Url;
+
+// How long a message stays in the status line
+pub const MESSAGE_DURATION: Duration = Duration::from_secs(5);
 
 pub struct Window {
     pub id: usize,
     pub tabs: Vec<Tab>,
     pub settings: Arc<AppSettings>,
     next_tab_id: usize,
+    // A message for the status line, such as why an action failed, and when it was shown
+    message: Option<(String, Instant)>,
 }
 
 impl Window {
@@ -18,20 +25,38 @@ impl Window {
             tabs: vec![default_tab],
             settings,
             next_tab_id: 1,
+            message: None,
         }
     }
 
+    // Shows a message in the status line for a while
+    pub fn show_message(&mut self, message: impl Into<String>) {
+        self.message = Some((message.into(), Instant::now()));
+    }
+
+    // The message in the status line, until it's been shown for long enough
+    pub fn message(&self) -> Option<&str> {
+        let (message, shown) = self.message.as_ref()?;
+        (shown.elapsed() < MESSAGE_DURATION).then_some(message.as_str())
+    }
+
     // Create a new tab
     pub fn new_tab(&mut self) {
+        let new_tab = Tab::new(self.next_tab_id, Arc::clone(&self.settings));
+        self.push_tab(new_tab);
+    }
+
+    // Adds a tab after the others and makes it the active one
+    fn push_tab(&mut self, new_tab: Tab) {
         for tab in &mut self.tabs {
             tab.set_state(TabState::Inactive);
         }
-        let new_tab = Tab::new(self.next_tab_id, Arc::clone(&self.settings));
         self.tabs.push(new_tab);
         self.next_tab_id += 1;
     }
 
-    // Set the active tab
+    // Set the active tab. Each tab keeps its own scroll positions, so a page shows where it
+    // was left when its tab comes back.
     pub fn set_active_tab(&mut self, id: usize) {
         for tab in &mut self.tabs {
             match tab.id == id {
@@ -67,6 +92,18 @@ impl Window {
         Ok(())
     }
 
+    // Follows a link from the active tab, in a new tab if asked. The new tab is only added
+    // once the link has loaded.
+    pub fn open_link(&mut self, url: &Url, new_tab: bool) -> Result<(), String> {
+        if !new_tab {
+            return self.get_active_tab_mut()?.navigate(url);
+        }
+        let device = self.get_active_tab()?.page.device().clone();
+        let tab = Tab::open(self.next_tab_id, Arc::clone(&self.settings), url, device)?;
+        self.push_tab(tab);
+        Ok(())
+    }
+
     // Close a tab and activate the next tab if it exists.
     // Returns true if the parent must also close the window
     pub fn close_tab(&mut self, id: usize) -> Result<bool, String> {
@@ -91,7 +128,7 @@ impl Window {
         match self.tabs.len() {
             1 => {
                 self.tabs.remove(index);
-                return Ok(true);
+                Ok(true)
             }
             tab_count => {
                 match index == tab_count - 1 {
@@ -99,7 +136,7 @@ impl Window {
                     false => self.set_active_tab(self.tabs[index + 1].id),
                 }
                 self.tabs.remove(index);
-                return Ok(false);
+                Ok(false)
             }
         }
     }
@@ -108,6 +145,11 @@ impl Window {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::css;
+    use crate::layout::FixedMetrics;
+    use crate::rendering;
+    use crate::state::page::Page;
+    use eframe::egui;
 
     #[test]
     fn test_window_creation() {
@@ -158,6 +200,87 @@ mod tests {
         assert!(!window.tabs[1].is_active());
     }
 
+    #[test]
+    fn test_set_active_tab_keeps_scroll_positions() {
+        let settings = Arc::new(AppSettings::default());
+        let mut window = Window::new(0, Arc::clone(&settings));
+        window.new_tab();
+        let size = egui::vec2(400.0, 200.0);
+
+        // Lays out the active tab's page and returns its display list
+        let show = |window: &mut Window, html: Option<&str>| {
+            let tab = window.get_active_tab_mut().unwrap();
+            if let Some(html) = html {
+                let device = css::Device {
+                    viewport_width: size.x,
+                    viewport_height: size.y,
+                    ..Default::default()
+                };
+                tab.page = Page::new(html, &tab.url, device);
+            }
+            let root = tab.page.layout(size.x, &FixedMetrics).unwrap();
+            rendering::build_display_list(root)
+        };
+
+        // A 100px tall scroller with 300px of content in a 1000px tall document
+        let list = show(
+            &mut window,
+            Some(
+                "<body style='margin: 0'><div style='overflow: auto; height: 100px'>\
+                 <div style='height: 300px; background: red'></div></div>\
+                 <div style='height: 900px'></div></body>",
+            ),
+        );
+        let inside = Some(egui::pos2(10.0, 50.0));
+        let tab = window.get_active_tab_mut().unwrap();
+        tab.scroll
+            .scroll_by(&list, inside, size, egui::vec2(0.0, 250.0));
+        assert_eq!(
+            tab.scroll.containers[&vec![0, 1, 0]],
+            egui::vec2(0.0, 200.0)
+        );
+        assert_eq!(tab.scroll.viewport, egui::vec2(0.0, 50.0));
+
+        // The other tab scrolls its own 600px tall page
+        window.set_active_tab(0);
+        let other = show(
+            &mut window,
+            Some("<body style='margin: 0'><div style='height: 600px'></div></body>"),
+        );
+        let tab = window.get_active_tab_mut().unwrap();
+        tab.scroll
+            .scroll_by(&other, None, size, egui::vec2(0.0, 1e6));
+        assert_eq!(tab.scroll.viewport, egui::vec2(0.0, 400.0));
+
+        // Back in the first tab its offsets still apply to its page, and scrolling goes on
+        // from them within its bounds
+        window.set_active_tab(1);
+        let list = show(&mut window, None);
+        let tab = window.get_active_tab_mut().unwrap();
+        tab.scroll.clamp(&list, size);
+        assert_eq!(tab.scroll.viewport, egui::vec2(0.0, 50.0));
+        let content = list.containers.iter().position(Option::is_some).unwrap();
+        let visible = tab.scroll.visible(size);
+        let (offset, _) = list.placement(content, visible, &tab.scroll.containers);
+        assert_eq!(offset, egui::vec2(0.0, -200.0));
+        tab.scroll
+            .scroll_by(&list, inside, size, egui::vec2(0.0, -50.0));
+        assert_eq!(
+            tab.scroll.containers[&vec![0, 1, 0]],
+            egui::vec2(0.0, 150.0)
+        );
+        tab.scroll
+            .scroll_by(&list, None, size, egui::vec2(0.0, 1e6));
+        assert_eq!(tab.scroll.viewport, egui::vec2(0.0, 800.0));
+
+        // While the other tab's stays where it was left
+        window.set_active_tab(0);
+        let tab = window.get_active_tab_mut().unwrap();
+        tab.scroll.clamp(&other, size);
+        assert_eq!(tab.scroll.viewport, egui::vec2(0.0, 400.0));
+        assert!(tab.scroll.containers.is_empty());
+    }
+
     #[test]
     fn test_get_active_tab() {
         let settings = Arc::new(AppSettings::default());
@@ -256,6 +379,55 @@ mod tests {
         assert_eq!(window.tabs.len(), 1);
     }
 
+    #[test]
+    fn test_open_link() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/next.html")
+            .with_body("<html><body>Next</body></html>")
+            .create();
+        let url = Url::parse(&server.url())
+            .unwrap()
+            .join("next.html")
+            .unwrap();
+        let settings = Arc::new(AppSettings::default());
+        let mut window = Window::new(0, Arc::clone(&settings));
+
+        // A plain click navigates the active tab
+        window.open_link(&url, false).unwrap();
+        assert_eq!(window.tabs.len(), 1);
+        assert_eq!(window.tabs[0].url, url);
+        assert_eq!(window.tabs[0].search_buffer, url.to_string());
+
+        // A middle or ctrl click opens the link in a new tab
+        window.open_link(&url, true).unwrap();
+        assert_eq!(window.tabs.len(), 2);
+        assert_eq!(window.get_active_tab().unwrap().id, 1);
+        assert_eq!(window.tabs[1].url, url);
+        assert_eq!(window.tabs[1].search_buffer, url.to_string());
+
+        // Links that fail to load leave no tab behind
+        server
+            .mock("GET", "/missing.html")
+            .with_status(404)
+            .create();
+        let missing = url.join("missing.html").unwrap();
+        assert!(window.open_link(&missing, true).is_err());
+        assert_eq!(window.tabs.len(), 2);
+        assert_eq!(window.get_active_tab().unwrap().id, 1);
+    }
+
+    #[test]
+    fn test_messages_expire() {
+        let mut window = Window::new(0, Arc::new(AppSettings::default()));
+        assert_eq!(window.message(), None);
+        window.show_message("Couldn't open the link");
+        assert_eq!(window.message(), Some("Couldn't open the link"));
+        let (_, shown) = window.message.as_mut().unwrap();
+        *shown -= MESSAGE_DURATION;
+        assert_eq!(window.message(), None);
+    }
+
     // Note: We can't easily test the case where the last tab is closed
     // because it calls std::process::exit(0)
 }
@@ -0,0 +1,102 @@
+use crate::state::site_store::{data_path, SiteStore};
+use std::path::PathBuf;
+use url::Url;
+
+// The steps Ctrl+plus and Ctrl+minus move through
+pub const ZOOM_LEVELS: [f32; 13] = [
+    0.3, 0.5, 0.67, 0.8, 0.9, 1.0, 1.1, 1.25, 1.5, 1.75, 2.0, 2.5, 3.0,
+];
+
+// The next level up from `zoom`
+pub fn zoom_in(zoom: f32) -> f32 {
+    ZOOM_LEVELS
+        .into_iter()
+        .find(|level| *level > zoom + 0.001)
+        .unwrap_or(ZOOM_LEVELS[ZOOM_LEVELS.len() - 1])
+}
+
+pub fn zoom_out(zoom: f32) -> f32 {
+    ZOOM_LEVELS
+        .into_iter()
+        .rev()
+        .find(|level| *level < zoom - 0.001)
+        .unwrap_or(ZOOM_LEVELS[0])
+}
+
+/// The zoom level of each site, by origin. Sites at 100% aren't stored. With a path the
+/// levels are read from and saved to a file, so they last between sessions.
+#[derive(Debug, Default)]
+pub struct SiteZoom {
+    levels: SiteStore,
+}
+
+impl SiteZoom {
+    // Levels kept in the file at `path`, which needn't exist yet
+    pub fn load(path: PathBuf) -> Self {
+        Self {
+            levels: SiteStore::load(path),
+        }
+    }
+
+    // The file in the user's data directory
+    pub fn default_path() -> Option<PathBuf> {
+        data_path("site_zoom")
+    }
+
+    pub fn get(&self, url: &Url) -> f32 {
+        self.levels
+            .get(url)
+            .and_then(|zoom| zoom.parse().ok())
+            .unwrap_or(1.0)
+    }
+
+    pub fn set(&self, url: &Url, zoom: f32) -> Result<(), String> {
+        let zoom = ((zoom - 1.0).abs() >= 0.001).then(|| zoom.to_string());
+        self.levels
+            .set(url, zoom)
+            .map_err(|e| format!("Failed to save zoom: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_zoom_levels_step() {
+        assert_eq!(zoom_in(1.0), 1.1);
+        assert_eq!(zoom_out(1.0), 0.9);
+        // Levels between steps go to the nearest step in that direction
+        assert_eq!(zoom_out(0.85), 0.8);
+        assert_eq!(zoom_in(3.0), 3.0);
+        assert_eq!(zoom_out(0.3), 0.3);
+    }
+
+    #[test]
+    fn test_site_zoom_is_saved_per_origin() {
+        let path = std::env::temp_dir()
+            .join(format!("oliver-explorer-{}", std::process::id()))
+            .join("site_zoom");
+        let dashboard = Url::parse("https://dashboard.example/graphs?range=7d").unwrap();
+        let zoom = SiteZoom::load(path.clone());
+        assert_eq!(zoom.get(&dashboard), 1.0);
+        zoom.set(&dashboard, 0.8).unwrap();
+        zoom.set(&Url::parse("https://other.example").unwrap(), 1.0)
+            .unwrap();
+
+        // Every page of the origin opens at the saved level, in later sessions too
+        let zoom = SiteZoom::load(path.clone());
+        let page = Url::parse("https://dashboard.example/alerts").unwrap();
+        assert_eq!(zoom.get(&page), 0.8);
+        assert_eq!(
+            zoom.get(&Url::parse("http://dashboard.example").unwrap()),
+            1.0
+        );
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "https://dashboard.example 0.8\n"
+        );
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
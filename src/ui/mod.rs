@@ -2,6 +2,7 @@ mod components;
 mod tab;
 mod window;
 
+use crate::images;
 use crate::state;
 use eframe::egui;
 
@@ -13,12 +14,44 @@ pub enum AppAction {
 
 impl state::OliverExplorer {
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        let state = Self::default();
+        // Sites keep their zoom level and dark mode between sessions
+        let settings = state::AppSettings {
+            site_zoom: state::SiteZoom::default_path()
+                .map(state::SiteZoom::load)
+                .unwrap_or_default(),
+            dark_sites: state::DarkSites::default_path()
+                .map(state::DarkSites::load)
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+        let state = Self::with_settings(settings);
         egui_extras::install_image_loaders(&cc.egui_ctx);
+        // Ctrl+plus and Ctrl+minus zoom the page rather than the whole interface
+        cc.egui_ctx
+            .options_mut(|options| options.zoom_with_keyboard = false);
+        // Pages are laid out again as their images arrive
+        images::ImageCache::global().set_repaint_context(&cc.egui_ctx);
         cc.egui_ctx
             .set_style_of(egui::Theme::Dark, state::default_style());
         state
     }
+
+    // Registers the web fonts of any newly loaded pages with egui. The new fonts are
+    // active from the next frame.
+    fn register_fonts(&mut self, ctx: &egui::Context) {
+        let mut changed = false;
+        for face in self
+            .windows
+            .iter()
+            .flat_map(|window| &window.tabs)
+            .flat_map(|tab| tab.page.fonts.faces())
+        {
+            changed |= self.fonts.add(face);
+        }
+        if changed {
+            ctx.set_fonts(self.fonts.font_definitions());
+        }
+    }
 }
 
 impl eframe::App for state::OliverExplorer {
@@ -27,6 +60,7 @@ impl eframe::App for state::OliverExplorer {
     }
 
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.register_fonts(ctx);
         for window in &mut self.windows {
             window.update(ctx);
         }
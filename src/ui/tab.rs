@@ -28,7 +28,7 @@ impl state::Tab {
             .corner_radius(corner_radius);
         let inner_frame = egui::Frame::new().inner_margin(ui.spacing().window_margin);
 
-        let label = egui::Label::new(egui::RichText::new(&self.url.to_string())).truncate();
+        let label = egui::Label::new(egui::RichText::new(self.url.to_string())).truncate();
 
         outer_frame.show(ui, |ui| {
             ui.set_width(width);
@@ -61,7 +61,10 @@ impl state::Tab {
                     }
                 });
             });
-            if ui.interact_bg(egui::Sense::click()).clicked() {
+            if ui
+                .interact(ui.max_rect(), ui.id(), egui::Sense::click())
+                .clicked()
+            {
                 action = WindowAction::SelectTab(self.id);
             }
         });
@@ -1,9 +1,21 @@
 // UI specific to window level changes
 
 use eframe::egui;
+use eframe::emath::TSTransform;
 
+use crate::css;
+use crate::dom;
+use crate::layout;
+use crate::rendering;
 use crate::state;
 use crate::ui::components;
+use std::sync::Mutex;
+use url::Url;
+
+// The thickness of the scrollbar thumbs drawn over the page
+const SCROLLBAR_WIDTH: f32 = 6.0;
+// The id of the find bar's text field, which Ctrl+F focuses
+const FIND_FIELD: &str = "find_field";
 
 // Actions to be executed at window level
 #[derive(Debug, PartialEq)]
@@ -12,6 +24,25 @@ pub enum WindowAction {
     SelectTab(usize),
     CloseTab(usize),
     Search(usize),
+    // Follow a link from the active tab, in a new tab if asked
+    OpenLink(Url, bool),
+    // Open, step through and close the find bar of the active tab
+    OpenFind,
+    FindNext,
+    FindPrevious,
+    CloseFind,
+    // Zoom the site of the active tab in, out or back to 100%
+    ZoomIn,
+    ZoomOut,
+    ResetZoom,
+    // Show the active tab's article in reader mode, and change how the reader looks
+    ToggleReader,
+    ReaderFontSize(bool),
+    ToggleReaderPalette,
+    // Print the active tab's page to a PDF file in the downloads folder
+    SavePdf,
+    // Turn forced dark mode on or off for the active tab's site
+    ToggleForcedDark,
     ToggleMaximize,
     DragWindow,
     CloseWindow,
@@ -46,6 +77,84 @@ impl WindowAction {
                     dbg!("Error searching: {}", e);
                 };
             }
+            WindowAction::OpenLink(url, new_tab) => {
+                if let Err(e) = window.open_link(&url, new_tab) {
+                    window.show_message(format!("Couldn't open {}: {}", url, e));
+                }
+            }
+            WindowAction::OpenFind => {
+                if let Ok(tab) = window.get_active_tab_mut() {
+                    tab.find.open();
+                    ui.memory_mut(|memory| memory.request_focus(egui::Id::new(FIND_FIELD)));
+                }
+            }
+            WindowAction::FindNext => {
+                if let Ok(tab) = window.get_active_tab_mut() {
+                    tab.find.next();
+                }
+            }
+            WindowAction::FindPrevious => {
+                if let Ok(tab) = window.get_active_tab_mut() {
+                    tab.find.previous();
+                }
+            }
+            WindowAction::CloseFind => {
+                if let Ok(tab) = window.get_active_tab_mut() {
+                    tab.find.close();
+                }
+            }
+            WindowAction::ZoomIn | WindowAction::ZoomOut | WindowAction::ResetZoom => {
+                let Ok(tab) = window.get_active_tab_mut() else {
+                    return;
+                };
+                let result = match self {
+                    WindowAction::ZoomIn => tab.zoom_in(),
+                    WindowAction::ZoomOut => tab.zoom_out(),
+                    _ => tab.reset_zoom(),
+                };
+                if let Err(e) = result {
+                    window.show_message(e);
+                }
+            }
+            WindowAction::ToggleReader => {
+                let result = window.get_active_tab_mut().map(|tab| tab.toggle_reader());
+                if let Ok(Err(e)) = result {
+                    window.show_message(e);
+                }
+            }
+            WindowAction::ReaderFontSize(larger) => {
+                if let Ok(tab) = window.get_active_tab_mut() {
+                    tab.reader.step_font_size(larger);
+                    tab.restyle_reader();
+                }
+            }
+            WindowAction::ToggleReaderPalette => {
+                if let Ok(tab) = window.get_active_tab_mut() {
+                    tab.reader.palette = match tab.reader.palette {
+                        state::ReaderPalette::Light => state::ReaderPalette::Dark,
+                        state::ReaderPalette::Dark => state::ReaderPalette::Light,
+                    };
+                    tab.restyle_reader();
+                }
+            }
+            WindowAction::SavePdf => {
+                let Ok(tab) = window.get_active_tab_mut() else {
+                    return;
+                };
+                let message = match tab.save_pdf(&state::download_dir()) {
+                    Ok(path) => format!("Saved PDF to {}", path.display()),
+                    Err(e) => format!("Couldn't save PDF: {}", e),
+                };
+                window.show_message(message);
+            }
+            WindowAction::ToggleForcedDark => {
+                let result = window
+                    .get_active_tab_mut()
+                    .map(|tab| tab.toggle_forced_dark());
+                if let Ok(Err(e)) = result {
+                    window.show_message(e);
+                }
+            }
             WindowAction::ToggleMaximize => {
                 let is_maximized = ui.input(|i| i.viewport().maximized.unwrap_or(false));
                 ui.ctx()
@@ -81,6 +190,27 @@ impl state::Window {
                     ui.spacing_mut().item_spacing = egui::vec2(0.0, 0.0);
                     self.draw_title_bar(ui);
                     self.draw_search_bar(ui);
+                    if ui.input_mut(|input| {
+                        input.consume_key(egui::Modifiers::COMMAND, egui::Key::F)
+                    }) {
+                        WindowAction::OpenFind.execute(self, ui);
+                    }
+                    let zoom = ui.input_mut(|input| {
+                        let mut consume = |key| input.consume_key(egui::Modifiers::COMMAND, key);
+                        if consume(egui::Key::Plus) || consume(egui::Key::Equals) {
+                            WindowAction::ZoomIn
+                        } else if consume(egui::Key::Minus) {
+                            WindowAction::ZoomOut
+                        } else if consume(egui::Key::Num0) {
+                            WindowAction::ResetZoom
+                        } else {
+                            WindowAction::None
+                        }
+                    });
+                    zoom.execute(self, ui);
+                    if self.get_active_tab().is_ok_and(|tab| tab.find.open) {
+                        self.draw_find_bar(ui);
+                    }
                     self.draw_content(ui);
                 });
             });
@@ -210,27 +340,487 @@ impl state::Window {
             .desired_width(search_width);
 
         let search_response = ui.add_sized([search_width, search_height], search);
-        ui.add_space(padding);
+        let spacing = ui.spacing().item_spacing.x.max(4.0);
+        let mut action = WindowAction::None;
+
+        // Reader mode, with the reader's text size and palette while it's showing
+        ui.add_space(spacing);
+        let reader = &active_tab.reader;
+        if ui
+            .selectable_label(reader.is_active(), "📖")
+            .on_hover_text("Reader mode")
+            .clicked()
+        {
+            action = WindowAction::ToggleReader;
+        }
+        if reader.is_active() {
+            if ui
+                .small_button("A−")
+                .on_hover_text("Smaller text")
+                .clicked()
+            {
+                action = WindowAction::ReaderFontSize(false);
+            }
+            if ui.small_button("A+").on_hover_text("Larger text").clicked() {
+                action = WindowAction::ReaderFontSize(true);
+            }
+            let palette = match reader.palette {
+                state::ReaderPalette::Light => "🌙",
+                state::ReaderPalette::Dark => "☀",
+            };
+            if ui
+                .small_button(palette)
+                .on_hover_text("Reader colors")
+                .clicked()
+            {
+                action = WindowAction::ToggleReaderPalette;
+            }
+        }
+
+        // Forced dark mode for the site
+        ui.add_space(spacing);
+        if ui
+            .selectable_label(active_tab.is_dark_site(), "🌓")
+            .on_hover_text("Dark mode for this site")
+            .clicked()
+        {
+            action = WindowAction::ToggleForcedDark;
+        }
+
+        // Sites that aren't at 100% show their zoom level, which resets it when clicked
+        let zoom = active_tab.zoom();
+        if (zoom - 1.0).abs() > 0.001 {
+            ui.add_space(spacing);
+            let label = egui::Button::new(format!("{:.0}%", zoom * 100.0)).frame(false);
+            if ui.add(label).on_hover_text("Reset zoom").clicked() {
+                action = WindowAction::ResetZoom;
+            }
+        }
+
+        // The page menu
+        ui.add_space(spacing);
+        ui.menu_button("⋮", |ui| {
+            if ui.button("Save page as PDF").clicked() {
+                action = WindowAction::SavePdf;
+                ui.close_menu();
+            }
+        });
 
         // Handle responses
         // Check if Enter key was pressed while the search box is focused
         if search_response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
-            WindowAction::Search(active_tab.id).execute(self, ui);
+            action = WindowAction::Search(active_tab.id);
         }
+        action.execute(self, ui);
     }
 
-    // Draws the content of the active tab
-    fn draw_content(&self, ui: &mut egui::Ui) {
+    // Draws the find bar under the search bar: the query, its options, the count of
+    // matches and buttons to step through them
+    fn draw_find_bar(&mut self, ui: &mut egui::Ui) {
+        let height = self.settings.layout.toolbar_height;
+        let mut actions = Vec::new();
         egui::Frame::new()
+            .fill(ui.visuals().widgets.noninteractive.bg_fill)
+            .inner_margin(ui.spacing().window_margin)
+            .show(ui, |ui| {
+                ui.set_min_width(ui.available_width());
+                ui.set_max_height(height);
+                ui.spacing_mut().item_spacing = egui::vec2(4.0, 0.0);
+                ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui| {
+                    let Ok(tab) = self.get_active_tab_mut() else {
+                        return;
+                    };
+                    let find = &mut tab.find;
+                    let field = egui::TextEdit::singleline(&mut find.query)
+                        .id(egui::Id::new(FIND_FIELD))
+                        .hint_text("Find in page")
+                        .desired_width(240.0);
+                    let response = ui.add(field);
+                    // Enter steps to the next match and Shift+Enter to the previous one,
+                    // keeping the focus in the field
+                    let (enter, shift, escape) = ui.input(|input| {
+                        (
+                            input.key_pressed(egui::Key::Enter),
+                            input.modifiers.shift,
+                            input.key_pressed(egui::Key::Escape),
+                        )
+                    });
+                    if response.lost_focus() && enter {
+                        actions.push(match shift {
+                            true => WindowAction::FindPrevious,
+                            false => WindowAction::FindNext,
+                        });
+                        response.request_focus();
+                    }
+                    if response.lost_focus() && escape {
+                        actions.push(WindowAction::CloseFind);
+                    }
+                    ui.toggle_value(&mut find.case_sensitive, "Aa")
+                        .on_hover_text("Match case");
+                    ui.toggle_value(&mut find.whole_word, "ab")
+                        .on_hover_text("Match whole words");
+                    ui.toggle_value(&mut find.regex, ".*")
+                        .on_hover_text("Use a regular expression");
+                    ui.label(find.status());
+                    let buttons = [
+                        ("⏶", "Previous match", WindowAction::FindPrevious),
+                        ("⏷", "Next match", WindowAction::FindNext),
+                        ("❌", "Close the find bar", WindowAction::CloseFind),
+                    ];
+                    for (content, hover_text, action) in buttons {
+                        actions.push(components::button(
+                            ui,
+                            components::ButtonParams {
+                                content: content.to_string(),
+                                hover_text: hover_text.to_string(),
+                                size: egui::Vec2::new(24.0, 24.0),
+                                action,
+                                ..Default::default()
+                            },
+                        ));
+                    }
+                });
+            });
+        for action in actions {
+            action.execute(self, ui);
+        }
+    }
+
+    // Draws the content of the active tab
+    fn draw_content(&mut self, ui: &mut egui::Ui) {
+        let action = egui::Frame::new()
             .fill(ui.visuals().panel_fill)
             .inner_margin(egui::Margin::ZERO)
             .show(ui, |ui| {
                 // Take all available remaining height
                 ui.set_min_height(ui.available_height());
                 ui.set_min_width(ui.available_width());
-                let active_tab = self.get_active_tab().expect("No active tab found");
-                ui.label(&active_tab.content);
-                // TODO: Add a proper HTML renderer here
-            });
+                // The page is laid out in CSS pixels, which zooming makes larger or smaller
+                // on screen, so zoomed pages reflow to the width they have
+                let zoom = self.get_active_tab().map_or(1.0, |tab| tab.zoom());
+                // Media queries are evaluated against the content area and the app theme,
+                // which is dark on sites in dark mode
+                let dark_site = self.get_active_tab().is_ok_and(|tab| tab.is_dark_site());
+                let device = css::Device {
+                    media_type: css::MediaType::Screen,
+                    viewport_width: ui.available_width() / zoom,
+                    viewport_height: ui.available_height() / zoom,
+                    color_scheme: match ui.visuals().dark_mode || dark_site {
+                        true => css::ColorScheme::Dark,
+                        false => css::ColorScheme::Light,
+                    },
+                    reduced_motion: self.settings.reduced_motion,
+                    pixel_ratio: ui.ctx().pixels_per_point() * zoom,
+                };
+                let width = ui.available_width() / zoom;
+                let message = self.message().map(str::to_string);
+                let active_tab = self.get_active_tab_mut().expect("No active tab found");
+                active_tab.page.set_device(device);
+                if active_tab.page.needs_restyle() {
+                    active_tab.page.restyle_dirty();
+                }
+                ui.ctx()
+                    .fonts(|fonts| active_tab.page.layout(width, fonts).is_some());
+                let find = &mut active_tab.find;
+                if let (true, Some(root)) = (find.open, active_tab.page.root()) {
+                    find.update(root, &active_tab.page.document, active_tab.page.layout_id());
+                }
+
+                // Matches of the find bar are highlighted under the selection
+                let mut highlights = Vec::new();
+                if find.open {
+                    for (index, found) in find.matches.iter().enumerate() {
+                        let color = match index == find.current {
+                            true => rendering::CURRENT_MATCH_COLOR,
+                            false => rendering::MATCH_COLOR,
+                        };
+                        highlights.push((&found.range, color));
+                    }
+                }
+                if let Some(selection) = &active_tab.page.selection {
+                    if !selection.is_collapsed() {
+                        highlights.push((selection, rendering::SELECTION_COLOR));
+                    }
+                }
+                let mut display_list = ui
+                    .ctx()
+                    .fonts(|fonts| {
+                        let root = active_tab.page.root()?;
+                        Some(match highlights.is_empty() {
+                            true => rendering::build_display_list(root),
+                            false => rendering::build_display_list_with_highlights(
+                                root,
+                                &highlights,
+                                fonts,
+                            ),
+                        })
+                    })
+                    .unwrap_or_default();
+                if active_tab.forced_dark() {
+                    rendering::force_dark(&mut display_list);
+                }
+                // Pages without a background are drawn on white like other browsers, or
+                // dark when shown in their dark color scheme
+                let canvas = display_list
+                    .background
+                    .unwrap_or(active_tab.page.canvas_color());
+                let size = ui.available_size();
+                let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+                ui.painter().rect_filled(rect, 0.0, canvas);
+                // Scrolling happens in CSS pixels
+                let size = size / zoom;
+
+                // The wheel scrolls what's under the pointer, and so do the keys unless
+                // a text field has focus
+                let scroll = &mut active_tab.scroll;
+                scroll.clamp(&display_list, size);
+                if let Some(found) = active_tab.find.take_moved() {
+                    scroll.reveal(&display_list, found, size);
+                }
+                let typing = ui.ctx().wants_keyboard_input();
+                let delta = ui.input(|input| {
+                    let mut delta = egui::Vec2::ZERO;
+                    if response.hovered() {
+                        delta -= input.smooth_scroll_delta / zoom;
+                    }
+                    for event in &input.events {
+                        if let egui::Event::Key {
+                            key,
+                            pressed: true,
+                            modifiers,
+                            ..
+                        } = event
+                        {
+                            if !typing && modifiers.is_none() {
+                                delta +=
+                                    state::ScrollState::key_delta(*key, size).unwrap_or_default();
+                            }
+                        }
+                    }
+                    delta
+                });
+                if delta != egui::Vec2::ZERO {
+                    let point = response
+                        .hover_pos()
+                        .map(|pos| ((pos - rect.min) / zoom + scroll.viewport).to_pos2());
+                    scroll.scroll_by(&display_list, point, size, delta);
+                }
+
+                let painter = ui.painter_at(rect);
+                let origin = rect.min.to_vec2() - scroll.viewport * zoom;
+                rendering::paint(&display_list, &painter, origin, &scroll.containers, zoom);
+                draw_scrollbars(&painter, rect, &display_list, scroll, zoom, ui.visuals());
+                // Where a point on screen is on the page
+                let to_page = |pos: egui::Pos2| ((pos - origin).to_vec2() / zoom).to_pos2();
+
+                // Pressing and dragging selects text, a double click selects a word and a
+                // triple click the block it's in. Shift extends the selection.
+                let visible = scroll.visible(size);
+                let (shift, press_origin) =
+                    ui.input(|input| (input.modifiers.shift, input.pointer.press_origin()));
+                let pointer = match response.drag_started_by(egui::PointerButton::Primary) {
+                    true => press_origin,
+                    false => response.interact_pointer_pos(),
+                };
+                let position = pointer.and_then(|pos| {
+                    ui.ctx().fonts(|fonts| {
+                        active_tab.page.position_at(
+                            to_page(pos),
+                            visible,
+                            &scroll.containers,
+                            fonts,
+                        )
+                    })
+                });
+                if let Some(position) = position {
+                    let page = &mut active_tab.page;
+                    if response.triple_clicked() {
+                        page.selection = page.block_selection(&position);
+                    } else if response.double_clicked() {
+                        page.selection = dom::Selection::word(&page.document, &position);
+                    } else if response.drag_started_by(egui::PointerButton::Primary)
+                        || response.clicked()
+                    {
+                        page.selection = match page.selection.take() {
+                            Some(selection) if shift => {
+                                Some(dom::Selection::new(selection.anchor, position))
+                            }
+                            _ => Some(dom::Selection::collapsed(position)),
+                        };
+                    } else if response.dragged_by(egui::PointerButton::Primary) {
+                        if let Some(selection) = &mut page.selection {
+                            selection.focus = position;
+                        }
+                    }
+                }
+                let (select_all, copy) = ui.input(|input| {
+                    let select_all = input.modifiers.command && input.key_pressed(egui::Key::A);
+                    let copy = input
+                        .events
+                        .iter()
+                        .any(|event| matches!(event, egui::Event::Copy));
+                    (!typing && select_all, !typing && copy)
+                });
+                if select_all {
+                    active_tab.page.select_all();
+                }
+                if copy {
+                    if let Some(text) = active_tab.page.selected_text() {
+                        copy_to_clipboard(ui.ctx(), text, active_tab.page.selected_html());
+                    }
+                }
+
+                // What's under the pointer is hovered, and links show where they go
+                let hit = response.hover_pos().and_then(|pos| {
+                    let visible = scroll.visible(size);
+                    active_tab
+                        .page
+                        .hit_test(to_page(pos), visible, &scroll.containers)
+                });
+                let cursor = hit.map_or(egui::CursorIcon::Default, cursor_icon);
+                let hovered = hit.and_then(|fragment| fragment.node.clone());
+                let link = hovered
+                    .as_deref()
+                    .and_then(|path| active_tab.page.link_at(path));
+                if response.hovered() {
+                    ui.ctx().set_cursor_icon(cursor);
+                }
+                active_tab.page.set_hovered(hovered.as_deref());
+                if active_tab.page.needs_restyle() {
+                    ui.ctx().request_repaint();
+                }
+                // The status line shows where a link goes, or else the window's message
+                if message.is_some() {
+                    ui.ctx().request_repaint_after(state::MESSAGE_DURATION);
+                }
+                let status = link.as_ref().map(Url::to_string).or(message);
+                if let Some(status) = &status {
+                    draw_status(&painter, rect, status, ui.visuals());
+                }
+                let link = link?;
+                let new_tab = response.middle_clicked()
+                    || (response.clicked() && ui.input(|input| input.modifiers.command));
+                let follow = response.clicked() && !shift;
+                match new_tab || follow {
+                    true => Some(WindowAction::OpenLink(link, new_tab)),
+                    false => None,
+                }
+            })
+            .inner;
+        if let Some(action) = action {
+            action.execute(self, ui);
+        }
+    }
+}
+
+// The pointer to show over a fragment for its `cursor` property. Auto is the text cursor
+// over text.
+fn cursor_icon(fragment: &layout::Fragment) -> egui::CursorIcon {
+    match fragment.style.keyword("cursor") {
+        Some("pointer") => egui::CursorIcon::PointingHand,
+        Some("text") => egui::CursorIcon::Text,
+        Some("move") => egui::CursorIcon::Move,
+        Some("grab") => egui::CursorIcon::Grab,
+        Some("grabbing") => egui::CursorIcon::Grabbing,
+        Some("crosshair") => egui::CursorIcon::Crosshair,
+        Some("help") => egui::CursorIcon::Help,
+        Some("wait") => egui::CursorIcon::Wait,
+        Some("progress") => egui::CursorIcon::Progress,
+        Some("not-allowed") => egui::CursorIcon::NotAllowed,
+        Some("none") => egui::CursorIcon::None,
+        Some("auto") | None if matches!(fragment.kind, layout::FragmentKind::Text(_)) => {
+            egui::CursorIcon::Text
+        }
+        _ => egui::CursorIcon::Default,
+    }
+}
+
+// Copies text to the system clipboard as HTML with a plain text alternative, or through
+// egui as plain text when the clipboard can't be opened
+fn copy_to_clipboard(ctx: &egui::Context, text: String, html: Option<String>) {
+    // The clipboard is kept open, some systems only offer its contents while it is
+    static CLIPBOARD: Mutex<Option<arboard::Clipboard>> = Mutex::new(None);
+    if let (Some(html), Ok(mut clipboard)) = (&html, CLIPBOARD.lock()) {
+        if clipboard.is_none() {
+            *clipboard = arboard::Clipboard::new().ok();
+        }
+        if let Some(clipboard) = clipboard.as_mut() {
+            if clipboard
+                .set_html(html.as_str(), Some(text.as_str()))
+                .is_ok()
+            {
+                return;
+            }
+        }
+    }
+    ctx.copy_text(text);
+}
+
+// Draws a status line with `text` in the bottom left corner of the viewport, over the
+// content
+fn draw_status(painter: &egui::Painter, rect: egui::Rect, text: &str, visuals: &egui::Visuals) {
+    let galley = painter.layout_no_wrap(
+        text.to_string(),
+        egui::FontId::proportional(12.0),
+        visuals.text_color(),
+    );
+    let margin = egui::vec2(6.0, 3.0);
+    let size = (galley.size() + margin * 2.0).min(rect.size());
+    let status = egui::Rect::from_min_size(egui::pos2(rect.left(), rect.bottom() - size.y), size);
+    let corner_radius = egui::CornerRadius {
+        ne: 4,
+        ..Default::default()
+    };
+    painter.rect_filled(status, corner_radius, visuals.window_fill());
+    painter.galley(status.min + margin, galley, visuals.text_color());
+}
+
+// Draws a thumb along the edge of the viewport and of each scroll container whose content
+// overflows it. The thumbs overlay the content rather than taking space from it.
+fn draw_scrollbars(
+    painter: &egui::Painter,
+    rect: egui::Rect,
+    list: &rendering::DisplayList,
+    scroll: &state::ScrollState,
+    zoom: f32,
+    visuals: &egui::Visuals,
+) {
+    let color = visuals.widgets.inactive.bg_fill.gamma_multiply(0.8);
+    let size = rect.size() / zoom;
+    let visible = scroll.visible(size);
+    let mut areas = vec![(visible, list.size.max(size), scroll.viewport)];
+    for (index, container) in list.scroll_containers.iter().enumerate() {
+        if !container.can_scroll() {
+            continue;
+        }
+        let area = list.container_rect(index, visible, &scroll.containers);
+        let content = container.rect.size() + container.max_offset;
+        areas.push((area, content, container.offset(&scroll.containers)));
+    }
+    // Measured in CSS pixels, and drawn scaled by the zoom
+    let origin = rect.min.to_vec2() - scroll.viewport * zoom;
+    for (area, content, offset) in areas {
+        let area = TSTransform::new(origin, zoom) * area;
+        let (content, offset) = (content * zoom, offset * zoom);
+        if content.y > area.height() + 0.5 {
+            let length = (area.height() * area.height() / content.y).max(16.0);
+            let top =
+                area.top() + offset.y / (content.y - area.height()) * (area.height() - length);
+            let thumb = egui::Rect::from_min_size(
+                egui::pos2(area.right() - SCROLLBAR_WIDTH, top),
+                egui::vec2(SCROLLBAR_WIDTH, length),
+            );
+            painter.rect_filled(thumb, SCROLLBAR_WIDTH / 2.0, color);
+        }
+        if content.x > area.width() + 0.5 {
+            let length = (area.width() * area.width() / content.x).max(16.0);
+            let left =
+                area.left() + offset.x / (content.x - area.width()) * (area.width() - length);
+            let thumb = egui::Rect::from_min_size(
+                egui::pos2(left, area.bottom() - SCROLLBAR_WIDTH),
+                egui::vec2(length, SCROLLBAR_WIDTH),
+            );
+            painter.rect_filled(thumb, SCROLLBAR_WIDTH / 2.0, color);
+        }
     }
 }
@@ -0,0 +1,198 @@
+// Reference tests: each test page must render the same as, or differently from, its
+// reference page. The tests are listed in tests/reftests/reftest.list, one per line:
+//
+//     [fuzzy(MAX_DIFFERENCE,MAX_PIXELS)] [display-list] (==|!=) TEST.html REFERENCE.html
+//
+// Pages are compared by their headless rendered pixels, or by their display lists with
+// `display-list`. `fuzzy` lets up to MAX_PIXELS pixels differ by up to MAX_DIFFERENCE in
+// any channel. Pages are local files and must not load anything over the network. The
+// renders of failing pixel tests are saved to target/reftests.
+
+use eframe::egui::{self, epaint, ColorImage};
+use oliver_explorer::{css, rendering, state::Page};
+use std::fs;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+// The viewport every page is rendered in
+const VIEWPORT: egui::Vec2 = egui::vec2(400.0, 300.0);
+
+#[derive(Debug)]
+struct Reftest {
+    line: usize,
+    equal: bool,
+    display_list: bool,
+    // The most a channel may differ by, and how many pixels may differ at all
+    fuzzy: (u8, usize),
+    test: PathBuf,
+    reference: PathBuf,
+}
+
+fn reftests_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/reftests")
+}
+
+fn parse_manifest(dir: &Path) -> Result<Vec<Reftest>, String> {
+    let manifest = fs::read_to_string(dir.join("reftest.list"))
+        .map_err(|e| format!("Failed to read reftest.list: {}", e))?;
+    let mut tests = Vec::new();
+    for (index, line) in manifest.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |message: &str| format!("reftest.list:{}: {}", line_number, message);
+        let mut words = line.split_whitespace();
+        let mut display_list = false;
+        let mut fuzzy = (0, 0);
+        let equal = loop {
+            match words.next() {
+                Some("==") => break true,
+                Some("!=") => break false,
+                Some("display-list") => display_list = true,
+                Some(word) if word.starts_with("fuzzy(") && word.ends_with(')') => {
+                    let (difference, pixels) = word["fuzzy(".len()..word.len() - 1]
+                        .split_once(',')
+                        .ok_or_else(|| error("fuzzy needs a difference and a pixel count"))?;
+                    fuzzy = (
+                        difference
+                            .parse()
+                            .map_err(|_| error("invalid difference"))?,
+                        pixels.parse().map_err(|_| error("invalid pixel count"))?,
+                    );
+                }
+                Some(word) => return Err(error(&format!("unknown word {}", word))),
+                None => return Err(error("missing == or !=")),
+            }
+        };
+        let (Some(test), Some(reference), None) = (words.next(), words.next(), words.next()) else {
+            return Err(error("expected a test and a reference"));
+        };
+        tests.push(Reftest {
+            line: line_number,
+            equal,
+            display_list,
+            fuzzy,
+            test: dir.join(test),
+            reference: dir.join(reference),
+        });
+    }
+    Ok(tests)
+}
+
+fn load_page(path: &Path) -> Result<Page, String> {
+    let html = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let url = Url::from_file_path(path).map_err(|_| "Invalid path".to_string())?;
+    let device = css::Device {
+        viewport_width: VIEWPORT.x,
+        viewport_height: VIEWPORT.y,
+        ..Default::default()
+    };
+    Ok(Page::new(&html, &url, device))
+}
+
+fn display_list(path: &Path) -> Result<rendering::DisplayList, String> {
+    let mut page = load_page(path)?;
+    let fonts = epaint::text::Fonts::new(1.0, 2048, egui::FontDefinitions::default());
+    fonts.begin_pass(1.0, 2048);
+    Ok(page
+        .layout(VIEWPORT.x, &fonts)
+        .map(rendering::build_display_list)
+        .unwrap_or_default())
+}
+
+// How many pixels differ, and the most any channel of them does
+fn difference(a: &ColorImage, b: &ColorImage) -> (usize, u8) {
+    let mut pixels = 0;
+    let mut most = 0;
+    for (a, b) in a.pixels.iter().zip(&b.pixels) {
+        let channels = a.to_array().into_iter().zip(b.to_array());
+        let channel = channels.map(|(a, b)| a.abs_diff(b)).max().unwrap_or(0);
+        if channel > 0 {
+            pixels += 1;
+            most = most.max(channel);
+        }
+    }
+    (pixels, most)
+}
+
+// Runs a test, returning why it failed
+fn run(reftest: &Reftest) -> Result<(), String> {
+    let expectation = if reftest.equal { "match" } else { "differ" };
+    if reftest.display_list {
+        let same = display_list(&reftest.test)? == display_list(&reftest.reference)?;
+        return match same == reftest.equal {
+            true => Ok(()),
+            false => Err(format!("display lists should {}", expectation)),
+        };
+    }
+    let test = load_page(&reftest.test)?.screenshot(VIEWPORT)?;
+    let reference = load_page(&reftest.reference)?.screenshot(VIEWPORT)?;
+    let (pixels, most) = difference(&test, &reference);
+    let (max_difference, max_pixels) = reftest.fuzzy;
+    let same = pixels <= max_pixels && most <= max_difference;
+    if same == reftest.equal {
+        return Ok(());
+    }
+    let output = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/reftests");
+    let _ = fs::create_dir_all(&output);
+    for (image, path) in [(&test, &reftest.test), (&reference, &reftest.reference)] {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let _ = rendering::save_png(image, &output.join(format!("{}.png", name)));
+    }
+    Err(format!(
+        "pixels should {}: {} differ, by up to {}",
+        expectation, pixels, most
+    ))
+}
+
+#[test]
+fn test_reftests() {
+    let tests = parse_manifest(&reftests_dir()).unwrap();
+    assert!(!tests.is_empty());
+    let failures: Vec<_> = tests
+        .iter()
+        .filter_map(|reftest| {
+            let error = run(reftest).err()?;
+            let name = reftest
+                .test
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy();
+            Some(format!("line {} ({}): {}", reftest.line, name, error))
+        })
+        .collect();
+    assert!(
+        failures.is_empty(),
+        "{} of {} reftests failed:\n{}",
+        failures.len(),
+        tests.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn test_manifest_errors() {
+    let dir = std::env::temp_dir().join(format!("oliver-explorer-reftests-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let parse = |manifest: &str| {
+        fs::write(dir.join("reftest.list"), manifest).unwrap();
+        parse_manifest(&dir)
+    };
+    let tests = parse("# Comment\n\nfuzzy(2,40) display-list != a.html b.html\n").unwrap();
+    assert_eq!(tests.len(), 1);
+    assert_eq!((tests[0].line, tests[0].equal), (3, false));
+    assert_eq!(tests[0].fuzzy, (2, 40));
+    assert!(tests[0].display_list);
+    assert_eq!(
+        parse("== a.html").unwrap_err(),
+        "reftest.list:1: expected a test and a reference"
+    );
+    assert_eq!(
+        parse("fuzzy(2) == a.html b.html").unwrap_err(),
+        "reftest.list:1: fuzzy needs a difference and a pixel count"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}